@@ -0,0 +1,263 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Unicode case folding, to compare typed answers regardless of case.
+//! Lowercasing is not enough: "ß" folds to "ss", and "ς", the final form of
+//! sigma, folds to "σ", but both are already lowercase.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use clap::ValueEnum;
+
+/// How to fold the case of typed answers.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug, Default)]
+pub enum CaseFolding {
+    /// A character can fold to several, e.g. "ß" and "ẞ" to "ss".
+    #[default]
+    Full,
+    /// Each character folds to a single one, e.g. "ẞ" to "ß", and characters
+    /// with no single-character folding, like "ß", are kept.
+    Simple,
+}
+
+impl Display for CaseFolding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaseFolding::Full => write!(f, "full"),
+            CaseFolding::Simple => write!(f, "simple"),
+        }
+    }
+}
+
+/// Fold the case of a character.
+pub fn fold_char(c: char, folding: CaseFolding) -> String {
+    let full: String = full_fold(c);
+    match folding {
+        CaseFolding::Full => full,
+        CaseFolding::Simple => {
+            if full.chars().count() == 1 {
+                return full;
+            }
+            // Where the full folding has several characters, the simple
+            // folding is the lowercase character, if there is one.
+            let mut lower = c.to_lowercase();
+            match (lower.next(), lower.next()) {
+                (Some(l), None) => l.to_string(),
+                _ => c.to_string(),
+            }
+        }
+    }
+}
+
+/// The full case folding of a character, from Unicode's `CaseFolding.txt`
+/// (statuses C and F). This is its lowercase, except for the characters in
+/// `FOLDINGS`, and for Cherokee, which folds to uppercase.
+fn full_fold(c: char) -> String {
+    let code: u32 = c as u32;
+    let cherokee: Option<u32> = match code {
+        0x13A0..=0x13F5 => Some(code),
+        0x13F8..=0x13FD => Some(code - 0x8),
+        0xAB70..=0xABBF => Some(code - 0x97D0),
+        _ => None,
+    };
+    if let Some(upper) = cherokee.and_then(char::from_u32) {
+        return upper.to_string();
+    }
+    match FOLDINGS.binary_search_by_key(&c, |(from, _)| *from) {
+        Ok(i) => FOLDINGS[i].1.to_string(),
+        Err(_) => c.to_lowercase().collect(),
+    }
+}
+
+/// The characters whose full case folding isn't their lowercase, sorted.
+const FOLDINGS: &[(char, &str)] = &[
+    ('\u{00b5}', "\u{03bc}"),
+    ('\u{00df}', "\u{0073}\u{0073}"),
+    ('\u{0149}', "\u{02bc}\u{006e}"),
+    ('\u{017f}', "\u{0073}"),
+    ('\u{01f0}', "\u{006a}\u{030c}"),
+    ('\u{0345}', "\u{03b9}"),
+    ('\u{0390}', "\u{03b9}\u{0308}\u{0301}"),
+    ('\u{03b0}', "\u{03c5}\u{0308}\u{0301}"),
+    ('\u{03c2}', "\u{03c3}"),
+    ('\u{03d0}', "\u{03b2}"),
+    ('\u{03d1}', "\u{03b8}"),
+    ('\u{03d5}', "\u{03c6}"),
+    ('\u{03d6}', "\u{03c0}"),
+    ('\u{03f0}', "\u{03ba}"),
+    ('\u{03f1}', "\u{03c1}"),
+    ('\u{03f5}', "\u{03b5}"),
+    ('\u{0587}', "\u{0565}\u{0582}"),
+    ('\u{1c80}', "\u{0432}"),
+    ('\u{1c81}', "\u{0434}"),
+    ('\u{1c82}', "\u{043e}"),
+    ('\u{1c83}', "\u{0441}"),
+    ('\u{1c84}', "\u{0442}"),
+    ('\u{1c85}', "\u{0442}"),
+    ('\u{1c86}', "\u{044a}"),
+    ('\u{1c87}', "\u{0463}"),
+    ('\u{1c88}', "\u{a64b}"),
+    ('\u{1e96}', "\u{0068}\u{0331}"),
+    ('\u{1e97}', "\u{0074}\u{0308}"),
+    ('\u{1e98}', "\u{0077}\u{030a}"),
+    ('\u{1e99}', "\u{0079}\u{030a}"),
+    ('\u{1e9a}', "\u{0061}\u{02be}"),
+    ('\u{1e9b}', "\u{1e61}"),
+    ('\u{1e9e}', "\u{0073}\u{0073}"),
+    ('\u{1f50}', "\u{03c5}\u{0313}"),
+    ('\u{1f52}', "\u{03c5}\u{0313}\u{0300}"),
+    ('\u{1f54}', "\u{03c5}\u{0313}\u{0301}"),
+    ('\u{1f56}', "\u{03c5}\u{0313}\u{0342}"),
+    ('\u{1f80}', "\u{1f00}\u{03b9}"),
+    ('\u{1f81}', "\u{1f01}\u{03b9}"),
+    ('\u{1f82}', "\u{1f02}\u{03b9}"),
+    ('\u{1f83}', "\u{1f03}\u{03b9}"),
+    ('\u{1f84}', "\u{1f04}\u{03b9}"),
+    ('\u{1f85}', "\u{1f05}\u{03b9}"),
+    ('\u{1f86}', "\u{1f06}\u{03b9}"),
+    ('\u{1f87}', "\u{1f07}\u{03b9}"),
+    ('\u{1f88}', "\u{1f00}\u{03b9}"),
+    ('\u{1f89}', "\u{1f01}\u{03b9}"),
+    ('\u{1f8a}', "\u{1f02}\u{03b9}"),
+    ('\u{1f8b}', "\u{1f03}\u{03b9}"),
+    ('\u{1f8c}', "\u{1f04}\u{03b9}"),
+    ('\u{1f8d}', "\u{1f05}\u{03b9}"),
+    ('\u{1f8e}', "\u{1f06}\u{03b9}"),
+    ('\u{1f8f}', "\u{1f07}\u{03b9}"),
+    ('\u{1f90}', "\u{1f20}\u{03b9}"),
+    ('\u{1f91}', "\u{1f21}\u{03b9}"),
+    ('\u{1f92}', "\u{1f22}\u{03b9}"),
+    ('\u{1f93}', "\u{1f23}\u{03b9}"),
+    ('\u{1f94}', "\u{1f24}\u{03b9}"),
+    ('\u{1f95}', "\u{1f25}\u{03b9}"),
+    ('\u{1f96}', "\u{1f26}\u{03b9}"),
+    ('\u{1f97}', "\u{1f27}\u{03b9}"),
+    ('\u{1f98}', "\u{1f20}\u{03b9}"),
+    ('\u{1f99}', "\u{1f21}\u{03b9}"),
+    ('\u{1f9a}', "\u{1f22}\u{03b9}"),
+    ('\u{1f9b}', "\u{1f23}\u{03b9}"),
+    ('\u{1f9c}', "\u{1f24}\u{03b9}"),
+    ('\u{1f9d}', "\u{1f25}\u{03b9}"),
+    ('\u{1f9e}', "\u{1f26}\u{03b9}"),
+    ('\u{1f9f}', "\u{1f27}\u{03b9}"),
+    ('\u{1fa0}', "\u{1f60}\u{03b9}"),
+    ('\u{1fa1}', "\u{1f61}\u{03b9}"),
+    ('\u{1fa2}', "\u{1f62}\u{03b9}"),
+    ('\u{1fa3}', "\u{1f63}\u{03b9}"),
+    ('\u{1fa4}', "\u{1f64}\u{03b9}"),
+    ('\u{1fa5}', "\u{1f65}\u{03b9}"),
+    ('\u{1fa6}', "\u{1f66}\u{03b9}"),
+    ('\u{1fa7}', "\u{1f67}\u{03b9}"),
+    ('\u{1fa8}', "\u{1f60}\u{03b9}"),
+    ('\u{1fa9}', "\u{1f61}\u{03b9}"),
+    ('\u{1faa}', "\u{1f62}\u{03b9}"),
+    ('\u{1fab}', "\u{1f63}\u{03b9}"),
+    ('\u{1fac}', "\u{1f64}\u{03b9}"),
+    ('\u{1fad}', "\u{1f65}\u{03b9}"),
+    ('\u{1fae}', "\u{1f66}\u{03b9}"),
+    ('\u{1faf}', "\u{1f67}\u{03b9}"),
+    ('\u{1fb2}', "\u{1f70}\u{03b9}"),
+    ('\u{1fb3}', "\u{03b1}\u{03b9}"),
+    ('\u{1fb4}', "\u{03ac}\u{03b9}"),
+    ('\u{1fb6}', "\u{03b1}\u{0342}"),
+    ('\u{1fb7}', "\u{03b1}\u{0342}\u{03b9}"),
+    ('\u{1fbc}', "\u{03b1}\u{03b9}"),
+    ('\u{1fbe}', "\u{03b9}"),
+    ('\u{1fc2}', "\u{1f74}\u{03b9}"),
+    ('\u{1fc3}', "\u{03b7}\u{03b9}"),
+    ('\u{1fc4}', "\u{03ae}\u{03b9}"),
+    ('\u{1fc6}', "\u{03b7}\u{0342}"),
+    ('\u{1fc7}', "\u{03b7}\u{0342}\u{03b9}"),
+    ('\u{1fcc}', "\u{03b7}\u{03b9}"),
+    ('\u{1fd2}', "\u{03b9}\u{0308}\u{0300}"),
+    ('\u{1fd3}', "\u{03b9}\u{0308}\u{0301}"),
+    ('\u{1fd6}', "\u{03b9}\u{0342}"),
+    ('\u{1fd7}', "\u{03b9}\u{0308}\u{0342}"),
+    ('\u{1fe2}', "\u{03c5}\u{0308}\u{0300}"),
+    ('\u{1fe3}', "\u{03c5}\u{0308}\u{0301}"),
+    ('\u{1fe4}', "\u{03c1}\u{0313}"),
+    ('\u{1fe6}', "\u{03c5}\u{0342}"),
+    ('\u{1fe7}', "\u{03c5}\u{0308}\u{0342}"),
+    ('\u{1ff2}', "\u{1f7c}\u{03b9}"),
+    ('\u{1ff3}', "\u{03c9}\u{03b9}"),
+    ('\u{1ff4}', "\u{03ce}\u{03b9}"),
+    ('\u{1ff6}', "\u{03c9}\u{0342}"),
+    ('\u{1ff7}', "\u{03c9}\u{0342}\u{03b9}"),
+    ('\u{1ffc}', "\u{03c9}\u{03b9}"),
+    ('\u{fb00}', "\u{0066}\u{0066}"),
+    ('\u{fb01}', "\u{0066}\u{0069}"),
+    ('\u{fb02}', "\u{0066}\u{006c}"),
+    ('\u{fb03}', "\u{0066}\u{0066}\u{0069}"),
+    ('\u{fb04}', "\u{0066}\u{0066}\u{006c}"),
+    ('\u{fb05}', "\u{0073}\u{0074}"),
+    ('\u{fb06}', "\u{0073}\u{0074}"),
+    ('\u{fb13}', "\u{0574}\u{0576}"),
+    ('\u{fb14}', "\u{0574}\u{0565}"),
+    ('\u{fb15}', "\u{0574}\u{056b}"),
+    ('\u{fb16}', "\u{057e}\u{0576}"),
+    ('\u{fb17}', "\u{0574}\u{056d}"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fold(text: &str, folding: CaseFolding) -> String {
+        text.chars().map(|c| fold_char(c, folding)).collect()
+    }
+
+    #[test]
+    fn test_foldings_sorted() {
+        assert!(FOLDINGS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_fold_sharp_s() {
+        assert_eq!(fold("Straße", CaseFolding::Full), "strasse");
+        assert_eq!(fold("STRAẞE", CaseFolding::Full), "strasse");
+        assert_eq!(fold("STRASSE", CaseFolding::Full), "strasse");
+        assert_eq!(fold("Straße", CaseFolding::Simple), "straße");
+        assert_eq!(fold("STRAẞE", CaseFolding::Simple), "straße");
+    }
+
+    /// Without Turkic rules, "İ" folds to "i" and a combining dot above.
+    #[test]
+    fn test_fold_dotted_capital_i() {
+        assert_eq!(fold("İstanbul", CaseFolding::Full), "i\u{0307}stanbul");
+        assert_eq!(
+            fold("İstanbul", CaseFolding::Full),
+            fold("I\u{0307}STANBUL", CaseFolding::Full)
+        );
+        assert_eq!(fold("İstanbul", CaseFolding::Simple), "İstanbul");
+        assert_eq!(fold("ISTANBUL", CaseFolding::Full), "istanbul");
+    }
+
+    #[test]
+    fn test_fold_final_sigma() {
+        for folding in [CaseFolding::Full, CaseFolding::Simple] {
+            assert_eq!(fold("ΟΔΟΣ", folding), "οδοσ");
+            assert_eq!(fold("οδος", folding), "οδοσ");
+        }
+    }
+
+    #[test]
+    fn test_fold_cherokee() {
+        assert_eq!(
+            fold("\u{ab70}\u{13a0}", CaseFolding::Full),
+            "\u{13a0}\u{13a0}"
+        );
+        assert_eq!(fold("\u{13f8}", CaseFolding::Simple), "\u{13f0}");
+    }
+}
@@ -12,27 +12,55 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::process::exit;
-
 use clap::Parser;
 use clap::Subcommand;
-use tokio::spawn;
+use clap::builder::NonEmptyStringValueParser;
 
+use crate::casefold::CaseFolding;
+use crate::cmd::browse::server::BrowseConfig;
+use crate::cmd::browse::server::BrowseOrder;
+use crate::cmd::browse::server::start_browse_server;
 use crate::cmd::check::check_collection;
+use crate::cmd::drill::backlog::BacklogPolicy;
+use crate::cmd::drill::family::FamilyPolicy;
+use crate::cmd::drill::leech::DEFAULT_LEECH_THRESHOLD;
+use crate::cmd::drill::leech::LeechAction;
+use crate::cmd::drill::new_order::NewCardOrder;
 use crate::cmd::drill::server::AnswerControls;
+use crate::cmd::drill::server::CardTimer;
 use crate::cmd::drill::server::ServerConfig;
 use crate::cmd::drill::server::start_server;
+use crate::cmd::drill::similarity::DEFAULT_EDIT_SIMILARITY;
+use crate::cmd::drill::template::Theme;
+use crate::cmd::drill::time_format::DEFAULT_TIME_FORMAT;
+use crate::cmd::due::print_due;
+use crate::cmd::export::ExportFormat;
 use crate::cmd::export::export_collection;
+use crate::cmd::fetch_media::fetch_media;
+use crate::cmd::import::ImportFormat;
+use crate::cmd::import::import_cards;
+use crate::cmd::import_schedule::import_anki_schedule;
+use crate::cmd::list::list_cards;
+use crate::cmd::media::list_media;
+use crate::cmd::open_source::open_source;
 use crate::cmd::orphans::delete_orphans;
 use crate::cmd::orphans::list_orphans;
+use crate::cmd::report::print_slow_cards;
+use crate::cmd::reset::ResetOptions;
+use crate::cmd::reset::reset;
 use crate::cmd::stats::StatsFormat;
 use crate::cmd::stats::print_stats;
+use crate::cmd::unsuspend::unsuspend;
 use crate::error::Fallible;
+use crate::markdown::ClozeBlank;
+use crate::types::performance::IntervalBounds;
+use crate::types::performance::IntervalLength;
 use crate::types::timestamp::Timestamp;
-use crate::utils::wait_for_server;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
+// The command is parsed once, so the size of the drill options doesn't matter.
+#[allow(clippy::large_enum_variant)]
 enum Command {
     /// Drill cards through a web interface.
     Drill {
@@ -42,17 +70,29 @@ enum Command {
         #[arg(long)]
         card_limit: Option<usize>,
         /// Maximum number of new cards to drill in a session.
-        #[arg(long)]
+        #[arg(long, alias = "max-new")]
         new_card_limit: Option<usize>,
         /// The host address to bind to. Default is 127.0.0.1.
         #[arg(long, default_value = "127.0.0.1")]
         host: String,
-        /// The port to use for the web server. Default is 8000.
+        /// The port to use for the web server. Default is 8000. Use 0 to pick a free port.
         #[arg(long, default_value_t = 8000)]
         port: u16,
+        /// Write the port the server is bound to into this file, once it is ready.
+        #[arg(long)]
+        port_file: Option<String>,
         /// Only drill cards from this deck.
         #[arg(long)]
         from_deck: Option<String>,
+        /// Only drill cards with this tag, with or without the `#`. Repeat to drill cards with any of several tags.
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Don't drill cards with this tag, with or without the `#`. Repeat to exclude several tags.
+        #[arg(long = "exclude-tag")]
+        exclude_tags: Vec<String>,
+        /// Only drill difficult cards: those tagged `#hard`, or whose measured retention is below this threshold (a number between 0 and 1).
+        #[arg(long)]
+        difficulty: Option<f64>,
         /// Whether to open the browser automatically. Default is true.
         #[arg(long)]
         open_browser: Option<bool>,
@@ -62,19 +102,231 @@ enum Command {
         /// Whether or not to bury siblings. Default is true.
         #[arg(long)]
         bury_siblings: Option<bool>,
+        /// Delay, in milliseconds, between grading a card and advancing to the next one. Pressing any key during the delay cancels it. By default, the next card is shown immediately.
+        #[arg(long)]
+        auto_advance: Option<u64>,
+        /// Automatically reveal the answer after this many seconds. By default, answers are only revealed manually.
+        #[arg(long)]
+        auto_reveal: Option<u64>,
+        /// Start the session with this many cards with the highest measured retention, to build momentum.
+        #[arg(long)]
+        warmup: Option<usize>,
+        /// Maximum number of review cards (i.e., cards that are not new) to drill in a session.
+        #[arg(long)]
+        max_reviews: Option<usize>,
+        /// Which review cards to drill when more than `--max-reviews` are due:
+        #[arg(long, default_value_t = BacklogPolicy::Cap)]
+        backlog: BacklogPolicy,
+        /// The order in which to drill new cards, and to pick them under `--new-card-limit`:
+        #[arg(long, default_value_t = NewCardOrder::File)]
+        new_order: NewCardOrder,
+        /// Author mode: after grading, show the card's source and hash, with a button to append it to `revisions.txt`.
+        #[arg(long)]
+        author: bool,
+        /// End the session after this many minutes. The card being reviewed when time runs out can still be graded.
+        #[arg(long)]
+        time_limit: Option<u64>,
+        /// The minimum time until a card's next review, in days, weeks, or years, like `3d`, `2w`, or `1y`. Default is 1 day. Intervals are whole days.
+        #[arg(long)]
+        min_interval: Option<IntervalLength>,
+        /// The maximum time until a card's next review, in days, weeks, or years, like `3d`, `2w`, or `1y`. Default is 256 days.
+        #[arg(long)]
+        max_interval: Option<IntervalLength>,
+        /// Randomly lengthen or shorten each new interval by up to this many percent, so cards learned together do not keep coming due together. Default is 5; 0 disables it.
+        #[arg(long)]
+        fuzz: Option<f64>,
+        /// Seed for the card shuffle and the interval fuzz, to make a session reproducible.
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Dim the text around cloze deletions, so the blank stands out. The revealed answer is shown as usual.
+        #[arg(long)]
+        dim_cloze_context: bool,
+        /// How to draw the blank in place of a hidden cloze deletion: `fixed`, the same 13 dots for every deletion; `proportional`, one dot per character of the deletion; `hint`, the first character of the deletion followed by one dot per remaining character; or a number of dots for every deletion.
+        #[arg(long, default_value_t = ClozeBlank::default())]
+        cloze_blank: ClozeBlank,
+        /// Don't highlight the syntax of code blocks tagged with a language.
+        #[arg(long)]
+        no_highlight: bool,
+        /// Show the siblings of a cloze card that are due together, as a single card with all deletions hidden, and grade each deletion separately. Siblings are not buried.
+        #[arg(long)]
+        group_cloze: bool,
+        /// After revealing the answer, ask how confident you were, from 1 to 5, and record it with the grade. It does not affect scheduling.
+        #[arg(long)]
+        track_confidence: bool,
+        /// The base URL to serve media from, e.g. a CDN mirroring the collection directory. By default, media is served by this server.
+        #[arg(long, env = "HASHCARDS_MEDIA_URL")]
+        media_url: Option<String>,
+        /// Download remote media again once its cached copy is this many days old. By default, remote media is downloaded once, the first time a card that references it is shown, and cached in `.remote-media` in the collection directory.
+        #[arg(long)]
+        remote_cache_expiry: Option<u64>,
+        /// Mark a milestone in the progress bar every this many cards, e.g. 25 or 50, and show the card count. Useful for long sessions.
+        #[arg(long)]
+        milestones: Option<usize>,
+        /// Study in two passes: first reveal each card without grading, then go through the same cards again to grade them.
+        #[arg(long)]
+        two_phase: bool,
+        /// Show a card at most this many times per session. A card forgotten that many times is not repeated again in the session, and is left to its next scheduled review. By default, forgotten cards are repeated until remembered.
+        #[arg(long)]
+        max_shown: Option<usize>,
+        /// What to do when a card becomes a leech, i.e. when it is forgotten for the `--leech-threshold`-th time. By default, leeches are not detected. Tagging adds a `#leech` line to the card in its deck file, and suspending excludes the card from future sessions. Both happen when the session is saved.
+        #[arg(long)]
+        leech_action: Option<LeechAction>,
+        /// The number of times a card is forgotten before it becomes a leech.
+        #[arg(long, default_value_t = DEFAULT_LEECH_THRESHOLD)]
+        leech_threshold: usize,
+        /// Suspend a card when it is forgotten this many times, which must be more than `--leech-threshold`. The card is suspended when the session is saved. This enables leech detection, even without `--leech-action`.
+        #[arg(long)]
+        leech_suspend_threshold: Option<usize>,
+        /// Update the page in place after each action, instead of reloading it. This is faster over a slow connection. If an update fails, the page is reloaded.
+        #[arg(long)]
+        no_reload: bool,
+        /// Watch the collection for changes: when a deck file is saved, the collection is reloaded, and the page refreshed. Cards whose source was removed are dropped from the session, the current card too.
+        #[arg(long)]
+        watch: bool,
+        /// A key that flips through the session hands-free: it reveals the answer, then grades the card "Good". The key is named as in JavaScript's `KeyboardEvent.key`, e.g. `Enter`.
+        #[arg(long, value_parser = NonEmptyStringValueParser::new())]
+        flip_key: Option<String>,
+        /// Study without saving reviews: the review store is only read, if it can be, and nothing is written to it. Use it when the store is locked by another program or corrupt, or for a quick session that should not affect scheduling.
+        #[arg(long)]
+        no_store: bool,
+        /// Parse every deck file, rather than reusing the cards of files that haven't changed since the last load, and leave the parse cache alone.
+        #[arg(long)]
+        no_cache: bool,
+        /// Do not flash the grade's color after grading. The colors are set in the `[colors.grades]` table of hashcards.toml.
+        #[arg(long)]
+        no_grade_flash: bool,
+        /// Do not play the audio in a card's answer when it is revealed. The audio can still be played with the Replay button.
+        #[arg(long)]
+        no_autoplay: bool,
+        /// The color theme of the pages. With `auto`, the system's preference is followed. The theme can also be switched from the session page, and the choice is remembered by the browser.
+        #[arg(long, default_value_t = Theme::Auto)]
+        theme: Theme,
+        /// The format of the session's start and end times on the completion page, in `strftime` syntax.
+        #[arg(long, default_value = DEFAULT_TIME_FORMAT)]
+        time_format: String,
+        /// The timezone to show the session's start and end times in: `UTC`, or an offset from UTC like `+05:30`. By default, the local timezone.
+        #[arg(long)]
+        timezone: Option<String>,
+        /// When to start timing each card: when it is `shown`, or when its answer is `revealed`. The timer stops when the card is graded, and the time is saved with the review.
+        #[arg(long, default_value_t = CardTimer::Shown)]
+        card_timer: CardTimer,
+        /// Keep at most this many reviews undoable. Older reviews are committed, and can no longer be undone. By default, every review in the session can be undone.
+        #[arg(long)]
+        undo_depth: Option<usize>,
+        /// Ignore case when checking the answers typed for type-in cards.
+        #[arg(long)]
+        ignore_case: bool,
+        /// How to fold case with `--ignore-case`: `full`, where a character can fold to several (e.g. "ß" matches "ss"), or `simple`, where each character folds to a single one.
+        #[arg(long, default_value_t = CaseFolding::Full)]
+        case_folding: CaseFolding,
+        /// How similar, from 0 to 1, an edited card must be to the card it replaces to keep its learning progress. Whitespace and punctuation are ignored, so 1 only keeps the progress through such changes. Larger edits make new cards.
+        #[arg(long, default_value_t = DEFAULT_EDIT_SIMILARITY)]
+        edit_similarity: f64,
+        /// How to handle the siblings of a cloze card (i.e., deletions from the same text) that are due in the same session:
+        #[arg(long, default_value_t = FamilyPolicy::All)]
+        family: FamilyPolicy,
+    },
+    /// Browse the decks in a collection through a web interface.
+    Browse {
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+        /// The host address to bind to. Default is 127.0.0.1.
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// The port to use for the web server. Default is 8000. Use 0 to pick a free port.
+        #[arg(long, default_value_t = 8000)]
+        port: u16,
+        /// Whether to open the browser automatically. Default is true.
+        #[arg(long)]
+        open_browser: Option<bool>,
+        /// The base URL to serve media from, e.g. a CDN mirroring the collection directory. By default, media is served by this server.
+        #[arg(long, env = "HASHCARDS_MEDIA_URL")]
+        media_url: Option<String>,
+        /// The order of the cards on deck pages. `difficulty` lists the most difficult cards first, to find the cards that need rewriting.
+        #[arg(long, default_value_t = BrowseOrder::File)]
+        by: BrowseOrder,
+        /// A key that flips through the cards of a deck page one at a time: the first press shows the first question, the next one its answer, and so on. Drill sessions started from the browser use it too. The key is named as in JavaScript's `KeyboardEvent.key`, e.g. `Enter`.
+        #[arg(long, value_parser = NonEmptyStringValueParser::new())]
+        flip_key: Option<String>,
     },
     /// Check the integrity of a collection.
     Check {
         /// Path to the collection directory. By default, the current working directory is used.
         directory: Option<String>,
     },
+    /// List every card in the collection.
+    List {
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+        /// Print the cards as JSON, with their hashes, decks, locations, and source text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// List every media file referenced in the collection, by deck, with the path it resolves to, and whether it exists. Use it to check that a collection can be drilled offline.
+    Media {
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+    },
+    /// Open the source of a card in $EDITOR, at the card's first line.
+    OpenSource {
+        /// The hash of the card, as printed by `list` or `report`.
+        hash: String,
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+    },
+    /// Print how many cards are due, overall and per deck, without starting a session.
+    Due {
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+        /// Print the counts as JSON, for widgets and scripts.
+        #[arg(long)]
+        json: bool,
+    },
     /// Print collection statistics.
     Stats {
         /// Path to the collection directory. By default, the current working directory is used.
         directory: Option<String>,
         /// Which output format to use.
-        #[arg(long, default_value_t = StatsFormat::Html)]
+        #[arg(long, default_value_t = StatsFormat::Text)]
         format: StatsFormat,
+        /// Print the statistics as JSON, for scripts. The same as `--format=json`.
+        #[arg(long, conflicts_with = "format")]
+        json: bool,
+        /// The number of days, ending today, to compute the retention and pace over.
+        #[arg(long, default_value_t = 30)]
+        days: usize,
+        /// List the cards with at least this many lapses as leeches, as in `drill --leech-threshold`.
+        #[arg(long, default_value_t = DEFAULT_LEECH_THRESHOLD)]
+        leech_threshold: usize,
+        /// The minimum interval the review load is estimated with, as in `drill --min-interval`.
+        #[arg(long)]
+        min_interval: Option<IntervalLength>,
+        /// The maximum interval the review load is estimated with, as in `drill --max-interval`.
+        #[arg(long)]
+        max_interval: Option<IntervalLength>,
+        /// Instead of printing statistics, export every recorded review as JSON to this path, or to stdout if it is `-`.
+        #[arg(long)]
+        export: Option<String>,
+        /// With `--export`, only export the reviews made on or after this date, as `YYYY-MM-DD`.
+        #[arg(long)]
+        from: Option<String>,
+        /// With `--export`, only export the reviews made on or before this date, as `YYYY-MM-DD`.
+        #[arg(long)]
+        to: Option<String>,
+        /// With `--export`, only export the reviews of cards in decks whose name matches this pattern. `*` matches any sequence of characters, and `?` any single character.
+        #[arg(long)]
+        deck: Option<String>,
+    },
+    /// Print reports about the review history.
+    Report {
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+        /// List the cards with the highest median answer time (from revealing the answer to grading).
+        #[arg(long, required = true)]
+        slow: bool,
+        /// Maximum number of cards to list.
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
     },
     /// Commands relating to orphan cards.
     Orphans {
@@ -88,6 +340,70 @@ enum Command {
         /// Optional path to the output file. By default, the output is printed to stdout.
         #[arg(long)]
         output: Option<String>,
+        /// Only export the cards in decks whose name matches this pattern. `*` matches any sequence of characters, and `?` any single character.
+        #[arg(long)]
+        deck: Option<String>,
+        /// The format of the export:
+        #[arg(long, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// With `--format text`, leave out the `Q:`, `A:`, and `C:` prefixes, for a cleaner read. The output can't be parsed back into cards.
+        #[arg(long)]
+        no_prefixes: bool,
+    },
+    /// Import the cards of an Anki package (`.apkg`) or a CSV file as deck files.
+    Import {
+        /// Path to the collection directory to write the deck files into. By default, the current working directory is used.
+        directory: Option<String>,
+        /// Path to the Anki package or CSV file.
+        #[arg(long)]
+        input: String,
+        /// The format of the input:
+        #[arg(long, default_value_t = ImportFormat::Apkg)]
+        format: ImportFormat,
+        /// Write the cards of a deck to a deck file, as `DECK=PATH`, where the path is relative to the collection directory. Repeat to map several decks. By default, the deck `A::B` is written to `A/B.md`.
+        #[arg(long = "deck")]
+        decks: Vec<String>,
+    },
+    /// Import the scheduling state of matching cards from an Anki collection.
+    ImportSchedule {
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+        /// Path to the Anki collection file (`collection.anki2`).
+        #[arg(long)]
+        anki: String,
+    },
+    /// Reset the scheduling state of the cards in some decks, so they are drilled as new cards again.
+    Reset {
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+        /// Only reset the cards in decks whose name matches this pattern. `*` matches any sequence of characters, and `?` any single character.
+        #[arg(long)]
+        deck: String,
+        /// Print how many cards would be reset, without resetting them.
+        #[arg(long)]
+        dry_run: bool,
+        /// Do not ask for confirmation.
+        #[arg(long)]
+        yes: bool,
+        /// Keep the reviews of the reset cards in the database, in the `archived_reviews` table, instead of deleting them.
+        #[arg(long)]
+        archive: bool,
+    },
+    /// Unsuspend cards, so they are drilled again. Without `--card` or `--all`, print the suspended cards.
+    Unsuspend {
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
+        /// The hash of a card to unsuspend, as printed by this command. Repeat to unsuspend several cards.
+        #[arg(long = "card")]
+        cards: Vec<String>,
+        /// Unsuspend every suspended card.
+        #[arg(long)]
+        all: bool,
+    },
+    /// Download remote images into the collection, and rewrite links to point to the local copies.
+    FetchMedia {
+        /// Path to the collection directory. By default, the current working directory is used.
+        directory: Option<String>,
     },
 }
 
@@ -102,6 +418,9 @@ enum OrphanCommand {
     Delete {
         /// Path to the collection directory. By default, the current working directory is used.
         directory: Option<String>,
+        /// Keep the reviews of the orphan cards in the database, in the `archived_reviews` table, instead of deleting them.
+        #[arg(long)]
+        archive: bool,
     },
 }
 
@@ -115,25 +434,55 @@ pub async fn entrypoint() -> Fallible<()> {
             host,
             port,
             from_deck,
+            tags,
+            exclude_tags,
+            difficulty,
             open_browser,
             answer_controls,
             bury_siblings,
+            auto_advance,
+            auto_reveal,
+            warmup,
+            max_reviews,
+            backlog,
+            new_order,
+            author,
+            family,
+            port_file,
+            time_limit,
+            min_interval,
+            max_interval,
+            fuzz,
+            seed,
+            dim_cloze_context,
+            cloze_blank,
+            no_highlight,
+            group_cloze,
+            track_confidence,
+            media_url,
+            remote_cache_expiry,
+            milestones,
+            two_phase,
+            max_shown,
+            leech_action,
+            leech_threshold,
+            leech_suspend_threshold,
+            no_reload,
+            watch,
+            flip_key,
+            no_store,
+            no_cache,
+            no_grade_flash,
+            no_autoplay,
+            theme,
+            time_format,
+            timezone,
+            card_timer,
+            undo_depth,
+            ignore_case,
+            case_folding,
+            edit_similarity,
         } => {
-            if open_browser.unwrap_or(true) {
-                // Start a separate task to open the browser once the server is up.
-                let browser_host = host.clone();
-                spawn(async move {
-                    match wait_for_server(&browser_host, port).await {
-                        Ok(_) => {
-                            let _ = open::that(format!("http://{browser_host}:{port}/"));
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to connect to server: {e}");
-                            exit(-1)
-                        }
-                    }
-                });
-            }
             let config = ServerConfig {
                 directory,
                 host,
@@ -142,18 +491,157 @@ pub async fn entrypoint() -> Fallible<()> {
                 card_limit,
                 new_card_limit,
                 deck_filter: from_deck,
+                tags,
+                exclude_tags,
+                difficulty,
                 shuffle: true,
                 answer_controls,
                 bury_siblings: bury_siblings.unwrap_or(true),
+                auto_advance,
+                auto_reveal,
+                warmup,
+                max_reviews,
+                backlog,
+                new_order,
+                author,
+                family,
+                open_browser: open_browser.unwrap_or(true),
+                port_file,
+                port_sender: None,
+                time_limit,
+                min_interval: min_interval.map(IntervalLength::days),
+                max_interval: max_interval.map(IntervalLength::days),
+                fuzz,
+                seed,
+                dim_cloze_context,
+                highlight_code: !no_highlight,
+                cloze_blank,
+                group_cloze,
+                track_confidence,
+                media_url,
+                remote_cache_expiry,
+                milestones,
+                two_phase,
+                max_shown,
+                leech_action,
+                leech_threshold,
+                leech_suspend_threshold,
+                no_reload,
+                watch,
+                flip_key,
+                no_store,
+                no_cache,
+                no_grade_flash,
+                no_autoplay,
+                theme,
+                time_format,
+                timezone,
+                card_timer,
+                undo_depth,
+                ignore_case,
+                case_folding,
+                edit_similarity,
             };
             start_server(config).await
         }
+        Command::Browse {
+            directory,
+            host,
+            port,
+            open_browser,
+            media_url,
+            by,
+            flip_key,
+        } => {
+            let config = BrowseConfig {
+                directory,
+                host,
+                port,
+                media_url,
+                order: by,
+                flip_key,
+                open_browser: open_browser.unwrap_or(true),
+            };
+            start_browse_server(config).await
+        }
         Command::Check { directory } => check_collection(directory),
-        Command::Stats { directory, format } => print_stats(directory, format),
+        Command::Media { directory } => list_media(directory),
+        Command::List { directory, json } => list_cards(directory, json),
+        Command::OpenSource { hash, directory } => open_source(directory, hash),
+        Command::Due { directory, json } => print_due(directory, json),
+        Command::Stats {
+            directory,
+            format,
+            json,
+            days,
+            leech_threshold,
+            min_interval,
+            max_interval,
+            export,
+            from,
+            to,
+            deck,
+        } => {
+            let format = if json { StatsFormat::Json } else { format };
+            let bounds = IntervalBounds::new(
+                min_interval.map(IntervalLength::days),
+                max_interval.map(IntervalLength::days),
+            )?;
+            print_stats(
+                directory,
+                format,
+                days,
+                leech_threshold,
+                bounds,
+                export,
+                from,
+                to,
+                deck,
+            )
+        }
+        Command::Report {
+            directory,
+            slow: _,
+            limit,
+        } => print_slow_cards(directory, limit),
         Command::Orphans { command } => match command {
             OrphanCommand::List { directory } => list_orphans(directory),
-            OrphanCommand::Delete { directory } => delete_orphans(directory),
+            OrphanCommand::Delete { directory, archive } => delete_orphans(directory, archive),
         },
-        Command::Export { directory, output } => export_collection(directory, output),
+        Command::Export {
+            directory,
+            output,
+            deck,
+            format,
+            no_prefixes,
+        } => export_collection(directory, output, deck, format, no_prefixes),
+        Command::Import {
+            directory,
+            input,
+            format,
+            decks,
+        } => import_cards(input, directory, format, decks),
+        Command::ImportSchedule { directory, anki } => import_anki_schedule(directory, anki),
+        Command::Reset {
+            directory,
+            deck,
+            dry_run,
+            yes,
+            archive,
+        } => reset(
+            directory,
+            deck,
+            ResetOptions {
+                dry_run,
+                yes,
+                archive,
+            },
+        ),
+        Command::Unsuspend {
+            directory,
+            cards,
+            all,
+        } => unsuspend(directory, cards, all),
+        Command::FetchMedia { directory } => fetch_media(directory).await,
     }
 }
@@ -0,0 +1,286 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::Html;
+use maud::Markup;
+use maud::html;
+use serde::Deserialize;
+
+use crate::cmd::browse::server::BrowseOrder;
+use crate::cmd::browse::server::BrowseState;
+use crate::cmd::drill::template::Theme;
+use crate::cmd::drill::template::page_template;
+use crate::config::CollectionConfig;
+use crate::config::LanguageConfig;
+use crate::config::TextDirection;
+use crate::db::Database;
+use crate::error::Fallible;
+use crate::fsrs::Difficulty;
+use crate::markdown::ClozeBlank;
+use crate::markdown::MarkdownRenderConfig;
+use crate::media::resolve::MediaResolverBuilder;
+use crate::types::aliases::DeckName;
+use crate::types::card::Card;
+use crate::types::card::CardType;
+use crate::types::card_hash::CardHash;
+use crate::types::date::Date;
+use crate::types::performance::Performance;
+
+pub async fn index_handler(State(state): State<BrowseState>) -> (StatusCode, Html<String>) {
+    respond("Decks", render_index(&state), &state.language())
+}
+
+#[derive(Deserialize)]
+pub struct DeckQuery {
+    name: DeckName,
+}
+
+pub async fn deck_handler(
+    State(state): State<BrowseState>,
+    Query(query): Query<DeckQuery>,
+) -> (StatusCode, Html<String>) {
+    respond(
+        &query.name,
+        render_deck(&state, &query.name),
+        &state.language(),
+    )
+}
+
+fn respond(
+    title: &str,
+    body: Fallible<Markup>,
+    language: &LanguageConfig,
+) -> (StatusCode, Html<String>) {
+    let (title, body) = match body {
+        Ok(body) => (title, body),
+        Err(e) => (
+            "Error",
+            html! {
+                div.error {
+                    h1 { "Error" }
+                    p { (e) }
+                }
+            },
+        ),
+    };
+    (
+        StatusCode::OK,
+        Html(page_template(title, body, language, Theme::Auto).into_string()),
+    )
+}
+
+#[derive(Debug, PartialEq)]
+struct DeckSummary {
+    name: DeckName,
+    cards: usize,
+    due: usize,
+}
+
+/// Summarize every deck in the collection, in the configured deck order.
+fn deck_summaries(
+    cards: &[Card],
+    db: &Database,
+    config: &CollectionConfig,
+    today: Date,
+) -> Fallible<Vec<DeckSummary>> {
+    let db_hashes: HashSet<CardHash> = db.card_hashes()?;
+    let due_today: HashSet<CardHash> = db.due_today(today)?;
+    let mut decks: BTreeMap<DeckName, DeckSummary> = BTreeMap::new();
+    for card in cards {
+        let summary = decks
+            .entry(card.deck_name().clone())
+            .or_insert_with(|| DeckSummary {
+                name: card.deck_name().clone(),
+                cards: 0,
+                due: 0,
+            });
+        summary.cards += 1;
+        // Cards not yet in the database are new, and therefore due.
+        let hash = card.hash();
+        if due_today.contains(&hash) || !db_hashes.contains(&hash) {
+            summary.due += 1;
+        }
+    }
+    let mut decks: Vec<DeckSummary> = decks.into_values().collect();
+    config.sort_decks(&mut decks, |deck| &deck.name);
+    Ok(decks)
+}
+
+fn render_index(state: &BrowseState) -> Fallible<Markup> {
+    let decks = {
+        let cards = state.cards.lock().unwrap();
+        let config = state.config.lock().unwrap();
+        let db = state.db.lock().unwrap();
+        deck_summaries(&cards, &db, &config, Date::today())?
+    };
+    let total_cards: usize = decks.iter().map(|deck| deck.cards).sum();
+    let total_due: usize = decks.iter().map(|deck| deck.due).sum();
+    let html = html! {
+        div.browse {
+            h1 { "Decks" }
+            form.reload action="/reload" method="post" {
+                input type="submit" value="Reload" title="Reload the collection from disk, after editing deck files.";
+            }
+            table.decks {
+                thead {
+                    tr {
+                        th { "Deck" }
+                        th { "Cards" }
+                        th { "Due" }
+                        th {}
+                    }
+                }
+                tbody {
+                    @for deck in &decks {
+                        tr {
+                            td.name { (deck.name) }
+                            td.count { (deck.cards) }
+                            td.count { (deck.due) }
+                            td {
+                                form action="/deck" method="get" {
+                                    input type="hidden" name="name" value=(deck.name);
+                                    input type="submit" value="Browse" title="Show the cards in this deck.";
+                                }
+                                form action="/drill" method="post" {
+                                    input type="hidden" name="deck" value=(deck.name);
+                                    input type="submit" value="Drill" disabled[deck.due == 0] title="Drill the cards due today in this deck.";
+                                }
+                            }
+                        }
+                    }
+                }
+                tfoot {
+                    tr {
+                        th { "Total" }
+                        th.count { (total_cards) }
+                        th.count { (total_due) }
+                        th {}
+                    }
+                }
+            }
+        }
+    };
+    Ok(html)
+}
+
+fn render_deck(state: &BrowseState, deck_name: &DeckName) -> Fallible<Markup> {
+    let cards = state.cards.lock().unwrap();
+    let mut cards: Vec<&Card> = cards
+        .iter()
+        .filter(|card| card.deck_name() == deck_name)
+        .collect();
+    let difficulties: HashMap<CardHash, Difficulty> = if state.order == BrowseOrder::Difficulty {
+        let db = state.db.lock().unwrap();
+        sort_by_difficulty(&mut cards, &db)?
+    } else {
+        HashMap::new()
+    };
+    let mut previews: Vec<Markup> = Vec::new();
+    for card in &cards {
+        let preview = render_card_preview(state, card)?;
+        let preview = match difficulties.get(&card.hash()) {
+            Some(difficulty) => html! {
+                div.card-difficulty { "Difficulty: " (format!("{difficulty:.2}")) }
+                (preview)
+            },
+            None => preview,
+        };
+        previews.push(preview);
+    }
+    let html = html! {
+        div.browse {
+            a.back href="/" { "← All decks" }
+            h1 { (deck_name) }
+            @if cards.is_empty() {
+                p { "No cards in this deck." }
+            }
+            @for preview in previews {
+                (preview)
+            }
+        }
+    };
+    Ok(html)
+}
+
+/// Sort cards from the most to the least difficult, and return the difficulty
+/// of each card that has one. Cards that were never reviewed keep their order,
+/// after the others.
+fn sort_by_difficulty(
+    cards: &mut [&Card],
+    db: &Database,
+) -> Fallible<HashMap<CardHash, Difficulty>> {
+    let mut difficulties: HashMap<CardHash, Difficulty> = HashMap::new();
+    for card in cards.iter() {
+        if let Some(Performance::Reviewed(performance)) =
+            db.get_card_performance_opt(card.hash())?
+        {
+            difficulties.insert(card.hash(), performance.difficulty);
+        }
+    }
+    cards.sort_by(
+        |a, b| match (difficulties.get(&a.hash()), difficulties.get(&b.hash())) {
+            (Some(a), Some(b)) => b.total_cmp(a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+    );
+    Ok(difficulties)
+}
+
+/// Render a card with its answer shown.
+fn render_card_preview(state: &BrowseState, card: &Card) -> Fallible<Markup> {
+    let coll_path = state.directory.clone();
+    let deck_path = card.relative_file_path(&coll_path)?;
+    let config = MarkdownRenderConfig {
+        resolver: MediaResolverBuilder::new()
+            .with_collection_path(coll_path)?
+            .with_deck_path(deck_path)?
+            .with_base_url(state.media_url.clone())?
+            .build()?,
+        port: state.port,
+        dim_cloze_context: false,
+        highlight_code: true,
+        cloze_blank: ClozeBlank::default(),
+    };
+    let dir: Option<&str> = TextDirection::of_card(card).map(TextDirection::as_str);
+    let html = match card.card_type() {
+        CardType::Basic | CardType::TypeIn => html! {
+            div.card-preview dir=[dir] {
+                div .question .rich-text {
+                    (card.html_front(&config)?)
+                }
+                div .answer .rich-text {
+                    (card.html_back(&config)?)
+                }
+            }
+        },
+        CardType::Cloze => html! {
+            div.card-preview dir=[dir] {
+                div .prompt .rich-text {
+                    (card.html_back(&config)?)
+                }
+            }
+        },
+    };
+    Ok(html)
+}
@@ -0,0 +1,257 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod get;
+pub mod server;
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+    use std::path::PathBuf;
+
+    use portpicker::pick_unused_port;
+    use tokio::spawn;
+
+    use crate::cmd::browse::server::BrowseConfig;
+    use crate::cmd::browse::server::BrowseOrder;
+    use crate::cmd::browse::server::start_browse_server;
+    use crate::collection::Collection;
+    use crate::error::Fallible;
+    use crate::helper::create_tmp_copy_of_test_directory;
+    use crate::types::card::CardType;
+    use crate::types::performance::Performance;
+    use crate::types::performance::ReviewedPerformance;
+    use crate::types::timestamp::Timestamp;
+    use crate::utils::wait_for_server;
+
+    const TEST_HOST: &str = "127.0.0.1";
+
+    #[tokio::test]
+    async fn test_browse_e2e() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = BrowseConfig {
+            directory: Some(directory),
+            host: TEST_HOST.to_string(),
+            port,
+            media_url: None,
+            order: BrowseOrder::File,
+            flip_key: None,
+            open_browser: false,
+        };
+        spawn(async move { start_browse_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // The index lists every deck, with its card and due counts.
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/")).await?;
+        assert!(response.status().is_success());
+        let html = response.text().await?;
+        assert!(html.contains("<td class=\"name\">Deck</td>"));
+        assert!(html.contains("<td class=\"count\">2</td>"));
+
+        // The deck page shows every card, with its answer.
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/deck?name=Deck")).await?;
+        assert!(response.status().is_success());
+        let html = response.text().await?;
+        assert!(html.contains("FOO"));
+        assert!(html.contains("BAR"));
+        assert!(html.contains("baz <span class='cloze-reveal'>quux</span>"));
+
+        // Starting a drill redirects to a new drill session.
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/drill"))
+            .form(&[("deck", "Deck")])
+            .send()
+            .await?;
+        assert!(response.status().is_success());
+        assert_ne!(response.url().port(), Some(port));
+        let drill_url = response.url().clone();
+        let html = response.text().await?;
+        assert!(html.contains("progress-bar"));
+
+        // Drilling the deck again goes back to the running session.
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/drill"))
+            .form(&[("deck", "Deck")])
+            .send()
+            .await?;
+        assert!(response.status().is_success());
+        assert_eq!(response.url(), &drill_url);
+
+        // Drilling a deck with no due cards shows an error.
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/drill"))
+            .form(&[("deck", "Nonexistent")])
+            .send()
+            .await?;
+        assert!(response.status().is_success());
+        let html = response.text().await?;
+        assert!(html.contains("No cards due today in this deck."));
+
+        Ok(())
+    }
+
+    /// The flip key is set in the browser's script, and in the script of the
+    /// drill sessions it starts.
+    #[tokio::test]
+    async fn test_browse_flip_key() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = BrowseConfig {
+            directory: Some(directory),
+            host: TEST_HOST.to_string(),
+            port,
+            media_url: None,
+            order: BrowseOrder::File,
+            flip_key: Some("Enter".to_string()),
+            open_browser: false,
+        };
+        spawn(async move { start_browse_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/script.js")).await?;
+        let script = response.text().await?;
+        assert!(script.contains("const FLIP_KEY = \"Enter\";"));
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/drill"))
+            .form(&[("deck", "Deck")])
+            .send()
+            .await?;
+        let drill_port = response.url().port().unwrap();
+        let response = reqwest::get(format!("http://{TEST_HOST}:{drill_port}/script.js")).await?;
+        let script = response.text().await?;
+        assert!(script.contains("const FLIP_KEY = \"Enter\";"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_browse_deck_order() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        write(
+            PathBuf::from(&directory).join("Zoology.md"),
+            "Q: Zebra?\nA: Stripes.\n",
+        )?;
+        write(
+            PathBuf::from(&directory).join("hashcards.toml"),
+            "deck_order = [\"Zoology\"]\n",
+        )?;
+        let config = BrowseConfig {
+            directory: Some(directory),
+            host: TEST_HOST.to_string(),
+            port,
+            media_url: None,
+            order: BrowseOrder::File,
+            flip_key: None,
+            open_browser: false,
+        };
+        spawn(async move { start_browse_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // Pinned decks are listed first.
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/")).await?;
+        let html = response.text().await?;
+        let zoology = html.find("<td class=\"name\">Zoology</td>").unwrap();
+        let deck = html.find("<td class=\"name\">Deck</td>").unwrap();
+        assert!(zoology < deck);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_browse_reload() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = BrowseConfig {
+            directory: Some(directory.clone()),
+            host: TEST_HOST.to_string(),
+            port,
+            media_url: None,
+            order: BrowseOrder::File,
+            flip_key: None,
+            open_browser: false,
+        };
+        spawn(async move { start_browse_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        write(
+            PathBuf::from(&directory).join("Zoology.md"),
+            "Q: Zebra?\nA: Stripes.\n",
+        )?;
+        let html = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/reload"))
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("1 added, 0 removed, 0 changed."));
+
+        // The new deck is listed.
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/")).await?;
+        let html = response.text().await?;
+        assert!(html.contains("<td class=\"name\">Zoology</td>"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_browse_by_difficulty() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        // The cloze card, which comes second in the deck file, is the most
+        // difficult.
+        let coll = Collection::new(Some(directory.clone()))?;
+        let now = Timestamp::now();
+        for card in &coll.cards {
+            let difficulty = match card.card_type() {
+                CardType::Basic | CardType::TypeIn => 3.0,
+                CardType::Cloze => 8.0,
+            };
+            coll.db.insert_card(card.hash(), now)?;
+            let performance = ReviewedPerformance {
+                last_reviewed_at: now,
+                stability: 1.0,
+                difficulty,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: now.date(),
+                review_count: 1,
+            };
+            coll.db
+                .update_card_performance(card.hash(), Performance::Reviewed(performance))?;
+        }
+        let config = BrowseConfig {
+            directory: Some(directory),
+            host: TEST_HOST.to_string(),
+            port,
+            media_url: None,
+            order: BrowseOrder::Difficulty,
+            flip_key: None,
+            open_browser: false,
+        };
+        spawn(async move { start_browse_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/deck?name=Deck")).await?;
+        let html = response.text().await?;
+        let cloze = html.find("Difficulty: 8.00").unwrap();
+        let basic = html.find("Difficulty: 3.00").unwrap();
+        assert!(cloze < basic);
+        assert!(html.find("quux").unwrap() < html.find("FOO").unwrap());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use axum::Form;
+use axum::Router;
+use axum::extract::Path;
+use axum::extract::State;
+use axum::http::HeaderName;
+use axum::http::StatusCode;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::Html;
+use axum::response::IntoResponse;
+use axum::response::Redirect;
+use axum::response::Response;
+use axum::routing::get;
+use axum::routing::post;
+use clap::ValueEnum;
+use maud::html;
+use serde::Deserialize;
+use tokio::signal;
+use tokio::spawn;
+
+use crate::cmd::browse::get::deck_handler;
+use crate::cmd::browse::get::index_handler;
+use crate::cmd::drill::katex::KATEX_CSS_URL;
+use crate::cmd::drill::katex::KATEX_JS_URL;
+use crate::cmd::drill::katex::katex_css_handler;
+use crate::cmd::drill::katex::katex_font_handler;
+use crate::cmd::drill::katex::katex_js_handler;
+use crate::cmd::drill::reload::reload_summary;
+use crate::cmd::drill::server::ServerConfig;
+use crate::cmd::drill::server::favicon_handler;
+use crate::cmd::drill::server::manifest_handler;
+use crate::cmd::drill::server::not_found_handler;
+use crate::cmd::drill::server::script_content;
+use crate::cmd::drill::server::serve_file;
+use crate::cmd::drill::server::start_server;
+use crate::cmd::drill::server::style_handler;
+use crate::cmd::drill::template::Theme;
+use crate::cmd::drill::template::page_template;
+use crate::collection::Collection;
+use crate::collection::CollectionDiff;
+use crate::config::CollectionConfig;
+use crate::config::LanguageConfig;
+use crate::db::Database;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::types::aliases::DeckName;
+use crate::types::card::Card;
+use crate::utils::SERVER_START_TIMEOUT;
+use crate::utils::bind_listener;
+use crate::utils::open_browser_when_ready;
+use std::collections::HashMap;
+use tokio::sync::oneshot::channel;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+/// The order of the cards on a deck page.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum BrowseOrder {
+    /// The order of the deck files.
+    File,
+    /// The most difficult cards first, by their FSRS difficulty. Cards that
+    /// were never reviewed come last.
+    Difficulty,
+}
+
+impl Display for BrowseOrder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrowseOrder::File => write!(f, "file"),
+            BrowseOrder::Difficulty => write!(f, "difficulty"),
+        }
+    }
+}
+
+pub struct BrowseConfig {
+    pub directory: Option<String>,
+    pub host: String,
+    pub port: u16,
+    /// The base URL to serve media from, if media is served separately.
+    pub media_url: Option<String>,
+    /// The order of the cards on deck pages.
+    pub order: BrowseOrder,
+    /// The key that flips through the cards, if any.
+    pub flip_key: Option<String>,
+    /// Whether to open the browser once the server is up.
+    pub open_browser: bool,
+}
+
+#[derive(Clone)]
+pub struct BrowseState {
+    pub host: String,
+    pub port: u16,
+    pub directory: PathBuf,
+    pub macros: Vec<(String, String)>,
+    pub cards: Arc<Mutex<Vec<Card>>>,
+    pub db: Arc<Mutex<Database>>,
+    pub config: Arc<Mutex<CollectionConfig>>,
+    pub media_url: Option<String>,
+    pub order: BrowseOrder,
+    pub flip_key: Option<String>,
+    /// The drill sessions started from the browser, by deck.
+    pub drills: Arc<tokio::sync::Mutex<HashMap<DeckName, DrillServer>>>,
+}
+
+/// A drill server started for a deck.
+pub struct DrillServer {
+    port: u16,
+    handle: JoinHandle<Fallible<()>>,
+}
+
+impl BrowseState {
+    /// The language of the pages, from the collection's configuration.
+    pub fn language(&self) -> LanguageConfig {
+        self.config.lock().unwrap().language.clone()
+    }
+}
+
+pub async fn start_browse_server(config: BrowseConfig) -> Fallible<()> {
+    let Collection {
+        directory,
+        db,
+        cards,
+        macros,
+        config: collection_config,
+    } = Collection::new(config.directory)?;
+
+    // Bind first, so media URLs use the port the server is bound to.
+    let listener = bind_listener(&config.host, config.port).await?;
+    let port: u16 = listener.local_addr()?.port();
+    println!("Listening on http://{}:{port}/", config.host);
+    if config.open_browser {
+        open_browser_when_ready(config.host.clone(), port);
+    }
+
+    let state = BrowseState {
+        host: config.host.clone(),
+        port,
+        directory,
+        macros,
+        cards: Arc::new(Mutex::new(cards)),
+        db: Arc::new(Mutex::new(db)),
+        config: Arc::new(Mutex::new(collection_config)),
+        media_url: config.media_url,
+        order: config.order,
+        flip_key: config.flip_key,
+        drills: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+    };
+    let app = Router::new();
+    let app = app.route("/", get(index_handler));
+    let app = app.route("/deck", get(deck_handler));
+    let app = app.route("/drill", post(drill_handler));
+    let app = app.route("/reload", post(reload_handler));
+    let app = app.route("/script.js", get(script_handler));
+    let app = app.route("/style.css", get(style_handler));
+    let app = app.route("/favicon.svg", get(favicon_handler));
+    let app = app.route("/manifest.json", get(manifest_handler));
+    let app = app.route(KATEX_CSS_URL, get(katex_css_handler));
+    let app = app.route(KATEX_JS_URL, get(katex_js_handler));
+    let app = app.route("/katex/fonts/{*path}", get(katex_font_handler));
+    let app = app.route("/file/{*path}", get(file_handler));
+    let app = app.fallback(not_found_handler);
+    let app = app.with_state(state);
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+async fn script_handler(
+    State(state): State<BrowseState>,
+) -> (StatusCode, [(HeaderName, &'static str); 1], String) {
+    let content = script_content(
+        &state.macros,
+        None,
+        None,
+        false,
+        false,
+        state.flip_key.as_deref(),
+    );
+    (StatusCode::OK, [(CONTENT_TYPE, "text/javascript")], content)
+}
+
+async fn file_handler(
+    State(state): State<BrowseState>,
+    Path(path): Path<String>,
+) -> (StatusCode, [(HeaderName, &'static str); 1], Vec<u8>) {
+    serve_file(state.directory, &path).await
+}
+
+/// Reload the collection from disk, and show what changed. Drill sessions
+/// already started load the collection themselves, and are not affected.
+async fn reload_handler(State(state): State<BrowseState>) -> (StatusCode, Html<String>) {
+    let result = reload(&state);
+    let language = state.language();
+    let html = match result {
+        Ok(diff) => page_template(
+            "Reloaded",
+            reload_summary(&diff, "/", "Back to the decks"),
+            &language,
+            Theme::Auto,
+        ),
+        Err(e) => page_template(
+            "Error",
+            html! {
+                div.error {
+                    h1 { "Error" }
+                    p { (e) }
+                }
+            },
+            &language,
+            Theme::Auto,
+        ),
+    };
+    (StatusCode::OK, Html(html.into_string()))
+}
+
+fn reload(state: &BrowseState) -> Fallible<CollectionDiff> {
+    let Collection { cards, config, .. } =
+        Collection::new(Some(state.directory.display().to_string()))?;
+    let mut loaded = state.cards.lock().unwrap();
+    let diff = CollectionDiff::new(&loaded, &cards);
+    *loaded = cards;
+    *state.config.lock().unwrap() = config;
+    Ok(diff)
+}
+
+#[derive(Deserialize)]
+pub struct DrillForm {
+    deck: DeckName,
+}
+
+/// Start a drill session for a single deck, and redirect to it.
+async fn drill_handler(State(state): State<BrowseState>, Form(form): Form<DrillForm>) -> Response {
+    match start_drill(&state, form.deck).await {
+        Ok(url) => Redirect::to(&url).into_response(),
+        Err(e) => {
+            let html = page_template(
+                "Error",
+                html! {
+                    div.error {
+                        h1 { "Error" }
+                        p { (e) }
+                    }
+                },
+                &state.language(),
+                Theme::Auto,
+            );
+            (StatusCode::OK, Html(html.into_string())).into_response()
+        }
+    }
+}
+
+/// Start a drill server for the deck on a free port, and return its URL once
+/// it is up. If a drill session for the deck is already running, return its
+/// URL instead.
+async fn start_drill(state: &BrowseState, deck: DeckName) -> Fallible<String> {
+    // Held until the server is up, so a deck's session is only started once.
+    let mut drills = state.drills.lock().await;
+    if let Some(drill) = drills.get(&deck) {
+        if !drill.handle.is_finished() {
+            return Ok(format!("http://{}:{}/", state.host, drill.port));
+        }
+    }
+    let (port_tx, port_rx) = channel();
+    let config = ServerConfig {
+        media_url: state.media_url.clone(),
+        flip_key: state.flip_key.clone(),
+        port_sender: Some(port_tx),
+        ..ServerConfig::for_deck(
+            state.directory.display().to_string(),
+            state.host.clone(),
+            deck.clone(),
+        )
+    };
+    let handle = spawn(start_server(config));
+    let port: u16 = match timeout(SERVER_START_TIMEOUT, port_rx).await {
+        Ok(Ok(port)) => port,
+        // The server returned before it started listening.
+        Ok(Err(_)) => {
+            return match handle.await {
+                Ok(Ok(())) => fail("No cards due today in this deck."),
+                Ok(Err(e)) => Err(e),
+                Err(_) => fail("Failed to start the drill session."),
+            };
+        }
+        Err(_) => {
+            handle.abort();
+            return fail("The drill session took too long to start.");
+        }
+    };
+    drills.insert(deck, DrillServer { port, handle });
+    Ok(format!("http://{}:{port}/", state.host))
+}
@@ -0,0 +1,202 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A JSON interface to the session, for frontends other than the browser.
+
+use axum::Form;
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use serde::Serialize;
+
+use crate::cmd::drill::get::can_undo;
+use crate::cmd::drill::get::render_card;
+use crate::cmd::drill::get::render_config;
+use crate::cmd::drill::get::shown_group;
+use crate::cmd::drill::get::type_in_result;
+use crate::cmd::drill::live::SessionSnapshot;
+use crate::cmd::drill::live::publish;
+use crate::cmd::drill::post::FormData;
+use crate::cmd::drill::post::action_handler;
+use crate::cmd::drill::server::AnswerControls;
+use crate::cmd::drill::state::MutableState;
+use crate::cmd::drill::state::Phase;
+use crate::cmd::drill::state::ServerState;
+use crate::csv::card_type_name;
+use crate::error::Fallible;
+use crate::markdown::MarkdownRenderConfig;
+use crate::types::aliases::Tag;
+use crate::types::card::Card;
+use crate::types::card::CardType;
+use crate::types::card_hash::CardHash;
+
+/// The state of the session, as served to other frontends.
+#[derive(Serialize)]
+pub struct CardState {
+    /// The progress of the session.
+    session: SessionSnapshot,
+    /// The card being shown, or `None` once the session is finished, or if
+    /// there is no card to show.
+    card: Option<CardView>,
+    /// The values of the `action` field accepted in this state.
+    actions: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct CardView {
+    hash: CardHash,
+    deck_name: String,
+    /// One of `basic`, `cloze`, or `type-in`.
+    card_type: &'static str,
+    tags: Vec<Tag>,
+    flagged: bool,
+    /// Whether the card is a leech: tagged as one, or forgotten as many
+    /// times as the leech threshold.
+    leech: bool,
+    /// The hashes of the cards shown together, when grouping clozes. Each
+    /// deletion remembered is sent as a `recalled-{hash}` field when grading
+    /// the group.
+    group: Vec<CardHash>,
+    /// The card's HTML with the answer hidden.
+    front: String,
+    /// The card's HTML with the answer shown, once it is revealed.
+    back: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ApiError {
+    error: String,
+}
+
+pub async fn api_card_handler(
+    State(state): State<ServerState>,
+) -> Result<Json<CardState>, (StatusCode, Json<ApiError>)> {
+    current(&state).map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Take an action, sent like the HTML form sends it, and respond with the new
+/// state of the session.
+pub async fn api_card_action_handler(
+    State(state): State<ServerState>,
+    Form(form): Form<FormData>,
+) -> Result<Json<CardState>, (StatusCode, Json<ApiError>)> {
+    let result: Fallible<()> = action_handler(state.clone(), form).await;
+    publish(&state);
+    result.map_err(|e| error(StatusCode::BAD_REQUEST, e.to_string()))?;
+    current(&state).map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+fn error(status: StatusCode, error: String) -> (StatusCode, Json<ApiError>) {
+    (status, Json(ApiError { error }))
+}
+
+fn current(state: &ServerState) -> Fallible<Json<CardState>> {
+    let mutable = state.mutable.lock().unwrap();
+    let session: SessionSnapshot = SessionSnapshot::new(state, &mutable);
+    // A session with no card to show counts as finished in the snapshot, but
+    // may still have reviews to undo or save.
+    let (card, actions) = if mutable.nothing_to_study() {
+        let actions = if mutable.reviews.is_empty() {
+            vec!["Shutdown"]
+        } else if can_undo(&mutable) {
+            vec!["Undo", "End"]
+        } else {
+            vec!["End"]
+        };
+        (None, actions)
+    } else if session.finished {
+        (None, vec!["Shutdown"])
+    } else {
+        let card: Card = mutable.cards[0].clone();
+        let view: CardView = card_view(state, &mutable, &card)?;
+        (Some(view), accepted_actions(state, &mutable, &card))
+    };
+    Ok(Json(CardState {
+        session,
+        card,
+        actions,
+    }))
+}
+
+fn card_view(state: &ServerState, mutable: &MutableState, card: &Card) -> Fallible<CardView> {
+    let config: MarkdownRenderConfig = render_config(state, card)?;
+    let group: Vec<Card> = shown_group(state, mutable);
+    let front = render_card(&group, false, &config, None, false);
+    let back = mutable.reveal.then(|| {
+        render_card(
+            &group,
+            true,
+            &config,
+            type_in_result(state, mutable, card),
+            false,
+        )
+    });
+    Ok(CardView {
+        hash: card.hash(),
+        deck_name: card.deck_name().clone(),
+        card_type: card_type_name(card.card_type()),
+        tags: card.tags().to_vec(),
+        flagged: mutable.is_flagged(card),
+        leech: mutable.is_leech(state, card)?,
+        group: group.iter().map(Card::hash).collect(),
+        front: front.into_string(),
+        back: back.map(|back| back.into_string()),
+    })
+}
+
+/// The actions the HTML page offers for the current card.
+fn accepted_actions(state: &ServerState, mutable: &MutableState, card: &Card) -> Vec<&'static str> {
+    let mut actions: Vec<&'static str> = Vec::new();
+    if can_undo(mutable) {
+        actions.push("Undo");
+        if mutable.phase == Phase::Grade {
+            actions.push("Lock");
+        }
+    }
+    if !mutable.redo.is_empty() {
+        actions.push("Redo");
+    }
+    if !mutable.reveal {
+        if card.card_type() == CardType::TypeIn {
+            actions.push("Check");
+        } else {
+            actions.push("Reveal");
+        }
+    } else if mutable.phase == Phase::Study {
+        actions.push("Next");
+    } else if shown_group(state, mutable).len() > 1 {
+        actions.push("Grade");
+    } else {
+        match state.answer_controls {
+            AnswerControls::Binary => actions.extend(["Forgot", "Good"]),
+            AnswerControls::Full => actions.extend(["Forgot", "Hard", "Good", "Easy"]),
+        }
+    }
+    actions.push("Save");
+    if mutable.phase == Phase::Grade && !mutable.skipped_all() {
+        actions.push("Skip");
+    }
+    if !mutable.is_flagged(card) {
+        actions.push("Flag");
+    }
+    actions.extend(["Suspend", "Discard", "End"]);
+    let revisable: bool = match mutable.reviews.last() {
+        Some(review) => !mutable.revised.contains(&review.card.hash()),
+        None => false,
+    };
+    if state.author && revisable {
+        actions.push("Revise");
+    }
+    actions
+}
@@ -0,0 +1,224 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use clap::ValueEnum;
+
+use crate::db::Database;
+use crate::error::Fallible;
+use crate::types::card::Card;
+use crate::types::date::Date;
+use crate::types::performance::Performance;
+
+/// The index of a review card in the deck, and its due date.
+type DueReview = (usize, Date);
+
+/// How to handle review cards beyond the `--max-reviews` cap.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum BacklogPolicy {
+    /// Drill the most overdue cards first. The rest roll over to the next day.
+    Cap,
+    /// Drill the cards due today first, then the most overdue cards. The
+    /// backlog piles up, but the regular schedule is kept.
+    Pile,
+    /// Drill the cards due today, plus an even share of the backlog, so that
+    /// it is cleared at the `--max-reviews` rate, without going over it.
+    Spread,
+}
+
+impl Display for BacklogPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BacklogPolicy::Cap => write!(f, "cap"),
+            BacklogPolicy::Pile => write!(f, "pile"),
+            BacklogPolicy::Spread => write!(f, "spread"),
+        }
+    }
+}
+
+/// Limit the review cards in the deck according to the backlog policy. New
+/// cards are not affected. The order of the deck is preserved.
+pub fn apply_backlog_policy(
+    db: &Database,
+    deck: Vec<Card>,
+    today: Date,
+    max_reviews: usize,
+    policy: BacklogPolicy,
+) -> Fallible<Vec<Card>> {
+    let mut reviews: Vec<DueReview> = Vec::new();
+    for (index, card) in deck.iter().enumerate() {
+        if let Performance::Reviewed(performance) = db.get_card_performance(card.hash())? {
+            reviews.push((index, performance.due_date));
+        }
+    }
+    let review_indices: HashSet<usize> = reviews.iter().map(|(index, _)| *index).collect();
+    let selected: HashSet<usize> = select_reviews(reviews, today, max_reviews, policy);
+    let deck: Vec<Card> = deck
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !review_indices.contains(index) || selected.contains(index))
+        .map(|(_, card)| card)
+        .collect();
+    Ok(deck)
+}
+
+/// Given the indices and due dates of the review cards, select the ones to
+/// drill today.
+///
+/// Let `R` be the maximum number of reviews, `T` the reviews due today, and
+/// `B` the backlog of overdue reviews.
+///
+/// - `cap`: the `R` most overdue reviews.
+/// - `pile`: the `T` reviews due today, then the most overdue reviews, up to
+///   `R` in total.
+/// - `spread`: the `T` reviews due today, plus the `ceil(B / D)` most overdue
+///   reviews, where `D = ceil(B / R)` is the number of days it takes to clear
+///   the backlog at `R` reviews a day.
+fn select_reviews(
+    mut reviews: Vec<DueReview>,
+    today: Date,
+    max_reviews: usize,
+    policy: BacklogPolicy,
+) -> HashSet<usize> {
+    // Most overdue first. The sort is stable, so ties keep the deck order.
+    reviews.sort_by_key(|(_, due_date)| *due_date);
+    let (backlog, current): (Vec<DueReview>, Vec<DueReview>) =
+        reviews.iter().partition(|(_, due_date)| *due_date < today);
+    let indices = |cards: &[DueReview], count: usize| -> Vec<usize> {
+        cards.iter().take(count).map(|(index, _)| *index).collect()
+    };
+    match policy {
+        BacklogPolicy::Cap => indices(&reviews, max_reviews).into_iter().collect(),
+        BacklogPolicy::Pile => {
+            let mut selected = indices(&current, max_reviews);
+            selected.extend(indices(
+                &backlog,
+                max_reviews.saturating_sub(selected.len()),
+            ));
+            selected.into_iter().collect()
+        }
+        BacklogPolicy::Spread => {
+            let mut selected = indices(&current, max_reviews);
+            let room = max_reviews - selected.len();
+            if !backlog.is_empty() && room > 0 {
+                let days = backlog.len().div_ceil(max_reviews);
+                let share = backlog.len().div_ceil(days);
+                selected.extend(indices(&backlog, share.min(room)));
+            }
+            selected.into_iter().collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn date(day: u32) -> Date {
+        Date::new(NaiveDate::from_ymd_opt(2025, 1, day).unwrap())
+    }
+
+    fn sorted(selected: HashSet<usize>) -> Vec<usize> {
+        let mut selected: Vec<usize> = selected.into_iter().collect();
+        selected.sort();
+        selected
+    }
+
+    /// Three cards due today (0-2), and six overdue cards (3-8), the most
+    /// overdue last.
+    fn make_reviews() -> Vec<DueReview> {
+        let today = date(10);
+        vec![
+            (0, today),
+            (1, today),
+            (2, today),
+            (3, date(9)),
+            (4, date(8)),
+            (5, date(7)),
+            (6, date(6)),
+            (7, date(5)),
+            (8, date(4)),
+        ]
+    }
+
+    #[test]
+    fn test_cap() {
+        let selected = select_reviews(make_reviews(), date(10), 4, BacklogPolicy::Cap);
+        assert_eq!(sorted(selected), vec![5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_pile() {
+        let selected = select_reviews(make_reviews(), date(10), 4, BacklogPolicy::Pile);
+        assert_eq!(sorted(selected), vec![0, 1, 2, 8]);
+    }
+
+    #[test]
+    fn test_spread() {
+        // A backlog of 6 at 6 a day takes 1 day to clear, but only 3 fit
+        // next to the cards due today.
+        let selected = select_reviews(make_reviews(), date(10), 6, BacklogPolicy::Spread);
+        assert_eq!(sorted(selected), vec![0, 1, 2, 6, 7, 8]);
+        // A backlog of 6 at 9 a day: all of it fits.
+        let selected = select_reviews(make_reviews(), date(10), 9, BacklogPolicy::Spread);
+        assert_eq!(selected.len(), 9);
+    }
+
+    /// Spreading the backlog never goes over the cap, even with many cards
+    /// due today.
+    #[test]
+    fn test_spread_respects_cap() {
+        for max_reviews in 0..9 {
+            let selected =
+                select_reviews(make_reviews(), date(10), max_reviews, BacklogPolicy::Spread);
+            assert_eq!(selected.len(), max_reviews);
+        }
+        let selected = select_reviews(make_reviews(), date(10), 2, BacklogPolicy::Spread);
+        assert_eq!(sorted(selected), vec![0, 1]);
+        let selected = select_reviews(make_reviews(), date(10), 4, BacklogPolicy::Spread);
+        assert_eq!(sorted(selected), vec![0, 1, 2, 8]);
+    }
+
+    #[test]
+    fn test_spread_distributes_backlog_evenly() {
+        // A backlog of 250 at 100 a day is cleared in 3 days, at 84, 83, and
+        // 83 cards a day, rather than 100, 100, and 50.
+        let today = date(10);
+        let mut backlog: Vec<DueReview> = (0..250).map(|i| (i, date(1))).collect();
+        let mut shares: Vec<usize> = Vec::new();
+        while !backlog.is_empty() {
+            let selected = select_reviews(backlog.clone(), today, 100, BacklogPolicy::Spread);
+            shares.push(selected.len());
+            backlog.retain(|(index, _)| !selected.contains(index));
+        }
+        assert_eq!(shares, vec![84, 83, 83]);
+    }
+
+    #[test]
+    fn test_under_cap() {
+        for policy in [
+            BacklogPolicy::Cap,
+            BacklogPolicy::Pile,
+            BacklogPolicy::Spread,
+        ] {
+            let selected = select_reviews(make_reviews(), date(10), 100, policy);
+            assert_eq!(selected.len(), 9);
+        }
+    }
+}
@@ -57,6 +57,11 @@ impl Cache {
         }
     }
 
+    /// Whether the card's performance information is in the cache.
+    pub fn contains(&self, card_hash: CardHash) -> bool {
+        self.changes.contains_key(&card_hash)
+    }
+
     /// Update's a card's performance information. If the hash is not in the
     /// cache, returns an error.
     pub fn update(&mut self, card_hash: CardHash, performance: Performance) -> Fallible<()> {
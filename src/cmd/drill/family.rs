@@ -0,0 +1,178 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use clap::ValueEnum;
+
+use crate::db::Database;
+use crate::error::Fallible;
+use crate::types::card::Card;
+use crate::types::card_hash::CardHash;
+use crate::types::date::Date;
+use crate::types::performance::Performance;
+
+/// How to handle the cloze cards of a family (i.e., siblings) that are due in
+/// the same session.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum FamilyPolicy {
+    /// Keep all siblings (subject to sibling burial).
+    All,
+    /// Keep only the most due sibling of each family.
+    OncePerSession,
+}
+
+impl Display for FamilyPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FamilyPolicy::All => write!(f, "all"),
+            FamilyPolicy::OncePerSession => write!(f, "once-per-session"),
+        }
+    }
+}
+
+/// Collapse each family in the deck to its most due sibling: the one with the
+/// earliest due date. New cards have no due date, so they are only chosen if
+/// no sibling has been reviewed. Ties keep the first sibling in deck order.
+/// The order of the deck is preserved.
+pub fn collapse_families(db: &Database, deck: Vec<Card>) -> Fallible<Vec<Card>> {
+    // The index and due date of the most due sibling of each family.
+    let mut chosen: HashMap<CardHash, (usize, Option<Date>)> = HashMap::new();
+    for (index, card) in deck.iter().enumerate() {
+        let Some(family) = card.family_hash() else {
+            continue;
+        };
+        let due_date: Option<Date> = match db.get_card_performance(card.hash())? {
+            Performance::New => None,
+            Performance::Reviewed(performance) => Some(performance.due_date),
+        };
+        match chosen.get(&family) {
+            Some((_, best)) if !is_more_due(due_date, *best) => {}
+            _ => {
+                chosen.insert(family, (index, due_date));
+            }
+        }
+    }
+    let deck: Vec<Card> = deck
+        .into_iter()
+        .enumerate()
+        .filter(|(index, card)| match card.family_hash() {
+            Some(family) => chosen.get(&family).is_some_and(|(i, _)| i == index),
+            None => true,
+        })
+        .map(|(_, card)| card)
+        .collect();
+    Ok(deck)
+}
+
+/// Whether a card due on `a` is strictly more due than one due on `b`.
+fn is_more_due(a: Option<Date>, b: Option<Date>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a < b,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::types::card::CardContent;
+    use crate::types::performance::ReviewedPerformance;
+    use crate::types::timestamp::Timestamp;
+
+    fn date(day: u32) -> Date {
+        Date::new(NaiveDate::from_ymd_opt(2025, 1, day).unwrap())
+    }
+
+    /// A cloze card deleting the given byte range of the text.
+    fn make_cloze(text: &str, start: usize, end: usize) -> Card {
+        Card::new(
+            "Deck".to_string(),
+            PathBuf::from("Deck.md"),
+            (0, 1),
+            CardContent::new_cloze(text, start, end).unwrap(),
+            Vec::new(),
+        )
+    }
+
+    fn set_due_date(db: &Database, card: &Card, due_date: Date) -> Fallible<()> {
+        let performance = Performance::Reviewed(ReviewedPerformance {
+            last_reviewed_at: Timestamp::now(),
+            stability: 1.0,
+            difficulty: 5.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date,
+            review_count: 1,
+        });
+        db.update_card_performance(card.hash(), performance)
+    }
+
+    #[test]
+    fn test_collapse_picks_most_due_sibling() -> Fallible<()> {
+        let db = Database::new(":memory:")?;
+        // Three siblings deleting "a", "b", and "c", and another family.
+        let deck: Vec<Card> = vec![
+            make_cloze("a b c", 0, 0),
+            make_cloze("a b c", 2, 2),
+            make_cloze("a b c", 4, 4),
+            make_cloze("x y", 0, 0),
+            make_cloze("x y", 2, 2),
+        ];
+        for card in deck.iter() {
+            db.insert_card(card.hash(), Timestamp::now())?;
+        }
+        set_due_date(&db, &deck[0], date(9))?;
+        set_due_date(&db, &deck[1], date(3))?;
+        set_due_date(&db, &deck[2], date(5))?;
+        // In the second family, the reviewed card beats the new one.
+        set_due_date(&db, &deck[4], date(9))?;
+
+        let collapsed = collapse_families(&db, deck.clone())?;
+        let hashes: Vec<CardHash> = collapsed.iter().map(|card| card.hash()).collect();
+        assert_eq!(hashes, vec![deck[1].hash(), deck[4].hash()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_collapse_keeps_basic_cards() -> Fallible<()> {
+        let db = Database::new(":memory:")?;
+        let deck: Vec<Card> = vec![
+            Card::new(
+                "Deck".to_string(),
+                PathBuf::from("Deck.md"),
+                (0, 1),
+                CardContent::new_basic("Q", "A"),
+                Vec::new(),
+            ),
+            make_cloze("a b", 0, 0),
+            make_cloze("a b", 2, 2),
+        ];
+        for card in deck.iter() {
+            db.insert_card(card.hash(), Timestamp::now())?;
+        }
+        // All new: the first sibling is kept.
+        let collapsed = collapse_families(&db, deck.clone())?;
+        let hashes: Vec<CardHash> = collapsed.iter().map(|card| card.hash()).collect();
+        assert_eq!(hashes, vec![deck[0].hash(), deck[1].hash()]);
+        Ok(())
+    }
+}
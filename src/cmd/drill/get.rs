@@ -12,92 +12,213 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashSet;
+
+use axum::extract::Query;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::Html;
 use maud::Markup;
+use maud::PreEscaped;
 use maud::html;
+use serde::Deserialize;
+use similar::ChangeTag;
+use similar::TextDiff;
 
+use crate::cmd::drill::group::cloze_group;
+use crate::cmd::drill::group::deleted_text;
+use crate::cmd::drill::group::render_group;
+use crate::cmd::drill::leech::LeechAction;
+use crate::cmd::drill::post::REVISIONS_FILE;
 use crate::cmd::drill::server::AnswerControls;
+use crate::cmd::drill::session::preview_intervals;
+use crate::cmd::drill::state::Edit;
 use crate::cmd::drill::state::MutableState;
+use crate::cmd::drill::state::Phase;
+use crate::cmd::drill::state::RejectedEdit;
+use crate::cmd::drill::state::Review;
 use crate::cmd::drill::state::ServerState;
 use crate::cmd::drill::template::page_template;
+use crate::cmd::drill::type_in;
+use crate::config::ColorConfig;
+use crate::config::TextDirection;
 use crate::error::Fallible;
+use crate::fsrs::Grade;
 use crate::markdown::MarkdownRenderConfig;
 use crate::media::resolve::MediaResolverBuilder;
 use crate::types::card::Card;
+use crate::types::card::CardContent;
 use crate::types::card::CardType;
+use crate::types::card_hash::CardHash;
+use crate::types::confidence::Confidence;
+use crate::types::retention::Retention;
 
 pub async fn get_handler(State(state): State<ServerState>) -> (StatusCode, Html<String>) {
-    let html = match inner(state).await {
-        Ok(html) => html,
-        Err(e) => page_template(html! {
-            div.error {
-                h1 { "Error" }
-                p { (e) }
-            }
-        }),
-    };
+    let (title, body) = render_page(&state);
+    let html = page_template(&title, body, &state.language, state.theme);
     (StatusCode::OK, Html(html.into_string()))
 }
 
-async fn inner(state: ServerState) -> Fallible<Markup> {
+/// The title and body of the page for the current state of the session, or
+/// of an error page.
+pub fn render_page(state: &ServerState) -> (String, Markup) {
+    match inner(state) {
+        Ok(page) => page,
+        Err(e) => (
+            "Error".to_string(),
+            html! {
+                div.error {
+                    h1 { "Error" }
+                    p { (e) }
+                }
+            },
+        ),
+    }
+}
+
+fn inner(state: &ServerState) -> Fallible<(String, Markup)> {
     let mutable = state.mutable.lock().unwrap();
-    let body = if mutable.finished_at.is_some() {
-        render_completion_page(&state, &mutable)?
+    let page = if mutable.finished_at.is_some() {
+        let title = if mutable.discarded {
+            "Session Discarded"
+        } else {
+            "Session Complete"
+        };
+        (title.to_string(), render_completion_page(state, &mutable)?)
+    } else if mutable.nothing_to_study() {
+        ("Nothing to Study".to_string(), render_empty_page(&mutable))
     } else {
-        render_session_page(&state, &mutable)?
+        (
+            session_title(state, &mutable),
+            render_session_page(state, &mutable)?,
+        )
     };
-    let html = page_template(body);
-    Ok(html)
+    Ok(page)
+}
+
+/// The title of the session page: the current deck, and the number of cards
+/// done out of the total, e.g. `french/verbs — 12/80`. This tells tabs apart
+/// when several sessions are open.
+fn session_title(state: &ServerState, mutable: &MutableState) -> String {
+    let deck_name = mutable.cards[0].deck_name();
+    let cards_done = cards_done(state, mutable);
+    format!("{deck_name} — {cards_done}/{}", state.total_cards)
+}
+
+/// The number of cards done: in the study phase, the cards studied so far, and
+/// otherwise, the cards graded so far.
+pub fn cards_done(state: &ServerState, mutable: &MutableState) -> usize {
+    match mutable.phase {
+        Phase::Study => mutable.studied.len(),
+        Phase::Grade => state.total_cards.saturating_sub(mutable.cards.len()),
+    }
+}
+
+/// Whether there is an action to undo: a card studied, a review not yet
+/// locked in, or a skip.
+pub fn can_undo(mutable: &MutableState) -> bool {
+    match mutable.phase {
+        Phase::Study => !mutable.studied.is_empty(),
+        Phase::Grade => mutable.reviews.len() > mutable.committed || mutable.last_skip().is_some(),
+    }
 }
 
 fn render_session_page(state: &ServerState, mutable: &MutableState) -> Fallible<Markup> {
-    let undo_disabled = mutable.reviews.is_empty();
-    let total_cards = state.total_cards;
-    let cards_done = state.total_cards - mutable.cards.len();
-    let percent_done = if total_cards == 0 {
-        100
+    let undo_disabled = !can_undo(mutable);
+    // Reviews can be locked in while there are reviews left to undo.
+    let lock_button: Markup = if mutable.phase == Phase::Grade && !undo_disabled {
+        lock_button()
     } else {
-        (cards_done * 100) / total_cards
+        html! {}
     };
-    let progress_bar_style = format!("width: {}%;", percent_done);
+    let total_cards = state.total_cards;
+    let cards_done = cards_done(state, mutable);
     let card = mutable.cards[0].clone();
-    let coll_path = state.directory.clone();
-    let deck_path = card.relative_file_path(&coll_path)?;
-    let source_text = card.content().to_source_text();
+    let deck_path = card.relative_file_path(&state.directory)?;
+    let source_text = card.to_source_text();
     let source_file = deck_path.display().to_string();
     let source_range = card.range();
-    let config = MarkdownRenderConfig {
-        resolver: MediaResolverBuilder::new()
-            .with_collection_path(coll_path)?
-            .with_deck_path(deck_path)?
-            .build()?,
-        port: state.port,
-    };
-    let card_content = render_card(&card, mutable.reveal, &config)?;
-    let card_controls = if mutable.reveal {
+    let config = render_config(state, &card)?;
+    let group: Vec<Card> = shown_group(state, mutable);
+    let expected: Option<&str> = expected_answer(&card);
+    let typed: Option<(&str, &str)> = expected.zip(mutable.typed.as_deref());
+    // The grade suggested by the typed answer, which can be overridden.
+    let suggested: Option<Grade> = typed
+        .map(|(expected, typed)| type_in::judge(typed, expected, state.case_folding()).grade());
+    let card_content = render_card(
+        &group,
+        mutable.reveal,
+        &config,
+        type_in_result(state, mutable, &card),
+        !state.no_autoplay,
+    );
+    let retention: Option<Retention> = mutable.db.get_retention(card.hash())?;
+    // Colors are validated when the configuration is loaded.
+    let card_color: Option<String> = state
+        .colors
+        .card_color(&card)
+        .map(|color| format!("--card-color: {color};"));
+    // Tagged cards override the direction of the page.
+    let card_dir: Option<&str> = TextDirection::of_card(&card).map(TextDirection::as_str);
+    let flagged: bool = mutable.is_flagged(&card);
+    let leech: bool = mutable.is_leech(state, &card)?;
+    let card_controls = if mutable.reveal && mutable.phase == Phase::Study {
+        html! {
+            form action="/" method="post" {
+                (undo_button(undo_disabled))
+                (redo_button(mutable.redo.is_empty()))
+                (lock_button)
+                (edit_button(&card))
+                div.spacer {}
+                input id="next" type="submit" name="action" value="Next" title="Study the next card. Shortcut: space.";
+                div.spacer {}
+                (flag_button(flagged))
+                (suspend_button())
+                (discard_button())
+                (end_button())
+            }
+        }
+    } else if mutable.reveal {
+        // The interval each grade would give the card.
+        let intervals: Vec<(Grade, i64)> = preview_intervals(state, mutable, &card)?;
+        let button = |grade: Grade, title: &str| {
+            let interval: Option<i64> = intervals
+                .iter()
+                .find(|(g, _)| *g == grade)
+                .map(|(_, days)| *days);
+            grade_button(grade, title, interval, suggested == Some(grade))
+        };
         let grades = match state.answer_controls {
+            _ if group.len() > 1 => deletion_grades(&group),
             AnswerControls::Binary => html! {
-                input id="forgot" type="submit" name="action" value="Forgot" title="Mark card as forgotten.";
-                input id="good" type="submit" name="action" value="Good" title="Mark card as remembered.";
+                (button(Grade::Forgot, "Mark card as forgotten."))
+                (button(Grade::Good, "Mark card as remembered."))
             },
             AnswerControls::Full => html! {
-                input id="forgot" type="submit" name="action" value="Forgot" title="Mark card as forgotten. Shortcut: 1.";
-                input id="hard" type="submit" name="action" value="Hard" title="Mark card as difficult. Shortcut: 2.";
-                input id="good" type="submit" name="action" value="Good" title="Mark card as remembered well. Shortcut: 3.";
-                input id="easy" type="submit" name="action" value="Easy" title="Mark card as very easy. Shortcut: 4.";
+                (button(Grade::Forgot, "Mark card as forgotten. Shortcut: 1."))
+                (button(Grade::Hard, "Mark card as difficult. Shortcut: 2."))
+                (button(Grade::Good, "Mark card as remembered well. Shortcut: 3."))
+                (button(Grade::Easy, "Mark card as very easy. Shortcut: 4."))
             },
         };
         html! {
             form action="/" method="post" {
                 (undo_button(undo_disabled))
-                input #edit-toggle type="button" value="Edit" title="Edit this card. Shortcut: e." onclick="toggleEdit()";
+                (redo_button(mutable.redo.is_empty()))
+                (lock_button)
+                (edit_button(&card))
                 div.spacer {}
+                @if state.track_confidence {
+                    (confidence_control())
+                }
                 div.grades {
                     (grades)
                 }
                 div.spacer {}
+                (skip_button(mutable))
+                (flag_button(flagged))
+                (suspend_button())
+                (discard_button())
                 (end_button())
             }
         }
@@ -105,24 +226,50 @@ fn render_session_page(state: &ServerState, mutable: &MutableState) -> Fallible<
         html! {
             form action="/" method="post" {
                 (undo_button(undo_disabled))
+                (redo_button(mutable.redo.is_empty()))
+                (lock_button)
                 div.spacer {}
-                input id="reveal" type="submit" name="action" value="Reveal" title="Show the answer. Shortcut: space.";
+                @if expected.is_some() {
+                    (type_in::render_field())
+                } @else {
+                    input id="reveal" type="submit" name="action" value="Reveal" title="Show the answer. Shortcut: space.";
+                }
                 div.spacer {}
+                (skip_button(mutable))
+                (flag_button(flagged))
+                (suspend_button())
+                (discard_button())
                 (end_button())
             }
+            @if expected.is_some() {
+                // The typed answer is sent with its own form, so that Enter
+                // checks it rather than pressing the first button.
+                form #type-in action="/" method="post" hidden {}
+            }
         }
     };
+    // A rejected edit is shown again, with the error, so it can be fixed.
+    let rejected = mutable.rejected_edit.as_ref();
+    let edit_text: &str = rejected.map_or(&source_text, |rejected| &rejected.content);
     let edit_form = html! {
-        div #edit-form hidden {
+        div #edit-form hidden[rejected.is_none()] {
             div.edit-source {
                 "Source: " (source_file) " (lines " (source_range.0 + 1) "-" (source_range.1 + 1) ")"
             }
             form action="/" method="post" {
-                textarea #edit-textarea name="edit_content" rows="8" {
-                    (source_text)
+                @if let Some(rejected) = rejected {
+                    (rejected_edit_error(rejected))
+                }
+                // The script fills the highlight layer, behind the textarea.
+                // Without it, this is a plain textarea.
+                div.edit-editor {
+                    pre #edit-highlight aria-hidden="true" {}
+                    textarea #edit-textarea name="edit_content" rows="8" autofocus[rejected.is_some()] {
+                        (edit_text)
+                    }
                 }
                 div.edit-warning {
-                    "Warning: Editing creates a new card. Learning progress will reset."
+                    "Warning: Editing creates a new card. Small edits, like fixing a typo, keep the card's learning progress; larger ones reset it."
                 }
                 div.edit-buttons {
                     input type="button" value="Cancel" onclick="toggleEdit()";
@@ -133,17 +280,39 @@ fn render_session_page(state: &ServerState, mutable: &MutableState) -> Fallible<
     };
     let html = html! {
         div.root {
+            @if let Some(grade) = mutable.last_grade.filter(|_| !state.no_grade_flash) {
+                (grade_flash(grade, &state.colors))
+            }
             div.header {
-                div.progress-bar {
-                    div.progress-fill style=(progress_bar_style) {}
+                @if state.two_phase {
+                    (phase_label(mutable.phase))
+                }
+                (progress_bar(cards_done, total_cards, state.milestones))
+                @if let Some(remaining) = state.remaining_seconds() {
+                    div #timer data-remaining=(remaining) title="Time left in the session. When it runs out, the session ends after the current card is graded." {
+                        (format_countdown(remaining))
+                    }
+                }
+                button #theme-toggle type="button" title="Switch between the light and dark themes." onclick="toggleTheme()" {
+                    "◐"
                 }
             }
             div.card-container {
-                div.card {
-                    div.card-header {
+                div.card dir=[card_dir] {
+                    div.card-header.colored[card_color.is_some()] style=[card_color] {
                         h1 {
+                            @if let Some(deck_color) = state.colors.deck_color(card.deck_name()) {
+                                span.deck-badge style=(format!("background: {deck_color};")) {}
+                            }
                             (card.deck_name())
+                            @if flagged {
+                                span.flagged title="Flagged for later review" { "Flagged" }
+                            }
+                            @if leech {
+                                span.leech title="Forgotten often: consider rewriting or splitting it" { "Leech" }
+                            }
                         }
+                        (card_info(&card, retention, &state.colors))
                     }
                     (card_content)
                 }
@@ -151,14 +320,460 @@ fn render_session_page(state: &ServerState, mutable: &MutableState) -> Fallible<
             div.controls {
                 (card_controls)
             }
-            (edit_form)
+            @if mutable.skipped_all() {
+                div.skip-notice {
+                    "You have skipped every card left. Grade one to go on, or end the session to come back to them another day."
+                }
+            }
+            @if let Some(review) = mutable.reviews.last().filter(|review| review.leech || review.leech_suspend) {
+                (leech_notice(state, review))
+            }
+            @if let Some(edit) = &mutable.last_edit {
+                (edit_summary(edit))
+            }
+            @if !card.is_from_regex() {
+                (edit_form)
+            }
+            (author_panel(state, mutable)?)
         }
     };
     Ok(html)
 }
 
-fn render_card(card: &Card, reveal: bool, config: &MarkdownRenderConfig) -> Fallible<Markup> {
+/// The configuration to render a card's Markdown with.
+pub fn render_config(state: &ServerState, card: &Card) -> Fallible<MarkdownRenderConfig> {
+    let coll_path = state.directory.clone();
+    let deck_path = card.relative_file_path(&coll_path)?;
+    Ok(MarkdownRenderConfig {
+        resolver: MediaResolverBuilder::new()
+            .with_collection_path(coll_path)?
+            .with_deck_path(deck_path)?
+            .with_base_url(state.media_url.clone())?
+            .with_remote_cache(Some(state.remote_cache.clone()))
+            .build()?,
+        port: state.port,
+        dim_cloze_context: state.dim_cloze_context,
+        highlight_code: state.highlight_code,
+        cloze_blank: state.cloze_blank,
+    })
+}
+
+/// The cards shown together: the current card, and, when grouping clozes, its
+/// due siblings.
+pub fn shown_group(state: &ServerState, mutable: &MutableState) -> Vec<Card> {
+    if state.group_cloze {
+        cloze_group(&mutable.cards)
+    } else {
+        vec![mutable.cards[0].clone()]
+    }
+}
+
+/// The expected answer of a type-in card.
+fn expected_answer(card: &Card) -> Option<&str> {
+    match card.content() {
+        CardContent::TypeIn { answer, .. } => Some(answer),
+        _ => None,
+    }
+}
+
+/// The difference between the typed and the expected answer of a type-in
+/// card, once it is checked.
+pub fn type_in_result(state: &ServerState, mutable: &MutableState, card: &Card) -> Option<Markup> {
+    let expected: &str = expected_answer(card)?;
+    let typed: &str = mutable.typed.as_deref()?;
+    Some(type_in::render_result(
+        typed,
+        expected,
+        state.case_folding(),
+    ))
+}
+
+/// A brief flash of the color of the grade just given. Colors are validated
+/// when the configuration is loaded.
+fn grade_flash(grade: Grade, colors: &ColorConfig) -> Markup {
+    let style = format!("--flash-color: {};", colors.grades.color(grade));
+    html! {
+        div.grade-flash.(grade.as_str()) aria-hidden="true" style=(style) {}
+    }
+}
+
+/// The label of the current phase of a two-phase session.
+fn phase_label(phase: Phase) -> Markup {
+    match phase {
+        Phase::Study => html! {
+            div.phase.study title="Study each card. You will grade them once every card has been studied." {
+                "Study"
+            }
+        },
+        Phase::Grade => html! {
+            div.phase.grade title="Grade each card you studied." {
+                "Grade"
+            }
+        },
+    }
+}
+
+/// The progress bar. With milestones, a tick marks every `milestones` cards,
+/// the fill changes color past the halfway and 90% marks, and the card count
+/// is shown next to the bar.
+fn progress_bar(cards_done: usize, total_cards: usize, milestones: Option<usize>) -> Markup {
+    let percent_done = (cards_done * 100).checked_div(total_cards).unwrap_or(100);
+    let fill_style = format!("width: {percent_done}%;");
+    let Some(step) = milestones.filter(|step| *step > 0) else {
+        return html! {
+            div.progress-bar {
+                div.progress-fill style=(fill_style) {}
+            }
+        };
+    };
+    let stage = match percent_done {
+        90.. => "stage-final",
+        50.. => "stage-half",
+        _ => "stage-start",
+    };
+    html! {
+        div.progress-bar.milestones {
+            div.progress-fill.(stage) style=(fill_style) {}
+            @for milestone in (step..total_cards).step_by(step) {
+                div.milestone
+                    .reached[milestone <= cards_done]
+                    style=(format!("inset-inline-start: {}%;", milestone * 100 / total_cards))
+                    title=(format!("{milestone} cards")) {}
+            }
+        }
+        div.progress-count {
+            (cards_done) "/" (total_cards)
+        }
+    }
+}
+
+/// Tell the user the card they just graded became a leech, or reached the
+/// threshold to suspend it at, and what will be done about it.
+fn leech_notice(state: &ServerState, review: &Review) -> Markup {
+    let card: &Card = &review.card;
+    if review.leech_suspend {
+        return html! {
+            div.leech-notice {
+                "The last card, from " (card.deck_name()) ", is a leech: you have forgotten it "
+                (state.leech_suspend_threshold.unwrap_or_default()) " times. "
+                "It will be suspended when the session ends."
+            }
+        };
+    }
+    let action: &str = match state.leech_action {
+        None | Some(LeechAction::Notify) => {
+            "It will be flagged when the session ends. Consider rewriting or splitting it."
+        }
+        Some(LeechAction::Tag) => {
+            "It will be tagged #leech and #flagged in its deck file when the session ends."
+        }
+        Some(LeechAction::Suspend) => "It will be flagged and suspended when the session ends.",
+    };
+    html! {
+        div.leech-notice {
+            "The last card, from " (card.deck_name()) ", is a leech: you have forgotten it "
+            (state.leech_threshold) " times. " (action)
+        }
+    }
+}
+
+/// Explain why an edit was rejected, quoting the line the error points at.
+fn rejected_edit_error(rejected: &RejectedEdit) -> Markup {
+    let line: Option<(usize, &str)> = rejected
+        .line_num
+        .and_then(|line_num| Some((line_num, rejected.content.lines().nth(line_num)?)));
+    html! {
+        div.edit-error {
+            "The edit was not saved: " (rejected.message)
+            @if let Some((line_num, line)) = line {
+                div.edit-error-line {
+                    span.line-number { "Line " (line_num + 1) ": " }
+                    code { (line) }
+                }
+            }
+        }
+    }
+}
+
+/// Summarize the last edit: a line diff of the source text, and the old and
+/// new hashes.
+fn edit_summary(edit: &Edit) -> Markup {
+    let diff = TextDiff::from_lines(&edit.old_source, &edit.new_source);
+    html! {
+        div.edit-summary {
+            h2 { "Card Edited" }
+            div.diff {
+                @for change in diff.iter_all_changes() {
+                    @let line = change.value().trim_end_matches('\n');
+                    @match change.tag() {
+                        ChangeTag::Equal => div.diff-equal { "  " (line) },
+                        ChangeTag::Delete => div.diff-delete { "- " (line) },
+                        ChangeTag::Insert => div.diff-insert { "+ " (line) },
+                    }
+                }
+            }
+            div.edit-hashes {
+                div { "Old: " (edit.old_hash) }
+                @for hash in &edit.new_hashes {
+                    div {
+                        "New: " (hash)
+                        @if edit.preserved.contains(hash) {
+                            " (progress kept)"
+                        }
+                    }
+                }
+            }
+            @if edit.preserved.is_empty() {
+                div.edit-warning {
+                    "Learning progress was reset: edited cards are new cards, with no review history."
+                }
+            } @else if edit.preserved.len() == edit.new_hashes.len() {
+                div.edit-kept {
+                    "Learning progress was kept: the edit is small enough that the card keeps its review history."
+                }
+            } @else {
+                div.edit-warning {
+                    "Learning progress was kept for the cards marked above. The others are new cards, with no review history."
+                }
+            }
+        }
+    }
+}
+
+/// In author mode, show the source and hash of the last graded card, with a
+/// button to flag it for revision.
+fn author_panel(state: &ServerState, mutable: &MutableState) -> Fallible<Markup> {
+    let card: &Card = match mutable.reviews.last() {
+        Some(review) if state.author => &review.card,
+        _ => return Ok(html! {}),
+    };
+    let deck_path = card.relative_file_path(&state.directory)?;
+    let (start, end) = card.range();
+    let revise_title = format!("Append this card to {REVISIONS_FILE}.");
+    let html = html! {
+        div.author-panel {
+            div.author-source {
+                "Last card: " (deck_path.display()) " (lines " (start + 1) "-" (end + 1) ")"
+            }
+            div.author-hash {
+                (card.hash())
+            }
+            pre {
+                (card.to_source_text())
+            }
+            form action="/" method="post" {
+                @if mutable.revised.contains(&card.hash()) {
+                    input #revise type="submit" name="action" value="Revise" disabled;
+                } @else {
+                    input #revise type="submit" name="action" value="Revise" title=(revise_title);
+                }
+            }
+            form action="/reload" method="post" {
+                input #reload type="submit" value="Reload" title="Reload the collection from disk, after editing deck files.";
+            }
+        }
+    };
+    Ok(html)
+}
+
+/// A grade button, labeled with the interval the grade would give the card.
+/// The suggested grade, from a typed answer, is focused.
+fn grade_button(grade: Grade, title: &str, interval: Option<i64>, suggested: bool) -> Markup {
+    let label: &str = match grade {
+        Grade::Forgot => "Forgot",
+        Grade::Hard => "Hard",
+        Grade::Good => "Good",
+        Grade::Easy => "Easy",
+    };
+    html! {
+        span.grade-option {
+            input id=(grade.as_str()) class=[suggested.then_some("suggested")] type="submit" name="action" value=(label) title=(title) autofocus[suggested];
+            @if let Some(days) = interval {
+                span.grade-interval title=(format!("The next review would be in {days} days.")) {
+                    (format_interval(days))
+                }
+            }
+        }
+    }
+}
+
+/// Format an interval in days briefly: in days under a month, in months
+/// under a year, and in years otherwise, e.g. `4d`, `3mo`, `1.5y`.
+fn format_interval(days: i64) -> String {
+    if days < 30 {
+        format!("{days}d")
+    } else if days < 365 {
+        format!("{}mo", (days as f64 / 30.0).round())
+    } else {
+        format!("{:.1}y", days as f64 / 365.0)
+    }
+}
+
+/// Format a number of seconds as `m:ss`. The script keeps this up to date.
+fn format_countdown(seconds: i64) -> String {
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Show the card's tags and measured retention, if any.
+fn card_info(card: &Card, retention: Option<Retention>, colors: &ColorConfig) -> Markup {
+    if card.tags().is_empty() && retention.is_none() {
+        return html! {};
+    }
+    html! {
+        div.card-info {
+            @for tag in card.tags() {
+                @match colors.tag_color(tag) {
+                    Some(color) => span.tag.colored style=(format!("--tag-color: {color};")) { "#" (tag) },
+                    None => span.tag { "#" (tag) },
+                }
+            }
+            @if let Some(retention) = retention {
+                span.retention title="Fraction of past reviews where this card was remembered." {
+                    "Retention: " (retention)
+                }
+            }
+        }
+    }
+}
+
+/// Radio buttons to rate confidence in the answer, submitted with the grade.
+/// None is chosen by default, so the rating is optional.
+fn confidence_control() -> Markup {
+    html! {
+        fieldset.confidence title="How confident were you in your answer? Recorded with the grade, without affecting scheduling." {
+            legend { "Confidence" }
+            @for value in Confidence::all() {
+                label {
+                    input type="radio" name="confidence" value=(value);
+                    (value)
+                }
+            }
+        }
+    }
+}
+
+/// A checkbox for each deletion of a cloze group, to grade them separately.
+fn deletion_grades(group: &[Card]) -> Markup {
+    html! {
+        @for card in group {
+            label.deletion title="Uncheck if you forgot this deletion." {
+                input type="checkbox" name=(format!("recalled-{}", card.hash())) checked;
+                (deleted_text(card))
+            }
+        }
+        input id="grade" type="submit" name="action" value="Grade" title="Grade the checked deletions as remembered, and the others as forgotten.";
+    }
+}
+
+/// Render the card, or a group of sibling cards shown together. If the card's
+/// content fails to render, e.g. because of a broken media reference, show the
+/// error in place of the content, so the card can still be graded or edited.
+pub fn render_card(
+    group: &[Card],
+    reveal: bool,
+    config: &MarkdownRenderConfig,
+    type_in_result: Option<Markup>,
+    autoplay: bool,
+) -> Markup {
+    let content: Fallible<Markup> = match group {
+        [card] => render_card_content(card, reveal, config, type_in_result),
+        _ => render_group(group, reveal, config),
+    };
+    // The extra notes are shown under the answer. The cards of a group are
+    // siblings, and share them.
+    let content: Fallible<Markup> = content.and_then(|html| {
+        let extra: Option<Markup> = match group.first() {
+            Some(card) if reveal => card.html_extra(config)?,
+            _ => None,
+        };
+        // The answer's audio gets a replay button, and plays on reveal unless
+        // autoplay is off.
+        let audio: Option<String> = if reveal {
+            mark_answer_audio(&html.0, autoplay)
+        } else {
+            None
+        };
+        Ok(html! {
+            @if let Some(audio) = audio {
+                (PreEscaped(audio))
+                button #replay type="button" title="Play the answer's audio again." onclick="replayAudio()" {
+                    "Replay"
+                }
+            } @else {
+                (html)
+            }
+            @if let Some(extra) = extra {
+                div .extra .rich-text {
+                    (extra)
+                }
+            }
+        })
+    });
+    let html = match content {
+        Ok(html) => html,
+        Err(e) => html! {
+            div.render-error {
+                p { "This card failed to render:" }
+                p { (e) }
+            }
+        },
+    };
+    html! {
+        div.card-content {
+            (html)
+        }
+    }
+}
+
+/// The opening of the audio elements in rendered Markdown.
+const AUDIO_OPEN: &str = "<audio ";
+
+/// Where the answer of a revealed card starts: at the answer box of a basic
+/// card, or at the first revealed deletion of a cloze card.
+const ANSWER_OPEN: [&str; 2] = ["class=\"answer", "class='cloze-reveal'"];
+
+/// Mark the first audio element in the answer of a revealed card, so the
+/// replay button can find it, and have it play on its own if `autoplay` is
+/// set. Audio on the front of the card is left alone. Returns `None` if the
+/// answer has no audio.
+fn mark_answer_audio(html: &str, autoplay: bool) -> Option<String> {
+    let answer: usize = ANSWER_OPEN
+        .iter()
+        .filter_map(|open| html.find(open))
+        .min()?;
+    let audio: usize = answer + html[answer..].find(AUDIO_OPEN)? + AUDIO_OPEN.len();
+    let attributes: &str = if autoplay {
+        "id=\"answer-audio\" autoplay "
+    } else {
+        "id=\"answer-audio\" "
+    };
+    Some(format!("{}{attributes}{}", &html[..audio], &html[audio..]))
+}
+
+fn render_card_content(
+    card: &Card,
+    reveal: bool,
+    config: &MarkdownRenderConfig,
+    type_in_result: Option<Markup>,
+) -> Fallible<Markup> {
     let html = match card.card_type() {
+        CardType::TypeIn => {
+            html! {
+                div .question .rich-text {
+                    (card.html_front(config)?)
+                }
+                @if reveal {
+                    @if let Some(result) = type_in_result {
+                        (result)
+                    }
+                    div .answer .rich-text {
+                        (card.html_back(config)?)
+                    }
+                } @else {
+                    div .answer .rich-text {}
+                }
+            }
+        }
         CardType::Basic => {
             if reveal {
                 html! {
@@ -194,40 +809,226 @@ fn render_card(card: &Card, reveal: bool, config: &MarkdownRenderConfig) -> Fall
             }
         }
     };
+    Ok(html)
+}
+
+/// Which side of the cards to show in the card list.
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Front,
+    Back,
+}
+
+impl Side {
+    fn name(self) -> &'static str {
+        match self {
+            Side::Front => "front",
+            Side::Back => "back",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Side::Front => "Questions",
+            Side::Back => "Answers",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    side: Option<Side>,
+    /// Whether to list the cards already reviewed in the session too, with
+    /// their grades and resulting intervals.
+    annotate: Option<bool>,
+}
+
+pub async fn list_handler(
+    State(state): State<ServerState>,
+    Query(query): Query<ListQuery>,
+) -> (StatusCode, Html<String>) {
+    let side = query.side.unwrap_or(Side::Back);
+    let annotate = query.annotate.unwrap_or(false);
+    let html = match render_list_page(&state, side, annotate) {
+        Ok(html) => page_template(side.title(), html, &state.language, state.theme),
+        Err(e) => page_template(
+            "Error",
+            html! {
+                div.error {
+                    h1 { "Error" }
+                    p { (e) }
+                }
+            },
+            &state.language,
+            state.theme,
+        ),
+    };
+    (StatusCode::OK, Html(html.into_string()))
+}
+
+/// List one side of every card left in the session, without grading. When
+/// annotated, the cards reviewed so far come first, each with its grades and
+/// resulting interval, so the list is a record of the session.
+fn render_list_page(state: &ServerState, side: Side, annotate: bool) -> Fallible<Markup> {
+    let mutable = state.mutable.lock().unwrap();
+    let reviewed: Vec<&Card> = if annotate {
+        mutable.reviews.iter().map(|review| &review.card).collect()
+    } else {
+        Vec::new()
+    };
+    let mut seen: HashSet<CardHash> = HashSet::new();
+    let mut cards: Vec<Markup> = Vec::new();
+    for card in reviewed.into_iter().chain(mutable.cards.iter()) {
+        // Cards graded "Forgot" or "Hard" are reviewed and queued more than
+        // once.
+        if seen.insert(card.hash()) {
+            let config = MarkdownRenderConfig {
+                resolver: MediaResolverBuilder::new()
+                    .with_collection_path(state.directory.clone())?
+                    .with_deck_path(card.relative_file_path(&state.directory)?)?
+                    .with_base_url(state.media_url.clone())?
+                    .with_remote_cache(Some(state.remote_cache.clone()))
+                    .build()?,
+                port: state.port,
+                dim_cloze_context: state.dim_cloze_context,
+                highlight_code: state.highlight_code,
+                cloze_blank: state.cloze_blank,
+            };
+            let reviews: Vec<&Review> = mutable
+                .reviews
+                .iter()
+                .filter(|review| annotate && review.card.hash() == card.hash())
+                .collect();
+            cards.push(render_side(card, side, &config, &reviews)?);
+        }
+    }
+    let html = html! {
+        div.browse {
+            a.back href="/" { "← Back to session" }
+            h1 { (side.title()) }
+            p.sides {
+                a href=(format!("/list?side=front&annotate={annotate}")) { "Questions" }
+                " · "
+                a href=(format!("/list?side=back&annotate={annotate}")) { "Answers" }
+                " · "
+                @if annotate {
+                    a href=(format!("/list?side={}", side.name())) { "Hide grades" }
+                } @else {
+                    a href=(format!("/list?side={}&annotate=true", side.name())) { "Show grades" }
+                }
+            }
+            @for card in cards {
+                (card)
+            }
+        }
+    };
+    Ok(html)
+}
+
+/// Render a single side of a card, with the grades it was given in the
+/// session, if any. Cloze cards only have a prompt, so the back side shows the
+/// prompt with the deletion revealed.
+fn render_side(
+    card: &Card,
+    side: Side,
+    config: &MarkdownRenderConfig,
+    reviews: &[&Review],
+) -> Fallible<Markup> {
+    let html = match (card.card_type(), side) {
+        (CardType::Basic | CardType::TypeIn, Side::Front) => html! {
+            div .question .rich-text {
+                (card.html_front(config)?)
+            }
+        },
+        (CardType::Basic | CardType::TypeIn, Side::Back) => html! {
+            div .answer .rich-text {
+                (card.html_back(config)?)
+            }
+        },
+        (CardType::Cloze, Side::Front) => html! {
+            div .prompt .rich-text {
+                (card.html_front(config)?)
+            }
+        },
+        (CardType::Cloze, Side::Back) => html! {
+            div .prompt .rich-text {
+                (card.html_back(config)?)
+            }
+        },
+    };
+    let dir: Option<&str> = TextDirection::of_card(card).map(TextDirection::as_str);
     Ok(html! {
-        div.card-content {
+        div.card-preview dir=[dir] {
+            div.card-preview-deck { (card.deck_name()) }
             (html)
+            @if let Some(last) = reviews.last() {
+                div.card-preview-reviews {
+                    @for (index, review) in reviews.iter().enumerate() {
+                        @if index > 0 { " → " }
+                        span.grade { (review.grade.as_str()) }
+                    }
+                    " · next review in " (last.interval_days) " days, on " (last.due_date)
+                }
+            }
         }
     })
 }
 
-const TS_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
-
 fn render_completion_page(state: &ServerState, mutable: &MutableState) -> Fallible<Markup> {
     let total_cards = state.total_cards;
-    let cards_reviewed = state.total_cards - mutable.cards.len();
+    // Cards studied in the first phase of a two-phase session haven't been
+    // reviewed until they are graded.
+    let cards_reviewed = state
+        .total_cards
+        .saturating_sub(mutable.cards.len() + mutable.studied.len());
     let start = state.session_started_at.into_inner();
     let end = mutable.finished_at.unwrap().into_inner();
     let duration_s = (end - start).num_seconds();
+    let grading_start = mutable.grading_started_at.map(|ts| ts.into_inner());
+    let study_s = (grading_start.unwrap_or(end) - start).num_seconds();
+    let grading_s = grading_start.map(|grading_start| (end - grading_start).num_seconds());
     let pace: f64 = if cards_reviewed == 0 {
         0.0
     } else {
         duration_s as f64 / cards_reviewed as f64
     };
     let pace = format!("{:.2}", pace);
-    let start_ts = start.format(TS_FORMAT).to_string();
-    let end_ts = end.format(TS_FORMAT).to_string();
+    let leeches: usize = mutable.reviews.iter().filter(|review| review.leech).count();
+    let start_ts = state.time_format.format(state.session_started_at);
+    let end_ts = state.time_format.format(mutable.finished_at.unwrap());
     let html = html! {
         div.finished {
-            h1 {
-                "Session Completed 🎉"
-            }
-            div.summary {
-                "Reviewed "
-                (cards_reviewed)
-                " cards in "
-                (duration_s)
-                " seconds."
+            @if mutable.discarded {
+                h1 {
+                    "Session Discarded"
+                }
+                div.summary {
+                    "Reviewed "
+                    (cards_reviewed)
+                    " cards in "
+                    (duration_s)
+                    " seconds. No changes were saved."
+                }
+            } @else {
+                h1 {
+                    "Session Completed 🎉"
+                }
+                div.summary {
+                    "Reviewed "
+                    (cards_reviewed)
+                    " cards in "
+                    (duration_s)
+                    " seconds."
+                    @if state.no_store {
+                        " Reviews were not saved, since the session ran with "
+                        code { "--no-store" }
+                        "."
+                    }
+                    @if state.deferred_cards > 0 {
+                        " More cards are due today, beyond the session's limits."
+                    }
+                }
             }
             h2 {
                 "Session Stats"
@@ -259,19 +1060,104 @@ fn render_completion_page(state: &ServerState, mutable: &MutableState) -> Fallib
                             td .key { "Pace (s/card)" }
                             td .val { (pace) }
                         }
+                        @if let Some(time_limit) = state.time_limit {
+                            tr {
+                                td .key { "Time Limit (minutes)" }
+                                td .val { (time_limit) }
+                            }
+                        }
+                        @if state.two_phase {
+                            tr {
+                                td .key { "Study Phase (seconds)" }
+                                td .val { (study_s) }
+                            }
+                            tr {
+                                td .key { "Grading Phase (seconds)" }
+                                td .val {
+                                    @match grading_s {
+                                        Some(grading_s) => (grading_s),
+                                        None => "Not started",
+                                    }
+                                }
+                            }
+                        }
+                        @if state.detects_leeches() {
+                            tr {
+                                td .key { "Leeches" }
+                                td .val { (leeches) }
+                            }
+                        }
+                        @if state.warmup_cards > 0 {
+                            tr {
+                                td .key { "Warm-up Cards" }
+                                td .val { (state.warmup_cards) }
+                            }
+                        }
+                        @if state.deferred_cards > 0 {
+                            tr {
+                                td .key { "Cards Left Due" }
+                                td .val { (state.deferred_cards) }
+                            }
+                        }
                     }
                 }
             }
-            div.shutdown-container {
-                form action="/" method="post" {
-                    input #shutdown .shutdown-button type="submit" name="action" value="Shutdown" title="Shut down the server";
-                }
+            p.session-record {
+                a href="/list?side=back&annotate=true" { "View the cards of this session, with their grades." }
             }
+            (author_panel(state, mutable)?)
+            (shutdown_form())
         }
     };
     Ok(html)
 }
 
+/// The page of a session with no card to show. Reviews made before a reload
+/// removed the cards left are saved by ending the session.
+fn render_empty_page(mutable: &MutableState) -> Markup {
+    html! {
+        div.finished {
+            h1 { "Nothing to Study" }
+            @if mutable.reviews.is_empty() {
+                div.summary { "There are no cards to drill in this session." }
+                (shutdown_form())
+            } @else {
+                div.summary {
+                    "The cards left in this session were removed from the collection. End the session to save its reviews."
+                }
+                form action="/" method="post" {
+                    (undo_button(!can_undo(mutable)))
+                    (end_button())
+                }
+            }
+        }
+    }
+}
+
+fn shutdown_form() -> Markup {
+    html! {
+        div.shutdown-container {
+            form action="/" method="post" {
+                input #shutdown .shutdown-button type="submit" name="action" value="Shutdown" title="Shut down the server";
+            }
+        }
+    }
+}
+
+/// Cards matched by the card regex can't be written back in their own
+/// format, so they are edited in their file instead.
+fn edit_button(card: &Card) -> Markup {
+    if card.is_from_regex() {
+        html! {
+            input #edit-toggle type="button" value="Edit" disabled title="This card is matched by card_regex: edit it in its file.";
+        }
+    } else {
+        html! {
+            input #edit-toggle type="button" value="Edit" title="Edit this card. Shortcut: e." onclick="toggleEdit()";
+        }
+    }
+}
+
 fn undo_button(disabled: bool) -> Markup {
     if disabled {
         html! {
@@ -284,6 +1170,58 @@ fn undo_button(disabled: bool) -> Markup {
     }
 }
 
+fn redo_button(disabled: bool) -> Markup {
+    if disabled {
+        html! {
+            input id="redo" type="submit" name="action" value="Redo" disabled;
+        }
+    } else {
+        html! {
+            input id="redo" type="submit" name="action" value="Redo" title="Redo the last action undone. Shortcut: r.";
+        }
+    }
+}
+
+fn lock_button() -> Markup {
+    html! {
+        input id="lock" type="submit" name="action" value="Lock" title="Lock in the reviews so far. They are saved, and can no longer be undone.";
+    }
+}
+
+fn flag_button(flagged: bool) -> Markup {
+    if flagged {
+        html! {
+            input id="flag" type="submit" name="action" value="Flag" disabled;
+        }
+    } else {
+        html! {
+            input id="flag" type="submit" name="action" value="Flag" title="Flag this card for later review. It is tagged #flagged when the session ends.";
+        }
+    }
+}
+
+/// Cards are only skipped while grading.
+fn skip_button(mutable: &MutableState) -> Markup {
+    if mutable.phase != Phase::Grade {
+        return html! {};
+    }
+    html! {
+        input id="skip" type="submit" name="action" value="Skip" title="Send this card to the back of the queue, without grading it. Shortcut: s." disabled[mutable.skipped_all()];
+    }
+}
+
+fn suspend_button() -> Markup {
+    html! {
+        input id="suspend" type="submit" name="action" value="Suspend" title="Remove this card from the session, and don't schedule it again until it is unsuspended." onclick="return confirm('Suspend this card? It will not be drilled again until it is unsuspended.')";
+    }
+}
+
+fn discard_button() -> Markup {
+    html! {
+        input id="discard" type="submit" name="action" value="Discard" title="End the session without saving this session's reviews" onclick="return confirm('Discard this session? Your reviews will not be saved.')";
+    }
+}
+
 fn end_button() -> Markup {
     html! {
         input id="end" type="submit" name="action" value="End" title="End the session (changes are saved)";
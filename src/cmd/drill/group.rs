@@ -0,0 +1,175 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+
+use maud::Markup;
+use maud::PreEscaped;
+use maud::html;
+
+use crate::error::Fallible;
+use crate::markdown::ClozeSide;
+use crate::markdown::MarkdownRenderConfig;
+use crate::markdown::cloze_group_to_html;
+use crate::types::card::Card;
+use crate::types::card::CardType;
+use crate::types::card_hash::CardHash;
+
+/// The cards shown together, starting with the card at the front of the
+/// queue. In a group, a cloze card is shown with the siblings queued in the
+/// same session, so each deletion is graded separately while sharing a
+/// display. Siblings queued more than once are included once. Other cards,
+/// like the directions of a reversible card, are shown on their own.
+pub fn cloze_group(cards: &[Card]) -> Vec<Card> {
+    let Some(first) = cards.first() else {
+        return Vec::new();
+    };
+    let family: CardHash = match first.family_hash() {
+        Some(family) if first.card_type() == CardType::Cloze => family,
+        _ => return vec![first.clone()],
+    };
+    let mut seen: HashSet<CardHash> = HashSet::new();
+    cards
+        .iter()
+        .filter(|card| card.family_hash() == Some(family))
+        .filter(|card| seen.insert(card.hash()))
+        .cloned()
+        .collect()
+}
+
+/// The deleted text of a cloze card, as written in the source. The
+/// deletions of a group are separated by commas.
+pub fn deleted_text(card: &Card) -> String {
+    match card.content().cloze_deletions() {
+        Some((text, deletions)) => deletions
+            .iter()
+            .map(|(start, end)| text.get(*start..=*end).unwrap_or_default())
+            .collect::<Vec<&str>>()
+            .join(", "),
+        None => String::new(),
+    }
+}
+
+/// Render a group of siblings as a single cloze card, with all of their
+/// deletions hidden or revealed.
+pub fn render_group(
+    group: &[Card],
+    reveal: bool,
+    config: &MarkdownRenderConfig,
+) -> Fallible<Markup> {
+    let mut text: &str = "";
+    let mut deletions: Vec<(usize, usize)> = Vec::new();
+    for card in group {
+        if let Some((card_text, card_deletions)) = card.content().cloze_deletions() {
+            text = card_text;
+            deletions.extend(card_deletions);
+        }
+    }
+    let side: ClozeSide = if reveal {
+        ClozeSide::Back
+    } else {
+        ClozeSide::Front
+    };
+    let html: String = cloze_group_to_html(config, text, &deletions, side)?;
+    let html = html! {
+        div .prompt .rich-text {
+            @if !reveal && config.dim_cloze_context {
+                div.cloze-context {
+                    (PreEscaped(html))
+                }
+            } @else {
+                (PreEscaped(html))
+            }
+        }
+    };
+    Ok(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::helper::create_tmp_directory;
+    use crate::markdown::ClozeBlank;
+    use crate::media::resolve::MediaResolverBuilder;
+    use crate::types::card::CardContent;
+
+    fn make_cloze(text: &str, start: usize, end: usize) -> Card {
+        Card::new(
+            "Deck".to_string(),
+            PathBuf::from("Deck.md"),
+            (0, 1),
+            CardContent::new_cloze(text, start, end).unwrap(),
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn test_cloze_group() {
+        let a = make_cloze("a b c", 0, 0);
+        let b = make_cloze("a b c", 2, 2);
+        let x = make_cloze("x y", 0, 0);
+        // Siblings are grouped, in queue order, even if queued twice.
+        let queue = vec![b.clone(), x.clone(), a.clone(), b.clone()];
+        let hashes: Vec<CardHash> = cloze_group(&queue).iter().map(|card| card.hash()).collect();
+        assert_eq!(hashes, vec![b.hash(), a.hash()]);
+        assert_eq!(deleted_text(&a), "a");
+        // Basic cards are shown alone.
+        let basic = Card::new(
+            "Deck".to_string(),
+            PathBuf::from("Deck.md"),
+            (0, 1),
+            CardContent::new_basic("Q", "A"),
+            Vec::new(),
+        );
+        assert_eq!(cloze_group(&[basic.clone(), a]).len(), 1);
+        // So are the directions of a reversible card.
+        let [forward, reversed] = CardContent::new_reversible("Q", "A").map(|content| {
+            Card::new(
+                "Deck".to_string(),
+                PathBuf::from("Deck.md"),
+                (0, 1),
+                content,
+                Vec::new(),
+            )
+        });
+        assert_eq!(cloze_group(&[forward, reversed]).len(), 1);
+        assert!(cloze_group(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_render_group() -> Fallible<()> {
+        let config = MarkdownRenderConfig {
+            resolver: MediaResolverBuilder::new()
+                .with_collection_path(create_tmp_directory()?)?
+                .with_deck_path(PathBuf::from("Deck.md"))?
+                .build()?,
+            port: 1234,
+            dim_cloze_context: false,
+            highlight_code: true,
+            cloze_blank: ClozeBlank::default(),
+        };
+        let group = vec![make_cloze("a b c", 4, 4), make_cloze("a b c", 0, 0)];
+        let front = render_group(&group, false, &config)?.into_string();
+        assert_eq!(front.matches("class='cloze'").count(), 2);
+        let back = render_group(&group, true, &config)?.into_string();
+        assert!(
+            back.contains(
+                "<span class='cloze-reveal'>a</span> b <span class='cloze-reveal'>c</span>"
+            )
+        );
+        Ok(())
+    }
+}
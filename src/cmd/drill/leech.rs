@@ -0,0 +1,242 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs::read_to_string;
+use std::fs::write;
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::fsrs::Grade;
+use crate::parser::frontmatter_lines;
+use crate::parser::is_card_boundary;
+use crate::types::card::Card;
+
+/// The tag added to leeches by [`LeechAction::Tag`].
+pub const LEECH_TAG: &str = "leech";
+
+/// The tag added to cards flagged for later review in the drill interface.
+pub const FLAG_TAG: &str = "flagged";
+
+/// The default number of lapses after which a card is a leech.
+pub const DEFAULT_LEECH_THRESHOLD: usize = 8;
+
+/// The number of reviews in a row, none of them forgotten, after which a
+/// card's lapses are forgiven.
+pub const LAPSE_RESET_STREAK: usize = 3;
+
+/// What to do when a card becomes a leech, i.e. when its lapse count reaches
+/// the threshold.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum LeechAction {
+    /// Only show a notice.
+    Notify,
+    /// Add a `#leech` tag to the card's source, at the end of the session.
+    Tag,
+    /// Suspend the card, at the end of the session.
+    Suspend,
+}
+
+impl Display for LeechAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LeechAction::Notify => write!(f, "notify"),
+            LeechAction::Tag => write!(f, "tag"),
+            LeechAction::Suspend => write!(f, "suspend"),
+        }
+    }
+}
+
+/// Count a card's lapses, given the grades of its reviews, oldest first. A
+/// lapse is a review graded [`Grade::Forgot`], but remembering the card
+/// [`LAPSE_RESET_STREAK`] times in a row resets the count to zero: only the
+/// lapses since the last such streak are counted.
+pub fn count_lapses(grades: &[Grade]) -> usize {
+    let mut lapses: usize = 0;
+    let mut streak: usize = 0;
+    for grade in grades {
+        if *grade == Grade::Forgot {
+            lapses += 1;
+            streak = 0;
+        } else {
+            streak += 1;
+            if streak == LAPSE_RESET_STREAK {
+                lapses = 0;
+            }
+        }
+    }
+    lapses
+}
+
+/// Add a tag line to the source of each card, after its last line, with the
+/// tags given for it. Each entry is a card and one of its tags. Tags the card
+/// already has are skipped, and cloze siblings share a single line. Tags are
+/// not part of a card's content, so its hash doesn't change.
+pub fn tag_cards(tagged: &[(&Card, &str)]) -> Fallible<()> {
+    // The tags to add, by file, and by the line range of the card.
+    let mut files: BTreeMap<&PathBuf, BTreeMap<(usize, usize), Vec<&str>>> = BTreeMap::new();
+    for (card, tag) in tagged {
+        if card.has_tag(tag) {
+            continue;
+        }
+        let tags: &mut Vec<&str> = files
+            .entry(card.file_path())
+            .or_default()
+            .entry(card.range())
+            .or_default();
+        if !tags.contains(tag) {
+            tags.push(tag);
+        }
+    }
+    for (file_path, cards) in files {
+        let text = read_to_string(file_path)?;
+        let offset = frontmatter_lines(&text)?;
+        let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+        // From the bottom up, so inserting a line doesn't move the cards that
+        // are left.
+        for ((start, end), tags) in cards.iter().rev() {
+            let position = tag_line_position(&lines, offset + start, offset + end)?;
+            let line: Vec<String> = tags.iter().map(|tag| format!("#{tag}")).collect();
+            lines.insert(position, line.join(" "));
+        }
+        let mut new_text = lines.join("\n");
+        if text.ends_with('\n') {
+            new_text.push('\n');
+        }
+        write(file_path, new_text)?;
+    }
+    Ok(())
+}
+
+/// The line to insert a tag line at, for the card spanning the given lines:
+/// after its last non-blank line.
+fn tag_line_position(lines: &[String], start: usize, end: usize) -> Fallible<usize> {
+    if !lines.get(start).is_some_and(|line| is_card_boundary(line)) {
+        return fail(format!(
+            "The card at line {} was moved or edited; not tagging it.",
+            start + 1
+        ));
+    }
+    let mut last = start;
+    for (index, line) in lines.iter().enumerate().take(end + 1).skip(start + 1) {
+        if is_card_boundary(line) {
+            break;
+        }
+        if !line.trim().is_empty() {
+            last = index;
+        }
+    }
+    Ok(last + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::ignore::IgnoreRules;
+    use crate::parser::DEFAULT_DECK_SEPARATOR;
+    use crate::parser::parse_deck;
+
+    fn parse(path: &Path) -> Fallible<Vec<Card>> {
+        let mut cards = parse_deck(
+            &path.parent().unwrap().to_path_buf(),
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )?;
+        cards.sort_by_key(|card| card.range().0);
+        Ok(cards)
+    }
+
+    fn tag_leeches(cards: &[Card]) -> Fallible<()> {
+        let tagged: Vec<(&Card, &str)> = cards.iter().map(|card| (card, LEECH_TAG)).collect();
+        tag_cards(&tagged)
+    }
+
+    #[test]
+    fn test_count_lapses() {
+        use Grade::*;
+        assert_eq!(count_lapses(&[]), 0);
+        assert_eq!(count_lapses(&[Forgot, Good, Forgot]), 2);
+        assert_eq!(count_lapses(&[Forgot, Hard, Good, Forgot]), 2);
+        // Three reviews in a row without a lapse forgive the earlier ones.
+        assert_eq!(count_lapses(&[Forgot, Forgot, Good, Hard, Easy]), 0);
+        assert_eq!(count_lapses(&[Forgot, Forgot, Good, Good, Good, Forgot]), 1);
+        // A lapse breaks the streak.
+        assert_eq!(count_lapses(&[Forgot, Good, Good, Forgot, Good, Good]), 2);
+    }
+
+    #[test]
+    fn test_tag_leeches() -> Fallible<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("Deck.md");
+        write(
+            &path,
+            "Q: foo\nA: bar\n\nQ: baz\nA: quux\n#hard\n\nC: [a] and [b]\n",
+        )?;
+        let cards = parse(&path)?;
+        tag_leeches(&cards)?;
+        assert_eq!(
+            read_to_string(&path)?,
+            "Q: foo\nA: bar\n#leech\n\nQ: baz\nA: quux\n#hard\n#leech\n\nC: [a] and [b]\n#leech\n"
+        );
+
+        // The hashes don't change, and the cards are tagged.
+        let tagged = parse(&path)?;
+        assert_eq!(tagged.len(), cards.len());
+        for (card, tagged) in cards.iter().zip(&tagged) {
+            assert_eq!(card.hash(), tagged.hash());
+            assert!(tagged.has_tag(LEECH_TAG));
+        }
+
+        // Tagging again changes nothing.
+        tag_leeches(&tagged)?;
+        assert_eq!(parse(&path)?.len(), cards.len());
+        assert_eq!(read_to_string(&path)?.matches("#leech").count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tag_leeches_with_frontmatter() -> Fallible<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("Deck.md");
+        write(&path, "---\nname = \"Deck\"\n---\n\nQ: foo\nA: bar\n")?;
+        let cards = parse(&path)?;
+        tag_leeches(&cards)?;
+        assert_eq!(
+            read_to_string(&path)?,
+            "---\nname = \"Deck\"\n---\n\nQ: foo\nA: bar\n#leech\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_tag_moved_card() -> Fallible<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("Deck.md");
+        write(&path, "Q: foo\nA: bar\n")?;
+        let cards = parse(&path)?;
+        write(&path, "Some text.\n\nQ: foo\nA: bar\n")?;
+        assert!(tag_leeches(&cards).is_err());
+        Ok(())
+    }
+}
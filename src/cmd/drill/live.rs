@@ -0,0 +1,160 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A read-only feed of the session's progress, for dashboards and overlays.
+
+use axum::Json;
+use axum::extract::State;
+use axum::extract::ws::Message;
+use axum::extract::ws::WebSocket;
+use axum::extract::ws::WebSocketUpgrade;
+use axum::response::Response;
+use serde::Serialize;
+use tokio::select;
+use tokio::sync::watch::Receiver;
+
+use crate::cmd::drill::get::cards_done;
+use crate::cmd::drill::state::MutableState;
+use crate::cmd::drill::state::Phase;
+use crate::cmd::drill::state::ServerState;
+use crate::fsrs::Grade;
+use crate::types::timestamp::Timestamp;
+
+/// The state of the session, as pushed to dashboards.
+#[derive(Clone, Default, PartialEq, Debug, Serialize)]
+pub struct SessionSnapshot {
+    /// The number of cards done: studied in the study phase, and graded
+    /// otherwise.
+    pub cards_done: usize,
+    pub total_cards: usize,
+    /// The number of the card being shown, counting from one, or `None` once
+    /// the session is finished.
+    pub card_number: Option<usize>,
+    /// Either `study` or `grade`.
+    pub phase: &'static str,
+    /// Whether the current card's answer is shown.
+    pub revealed: bool,
+    pub finished: bool,
+    /// The seconds since the session started, or until it finished.
+    pub elapsed_seconds: i64,
+    /// The average number of seconds per review, once there are reviews.
+    pub seconds_per_review: Option<f64>,
+    /// The number of reviews with each grade.
+    pub grades: GradeCounts,
+    /// The number of times the collection was reloaded. The page refreshes
+    /// when it changes.
+    pub reloads: usize,
+}
+
+#[derive(Clone, Default, PartialEq, Debug, Serialize)]
+pub struct GradeCounts {
+    pub forgot: usize,
+    pub hard: usize,
+    pub good: usize,
+    pub easy: usize,
+}
+
+impl SessionSnapshot {
+    pub fn new(state: &ServerState, mutable: &MutableState) -> Self {
+        let cards_done: usize = cards_done(state, mutable);
+        let finished: bool = mutable.finished_at.is_some() || mutable.cards.is_empty();
+        let until: Timestamp = mutable.finished_at.unwrap_or_else(Timestamp::now);
+        let elapsed_seconds: i64 =
+            (until.into_inner() - state.session_started_at.into_inner()).num_seconds();
+        let reviews: usize = mutable.reviews.len();
+        let seconds_per_review: Option<f64> =
+            (reviews > 0).then(|| elapsed_seconds as f64 / reviews as f64);
+        let mut grades = GradeCounts::default();
+        for review in mutable.reviews.iter() {
+            match review.grade {
+                Grade::Forgot => grades.forgot += 1,
+                Grade::Hard => grades.hard += 1,
+                Grade::Good => grades.good += 1,
+                Grade::Easy => grades.easy += 1,
+            }
+        }
+        Self {
+            cards_done,
+            total_cards: state.total_cards,
+            card_number: (!finished).then_some(cards_done + 1),
+            phase: match mutable.phase {
+                Phase::Study => "study",
+                Phase::Grade => "grade",
+            },
+            revealed: mutable.reveal,
+            finished,
+            elapsed_seconds,
+            seconds_per_review,
+            grades,
+            reloads: mutable.reloads,
+        }
+    }
+}
+
+/// The current snapshot of the session.
+fn current(state: &ServerState) -> SessionSnapshot {
+    let mutable = state.mutable.lock().unwrap();
+    SessionSnapshot::new(state, &mutable)
+}
+
+/// Push the current snapshot to every connected dashboard. Called after each
+/// action, once the session state is committed and its lock released.
+pub fn publish(state: &ServerState) {
+    let snapshot: SessionSnapshot = current(state);
+    state.live.send_replace(snapshot);
+}
+
+pub async fn session_handler(State(state): State<ServerState>) -> Json<SessionSnapshot> {
+    Json(current(&state))
+}
+
+/// Send the current snapshot on connection, and a new one after every action.
+/// Messages from the client are ignored.
+pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<ServerState>) -> Response {
+    let rx: Receiver<SessionSnapshot> = state.live.subscribe();
+    let snapshot: SessionSnapshot = current(&state);
+    ws.on_upgrade(move |socket| feed(socket, rx, snapshot))
+}
+
+async fn feed(mut socket: WebSocket, mut rx: Receiver<SessionSnapshot>, first: SessionSnapshot) {
+    let mut snapshot: SessionSnapshot = first;
+    loop {
+        let text: String = match serde_json::to_string(&snapshot) {
+            Ok(text) => text,
+            Err(e) => {
+                log::error!("error: {e}");
+                return;
+            }
+        };
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            return;
+        }
+        loop {
+            select! {
+                changed = rx.changed() => {
+                    if changed.is_err() {
+                        // The server is shutting down.
+                        return;
+                    }
+                    snapshot = rx.borrow_and_update().clone();
+                    break;
+                }
+                message = socket.recv() => match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+    }
+}
@@ -12,49 +12,86 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod api;
+pub mod backlog;
 mod cache;
+pub mod family;
 mod get;
-mod katex;
+mod group;
+pub mod katex;
+pub mod leech;
+mod live;
+pub mod new_order;
 mod post;
+pub mod reload;
 pub mod server;
+mod session;
+pub mod similarity;
 mod state;
-mod template;
+pub mod template;
+pub mod time_format;
+mod type_in;
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
     use std::fs::create_dir_all;
+    use std::fs::read_to_string;
+    use std::fs::remove_file;
+    use std::fs::write;
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpStream;
+    use std::path::PathBuf;
+    use std::time::Duration;
 
+    use axum::Router;
+    use axum::routing::get;
+    use chrono::TimeDelta;
     use portpicker::pick_unused_port;
     use reqwest::StatusCode;
     use tempfile::tempdir;
+    use tokio::net::TcpListener;
     use tokio::spawn;
+    use tokio::task::spawn_blocking;
+    use tokio::time::sleep;
 
-    use crate::cmd::drill::server::AnswerControls;
     use crate::cmd::drill::server::ServerConfig;
     use crate::cmd::drill::server::start_server;
+    use crate::cmd::drill::template::Theme;
+    use crate::db::Database;
     use crate::error::Fallible;
     use crate::helper::create_tmp_copy_of_test_directory;
+    use crate::helper::create_tmp_directory;
+    use crate::media::remote::BROKEN_IMAGE;
+    use crate::media::remote::REMOTE_CACHE_DIR;
+    use crate::media::remote::remote_key;
+    use crate::types::card::CardContent;
+    use crate::types::card_hash::CardHash;
+    use crate::types::confidence::Confidence;
+    use crate::types::date::Date;
+    use crate::types::performance::Performance;
     use crate::types::timestamp::Timestamp;
     use crate::utils::wait_for_server;
 
     const TEST_HOST: &str = "127.0.0.1";
 
+    fn make_test_config(
+        directory: Option<String>,
+        port: u16,
+        session_started_at: Timestamp,
+    ) -> ServerConfig {
+        ServerConfig {
+            session_started_at,
+            ..ServerConfig::new(directory, TEST_HOST.to_string(), port)
+        }
+    }
+
     #[tokio::test]
     async fn test_start_server_on_non_existent_directory() -> Fallible<()> {
         let port = pick_unused_port().unwrap();
         let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some("./derpherp".to_string()),
-            host: TEST_HOST.to_string(),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-            answer_controls: AnswerControls::Full,
-            bury_siblings: false,
-        };
+        let config = make_test_config(Some("./derpherp".to_string()), port, session_started_at);
         let result = start_server(config).await;
         assert!(result.is_err());
         let err = result.err().unwrap();
@@ -69,18 +106,7 @@ mod tests {
         create_dir_all(&dir)?;
         let session_started_at = Timestamp::now();
         let dir = dir.canonicalize().unwrap().display().to_string();
-        let config = ServerConfig {
-            directory: Some(dir),
-            host: TEST_HOST.to_string(),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-            answer_controls: AnswerControls::Full,
-            bury_siblings: false,
-        };
+        let config = make_test_config(Some(dir), port, session_started_at);
         start_server(config).await?;
         Ok(())
     }
@@ -90,18 +116,7 @@ mod tests {
         let port = pick_unused_port().unwrap();
         let directory = create_tmp_copy_of_test_directory()?;
         let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some(directory),
-            host: TEST_HOST.to_string(),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-            answer_controls: AnswerControls::Full,
-            bury_siblings: false,
-        };
+        let config = make_test_config(Some(directory), port, session_started_at);
         spawn(async move { start_server(config).await });
         wait_for_server(TEST_HOST, port).await?;
 
@@ -118,6 +133,21 @@ mod tests {
             "text/javascript"
         );
 
+        // Hit the `favicon.svg` endpoint.
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/favicon.svg")).await?;
+        assert!(response.status().is_success());
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "image/svg+xml"
+        );
+
+        // Hit the `manifest.json` endpoint.
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/manifest.json")).await?;
+        assert!(response.status().is_success());
+        let manifest: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+        assert_eq!(manifest["name"], "hashcards");
+        assert_eq!(manifest["icons"][0]["src"], "/favicon.svg");
+
         // Hit the not found endpoint.
         let response = reqwest::get(format!("http://{TEST_HOST}:{port}/herp-derp")).await?;
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
@@ -143,6 +173,8 @@ mod tests {
         );
         let html = response.text().await?;
         assert!(html.contains("baz <span class='cloze'>.............</span>"));
+        assert!(html.contains("<title>Deck — 0/2 — hashcards</title>"));
+        assert!(html.contains("<link rel=\"manifest\" href=\"/manifest.json\">"));
 
         // Hit reveal.
         let response = reqwest::Client::new()
@@ -163,6 +195,7 @@ mod tests {
         assert!(response.status().is_success());
         let html = response.text().await?;
         assert!(html.contains("FOO"));
+        assert!(html.contains("<title>Deck — 1/2 — hashcards</title>"));
 
         // Hit reveal.
         let response = reqwest::Client::new()
@@ -183,6 +216,7 @@ mod tests {
         assert!(response.status().is_success());
         let html = response.text().await?;
         assert!(html.contains("Session Completed"));
+        assert!(html.contains("<title>Session Complete — hashcards</title>"));
 
         Ok(())
     }
@@ -192,18 +226,7 @@ mod tests {
         let port = pick_unused_port().unwrap();
         let directory = create_tmp_copy_of_test_directory()?;
         let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some(directory),
-            host: TEST_HOST.to_string(),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-            answer_controls: AnswerControls::Full,
-            bury_siblings: false,
-        };
+        let config = make_test_config(Some(directory), port, session_started_at);
         spawn(async move { start_server(config).await });
         wait_for_server(TEST_HOST, port).await?;
 
@@ -223,7 +246,7 @@ mod tests {
             .await?;
         assert!(response.status().is_success());
 
-        // Hit undo.
+        // Hit undo: the card is back, revealed as it was.
         let response = reqwest::Client::new()
             .post(format!("http://{TEST_HOST}:{port}/"))
             .form(&[("action", "Undo")])
@@ -231,7 +254,7 @@ mod tests {
             .await?;
         assert!(response.status().is_success());
         let html = response.text().await?;
-        assert!(html.contains("baz <span class='cloze'>.............</span>"));
+        assert!(html.contains("baz <span class='cloze-reveal'>quux</span>"));
 
         Ok(())
     }
@@ -241,18 +264,7 @@ mod tests {
         let port = pick_unused_port().unwrap();
         let directory = create_tmp_copy_of_test_directory()?;
         let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some(directory),
-            host: TEST_HOST.to_string(),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-            answer_controls: AnswerControls::Full,
-            bury_siblings: false,
-        };
+        let config = make_test_config(Some(directory), port, session_started_at);
         spawn(async move { start_server(config).await });
         wait_for_server(TEST_HOST, port).await?;
 
@@ -272,18 +284,7 @@ mod tests {
         let port = pick_unused_port().unwrap();
         let directory = create_tmp_copy_of_test_directory()?;
         let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some(directory),
-            host: TEST_HOST.to_string(),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-            answer_controls: AnswerControls::Full,
-            bury_siblings: false,
-        };
+        let config = make_test_config(Some(directory), port, session_started_at);
         spawn(async move { start_server(config).await });
         wait_for_server(TEST_HOST, port).await?;
 
@@ -303,18 +304,7 @@ mod tests {
         let port = pick_unused_port().unwrap();
         let directory = create_tmp_copy_of_test_directory()?;
         let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some(directory),
-            host: TEST_HOST.to_string(),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-            answer_controls: AnswerControls::Full,
-            bury_siblings: false,
-        };
+        let config = make_test_config(Some(directory), port, session_started_at);
         spawn(async move { start_server(config).await });
         wait_for_server(TEST_HOST, port).await?;
 
@@ -334,7 +324,7 @@ mod tests {
             .await?;
         assert!(response.status().is_success());
 
-        // Hit undo.
+        // Hit undo: the card is back, revealed as it was.
         let response = reqwest::Client::new()
             .post(format!("http://{TEST_HOST}:{port}/"))
             .form(&[("action", "Undo")])
@@ -342,7 +332,7 @@ mod tests {
             .await?;
         assert!(response.status().is_success());
         let html = response.text().await?;
-        assert!(html.contains("baz <span class='cloze'>.............</span>"));
+        assert!(html.contains("baz <span class='cloze-reveal'>quux</span>"));
 
         Ok(())
     }
@@ -352,18 +342,7 @@ mod tests {
         let port = pick_unused_port().unwrap();
         let directory = create_tmp_copy_of_test_directory()?;
         let session_started_at = Timestamp::now();
-        let config = ServerConfig {
-            directory: Some(directory),
-            host: TEST_HOST.to_string(),
-            port,
-            session_started_at,
-            card_limit: None,
-            new_card_limit: None,
-            deck_filter: None,
-            shuffle: false,
-            answer_controls: AnswerControls::Full,
-            bury_siblings: false,
-        };
+        let config = make_test_config(Some(directory), port, session_started_at);
         spawn(async move { start_server(config).await });
         wait_for_server(TEST_HOST, port).await?;
 
@@ -379,4 +358,1743 @@ mod tests {
 
         Ok(())
     }
+
+    /// A corrupt store stops the server, unless it runs with `--no-store`: then
+    /// every card is new, and the store is left as it is.
+    #[tokio::test]
+    async fn test_no_store() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let store = PathBuf::from(&directory).join("hashcards.db");
+        let garbage = "this is not a database, but it is long enough to look like one";
+        write(&store, garbage)?;
+
+        let config = make_test_config(Some(directory.clone()), port, Timestamp::now());
+        let error = start_server(config).await.err().unwrap().to_string();
+        assert!(error.contains("the file is corrupt, or is not a database."));
+        assert!(error.contains("hashcards drill --no-store"));
+
+        let config = ServerConfig {
+            no_store: true,
+            ..make_test_config(Some(directory), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let mut html = String::new();
+        for action in ["Reveal", "Good", "Reveal", "Good"] {
+            let response = reqwest::Client::new()
+                .post(format!("http://{TEST_HOST}:{port}/"))
+                .form(&[("action", action)])
+                .send()
+                .await?;
+            assert!(response.status().is_success());
+            html = response.text().await?;
+        }
+        assert!(html.contains("Session Completed"));
+        assert!(html.contains("Reviews were not saved"));
+        assert_eq!(read_to_string(&store)?, garbage);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auto_advance_script() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let config = ServerConfig {
+            auto_advance: Some(250),
+            auto_reveal: Some(5),
+            ..make_test_config(Some(directory), port, session_started_at)
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/script.js")).await?;
+        assert!(response.status().is_success());
+        let script = response.text().await?;
+        assert!(script.contains("const AUTO_ADVANCE_MS = 250;"));
+        assert!(script.contains("const AUTO_REVEAL_MS = 5000;"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_action() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let config = ServerConfig {
+            no_reload: true,
+            ..make_test_config(Some(directory), port, session_started_at)
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/script.js")).await?;
+        let script = response.text().await?;
+        assert!(script.contains("const NO_RELOAD = true;"));
+
+        // Hit reveal.
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/api/action"))
+            .form(&[("action", "Reveal")])
+            .send()
+            .await?;
+        assert!(response.status().is_success());
+        let page: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+        assert_eq!(page["title"], "Deck — 0/2 — hashcards");
+        let html = page["html"].as_str().unwrap_or_default();
+        assert!(html.contains("baz <span class='cloze-reveal'>quux</span>"));
+
+        // Hit 'Good'.
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/api/action"))
+            .form(&[("action", "Good")])
+            .send()
+            .await?;
+        assert!(response.status().is_success());
+        let page: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+        assert_eq!(page["title"], "Deck — 1/2 — hashcards");
+        let html = page["html"].as_str().unwrap_or_default();
+        assert!(html.contains("FOO"));
+        assert!(!html.contains("<html"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_session() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let config = make_test_config(Some(directory), port, session_started_at);
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let url = format!("http://{TEST_HOST}:{port}/api/session");
+        let session: serde_json::Value =
+            serde_json::from_str(&reqwest::get(&url).await?.text().await?)?;
+        assert_eq!(session["cards_done"], 0);
+        assert_eq!(session["total_cards"], 2);
+        assert_eq!(session["card_number"], 1);
+        assert_eq!(session["phase"], "grade");
+        assert_eq!(session["finished"], false);
+        assert_eq!(session["seconds_per_review"], serde_json::Value::Null);
+
+        // Grade both cards.
+        for action in ["Reveal", "Good", "Reveal", "Forgot", "Reveal", "Easy"] {
+            reqwest::Client::new()
+                .post(format!("http://{TEST_HOST}:{port}/"))
+                .form(&[("action", action)])
+                .send()
+                .await?;
+        }
+        let session: serde_json::Value =
+            serde_json::from_str(&reqwest::get(&url).await?.text().await?)?;
+        assert_eq!(session["cards_done"], 2);
+        assert_eq!(session["card_number"], serde_json::Value::Null);
+        assert_eq!(session["finished"], true);
+        assert_eq!(session["grades"]["good"], 1);
+        assert_eq!(session["grades"]["forgot"], 1);
+        assert_eq!(session["grades"]["easy"], 1);
+        assert_eq!(session["grades"]["hard"], 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_api_card() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = make_test_config(Some(directory), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let url = format!("http://{TEST_HOST}:{port}/api/card");
+        let state: serde_json::Value =
+            serde_json::from_str(&reqwest::get(&url).await?.text().await?)?;
+        assert_eq!(state["session"]["card_number"], 1);
+        assert_eq!(state["session"]["revealed"], false);
+        assert_eq!(state["card"]["deck_name"], "Deck");
+        assert_eq!(state["card"]["card_type"], "cloze");
+        assert_eq!(state["card"]["back"], serde_json::Value::Null);
+        let front = state["card"]["front"].as_str().unwrap_or_default();
+        assert!(front.contains("<span class='cloze'>"));
+        assert_eq!(
+            state["actions"],
+            serde_json::json!([
+                "Reveal", "Save", "Skip", "Flag", "Suspend", "Discard", "End"
+            ])
+        );
+
+        // Actions are sent like the form sends them.
+        let post = async |form: &[(&str, &str)]| -> Fallible<(StatusCode, serde_json::Value)> {
+            let response = reqwest::Client::new().post(&url).form(form).send().await?;
+            let status = response.status();
+            Ok((status, serde_json::from_str(&response.text().await?)?))
+        };
+        let (status, state) = post(&[("action", "Reveal")]).await?;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(state["session"]["revealed"], true);
+        let back = state["card"]["back"].as_str().unwrap_or_default();
+        assert!(back.contains("baz <span class='cloze-reveal'>quux</span>"));
+        assert_eq!(
+            state["actions"],
+            serde_json::json!([
+                "Forgot", "Hard", "Good", "Easy", "Save", "Skip", "Flag", "Suspend", "Discard",
+                "End"
+            ])
+        );
+
+        // A failed action is reported as an error.
+        let (status, state) = post(&[("action", "Grade"), ("recalled-xyz", "on")]).await?;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(state["error"].as_str().unwrap_or_default().contains("xyz"));
+
+        let (_, state) = post(&[("action", "Good")]).await?;
+        assert_eq!(state["session"]["cards_done"], 1);
+        assert_eq!(state["actions"][0], "Undo");
+        assert_eq!(state["actions"][1], "Lock");
+        post(&[("action", "Reveal")]).await?;
+        let (_, state) = post(&[("action", "Easy")]).await?;
+        assert_eq!(state["session"]["finished"], true);
+        assert_eq!(state["card"], serde_json::Value::Null);
+        assert_eq!(state["actions"], serde_json::json!(["Shutdown"]));
+        Ok(())
+    }
+
+    /// Read a text frame sent by the server, which is never masked.
+    fn read_text_frame(stream: &mut TcpStream) -> Fallible<serde_json::Value> {
+        let mut header = [0u8; 2];
+        stream.read_exact(&mut header)?;
+        assert_eq!(header[0], 0x81);
+        let len: usize = match header[1] & 0x7f {
+            126 => {
+                let mut len = [0u8; 2];
+                stream.read_exact(&mut len)?;
+                u16::from_be_bytes(len) as usize
+            }
+            len => len as usize,
+        };
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload)?;
+        Ok(serde_json::from_slice(&payload)?)
+    }
+
+    #[tokio::test]
+    async fn test_websocket() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let config = make_test_config(Some(directory), port, session_started_at);
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // Connect, and read the snapshot sent on connection.
+        let (stream, first) = spawn_blocking(move || -> Fallible<_> {
+            let mut stream = TcpStream::connect((TEST_HOST, port))?;
+            stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+            write!(
+                stream,
+                "GET /ws HTTP/1.1\r\n\
+                 Host: {TEST_HOST}:{port}\r\n\
+                 Connection: Upgrade\r\n\
+                 Upgrade: websocket\r\n\
+                 Sec-WebSocket-Version: 13\r\n\
+                 Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+            )?;
+            // Read the response headers, up to the blank line.
+            let mut response = Vec::new();
+            let mut byte = [0u8; 1];
+            while !response.ends_with(b"\r\n\r\n") {
+                stream.read_exact(&mut byte)?;
+                response.push(byte[0]);
+            }
+            assert!(response.starts_with(b"HTTP/1.1 101"));
+            let first = read_text_frame(&mut stream)?;
+            Ok((stream, first))
+        })
+        .await
+        .unwrap()?;
+        assert_eq!(first["cards_done"], 0);
+        assert_eq!(first["revealed"], false);
+
+        // Each action pushes a new snapshot.
+        reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Reveal")])
+            .send()
+            .await?;
+        let (mut stream, update) = spawn_blocking(move || -> Fallible<_> {
+            let mut stream = stream;
+            let update = read_text_frame(&mut stream)?;
+            Ok((stream, update))
+        })
+        .await
+        .unwrap()?;
+        assert_eq!(update["cards_done"], 0);
+        assert_eq!(update["revealed"], true);
+
+        reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Good")])
+            .send()
+            .await?;
+        let update = spawn_blocking(move || read_text_frame(&mut stream))
+            .await
+            .unwrap()?;
+        assert_eq!(update["cards_done"], 1);
+        assert_eq!(update["card_number"], 2);
+        assert_eq!(update["grades"]["good"], 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_time_format() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            time_format: "%Y-%m-%d %Q".to_string(),
+            ..make_test_config(Some(directory), port, Timestamp::now())
+        };
+        let result = start_server(config).await;
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "error: invalid time format: '%Y-%m-%d %Q'."
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_leech_suspend_threshold() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            leech_threshold: 4,
+            leech_suspend_threshold: Some(4),
+            ..make_test_config(Some(directory), port, Timestamp::now())
+        };
+        let result = start_server(config).await;
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "error: leech-suspend-threshold must be greater than leech-threshold."
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_time_format() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            time_format: "started %H:%M %z".to_string(),
+            timezone: Some("+05:30".to_string()),
+            ..make_test_config(Some(directory), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        post_action(port, "Reveal").await?;
+        post_action(port, "Good").await?;
+        post_action(port, "Reveal").await?;
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("Session Completed"));
+        assert!(html.contains("started "));
+        assert!(html.contains(" +0530"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_difficulty() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let config = ServerConfig {
+            difficulty: Some(1.5),
+            ..make_test_config(Some(directory), port, session_started_at)
+        };
+        let result = start_server(config).await;
+        assert!(result.is_err());
+        let err = result.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "error: difficulty threshold must be between 0 and 1."
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_difficulty_filter() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        write(
+            PathBuf::from(&directory).join("Hard.md"),
+            "Q: Difficult question\nA: Difficult answer\n#hard",
+        )?;
+        let session_started_at = Timestamp::now();
+        let config = ServerConfig {
+            difficulty: Some(0.5),
+            ..make_test_config(Some(directory), port, session_started_at)
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // Only the card tagged `#hard` is in the session.
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/")).await?;
+        assert!(response.status().is_success());
+        let html = response.text().await?;
+        assert!(html.contains("Difficult question"));
+        assert!(html.contains("<span class=\"tag\">#hard</span>"));
+
+        // Hit reveal.
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Reveal")])
+            .send()
+            .await?;
+        assert!(response.status().is_success());
+
+        // Hit 'Good'.
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Good")])
+            .send()
+            .await?;
+        assert!(response.status().is_success());
+        let html = response.text().await?;
+        assert!(html.contains("Session Completed"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tag_filter() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        write(
+            PathBuf::from(&directory).join("Geology.md"),
+            "---\ntags = [\"geology\"]\n---\n\nQ: Crust\nA: Outer layer\n\nQ: Moho\nA: Boundary\n#hard\n",
+        )?;
+        let session_started_at = Timestamp::now();
+
+        // A tag on no card is an error.
+        let config = ServerConfig {
+            tags: vec!["geolgy".to_string()],
+            ..make_test_config(Some(directory.clone()), port, session_started_at)
+        };
+        let err = start_server(config).await.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "error: No card in the collection is tagged #geolgy."
+        );
+
+        // A filter that leaves no card to drill ends without serving.
+        let config = ServerConfig {
+            tags: vec!["hard".to_string()],
+            exclude_tags: vec!["geology".to_string()],
+            ..make_test_config(Some(directory.clone()), port, session_started_at)
+        };
+        start_server(config).await?;
+        assert!(TcpStream::connect(format!("{TEST_HOST}:{port}")).is_err());
+
+        let config = ServerConfig {
+            tags: vec!["#geology".to_string()],
+            exclude_tags: vec!["hard".to_string()],
+            ..make_test_config(Some(directory), port, session_started_at)
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // Only the deck's untagged card is in the session.
+        let html = reqwest::get(format!("http://{TEST_HOST}:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("Crust"));
+        post_action(port, "Reveal").await?;
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("Session Completed"));
+        Ok(())
+    }
+
+    async fn post_action(port: u16, action: &str) -> Fallible<String> {
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", action)])
+            .send()
+            .await?;
+        assert!(response.status().is_success());
+        Ok(response.text().await?)
+    }
+
+    #[tokio::test]
+    async fn test_end_saves_reviews() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let config = make_test_config(Some(directory.clone()), port, session_started_at);
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        post_action(port, "Reveal").await?;
+        post_action(port, "Good").await?;
+        let html = post_action(port, "End").await?;
+        assert!(html.contains("Session Completed"));
+        // Ending early doesn't count as cards left out by the limits.
+        assert!(!html.contains("Cards Left Due"));
+
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        assert_eq!(db.count_reviews_in_date(Date::today())?, 1);
+        let sessions = db.get_all_sessions()?;
+        assert_eq!(sessions.len(), 1);
+
+        // The reveal time is recorded.
+        let reviews = db.get_reviews_for_session(sessions[0].session_id)?;
+        let review = &reviews[0].data;
+        assert!(
+            review
+                .revealed_at
+                .is_some_and(|t| t.into_inner() <= review.reviewed_at.into_inner())
+        );
+        Ok(())
+    }
+
+    /// The session is capped by the limits, and the completion page says how
+    /// many cards are left due.
+    #[tokio::test]
+    async fn test_cards_left_due() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            new_card_limit: Some(1),
+            ..make_test_config(Some(directory), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let html = post_action(port, "Reveal").await?;
+        assert!(html.contains("0/1"));
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("Session Completed"));
+        assert!(html.contains("<td class=\"key\">Total Cards</td><td class=\"val\">1</td>"));
+        assert!(html.contains("<td class=\"key\">Cards Left Due</td><td class=\"val\">1</td>"));
+        assert!(html.contains("More cards are due today"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_track_confidence() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            track_confidence: true,
+            ..make_test_config(Some(directory.clone()), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let html = post_action(port, "Reveal").await?;
+        assert!(html.contains("<legend>Confidence</legend>"));
+        reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Good"), ("confidence", "4")])
+            .send()
+            .await?;
+        // The rating is optional.
+        post_action(port, "Reveal").await?;
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("Session Completed"));
+
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        let sessions = db.get_all_sessions()?;
+        let reviews = db.get_reviews_for_session(sessions[0].session_id)?;
+        let confidence: Vec<Option<Confidence>> =
+            reviews.iter().map(|r| r.data.confidence).collect();
+        assert_eq!(confidence, vec![Some(Confidence::new(4)?), None]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_milestones() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            milestones: Some(1),
+            ..make_test_config(Some(directory), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let html = reqwest::get(format!("http://{TEST_HOST}:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("<div class=\"progress-count\">0/2</div>"));
+        assert!(html.contains("<div class=\"milestone\" style=\"inset-inline-start: 50%;\""));
+        assert!(html.contains("progress-fill stage-start"));
+
+        post_action(port, "Reveal").await?;
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("<div class=\"progress-count\">1/2</div>"));
+        assert!(html.contains("<div class=\"milestone reached\""));
+        assert!(html.contains("progress-fill stage-half"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_two_phase() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            two_phase: true,
+            ..make_test_config(Some(directory.clone()), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // The study phase: cards can't be graded, only moved past.
+        let html = post_action(port, "Reveal").await?;
+        assert!(html.contains("<div class=\"phase study\""));
+        assert!(html.contains("value=\"Next\""));
+        assert!(!html.contains("value=\"Good\""));
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("value=\"Next\""));
+        post_action(port, "Next").await?;
+        let html = post_action(port, "Undo").await?;
+        assert!(html.contains("value=\"Next\""));
+        post_action(port, "Reveal").await?;
+        post_action(port, "Next").await?;
+        post_action(port, "Reveal").await?;
+        let html = post_action(port, "Next").await?;
+
+        // The grading phase goes through the same cards.
+        assert!(html.contains("<div class=\"phase grade\""));
+        post_action(port, "Reveal").await?;
+        post_action(port, "Good").await?;
+        post_action(port, "Reveal").await?;
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("Study Phase (seconds)"));
+        assert!(html.contains("Grading Phase (seconds)"));
+
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        assert_eq!(db.count_reviews_in_date(Date::today())?, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_colors() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        write(
+            PathBuf::from(&directory).join("hashcards.toml"),
+            "[colors.decks]\nDeck = \"#e07a5f\"\n",
+        )?;
+        let config = make_test_config(Some(directory), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let html = reqwest::get(format!("http://{TEST_HOST}:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(
+            html.contains("<div class=\"card-header colored\" style=\"--card-color: #e07a5f;\">")
+        );
+        assert!(html.contains("<span class=\"deck-badge\" style=\"background: #e07a5f;\">"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_theme() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            theme: Theme::Dark,
+            ..make_test_config(Some(directory), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let html = reqwest::get(format!("http://{TEST_HOST}:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("data-theme=\"dark\""));
+        assert!(html.contains("id=\"theme-toggle\""));
+
+        // The completion page has the same theme.
+        post_action(port, "Reveal").await?;
+        post_action(port, "Good").await?;
+        post_action(port, "Reveal").await?;
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("Session Completed"));
+        assert!(html.contains("data-theme=\"dark\""));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grade_flash() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        write(
+            PathBuf::from(&directory).join("hashcards.toml"),
+            "[colors.grades]\ngood = \"lime\"\n",
+        )?;
+        let config = make_test_config(Some(directory), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let post = |action: &'static str| {
+            reqwest::Client::new()
+                .post(format!("http://{TEST_HOST}:{port}/"))
+                .form(&[("action", action)])
+                .send()
+        };
+        let html = post("Reveal").await?.text().await?;
+        assert!(!html.contains("grade-flash"));
+        // The page after a grade flashes the grade's color.
+        let html = post("Good").await?.text().await?;
+        assert!(html.contains(
+            "<div class=\"grade-flash good\" aria-hidden=\"true\" style=\"--flash-color: lime;\">"
+        ));
+        // The next action clears it.
+        let html = post("Reveal").await?.text().await?;
+        assert!(!html.contains("grade-flash"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_no_grade_flash() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            no_grade_flash: true,
+            ..make_test_config(Some(directory), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        for action in ["Reveal", "Forgot"] {
+            let html = reqwest::Client::new()
+                .post(format!("http://{TEST_HOST}:{port}/"))
+                .form(&[("action", action)])
+                .send()
+                .await?
+                .text()
+                .await?;
+            assert!(!html.contains("grade-flash"));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grade_intervals() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = make_test_config(Some(directory), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let html = reqwest::get(format!("http://{TEST_HOST}:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(!html.contains("grade-interval"));
+        // Once the answer is shown, each grade button has its interval.
+        let html = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Reveal")])
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert_eq!(html.matches("class=\"grade-interval\"").count(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_type_in() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        write(
+            PathBuf::from(&directory).join("Deck.md"),
+            "T: chat\nA: **Le Chat**\n",
+        )?;
+        let config = ServerConfig {
+            ignore_case: true,
+            ..make_test_config(Some(directory), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // The answer is typed in, rather than revealed.
+        let html = reqwest::get(format!("http://{TEST_HOST}:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("name=\"typed\""));
+        assert!(!html.contains("value=\"Reveal\""));
+        // A typo is close, and suggests "Good", which can be overridden.
+        let html = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Check"), ("typed", "  le cht ")])
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("<div class=\"type-in-result close\">"));
+        assert!(html.contains("<ins class=\"diff-missing\">a</ins>"));
+        assert!(html.contains("id=\"good\" class=\"suggested\""));
+        assert!(html.contains("id=\"forgot\""));
+        // Grading moves on.
+        let html = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Forgot")])
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert!(!html.contains("type-in-result"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rtl_card() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        write(
+            PathBuf::from(&directory).join("hashcards.toml"),
+            "[language]\nlang = \"ar\"\n",
+        )?;
+        write(
+            PathBuf::from(&directory).join("Deck.md"),
+            "Q: ما اسمك؟\nA: اسمي سامي.\n#rtl\n",
+        )?;
+        let config = make_test_config(Some(directory), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let html = reqwest::get(format!("http://{TEST_HOST}:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("<html lang=\"ar\" dir=\"ltr\" data-theme=\"auto\">"));
+        assert!(html.contains("<div class=\"card\" dir=\"rtl\">"));
+        assert!(html.contains("ما اسمك؟"));
+        Ok(())
+    }
+
+    /// The extra notes are only shown once the answer is revealed.
+    #[tokio::test]
+    async fn test_extra() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        write(
+            PathBuf::from(&directory).join("Deck.md"),
+            "Q: What is the capital of France?\nA: Paris.\nE: On the *Seine*.\n",
+        )?;
+        let config = make_test_config(Some(directory), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let html = reqwest::get(format!("http://{TEST_HOST}:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(!html.contains("class=\"extra"));
+        let html = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Reveal")])
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("<div class=\"extra rich-text\"><p>On the <em>Seine</em>.</p>"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reload() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = make_test_config(Some(directory.clone()), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // Remove the card queued after the current card, and add a new card.
+        let html = reqwest::get(format!("http://{TEST_HOST}:{port}/"))
+            .await?
+            .text()
+            .await?;
+        let (current, deck) = if html.contains("FOO") {
+            ("FOO", "Q: FOO\nA: BAR\n\nQ: New\nA: Card\n")
+        } else {
+            ("baz", "C: baz [quux]\n\nQ: New\nA: Card\n")
+        };
+        write(PathBuf::from(&directory).join("Deck.md"), deck)?;
+        let html = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/reload"))
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("1 added, 1 removed, "));
+        // Nothing is written to the database during the session.
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        assert_eq!(db.card_hashes()?.len(), 2);
+
+        // The current card can be finished, and the removed card is no longer
+        // queued, so that ends the session.
+        let html = post_action(port, "Reveal").await?;
+        assert!(html.contains(current));
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("Session Completed"));
+
+        // The new card is in the database, to be drilled next time.
+        assert_eq!(db.card_hashes()?.len(), 3);
+        assert_eq!(db.due_today(Date::today())?.len(), 2);
+        Ok(())
+    }
+
+    /// With `--no-store`, reloading doesn't create or write the database.
+    #[tokio::test]
+    async fn test_reload_no_store() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            no_store: true,
+            ..make_test_config(Some(directory.clone()), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let deck = PathBuf::from(&directory).join("Deck.md");
+        let text = read_to_string(&deck)?;
+        write(&deck, format!("{text}\nQ: New\nA: Card\n"))?;
+        let html = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/reload"))
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("1 added, 0 removed, "));
+        post_action(port, "End").await?;
+        assert!(!PathBuf::from(&directory).join("hashcards.db").exists());
+        Ok(())
+    }
+
+    /// With `--watch`, saving a deck file reloads the collection.
+    #[tokio::test]
+    async fn test_watch() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            watch: true,
+            ..make_test_config(Some(directory.clone()), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let script = reqwest::get(format!("http://{TEST_HOST}:{port}/script.js"))
+            .await?
+            .text()
+            .await?;
+        assert!(script.contains("const WATCH = true;"));
+
+        let deck = PathBuf::from(&directory).join("Deck.md");
+        let url = format!("http://{TEST_HOST}:{port}/api/card");
+        let reloaded = async |reloads: usize| -> Fallible<serde_json::Value> {
+            for _ in 0..50 {
+                let state: serde_json::Value =
+                    serde_json::from_str(&reqwest::get(&url).await?.text().await?)?;
+                if state["session"]["reloads"] == reloads {
+                    return Ok(state);
+                }
+                sleep(Duration::from_millis(100)).await;
+            }
+            panic!("The collection was not reloaded.");
+        };
+        post_action(port, "Reveal").await?;
+
+        // Files that are not part of the collection are not watched.
+        let root = PathBuf::from(&directory);
+        std::fs::create_dir_all(root.join(".git"))?;
+        write(root.join(".git").join("Notes.md"), "Q: Git\nA: Notes\n")?;
+        write(root.join(".hashcards-cache.json"), "{}")?;
+        write(root.join("hashcards.db-journal"), "")?;
+        sleep(Duration::from_millis(1500)).await;
+        let state: serde_json::Value =
+            serde_json::from_str(&reqwest::get(&url).await?.text().await?)?;
+        assert_eq!(state["session"]["reloads"], 0);
+
+        // Tagging the current card keeps its answer revealed.
+        write(&deck, "Q: FOO\nA: BAR\n\nC: baz [quux]\n#geo\n")?;
+        let state = reloaded(1).await?;
+        assert_eq!(state["card"]["tags"], serde_json::json!(["geo"]));
+        assert_eq!(state["session"]["revealed"], true);
+
+        // Removing the current card moves on to the next one.
+        write(&deck, "Q: FOO\nA: BAR\n")?;
+        let state = reloaded(2).await?;
+        assert_eq!(state["card"]["card_type"], "basic");
+        assert_eq!(state["session"]["revealed"], false);
+
+        // Removing the last card finishes the session.
+        write(&deck, "")?;
+        let state = reloaded(3).await?;
+        assert_eq!(state["session"]["finished"], true);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_shown() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = ServerConfig {
+            max_shown: Some(2),
+            ..make_test_config(Some(directory.clone()), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // Each card is forgotten twice, after which it is not repeated.
+        for _ in 0..3 {
+            post_action(port, "Reveal").await?;
+            post_action(port, "Forgot").await?;
+        }
+        // Undoing the last review puts the card back, without requeueing it.
+        post_action(port, "Undo").await?;
+        post_action(port, "Reveal").await?;
+        post_action(port, "Forgot").await?;
+        post_action(port, "Reveal").await?;
+        let html = post_action(port, "Forgot").await?;
+        assert!(html.contains("Session Completed"));
+
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        let sessions = db.get_all_sessions()?;
+        let reviews = db.get_reviews_for_session(sessions[0].session_id)?;
+        assert_eq!(reviews.len(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_discard_does_not_save_reviews() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let config = make_test_config(Some(directory.clone()), port, session_started_at);
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        post_action(port, "Reveal").await?;
+        post_action(port, "Good").await?;
+        let html = post_action(port, "Discard").await?;
+        assert!(html.contains("Session Discarded"));
+        assert!(html.contains("No changes were saved."));
+
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        assert_eq!(db.count_reviews_in_date(Date::today())?, 0);
+        assert!(db.get_all_sessions()?.is_empty());
+        assert_eq!(db.due_today(Date::today())?.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flag_and_suspend() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let config = make_test_config(Some(directory.clone()), port, session_started_at);
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // A flagged card is marked, and can't be flagged again.
+        let html = post_action(port, "Flag").await?;
+        assert!(html.contains("class=\"flagged\""));
+        assert!(html.contains("value=\"Flag\" disabled"));
+
+        // Suspending moves on to the next card, which isn't flagged.
+        let html = post_action(port, "Suspend").await?;
+        assert!(!html.contains("class=\"flagged\""));
+        post_action(port, "Reveal").await?;
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("Session Completed"));
+
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        assert_eq!(db.suspended_cards()?.len(), 1);
+        assert_eq!(db.count_reviews_in_date(Date::today())?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_skip() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = make_test_config(Some(directory.clone()), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // Skipping doesn't advance the progress bar.
+        let html = post_action(port, "Skip").await?;
+        assert!(html.contains("0/2"));
+        assert!(!html.contains("class=\"skip-notice\""));
+        // Once every card is skipped, skipping is disabled.
+        let html = post_action(port, "Skip").await?;
+        assert!(html.contains("class=\"skip-notice\""));
+        assert!(html.contains("Shortcut: s.\" disabled>"));
+        // Undo restores the skip, and redo skips again.
+        let html = post_action(port, "Undo").await?;
+        assert!(!html.contains("class=\"skip-notice\""));
+        assert!(html.contains("value=\"Redo\" title"));
+        let html = post_action(port, "Redo").await?;
+        assert!(html.contains("class=\"skip-notice\""));
+        assert!(html.contains("value=\"Redo\" disabled"));
+        post_action(port, "Undo").await?;
+        for _ in 0..2 {
+            post_action(port, "Reveal").await?;
+            post_action(port, "Good").await?;
+        }
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        assert_eq!(db.count_reviews_in_date(Date::today())?, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let config = make_test_config(Some(directory), port, session_started_at);
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // The front side shows only the questions.
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/list?side=front")).await?;
+        assert!(response.status().is_success());
+        let html = response.text().await?;
+        assert!(html.contains("FOO"));
+        assert!(!html.contains("BAR"));
+        assert!(html.contains("baz <span class='cloze'>.............</span>"));
+
+        // The back side shows only the answers.
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/list?side=back")).await?;
+        assert!(response.status().is_success());
+        let html = response.text().await?;
+        assert!(!html.contains("FOO"));
+        assert!(html.contains("BAR"));
+        assert!(html.contains("baz <span class='cloze-reveal'>quux</span>"));
+
+        // Listing does not affect the session.
+        let html = post_action(port, "Reveal").await?;
+        assert!(html.contains("baz <span class='cloze-reveal'>quux</span>"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_annotated_list() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = make_test_config(Some(directory), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // Forget the cloze card, then remember it, and end the session.
+        post_action(port, "Reveal").await?;
+        post_action(port, "Forgot").await?;
+        post_action(port, "Reveal").await?;
+        post_action(port, "Good").await?;
+        post_action(port, "Reveal").await?;
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("Session Completed"));
+        assert!(html.contains("/list?side=back&amp;annotate=true"));
+
+        // Without annotations, only the cards left are listed: none.
+        let html = reqwest::get(format!("http://{TEST_HOST}:{port}/list"))
+            .await?
+            .text()
+            .await?;
+        assert!(!html.contains("card-preview"));
+
+        // With annotations, every reviewed card is listed with its grades.
+        let html = reqwest::get(format!(
+            "http://{TEST_HOST}:{port}/list?side=back&annotate=true"
+        ))
+        .await?
+        .text()
+        .await?;
+        assert!(html.contains("baz <span class='cloze-reveal'>quux</span>"));
+        assert!(html.contains("BAR"));
+        assert!(html.contains(
+            "<span class=\"grade\">forgot</span> → <span class=\"grade\">good</span> · next review in"
+        ));
+        assert_eq!(html.matches("card-preview-reviews").count(), 2);
+        Ok(())
+    }
+
+    /// Remote media is served from the cache, and a broken image if it can't
+    /// be downloaded.
+    #[tokio::test]
+    async fn test_remote_media() -> Fallible<()> {
+        let origin = pick_unused_port().unwrap();
+        let app = Router::new().route("/a.png", get(|| async { b"image".to_vec() }));
+        let listener = TcpListener::bind(format!("{TEST_HOST}:{origin}")).await?;
+        spawn(async move { axum::serve(listener, app).await });
+        wait_for_server(TEST_HOST, origin).await?;
+
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let image = format!("http://{TEST_HOST}:{origin}/a.png");
+        let missing = format!("http://{TEST_HOST}:{origin}/missing.png");
+        write(
+            PathBuf::from(&directory).join("Deck.md"),
+            format!("Q: ![]({image})\nA: ![]({missing})\n"),
+        )?;
+        let config = make_test_config(Some(directory.clone()), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let html = post_action(port, "Reveal").await?;
+        let image_url = format!("http://localhost:{port}/remote/{}", remote_key(&image));
+        let missing_url = format!("http://localhost:{port}/remote/{}", remote_key(&missing));
+        assert!(html.contains(&image_url));
+        assert!(html.contains(&missing_url));
+
+        let response = reqwest::get(&image_url).await?;
+        assert_eq!(response.headers()["content-type"], "image/png");
+        assert_eq!(response.bytes().await?.as_ref(), b"image");
+        let cached = PathBuf::from(&directory)
+            .join(REMOTE_CACHE_DIR)
+            .join(format!("{}.png", remote_key(&image)));
+        assert!(cached.exists());
+
+        let response = reqwest::get(&missing_url).await?;
+        assert!(response.status().is_success());
+        assert_eq!(response.headers()["content-type"], "image/svg+xml");
+        assert_eq!(response.text().await?, BROKEN_IMAGE);
+        Ok(())
+    }
+
+    /// Start a server on a deck of one card with audio on both sides.
+    async fn start_audio_server(no_autoplay: bool) -> Fallible<u16> {
+        let port = pick_unused_port().unwrap();
+        let directory = tempdir()?.path().to_path_buf();
+        create_dir_all(&directory)?;
+        write(
+            directory.join("Deck.md"),
+            "Q: Listen: ![](front.mp3)\nA: ![](back.mp3) ![](again.mp3)\n",
+        )?;
+        for name in ["front.mp3", "back.mp3", "again.mp3"] {
+            write(directory.join(name), "audio")?;
+        }
+        let config = ServerConfig {
+            no_autoplay,
+            ..make_test_config(
+                Some(directory.display().to_string()),
+                port,
+                Timestamp::now(),
+            )
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+        Ok(port)
+    }
+
+    #[tokio::test]
+    async fn test_autoplay() -> Fallible<()> {
+        let port = start_audio_server(false).await?;
+        let html = reqwest::get(format!("http://{TEST_HOST}:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains(&format!(
+            r#"<audio controls src="http://localhost:{port}/file/front.mp3""#
+        )));
+        assert!(!html.contains("autoplay"));
+        assert!(!html.contains("id=\"replay\""));
+
+        // Only the first clip of the answer plays.
+        let html = post_action(port, "Reveal").await?;
+        assert!(html.contains(&format!(
+            r#"<audio controls src="http://localhost:{port}/file/front.mp3""#
+        )));
+        assert!(html.contains(&format!(
+            r#"<audio id="answer-audio" autoplay controls src="http://localhost:{port}/file/back.mp3""#
+        )));
+        assert_eq!(html.matches("autoplay").count(), 1);
+        assert!(html.contains("id=\"replay\""));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_no_autoplay() -> Fallible<()> {
+        let port = start_audio_server(true).await?;
+        let html = post_action(port, "Reveal").await?;
+        assert!(html.contains(&format!(
+            r#"<audio id="answer-audio" controls src="http://localhost:{port}/file/back.mp3""#
+        )));
+        assert!(!html.contains("autoplay"));
+        assert!(html.contains("id=\"replay\""));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_card_that_fails_to_render() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let dir = tempdir()?;
+        let directory = dir.path().to_path_buf();
+        write(directory.join("Deck.md"), "Q: ![](broken.png)\nA: answer\n")?;
+        write(directory.join("broken.png"), "")?;
+        let session_started_at = Timestamp::now();
+        let config = make_test_config(
+            Some(directory.display().to_string()),
+            port,
+            session_started_at,
+        );
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // Break the media reference after the collection is loaded.
+        remove_file(directory.join("broken.png"))?;
+
+        // The card shows the error instead of the whole page failing.
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/")).await?;
+        assert!(response.status().is_success());
+        let html = response.text().await?;
+        assert!(html.contains("This card failed to render:"));
+        assert!(html.contains("Failed to resolve media path"));
+        assert!(html.contains("id=\"reveal\""));
+
+        // The session continues.
+        let html = post_action(port, "Reveal").await?;
+        assert!(html.contains("This card failed to render:"));
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("Session Completed"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_author_revise() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let mut config = make_test_config(Some(directory.clone()), port, session_started_at);
+        config.author = true;
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // Nothing has been graded yet.
+        let html = post_action(port, "Reveal").await?;
+        assert!(!html.contains("author-panel"));
+
+        // After grading, the card's source and hash are shown.
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("Last card: Deck.md"));
+        assert!(html.contains("<pre>C: baz [quux]</pre>"));
+        let hash = html_hash(&html);
+
+        // Revising appends the card to the revisions file, once.
+        let html = post_action(port, "Revise").await?;
+        assert!(html.contains("value=\"Revise\" disabled"));
+        post_action(port, "Revise").await?;
+        let revisions = read_to_string(PathBuf::from(&directory).join("revisions.txt"))?;
+        assert_eq!(
+            revisions,
+            format!("{hash} Deck.md (lines 4-4)\nC: baz [quux]\n\n")
+        );
+
+        Ok(())
+    }
+
+    /// Extract the card hash shown in the author panel.
+    fn html_hash(html: &str) -> String {
+        let prefix = "<div class=\"author-hash\">";
+        let start = html.find(prefix).unwrap() + prefix.len();
+        let end = start + html[start..].find('<').unwrap();
+        html[start..end].to_string()
+    }
+
+    #[tokio::test]
+    async fn test_port_zero() -> Fallible<()> {
+        let directory = tempdir()?.path().to_path_buf();
+        create_dir_all(&directory)?;
+        write(directory.join("Deck.md"), "Q: ![](foo.jpg)\nA: bar\n")?;
+        write(directory.join("foo.jpg"), "")?;
+        let port_file = directory.join("port");
+        let session_started_at = Timestamp::now();
+        let mut config =
+            make_test_config(Some(directory.display().to_string()), 0, session_started_at);
+        config.port_file = Some(port_file.display().to_string());
+        spawn(async move { start_server(config).await });
+
+        // Wait for the server to write the port it is bound to.
+        let port: u16 = loop {
+            match read_to_string(&port_file).ok().and_then(|s| s.parse().ok()) {
+                Some(port) => break port,
+                None => sleep(Duration::from_millis(1)).await,
+            }
+        };
+        assert_ne!(port, 0);
+        wait_for_server(TEST_HOST, port).await?;
+
+        // Media URLs use the bound port.
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/")).await?;
+        assert!(response.status().is_success());
+        let html = response.text().await?;
+        assert!(html.contains(&format!("http://localhost:{port}/file/foo.jpg")));
+
+        Ok(())
+    }
+
+    /// A busy port falls back to a free one.
+    #[tokio::test]
+    async fn test_busy_port() -> Fallible<()> {
+        let busy = TcpListener::bind(format!("{TEST_HOST}:0")).await?;
+        let busy_port: u16 = busy.local_addr()?.port();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let port_file = PathBuf::from(&directory).join("port");
+        let session_started_at = Timestamp::now();
+        let mut config = make_test_config(Some(directory), busy_port, session_started_at);
+        config.port_file = Some(port_file.display().to_string());
+        spawn(async move { start_server(config).await });
+
+        let port: u16 = loop {
+            match read_to_string(&port_file).ok().and_then(|s| s.parse().ok()) {
+                Some(port) => break port,
+                None => sleep(Duration::from_millis(1)).await,
+            }
+        };
+        assert_ne!(port, busy_port);
+        wait_for_server(TEST_HOST, port).await?;
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/")).await?;
+        assert!(response.status().is_success());
+        drop(busy);
+
+        Ok(())
+    }
+
+    /// Editing a card after locking in reviews, then discarding the session,
+    /// keeps the locked reviews.
+    #[tokio::test]
+    async fn test_edit_after_lock_in() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = make_test_config(Some(directory.clone()), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        post_action(port, "Reveal").await?;
+        post_action(port, "Good").await?;
+        post_action(port, "Lock").await?;
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Save"), ("edit_content", "Q: New\nA: Card")])
+            .send()
+            .await?;
+        assert!(response.text().await?.contains("Card Edited"));
+        let html = post_action(port, "Discard").await?;
+        assert!(html.contains("Session Discarded"));
+
+        // The locked review was saved with its performance when it was
+        // locked in.
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        let sessions = db.get_all_sessions()?;
+        assert_eq!(sessions.len(), 1);
+        let reviews = db.get_reviews_for_session(sessions[0].session_id)?;
+        assert_eq!(reviews.len(), 1);
+        assert!(matches!(
+            db.get_card_performance(reviews[0].data.card_hash)?,
+            Performance::Reviewed(performance) if performance.review_count == 1
+        ));
+        Ok(())
+    }
+
+    /// Editing a card clears the skips, so they can't be undone across the
+    /// edit.
+    #[tokio::test]
+    async fn test_edit_clears_skips() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = make_test_config(Some(directory), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let html = post_action(port, "Skip").await?;
+        assert!(html.contains("value=\"Undo\" title="));
+        let html = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Save"), ("edit_content", "Q: New\nA: Card")])
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("Card Edited"));
+        assert!(html.contains("value=\"Undo\" disabled"));
+        Ok(())
+    }
+
+    /// Cards matched by the card regex can't be edited, since they would be
+    /// rewritten in the card format.
+    #[tokio::test]
+    async fn test_edit_regex_card() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("hashcards.toml"),
+            "card_regex = '(?m)^(?P<q>.+) :: (?P<a>.+)$'\n",
+        )?;
+        std::fs::write(directory.join("notes.md"), "Paris :: France\n")?;
+        let config = make_test_config(
+            Some(directory.display().to_string()),
+            port,
+            Timestamp::now(),
+        );
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let html = post_action(port, "Reveal").await?;
+        assert!(html.contains("This card is matched by card_regex"));
+        assert!(!html.contains("edit-form"));
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Save"), ("edit_content", "Q: Paris\nA: France")])
+            .send()
+            .await?;
+        assert!(!response.text().await?.contains("Card Edited"));
+        let notes = read_to_string(directory.join("notes.md"))?;
+        assert_eq!(notes, "Paris :: France\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_edit_shows_diff() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let config = make_test_config(Some(directory.clone()), port, session_started_at);
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Save"), ("edit_content", "C: baz [qux]")])
+            .send()
+            .await?;
+        assert!(response.status().is_success());
+        let html = response.text().await?;
+        assert!(html.contains("Card Edited"));
+        assert!(html.contains("<div class=\"diff-delete\">- C: baz [quux]</div>"));
+        assert!(html.contains("<div class=\"diff-insert\">+ C: baz [qux]</div>"));
+        assert!(html.contains("Learning progress was reset"));
+        let deck = read_to_string(PathBuf::from(&directory).join("Deck.md"))?;
+        assert!(deck.contains("C: baz [qux]"));
+
+        // The summary is only shown once.
+        let html = post_action(port, "Reveal").await?;
+        assert!(!html.contains("Card Edited"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_small_edit_keeps_progress() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let config = make_test_config(Some(directory.clone()), port, session_started_at);
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        let before: HashSet<CardHash> = db.card_hashes()?;
+
+        // Only punctuation changes.
+        let html = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Save"), ("edit_content", "C: baz [quux].")])
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("Card Edited"));
+        assert!(html.contains("(progress kept)"));
+        assert!(html.contains("Learning progress was kept"));
+        assert!(!html.contains("Learning progress was reset"));
+
+        // The database is only changed when the session ends.
+        assert_eq!(db.card_hashes()?, before);
+        post_action(port, "End").await?;
+        let after: HashSet<CardHash> = db.card_hashes()?;
+        assert_eq!(after.difference(&before).count(), 1);
+        Ok(())
+    }
+
+    /// A card suspended while it is drilled, e.g. from another session, stays
+    /// suspended when an edit keeps its progress.
+    #[tokio::test]
+    async fn test_edit_suspended_card() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let config = make_test_config(Some(directory.clone()), port, Timestamp::now());
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        let before: HashSet<CardHash> = db.card_hashes()?;
+        let old = CardContent::new_cloze("baz quux", 4, 7)?.hash();
+        assert!(before.contains(&old));
+        db.suspend_card(old)?;
+
+        let html = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Save"), ("edit_content", "C: baz [quux].")])
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("(progress kept)"));
+        post_action(port, "End").await?;
+
+        let after: HashSet<CardHash> = db.card_hashes()?;
+        let new: Vec<CardHash> = after.difference(&before).copied().collect();
+        assert_eq!(new.len(), 1);
+        assert_eq!(db.suspended_cards()?, new);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_edit_is_not_saved() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        let session_started_at = Timestamp::now();
+        let config = make_test_config(Some(directory.clone()), port, session_started_at);
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+        let before = read_to_string(PathBuf::from(&directory).join("Deck.md"))?;
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[("action", "Save"), ("edit_content", "Q: foo\nQ: bar")])
+            .send()
+            .await?;
+        let html = response.text().await?;
+        // The form is shown again, with the edit and the error.
+        assert!(html.contains("<div id=\"edit-form\">"));
+        assert!(html.contains("Q: foo\nQ: bar</textarea>"));
+        // The highlight layer is empty until the script fills it, so the
+        // textarea works without JavaScript.
+        assert!(html.contains(
+            "<div class=\"edit-editor\"><pre id=\"edit-highlight\" aria-hidden=\"true\"></pre><textarea id=\"edit-textarea\" name=\"edit_content\""
+        ));
+        assert!(html.contains("New question without answer."));
+        assert!(html.contains("<span class=\"line-number\">Line 2: </span><code>Q: bar</code>"));
+        let after = read_to_string(PathBuf::from(&directory).join("Deck.md"))?;
+        assert_eq!(before, after);
+
+        // The error is only shown once.
+        let html = post_action(port, "Reveal").await?;
+        assert!(html.contains("<div id=\"edit-form\" hidden>"));
+        assert!(!html.contains("The edit was not saved"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_time_limit() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = create_tmp_copy_of_test_directory()?;
+        // The session started 21 minutes ago, so a 20 minute limit has passed.
+        let session_started_at =
+            Timestamp::new(Timestamp::now().into_inner() - TimeDelta::minutes(21));
+        let mut config = make_test_config(Some(directory), port, session_started_at);
+        config.time_limit = Some(20);
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // The countdown is shown, and the current card can still be answered.
+        let response = reqwest::get(format!("http://{TEST_HOST}:{port}/")).await?;
+        let html = response.text().await?;
+        assert!(html.contains("data-remaining=\"0\""));
+        assert!(html.contains("0:00"));
+        let html = post_action(port, "Reveal").await?;
+        assert!(html.contains("id=\"good\""));
+
+        // Grading it ends the session, even though a card is left.
+        let html = post_action(port, "Good").await?;
+        assert!(html.contains("Session Completed"));
+        assert!(html.contains("Time Limit (minutes)"));
+        Ok(())
+    }
+
+    /// The names of the deletion checkboxes in the page.
+    fn deletion_checkboxes(html: &str) -> Vec<String> {
+        html.match_indices("name=\"recalled-")
+            .map(|(start, _)| {
+                let start = start + "name=\"".len();
+                let end = start + html[start..].find('"').unwrap();
+                html[start..end].to_string()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_group_cloze() -> Fallible<()> {
+        let port = pick_unused_port().unwrap();
+        let directory = tempdir()?.path().to_path_buf();
+        create_dir_all(&directory)?;
+        write(
+            directory.join("Deck.md"),
+            "C: [Paris] is the capital of [France].\n",
+        )?;
+        let directory = directory.display().to_string();
+        let config = ServerConfig {
+            group_cloze: true,
+            ..make_test_config(Some(directory.clone()), port, Timestamp::now())
+        };
+        spawn(async move { start_server(config).await });
+        wait_for_server(TEST_HOST, port).await?;
+
+        // Both deletions are hidden on the same card.
+        let html = reqwest::get(format!("http://{TEST_HOST}:{port}/"))
+            .await?
+            .text()
+            .await?;
+        assert_eq!(html.matches("class='cloze'").count(), 2);
+
+        // Each deletion is graded separately: remember the first, forget the
+        // second.
+        let html = post_action(port, "Reveal").await?;
+        let checkboxes = deletion_checkboxes(&html);
+        assert_eq!(checkboxes.len(), 2);
+        let grade = |recalled: &str| {
+            reqwest::Client::new()
+                .post(format!("http://{TEST_HOST}:{port}/"))
+                .form(&[("action", "Grade"), (recalled, "on")])
+                .send()
+        };
+        let html = grade(&checkboxes[0]).await?.text().await?;
+        // The forgotten deletion is repeated on its own.
+        assert_eq!(html.matches("class='cloze'").count(), 1);
+
+        // Undo restores both deletions, revealed.
+        let html = post_action(port, "Undo").await?;
+        assert_eq!(html.matches("class='cloze-reveal'").count(), 2);
+
+        post_action(port, "Reveal").await?;
+        let html = reqwest::Client::new()
+            .post(format!("http://{TEST_HOST}:{port}/"))
+            .form(&[
+                ("action", "Grade"),
+                (checkboxes[0].as_str(), "on"),
+                (checkboxes[1].as_str(), "on"),
+            ])
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert!(html.contains("Session Completed"));
+
+        let db = Database::new(&format!("{directory}/hashcards.db"))?;
+        assert_eq!(db.count_reviews_in_date(Date::today())?, 2);
+        Ok(())
+    }
 }
@@ -0,0 +1,197 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs::metadata;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::path::PathBuf;
+
+use chrono::DateTime;
+use chrono::Local;
+use chrono::NaiveDateTime;
+use chrono::NaiveTime;
+use clap::ValueEnum;
+
+use crate::db::Database;
+use crate::error::Fallible;
+use crate::parser::deck_created;
+use crate::types::card::Card;
+
+/// The order in which new cards are drilled, and picked under the
+/// `--new-card-limit`.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum NewCardOrder {
+    /// The order the cards are loaded in.
+    File,
+    /// The most recently written cards first: by the `created` date in the
+    /// deck's frontmatter, or else by the time the file was last modified.
+    Recent,
+}
+
+impl Display for NewCardOrder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NewCardOrder::File => write!(f, "file"),
+            NewCardOrder::Recent => write!(f, "recent"),
+        }
+    }
+}
+
+/// Reorder the new cards in the deck. Review cards keep their positions, and
+/// new cards are moved among the positions of new cards.
+pub fn order_new_cards(db: &Database, deck: Vec<Card>, order: NewCardOrder) -> Fallible<Vec<Card>> {
+    match order {
+        NewCardOrder::File => Ok(deck),
+        NewCardOrder::Recent => {
+            let mut authored: HashMap<PathBuf, NaiveDateTime> = HashMap::new();
+            let mut new_indices: Vec<usize> = Vec::new();
+            for (index, card) in deck.iter().enumerate() {
+                if db.get_card_performance(card.hash())?.is_new() {
+                    new_indices.push(index);
+                    if !authored.contains_key(card.file_path()) {
+                        let at: NaiveDateTime = authored_at(card.file_path())?;
+                        authored.insert(card.file_path().to_path_buf(), at);
+                    }
+                }
+            }
+            Ok(sort_by_recency(deck, &new_indices, &authored))
+        }
+    }
+}
+
+/// When a deck file was written: the `created` date in its frontmatter, at
+/// midnight, or the time it was last modified, in local time. An invalid
+/// `created` date is warned about and ignored.
+fn authored_at(path: &Path) -> Fallible<NaiveDateTime> {
+    let text: String = read_to_string(path)?;
+    match deck_created(&text) {
+        Ok(Some(created)) => return Ok(created.and_time(NaiveTime::MIN)),
+        Ok(None) => {}
+        Err(e) => log::warn!("{}: {e}", path.display()),
+    }
+    let modified: DateTime<Local> = metadata(path)?.modified()?.into();
+    Ok(modified.naive_local())
+}
+
+/// Sort the cards at the given indices, newest first. Cards written at the
+/// same time keep their relative order.
+fn sort_by_recency(
+    deck: Vec<Card>,
+    indices: &[usize],
+    authored: &HashMap<PathBuf, NaiveDateTime>,
+) -> Vec<Card> {
+    let mut deck: Vec<Option<Card>> = deck.into_iter().map(Some).collect();
+    let mut cards: Vec<Card> = indices.iter().filter_map(|i| deck[*i].take()).collect();
+    cards.sort_by_key(|card| Reverse(authored.get(card.file_path()).copied()));
+    for (index, card) in indices.iter().zip(cards) {
+        deck[*index] = Some(card);
+    }
+    deck.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::fs::write;
+    use std::time::Duration;
+    use std::time::UNIX_EPOCH;
+
+    use chrono::NaiveDate;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::ignore::IgnoreRules;
+    use crate::parser::DEFAULT_DECK_SEPARATOR;
+    use crate::parser::parse_deck;
+    use crate::types::card::CardContent;
+    use crate::types::timestamp::Timestamp;
+
+    fn questions(deck: &[Card]) -> Vec<String> {
+        deck.iter()
+            .map(|card| match card.content() {
+                CardContent::Basic { question, .. } | CardContent::Reversible { question, .. } => {
+                    question.clone()
+                }
+                CardContent::TypeIn { prompt, .. } => prompt.clone(),
+                CardContent::Cloze { text, .. } | CardContent::GroupedCloze { text, .. } => {
+                    text.clone()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_recent() -> Fallible<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        write(
+            path.join("old.md"),
+            "---\ncreated = 2020-01-01\n---\nQ: old\nA: a\n",
+        )?;
+        write(
+            path.join("new.md"),
+            "---\ncreated = 2025-06-01\n---\nQ: new\nA: a\n",
+        )?;
+        // Without a created date, the modification time is used.
+        write(path.join("middle.md"), "Q: middle\nA: a\n")?;
+        let modified = UNIX_EPOCH + Duration::from_secs(1_640_995_200); // 2022-01-01
+        File::options()
+            .write(true)
+            .open(path.join("middle.md"))?
+            .set_modified(modified)?;
+
+        let deck: Vec<Card> =
+            parse_deck(&path, None, &IgnoreRules::default(), DEFAULT_DECK_SEPARATOR)?;
+        let db = Database::new(path.join("db.sqlite3").to_str().unwrap_or_default())?;
+        for card in deck.iter() {
+            db.insert_card(card.hash(), Timestamp::now())?;
+        }
+
+        let ordered = order_new_cards(&db, deck.clone(), NewCardOrder::Recent)?;
+        assert_eq!(questions(&ordered), vec!["new", "middle", "old"]);
+        let unchanged = order_new_cards(&db, deck.clone(), NewCardOrder::File)?;
+        assert_eq!(questions(&unchanged), questions(&deck));
+        Ok(())
+    }
+
+    #[test]
+    fn test_review_cards_keep_positions() -> Fallible<()> {
+        let dir = tempdir()?;
+        let path = dir.path().to_path_buf();
+        write(path.join("a.md"), "Q: a1\nA: a\n\nQ: a2\nA: a\n")?;
+        write(path.join("b.md"), "Q: b\nA: b\n")?;
+        let deck: Vec<Card> =
+            parse_deck(&path, None, &IgnoreRules::default(), DEFAULT_DECK_SEPARATOR)?;
+        let a = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap_or_default();
+        let b = NaiveDate::from_ymd_opt(2021, 1, 1).unwrap_or_default();
+        let authored: HashMap<PathBuf, NaiveDateTime> = HashMap::from([
+            (path.join("a.md"), a.and_time(NaiveTime::MIN)),
+            (path.join("b.md"), b.and_time(NaiveTime::MIN)),
+        ]);
+        // Put the cards from `a` first, and treat the first one as a review.
+        let mut deck = deck;
+        deck.sort_by_key(|card| card.file_path().ends_with("b.md"));
+        let before = questions(&deck);
+        let ordered = sort_by_recency(deck, &[1, 2], &authored);
+        let after = questions(&ordered);
+        assert_eq!(after[0], before[0]);
+        assert_eq!(after[1], "b");
+        assert_eq!(after[2], before[1]);
+        Ok(())
+    }
+}
@@ -12,51 +12,76 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
 use std::path::Path;
 
 use axum::Form;
+use axum::Json;
 use axum::extract::State;
 use axum::response::Redirect;
 use serde::Deserialize;
-
+use serde::Serialize;
+
+use crate::cmd::drill::get::can_undo;
+use crate::cmd::drill::get::render_page;
+use crate::cmd::drill::live::publish;
+use crate::cmd::drill::session::Session;
+use crate::cmd::drill::similarity::match_edited;
+use crate::cmd::drill::state::Edit;
 use crate::cmd::drill::state::MutableState;
-use crate::cmd::drill::state::Review;
+use crate::cmd::drill::state::RejectedEdit;
 use crate::cmd::drill::state::ServerState;
-use crate::db::ReviewRecord;
+use crate::cmd::drill::template::document_title;
+use crate::error::ErrorReport;
 use crate::error::Fallible;
 use crate::error::fail;
 use crate::fsrs::Grade;
 use crate::parser::Parser;
 use crate::types::card::Card;
-use crate::types::card::CardType;
 use crate::types::card_hash::CardHash;
+use crate::types::confidence::Confidence;
 use crate::types::performance::Performance;
-use crate::types::performance::ReviewedPerformance;
-use crate::types::performance::update_performance;
-use crate::types::timestamp::Timestamp;
+
+/// The file, relative to the collection directory, that cards flagged for
+/// revision are appended to.
+pub const REVISIONS_FILE: &str = "revisions.txt";
 
 #[derive(Debug, Deserialize)]
 enum Action {
     Reveal,
+    Check,
+    Next,
     Undo,
+    Redo,
+    Lock,
     End,
+    Discard,
     Forgot,
     Hard,
     Good,
     Easy,
     Shutdown,
     Save,
+    Revise,
+    Flag,
+    Skip,
+    Suspend,
+    Grade,
 }
 
 impl Action {
-    pub fn grade(&self) -> Grade {
+    /// The grade the action gives the card, if it is a grade.
+    pub fn grade(&self) -> Option<Grade> {
         match self {
-            Action::Forgot => Grade::Forgot,
-            Action::Hard => Grade::Hard,
-            Action::Good => Grade::Good,
-            Action::Easy => Grade::Easy,
-            _ => panic!("Action does not correspond to a grade"),
+            Action::Forgot => Some(Grade::Forgot),
+            Action::Hard => Some(Grade::Hard),
+            Action::Good => Some(Grade::Good),
+            Action::Easy => Some(Grade::Easy),
+            _ => None,
         }
     }
 }
@@ -65,57 +90,98 @@ impl Action {
 pub struct FormData {
     action: Action,
     edit_content: Option<String>,
+    /// The answer typed for a type-in card.
+    typed: Option<String>,
+    /// The self-rated confidence, from 1 to [`MAX_CONFIDENCE`](crate::types::confidence::MAX_CONFIDENCE), if tracked and
+    /// chosen. A string, because typed fields can't be flattened.
+    confidence: Option<String>,
+    /// The remaining fields: when grading a cloze group, a `recalled-{hash}`
+    /// checkbox for each deletion that was remembered.
+    #[serde(flatten)]
+    checkboxes: HashMap<String, String>,
 }
 
 pub async fn post_handler(
     State(state): State<ServerState>,
     Form(form): Form<FormData>,
 ) -> Redirect {
-    match action_handler(state, form.action, form.edit_content).await {
+    match action_handler(state.clone(), form).await {
         Ok(_) => {}
         Err(e) => {
             log::error!("error: {e}");
         }
     }
+    publish(&state);
     Redirect::to("/")
 }
 
-async fn action_handler(
-    state: ServerState,
-    action: Action,
-    edit_content: Option<String>,
-) -> Fallible<()> {
+/// The page to show after an action, for the script to swap in without
+/// reloading.
+#[derive(Serialize)]
+pub struct PageUpdate {
+    /// The title of the document.
+    title: String,
+    /// The body of the page, a single element.
+    html: String,
+}
+
+/// Like [`post_handler`], but respond with the next page as JSON, rather than
+/// a redirect. The script uses this with `--no-reload`.
+pub async fn api_action_handler(
+    State(state): State<ServerState>,
+    Form(form): Form<FormData>,
+) -> Json<PageUpdate> {
+    if let Err(e) = action_handler(state.clone(), form).await {
+        log::error!("error: {e}");
+    }
+    publish(&state);
+    let (title, body) = render_page(&state);
+    Json(PageUpdate {
+        title: document_title(&title),
+        html: body.into_string(),
+    })
+}
+
+pub async fn action_handler(state: ServerState, form: FormData) -> Fallible<()> {
+    let FormData {
+        action,
+        edit_content,
+        typed,
+        confidence,
+        checkboxes,
+    } = form;
+    let confidence: Option<Confidence> = if state.track_confidence {
+        parse_confidence(confidence)?
+    } else {
+        None
+    };
     let mut mutable = state.mutable.lock().unwrap();
+    // The edit summary is only shown on the page right after the edit.
+    mutable.last_edit = None;
+    mutable.last_grade = None;
+    mutable.rejected_edit = None;
+    // With no card to show, the session can only end or shut down, or undo
+    // its last review.
+    if mutable.nothing_to_study()
+        && !matches!(action, Action::End | Action::Discard | Action::Shutdown)
+        && !(matches!(action, Action::Undo) && can_undo(&mutable))
+    {
+        return Ok(());
+    }
+    let mut session = Session::new(&state, &mut mutable);
     match action {
-        Action::Reveal => {
-            if !mutable.reveal {
-                mutable.reveal = true;
-            }
-        }
-        Action::Undo => {
-            if !mutable.reviews.is_empty() {
-                let last_review: Review = mutable.reviews.pop().unwrap();
-                if last_review.should_repeat() {
-                    // Remove the card from the back of the queue.
-                    mutable.cards.pop();
-                }
-                let card: Card = last_review.card;
-                let hash: CardHash = card.hash();
-                mutable.cards.insert(0, card);
-                // Restore the performance cache to the value in the database
-                // if it exists.
-                let performance = mutable.db.get_card_performance(hash)?;
-                mutable.cache.update(hash, performance)?;
-                mutable.finished_at = None;
-                mutable.reveal = false;
-            }
-        }
-        Action::End => {
-            finish_session(&mut mutable, &state)?;
-        }
+        Action::Reveal => session.reveal(),
+        Action::Check => session.check(typed.unwrap_or_default()),
+        Action::Next => session.next(),
+        Action::Undo => session.undo()?,
+        Action::Redo => session.redo()?,
+        Action::Lock => session.lock_in()?,
+        Action::End => session.end()?,
+        Action::Discard => session.discard()?,
         Action::Shutdown => {
-            // Only allow shutdown if session is finished
-            if mutable.finished_at.is_some() {
+            // Only allow shutdown if session is finished, or had nothing to
+            // study.
+            if session.is_finished() || session.is_empty() {
                 // Release the lock before sending shutdown signal.
                 drop(mutable);
                 let mut shutdown_tx = state.shutdown_tx.lock().unwrap();
@@ -128,138 +194,257 @@ async fn action_handler(
             }
         }
         Action::Forgot | Action::Hard | Action::Good | Action::Easy => {
-            if mutable.reveal {
-                let reviewed_at: Timestamp = Timestamp::now();
-                let card: Card = mutable.cards.remove(0);
-                let hash: CardHash = card.hash();
-                let grade: Grade = action.grade();
-                let performance: Performance = mutable.cache.get(hash)?;
-                let performance: ReviewedPerformance =
-                    update_performance(performance, grade, reviewed_at);
-                let review = Review {
-                    card: card.clone(),
-                    reviewed_at,
-                    grade,
-                    stability: performance.stability,
-                    difficulty: performance.difficulty,
-                    interval_raw: performance.interval_raw,
-                    interval_days: performance.interval_days,
-                    due_date: performance.due_date,
-                };
-
-                mutable
-                    .cache
-                    .update(hash, Performance::Reviewed(performance))?;
-                if review.should_repeat() {
-                    mutable.cards.push(card.clone());
-                }
-                mutable.reviews.push(review);
-                mutable.reveal = false;
-
-                // Was this the last card?
-                if mutable.cards.is_empty() {
-                    finish_session(&mut mutable, &state)?;
-                }
+            if let Some(grade) = action.grade() {
+                session.grade(grade, confidence)?;
             }
         }
+        Action::Grade => {
+            let recalled: HashSet<CardHash> = recalled_deletions(&checkboxes)?;
+            session.grade_group(&recalled, confidence)?;
+        }
         Action::Save => {
-            if let Some(content) = edit_content {
-                handle_edit(&state, &mut mutable, content)?;
+            // There is no card to edit once the session is finished.
+            if session.current_card().is_some() {
+                if let Some(content) = edit_content {
+                    handle_edit(&state, &mut mutable, content)?;
+                }
+            }
+        }
+        Action::Flag => session.flag(),
+        Action::Skip => session.skip(),
+        Action::Suspend => session.suspend()?,
+        Action::Revise => {
+            if state.author {
+                revise_last_card(&state, &mut mutable)?;
             }
         }
     }
     Ok(())
 }
 
-fn finish_session(mutable: &mut MutableState, state: &ServerState) -> Fallible<()> {
-    log::debug!("Session completed");
-    let session_ended_at = Timestamp::now();
-    let reviews: Vec<Review> = mutable.reviews.clone();
-    let reviews: Vec<ReviewRecord> = reviews.into_iter().map(Review::into_record).collect();
-    mutable
-        .db
-        .save_session(state.session_started_at, session_ended_at, reviews)?;
-    mutable.finished_at = Some(session_ended_at);
-    for (card_hash, performance) in mutable.cache.iter() {
-        mutable
-            .db
-            .update_card_performance(*card_hash, *performance)?;
+/// The hashes of the deletions checked as recalled, when grading a cloze
+/// group.
+fn recalled_deletions(checkboxes: &HashMap<String, String>) -> Fallible<HashSet<CardHash>> {
+    checkboxes
+        .keys()
+        .filter_map(|key| key.strip_prefix("recalled-"))
+        .map(|hash| {
+            CardHash::from_hex(hash)
+                .map_err(|_| ErrorReport::new(format!("Invalid card hash '{hash}' in the form.")))
+        })
+        .collect()
+}
+
+/// Parse the confidence field of the form. An empty or missing field means
+/// no confidence was chosen.
+fn parse_confidence(confidence: Option<String>) -> Fallible<Option<Confidence>> {
+    let Some(confidence) = confidence.filter(|c| !c.is_empty()) else {
+        return Ok(None);
+    };
+    Ok(Some(confidence.parse()?))
+}
+
+/// Append the source of the last graded card to the revisions file, so the
+/// author can fix it after the session.
+fn revise_last_card(state: &ServerState, mutable: &mut MutableState) -> Fallible<()> {
+    let card: Card = match mutable.reviews.last() {
+        Some(review) => review.card.clone(),
+        None => return Ok(()),
+    };
+    if mutable.revised.contains(&card.hash()) {
+        return Ok(());
     }
+    let deck_path = card.relative_file_path(&state.directory)?;
+    let (start, end) = card.range();
+    let entry = format!(
+        "{} {} (lines {}-{})\n{}\n\n",
+        card.hash(),
+        deck_path.display(),
+        start + 1,
+        end + 1,
+        card.to_source_text()
+    );
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(state.directory.join(REVISIONS_FILE))?;
+    file.write_all(entry.as_bytes())?;
+    mutable.revised.insert(card.hash());
+    log::info!("Card sent for revision: {}", card.hash());
     Ok(())
 }
 
-fn handle_edit(_state: &ServerState, mutable: &mut MutableState, content: String) -> Fallible<()> {
+fn handle_edit(state: &ServerState, mutable: &mut MutableState, content: String) -> Fallible<()> {
     let content = content.trim();
-    if content.is_empty() {
-        return fail("Edit content cannot be empty.");
-    }
 
     // Get the current card (don't remove yet - we need it for metadata)
     let card = mutable.cards[0].clone();
+    if card.is_from_regex() {
+        return fail("Cards matched by card_regex can't be edited here: edit them in their file.");
+    }
     let file_path = card.file_path().clone();
     let range = card.range();
     let deck_name = card.deck_name().clone();
 
-    // Validate by parsing the new content
+    // Validate by parsing the new content. If it's invalid, nothing is
+    // written, and the edit form is shown again with the error.
     let parser = Parser::new(deck_name.clone(), file_path.clone());
-    let new_cards = parser.parse(content).map_err(|e| {
-        crate::error::ErrorReport::new(format!("Invalid card syntax: {}", e))
-    })?;
-
-    if new_cards.is_empty() {
-        return fail("Edit resulted in no valid cards.");
-    }
+    let new_cards = match validate_edit(&parser, content) {
+        Ok(new_cards) => new_cards,
+        Err(rejected) => {
+            log::debug!("Rejected edit: {}", rejected.message);
+            mutable.rejected_edit = Some(rejected);
+            return Ok(());
+        }
+    };
 
     // Write changes to file
     write_card_edit(&file_path, range, content)?;
 
+    // The cards the edit replaces: the card, and for cloze cards, its
+    // siblings. Record their performance before they leave the queue.
+    // Siblings that are not due are not in the cache.
+    let mut old_cards: Vec<Card> = vec![card.clone()];
+    if let Some(family_hash) = card.family_hash() {
+        old_cards.extend(
+            mutable
+                .loaded
+                .iter()
+                .filter(|c| c.family_hash() == Some(family_hash) && c.hash() != card.hash())
+                .cloned(),
+        );
+    }
+    let mut old_performance: HashMap<CardHash, Performance> = HashMap::new();
+    for old_card in &old_cards {
+        let hash = old_card.hash();
+        let performance: Performance = match mutable.cache.get(hash) {
+            Ok(performance) => performance,
+            Err(_) => mutable
+                .db
+                .get_card_performance_opt(hash)?
+                .unwrap_or(Performance::New),
+        };
+        old_performance.insert(hash, performance);
+    }
+    // The new cards that replace an old one closely enough to keep its
+    // progress, mapped to the old card.
+    let replaced: HashMap<CardHash, CardHash> = match_edited(
+        &old_cards
+            .iter()
+            .map(|c| c.content().clone())
+            .collect::<Vec<_>>(),
+        &new_cards
+            .iter()
+            .map(|c| c.content().clone())
+            .collect::<Vec<_>>(),
+        state.edit_similarity,
+    )
+    .into_iter()
+    .map(|(old, new)| (new, old))
+    .collect();
+
     // Remove current card from queue
     mutable.cards.remove(0);
     let old_hash = card.hash();
     mutable.cache.remove(old_hash);
 
-    // For cloze cards: remove all sibling cards (same family_hash) from queue
-    if card.card_type() == CardType::Cloze {
-        if let Some(family_hash) = card.family_hash() {
-            // Collect hashes of siblings to remove from cache
-            let sibling_hashes: Vec<CardHash> = mutable
-                .cards
-                .iter()
-                .filter(|c| c.family_hash() == Some(family_hash))
-                .map(|c| c.hash())
-                .collect();
-
-            // Remove siblings from queue
-            mutable.cards.retain(|c| c.family_hash() != Some(family_hash));
+    // For cloze and reversible cards: remove all sibling cards (same
+    // family_hash) from queue
+    if let Some(family_hash) = card.family_hash() {
+        // Collect hashes of siblings to remove from cache
+        let sibling_hashes: Vec<CardHash> = mutable
+            .cards
+            .iter()
+            .chain(mutable.studied.iter())
+            .filter(|c| c.family_hash() == Some(family_hash))
+            .map(|c| c.hash())
+            .collect();
+
+        // Remove siblings from queue
+        mutable
+            .cards
+            .retain(|c| c.family_hash() != Some(family_hash));
+        mutable
+            .studied
+            .retain(|c| c.family_hash() != Some(family_hash));
 
-            // Remove siblings from cache
-            for hash in sibling_hashes {
-                mutable.cache.remove(hash);
-            }
+        // Remove siblings from cache
+        for hash in sibling_hashes {
+            mutable.cache.remove(hash);
         }
     }
 
-    // Add new cards to end of queue with Performance::New
-    let now = Timestamp::now();
+    let new_hashes: Vec<CardHash> = new_cards.iter().map(|c| c.hash()).collect();
+
+    // Add new cards to end of queue. Unchanged cards keep their performance,
+    // and so do cards that replace an old one closely enough, which take over
+    // its review history. The others are new.
+    let mut preserved: Vec<CardHash> = Vec::new();
     for new_card in new_cards {
         let hash = new_card.hash();
-        // Insert into database (ignore error if already exists)
-        let _ = mutable.db.insert_card(hash, now);
+        // The card's history moves with its progress when reviews are next
+        // saved. Until then, its performance is only in the cache.
+        let performance: Option<Performance> = match replaced.get(&hash) {
+            Some(old) => {
+                mutable.transfers.push((*old, hash));
+                old_performance.get(old).copied()
+            }
+            None => old_performance.get(&hash).copied(),
+        };
+        if performance.is_some() {
+            preserved.push(hash);
+        }
         // Insert into cache (ignore error if already exists - shouldn't happen)
-        let _ = mutable.cache.insert(hash, Performance::New);
+        let _ = mutable
+            .cache
+            .insert(hash, performance.unwrap_or(Performance::New));
         mutable.cards.push(new_card);
     }
 
+    mutable.last_edit = Some(Edit {
+        old_source: card.to_source_text(),
+        new_source: content.to_string(),
+        old_hash,
+        new_hashes,
+        preserved,
+    });
+
     // Clear undo history (edits break the undo chain)
     mutable.reviews.clear();
+    mutable.committed = 0;
+    mutable.skips.clear();
+    mutable.redo.clear();
 
     // Reset reveal state
     mutable.reveal = false;
+    mutable.revealed_at = None;
 
     log::info!("Card edited. File: {}", file_path.display());
     Ok(())
 }
 
+/// Parse the text of an edit into cards, or explain why it can't be saved.
+fn validate_edit(parser: &Parser, content: &str) -> Result<Vec<Card>, RejectedEdit> {
+    let reject = |message: String, line_num: Option<usize>| RejectedEdit {
+        content: content.to_string(),
+        message,
+        line_num,
+    };
+    if content.is_empty() {
+        return Err(reject("The card can't be empty.".to_string(), None));
+    }
+    let cards: Vec<Card> = parser
+        .parse(content)
+        .map_err(|e| reject(e.message, Some(e.line_num)))?;
+    if cards.is_empty() {
+        return Err(reject(
+            "No cards found: a card starts with a `Q:` or `C:` line.".to_string(),
+            None,
+        ));
+    }
+    Ok(cards)
+}
+
 fn write_card_edit(file_path: &Path, range: (usize, usize), new_text: &str) -> Fallible<()> {
     let content = fs::read_to_string(file_path)?;
     let lines: Vec<&str> = content.lines().collect();
@@ -302,13 +487,68 @@ fn write_card_edit(file_path: &Path, range: (usize, usize), new_text: &str) -> F
 
 #[cfg(test)]
 mod tests {
+    use std::path::PathBuf;
+
     use super::*;
 
     #[test]
     fn test_action_grade() {
-        assert_eq!(Action::Forgot.grade(), Grade::Forgot);
-        assert_eq!(Action::Hard.grade(), Grade::Hard);
-        assert_eq!(Action::Good.grade(), Grade::Good);
-        assert_eq!(Action::Easy.grade(), Grade::Easy);
+        assert_eq!(Action::Forgot.grade(), Some(Grade::Forgot));
+        assert_eq!(Action::Hard.grade(), Some(Grade::Hard));
+        assert_eq!(Action::Good.grade(), Some(Grade::Good));
+        assert_eq!(Action::Easy.grade(), Some(Grade::Easy));
+        assert_eq!(Action::Reveal.grade(), None);
+        assert_eq!(Action::Skip.grade(), None);
+    }
+
+    #[test]
+    fn test_parse_confidence() -> Fallible<()> {
+        assert_eq!(parse_confidence(None)?, None);
+        assert_eq!(parse_confidence(Some(String::new()))?, None);
+        assert_eq!(
+            parse_confidence(Some("3".to_string()))?,
+            Some(Confidence::new(3)?)
+        );
+        assert!(parse_confidence(Some("0".to_string())).is_err());
+        assert!(parse_confidence(Some("6".to_string())).is_err());
+        assert!(parse_confidence(Some("high".to_string())).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_edit() {
+        let parser = Parser::new("Deck".to_string(), PathBuf::from("Deck.md"));
+        let cards = validate_edit(&parser, "Q: foo\nA: bar").unwrap();
+        assert_eq!(cards.len(), 1);
+        let cards = validate_edit(&parser, "C: [foo] and [bar]").unwrap();
+        assert_eq!(cards.len(), 2);
+    }
+
+    #[test]
+    fn test_validate_malformed_edits() {
+        let parser = Parser::new("Deck".to_string(), PathBuf::from("Deck.md"));
+        let cases: Vec<(&str, Option<usize>)> = vec![
+            // Empty.
+            ("", None),
+            // The answer was deleted.
+            ("Q: foo", Some(0)),
+            ("Q: foo\nmore of the question", Some(1)),
+            // The question was deleted.
+            ("A: bar", Some(0)),
+            // Two questions, one answer.
+            ("Q: foo\nQ: baz\nA: bar", Some(1)),
+            // A cloze card without deletions.
+            ("C: foo bar", Some(0)),
+            // Plain text, with no card.
+            ("foo bar", None),
+        ];
+        for (content, line_num) in cases {
+            let Err(rejected) = validate_edit(&parser, content) else {
+                panic!("edit should be rejected: {content:?}");
+            };
+            assert_eq!(rejected.content, content);
+            assert!(!rejected.message.is_empty());
+            assert_eq!(rejected.line_num, line_num, "{content:?}");
+        }
     }
 }
@@ -0,0 +1,251 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::mem::take;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::Html;
+use maud::Markup;
+use maud::html;
+use tokio::time::sleep;
+
+use crate::cmd::drill::live::publish;
+use crate::cmd::drill::session::Session;
+use crate::cmd::drill::state::ServerState;
+use crate::cmd::drill::template::page_template;
+use crate::collection::Collection;
+use crate::collection::CollectionDiff;
+use crate::collection::MACROS_FILE;
+use crate::config::CONFIG_FILE;
+use crate::config::CardRegex;
+use crate::config::CollectionConfig;
+use crate::error::Fallible;
+use crate::ignore::IGNORE_FILE;
+use crate::ignore::IgnoreRules;
+use crate::parse_cache::CacheMode;
+use crate::parser::deck_files;
+use crate::types::card::Card;
+use crate::types::card_hash::CardHash;
+use crate::types::performance::Performance;
+
+/// Reload the collection from disk, and show what changed.
+pub async fn reload_handler(State(state): State<ServerState>) -> (StatusCode, Html<String>) {
+    let html = match reload(&state, false) {
+        Ok(diff) => page_template(
+            "Reloaded",
+            reload_summary(&diff, "/", "Back to the session"),
+            &state.language,
+            state.theme,
+        ),
+        Err(e) => page_template(
+            "Error",
+            html! {
+                div.error {
+                    h1 { "Error" }
+                    p { (e) }
+                }
+            },
+            &state.language,
+            state.theme,
+        ),
+    };
+    publish(&state);
+    (StatusCode::OK, Html(html.into_string()))
+}
+
+/// How often the deck files are checked for changes, with `--watch`.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Reload the collection whenever a deck file is added, removed, or modified,
+/// until the session is finished. The page is refreshed through the live
+/// feed.
+pub async fn watch_collection(state: ServerState) {
+    let mut files: Vec<DeckFile> = watched_files(&state.directory).unwrap_or_default();
+    loop {
+        sleep(WATCH_INTERVAL).await;
+        if state.mutable.lock().unwrap().finished_at.is_some() {
+            return;
+        }
+        // If the settings can't be read, e.g. halfway through an edit, the
+        // files are checked again once they can.
+        let current: Vec<DeckFile> = match watched_files(&state.directory) {
+            Ok(current) => current,
+            Err(e) => {
+                log::debug!("Not watching the collection: {e}");
+                continue;
+            }
+        };
+        if current == files {
+            continue;
+        }
+        files = current;
+        // A deck file that fails to parse, e.g. halfway through an edit, is
+        // reloaded again once it is saved.
+        if let Err(e) = reload(&state, true) {
+            log::error!("error: {e}");
+        }
+        publish(&state);
+    }
+}
+
+/// A deck file, with its modification time and size.
+type DeckFile = (PathBuf, Option<SystemTime>, u64);
+
+/// The files that make up the collection: the deck files the collection is
+/// loaded from, skipping ignored files, and the files with its settings.
+/// Anything else, like the store, the parse cache, or `.git`, is not
+/// watched, so writing to it doesn't reload the collection.
+fn watched_files(directory: &PathBuf) -> Fallible<Vec<DeckFile>> {
+    let config: CollectionConfig = CollectionConfig::load(directory)?;
+    let ignore: IgnoreRules = IgnoreRules::load(directory, &config.ignored_extensions)?;
+    let card_regex: Option<CardRegex> = config.card_regex()?;
+    let mut paths: Vec<PathBuf> = deck_files(directory, &ignore, card_regex.as_ref())?;
+    for name in [CONFIG_FILE, IGNORE_FILE, MACROS_FILE] {
+        paths.push(directory.join(name));
+    }
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            let metadata = path.metadata().ok();
+            let modified: Option<SystemTime> = metadata
+                .as_ref()
+                .and_then(|metadata| metadata.modified().ok());
+            let len: u64 = metadata.map_or(0, |metadata| metadata.len());
+            (path, modified, len)
+        })
+        .collect())
+}
+
+/// Reload the collection, and reconcile the session with it. In the queue,
+/// removed cards are dropped, and changed cards are replaced by their new
+/// versions. Added cards are registered in the cache, and saved with the
+/// session, to be drilled in a later session.
+///
+/// On a manual reload, the current card is left as is, so it can be finished.
+/// When watching, it is reconciled too: if it was removed, the session moves
+/// on to the next card, and otherwise its answer stays as it was, revealed or
+/// not.
+fn reload(state: &ServerState, watching: bool) -> Fallible<CollectionDiff> {
+    let cache: CacheMode = if state.no_cache {
+        CacheMode::Disabled
+    } else {
+        CacheMode::Enabled
+    };
+    let collection = Collection::open(
+        Some(state.directory.display().to_string()),
+        state.store_mode(),
+        cache,
+    )?;
+    let mut mutable = state.mutable.lock().unwrap();
+    let diff = CollectionDiff::new(&mutable.loaded, &collection.cards);
+    for card in &diff.added {
+        let hash: CardHash = card.hash();
+        // A card removed and added back in this session is already cached.
+        if !mutable.cache.contains(hash) {
+            let performance: Performance = mutable
+                .db
+                .get_card_performance_opt(hash)?
+                .unwrap_or(Performance::New);
+            mutable.cache.insert(hash, performance)?;
+        }
+    }
+    mutable.loaded = collection.cards;
+    mutable.reloads += 1;
+    let studied: Vec<Card> = take(&mut mutable.studied);
+    mutable.studied = reconcile(studied, &diff);
+    let session_active = mutable.finished_at.is_none();
+    if session_active && !mutable.cards.is_empty() {
+        if watching {
+            let current: CardHash = mutable.cards[0].hash();
+            let queue: Vec<Card> = take(&mut mutable.cards);
+            mutable.cards = reconcile(queue, &diff);
+            if mutable.cards.first().map(Card::hash) != Some(current) {
+                Session::new(state, &mut mutable).skip_current()?;
+            }
+        } else {
+            let current: Card = mutable.cards.remove(0);
+            let queue: Vec<Card> = take(&mut mutable.cards);
+            mutable.cards = reconcile(queue, &diff);
+            mutable.cards.insert(0, current);
+        }
+    }
+    log::info!(
+        "Collection reloaded: {} added, {} removed, {} changed.",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    );
+    Ok(diff)
+}
+
+/// Drop the removed cards from a queue, and replace the changed ones.
+fn reconcile(queue: Vec<Card>, diff: &CollectionDiff) -> Vec<Card> {
+    let removed: Vec<CardHash> = diff.removed.iter().map(|c| c.hash()).collect();
+    queue
+        .into_iter()
+        .filter(|card| !removed.contains(&card.hash()))
+        .map(|card| {
+            diff.changed
+                .iter()
+                .find(|changed| changed.hash() == card.hash())
+                .cloned()
+                .unwrap_or(card)
+        })
+        .collect()
+}
+
+/// Summarize a reload: the number of added, removed, and changed cards, and
+/// their hashes and locations.
+pub fn reload_summary(diff: &CollectionDiff, back_url: &str, back_label: &str) -> Markup {
+    let sections: [(&str, &[Card]); 3] = [
+        ("Added", &diff.added),
+        ("Removed", &diff.removed),
+        ("Changed", &diff.changed),
+    ];
+    html! {
+        div.browse.reload-summary {
+            h1 { "Collection Reloaded" }
+            @if diff.is_empty() {
+                p { "No cards were added, removed, or changed." }
+            } @else {
+                p {
+                    (diff.added.len()) " added, "
+                    (diff.removed.len()) " removed, "
+                    (diff.changed.len()) " changed. "
+                    "Edited cards count as removed and added, since a card is identified by its content."
+                }
+            }
+            @for (name, cards) in sections {
+                @if !cards.is_empty() {
+                    h2 { (name) }
+                    ul {
+                        @for card in cards {
+                            li {
+                                code { (card.hash()) }
+                                " " (card.deck_name()) " (line " (card.range().0 + 1) ")"
+                            }
+                        }
+                    }
+                }
+            }
+            p {
+                a href=(back_url) { (back_label) }
+            }
+        }
+    }
+}
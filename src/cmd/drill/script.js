@@ -12,7 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-document.addEventListener("DOMContentLoaded", function () {
+document.addEventListener("DOMContentLoaded", initPage);
+
+// Set up the page: render math and code, and start the timers. With
+// `--no-reload`, this runs again every time the page is swapped in place.
+function initPage() {
   // Render inline math
   document.querySelectorAll(".math-inline").forEach(function (element) {
     katex.render(element.textContent, element, {
@@ -29,17 +33,261 @@ document.addEventListener("DOMContentLoaded", function () {
       macros: MACROS,
     });
   });
-  // Initialize syntax highlighting
+  // Highlight the code blocks in languages the server does not know.
   if (typeof hljs !== "undefined") {
-    hljs.highlightAll();
+    document
+      .querySelectorAll("pre code[data-highlight='browser']")
+      .forEach(function (element) {
+        hljs.highlightElement(element);
+      });
   }
   const cardContent = document.querySelector(".card-content");
   if (cardContent) {
     cardContent.style.opacity = "1";
   }
-});
+  // Schedule the automatic reveal, if enabled.
+  const reveal = document.getElementById("reveal");
+  if (reveal && AUTO_REVEAL_MS > 0) {
+    autoRevealTimer = setTimeout(function () {
+      autoRevealTimer = null;
+      reveal.click();
+    }, AUTO_REVEAL_MS);
+  }
+  // Count down the session's time limit, if any.
+  const timer = document.getElementById("timer");
+  if (timer) {
+    startCountdown(timer);
+  }
+  // Defer grade submissions, if auto-advance is enabled.
+  const grades = document.querySelector(".grades");
+  if (grades && AUTO_ADVANCE_MS > 0) {
+    grades.closest("form").addEventListener("submit", deferGrade);
+  }
+  // Send the session controls in place, if enabled. This comes after
+  // `deferGrade`, which takes over deferred grades.
+  if (NO_RELOAD) {
+    for (const controls of document.querySelectorAll(".controls form")) {
+      controls.addEventListener("submit", function (event) {
+        if (!event.defaultPrevented) {
+          event.preventDefault();
+          sendInPlace(event.target, event.submitter);
+        }
+      });
+    }
+  }
+  // Focus the element marked for it, which is not done for swapped pages.
+  const autofocus = document.querySelector("[autofocus]");
+  if (autofocus) {
+    autofocus.focus();
+  }
+  // Highlight the card source in the edit form.
+  const editor = document.querySelector(".edit-editor");
+  if (editor) {
+    startHighlighting(editor);
+  }
+}
+
+// With `--watch`, refresh the page when the collection is reloaded, as told
+// by the live feed. The first message gives the number of reloads so far. The
+// page is not refreshed while the edit form is open, so the edit is not lost.
+if (WATCH) {
+  const scheme = window.location.protocol === "https:" ? "wss:" : "ws:";
+  const socket = new WebSocket(scheme + "//" + window.location.host + "/ws");
+  let reloads = null;
+  socket.addEventListener("message", function (event) {
+    const snapshot = JSON.parse(event.data);
+    const editForm = document.getElementById("edit-form");
+    const editing = editForm && !editForm.hidden;
+    if (reloads !== null && snapshot.reloads !== reloads && !editing) {
+      window.location.reload();
+    }
+    reloads = snapshot.reloads;
+  });
+}
+
+// Send a form to `/api/action`, and swap in the page that comes back,
+// keeping the focus on the element with the same ID, if any. If the request
+// fails, reload the page instead: the action may have been applied, so it is
+// not sent again.
+function sendInPlace(form, submitter) {
+  const body = new URLSearchParams(new FormData(form, submitter));
+  const focused = document.activeElement ? document.activeElement.id : "";
+  fetch("/api/action", { method: "POST", body: body })
+    .then(function (response) {
+      if (!response.ok) {
+        throw new Error(response.statusText);
+      }
+      return response.json();
+    })
+    .then(function (page) {
+      cancelPendingTimers();
+      clearInterval(countdownInterval);
+      document.title = page.title;
+      document.body.firstElementChild.outerHTML = page.html;
+      initPage();
+      const element = focused ? document.getElementById(focused) : null;
+      if (element) {
+        element.focus();
+      }
+    })
+    .catch(function () {
+      window.location.assign("/");
+    });
+}
+
+// Pending auto-reveal and auto-advance timers.
+let autoRevealTimer = null;
+let autoAdvanceTimer = null;
+
+// Submit a grade after `AUTO_ADVANCE_MS`, unless cancelled in the meantime.
+function deferGrade(event) {
+  const button = event.submitter;
+  if (!button || !button.closest(".grades")) {
+    return;
+  }
+  event.preventDefault();
+  cancelPendingTimers();
+  const form = event.target;
+  button.classList.add("pending");
+  autoAdvanceTimer = setTimeout(function () {
+    autoAdvanceTimer = null;
+    if (NO_RELOAD) {
+      sendInPlace(form, button);
+      return;
+    }
+    // `submit()` does not include the submitter's value, nor does it fire
+    // the `submit` event again, so we add the action by hand.
+    const action = document.createElement("input");
+    action.type = "hidden";
+    action.name = "action";
+    action.value = button.value;
+    form.appendChild(action);
+    form.submit();
+  }, AUTO_ADVANCE_MS);
+}
+
+// Cancel any pending auto-reveal or auto-advance.
+function cancelPendingTimers() {
+  if (autoRevealTimer !== null) {
+    clearTimeout(autoRevealTimer);
+    autoRevealTimer = null;
+  }
+  if (autoAdvanceTimer !== null) {
+    clearTimeout(autoAdvanceTimer);
+    autoAdvanceTimer = null;
+    document.querySelectorAll(".grades .pending").forEach(function (node) {
+      node.classList.remove("pending");
+    });
+  }
+}
+
+// Update the countdown every second, from the number of seconds left when the
+// page was rendered.
+let countdownInterval = null;
+
+function startCountdown(timer) {
+  clearInterval(countdownInterval);
+  const deadline = Date.now() + parseInt(timer.dataset.remaining, 10) * 1000;
+  const update = function () {
+    const remaining = Math.max(0, Math.ceil((deadline - Date.now()) / 1000));
+    const minutes = Math.floor(remaining / 60);
+    const seconds = String(remaining % 60).padStart(2, "0");
+    timer.textContent = minutes + ":" + seconds;
+    if (remaining === 0) {
+      timer.classList.add("expired");
+      clearInterval(countdownInterval);
+    }
+  };
+  countdownInterval = setInterval(update, 1000);
+  update();
+}
+
+// Draw the highlighted source of the textarea in the layer behind it, as the
+// user types. The textarea is made transparent, and still holds the text that
+// is submitted.
+function startHighlighting(editor) {
+  const textarea = editor.querySelector("textarea");
+  const highlight = editor.querySelector("#edit-highlight");
+  const update = function () {
+    // A trailing newline is not drawn, so the layer would be a line short.
+    highlight.innerHTML = highlightSource(textarea.value) + "\n ";
+    highlight.scrollTop = textarea.scrollTop;
+  };
+  textarea.addEventListener("input", update);
+  textarea.addEventListener("scroll", function () {
+    highlight.scrollTop = textarea.scrollTop;
+  });
+  editor.classList.add("highlighting");
+  update();
+}
+
+function escapeHtml(text) {
+  return text
+    .replaceAll("&", "&amp;")
+    .replaceAll("<", "&lt;")
+    .replaceAll(">", "&gt;");
+}
+
+// Highlight card Markdown as HTML: the `Q:`, `T:`, `R:`, `A:`, and `C:` markers,
+// separators, tag lines, cloze deletions, bold text, and code spans. Every
+// character of the source is kept, so the result lines up with the textarea.
+function highlightSource(source) {
+  return source
+    .split("\n")
+    .map(function (line) {
+      if (/^---\s*$/.test(line)) {
+        return '<span class="hl-separator">' + escapeHtml(line) + "</span>";
+      }
+      if (/^#[^\s#]/.test(line)) {
+        return '<span class="hl-tag">' + escapeHtml(line) + "</span>";
+      }
+      const marker = /^(Q:|T:|R:|A:|C:)/.exec(line);
+      const rest = marker ? line.slice(marker[0].length) : line;
+      const prefix = marker
+        ? '<span class="hl-marker">' + marker[0] + "</span>"
+        : "";
+      return prefix + highlightInline(rest);
+    })
+    .join("\n");
+}
+
+function highlightInline(text) {
+  const pattern = /(`[^`]*`)|(\*\*[^*]+\*\*)|((?<!\\)\[(?:\\.|[^\]\\])*\])/g;
+  let html = "";
+  let last = 0;
+  for (const match of text.matchAll(pattern)) {
+    const kind = match[1] ? "hl-code" : match[2] ? "hl-bold" : "hl-cloze";
+    html += escapeHtml(text.slice(last, match.index));
+    html += '<span class="' + kind + '">' + escapeHtml(match[0]) + "</span>";
+    last = match.index + match[0].length;
+  }
+  return html + escapeHtml(text.slice(last));
+}
 
 // Toggle the edit form visibility
+// Switch between the light and dark themes, and remember the choice in the
+// browser. With the `auto` theme, this switches away from the theme the
+// system prefers.
+function toggleTheme() {
+  const root = document.documentElement;
+  const dark =
+    root.dataset.theme === "dark" ||
+    (root.dataset.theme === "auto" &&
+      window.matchMedia("(prefers-color-scheme: dark)").matches);
+  const theme = dark ? "light" : "dark";
+  root.dataset.theme = theme;
+  localStorage.setItem("theme", theme);
+}
+
+// Play the answer's audio again from the start.
+function replayAudio() {
+  const audio = document.getElementById("answer-audio");
+  if (audio) {
+    audio.currentTime = 0;
+    audio.play();
+  }
+}
+
 function toggleEdit() {
   const editForm = document.getElementById("edit-form");
   if (editForm) {
@@ -55,7 +303,62 @@ function toggleEdit() {
   }
 }
 
+// Take one step through the cards with the flip key: reveal the answer, then
+// go on to the next card. In a drill, the next card is reached by grading the
+// current one "Good"; on a deck page, by showing the next preview.
+function flip() {
+  for (const id of ["reveal", "next", "good"]) {
+    const node = document.getElementById(id);
+    if (node) {
+      node.click();
+      return;
+    }
+  }
+  flipPreview();
+}
+
+// Show the card previews of a deck page one at a time: the question, then the
+// answer, then the next card. After the last card, the full list is shown
+// again.
+function flipPreview() {
+  const browse = document.querySelector(".browse");
+  if (!browse) {
+    return;
+  }
+  const previews = Array.from(browse.querySelectorAll(".card-preview"));
+  const current = browse.querySelector(".card-preview.flip-current");
+  if (current) {
+    // Cloze previews have no separate answer to reveal.
+    if (
+      !current.classList.contains("flip-revealed") &&
+      current.querySelector(".answer")
+    ) {
+      current.classList.add("flip-revealed");
+      return;
+    }
+    current.classList.remove("flip-current", "flip-revealed");
+  }
+  const next = previews[current ? previews.indexOf(current) + 1 : 0];
+  if (!next) {
+    stopFlipping();
+    return;
+  }
+  browse.classList.add("flipping");
+  next.classList.add("flip-current");
+}
+
+function stopFlipping() {
+  const browse = document.querySelector(".browse");
+  browse.classList.remove("flipping");
+  for (const preview of browse.querySelectorAll(".flip-current")) {
+    preview.classList.remove("flip-current", "flip-revealed");
+  }
+}
+
 document.addEventListener("keydown", function (event) {
+  // Any key press puts the user back in control.
+  cancelPendingTimers();
+
   // Skip during text input.
   if (event.target.tagName === "INPUT" && event.target.type === "text") {
     return;
@@ -74,6 +377,22 @@ document.addEventListener("keydown", function (event) {
       toggleEdit();
       return;
     }
+    if (document.querySelector(".browse.flipping")) {
+      event.preventDefault();
+      stopFlipping();
+      return;
+    }
+  }
+
+  // Handle the flip key, if any.
+  if (FLIP_KEY !== null && event.key === FLIP_KEY) {
+    // Ignore modifiers.
+    if (event.shiftKey || event.ctrlKey || event.altKey || event.metaKey) {
+      return;
+    }
+    event.preventDefault();
+    flip();
+    return;
   }
 
   // Handle 'e' key to open edit form
@@ -91,8 +410,10 @@ document.addEventListener("keydown", function (event) {
   }
 
   const keybindings = {
-    " ": "reveal", // Space
+    " ": ["reveal", "next"], // Space
     u: "undo",
+    r: "redo",
+    s: "skip",
     1: "forgot",
     2: "hard",
     3: "good",
@@ -105,10 +426,14 @@ document.addEventListener("keydown", function (event) {
       return;
     }
     event.preventDefault();
-    const id = keybindings[event.key];
-    const node = document.getElementById(id);
-    if (node) {
-      node.click();
+    // A key can be bound to several buttons, of which at most one is shown.
+    const ids = [keybindings[event.key]].flat();
+    for (const id of ids) {
+      const node = document.getElementById(id);
+      if (node) {
+        node.click();
+        break;
+      }
     }
   }
 });
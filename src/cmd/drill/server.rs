@@ -12,12 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::fs::write;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
@@ -32,34 +35,79 @@ use axum::response::Html;
 use axum::routing::get;
 use axum::routing::post;
 use clap::ValueEnum;
-use tokio::net::TcpListener;
+use serde_json::Value;
+use serde_json::json;
 use tokio::select;
 use tokio::signal;
+use tokio::spawn;
 use tokio::sync::oneshot::Receiver;
 use tokio::sync::oneshot::channel;
+use tokio::sync::watch;
 
+use crate::casefold::CaseFolding;
+use crate::cmd::drill::api::api_card_action_handler;
+use crate::cmd::drill::api::api_card_handler;
+use crate::cmd::drill::backlog::BacklogPolicy;
+use crate::cmd::drill::backlog::apply_backlog_policy;
 use crate::cmd::drill::cache::Cache;
+use crate::cmd::drill::family::FamilyPolicy;
+use crate::cmd::drill::family::collapse_families;
 use crate::cmd::drill::get::get_handler;
+use crate::cmd::drill::get::list_handler;
 use crate::cmd::drill::katex::KATEX_CSS_URL;
 use crate::cmd::drill::katex::KATEX_JS_URL;
 use crate::cmd::drill::katex::katex_css_handler;
 use crate::cmd::drill::katex::katex_font_handler;
 use crate::cmd::drill::katex::katex_js_handler;
+use crate::cmd::drill::leech::DEFAULT_LEECH_THRESHOLD;
+use crate::cmd::drill::leech::LeechAction;
+use crate::cmd::drill::live::SessionSnapshot;
+use crate::cmd::drill::live::session_handler;
+use crate::cmd::drill::live::ws_handler;
+use crate::cmd::drill::new_order::NewCardOrder;
+use crate::cmd::drill::new_order::order_new_cards;
+use crate::cmd::drill::post::api_action_handler;
 use crate::cmd::drill::post::post_handler;
+use crate::cmd::drill::reload::reload_handler;
+use crate::cmd::drill::reload::watch_collection;
+use crate::cmd::drill::similarity::DEFAULT_EDIT_SIMILARITY;
 use crate::cmd::drill::state::MutableState;
+use crate::cmd::drill::state::Phase;
 use crate::cmd::drill::state::ServerState;
+use crate::cmd::drill::template::THEME_COLOR;
+use crate::cmd::drill::template::Theme;
+use crate::cmd::drill::time_format::DEFAULT_TIME_FORMAT;
+use crate::cmd::drill::time_format::TimeFormat;
 use crate::collection::Collection;
+use crate::collection::StoreMode;
 use crate::db::Database;
+use crate::error::ErrorReport;
 use crate::error::Fallible;
 use crate::error::fail;
+use crate::markdown::ClozeBlank;
 use crate::media::load::MediaLoader;
+use crate::media::remote::BROKEN_IMAGE;
+use crate::media::remote::REMOTE_CACHE_DIR;
+use crate::media::remote::RemoteCache;
+use crate::parse_cache::CacheMode;
 use crate::rng::TinyRng;
 use crate::rng::shuffle;
+use crate::types::aliases::DeckName;
+use crate::types::aliases::Tag;
 use crate::types::card::Card;
 use crate::types::card_hash::CardHash;
 use crate::types::date::Date;
+use crate::types::performance::IntervalBounds;
+use crate::types::performance::IntervalFuzz;
+use crate::types::retention::Retention;
 use crate::types::timestamp::Timestamp;
 use crate::utils::CACHE_CONTROL_IMMUTABLE;
+use crate::utils::bind_listener;
+use crate::utils::open_browser_when_ready;
+use tokio::sync::oneshot::Sender;
+
+/// The tag used to manually mark cards as difficult.
+const HARD_TAG: &str = "hard";
 
 #[derive(ValueEnum, Clone, Copy, PartialEq)]
 pub enum AnswerControls {
@@ -78,6 +126,24 @@ impl Display for AnswerControls {
     }
 }
 
+/// When the timer of each card starts. It stops when the card is graded.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum CardTimer {
+    /// When the card is shown.
+    Shown,
+    /// When the card's answer is revealed.
+    Revealed,
+}
+
+impl Display for CardTimer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CardTimer::Shown => write!(f, "shown"),
+            CardTimer::Revealed => write!(f, "revealed"),
+        }
+    }
+}
+
 pub struct ServerConfig {
     pub directory: Option<String>,
     pub host: String,
@@ -86,21 +152,247 @@ pub struct ServerConfig {
     pub card_limit: Option<usize>,
     pub new_card_limit: Option<usize>,
     pub deck_filter: Option<String>,
+    /// Only drill cards with one of these tags, if any.
+    pub tags: Vec<Tag>,
+    /// Don't drill cards with any of these tags.
+    pub exclude_tags: Vec<Tag>,
+    /// Only drill cards tagged `#hard` or with retention below this threshold.
+    pub difficulty: Option<f64>,
     pub shuffle: bool,
     pub answer_controls: AnswerControls,
     pub bury_siblings: bool,
+    /// Milliseconds to wait after grading before advancing to the next card.
+    pub auto_advance: Option<u64>,
+    /// Seconds to wait before automatically revealing the answer.
+    pub auto_reveal: Option<u64>,
+    /// Number of well-known cards to start the session with.
+    pub warmup: Option<usize>,
+    /// Maximum number of review (i.e., non-new) cards to drill in a session.
+    pub max_reviews: Option<usize>,
+    /// How to choose the review cards when there are more than `max_reviews`.
+    pub backlog: BacklogPolicy,
+    /// The order in which new cards are drilled.
+    pub new_order: NewCardOrder,
+    /// Show the source of each card after grading, and allow flagging it for
+    /// revision.
+    pub author: bool,
+    /// How to handle siblings due in the same session.
+    pub family: FamilyPolicy,
+    /// Whether to open the browser once the server is up.
+    pub open_browser: bool,
+    /// A file to write the bound port to once the server is up. Useful with
+    /// port 0, where the port is chosen by the operating system.
+    pub port_file: Option<String>,
+    /// A channel to send the bound port to once the server is about to
+    /// accept connections.
+    pub port_sender: Option<Sender<u16>>,
+    /// End the session after this many minutes, once the current card is
+    /// graded.
+    pub time_limit: Option<u64>,
+    /// The minimum review interval, in days.
+    pub min_interval: Option<u64>,
+    /// The maximum review interval, in days.
+    pub max_interval: Option<u64>,
+    /// The largest random change to review intervals, in percent.
+    pub fuzz: Option<f64>,
+    /// The seed for shuffling and interval fuzz. Random if not given.
+    pub seed: Option<u64>,
+    /// Dim the text around cloze deletions until the answer is revealed.
+    pub dim_cloze_context: bool,
+    /// Highlight the syntax of code blocks tagged with a language.
+    pub highlight_code: bool,
+    /// How to draw the blank in place of a hidden cloze deletion.
+    pub cloze_blank: ClozeBlank,
+    /// Show the siblings of a cloze card together, and grade each deletion
+    /// separately.
+    pub group_cloze: bool,
+    /// Ask for a confidence rating with each grade, and record it in the
+    /// review log.
+    pub track_confidence: bool,
+    /// The base URL to serve media from, if media is served separately.
+    pub media_url: Option<String>,
+    /// The number of days after which cached remote media is downloaded
+    /// again. If `None`, it is never downloaded again.
+    pub remote_cache_expiry: Option<u64>,
+    /// Show a milestone tick in the progress bar every this many cards, along
+    /// with the card count.
+    pub milestones: Option<usize>,
+    /// Reveal every card for study first, and grade them in a second pass.
+    pub two_phase: bool,
+    /// The number of times a card can be shown in a session.
+    pub max_shown: Option<usize>,
+    /// What to do with cards that become leeches, if anything.
+    pub leech_action: Option<LeechAction>,
+    /// The number of lapses after which a card is a leech.
+    pub leech_threshold: usize,
+    /// The number of lapses after which a leech is suspended, if any.
+    pub leech_suspend_threshold: Option<usize>,
+    /// Update the page in place after each action, instead of reloading it.
+    pub no_reload: bool,
+    /// Reload the collection when a deck file changes, and refresh the page.
+    pub watch: bool,
+    /// The key that flips through the cards, if any.
+    pub flip_key: Option<String>,
+    /// Do not write to the review store.
+    pub no_store: bool,
+    /// Do not use the parse cache.
+    pub no_cache: bool,
+    /// Do not flash the grade's color after grading.
+    pub no_grade_flash: bool,
+    /// Do not play the answer's audio when it is revealed.
+    pub no_autoplay: bool,
+    /// The color theme of the pages.
+    pub theme: Theme,
+    /// The format of the session's start and end times on the completion page.
+    pub time_format: String,
+    /// The timezone to show the session's start and end times in, as UTC or
+    /// an offset from UTC. By default, the local timezone.
+    pub timezone: Option<String>,
+    /// When the timer of each card starts.
+    pub card_timer: CardTimer,
+    /// The number of reviews that can be undone, if limited.
+    pub undo_depth: Option<usize>,
+    /// Ignore case when checking typed answers.
+    pub ignore_case: bool,
+    /// How to fold case when it is ignored.
+    pub case_folding: CaseFolding,
+    /// How similar an edited card must be to keep its learning progress.
+    pub edit_similarity: f64,
+}
+
+impl ServerConfig {
+    /// A session with every option off or at its default.
+    pub fn new(directory: Option<String>, host: String, port: u16) -> Self {
+        Self {
+            directory,
+            host,
+            port,
+            session_started_at: Timestamp::now(),
+            card_limit: None,
+            new_card_limit: None,
+            deck_filter: None,
+            tags: Vec::new(),
+            exclude_tags: Vec::new(),
+            difficulty: None,
+            shuffle: false,
+            answer_controls: AnswerControls::Full,
+            bury_siblings: false,
+            auto_advance: None,
+            auto_reveal: None,
+            warmup: None,
+            max_reviews: None,
+            backlog: BacklogPolicy::Cap,
+            new_order: NewCardOrder::File,
+            author: false,
+            family: FamilyPolicy::All,
+            open_browser: false,
+            port_file: None,
+            port_sender: None,
+            time_limit: None,
+            min_interval: None,
+            max_interval: None,
+            fuzz: None,
+            seed: None,
+            dim_cloze_context: false,
+            highlight_code: true,
+            cloze_blank: ClozeBlank::default(),
+            group_cloze: false,
+            track_confidence: false,
+            media_url: None,
+            remote_cache_expiry: None,
+            milestones: None,
+            two_phase: false,
+            max_shown: None,
+            leech_action: None,
+            leech_threshold: DEFAULT_LEECH_THRESHOLD,
+            leech_suspend_threshold: None,
+            no_reload: false,
+            watch: false,
+            flip_key: None,
+            no_store: false,
+            no_cache: false,
+            no_grade_flash: false,
+            no_autoplay: false,
+            theme: Theme::Auto,
+            time_format: DEFAULT_TIME_FORMAT.to_string(),
+            timezone: None,
+            card_timer: CardTimer::Shown,
+            undo_depth: None,
+            ignore_case: false,
+            case_folding: CaseFolding::Full,
+            edit_similarity: DEFAULT_EDIT_SIMILARITY,
+        }
+    }
+
+    /// A session started from `browse`: the shuffled cards of a single deck,
+    /// with siblings buried, on a port chosen by the operating system.
+    pub fn for_deck(directory: String, host: String, deck: DeckName) -> Self {
+        Self {
+            deck_filter: Some(deck),
+            shuffle: true,
+            bury_siblings: true,
+            ..Self::new(Some(directory), host, 0)
+        }
+    }
 }
 
 pub async fn start_server(config: ServerConfig) -> Fallible<()> {
+    if let Some(threshold) = config.difficulty {
+        if !(0.0..=1.0).contains(&threshold) {
+            return fail("difficulty threshold must be between 0 and 1.");
+        }
+    }
+    if config.max_shown == Some(0) {
+        return fail("max-shown must be at least 1.");
+    }
+    if config.leech_threshold == 0 {
+        return fail("leech-threshold must be at least 1.");
+    }
+    if let Some(threshold) = config.leech_suspend_threshold {
+        if threshold <= config.leech_threshold {
+            return fail("leech-suspend-threshold must be greater than leech-threshold.");
+        }
+    }
+    if !(0.0..=1.0).contains(&config.edit_similarity) {
+        return fail("edit-similarity must be between 0 and 1.");
+    }
+    let interval_bounds = IntervalBounds::new(config.min_interval, config.max_interval)?;
+    let time_format = TimeFormat::new(&config.time_format, config.timezone.as_deref())?;
+    let seed: u64 = match config.seed {
+        Some(seed) => seed,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64,
+    };
+    let interval_fuzz = IntervalFuzz::new(config.fuzz, seed)?;
+
     let Collection {
         directory,
         db,
         cards,
         macros,
-    } = Collection::new(config.directory)?;
+        config: collection_config,
+    } = Collection::open(
+        config.directory,
+        if config.no_store {
+            StoreMode::NoPersistence
+        } else {
+            StoreMode::ReadWrite
+        },
+        if config.no_cache {
+            CacheMode::Disabled
+        } else {
+            CacheMode::Enabled
+        },
+    )?;
 
     let today: Date = config.session_started_at.date();
 
+    let tags: Vec<Tag> = normalize_tags(config.tags);
+    let exclude_tags: Vec<Tag> = normalize_tags(config.exclude_tags);
+    check_tags_exist(&cards, tags.iter().chain(exclude_tags.iter()))?;
+
     let db_hashes: HashSet<CardHash> = db.card_hashes()?;
     // If a card is in the directory, but not in the DB, it is new. Add it to
     // the database.
@@ -113,19 +405,41 @@ pub async fn start_server(config: ServerConfig) -> Fallible<()> {
     // Find cards due today.
     let due_today: HashSet<CardHash> = db.due_today(today)?;
     let due_today: Vec<Card> = cards
-        .into_iter()
+        .iter()
         .filter(|card| due_today.contains(&card.hash()))
+        .cloned()
         .collect::<Vec<_>>();
 
-    let due_today: Vec<Card> = filter_deck(
-        &db,
-        due_today,
-        config.card_limit,
-        config.new_card_limit,
-        config.deck_filter,
-    )?;
+    let due_today: Vec<Card> = match config.family {
+        FamilyPolicy::All => due_today,
+        FamilyPolicy::OncePerSession => collapse_families(&db, due_today)?,
+    };
+
+    let due_today: Vec<Card> = order_new_cards(&db, due_today, config.new_order)?;
+
+    // Cards from pinned decks take priority under the card limits.
+    let due_today: Vec<Card> = collection_config.pin_cards(due_today);
 
-    let due_today: Vec<Card> = if config.bury_siblings {
+    let due_today: Vec<Card> = filter_tags(due_today, &tags, &exclude_tags);
+
+    let due_today: Vec<Card> = filter_deck(&db, due_today, config.deck_filter, config.difficulty)?;
+
+    // New and review cards are told apart once, here: the limits decide
+    // which cards enter the session, and the rest stay due.
+    let due_count: usize = due_today.len();
+    let due_today: Vec<Card> =
+        limit_cards(&db, due_today, config.card_limit, config.new_card_limit)?;
+
+    let due_today: Vec<Card> = match config.max_reviews {
+        Some(max_reviews) => {
+            apply_backlog_policy(&db, due_today, today, max_reviews, config.backlog)?
+        }
+        None => due_today,
+    };
+    let deferred_cards: usize = due_count - due_today.len();
+
+    // Grouped siblings are shown together, so none of them are buried.
+    let due_today: Vec<Card> = if config.bury_siblings && !config.group_cloze {
         bury_siblings(due_today)
     } else {
         due_today
@@ -138,16 +452,21 @@ pub async fn start_server(config: ServerConfig) -> Fallible<()> {
 
     // Finally, shuffle the cards.
     let due_today: Vec<Card> = if config.shuffle {
-        let seed = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos() as u64;
         let mut rng = TinyRng::from_seed(seed);
         shuffle(due_today, &mut rng)
     } else {
         due_today
     };
 
+    // Drill pinned decks first.
+    let due_today: Vec<Card> = collection_config.pin_cards(due_today);
+
+    // Start with the warm-up cards, if any.
+    let (due_today, warmup_cards): (Vec<Card>, usize) = match config.warmup {
+        Some(count) => warm_up(&db, due_today, count)?,
+        None => (due_today, 0),
+    };
+
     // For all cards due today, fetch their performance from the database and store it in the cache.
     let mut cache = Cache::new();
     for card in due_today.iter() {
@@ -155,42 +474,139 @@ pub async fn start_server(config: ServerConfig) -> Fallible<()> {
         cache.insert(card.hash(), performance)?;
     }
 
+    // Bind first: if the requested port is 0 or busy, the operating system
+    // chooses one, and media URLs must use the chosen port.
+    let listener = bind_listener(&config.host, config.port).await?;
+    let port: u16 = listener.local_addr()?.port();
+    println!("Listening on http://{}:{port}/", config.host);
+    if let Some(port_file) = &config.port_file {
+        write(port_file, port.to_string())?;
+    }
+    if config.open_browser {
+        open_browser_when_ready(config.host.clone(), port);
+    }
+
     // Create shutdown channel
     let (shutdown_tx, shutdown_rx) = channel();
 
+    let remote_cache = Arc::new(RemoteCache::new(
+        directory.join(REMOTE_CACHE_DIR),
+        config
+            .remote_cache_expiry
+            .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+    )?);
     let state = ServerState {
-        port: config.port,
+        port,
         directory,
         macros,
         total_cards: due_today.len(),
+        warmup_cards,
+        deferred_cards,
         session_started_at: config.session_started_at,
         mutable: Arc::new(Mutex::new(MutableState {
             reveal: false,
+            revealed_at: None,
+            shown_at: Timestamp::now(),
             db,
             cache,
             cards: due_today,
             reviews: Vec::new(),
             finished_at: None,
+            discarded: false,
+            revised: HashSet::new(),
+            flagged: Vec::new(),
+            suspended: Vec::new(),
+            transfers: Vec::new(),
+            skips: Vec::new(),
+            redo: Vec::new(),
+            last_edit: None,
+            rejected_edit: None,
+            phase: if config.two_phase {
+                Phase::Study
+            } else {
+                Phase::Grade
+            },
+            studied: Vec::new(),
+            grading_started_at: None,
+            loaded: cards,
+            shown: HashMap::new(),
+            last_grade: None,
+            committed: 0,
+            session_id: None,
+            typed: None,
+            reloads: 0,
         })),
         shutdown_tx: Arc::new(Mutex::new(Some(shutdown_tx))),
         answer_controls: config.answer_controls,
+        auto_advance: config.auto_advance,
+        auto_reveal: config.auto_reveal,
+        author: config.author,
+        time_limit: config.time_limit,
+        interval_bounds,
+        interval_fuzz,
+        dim_cloze_context: config.dim_cloze_context,
+        highlight_code: config.highlight_code,
+        cloze_blank: config.cloze_blank,
+        group_cloze: config.group_cloze,
+        track_confidence: config.track_confidence,
+        media_url: config.media_url,
+        remote_cache,
+        milestones: config.milestones,
+        two_phase: config.two_phase,
+        colors: collection_config.colors,
+        language: collection_config.language,
+        max_shown: config.max_shown,
+        leech_action: config.leech_action,
+        leech_threshold: config.leech_threshold,
+        leech_suspend_threshold: config.leech_suspend_threshold,
+        no_reload: config.no_reload,
+        watch: config.watch,
+        flip_key: config.flip_key,
+        no_store: config.no_store,
+        no_cache: config.no_cache,
+        no_grade_flash: config.no_grade_flash,
+        no_autoplay: config.no_autoplay,
+        theme: config.theme,
+        time_format,
+        card_timer: config.card_timer,
+        undo_depth: config.undo_depth,
+        ignore_case: config.ignore_case,
+        case_folding: config.case_folding,
+        edit_similarity: config.edit_similarity,
+        retention: collection_config.retention,
+        live: Arc::new(watch::Sender::new(SessionSnapshot::default())),
     };
     let app = Router::new();
     let app = app.route("/", get(get_handler));
     let app = app.route("/", post(post_handler));
+    let app = app.route("/api/action", post(api_action_handler));
+    let app = app.route("/api/card", get(api_card_handler));
+    let app = app.route("/api/card", post(api_card_action_handler));
+    let app = app.route("/api/session", get(session_handler));
+    let app = app.route("/ws", get(ws_handler));
+    let app = app.route("/list", get(list_handler));
+    let app = app.route("/reload", post(reload_handler));
     let app = app.route("/script.js", get(script_handler));
     let app = app.route("/style.css", get(style_handler));
+    let app = app.route("/favicon.svg", get(favicon_handler));
+    let app = app.route("/manifest.json", get(manifest_handler));
     let app = app.route(KATEX_CSS_URL, get(katex_css_handler));
     let app = app.route(KATEX_JS_URL, get(katex_js_handler));
     let app = app.route("/katex/fonts/{*path}", get(katex_font_handler));
     let app = app.route("/file/{*path}", get(file_handler));
+    let app = app.route("/remote/{key}", get(remote_handler));
     let app = app.fallback(not_found_handler);
     let app = app.with_state(state.clone());
-    let bind = format!("{}:{}", config.host, config.port);
+
+    if state.watch {
+        spawn(watch_collection(state.clone()));
+    }
+
+    if let Some(port_sender) = config.port_sender {
+        let _ = port_sender.send(port);
+    }
 
     // Start the server with graceful shutdown on Ctrl+C or shutdown button.
-    log::debug!("Starting server on {bind}");
-    let listener = TcpListener::bind(bind).await?;
     axum::serve(listener, app)
         .with_graceful_shutdown(shutdown_signal(shutdown_rx))
         .await?;
@@ -209,16 +625,45 @@ pub async fn start_server(config: ServerConfig) -> Fallible<()> {
 async fn script_handler(
     State(state): State<ServerState>,
 ) -> (StatusCode, [(HeaderName, &'static str); 1], String) {
+    let content = script_content(
+        &state.macros,
+        state.auto_advance,
+        state.auto_reveal,
+        state.no_reload,
+        state.watch,
+        state.flip_key.as_deref(),
+    );
+    (StatusCode::OK, [(CONTENT_TYPE, "text/javascript")], content)
+}
+
+/// Build the page script, prefixed with the collection's TeX macros and the
+/// auto-advance, auto-reveal, no-reload, watch, and flip key settings.
+pub fn script_content(
+    macros: &[(String, String)],
+    auto_advance: Option<u64>,
+    auto_reveal: Option<u64>,
+    no_reload: bool,
+    watch: bool,
+    flip_key: Option<&str>,
+) -> String {
     let mut content = String::new();
     content.push_str("let MACROS = {};\n");
-    for (name, definition) in &state.macros {
+    for (name, definition) in macros {
         let name = escape_js_string_literal(name);
         let definition = escape_js_string_literal(definition);
         content.push_str(&format!("MACROS['{name}'] = '{definition}';\n"));
     }
+    let auto_advance_ms: u64 = auto_advance.unwrap_or(0);
+    let auto_reveal_ms: u64 = auto_reveal.unwrap_or(0).saturating_mul(1000);
+    content.push_str(&format!("const AUTO_ADVANCE_MS = {auto_advance_ms};\n"));
+    content.push_str(&format!("const AUTO_REVEAL_MS = {auto_reveal_ms};\n"));
+    content.push_str(&format!("const NO_RELOAD = {no_reload};\n"));
+    content.push_str(&format!("const WATCH = {watch};\n"));
+    let flip_key: Value = flip_key.map(Value::from).unwrap_or(Value::Null);
+    content.push_str(&format!("const FLIP_KEY = {flip_key};\n"));
     content.push('\n');
     content.push_str(include_str!("script.js"));
-    (StatusCode::OK, [(CONTENT_TYPE, "text/javascript")], content)
+    content
 }
 
 fn escape_js_string_literal(s: &str) -> String {
@@ -227,7 +672,7 @@ fn escape_js_string_literal(s: &str) -> String {
         .replace('$', "\\$")
 }
 
-async fn style_handler() -> (StatusCode, [(HeaderName, &'static str); 2], &'static [u8]) {
+pub async fn style_handler() -> (StatusCode, [(HeaderName, &'static str); 2], &'static [u8]) {
     let bytes = include_bytes!("style.css");
     (
         StatusCode::OK,
@@ -239,7 +684,44 @@ async fn style_handler() -> (StatusCode, [(HeaderName, &'static str); 2], &'stat
     )
 }
 
-async fn not_found_handler() -> (StatusCode, Html<String>) {
+pub async fn favicon_handler() -> (StatusCode, [(HeaderName, &'static str); 2], &'static [u8]) {
+    let bytes = include_bytes!("favicon.svg");
+    (
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, "image/svg+xml"),
+            (CACHE_CONTROL, CACHE_CONTROL_IMMUTABLE),
+        ],
+        bytes,
+    )
+}
+
+/// The web app manifest, so the drill page can be added to a phone's home
+/// screen with a name and icon.
+pub async fn manifest_handler() -> (StatusCode, [(HeaderName, &'static str); 1], String) {
+    let manifest = json!({
+        "name": "hashcards",
+        "short_name": "hashcards",
+        "start_url": "/",
+        "display": "standalone",
+        "background_color": "#ffffff",
+        "theme_color": THEME_COLOR,
+        "icons": [
+            {
+                "src": "/favicon.svg",
+                "sizes": "any",
+                "type": "image/svg+xml",
+            },
+        ],
+    });
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "application/manifest+json")],
+        manifest.to_string(),
+    )
+}
+
+pub async fn not_found_handler() -> (StatusCode, Html<String>) {
     (StatusCode::NOT_FOUND, Html("Not Found".to_string()))
 }
 
@@ -247,8 +729,16 @@ async fn file_handler(
     State(state): State<ServerState>,
     Path(path): Path<String>,
 ) -> (StatusCode, [(HeaderName, &'static str); 1], Vec<u8>) {
-    let loader = MediaLoader::new(state.directory.clone());
-    let validated_path: PathBuf = match loader.validate(&path) {
+    serve_file(state.directory, &path).await
+}
+
+/// Serve a media file from the collection directory.
+pub async fn serve_file(
+    directory: PathBuf,
+    path: &str,
+) -> (StatusCode, [(HeaderName, &'static str); 1], Vec<u8>) {
+    let loader = MediaLoader::new(directory);
+    let validated_path: PathBuf = match loader.validate(path) {
         Ok(p) => p,
         Err(_) => {
             return (
@@ -258,15 +748,60 @@ async fn file_handler(
             );
         }
     };
-    let extension = validated_path
+    let content_type: &str = media_content_type(&validated_path);
+    let content = tokio::fs::read(validated_path).await;
+    match content {
+        Ok(bytes) => (StatusCode::OK, [(CONTENT_TYPE, content_type)], bytes),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain")],
+            b"Internal Server Error".to_vec(),
+        ),
+    }
+}
+
+/// Serve remote media from the cache, downloading it if needed. If it can't
+/// be downloaded, a broken image is served instead, so the card still renders.
+async fn remote_handler(
+    State(state): State<ServerState>,
+    Path(key): Path<String>,
+) -> (StatusCode, [(HeaderName, &'static str); 2], Vec<u8>) {
+    let content = match state.remote_cache.fetch(&key).await {
+        Ok(path) => tokio::fs::read(&path)
+            .await
+            .map(|bytes| (media_content_type(&path), bytes))
+            .map_err(ErrorReport::from),
+        Err(e) => Err(e),
+    };
+    match content {
+        Ok((content_type, bytes)) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, content_type), (CACHE_CONTROL, "no-cache")],
+            bytes,
+        ),
+        Err(e) => {
+            log::error!("error: {e}");
+            (
+                StatusCode::OK,
+                [(CONTENT_TYPE, "image/svg+xml"), (CACHE_CONTROL, "no-store")],
+                BROKEN_IMAGE.as_bytes().to_vec(),
+            )
+        }
+    }
+}
+
+/// The content type of a media file, from its extension.
+fn media_content_type(path: &std::path::Path) -> &'static str {
+    let extension = path
         .extension()
         .and_then(|ext| ext.to_str())
         .unwrap_or("")
         .to_lowercase();
-    let content_type: &str = match extension.as_str() {
+    match extension.as_str() {
         "png" => "image/png",
         "jpg" | "jpeg" => "image/jpeg",
         "gif" => "image/gif",
+        "webp" => "image/webp",
         "svg" => "image/svg+xml",
         "mp3" => "audio/mpeg",
         "wav" => "audio/wav",
@@ -274,15 +809,6 @@ async fn file_handler(
         "mp4" => "video/mp4",
         "webm" => "video/webm",
         _ => "application/octet-stream",
-    };
-    let content = tokio::fs::read(validated_path).await;
-    match content {
-        Ok(bytes) => (StatusCode::OK, [(CONTENT_TYPE, content_type)], bytes),
-        Err(_) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            [(CONTENT_TYPE, "text/plain")],
-            b"Internal Server Error".to_vec(),
-        ),
     }
 }
 
@@ -307,12 +833,38 @@ async fn shutdown_signal(shutdown_rx: Receiver<()>) {
     }
 }
 
+/// Tags given on the command line, without the `#`.
+fn normalize_tags(tags: Vec<String>) -> Vec<Tag> {
+    tags.into_iter()
+        .map(|tag| tag.strip_prefix('#').unwrap_or(&tag).to_string())
+        .collect()
+}
+
+/// Fail if a tag is on no card of the collection, since it is most likely a
+/// typo, and would give an empty session or exclude nothing.
+fn check_tags_exist<'a>(cards: &[Card], tags: impl Iterator<Item = &'a Tag>) -> Fallible<()> {
+    for tag in tags {
+        if !cards.iter().any(|card| card.has_tag(tag)) {
+            return fail(format!("No card in the collection is tagged #{tag}."));
+        }
+    }
+    Ok(())
+}
+
+/// Keep the cards with one of the given tags, if any are given, and none of
+/// the excluded tags.
+fn filter_tags(deck: Vec<Card>, tags: &[Tag], exclude_tags: &[Tag]) -> Vec<Card> {
+    deck.into_iter()
+        .filter(|card| tags.is_empty() || tags.iter().any(|tag| card.has_tag(tag)))
+        .filter(|card| !exclude_tags.iter().any(|tag| card.has_tag(tag)))
+        .collect()
+}
+
 fn filter_deck(
     db: &Database,
     deck: Vec<Card>,
-    card_limit: Option<usize>,
-    new_card_limit: Option<usize>,
     deck_filter: Option<String>,
+    difficulty: Option<f64>,
 ) -> Fallible<Vec<Card>> {
     // Apply the deck filter.
     let deck = match deck_filter {
@@ -323,6 +875,31 @@ fn filter_deck(
         None => deck,
     };
 
+    // Apply the difficulty filter.
+    let deck = match difficulty {
+        Some(threshold) => {
+            let mut result = Vec::new();
+            for card in deck.into_iter() {
+                if is_difficult(db, &card, threshold)? {
+                    result.push(card);
+                }
+            }
+            result
+        }
+        None => deck,
+    };
+
+    Ok(deck)
+}
+
+/// Apply the card limit, then the new card limit. Cards that have never been
+/// reviewed are new.
+fn limit_cards(
+    db: &Database,
+    deck: Vec<Card>,
+    card_limit: Option<usize>,
+    new_card_limit: Option<usize>,
+) -> Fallible<Vec<Card>> {
     // Apply the card limit.
     let deck = match card_limit {
         Some(limit) => deck.into_iter().take(limit).collect(),
@@ -352,6 +929,47 @@ fn filter_deck(
     Ok(deck)
 }
 
+/// A card is difficult if it is tagged as such, or if its measured retention
+/// is below the threshold. Cards without a review history are only difficult
+/// if tagged.
+fn is_difficult(db: &Database, card: &Card, threshold: f64) -> Fallible<bool> {
+    if card.has_tag(HARD_TAG) {
+        return Ok(true);
+    }
+    let retention: Option<Retention> = db.get_retention(card.hash())?;
+    Ok(retention.is_some_and(|r| r.rate() < threshold))
+}
+
+/// Move up to `count` cards with the highest measured retention to the front
+/// of the queue, and return the number of cards moved. Cards that have never
+/// been reviewed are not eligible.
+fn warm_up(db: &Database, deck: Vec<Card>, count: usize) -> Fallible<(Vec<Card>, usize)> {
+    let mut eligible: Vec<(usize, f64)> = Vec::new();
+    for (index, card) in deck.iter().enumerate() {
+        if let Some(retention) = db.get_retention(card.hash())? {
+            eligible.push((index, retention.rate()));
+        }
+    }
+    // Stable sort, so ties keep their queue order.
+    eligible.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let warmup: Vec<usize> = eligible
+        .into_iter()
+        .take(count)
+        .map(|(index, _)| index)
+        .collect();
+    let mut result: Vec<Card> = warmup.iter().map(|index| deck[*index].clone()).collect();
+    result.extend(
+        deck.into_iter()
+            .enumerate()
+            .filter(|(index, _)| !warmup.contains(index))
+            .map(|(_, card)| card),
+    );
+    Ok((result, warmup.len()))
+}
+
+/// Keep only the first card of each cloze family, so siblings are not shown
+/// in the same session. The others are left due, for a later session. Basic
+/// and type-in cards have no family, and are never buried.
 fn bury_siblings(deck: Vec<Card>) -> Vec<Card> {
     let mut seen_families = HashSet::new();
     let mut result = Vec::new();
@@ -366,3 +984,140 @@ fn bury_siblings(deck: Vec<Card>) -> Vec<Card> {
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::db::ReviewRecord;
+    use crate::fsrs::Grade;
+    use crate::types::card::CardContent;
+
+    fn make_card(question: &str) -> Card {
+        Card::new(
+            "Deck".to_string(),
+            PathBuf::from("Deck.md"),
+            (0, 1),
+            CardContent::new_basic(question, "A"),
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn test_bury_siblings() -> Fallible<()> {
+        let text = "Paris is the capital of France.";
+        let cloze = |start: usize, end: usize| -> Fallible<Card> {
+            Ok(Card::new(
+                "Deck".to_string(),
+                PathBuf::from("Deck.md"),
+                (0, 1),
+                CardContent::new_cloze(text, start, end)?,
+                Vec::new(),
+            ))
+        };
+        let other = Card::new(
+            "Deck".to_string(),
+            PathBuf::from("Deck.md"),
+            (2, 3),
+            CardContent::new_cloze("Rome is the capital of Italy.", 0, 3)?,
+            Vec::new(),
+        );
+        let deck = vec![
+            make_card("Q1"),
+            cloze(13, 19)?,
+            make_card("Q2"),
+            cloze(0, 4)?,
+            other.clone(),
+            cloze(24, 29)?,
+        ];
+        let hashes: Vec<CardHash> = bury_siblings(deck.clone())
+            .iter()
+            .map(|card| card.hash())
+            .collect();
+        assert_eq!(
+            hashes,
+            [&deck[0], &deck[1], &deck[2], &other]
+                .iter()
+                .map(|card| card.hash())
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    /// The directions of a reversible card are siblings.
+    #[test]
+    fn test_bury_reversed_sibling() {
+        let [forward, reversed] =
+            CardContent::new_reversible("le chat", "the cat").map(|content| {
+                Card::new(
+                    "Deck".to_string(),
+                    PathBuf::from("Deck.md"),
+                    (0, 1),
+                    content,
+                    Vec::new(),
+                )
+            });
+        let deck = vec![reversed.clone(), make_card("Q1"), forward];
+        let hashes: Vec<CardHash> = bury_siblings(deck).iter().map(|card| card.hash()).collect();
+        assert_eq!(hashes, vec![reversed.hash(), make_card("Q1").hash()]);
+    }
+
+    #[test]
+    fn test_script_content_flip_key() {
+        let script = script_content(&[], None, None, false, false, None);
+        assert!(script.contains("const FLIP_KEY = null;"));
+        let script = script_content(&[], None, None, false, false, Some("\""));
+        assert!(script.contains("const FLIP_KEY = \"\\\"\";"));
+    }
+
+    fn review(db: &mut Database, card: &Card, grades: &[Grade]) -> Fallible<()> {
+        let now = Timestamp::now();
+        let reviews = grades
+            .iter()
+            .map(|grade| ReviewRecord {
+                card_hash: card.hash(),
+                reviewed_at: now,
+                grade: *grade,
+                stability: 2.0,
+                difficulty: 2.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: now.date(),
+                revealed_at: None,
+                confidence: None,
+                duration_ms: None,
+            })
+            .collect();
+        db.save_session(now, now, reviews)
+    }
+
+    #[test]
+    fn test_warm_up() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let cards: Vec<Card> = ["new", "weak", "strong", "medium"]
+            .into_iter()
+            .map(make_card)
+            .collect();
+        for card in cards.iter() {
+            db.insert_card(card.hash(), Timestamp::now())?;
+        }
+        review(&mut db, &cards[1], &[Grade::Forgot, Grade::Forgot])?;
+        review(&mut db, &cards[2], &[Grade::Good, Grade::Easy])?;
+        review(&mut db, &cards[3], &[Grade::Forgot, Grade::Good])?;
+
+        let (deck, count) = warm_up(&db, cards.clone(), 2)?;
+        assert_eq!(count, 2);
+        let order: Vec<CardHash> = deck.iter().map(|card| card.hash()).collect();
+        let expected: Vec<CardHash> = [2, 3, 0, 1].iter().map(|i| cards[*i].hash()).collect();
+        assert_eq!(order, expected);
+
+        // Only cards with a review history are eligible.
+        let (deck, count) = warm_up(&db, cards.clone(), 10)?;
+        assert_eq!(count, 3);
+        let order: Vec<CardHash> = deck.iter().map(|card| card.hash()).collect();
+        let expected: Vec<CardHash> = [2, 3, 1, 0].iter().map(|i| cards[*i].hash()).collect();
+        assert_eq!(order, expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,1755 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::mem::take;
+
+use crate::cmd::drill::group::cloze_group;
+use crate::cmd::drill::leech::FLAG_TAG;
+use crate::cmd::drill::leech::LEECH_TAG;
+use crate::cmd::drill::leech::LeechAction;
+use crate::cmd::drill::leech::count_lapses;
+use crate::cmd::drill::leech::tag_cards;
+use crate::cmd::drill::server::CardTimer;
+use crate::cmd::drill::state::MutableState;
+use crate::cmd::drill::state::Phase;
+use crate::cmd::drill::state::Redo;
+use crate::cmd::drill::state::Review;
+use crate::cmd::drill::state::ServerState;
+use crate::cmd::drill::state::Skip;
+use crate::db::ReviewRecord;
+use crate::error::Fallible;
+use crate::fsrs::Grade;
+use crate::types::card::Card;
+use crate::types::card::CardType;
+use crate::types::card_hash::CardHash;
+use crate::types::confidence::Confidence;
+use crate::types::performance::Performance;
+use crate::types::performance::ReviewedPerformance;
+use crate::types::performance::update_performance;
+use crate::types::timestamp::Timestamp;
+
+/// When a card was reviewed, and how long it took.
+#[derive(Clone, Copy)]
+struct Timing {
+    reviewed_at: Timestamp,
+    revealed_at: Option<Timestamp>,
+    duration_ms: Option<i64>,
+}
+
+/// The review loop of a drill session: revealing, grading, and undoing, until
+/// the session is finished. This is independent of the HTTP handlers, which
+/// only parse the form and call these methods.
+///
+/// Actions that don't apply in the current state, like grading a card whose
+/// answer isn't revealed, are ignored.
+pub struct Session<'a> {
+    state: &'a ServerState,
+    mutable: &'a mut MutableState,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(state: &'a ServerState, mutable: &'a mut MutableState) -> Self {
+        Self { state, mutable }
+    }
+
+    /// The card being shown, or `None` once the session is finished.
+    pub fn current_card(&self) -> Option<&Card> {
+        if self.is_finished() {
+            None
+        } else {
+            self.mutable.cards.first()
+        }
+    }
+
+    #[cfg(test)]
+    pub fn is_revealed(&self) -> bool {
+        self.mutable.reveal
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.mutable.finished_at.is_some()
+    }
+
+    /// Whether the session has no card to show and no review to save, so
+    /// the server can shut down without ending it.
+    pub fn is_empty(&self) -> bool {
+        self.mutable.nothing_to_study() && self.mutable.reviews.is_empty()
+    }
+
+    /// Reveal the answer of the current card.
+    pub fn reveal(&mut self) {
+        if !self.mutable.reveal {
+            self.mutable.reveal = true;
+            self.mutable.revealed_at = Some(Timestamp::now());
+        }
+    }
+
+    /// Check the answer typed for the current type-in card, and reveal the
+    /// expected answer.
+    pub fn check(&mut self, typed: String) {
+        let type_in: bool = self
+            .current_card()
+            .is_some_and(|card| card.card_type() == CardType::TypeIn);
+        if type_in && !self.mutable.reveal {
+            self.mutable.typed = Some(typed);
+            self.reveal();
+        }
+    }
+
+    /// In the study phase of a two-phase session, move on to the next card.
+    /// After the last card, the grading phase starts.
+    pub fn next(&mut self) {
+        if self.mutable.reveal && self.mutable.phase == Phase::Study {
+            self.mutable.redo.clear();
+            self.study_current();
+        }
+    }
+
+    /// Grade the current card. Cards that need to be repeated are queued
+    /// again. The session is finished after the last card, or once the time
+    /// limit is reached.
+    pub fn grade(&mut self, grade: Grade, confidence: Option<Confidence>) -> Fallible<()> {
+        if !self.mutable.reveal || self.mutable.phase != Phase::Grade {
+            return Ok(());
+        }
+        self.mutable.redo.clear();
+        let reviewed_at: Timestamp = Timestamp::now();
+        let revealed_at: Option<Timestamp> = self.mutable.revealed_at.take();
+        let timing: Timing = self.timing(reviewed_at, revealed_at);
+        let card: Card = self.mutable.cards.remove(0);
+        self.grade_card(card, grade, timing, confidence)?;
+        self.hide();
+        self.mutable.last_grade = Some(grade);
+        self.trim_undo_history()?;
+        self.finish_if_done()
+    }
+
+    /// Grade the cloze group of the current card, when siblings are shown
+    /// together: the deletions in `recalled` are graded "Good", and the
+    /// others "Forgot". The reviews are undone together.
+    pub fn grade_group(
+        &mut self,
+        recalled: &HashSet<CardHash>,
+        confidence: Option<Confidence>,
+    ) -> Fallible<()> {
+        if !self.mutable.reveal || self.mutable.phase != Phase::Grade || !self.state.group_cloze {
+            return Ok(());
+        }
+        self.mutable.redo.clear();
+        let reviewed_at: Timestamp = Timestamp::now();
+        let revealed_at: Option<Timestamp> = self.mutable.revealed_at.take();
+        let timing: Timing = self.timing(reviewed_at, revealed_at);
+        // The group flashes "Good" if every deletion was recalled.
+        let mut flash: Grade = Grade::Good;
+        for (index, card) in cloze_group(&self.mutable.cards).into_iter().enumerate() {
+            if let Some(position) = self
+                .mutable
+                .cards
+                .iter()
+                .position(|c| c.hash() == card.hash())
+            {
+                self.mutable.cards.remove(position);
+            }
+            let grade: Grade = if recalled.contains(&card.hash()) {
+                Grade::Good
+            } else {
+                flash = Grade::Forgot;
+                Grade::Forgot
+            };
+            self.grade_card(card, grade, timing, confidence)?;
+            if index > 0 {
+                if let Some(review) = self.mutable.reviews.last_mut() {
+                    review.grouped = true;
+                }
+            }
+        }
+        self.hide();
+        self.mutable.last_grade = Some(flash);
+        self.trim_undo_history()?;
+        self.finish_if_done()
+    }
+
+    /// Whether there is an action to undo.
+    pub fn can_undo(&self) -> bool {
+        match self.mutable.phase {
+            Phase::Study => !self.mutable.studied.is_empty(),
+            Phase::Grade => {
+                self.mutable.reviews.len() > self.mutable.committed
+                    || self.mutable.last_skip().is_some()
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub fn can_redo(&self) -> bool {
+        !self.mutable.redo.is_empty()
+    }
+
+    /// Undo the last review, putting its card back at the front of the queue,
+    /// or the last skip, if it came after it. In the study phase, go back to
+    /// the previous card instead. Committed reviews can't be undone. The card
+    /// is shown as it was, revealed or not, and the action can be redone.
+    pub fn undo(&mut self) -> Fallible<()> {
+        if self.mutable.phase == Phase::Study {
+            if let Some(card) = self.mutable.studied.pop() {
+                self.mutable.cards.insert(0, card);
+                self.mutable.redo.push(Redo::Study);
+                self.show_again(true);
+            }
+            return Ok(());
+        }
+        if !self.can_undo() {
+            return Ok(());
+        }
+        if self.mutable.last_skip().is_some() {
+            if let Some(skip) = self.mutable.skips.pop() {
+                self.undo_skip(&skip);
+                self.show_again(skip.revealed);
+                self.mutable.redo.push(Redo::Skip(skip));
+            }
+            return Ok(());
+        }
+        if let Some(last_review) = self.mutable.reviews.pop() {
+            let mut grouped: bool = last_review.grouped;
+            self.undo_review(&last_review)?;
+            let mut undone: Vec<Review> = vec![last_review];
+            // The deletions of a cloze group are undone together. Groups are
+            // never split by the committed reviews.
+            while grouped {
+                match self.mutable.reviews.pop() {
+                    Some(review) => {
+                        grouped = review.grouped;
+                        self.undo_review(&review)?;
+                        undone.push(review);
+                    }
+                    None => grouped = false,
+                }
+            }
+            undone.reverse();
+            self.mutable.redo.push(Redo::Reviews(undone));
+            self.mutable.finished_at = None;
+            self.show_again(true);
+        }
+        Ok(())
+    }
+
+    /// Redo the last action undone. If the queue has changed since, e.g. by
+    /// an edit, the redo stack is stale, and it is cleared instead.
+    pub fn redo(&mut self) -> Fallible<()> {
+        let Some(redo) = self.mutable.redo.pop() else {
+            return Ok(());
+        };
+        let front: Option<CardHash> = self.current_card().map(Card::hash);
+        match redo {
+            Redo::Study if self.mutable.phase == Phase::Study && front.is_some() => {
+                self.study_current();
+            }
+            Redo::Skip(skip)
+                if self.mutable.phase == Phase::Grade
+                    && front.is_some()
+                    && front == skip.card_hashes.first().copied() =>
+            {
+                self.skip_cards(skip.card_hashes);
+            }
+            Redo::Reviews(reviews)
+                if self.mutable.phase == Phase::Grade
+                    && front.is_some()
+                    && front == reviews.first().map(|review| review.card.hash()) =>
+            {
+                self.redo_reviews(reviews)?;
+            }
+            _ => self.mutable.redo.clear(),
+        }
+        Ok(())
+    }
+
+    /// Commit every review so far: they can no longer be undone.
+    pub fn lock_in(&mut self) -> Fallible<()> {
+        if self.is_finished() {
+            return Ok(());
+        }
+        self.commit(self.mutable.reviews.len())
+    }
+
+    /// Finish the session, and save its reviews.
+    pub fn end(&mut self) -> Fallible<()> {
+        if self.is_finished() {
+            return Ok(());
+        }
+        log::debug!("Session completed");
+        let session_ended_at = Timestamp::now();
+        // The committed reviews are already saved.
+        let committed: usize = self.mutable.committed.min(self.mutable.reviews.len());
+        let reviews: Vec<ReviewRecord> = self.mutable.reviews[committed..]
+            .iter()
+            .cloned()
+            .map(Review::into_record)
+            .collect();
+        let performance: Vec<(CardHash, Performance)> = self
+            .mutable
+            .cache
+            .iter()
+            .map(|(card_hash, performance)| (*card_hash, *performance))
+            .collect();
+        self.save_transfers()?;
+        self.mutable.db.save_reviews(
+            self.mutable.session_id,
+            self.state.session_started_at,
+            session_ended_at,
+            reviews,
+            &performance,
+        )?;
+        self.save_suspensions()?;
+        self.mutable.finished_at = Some(session_ended_at);
+        self.handle_leeches();
+        Ok(())
+    }
+
+    /// Flag the current card for later review. Its scheduling is not
+    /// affected: the `#flagged` tag is added to its deck file once the
+    /// session ends.
+    pub fn flag(&mut self) {
+        if let Some(card) = self.current_card() {
+            if !self.mutable.is_flagged(card) {
+                let card: Card = card.clone();
+                self.mutable.flagged.push(card);
+            }
+        }
+    }
+
+    /// Skip the current card: send it to the back of the queue without
+    /// grading it, with its cloze group if siblings are shown together. The
+    /// progress doesn't change, since the card is still to be graded.
+    pub fn skip(&mut self) {
+        if self.mutable.phase != Phase::Grade || self.current_card().is_none() {
+            return;
+        }
+        self.mutable.redo.clear();
+        let group: Vec<Card> = if self.state.group_cloze {
+            cloze_group(&self.mutable.cards)
+        } else {
+            vec![self.mutable.cards[0].clone()]
+        };
+        self.skip_cards(group.iter().map(Card::hash).collect());
+    }
+
+    /// Send the given cards to the back of the queue, and record the skip.
+    fn skip_cards(&mut self, card_hashes: Vec<CardHash>) {
+        for hash in &card_hashes {
+            if let Some(position) = self.mutable.cards.iter().position(|c| c.hash() == *hash) {
+                let card: Card = self.mutable.cards.remove(position);
+                self.mutable.cards.push(card);
+            }
+        }
+        self.mutable.skips.push(Skip {
+            card_hashes,
+            review_count: self.mutable.reviews.len(),
+            revealed: self.mutable.reveal,
+        });
+        self.hide();
+    }
+
+    /// Suspend the current card: it is removed from the session, and not
+    /// scheduled again until it is unsuspended. The suspension is saved when
+    /// the session ends.
+    pub fn suspend(&mut self) -> Fallible<()> {
+        let Some(card) = self.current_card() else {
+            return Ok(());
+        };
+        let hash: CardHash = card.hash();
+        self.mutable.redo.clear();
+        self.mutable.suspended.push(hash);
+        self.mutable.cards.retain(|card| card.hash() != hash);
+        log::info!("Card suspended: {hash}");
+        self.skip_current()
+    }
+
+    /// Move on after the current card was taken out of the queue: hide the
+    /// answer, and once the queue is empty, start grading, or finish the
+    /// session.
+    pub fn skip_current(&mut self) -> Fallible<()> {
+        self.hide();
+        if self.mutable.phase == Phase::Grade {
+            return self.finish_if_done();
+        }
+        if self.mutable.cards.is_empty() {
+            self.start_grading();
+            // The studied cards may have been taken out too.
+            if self.mutable.cards.is_empty() {
+                self.end()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// End the session without persisting its reviews. Committed reviews
+    /// are already saved, and the session they belong to ends here.
+    pub fn discard(&mut self) -> Fallible<()> {
+        log::debug!("Session discarded");
+        let discarded_at = Timestamp::now();
+        // The edits are already written, so their cards keep their history.
+        self.save_transfers()?;
+        if let Some(session_id) = self.mutable.session_id {
+            self.mutable.db.save_reviews(
+                Some(session_id),
+                self.state.session_started_at,
+                discarded_at,
+                Vec::new(),
+                &[],
+            )?;
+        }
+        self.save_suspensions()?;
+        self.mutable.finished_at = Some(discarded_at);
+        self.mutable.discarded = true;
+        self.write_tags(&[], &[]);
+        Ok(())
+    }
+
+    /// Move the review histories of the cards edited in this session to the
+    /// cards that replace them. This is done before reviews are saved, so
+    /// the saved performance of the new cards, from the cache, comes last.
+    fn save_transfers(&mut self) -> Fallible<()> {
+        let now: Timestamp = Timestamp::now();
+        for (from, to) in take(&mut self.mutable.transfers) {
+            if self.mutable.db.get_card_performance_opt(to)?.is_none() {
+                self.mutable.db.insert_card(to, now)?;
+            }
+            self.mutable.db.transfer_history(from, to)?;
+        }
+        Ok(())
+    }
+
+    /// Suspend the cards suspended in this session in the database. They
+    /// are kept even if the session is discarded, like flags.
+    fn save_suspensions(&mut self) -> Fallible<()> {
+        for hash in take(&mut self.mutable.suspended) {
+            self.mutable.db.suspend_card(hash)?;
+        }
+        Ok(())
+    }
+
+    /// Apply the leech action to the cards that became leeches in this
+    /// session, flag them, and tag the flagged cards. Leeches that reached
+    /// the suspension threshold are suspended. The reviews are already saved,
+    /// so failures are logged rather than failing the session.
+    fn handle_leeches(&mut self) {
+        let leeches: Vec<Card> = self
+            .mutable
+            .reviews
+            .iter()
+            .filter(|review| review.leech)
+            .map(|review| review.card.clone())
+            .collect();
+        let suspend_all: bool = self.state.leech_action == Some(LeechAction::Suspend);
+        let suspended: Vec<CardHash> = self
+            .mutable
+            .reviews
+            .iter()
+            .filter(|review| review.leech_suspend || (suspend_all && review.leech))
+            .map(|review| review.card.hash())
+            .collect();
+        for hash in suspended {
+            if let Err(e) = self.mutable.db.suspend_card(hash) {
+                log::error!("Failed to suspend leech: {e}");
+            }
+        }
+        if self.state.leech_action == Some(LeechAction::Tag) {
+            self.write_tags(&leeches, &leeches);
+        } else {
+            self.write_tags(&[], &leeches);
+        }
+    }
+
+    /// Add the leech tag to the given cards, and the flag tag to the given
+    /// leeches and to the flagged cards, in their deck files. Files are only
+    /// written once the session is over, since tagging a card shifts the
+    /// lines of the cards below it.
+    fn write_tags(&self, tagged_leeches: &[Card], flagged_leeches: &[Card]) {
+        let mut tagged: Vec<(&Card, &str)> = tagged_leeches
+            .iter()
+            .map(|card| (card, LEECH_TAG))
+            .collect();
+        tagged.extend(flagged_leeches.iter().map(|card| (card, FLAG_TAG)));
+        tagged.extend(self.mutable.flagged.iter().map(|card| (card, FLAG_TAG)));
+        if tagged.is_empty() {
+            return;
+        }
+        if let Err(e) = tag_cards(&tagged) {
+            log::error!("Failed to tag cards: {e}");
+        }
+    }
+
+    /// Commit the oldest reviews beyond the undo depth, if it is limited.
+    fn trim_undo_history(&mut self) -> Fallible<()> {
+        if let Some(depth) = self.state.undo_depth {
+            let count: usize = self.mutable.reviews.len().saturating_sub(depth);
+            self.commit(count)?;
+        }
+        Ok(())
+    }
+
+    /// Commit the first `count` reviews of the session, or more, so as not to
+    /// split a cloze group. The reviews are saved with the performance of
+    /// their cards, in one transaction, so that undoing a later review of the
+    /// same card restores it, and the saved performance always has the
+    /// reviews behind it.
+    fn commit(&mut self, count: usize) -> Fallible<()> {
+        let reviews: &[Review] = &self.mutable.reviews;
+        let mut count: usize = count.min(reviews.len());
+        while count < reviews.len() && reviews[count].grouped {
+            count += 1;
+        }
+        let committed: usize = self.mutable.committed.min(count);
+        if committed == count {
+            return Ok(());
+        }
+        self.save_transfers()?;
+        // A card may be reviewed more than once in the committed reviews.
+        let mut review_counts: HashMap<CardHash, usize> = HashMap::new();
+        let mut performance: Vec<(CardHash, Performance)> = Vec::new();
+        for review in &self.mutable.reviews[committed..count] {
+            let hash: CardHash = review.card.hash();
+            let review_count: usize = match review_counts.get(&hash) {
+                Some(review_count) => *review_count,
+                None => match self.mutable.db.get_card_performance(hash)? {
+                    Performance::New => 0,
+                    Performance::Reviewed(performance) => performance.review_count,
+                },
+            };
+            review_counts.insert(hash, review_count + 1);
+            let reviewed = ReviewedPerformance {
+                last_reviewed_at: review.reviewed_at,
+                stability: review.stability,
+                difficulty: review.difficulty,
+                interval_raw: review.interval_raw,
+                interval_days: review.interval_days,
+                due_date: review.due_date,
+                review_count: review_count + 1,
+            };
+            performance.push((hash, Performance::Reviewed(reviewed)));
+        }
+        let reviews: Vec<ReviewRecord> = self.mutable.reviews[committed..count]
+            .iter()
+            .cloned()
+            .map(Review::into_record)
+            .collect();
+        let session_id: i64 = self.mutable.db.save_reviews(
+            self.mutable.session_id,
+            self.state.session_started_at,
+            Timestamp::now(),
+            reviews,
+            &performance,
+        )?;
+        self.mutable.session_id = Some(session_id);
+        self.mutable.committed = count;
+        Ok(())
+    }
+
+    /// Hide the answer of the current card, and start timing it anew. This is
+    /// called whenever the current card changes.
+    fn hide(&mut self) {
+        self.mutable.reveal = false;
+        self.mutable.revealed_at = None;
+        self.mutable.shown_at = Timestamp::now();
+        self.mutable.typed = None;
+    }
+
+    /// Show the current card again after an undo, with its answer revealed
+    /// if it was. The card timer starts anew.
+    fn show_again(&mut self, revealed: bool) {
+        self.hide();
+        if revealed {
+            self.reveal();
+        }
+    }
+
+    /// In the study phase, move the current card to the studied cards. After
+    /// the last card, the grading phase starts.
+    fn study_current(&mut self) {
+        let card: Card = self.mutable.cards.remove(0);
+        self.mutable.studied.push(card);
+        self.hide();
+        if self.mutable.cards.is_empty() {
+            self.start_grading();
+        }
+    }
+
+    /// Grade the cards of undone reviews again, as they were graded, and
+    /// move on like after grading them.
+    fn redo_reviews(&mut self, reviews: Vec<Review>) -> Fallible<()> {
+        for review in reviews {
+            let hash: CardHash = review.card.hash();
+            if let Some(position) = self.mutable.cards.iter().position(|c| c.hash() == hash) {
+                self.mutable.cards.remove(position);
+            }
+            let timing = Timing {
+                reviewed_at: review.reviewed_at,
+                revealed_at: review.revealed_at,
+                duration_ms: review.duration_ms,
+            };
+            self.grade_card(review.card, review.grade, timing, review.confidence)?;
+            if let Some(last) = self.mutable.reviews.last_mut() {
+                last.grouped = review.grouped;
+            }
+        }
+        self.hide();
+        self.trim_undo_history()?;
+        self.finish_if_done()
+    }
+
+    /// The times of a review of the current card, graded now.
+    fn timing(&self, reviewed_at: Timestamp, revealed_at: Option<Timestamp>) -> Timing {
+        let started_at: Option<Timestamp> = match self.state.card_timer {
+            CardTimer::Shown => Some(self.mutable.shown_at),
+            CardTimer::Revealed => revealed_at,
+        };
+        let duration_ms: Option<i64> = started_at.map(|started_at| {
+            (reviewed_at.into_inner() - started_at.into_inner()).num_milliseconds()
+        });
+        Timing {
+            reviewed_at,
+            revealed_at,
+            duration_ms,
+        }
+    }
+
+    /// Finish the session if this was the last card, or the time has run out.
+    fn finish_if_done(&mut self) -> Fallible<()> {
+        if self.mutable.cards.is_empty() || self.state.remaining_seconds() == Some(0) {
+            self.end()?;
+        }
+        Ok(())
+    }
+
+    /// End the study phase of a two-phase session: queue the studied cards
+    /// again, in the order they were studied, for grading.
+    fn start_grading(&mut self) {
+        let studied: Vec<Card> = take(&mut self.mutable.studied);
+        self.mutable.cards.extend(studied);
+        self.mutable.phase = Phase::Grade;
+        self.mutable.grading_started_at = Some(Timestamp::now());
+    }
+
+    /// Grade a card that was taken off the queue, and queue it again if it
+    /// needs to be repeated.
+    fn grade_card(
+        &mut self,
+        card: Card,
+        grade: Grade,
+        timing: Timing,
+        confidence: Option<Confidence>,
+    ) -> Fallible<()> {
+        let Timing {
+            reviewed_at,
+            revealed_at,
+            duration_ms,
+        } = timing;
+        let hash: CardHash = card.hash();
+        let performance: Performance = self.mutable.cache.get(hash)?;
+        let performance: ReviewedPerformance =
+            schedule(self.state, performance, &card, grade, reviewed_at);
+        let (leech, leech_suspend): (bool, bool) = self.reaches_leech_thresholds(hash, grade)?;
+        let review = Review {
+            card: card.clone(),
+            reviewed_at,
+            grade,
+            stability: performance.stability,
+            difficulty: performance.difficulty,
+            interval_raw: performance.interval_raw,
+            interval_days: performance.interval_days,
+            due_date: performance.due_date,
+            revealed_at,
+            confidence,
+            duration_ms,
+            grouped: false,
+            leech,
+            leech_suspend,
+        };
+
+        self.mutable
+            .cache
+            .update(hash, Performance::Reviewed(performance))?;
+        *self.mutable.shown.entry(hash).or_insert(0) += 1;
+        if review.should_repeat() && !self.reached_max_shown(hash) {
+            self.mutable.cards.push(card);
+        }
+        self.mutable.reviews.push(review);
+        Ok(())
+    }
+
+    /// Whether forgetting the card now makes it a leech, and whether it
+    /// brings it to the threshold to suspend it at. Lapses are counted by
+    /// [`count_lapses`], over the saved reviews, the reviews in this session,
+    /// and this one. A card whose lapses were forgiven can become a leech
+    /// again.
+    fn reaches_leech_thresholds(&self, hash: CardHash, grade: Grade) -> Fallible<(bool, bool)> {
+        if !self.state.detects_leeches() || grade != Grade::Forgot {
+            return Ok((false, false));
+        }
+        let mut grades: Vec<Grade> = self.mutable.grades(hash)?;
+        grades.push(grade);
+        let lapses: usize = count_lapses(&grades);
+        let leech: bool = lapses == self.state.leech_threshold;
+        let suspend: bool = self.state.leech_suspend_threshold == Some(lapses);
+        Ok((leech, suspend))
+    }
+
+    /// Whether the card has been shown as many times as a card can be in a
+    /// session. If so, it is not repeated, whatever its grade.
+    fn reached_max_shown(&self, hash: CardHash) -> bool {
+        let shown: usize = self.mutable.shown.get(&hash).copied().unwrap_or(0);
+        self.state
+            .max_shown
+            .is_some_and(|max_shown| shown >= max_shown)
+    }
+
+    /// Put skipped cards back at the front of the queue. Cards that have
+    /// since left the session, e.g. on reload, are left out.
+    fn undo_skip(&mut self, skip: &Skip) {
+        for hash in skip.card_hashes.iter().rev() {
+            if let Some(position) = self.mutable.cards.iter().rposition(|c| c.hash() == *hash) {
+                let card: Card = self.mutable.cards.remove(position);
+                self.mutable.cards.insert(0, card);
+            }
+        }
+    }
+
+    /// Put the card of an undone review back at the front of the queue.
+    fn undo_review(&mut self, review: &Review) -> Fallible<()> {
+        let should_repeat: bool = review.should_repeat();
+        let card: Card = review.card.clone();
+        let hash: CardHash = card.hash();
+        if should_repeat && !self.reached_max_shown(hash) {
+            // Remove the card from the back of the queue.
+            self.mutable.cards.pop();
+        }
+        if let Some(shown) = self.mutable.shown.get_mut(&hash) {
+            *shown = shown.saturating_sub(1);
+        }
+        self.mutable.cards.insert(0, card);
+        // Restore the performance cache to the value in the database if it
+        // exists.
+        let performance = self.mutable.db.get_card_performance(hash)?;
+        self.mutable.cache.update(hash, performance)?;
+        Ok(())
+    }
+}
+
+/// The performance of a card after it is given a grade, with the session's
+/// interval bounds, fuzz, and the deck's desired retention.
+fn schedule(
+    state: &ServerState,
+    performance: Performance,
+    card: &Card,
+    grade: Grade,
+    reviewed_at: Timestamp,
+) -> ReviewedPerformance {
+    let fuzz: f64 = state.interval_fuzz.factor(card.hash().to_u64());
+    update_performance(
+        performance,
+        grade,
+        reviewed_at,
+        state.interval_bounds,
+        state.retention.desired_retention(card.deck_name()),
+        fuzz,
+    )
+}
+
+/// The interval, in days, that grading the card now would give it, for each
+/// grade. Nothing is changed, and after an undo, the intervals are those of
+/// the restored performance.
+pub fn preview_intervals(
+    state: &ServerState,
+    mutable: &MutableState,
+    card: &Card,
+) -> Fallible<Vec<(Grade, i64)>> {
+    let performance: Performance = mutable.cache.get(card.hash())?;
+    let now: Timestamp = Timestamp::now();
+    Ok([Grade::Forgot, Grade::Hard, Grade::Good, Grade::Easy]
+        .into_iter()
+        .map(|grade| {
+            let scheduled = schedule(state, performance, card, grade, now);
+            (grade, scheduled.interval_days)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs::read_to_string;
+    use std::fs::write;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use axum::Json;
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::response::Html;
+    use chrono::NaiveDate;
+    use chrono::TimeDelta;
+    use tempfile::tempdir;
+    use tokio::sync::oneshot;
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::casefold::CaseFolding;
+    use crate::cmd::drill::api::api_card_handler;
+    use crate::cmd::drill::cache::Cache;
+    use crate::cmd::drill::get::get_handler;
+    use crate::cmd::drill::leech::DEFAULT_LEECH_THRESHOLD;
+    use crate::cmd::drill::live::SessionSnapshot;
+    use crate::cmd::drill::post::FormData;
+    use crate::cmd::drill::post::action_handler;
+    use crate::cmd::drill::server::AnswerControls;
+    use crate::cmd::drill::similarity::DEFAULT_EDIT_SIMILARITY;
+    use crate::cmd::drill::state::MutableState;
+    use crate::cmd::drill::template::Theme;
+    use crate::cmd::drill::time_format::DEFAULT_TIME_FORMAT;
+    use crate::cmd::drill::time_format::TimeFormat;
+    use crate::config::ColorConfig;
+    use crate::config::LanguageConfig;
+    use crate::config::RetentionConfig;
+    use crate::db::Database;
+    use crate::ignore::IgnoreRules;
+    use crate::markdown::ClozeBlank;
+    use crate::media::remote::REMOTE_CACHE_DIR;
+    use crate::media::remote::RemoteCache;
+    use crate::parser::DEFAULT_DECK_SEPARATOR;
+    use crate::parser::parse_deck;
+    use crate::types::card::CardContent;
+    use crate::types::date::Date;
+    use crate::types::performance::IntervalBounds;
+    use crate::types::performance::IntervalFuzz;
+
+    fn make_card(question: &str) -> Card {
+        Card::new(
+            "Deck".to_string(),
+            PathBuf::from("Deck.md"),
+            (0, 1),
+            CardContent::new_basic(question, "A"),
+            Vec::new(),
+        )
+    }
+
+    /// A session over the given cards, all of them new, with an in-memory
+    /// database.
+    fn make_state(cards: Vec<Card>) -> Fallible<ServerState> {
+        let db = Database::new(":memory:")?;
+        let mut cache = Cache::new();
+        let now = Timestamp::now();
+        for card in &cards {
+            db.insert_card(card.hash(), now)?;
+            cache.insert(card.hash(), Performance::New)?;
+        }
+        Ok(ServerState {
+            port: 0,
+            directory: PathBuf::from("."),
+            macros: Vec::new(),
+            total_cards: cards.len(),
+            warmup_cards: 0,
+            deferred_cards: 0,
+            session_started_at: now,
+            mutable: Arc::new(Mutex::new(MutableState {
+                reveal: false,
+                revealed_at: None,
+                shown_at: Timestamp::now(),
+                db,
+                cache,
+                cards: cards.clone(),
+                reviews: Vec::new(),
+                finished_at: None,
+                discarded: false,
+                revised: HashSet::new(),
+                flagged: Vec::new(),
+                suspended: Vec::new(),
+                transfers: Vec::new(),
+                skips: Vec::new(),
+                redo: Vec::new(),
+                last_edit: None,
+                rejected_edit: None,
+                phase: Phase::Grade,
+                studied: Vec::new(),
+                grading_started_at: None,
+                loaded: cards,
+                shown: HashMap::new(),
+                last_grade: None,
+                committed: 0,
+                session_id: None,
+                typed: None,
+                reloads: 0,
+            })),
+            shutdown_tx: Arc::new(Mutex::new(None)),
+            answer_controls: AnswerControls::Full,
+            auto_advance: None,
+            auto_reveal: None,
+            author: false,
+            time_limit: None,
+            interval_bounds: IntervalBounds::default(),
+            interval_fuzz: IntervalFuzz::none(),
+            dim_cloze_context: false,
+            highlight_code: true,
+            cloze_blank: ClozeBlank::default(),
+            group_cloze: false,
+            track_confidence: false,
+            media_url: None,
+            remote_cache: Arc::new(RemoteCache::new(PathBuf::from(REMOTE_CACHE_DIR), None)?),
+            milestones: None,
+            two_phase: false,
+            colors: ColorConfig::default(),
+            language: LanguageConfig::default(),
+            max_shown: None,
+            leech_action: None,
+            leech_threshold: DEFAULT_LEECH_THRESHOLD,
+            leech_suspend_threshold: None,
+            no_reload: false,
+            watch: false,
+            flip_key: None,
+            no_store: false,
+            no_cache: false,
+            no_grade_flash: false,
+            no_autoplay: false,
+            theme: Theme::Auto,
+            time_format: TimeFormat::new(DEFAULT_TIME_FORMAT, None)?,
+            card_timer: CardTimer::Shown,
+            retention: RetentionConfig::default(),
+            undo_depth: None,
+            ignore_case: false,
+            case_folding: CaseFolding::Full,
+            edit_similarity: DEFAULT_EDIT_SIMILARITY,
+            live: Arc::new(watch::Sender::new(SessionSnapshot::default())),
+        })
+    }
+
+    fn question(session: &Session) -> Option<String> {
+        session
+            .current_card()
+            .map(|card| card.content().to_source_text())
+    }
+
+    #[test]
+    fn test_review_loop() -> Fallible<()> {
+        let state = make_state(vec![make_card("Q1"), make_card("Q2")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+        assert!(!session.is_revealed());
+
+        session.reveal();
+        assert!(session.is_revealed());
+        session.grade(Grade::Good, None)?;
+        assert!(!session.is_revealed());
+        assert_eq!(question(&session), Some("Q: Q2\nA: A".to_string()));
+
+        // Undo puts the card back, revealed as it was when it was graded.
+        session.undo()?;
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+        assert!(session.is_revealed());
+
+        session.reveal();
+        session.grade(Grade::Good, Some(Confidence::new(4)?))?;
+        session.reveal();
+        session.grade(Grade::Easy, None)?;
+        assert!(session.is_finished());
+        assert_eq!(question(&session), None);
+
+        // The reviews are saved.
+        assert_eq!(mutable.reviews.len(), 2);
+        assert_eq!(
+            mutable.db.count_reviews_in_date(Timestamp::now().date())?,
+            2
+        );
+        assert_eq!(mutable.db.get_all_sessions()?.len(), 1);
+        Ok(())
+    }
+
+    /// Undo and redo step backward and forward through the reviews, and a
+    /// fresh grade clears the redo stack.
+    #[test]
+    fn test_redo() -> Fallible<()> {
+        let state = make_state(vec![make_card("Q1"), make_card("Q2")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        assert!(!session.can_redo());
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        session.reveal();
+        session.grade(Grade::Forgot, None)?;
+        assert_eq!(question(&session), Some("Q: Q2\nA: A".to_string()));
+        assert_eq!(session.mutable.cards.len(), 1);
+
+        session.undo()?;
+        assert_eq!(question(&session), Some("Q: Q2\nA: A".to_string()));
+        assert!(session.is_revealed());
+        assert_eq!(session.mutable.cards.len(), 1);
+        assert_eq!(session.mutable.reviews.len(), 1);
+        session.undo()?;
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+        assert_eq!(session.mutable.cards.len(), 2);
+        assert!(session.mutable.reviews.is_empty());
+        assert!(!session.can_undo());
+        assert!(session.can_redo());
+
+        session.redo()?;
+        assert_eq!(question(&session), Some("Q: Q2\nA: A".to_string()));
+        assert!(!session.is_revealed());
+        session.redo()?;
+        assert!(!session.can_redo());
+        // The forgotten card is queued again.
+        assert_eq!(question(&session), Some("Q: Q2\nA: A".to_string()));
+        assert_eq!(session.mutable.cards.len(), 1);
+        let grades: Vec<Grade> = session.mutable.reviews.iter().map(|r| r.grade).collect();
+        assert_eq!(grades, vec![Grade::Good, Grade::Forgot]);
+
+        // Grading after an undo clears the redo stack.
+        session.undo()?;
+        session.grade(Grade::Good, None)?;
+        assert!(!session.can_redo());
+        session.redo()?;
+        let grades: Vec<Grade> = session.mutable.reviews.iter().map(|r| r.grade).collect();
+        assert_eq!(grades, vec![Grade::Good, Grade::Good]);
+        assert!(session.is_finished());
+        Ok(())
+    }
+
+    /// Skips are redone too, and the card is shown revealed or not, as it was
+    /// when it was skipped.
+    #[test]
+    fn test_redo_skip() -> Fallible<()> {
+        let state = make_state(vec![make_card("Q1"), make_card("Q2")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.skip();
+        session.reveal();
+        session.skip();
+        session.undo()?;
+        assert_eq!(question(&session), Some("Q: Q2\nA: A".to_string()));
+        assert!(session.is_revealed());
+        session.undo()?;
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+        assert!(!session.is_revealed());
+        session.redo()?;
+        session.redo()?;
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+        assert!(session.mutable.skipped_all());
+        // A new skip clears the redo stack.
+        session.undo()?;
+        session.skip();
+        assert!(!session.can_redo());
+        Ok(())
+    }
+
+    #[test]
+    fn test_redo_study() -> Fallible<()> {
+        let state = make_state(vec![make_card("Q1"), make_card("Q2")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        mutable.phase = Phase::Study;
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.next();
+        session.undo()?;
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+        assert!(session.is_revealed());
+        session.redo()?;
+        assert_eq!(question(&session), Some("Q: Q2\nA: A".to_string()));
+        assert!(!session.can_redo());
+        Ok(())
+    }
+
+    /// A session with no cards shows a page saying so, where the server can
+    /// be shut down, and ignores actions on a card.
+    #[tokio::test]
+    async fn test_empty_session() -> Fallible<()> {
+        let state = make_state(Vec::new())?;
+        let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
+        *state.shutdown_tx.lock().unwrap() = Some(shutdown_tx);
+
+        let (status, Html(html)) = get_handler(State(state.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("Nothing to Study"));
+        assert!(html.contains("value=\"Shutdown\""));
+
+        let Ok(Json(card_state)) = api_card_handler(State(state.clone())).await else {
+            panic!("the API failed on an empty session");
+        };
+        let card_state: serde_json::Value = serde_json::to_value(&card_state)?;
+        assert!(card_state["card"].is_null());
+        assert_eq!(card_state["actions"], serde_json::json!(["Shutdown"]));
+
+        for action in ["Reveal", "Good", "Skip", "Flag", "Save", "Undo"] {
+            action_handler(state.clone(), form(action)?).await?;
+        }
+        assert!(state.mutable.lock().unwrap().nothing_to_study());
+        action_handler(state.clone(), form("Shutdown")?).await?;
+        assert!(shutdown_rx.try_recv().is_ok());
+
+        // A session whose cards left were removed, e.g. by a reload, can
+        // still undo its last review.
+        let state = make_state(vec![make_card("Q1"), make_card("Q2")])?;
+        action_handler(state.clone(), form("Reveal")?).await?;
+        action_handler(state.clone(), form("Good")?).await?;
+        state.mutable.lock().unwrap().cards.clear();
+        let (_, Html(html)) = get_handler(State(state.clone())).await;
+        assert!(html.contains("Nothing to Study"));
+        assert!(html.contains("value=\"Undo\" title="));
+        let Ok(Json(card_state)) = api_card_handler(State(state.clone())).await else {
+            panic!("the API failed on an emptied session");
+        };
+        let card_state: serde_json::Value = serde_json::to_value(&card_state)?;
+        assert_eq!(card_state["actions"], serde_json::json!(["Undo", "End"]));
+        action_handler(state.clone(), form("Undo")?).await?;
+        let mutable = state.mutable.lock().unwrap();
+        assert!(!mutable.nothing_to_study());
+        assert!(mutable.reviews.is_empty());
+        assert_eq!(mutable.cards.len(), 1);
+        Ok(())
+    }
+
+    fn form(action: &str) -> Fallible<FormData> {
+        Ok(serde_json::from_value(
+            serde_json::json!({ "action": action }),
+        )?)
+    }
+
+    /// A redo stack made stale by a change to the queue is cleared.
+    #[test]
+    fn test_redo_stale() -> Fallible<()> {
+        let state = make_state(vec![make_card("Q1"), make_card("Q2")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        session.undo()?;
+        session.mutable.cards.remove(0);
+        session.redo()?;
+        assert!(!session.can_redo());
+        assert!(session.mutable.reviews.is_empty());
+        Ok(())
+    }
+
+    /// A timestamp some seconds ago.
+    fn seconds_ago(seconds: i64) -> Timestamp {
+        Timestamp::new(Timestamp::now().into_inner() - TimeDelta::seconds(seconds))
+    }
+
+    #[test]
+    fn test_card_timer() -> Fallible<()> {
+        let state = make_state(vec![make_card("Q1")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        mutable.shown_at = seconds_ago(5);
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Forgot, None)?;
+        let duration: i64 = mutable.reviews[0].duration_ms.unwrap();
+        assert!((5000..6000).contains(&duration));
+
+        // Undo starts timing the card anew.
+        let mut session = Session::new(&state, &mut mutable);
+        session.undo()?;
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        let duration: i64 = mutable.reviews[0].duration_ms.unwrap();
+        assert!((0..1000).contains(&duration));
+
+        // The time is saved with the review.
+        let session_id: i64 = mutable.db.get_all_sessions()?[0].session_id;
+        let saved = mutable.db.get_reviews_for_session(session_id)?;
+        assert_eq!(saved[0].data.duration_ms, Some(duration));
+        Ok(())
+    }
+
+    #[test]
+    fn test_card_timer_from_reveal() -> Fallible<()> {
+        let mut state = make_state(vec![make_card("Q1")])?;
+        state.card_timer = CardTimer::Revealed;
+        let mut mutable = state.mutable.lock().unwrap();
+        mutable.shown_at = seconds_ago(5);
+        Session::new(&state, &mut mutable).reveal();
+        mutable.revealed_at = Some(seconds_ago(2));
+        Session::new(&state, &mut mutable).grade(Grade::Good, None)?;
+        let duration: i64 = mutable.reviews[0].duration_ms.unwrap();
+        assert!((2000..3000).contains(&duration));
+        Ok(())
+    }
+
+    #[test]
+    fn test_grade_without_reveal() -> Fallible<()> {
+        let state = make_state(vec![make_card("Q1")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.grade(Grade::Good, None)?;
+        assert!(!session.is_finished());
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+        assert!(mutable.reviews.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_forgotten_card_is_repeated() -> Fallible<()> {
+        let state = make_state(vec![make_card("Q1"), make_card("Q2")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Forgot, None)?;
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+
+        // Undoing the forgotten card's repeat, then the grade of the other
+        // card, leaves the queue as it was.
+        session.undo()?;
+        session.undo()?;
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+        assert_eq!(mutable.cards.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_shown() -> Fallible<()> {
+        let mut state = make_state(vec![make_card("Q1")])?;
+        state.max_shown = Some(2);
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Forgot, None)?;
+        assert!(!session.is_finished());
+        session.reveal();
+        session.grade(Grade::Forgot, None)?;
+        assert!(session.is_finished());
+        Ok(())
+    }
+
+    /// Forget a card twice, then remember it, in a session where cards
+    /// become leeches on their second lapse.
+    fn drill_leech(state: &ServerState) -> Fallible<()> {
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(state, &mut mutable);
+        for grade in [Grade::Forgot, Grade::Forgot, Grade::Good] {
+            session.reveal();
+            session.grade(grade, None)?;
+        }
+        assert!(session.is_finished());
+        let leeches: Vec<bool> = mutable.reviews.iter().map(|review| review.leech).collect();
+        assert_eq!(leeches, vec![false, true, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_leech_notify() -> Fallible<()> {
+        let card = make_card("Q1");
+        let mut state = make_state(vec![card.clone()])?;
+        state.leech_action = Some(LeechAction::Notify);
+        state.leech_threshold = 2;
+        drill_leech(&state)?;
+        let mutable = state.mutable.lock().unwrap();
+        let future = Date::new(NaiveDate::MAX);
+        assert!(mutable.db.due_today(future)?.contains(&card.hash()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_leech_undo() -> Fallible<()> {
+        let mut state = make_state(vec![make_card("Q1")])?;
+        state.leech_action = Some(LeechAction::Notify);
+        state.leech_threshold = 2;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Forgot, None)?;
+        session.reveal();
+        session.grade(Grade::Forgot, None)?;
+        session.undo()?;
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        assert!(mutable.reviews.iter().all(|review| !review.leech));
+        Ok(())
+    }
+
+    #[test]
+    fn test_leech_suspend() -> Fallible<()> {
+        let leech = make_card("Q1");
+        let other = make_card("Q2");
+        let mut state = make_state(vec![leech.clone()])?;
+        state.leech_action = Some(LeechAction::Suspend);
+        state.leech_threshold = 2;
+        {
+            let mutable = state.mutable.lock().unwrap();
+            mutable.db.insert_card(other.hash(), Timestamp::now())?;
+        }
+        drill_leech(&state)?;
+        let mutable = state.mutable.lock().unwrap();
+        let future = Date::new(NaiveDate::MAX);
+        assert_eq!(mutable.db.due_today(future)?, HashSet::from([other.hash()]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_leech_tag() -> Fallible<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("Deck.md");
+        write(&path, "Q: Q1\nA: A\n")?;
+        let cards = parse_deck(
+            &dir.path().to_path_buf(),
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )?;
+        let mut state = make_state(cards.clone())?;
+        state.leech_action = Some(LeechAction::Tag);
+        state.leech_threshold = 2;
+        drill_leech(&state)?;
+        assert_eq!(read_to_string(&path)?, "Q: Q1\nA: A\n#leech #flagged\n");
+        // The card keeps its hash, and its progress.
+        let tagged = parse_deck(
+            &dir.path().to_path_buf(),
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )?;
+        assert_eq!(tagged[0].hash(), cards[0].hash());
+        assert!(tagged[0].has_tag(LEECH_TAG));
+        assert!(tagged[0].has_tag(FLAG_TAG));
+        Ok(())
+    }
+
+    /// Leeches are flagged, whatever the leech action.
+    #[test]
+    fn test_leech_flag() -> Fallible<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("Deck.md");
+        write(&path, "Q: Q1\nA: A\n")?;
+        let cards = parse_deck(
+            &dir.path().to_path_buf(),
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )?;
+        let mut state = make_state(cards)?;
+        state.leech_action = Some(LeechAction::Notify);
+        state.leech_threshold = 2;
+        drill_leech(&state)?;
+        assert_eq!(read_to_string(&path)?, "Q: Q1\nA: A\n#flagged\n");
+        Ok(())
+    }
+
+    /// With a suspension threshold, leeches are detected without a leech
+    /// action, and suspended once they reach it.
+    #[test]
+    fn test_leech_suspend_threshold() -> Fallible<()> {
+        let card = make_card("Q1");
+        let mut state = make_state(vec![card.clone()])?;
+        state.leech_threshold = 2;
+        state.leech_suspend_threshold = Some(3);
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        for grade in [Grade::Forgot, Grade::Forgot, Grade::Forgot, Grade::Good] {
+            session.reveal();
+            session.grade(grade, None)?;
+        }
+        assert!(session.is_finished());
+        let flags: Vec<(bool, bool)> = mutable
+            .reviews
+            .iter()
+            .map(|review| (review.leech, review.leech_suspend))
+            .collect();
+        assert_eq!(
+            flags,
+            vec![(false, false), (true, false), (false, true), (false, false)]
+        );
+        assert_eq!(mutable.db.suspended_cards()?, vec![card.hash()]);
+        Ok(())
+    }
+
+    /// Remembering a card three times in a row forgives its lapses.
+    #[test]
+    fn test_leech_lapses_reset() -> Fallible<()> {
+        let card = make_card("Q1");
+        let mut state = make_state(vec![card.clone()])?;
+        state.leech_action = Some(LeechAction::Notify);
+        state.leech_threshold = 2;
+        let mut mutable = state.mutable.lock().unwrap();
+        let now = Timestamp::now();
+        let grades = [Grade::Forgot, Grade::Good, Grade::Hard, Grade::Good];
+        let reviews: Vec<ReviewRecord> = grades
+            .into_iter()
+            .map(|grade| ReviewRecord {
+                card_hash: card.hash(),
+                reviewed_at: now,
+                grade,
+                stability: 2.0,
+                difficulty: 2.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: now.date(),
+                revealed_at: None,
+                confidence: None,
+                duration_ms: None,
+            })
+            .collect();
+        mutable.db.save_session(now, now, reviews)?;
+        assert!(!mutable.is_leech(&state, &card)?);
+        let mut session = Session::new(&state, &mut mutable);
+        for grade in [Grade::Forgot, Grade::Forgot, Grade::Good] {
+            session.reveal();
+            session.grade(grade, None)?;
+        }
+        let leeches: Vec<bool> = mutable.reviews.iter().map(|review| review.leech).collect();
+        assert_eq!(leeches, vec![false, true, false]);
+        Ok(())
+    }
+
+    /// A card is a leech if it is tagged as one, or if leeches are detected
+    /// and it has been forgotten as many times as the threshold.
+    #[test]
+    fn test_is_leech() -> Fallible<()> {
+        let card = make_card("Q1");
+        let mut state = make_state(vec![card.clone()])?;
+        state.leech_threshold = 1;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Forgot, None)?;
+        assert!(!mutable.is_leech(&state, &card)?);
+        drop(mutable);
+        state.leech_action = Some(LeechAction::Notify);
+        let mutable = state.mutable.lock().unwrap();
+        assert!(mutable.is_leech(&state, &card)?);
+        let tagged = Card::new(
+            "Deck".to_string(),
+            PathBuf::from("Deck.md"),
+            (0, 1),
+            CardContent::new_basic("Q2", "A"),
+            vec![LEECH_TAG.to_string()],
+        );
+        assert!(mutable.is_leech(&state, &tagged)?);
+        Ok(())
+    }
+
+    /// Flagging a card doesn't change its schedule. The tag is written when
+    /// the session ends, even if it is discarded.
+    #[test]
+    fn test_flag() -> Fallible<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("Deck.md");
+        write(&path, "Q: Q1\nA: A\n\nQ: Q2\nA: B\n")?;
+        let cards = parse_deck(
+            &dir.path().to_path_buf(),
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )?;
+        let state = make_state(cards.clone())?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.flag();
+        session.flag();
+        assert_eq!(
+            session.current_card().map(Card::hash),
+            Some(cards[0].hash())
+        );
+        assert!(mutable.is_flagged(&cards[0]));
+        assert!(!mutable.is_flagged(&cards[1]));
+        assert_eq!(read_to_string(&path)?, "Q: Q1\nA: A\n\nQ: Q2\nA: B\n");
+        Session::new(&state, &mut mutable).discard()?;
+        assert_eq!(
+            read_to_string(&path)?,
+            "Q: Q1\nA: A\n#flagged\n\nQ: Q2\nA: B\n"
+        );
+        Ok(())
+    }
+
+    /// Suspending a card removes it from the session, and from future ones.
+    #[test]
+    fn test_suspend() -> Fallible<()> {
+        let first = make_card("Q1");
+        let second = make_card("Q2");
+        let state = make_state(vec![first.clone(), second.clone()])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.suspend()?;
+        assert_eq!(session.current_card().map(Card::hash), Some(second.hash()));
+        // The suspension is only saved when the session ends.
+        assert!(session.mutable.db.suspended_cards()?.is_empty());
+        session.suspend()?;
+        assert!(session.is_finished());
+        let future = Date::new(NaiveDate::MAX);
+        assert!(mutable.db.due_today(future)?.is_empty());
+        assert_eq!(mutable.db.suspended_cards()?.len(), 2);
+        Ok(())
+    }
+
+    /// The history of an edited card is moved to the card that replaces it
+    /// when the session ends, and counts towards it until then.
+    #[test]
+    fn test_transfers() -> Fallible<()> {
+        let old = make_card("Q1");
+        let new = make_card("Q1.");
+        let state = make_state(vec![new.clone()])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let now = Timestamp::now();
+        mutable.db.insert_card(old.hash(), now)?;
+        let review = ReviewRecord {
+            card_hash: old.hash(),
+            reviewed_at: now,
+            grade: Grade::Forgot,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            revealed_at: None,
+            confidence: None,
+            duration_ms: None,
+        };
+        mutable.db.save_session(now, now, vec![review])?;
+        mutable.transfers.push((old.hash(), new.hash()));
+        assert_eq!(mutable.grades(new.hash())?, vec![Grade::Forgot]);
+        assert!(mutable.db.get_grades(new.hash())?.is_empty());
+
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        assert!(session.is_finished());
+        assert!(mutable.transfers.is_empty());
+        assert!(mutable.db.get_grades(old.hash())?.is_empty());
+        assert_eq!(
+            mutable.db.get_grades(new.hash())?,
+            vec![Grade::Forgot, Grade::Good]
+        );
+        Ok(())
+    }
+
+    /// Skipping a card sends it to the back of the queue without a review,
+    /// and undo restores skips and reviews in the order they were made.
+    #[test]
+    fn test_skip() -> Fallible<()> {
+        let cards = vec![make_card("Q1"), make_card("Q2"), make_card("Q3")];
+        let state = make_state(cards.clone())?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        let current = |session: &Session| session.current_card().map(Card::hash);
+        session.skip();
+        assert_eq!(current(&session), Some(cards[1].hash()));
+        session.skip();
+        assert_eq!(current(&session), Some(cards[2].hash()));
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        assert_eq!(current(&session), Some(cards[0].hash()));
+
+        session.undo()?;
+        assert_eq!(current(&session), Some(cards[2].hash()));
+        session.undo()?;
+        assert_eq!(current(&session), Some(cards[1].hash()));
+        session.undo()?;
+        assert_eq!(current(&session), Some(cards[0].hash()));
+        assert!(!session.can_undo());
+        assert!(mutable.reviews.is_empty());
+        assert_eq!(mutable.cards.len(), 3);
+        Ok(())
+    }
+
+    /// Once every card left is skipped, skipping is disabled until a card is
+    /// graded.
+    #[test]
+    fn test_skip_all() -> Fallible<()> {
+        let cards = vec![make_card("Q1"), make_card("Q2")];
+        let state = make_state(cards.clone())?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.skip();
+        assert!(!session.mutable.skipped_all());
+        session.skip();
+        assert!(session.mutable.skipped_all());
+        assert_eq!(
+            session.current_card().map(Card::hash),
+            Some(cards[0].hash())
+        );
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        assert!(!session.mutable.skipped_all());
+        session.skip();
+        assert!(session.mutable.skipped_all());
+        Ok(())
+    }
+
+    #[test]
+    fn test_end_and_discard() -> Fallible<()> {
+        let state = make_state(vec![make_card("Q1"), make_card("Q2")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        session.end()?;
+        assert!(session.is_finished());
+        // Ending twice saves the session once.
+        session.end()?;
+        assert_eq!(mutable.db.get_all_sessions()?.len(), 1);
+
+        let state = make_state(vec![make_card("Q1")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.discard()?;
+        assert!(session.is_finished());
+        assert!(mutable.discarded);
+        assert!(mutable.db.get_all_sessions()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_two_phase() -> Fallible<()> {
+        let state = make_state(vec![make_card("Q1"), make_card("Q2")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        mutable.phase = Phase::Study;
+        let mut session = Session::new(&state, &mut mutable);
+
+        // Cards can't be graded while studying.
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+        session.next();
+        assert_eq!(question(&session), Some("Q: Q2\nA: A".to_string()));
+        session.undo()?;
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+        session.reveal();
+        session.next();
+        session.reveal();
+        session.next();
+
+        // The same cards are then graded.
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        assert!(session.is_finished());
+        assert_eq!(mutable.phase, Phase::Grade);
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_depth() -> Fallible<()> {
+        let cards = vec![make_card("Q1"), make_card("Q2"), make_card("Q3")];
+        let mut state = make_state(cards.clone())?;
+        state.undo_depth = Some(1);
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        // One review is within the depth.
+        assert!(session.can_undo());
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        // The first review is now committed, and only the second is undone.
+        session.undo()?;
+        assert_eq!(question(&session), Some("Q: Q2\nA: A".to_string()));
+        assert!(!session.can_undo());
+        session.undo()?;
+        assert_eq!(question(&session), Some("Q: Q2\nA: A".to_string()));
+        assert_eq!(mutable.committed, 1);
+        assert_eq!(mutable.reviews.len(), 1);
+        // The committed review's performance is saved.
+        assert!(matches!(
+            mutable.db.get_card_performance(cards[0].hash())?,
+            Performance::Reviewed(performance) if performance.review_count == 1
+        ));
+        assert_eq!(
+            mutable.db.get_card_performance(cards[1].hash())?,
+            Performance::New
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo_depth_zero() -> Fallible<()> {
+        let mut state = make_state(vec![make_card("Q1"), make_card("Q2")])?;
+        state.undo_depth = Some(0);
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        assert!(!session.can_undo());
+        session.undo()?;
+        assert_eq!(question(&session), Some("Q: Q2\nA: A".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_preview_intervals() -> Fallible<()> {
+        let card = make_card("Q1");
+        let state = make_state(vec![card.clone(), make_card("Q2")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let before = preview_intervals(&state, &mutable, &card)?;
+        let days: Vec<i64> = before.iter().map(|(_, days)| *days).collect();
+        assert!(days.is_sorted());
+        assert!(days[0] >= 1);
+
+        // Grading the card changes the preview, and undoing restores it.
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        assert_ne!(preview_intervals(&state, &mutable, &card)?, before);
+        let mut session = Session::new(&state, &mut mutable);
+        session.undo()?;
+        assert_eq!(preview_intervals(&state, &mutable, &card)?, before);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_in() -> Fallible<()> {
+        let card = make_card("Q1");
+        let state = make_state(vec![card.clone(), make_card("Q2")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Forgot, None)?;
+        session.lock_in()?;
+        assert!(!session.can_undo());
+        // Undoing a later review of the same card restores the committed
+        // performance, not the one from before the session.
+        session.reveal();
+        session.grade(Grade::Hard, None)?;
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        session.undo()?;
+        assert_eq!(question(&session), Some("Q: Q1\nA: A".to_string()));
+        let restored = mutable.cache.get(card.hash())?;
+        assert!(
+            matches!(restored, Performance::Reviewed(performance) if performance.review_count == 1)
+        );
+
+        // Discarding the session still saves the locked reviews.
+        let state = make_state(vec![make_card("Q1"), make_card("Q2")])?;
+        let mut mutable = state.mutable.lock().unwrap();
+        let mut session = Session::new(&state, &mut mutable);
+        session.reveal();
+        session.grade(Grade::Good, None)?;
+        session.lock_in()?;
+        session.reveal();
+        session.discard()?;
+        let sessions = mutable.db.get_all_sessions()?;
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(
+            mutable
+                .db
+                .get_reviews_for_session(sessions[0].session_id)?
+                .len(),
+            1
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Matching edited cards to the cards they replace, so that a small edit,
+//! like fixing a typo, keeps the card's learning progress.
+
+use std::collections::HashSet;
+
+use crate::cmd::drill::type_in::levenshtein;
+use crate::types::card::CardContent;
+use crate::types::card_hash::CardHash;
+
+/// The default similarity an edited card must have to the card it replaces
+/// to keep its learning progress.
+pub const DEFAULT_EDIT_SIMILARITY: f64 = 0.8;
+
+/// Pair each card of an edit with the old card it replaces, if they are of
+/// the same type and at least `threshold` similar. Each old card is paired
+/// with at most one new card, most similar pairs first. Cards whose content
+/// is unchanged keep their hash, and are not paired.
+///
+/// Returns `(old, new)` pairs of hashes.
+pub fn match_edited(
+    old: &[CardContent],
+    new: &[CardContent],
+    threshold: f64,
+) -> Vec<(CardHash, CardHash)> {
+    let old_hashes: HashSet<CardHash> = old.iter().map(|c| c.hash()).collect();
+    let new_hashes: HashSet<CardHash> = new.iter().map(|c| c.hash()).collect();
+    let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+    for (i, a) in old.iter().enumerate() {
+        if new_hashes.contains(&a.hash()) {
+            continue;
+        }
+        for (j, b) in new.iter().enumerate() {
+            if old_hashes.contains(&b.hash()) {
+                continue;
+            }
+            if let Some(score) = card_similarity(a, b) {
+                if score >= threshold {
+                    candidates.push((score, i, j));
+                }
+            }
+        }
+    }
+    // Most similar first. The sort is stable, so ties are broken by the
+    // order of the cards.
+    candidates.sort_by(|x, y| y.0.total_cmp(&x.0));
+    let mut used_old: HashSet<usize> = HashSet::new();
+    let mut used_new: HashSet<usize> = HashSet::new();
+    let mut pairs: Vec<(CardHash, CardHash)> = Vec::new();
+    for (_, i, j) in candidates {
+        if used_old.contains(&i) || used_new.contains(&j) {
+            continue;
+        }
+        used_old.insert(i);
+        used_new.insert(j);
+        pairs.push((old[i].hash(), new[j].hash()));
+    }
+    pairs
+}
+
+/// The similarity of two cards: that of their least similar field. Cards of
+/// different types are not comparable.
+fn card_similarity(a: &CardContent, b: &CardContent) -> Option<f64> {
+    let (a, b) = match (fields(a), fields(b)) {
+        (Some(a), Some(b)) if a.0 == b.0 => (a.1, b.1),
+        _ => return None,
+    };
+    Some(
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| similarity(x, y))
+            .fold(1.0, f64::min),
+    )
+}
+
+/// The card's type, and the fields compared to match it: the question and
+/// answer, or the text and the deleted text of a cloze card.
+fn fields(card: &CardContent) -> Option<(&'static str, [String; 2])> {
+    match card {
+        CardContent::Basic { question, answer } => {
+            Some(("basic", [question.clone(), answer.clone()]))
+        }
+        CardContent::TypeIn { prompt, answer } => {
+            Some(("type-in", [prompt.clone(), answer.clone()]))
+        }
+        // The forward card matches a basic card, like its hash.
+        CardContent::Reversible {
+            question,
+            answer,
+            reversed,
+        } => {
+            let kind: &'static str = if *reversed { "reversed" } else { "basic" };
+            Some((kind, [question.clone(), answer.clone()]))
+        }
+        CardContent::Cloze { .. } | CardContent::GroupedCloze { .. } => {
+            let (text, deletions) = card.cloze_deletions()?;
+            let deleted: Vec<&str> = deletions
+                .iter()
+                .map(|(start, end)| text.get(*start..=*end))
+                .collect::<Option<Vec<&str>>>()?;
+            Some(("cloze", [text.to_string(), deleted.join(" ")]))
+        }
+    }
+}
+
+/// How similar two texts are, from 0 to 1, ignoring whitespace and
+/// punctuation: one minus their edit distance, relative to the length of the
+/// longer one.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = normalize(a).chars().collect();
+    let b: Vec<char> = normalize(b).chars().collect();
+    let length: usize = a.len().max(b.len());
+    if length == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / length as f64
+}
+
+/// Drop punctuation, and collapse runs of whitespace to a single space.
+fn normalize(text: &str) -> String {
+    text.split(|c: char| c.is_whitespace() || c.is_ascii_punctuation())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Fallible;
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(
+            similarity("The capital  of France.", "The capital of France"),
+            1.0
+        );
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("Paris", "Pari"), 0.8);
+        assert!(similarity("Paris", "Lyon") < 0.5);
+    }
+
+    #[test]
+    fn test_match_basic() {
+        let old = vec![CardContent::new_basic(
+            "What is the capitl of France?",
+            "Paris",
+        )];
+        // A typo fix keeps the progress.
+        let new = vec![CardContent::new_basic(
+            "What is the capital of France?",
+            "Paris",
+        )];
+        assert_eq!(
+            match_edited(&old, &new, DEFAULT_EDIT_SIMILARITY),
+            vec![(old[0].hash(), new[0].hash())]
+        );
+        // A new answer doesn't.
+        let new = vec![CardContent::new_basic(
+            "What is the capital of France?",
+            "Lyon",
+        )];
+        assert!(match_edited(&old, &new, DEFAULT_EDIT_SIMILARITY).is_empty());
+        // Nor a different type of card.
+        let new = vec![CardContent::new_type_in(
+            "What is the capitl of France?",
+            "Paris",
+        )];
+        assert!(match_edited(&old, &new, DEFAULT_EDIT_SIMILARITY).is_empty());
+        // A threshold of 1 only allows whitespace and punctuation changes.
+        let new = vec![CardContent::new_basic(
+            "What is the capitl of France",
+            "Paris.",
+        )];
+        assert_eq!(match_edited(&old, &new, 1.0).len(), 1);
+    }
+
+    #[test]
+    fn test_match_cloze() -> Fallible<()> {
+        let text = "Paris is the capital of Frnace.";
+        let old = vec![
+            CardContent::new_cloze(text, 0, 4)?,
+            CardContent::new_cloze(text, 13, 19)?,
+        ];
+        // Fixing the typo changes the text, and so every deletion.
+        let text = "Paris is the capital of France.";
+        let new = vec![
+            CardContent::new_cloze(text, 0, 4)?,
+            CardContent::new_cloze(text, 13, 19)?,
+        ];
+        assert_eq!(
+            match_edited(&old, &new, DEFAULT_EDIT_SIMILARITY),
+            vec![
+                (old[0].hash(), new[0].hash()),
+                (old[1].hash(), new[1].hash())
+            ]
+        );
+        // A deletion of different text is a new card, and an unchanged one
+        // keeps its hash.
+        let new = vec![
+            CardContent::new_cloze("Paris is the capital of Frnace.", 0, 4)?,
+            CardContent::new_cloze("Paris is the capital of Frnace.", 24, 29)?,
+        ];
+        assert!(match_edited(&old, &new, DEFAULT_EDIT_SIMILARITY).is_empty());
+        Ok(())
+    }
+}
@@ -12,21 +12,44 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
 
 use tokio::sync::oneshot::Sender;
+use tokio::sync::watch;
 
+use crate::casefold::CaseFolding;
 use crate::cmd::drill::cache::Cache;
+use crate::cmd::drill::leech::FLAG_TAG;
+use crate::cmd::drill::leech::LEECH_TAG;
+use crate::cmd::drill::leech::LeechAction;
+use crate::cmd::drill::leech::count_lapses;
+use crate::cmd::drill::live::SessionSnapshot;
 use crate::cmd::drill::server::AnswerControls;
+use crate::cmd::drill::server::CardTimer;
+use crate::cmd::drill::template::Theme;
+use crate::cmd::drill::time_format::TimeFormat;
+use crate::collection::StoreMode;
+use crate::config::ColorConfig;
+use crate::config::LanguageConfig;
+use crate::config::RetentionConfig;
 use crate::db::Database;
 use crate::db::ReviewRecord;
+use crate::error::Fallible;
 use crate::fsrs::Difficulty;
 use crate::fsrs::Grade;
 use crate::fsrs::Stability;
+use crate::markdown::ClozeBlank;
+use crate::media::remote::RemoteCache;
 use crate::types::card::Card;
+use crate::types::card_hash::CardHash;
+use crate::types::confidence::Confidence;
 use crate::types::date::Date;
+use crate::types::performance::IntervalBounds;
+use crate::types::performance::IntervalFuzz;
 use crate::types::timestamp::Timestamp;
 
 #[derive(Clone)]
@@ -35,19 +58,315 @@ pub struct ServerState {
     pub directory: PathBuf,
     pub macros: Vec<(String, String)>,
     pub total_cards: usize,
+    /// The number of warm-up cards at the start of the session.
+    pub warmup_cards: usize,
+    /// The number of cards due today that the session's limits left out.
+    pub deferred_cards: usize,
     pub session_started_at: Timestamp,
     pub mutable: Arc<Mutex<MutableState>>,
     pub shutdown_tx: Arc<Mutex<Option<Sender<()>>>>,
     pub answer_controls: AnswerControls,
+    pub auto_advance: Option<u64>,
+    pub auto_reveal: Option<u64>,
+    /// Whether to show the source of graded cards, for deck authors.
+    pub author: bool,
+    /// The session's time limit, in minutes.
+    pub time_limit: Option<u64>,
+    pub interval_bounds: IntervalBounds,
+    /// The jitter applied to review intervals.
+    pub interval_fuzz: IntervalFuzz,
+    /// Whether to dim the text around cloze deletions.
+    pub dim_cloze_context: bool,
+    /// Whether to highlight code blocks.
+    pub highlight_code: bool,
+    /// How to draw the blank of hidden cloze deletions.
+    pub cloze_blank: ClozeBlank,
+    /// Whether to show cloze siblings together.
+    pub group_cloze: bool,
+    /// Whether to ask for a confidence rating with each grade.
+    pub track_confidence: bool,
+    /// The base URL to serve media from, if not from this server.
+    pub media_url: Option<String>,
+    /// The cache remote media is served from.
+    pub remote_cache: Arc<RemoteCache>,
+    /// The number of cards between milestone ticks in the progress bar.
+    pub milestones: Option<usize>,
+    /// Whether the session studies every card before grading them.
+    pub two_phase: bool,
+    /// The colors of decks and tags, from the collection's configuration.
+    pub colors: ColorConfig,
+    /// The language and direction of the pages.
+    pub language: LanguageConfig,
+    /// The number of times a card can be shown in a session.
+    pub max_shown: Option<usize>,
+    /// What to do with cards that become leeches, if anything.
+    pub leech_action: Option<LeechAction>,
+    /// The number of lapses after which a card is a leech.
+    pub leech_threshold: usize,
+    /// The number of lapses after which a leech is suspended, if any.
+    pub leech_suspend_threshold: Option<usize>,
+    /// Whether the script updates the page in place after each action.
+    pub no_reload: bool,
+    /// Whether the collection is reloaded when a deck file changes, and the
+    /// page refreshed.
+    pub watch: bool,
+    /// The key that flips through the cards, if any.
+    pub flip_key: Option<String>,
+    /// Whether reviews are kept out of the review store.
+    pub no_store: bool,
+    /// Whether the parse cache is bypassed.
+    pub no_cache: bool,
+    /// Whether to skip the colored flash after grading.
+    pub no_grade_flash: bool,
+    /// Whether to keep answer audio from playing on reveal.
+    pub no_autoplay: bool,
+    /// The color theme of the pages.
+    pub theme: Theme,
+    /// How the completion page shows the session's start and end times.
+    pub time_format: TimeFormat,
+    /// When the card timer starts.
+    pub card_timer: CardTimer,
+    /// The desired retention of each deck.
+    pub retention: RetentionConfig,
+    /// The number of reviews that can be undone, if limited.
+    pub undo_depth: Option<usize>,
+    /// Whether to ignore case when checking typed answers.
+    pub ignore_case: bool,
+    /// How to fold case when it is ignored.
+    pub case_folding: CaseFolding,
+    /// How similar an edited card must be to the card it replaces to keep
+    /// its learning progress.
+    pub edit_similarity: f64,
+    /// Publishes the session's progress to websocket clients.
+    pub live: Arc<watch::Sender<SessionSnapshot>>,
+}
+
+impl ServerState {
+    /// The number of seconds left before the time limit, if any. Zero once
+    /// the limit has passed.
+    pub fn remaining_seconds(&self) -> Option<i64> {
+        let limit: u64 = self.time_limit?;
+        let elapsed: i64 =
+            (Timestamp::now().into_inner() - self.session_started_at.into_inner()).num_seconds();
+        let limit: i64 = i64::try_from(limit.saturating_mul(60)).unwrap_or(i64::MAX);
+        Some((limit - elapsed).max(0))
+    }
+
+    /// How to fold the case of typed answers, if case is ignored.
+    pub fn case_folding(&self) -> Option<CaseFolding> {
+        self.ignore_case.then_some(self.case_folding)
+    }
+
+    /// How the session opens the collection's review store.
+    pub fn store_mode(&self) -> StoreMode {
+        if self.no_store {
+            StoreMode::NoPersistence
+        } else {
+            StoreMode::ReadWrite
+        }
+    }
+
+    /// Whether leeches are detected: if there is a leech action, or a
+    /// threshold to suspend them at.
+    pub fn detects_leeches(&self) -> bool {
+        self.leech_action.is_some() || self.leech_suspend_threshold.is_some()
+    }
 }
 
 pub struct MutableState {
     pub reveal: bool,
+    /// When the current card's answer was revealed.
+    pub revealed_at: Option<Timestamp>,
+    /// When the current card was shown, with its answer hidden. This is
+    /// reset whenever the current card changes, including on undo.
+    pub shown_at: Timestamp,
     pub db: Database,
     pub cache: Cache,
     pub cards: Vec<Card>,
     pub reviews: Vec<Review>,
     pub finished_at: Option<Timestamp>,
+    /// Whether the session was ended without saving its reviews.
+    pub discarded: bool,
+    /// Cards sent to the revisions file in this session, in author mode.
+    pub revised: HashSet<CardHash>,
+    /// Cards flagged for later review in this session. They are tagged when
+    /// the session ends.
+    pub flagged: Vec<Card>,
+    /// Cards suspended in this session. They are suspended in the database
+    /// when the session ends.
+    pub suspended: Vec<CardHash>,
+    /// The review histories to move from edited cards to the cards that
+    /// replace them, oldest first, as `(from, to)`. They are moved when
+    /// reviews are next saved.
+    pub transfers: Vec<(CardHash, CardHash)>,
+    /// The cards skipped in this session, oldest first.
+    pub skips: Vec<Skip>,
+    /// The actions undone, the last undone last, so they can be redone. The
+    /// actions that can be undone are the reviews not yet committed, the
+    /// skips since, and the cards studied.
+    pub redo: Vec<Redo>,
+    /// The last edit, shown on the page after it is saved.
+    pub last_edit: Option<Edit>,
+    /// The last edit, if it was rejected, shown in the edit form so it can be
+    /// fixed.
+    pub rejected_edit: Option<RejectedEdit>,
+    /// The current phase of the session.
+    pub phase: Phase,
+    /// In the study phase, the cards studied so far, in order. They are
+    /// queued again for grading once every card has been studied.
+    pub studied: Vec<Card>,
+    /// When the grading phase of a two-phase session started.
+    pub grading_started_at: Option<Timestamp>,
+    /// Every card in the collection, as of the last time it was loaded.
+    pub loaded: Vec<Card>,
+    /// The number of times each card was graded in this session.
+    pub shown: HashMap<CardHash, usize>,
+    /// The grade of the last action, if it was a grade, flashed on the page
+    /// after it.
+    pub last_grade: Option<Grade>,
+    /// The number of reviews, from the start of the session, that are
+    /// committed: their performance is saved, and they can't be undone.
+    pub committed: usize,
+    /// The session's row in the database, once reviews are committed.
+    pub session_id: Option<i64>,
+    /// The answer typed for the current type-in card, once it is checked.
+    pub typed: Option<String>,
+    /// The number of times the collection was reloaded in this session.
+    pub reloads: usize,
+}
+
+impl MutableState {
+    /// Whether a card is flagged for later review: tagged in its deck, or
+    /// flagged in this session.
+    pub fn is_flagged(&self, card: &Card) -> bool {
+        card.has_tag(FLAG_TAG) || self.flagged.iter().any(|c| c.hash() == card.hash())
+    }
+
+    /// Whether the session has no card to show, though it isn't finished: it
+    /// had no cards, or a reload removed the ones left.
+    pub fn nothing_to_study(&self) -> bool {
+        self.finished_at.is_none() && self.cards.is_empty()
+    }
+
+    /// The last skip, if no card was graded since: it is undone before the
+    /// last review.
+    pub fn last_skip(&self) -> Option<&Skip> {
+        self.skips
+            .last()
+            .filter(|skip| skip.review_count == self.reviews.len())
+    }
+
+    /// Whether every card left was skipped since the last review, so that
+    /// skipping again would only go around the queue.
+    pub fn skipped_all(&self) -> bool {
+        let skipped: usize = self
+            .skips
+            .iter()
+            .rev()
+            .take_while(|skip| skip.review_count == self.reviews.len())
+            .map(|skip| skip.card_hashes.len())
+            .sum();
+        skipped > 0 && skipped >= self.cards.len()
+    }
+
+    /// The grades of a card's reviews, oldest first: the saved ones, then
+    /// the ones in this session, which are saved when it ends.
+    pub fn grades(&self, card_hash: CardHash) -> Fallible<Vec<Grade>> {
+        let mut grades: Vec<Grade> = Vec::new();
+        for hash in self.history(card_hash) {
+            grades.extend(self.db.get_grades(hash)?);
+        }
+        grades.extend(
+            self.reviews
+                .iter()
+                .filter(|review| review.card.hash() == card_hash)
+                .map(|review| review.grade),
+        );
+        Ok(grades)
+    }
+
+    /// The cards whose saved reviews are the card's history, oldest first:
+    /// those it replaced by edits in this session, whose history is not yet
+    /// moved to it, and the card itself.
+    fn history(&self, card_hash: CardHash) -> Vec<CardHash> {
+        let mut hashes: Vec<CardHash> = vec![card_hash];
+        for (from, to) in self.transfers.iter().rev() {
+            if hashes.contains(to) {
+                hashes.push(*from);
+            }
+        }
+        hashes.reverse();
+        hashes
+    }
+
+    /// Whether a card is a leech: tagged as one in its deck, or, if leeches
+    /// are detected, with at least as many lapses as the threshold.
+    pub fn is_leech(&self, state: &ServerState, card: &Card) -> Fallible<bool> {
+        if card.has_tag(LEECH_TAG) {
+            return Ok(true);
+        }
+        if !state.detects_leeches() {
+            return Ok(false);
+        }
+        let lapses: usize = count_lapses(&self.grades(card.hash())?);
+        Ok(lapses >= state.leech_threshold)
+    }
+}
+
+/// The phase of a session. Sessions are graded from the start, unless they
+/// are two-phase, in which case every card is first revealed for study.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Phase {
+    /// Cards are revealed one by one, without grading.
+    Study,
+    /// Cards are revealed and graded.
+    Grade,
+}
+
+/// An edit to a card's source text.
+pub struct Edit {
+    pub old_source: String,
+    pub new_source: String,
+    pub old_hash: CardHash,
+    /// The hashes of the cards parsed from the new source.
+    pub new_hashes: Vec<CardHash>,
+    /// The new cards that kept their learning progress: those that are
+    /// unchanged, or close enough to the card they replace.
+    pub preserved: Vec<CardHash>,
+}
+
+/// An edit that doesn't parse into valid cards. Nothing is written to disk.
+#[derive(Debug)]
+pub struct RejectedEdit {
+    /// The edited text, as parsed.
+    pub content: String,
+    pub message: String,
+    /// The line of the edited text the error points at, zero-indexed.
+    pub line_num: Option<usize>,
+}
+
+/// A skip: the cards shown were sent to the back of the queue, without a
+/// grade.
+#[derive(Clone, Debug)]
+pub struct Skip {
+    /// The cards skipped: the current card, or its cloze group.
+    pub card_hashes: Vec<CardHash>,
+    /// The number of reviews in the session when the cards were skipped.
+    pub review_count: usize,
+    /// Whether the answer was revealed when the cards were skipped.
+    pub revealed: bool,
+}
+
+/// An action that was undone, kept to redo it.
+#[derive(Clone)]
+pub enum Redo {
+    /// The reviews undone together: a review, or those of a cloze group, in
+    /// the order they were made.
+    Reviews(Vec<Review>),
+    /// A skip.
+    Skip(Skip),
+    /// Moving on from a card in the study phase.
+    Study,
 }
 
 #[derive(Clone)]
@@ -60,6 +379,20 @@ pub struct Review {
     pub interval_raw: f64,
     pub interval_days: i64,
     pub due_date: Date,
+    pub revealed_at: Option<Timestamp>,
+    /// The self-rated confidence in the answer, if tracked.
+    pub confidence: Option<Confidence>,
+    /// How long the card took, in milliseconds, as measured by the card
+    /// timer.
+    pub duration_ms: Option<i64>,
+    /// Whether the card was graded together with the previous review's card,
+    /// as part of a cloze group.
+    pub grouped: bool,
+    /// Whether this review made the card a leech.
+    pub leech: bool,
+    /// Whether this review brought the card's lapses to the threshold to
+    /// suspend it at.
+    pub leech_suspend: bool,
 }
 
 impl Review {
@@ -77,6 +410,9 @@ impl Review {
             interval_raw: self.interval_raw,
             interval_days: self.interval_days,
             due_date: self.due_date,
+            revealed_at: self.revealed_at,
+            confidence: self.confidence,
+            duration_ms: self.duration_ms,
         }
     }
 }
@@ -12,31 +12,88 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use clap::ValueEnum;
 use maud::DOCTYPE;
 use maud::Markup;
+use maud::PreEscaped;
 use maud::html;
 
 use crate::cmd::drill::katex::KATEX_CSS_URL;
 use crate::cmd::drill::katex::KATEX_JS_URL;
+use crate::config::LanguageConfig;
 
 const HIGHLIGHT_JS_URL: &str =
     "https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.9.0/highlight.min.js";
 const HIGHLIGHT_CSS_URL: &str =
     "https://cdnjs.cloudflare.com/ajax/libs/highlight.js/11.9.0/styles/github.min.css";
 
-pub fn page_template(body: Markup) -> Markup {
+/// The browser UI color, used in the app manifest.
+pub const THEME_COLOR: &str = "#f8f8f8";
+
+/// The color theme of the pages. The theme can be switched from the session
+/// page, and the choice is kept in the browser, overriding this one.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum Theme {
+    /// Follow the system's preference.
+    Auto,
+    /// Dark text on a light background.
+    Light,
+    /// Light text on a dark background.
+    Dark,
+}
+
+impl Theme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Theme::Auto => "auto",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+}
+
+impl Display for Theme {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Apply the theme chosen with the theme toggle, if any. This runs before the
+/// page is drawn, so it does not flash in the default theme first. It is in
+/// a block so its variable stays out of the scope of `script.js`.
+const THEME_SCRIPT: &str = r#"{
+  const theme = localStorage.getItem("theme");
+  if (theme) {
+    document.documentElement.dataset.theme = theme;
+  }
+}"#;
+
+/// The title of a page, as shown in the browser.
+pub fn document_title(title: &str) -> String {
+    format!("{title} — hashcards")
+}
+
+pub fn page_template(title: &str, body: Markup, language: &LanguageConfig, theme: Theme) -> Markup {
     html! {
         (DOCTYPE)
-        html lang="en" {
+        html lang=(language.lang) dir=(language.dir.as_str()) data-theme=(theme.as_str()) {
             head {
                 meta charset="utf-8";
                 meta name="viewport" content="width=device-width, initial-scale=1";
-                title { "hashcards" }
+                meta name="theme-color" content=(THEME_COLOR);
+                title { (document_title(title)) }
+                link rel="icon" href="/favicon.svg" type="image/svg+xml";
+                link rel="apple-touch-icon" href="/favicon.svg";
+                link rel="manifest" href="/manifest.json";
                 link rel="stylesheet" href=(KATEX_CSS_URL);
                 link rel="stylesheet" href=(HIGHLIGHT_CSS_URL);
                 script defer src=(KATEX_JS_URL) {};
                 script defer src=(HIGHLIGHT_JS_URL) {};
                 link rel="stylesheet" href="/style.css";
+                script { (PreEscaped(THEME_SCRIPT)) }
                 style { ".card-content { opacity: 0; }" }
                 noscript { style { ".card-content { opacity: 1; }" }}
             }
@@ -0,0 +1,144 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The start and end times of a session, as shown on the completion page.
+
+use std::fmt::Write;
+
+use chrono::DateTime;
+use chrono::FixedOffset;
+use chrono::Local;
+use chrono::NaiveDateTime;
+use chrono::Offset;
+use chrono::TimeZone;
+use chrono::Utc;
+use chrono::format::Item;
+use chrono::format::StrftimeItems;
+
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::types::timestamp::Timestamp;
+
+/// The default format of the session's start and end times.
+pub const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// How to show a time: a `strftime`-style format, and the timezone to show
+/// it in, as a fixed offset from UTC. Without a timezone, times are shown in
+/// the local timezone.
+#[derive(Clone, Debug)]
+pub struct TimeFormat {
+    format: String,
+    timezone: Option<FixedOffset>,
+}
+
+impl TimeFormat {
+    /// Check the format and the timezone, so that a bad one fails before the
+    /// session starts rather than on the completion page. The timezone is
+    /// `UTC`, or an offset like `+05:30` or `-08:00`.
+    pub fn new(format: &str, timezone: Option<&str>) -> Fallible<Self> {
+        let items: Vec<Item<'_>> = match StrftimeItems::new(format).parse() {
+            Ok(items) => items,
+            Err(_) => return fail(format!("invalid time format: '{format}'.")),
+        };
+        // Some valid specifiers still fail to format, so try one out.
+        let sample: DateTime<FixedOffset> = DateTime::UNIX_EPOCH.fixed_offset();
+        let mut output: String = String::new();
+        if write!(output, "{}", sample.format_with_items(items.iter())).is_err() {
+            return fail(format!("invalid time format: '{format}'."));
+        }
+        let timezone: Option<FixedOffset> = match timezone {
+            Some(timezone) => Some(parse_timezone(timezone)?),
+            None => None,
+        };
+        Ok(Self {
+            format: format.to_string(),
+            timezone,
+        })
+    }
+
+    /// Format a timestamp, which is in the local timezone.
+    pub fn format(&self, timestamp: Timestamp) -> String {
+        self.format_from(&Local, timestamp.into_inner())
+    }
+
+    /// Format a time in the given timezone.
+    fn format_from<Tz: TimeZone>(&self, source: &Tz, time: NaiveDateTime) -> String {
+        // A time skipped by a change to daylight saving time is taken to be
+        // in UTC.
+        let time: DateTime<FixedOffset> = match source.from_local_datetime(&time).earliest() {
+            Some(time) => time.fixed_offset(),
+            None => time.and_utc().fixed_offset(),
+        };
+        let time: DateTime<FixedOffset> = match self.timezone {
+            Some(timezone) => time.with_timezone(&timezone),
+            None => time,
+        };
+        time.format(&self.format).to_string()
+    }
+}
+
+fn parse_timezone(timezone: &str) -> Fallible<FixedOffset> {
+    if timezone.eq_ignore_ascii_case("utc") || timezone == "Z" {
+        return Ok(Utc.fix());
+    }
+    match timezone.parse::<FixedOffset>() {
+        Ok(offset) => Ok(offset),
+        Err(_) => fail(format!(
+            "invalid timezone: '{timezone}'. Expected UTC, or an offset from UTC like +05:30."
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn noon() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2025-03-01 12:00:00", "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_default_format() -> Fallible<()> {
+        let format = TimeFormat::new(DEFAULT_TIME_FORMAT, None)?;
+        assert_eq!(format.format_from(&Utc, noon()), "2025-03-01 12:00:00");
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_utc_timezone() -> Fallible<()> {
+        let format = TimeFormat::new("%d/%m/%Y %H:%M %z", Some("+05:30"))?;
+        assert_eq!(format.format_from(&Utc, noon()), "01/03/2025 17:30 +0530");
+        let format = TimeFormat::new("%H:%M", Some("-08:00"))?;
+        assert_eq!(format.format_from(&Utc, noon()), "04:00");
+        // From a timezone other than UTC.
+        let source = FixedOffset::east_opt(2 * 3600).unwrap();
+        let format = TimeFormat::new("%H:%M", Some("UTC"))?;
+        assert_eq!(format.format_from(&source, noon()), "10:00");
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_format() {
+        assert!(TimeFormat::new("%Y-%m-%d %Q", None).is_err());
+        assert!(TimeFormat::new("%", None).is_err());
+    }
+
+    #[test]
+    fn test_invalid_timezone() {
+        assert!(TimeFormat::new(DEFAULT_TIME_FORMAT, Some("Mars/Olympus")).is_err());
+        assert!(TimeFormat::new(DEFAULT_TIME_FORMAT, Some("+25:00")).is_err());
+    }
+}
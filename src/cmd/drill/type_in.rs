@@ -0,0 +1,343 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use maud::Markup;
+use maud::html;
+
+use crate::casefold::CaseFolding;
+use crate::casefold::fold_char;
+use crate::fsrs::Grade;
+use crate::markdown::markdown_to_plain_text;
+use icu_normalizer::ComposingNormalizerBorrowed;
+
+/// How a typed answer compares to the expected answer.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Verdict {
+    /// The answers are the same, once normalized.
+    Correct,
+    /// The answers differ by a few typos.
+    Close,
+    Wrong,
+}
+
+impl Verdict {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Verdict::Correct => "correct",
+            Verdict::Close => "close",
+            Verdict::Wrong => "wrong",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Verdict::Correct => "Correct",
+            Verdict::Close => "Almost",
+            Verdict::Wrong => "Wrong",
+        }
+    }
+
+    /// The grade suggested by the comparison. A close answer is not marked
+    /// wrong. The grade buttons are all shown, so this can be overridden.
+    pub fn grade(self) -> Grade {
+        match self {
+            Verdict::Correct | Verdict::Close => Grade::Good,
+            Verdict::Wrong => Grade::Forgot,
+        }
+    }
+}
+
+/// Compare a typed answer to the expected answer, which is Markdown and is
+/// compared as the text it renders to. Both are trimmed, runs of whitespace
+/// are collapsed to a single space, and their case is folded if given a case
+/// folding.
+pub fn judge(typed: &str, expected: &str, folding: Option<CaseFolding>) -> Verdict {
+    let expected: String = markdown_to_plain_text(expected);
+    let folded = |text: &str| -> Vec<char> {
+        characters(text, folding)
+            .into_iter()
+            .flat_map(|(_, folded)| folded.chars().collect::<Vec<char>>())
+            .collect()
+    };
+    let typed: Vec<char> = folded(typed);
+    let expected: Vec<char> = folded(&expected);
+    if typed == expected {
+        Verdict::Correct
+    } else if !typed.is_empty() && levenshtein(&typed, &expected) <= close_band(expected.len()) {
+        Verdict::Close
+    } else {
+        Verdict::Wrong
+    }
+}
+
+/// The characters of an answer, each with the text it is compared as. The
+/// whitespace of the answer is collapsed, and it is put in Unicode
+/// Normalization Form C, so that accents typed as combining characters match.
+/// With a case folding, each character is compared as its folded case, which
+/// can be several characters, e.g. "ss" for "ß". Both `judge` and `diff`
+/// compare answers through this, so they agree.
+fn characters(text: &str, folding: Option<CaseFolding>) -> Vec<(char, String)> {
+    let text: String = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let nfc = ComposingNormalizerBorrowed::new_nfc();
+    nfc.normalize(&text)
+        .chars()
+        .map(|c| match folding {
+            Some(folding) => (c, fold_char(c, folding)),
+            None => (c, c.to_string()),
+        })
+        .collect()
+}
+
+/// The number of edits by which an answer of the given length can be off and
+/// still be close: one for every five characters. Answers shorter than that
+/// must be exact, since a single edit can turn them into another word.
+fn close_band(length: usize) -> usize {
+    length / 5
+}
+
+/// The number of single-character insertions, deletions, and substitutions
+/// needed to turn one string into the other.
+pub fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current: Vec<usize> = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            let deletion = previous[j + 1] + 1;
+            let insertion = current[j] + 1;
+            current.push(substitution.min(deletion).min(insertion));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// A run of characters in the diff of a typed answer against the expected
+/// answer.
+#[derive(Debug, PartialEq)]
+enum Span {
+    /// In both answers.
+    Same(String),
+    /// Typed, but not in the expected answer.
+    Extra(String),
+    /// In the expected answer, but not typed.
+    Missing(String),
+}
+
+/// The diff of the typed answer against the expected answer, from their
+/// longest common subsequence. Characters are the same if they are compared
+/// as the same text, and are shown as typed.
+fn diff(typed: &str, expected: &str, folding: Option<CaseFolding>) -> Vec<Span> {
+    let a: Vec<(char, String)> = characters(typed, folding);
+    let b: Vec<(char, String)> = characters(expected, folding);
+    let same = |x: &(char, String), y: &(char, String)| x.1 == y.1;
+    // The length of the longest common subsequence of the suffixes.
+    let mut lcs: Vec<Vec<usize>> = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if same(&a[i], &b[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut spans: Vec<Span> = Vec::new();
+    let mut push = |span: Span| match (spans.last_mut(), span) {
+        (Some(Span::Same(s)), Span::Same(t))
+        | (Some(Span::Extra(s)), Span::Extra(t))
+        | (Some(Span::Missing(s)), Span::Missing(t)) => s.push_str(&t),
+        (_, span) => spans.push(span),
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && same(&a[i], &b[j]) {
+            push(Span::Same(a[i].0.to_string()));
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push(Span::Extra(a[i].0.to_string()));
+            i += 1;
+        } else {
+            push(Span::Missing(b[j].0.to_string()));
+            j += 1;
+        }
+    }
+    spans
+}
+
+/// The result of checking a typed answer: the verdict, and the diff of the
+/// typed answer against the expected one.
+pub fn render_result(typed: &str, expected: &str, folding: Option<CaseFolding>) -> Markup {
+    let verdict: Verdict = judge(typed, expected, folding);
+    let expected: String = markdown_to_plain_text(expected);
+    html! {
+        div.type-in-result.(verdict.as_str()) {
+            span.type-in-verdict { (verdict.label()) }
+            @if verdict != Verdict::Correct {
+                span.type-in-diff title="Your answer, against the expected answer." {
+                    @for span in diff(typed, &expected, folding) {
+                        @match span {
+                            Span::Same(text) => span { (text) },
+                            Span::Extra(text) => del.diff-extra { (text) },
+                            Span::Missing(text) => ins.diff-missing { (text) },
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The field to type the answer in, in place of the reveal button. It belongs
+/// to the `type-in` form.
+pub fn render_field() -> Markup {
+    html! {
+        input #type-in-answer form="type-in" type="text" name="typed" autocomplete="off" autocapitalize="off" spellcheck="false" autofocus placeholder="Type the answer";
+        input id="reveal" form="type-in" type="submit" name="action" value="Check" title="Check the answer. Shortcut: enter.";
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_judge() {
+        assert_eq!(judge("le chat", "le chat", None), Verdict::Correct);
+        // Whitespace is normalized.
+        assert_eq!(judge("  le   chat ", "le chat", None), Verdict::Correct);
+        // Case only matters if it isn't ignored.
+        assert_eq!(
+            judge("Le Chat", "le chat", Some(CaseFolding::Full)),
+            Verdict::Correct
+        );
+        assert_eq!(judge("Le chat", "le chat", None), Verdict::Close);
+        // A typo is close.
+        assert_eq!(judge("le chst", "le chat", None), Verdict::Close);
+        // A swap is two edits.
+        assert_eq!(judge("le chta", "le chat", None), Verdict::Wrong);
+        assert_eq!(judge("le chien", "le chat", None), Verdict::Wrong);
+        assert_eq!(judge("", "le chat", None), Verdict::Wrong);
+        // Short answers must be exact.
+        assert_eq!(judge("cta", "cat", None), Verdict::Wrong);
+        assert_eq!(judge("ca", "cat", None), Verdict::Wrong);
+        assert_eq!(judge("chst", "chat", None), Verdict::Wrong);
+        assert_eq!(judge("chats", "chat", None), Verdict::Wrong);
+        // The expected answer is compared as rendered.
+        assert_eq!(judge("Paris", "**Paris**", None), Verdict::Correct);
+        assert_eq!(judge("le chat", "*le* `chat`", None), Verdict::Correct);
+    }
+
+    #[test]
+    fn test_judge_case_folding() {
+        let full = Some(CaseFolding::Full);
+        let simple = Some(CaseFolding::Simple);
+        // "ß" folds to "ss" with full folding only, and "ẞ" to "ß" with both.
+        assert_eq!(judge("STRASSE", "Straße", full), Verdict::Correct);
+        assert_eq!(judge("strasse", "Straße", simple), Verdict::Wrong);
+        assert_eq!(judge("STRAẞE", "Straße", simple), Verdict::Correct);
+        // "İ" folds to "i" and a combining dot, so the dot still counts.
+        assert_eq!(
+            judge("i\u{0307}stanbul", "İSTANBUL", full),
+            Verdict::Correct
+        );
+        assert_eq!(judge("istanbul", "İstanbul", full), Verdict::Close);
+        assert_eq!(judge("ISTANBUL", "istanbul", full), Verdict::Correct);
+        // The final sigma folds like any other sigma.
+        assert_eq!(judge("ΟΔΟΣ", "οδος", full), Verdict::Correct);
+        assert_eq!(judge("οδοσ", "οδος", simple), Verdict::Correct);
+        assert_eq!(judge("οδοσ", "οδος", None), Verdict::Wrong);
+        // Accents typed as combining characters match.
+        assert_eq!(judge("cafe\u{0301}", "café", None), Verdict::Correct);
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        let chars = |s: &str| s.chars().collect::<Vec<char>>();
+        assert_eq!(levenshtein(&chars("kitten"), &chars("sitting")), 3);
+        assert_eq!(levenshtein(&chars(""), &chars("abc")), 3);
+        assert_eq!(levenshtein(&chars("été"), &chars("ete")), 2);
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(
+            diff("le chta", "le chat", None),
+            vec![
+                Span::Same("le ch".to_string()),
+                Span::Extra("t".to_string()),
+                Span::Same("a".to_string()),
+                Span::Missing("t".to_string()),
+            ]
+        );
+        assert_eq!(
+            diff("Cat", "cats", Some(CaseFolding::Full)),
+            vec![
+                Span::Same("Cat".to_string()),
+                Span::Missing("s".to_string())
+            ]
+        );
+    }
+
+    /// The diff compares characters by their folded case, like `judge`.
+    #[test]
+    fn test_diff_case_folding() {
+        let full = Some(CaseFolding::Full);
+        assert_eq!(
+            diff("ΟΔΟΣ", "οδος", full),
+            vec![Span::Same("ΟΔΟΣ".to_string())]
+        );
+        // "İ" folds to "i" and a combining dot, so "I" doesn't match it.
+        assert_eq!(judge("ISTANBUL", "İstanbul", full), Verdict::Close);
+        assert_eq!(
+            diff("ISTANBUL", "İstanbul", full),
+            vec![
+                Span::Extra("I".to_string()),
+                Span::Missing("İ".to_string()),
+                Span::Same("STANBUL".to_string()),
+            ]
+        );
+        // "ß" is compared as "ss", and is one character in the diff.
+        assert_eq!(
+            diff("STRASE", "straße", full),
+            vec![
+                Span::Same("STRA".to_string()),
+                Span::Extra("S".to_string()),
+                Span::Missing("ß".to_string()),
+                Span::Same("E".to_string()),
+            ]
+        );
+        assert_eq!(judge("STRASE", "straße", full), Verdict::Close);
+        // Accents typed as combining characters are one character.
+        assert_eq!(
+            diff("cafe\u{0301}s", "café", None),
+            vec![
+                Span::Same("caf\u{00e9}".to_string()),
+                Span::Extra("s".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_result() {
+        let html = render_result("cat", "cat", None).into_string();
+        assert_eq!(
+            html,
+            "<div class=\"type-in-result correct\"><span class=\"type-in-verdict\">Correct</span></div>"
+        );
+        let html = render_result("ct", "cat", None).into_string();
+        assert!(html.contains("<ins class=\"diff-missing\">a</ins>"));
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::collection::Collection;
+use crate::config::CollectionConfig;
+use crate::db::Database;
+use crate::error::Fallible;
+use crate::types::aliases::DeckName;
+use crate::types::card::Card;
+use crate::types::card_hash::CardHash;
+use crate::types::date::Date;
+
+/// The number of days, starting today, counted as "this week".
+const WEEK_DAYS: i64 = 7;
+
+/// How many cards are due, as printed by `hashcards due --json`.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct DueReport {
+    /// The local date the counts are relative to.
+    today: Date,
+    total: DueCounts,
+    decks: Vec<DeckDue>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct DeckDue {
+    deck_name: DeckName,
+    #[serde(flatten)]
+    counts: DueCounts,
+}
+
+#[derive(Serialize, Debug, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+struct DueCounts {
+    /// Cards due before today.
+    overdue: usize,
+    /// Cards due today or earlier, including new cards: what a drill session
+    /// started now would show, before any limits.
+    today: usize,
+    /// Cards due in the next seven days, including today.
+    week: usize,
+    /// The earliest due date after today, if any.
+    next_due: Option<Date>,
+}
+
+impl DueCounts {
+    /// Count a card with the given due date. New cards have none, and are due
+    /// today.
+    fn add(&mut self, due_date: Option<Date>, today: Date) {
+        let days: i64 = match due_date {
+            Some(due_date) => (due_date.into_inner() - today.into_inner()).num_days(),
+            None => 0,
+        };
+        if days < 0 {
+            self.overdue += 1;
+        }
+        if days <= 0 {
+            self.today += 1;
+        }
+        if days < WEEK_DAYS {
+            self.week += 1;
+        }
+        if let Some(due_date) = due_date.filter(|_| days > 0) {
+            self.next_due = Some(self.next_due.map_or(due_date, |next| next.min(due_date)));
+        }
+    }
+}
+
+/// Print how many cards are due, overall and per deck, without starting a
+/// session.
+pub fn print_due(directory: Option<String>, json: bool) -> Fallible<()> {
+    let coll = Collection::new(directory)?;
+    let report = due_report(&coll.cards, &coll.db, &coll.config, Date::today())?;
+    print!("{}", format_report(&report, json)?);
+    Ok(())
+}
+
+/// The report as JSON, or as a line per deck and a total.
+fn format_report(report: &DueReport, json: bool) -> Fallible<String> {
+    if json {
+        return Ok(format!("{}\n", serde_json::to_string_pretty(report)?));
+    }
+    let mut text = String::new();
+    for deck in &report.decks {
+        text.push_str(&format!(
+            "{}: {}\n",
+            deck.deck_name,
+            format_counts(&deck.counts)
+        ));
+    }
+    text.push_str(&format!("Total: {}\n", format_counts(&report.total)));
+    Ok(text)
+}
+
+fn format_counts(counts: &DueCounts) -> String {
+    let mut line = format!(
+        "{} due today ({} overdue), {} this week",
+        counts.today, counts.overdue, counts.week
+    );
+    if let Some(next_due) = counts.next_due {
+        line.push_str(&format!(", next due {next_due}"));
+    }
+    line
+}
+
+/// The due date of each card that isn't suspended. New cards, including
+/// those not yet in the database, have none.
+pub fn card_due_dates<'a>(
+    cards: &'a [Card],
+    db: &Database,
+) -> Fallible<Vec<(&'a Card, Option<Date>)>> {
+    let db_hashes: HashSet<CardHash> = db.card_hashes()?;
+    let due_dates: HashMap<CardHash, Option<Date>> = db.due_dates()?;
+    let mut result: Vec<(&Card, Option<Date>)> = Vec::new();
+    for card in cards {
+        let hash = card.hash();
+        let due_date: Option<Date> = match due_dates.get(&hash) {
+            Some(due_date) => *due_date,
+            // Suspended cards are never due.
+            None if db_hashes.contains(&hash) => continue,
+            // Cards not yet in the database are new.
+            None => None,
+        };
+        result.push((card, due_date));
+    }
+    Ok(result)
+}
+
+fn due_report(
+    cards: &[Card],
+    db: &Database,
+    config: &CollectionConfig,
+    today: Date,
+) -> Fallible<DueReport> {
+    let mut total = DueCounts::default();
+    let mut decks: BTreeMap<DeckName, DueCounts> = BTreeMap::new();
+    for (card, due_date) in card_due_dates(cards, db)? {
+        total.add(due_date, today);
+        decks
+            .entry(card.deck_name().clone())
+            .or_default()
+            .add(due_date, today);
+    }
+    let mut decks: Vec<DeckDue> = decks
+        .into_iter()
+        .map(|(deck_name, counts)| DeckDue { deck_name, counts })
+        .collect();
+    config.sort_decks(&mut decks, |deck| &deck.deck_name);
+    Ok(DueReport {
+        today,
+        total,
+        decks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::helper::create_tmp_copy_of_test_directory;
+    use crate::types::card::CardContent;
+    use crate::types::performance::Performance;
+    use crate::types::performance::ReviewedPerformance;
+    use crate::types::timestamp::Timestamp;
+
+    fn make_card(deck: &str, question: &str) -> Card {
+        Card::new(
+            deck.to_string(),
+            PathBuf::from(format!("{deck}.md")),
+            (0, 1),
+            CardContent::new_basic(question, "A"),
+            Vec::new(),
+        )
+    }
+
+    fn date(day: u32) -> Date {
+        Date::new(NaiveDate::from_ymd_opt(2026, 3, day).unwrap())
+    }
+
+    /// Add a card to the database, due on the given date.
+    fn insert_due(db: &Database, card: &Card, due_date: Date) -> Fallible<()> {
+        let now = Timestamp::now();
+        db.insert_card(card.hash(), now)?;
+        let performance = ReviewedPerformance {
+            last_reviewed_at: now,
+            stability: 1.0,
+            difficulty: 5.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date,
+            review_count: 1,
+        };
+        db.update_card_performance(card.hash(), Performance::Reviewed(performance))
+    }
+
+    #[test]
+    fn test_due_report() -> Fallible<()> {
+        let db = Database::new(":memory:")?;
+        let new = make_card("A", "new");
+        let overdue = make_card("A", "overdue");
+        let later = make_card("A", "later");
+        let tomorrow = make_card("B", "tomorrow");
+        let suspended = make_card("B", "suspended");
+        insert_due(&db, &overdue, date(1))?;
+        insert_due(&db, &later, date(20))?;
+        insert_due(&db, &tomorrow, date(11))?;
+        insert_due(&db, &suspended, date(1))?;
+        db.suspend_card(suspended.hash())?;
+        let cards = vec![new, overdue, later, tomorrow, suspended];
+        let report = due_report(&cards, &db, &CollectionConfig::default(), date(10))?;
+        assert_eq!(
+            report.total,
+            DueCounts {
+                overdue: 1,
+                today: 2,
+                week: 3,
+                next_due: Some(date(11)),
+            }
+        );
+        assert_eq!(
+            report.decks,
+            vec![
+                DeckDue {
+                    deck_name: "A".to_string(),
+                    counts: DueCounts {
+                        overdue: 1,
+                        today: 2,
+                        week: 2,
+                        next_due: Some(date(20)),
+                    },
+                },
+                DeckDue {
+                    deck_name: "B".to_string(),
+                    counts: DueCounts {
+                        overdue: 0,
+                        today: 0,
+                        week: 1,
+                        next_due: Some(date(11)),
+                    },
+                },
+            ]
+        );
+        let json = serde_json::to_value(&report)?;
+        assert_eq!(json["today"], "2026-03-10");
+        assert_eq!(json["decks"][1]["deckName"], "B");
+        assert_eq!(json["decks"][1]["nextDue"], "2026-03-11");
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_report() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+        let coll = Collection::new(Some(directory))?;
+        let today = Date::today();
+        let report = due_report(&coll.cards, &coll.db, &coll.config, today)?;
+        let text = format_report(&report, false)?;
+        assert_eq!(
+            text,
+            "Deck: 2 due today (0 overdue), 2 this week\nTotal: 2 due today (0 overdue), 2 this week\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&format_report(&report, true)?)?;
+        assert_eq!(json["today"], today.to_string());
+        Ok(())
+    }
+}
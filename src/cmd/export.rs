@@ -12,33 +12,101 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod apkg;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::hash_map::Entry;
+use std::fmt::Display;
+use std::fmt::Formatter;
 use std::fs::write;
+use std::path::Path;
 
+use clap::ValueEnum;
 use serde::Serialize;
 
+use crate::cmd::export::apkg::export_apkg;
 use crate::collection::Collection;
+use crate::csv::CARD_COLUMNS;
+use crate::csv::card_type_column;
+use crate::csv::csv_row;
 use crate::db::ReviewRow;
 use crate::db::SessionRow;
 use crate::error::Fallible;
+use crate::error::fail;
 use crate::fsrs::Difficulty;
 use crate::fsrs::Grade;
 use crate::fsrs::Interval;
 use crate::fsrs::Stability;
 use crate::types::aliases::DeckName;
+use crate::types::card::Card;
 use crate::types::card::CardContent;
+use crate::types::card::with_brackets;
+use crate::types::card::with_numbered_brackets;
 use crate::types::card_hash::CardHash;
+use crate::types::confidence::Confidence;
 use crate::types::date::Date;
 use crate::types::performance::Performance;
 use crate::types::performance::ReviewedPerformance;
 use crate::types::timestamp::Timestamp;
+use crate::utils::glob_match;
+
+/// The format of an export.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    /// The cards, their scheduling state, and the review history, as JSON.
+    Json,
+    /// The source text of the cards, separated by blank lines.
+    Text,
+    /// A CSV file with a card per row, and the columns `deck`, `type`,
+    /// `front`, `back`, and `tags`, for editing in a spreadsheet.
+    Csv,
+    /// An Anki package (`.apkg`), with the cards as new notes, and their
+    /// media. Requires `--output`.
+    Apkg,
+}
 
-pub fn export_collection(directory: Option<String>, output: Option<String>) -> Fallible<()> {
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Json => write!(f, "json"),
+            ExportFormat::Text => write!(f, "text"),
+            ExportFormat::Csv => write!(f, "csv"),
+            ExportFormat::Apkg => write!(f, "apkg"),
+        }
+    }
+}
+
+pub fn export_collection(
+    directory: Option<String>,
+    output: Option<String>,
+    deck: Option<String>,
+    format: ExportFormat,
+    no_prefixes: bool,
+) -> Fallible<()> {
+    if no_prefixes && format != ExportFormat::Text {
+        return fail("--no-prefixes only applies to the text format.");
+    }
     let coll: Collection = Collection::new(directory)?;
-    let export: Export = get_export(coll)?;
-    let json = serde_json::to_string_pretty(&export)?;
+    let text: String = match format {
+        ExportFormat::Json => {
+            let export: Export = get_export(coll, deck)?;
+            serde_json::to_string_pretty(&export)?
+        }
+        ExportFormat::Text => get_text_export(&coll, deck.as_deref(), no_prefixes)?,
+        ExportFormat::Csv => get_csv_export(&coll, deck.as_deref())?,
+        ExportFormat::Apkg => {
+            let Some(output) = output else {
+                return fail(
+                    "--format apkg requires --output, since the package is a binary file.",
+                );
+            };
+            return export_apkg(&coll, deck.as_deref(), Path::new(&output));
+        }
+    };
     match output {
-        Some(path) => write(path, json)?,
-        None => println!("{}", json),
+        Some(path) => write(path, text)?,
+        None => println!("{}", text),
     }
     Ok(())
 }
@@ -58,6 +126,7 @@ struct CardExport {
     deck_name: DeckName,
     location: LocationExport,
     content: CardContentExport,
+    extra: Option<String>,
     performance: Option<PerformanceExport>,
 }
 
@@ -81,6 +150,28 @@ enum CardContentExport {
         start: usize,
         end: usize,
     },
+    GroupedCloze {
+        text: String,
+        deletions: Vec<DeletionExport>,
+        group: usize,
+    },
+    TypeIn {
+        prompt: String,
+        answer: String,
+    },
+    Reversible {
+        question: String,
+        answer: String,
+        reversed: bool,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeletionExport {
+    group: usize,
+    start: usize,
+    end: usize,
 }
 
 #[derive(Serialize)]
@@ -116,17 +207,175 @@ struct ReviewExport {
     interval_raw: Interval,
     interval_days: i64,
     due_date: Date,
+    revealed_at: Option<Timestamp>,
+    confidence: Option<Confidence>,
+    duration_ms: Option<i64>,
 }
 
-fn get_export(coll: Collection) -> Fallible<Export> {
-    let cards: Vec<CardExport> = get_card_export(&coll)?;
-    let sessions: Vec<SessionExport> = get_session_export(&coll)?;
+/// Export the collection. If a deck pattern is given, only the cards in
+/// matching decks are exported, along with their reviews, so a deck can be
+/// shared without the rest of the collection.
+fn get_export(coll: Collection, deck: Option<String>) -> Fallible<Export> {
+    let cards: Vec<&Card> = select_cards(&coll, deck.as_deref())?;
+    let hashes: Option<HashSet<CardHash>> = deck
+        .as_ref()
+        .map(|_| cards.iter().map(|card| card.hash()).collect());
+    let cards: Vec<CardExport> = get_card_export(&coll, &cards)?;
+    let sessions: Vec<SessionExport> = get_session_export(&coll, hashes.as_ref())?;
     Ok(Export { cards, sessions })
 }
 
-fn get_card_export(coll: &Collection) -> Fallible<Vec<CardExport>> {
-    let mut cards: Vec<CardExport> = Vec::new();
-    for card in coll.cards.iter() {
+/// The cards in decks matching the pattern, or every card.
+fn select_cards<'a>(coll: &'a Collection, deck: Option<&str>) -> Fallible<Vec<&'a Card>> {
+    match deck {
+        Some(pattern) => {
+            let cards: Vec<&Card> = coll
+                .cards
+                .iter()
+                .filter(|card| glob_match(pattern, card.deck_name()))
+                .collect();
+            if cards.is_empty() {
+                return fail(format!("no decks match the pattern '{pattern}'."));
+            }
+            Ok(cards)
+        }
+        None => Ok(coll.cards.iter().collect()),
+    }
+}
+
+/// Export the source text of the cards, in the order they appear in their
+/// files, separated by blank lines. The deletions of a cloze card are merged
+/// back into a single card, so the text parses back into the same cards.
+/// Without prefixes, the text reads better, but does not parse.
+fn get_text_export(coll: &Collection, deck: Option<&str>, no_prefixes: bool) -> Fallible<String> {
+    let cards: Vec<&Card> = select_cards(coll, deck)?;
+    let texts: Vec<String> = group_families(cards)
+        .into_iter()
+        .map(|(card, deletions)| card_text(card, deletions, no_prefixes))
+        .collect();
+    Ok(texts.join("\n\n"))
+}
+
+/// Export the cards as CSV, with a header row, and a card per row, in the
+/// order they appear in their files. A cloze card's deletions are merged
+/// back into a single row, whose front is the text with the deletions in
+/// brackets. Deck names are written with `::` between their components,
+/// whatever the collection's separator, so `import --format csv` writes the
+/// cards back into the same paths.
+fn get_csv_export(coll: &Collection, deck: Option<&str>) -> Fallible<String> {
+    let cards: Vec<&Card> = select_cards(coll, deck)?;
+    let separator: &str = coll.config.deck_separator();
+    let mut rows: Vec<String> = vec![csv_row(&CARD_COLUMNS)];
+    for (card, deletions) in group_families(cards) {
+        let deck: String = card
+            .deck_name()
+            .split(separator)
+            .collect::<Vec<&str>>()
+            .join("::");
+        let (front, back): (String, &str) = match card.content() {
+            CardContent::Basic { question, answer }
+            | CardContent::TypeIn {
+                prompt: question,
+                answer,
+            }
+            | CardContent::Reversible {
+                question, answer, ..
+            } => (question.clone(), answer),
+            CardContent::Cloze { text, .. } => (with_brackets(text, &deletions), ""),
+            CardContent::GroupedCloze {
+                text, deletions, ..
+            } => (with_numbered_brackets(text, deletions), ""),
+        };
+        let tags: String = card.tags().join(" ");
+        rows.push(csv_row(&[
+            &deck,
+            card_type_column(card),
+            &front,
+            back,
+            &tags,
+        ]));
+    }
+    Ok(rows.join("\n"))
+}
+
+/// Sort the cards in the order they appear in their files, and merge the
+/// deletions of each cloze family. Each entry is a card, and the deletions
+/// of its family, which are empty for basic and type-in cards. Cards with
+/// numbered deletions already have those of their whole family, and a
+/// reversible card is a single entry for both directions.
+fn group_families(mut cards: Vec<&Card>) -> Vec<(&Card, Vec<(usize, usize)>)> {
+    cards.sort_by_key(|card| (card.file_path(), card.range()));
+    let mut entries: Vec<(&Card, Vec<(usize, usize)>)> = Vec::new();
+    let mut families: HashMap<CardHash, usize> = HashMap::new();
+    for card in cards {
+        match (card.family_hash(), card.content()) {
+            (Some(family), CardContent::Cloze { start, end, .. }) => match families.get(&family) {
+                Some(index) => entries[*index].1.push((*start, *end)),
+                None => {
+                    families.insert(family, entries.len());
+                    entries.push((card, vec![(*start, *end)]));
+                }
+            },
+            (Some(family), CardContent::GroupedCloze { .. } | CardContent::Reversible { .. }) => {
+                if let Entry::Vacant(entry) = families.entry(family) {
+                    entry.insert(entries.len());
+                    entries.push((card, Vec::new()));
+                }
+            }
+            _ => entries.push((card, Vec::new())),
+        }
+    }
+    entries
+}
+
+/// The source text of a card, with the given cloze deletions, its extra
+/// notes, and its tags.
+fn card_text(card: &Card, deletions: Vec<(usize, usize)>, no_prefixes: bool) -> String {
+    let cloze = |text: String| -> String {
+        if no_prefixes {
+            text
+        } else {
+            format!("C: {text}")
+        }
+    };
+    let text: String = match card.content() {
+        CardContent::Basic { question, answer }
+        | CardContent::TypeIn {
+            prompt: question,
+            answer,
+        }
+        | CardContent::Reversible {
+            question, answer, ..
+        } => {
+            if no_prefixes {
+                format!("{question}\n{answer}")
+            } else {
+                card.content().to_source_text()
+            }
+        }
+        CardContent::Cloze { text, .. } => cloze(with_brackets(text, &deletions)),
+        CardContent::GroupedCloze {
+            text,
+            deletions: numbered,
+            ..
+        } => cloze(with_numbered_brackets(text, numbered)),
+    };
+    let text: String = match (card.extra(), no_prefixes) {
+        (Some(extra), false) => format!("{text}\nE: {extra}"),
+        (Some(extra), true) => format!("{text}\n{extra}"),
+        (None, _) => text,
+    };
+    if card.tags().is_empty() {
+        text
+    } else {
+        let tags: Vec<String> = card.tags().iter().map(|tag| format!("#{tag}")).collect();
+        format!("{text}\n{}", tags.join(" "))
+    }
+}
+
+fn get_card_export(coll: &Collection, cards: &[&Card]) -> Fallible<Vec<CardExport>> {
+    let mut exports: Vec<CardExport> = Vec::new();
+    for card in cards.iter() {
         let p = coll.db.get_card_performance_opt(card.hash())?;
         let ce = CardExport {
             hash: card.hash(),
@@ -147,12 +396,42 @@ fn get_card_export(coll: &Collection) -> Fallible<Vec<CardExport>> {
                     start: *start,
                     end: *end,
                 },
+                CardContent::GroupedCloze {
+                    text,
+                    deletions,
+                    group,
+                } => CardContentExport::GroupedCloze {
+                    text: text.clone(),
+                    deletions: deletions
+                        .iter()
+                        .map(|(group, start, end)| DeletionExport {
+                            group: *group,
+                            start: *start,
+                            end: *end,
+                        })
+                        .collect(),
+                    group: *group,
+                },
+                CardContent::TypeIn { prompt, answer } => CardContentExport::TypeIn {
+                    prompt: prompt.clone(),
+                    answer: answer.clone(),
+                },
+                CardContent::Reversible {
+                    question,
+                    answer,
+                    reversed,
+                } => CardContentExport::Reversible {
+                    question: question.clone(),
+                    answer: answer.clone(),
+                    reversed: *reversed,
+                },
             },
+            extra: card.extra().map(str::to_string),
             performance: export_performance(p),
         };
-        cards.push(ce);
+        exports.push(ce);
     }
-    Ok(cards)
+    Ok(exports)
 }
 
 fn export_performance(p: Option<Performance>) -> Option<PerformanceExport> {
@@ -181,20 +460,34 @@ fn export_performance(p: Option<Performance>) -> Option<PerformanceExport> {
     }
 }
 
-fn get_session_export(coll: &Collection) -> Fallible<Vec<SessionExport>> {
+/// Export the sessions. If `hashes` is given, only reviews of those cards
+/// are exported, and sessions with no such reviews are left out.
+fn get_session_export(
+    coll: &Collection,
+    hashes: Option<&HashSet<CardHash>>,
+) -> Fallible<Vec<SessionExport>> {
     let sessions = coll.db.get_all_sessions()?;
     let mut session_exports: Vec<SessionExport> = Vec::new();
     for session in sessions.into_iter() {
-        let session_export = export_session(coll, session)?;
-        session_exports.push(session_export);
+        let session_export = export_session(coll, session, hashes)?;
+        if hashes.is_none() || !session_export.reviews.is_empty() {
+            session_exports.push(session_export);
+        }
     }
     Ok(session_exports)
 }
 
-fn export_session(coll: &Collection, session: SessionRow) -> Fallible<SessionExport> {
+fn export_session(
+    coll: &Collection,
+    session: SessionRow,
+    hashes: Option<&HashSet<CardHash>>,
+) -> Fallible<SessionExport> {
     let reviews = coll.db.get_reviews_for_session(session.session_id)?;
     let mut review_exports: Vec<ReviewExport> = Vec::new();
     for review in reviews.into_iter() {
+        if hashes.is_some_and(|hashes| !hashes.contains(&review.data.card_hash)) {
+            continue;
+        }
         let review_export = export_review(review);
         review_exports.push(review_export)
     }
@@ -217,24 +510,40 @@ fn export_review(review: ReviewRow) -> ReviewExport {
         interval_raw: review.data.interval_raw,
         interval_days: review.data.interval_days,
         due_date: review.data.due_date,
+        revealed_at: review.data.revealed_at,
+        confidence: review.data.confidence,
+        duration_ms: review.data.duration_ms,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs::create_dir_all;
+    use std::fs::read_to_string;
     use std::path::PathBuf;
 
     use super::*;
+    use crate::cmd::import::ImportFormat;
+    use crate::cmd::import::import_cards;
     use crate::db::ReviewRecord;
+    use crate::error::ErrorReport;
     use crate::helper::create_tmp_copy_of_test_directory;
     use crate::helper::create_tmp_directory;
+    use crate::ignore::IgnoreRules;
+    use crate::parser::DEFAULT_DECK_SEPARATOR;
+    use crate::parser::Parser;
     use crate::parser::parse_deck;
 
     #[test]
     fn test_full_export() -> Fallible<()> {
         let dir = create_tmp_copy_of_test_directory()?;
         let mut coll = Collection::new(Some(dir.clone()))?;
-        let deck = parse_deck(&PathBuf::from(dir.clone()))?;
+        let deck = parse_deck(
+            &PathBuf::from(dir.clone()),
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )?;
         let now = Timestamp::now();
         let mut reviews = Vec::new();
         for card in deck {
@@ -258,15 +567,198 @@ mod tests {
                 interval_raw: 1.0,
                 interval_days: 1,
                 due_date: now.date(),
+                revealed_at: None,
+                confidence: None,
+                duration_ms: None,
             };
             reviews.push(review);
         }
         coll.db.save_session(now, now, reviews)?;
         // Export.
-        export_collection(Some(dir.clone()), None)?;
+        export_collection(Some(dir.clone()), None, None, ExportFormat::Json, false)?;
         let tmp = create_tmp_directory()?;
         let output = tmp.join("export.json").display().to_string();
-        export_collection(Some(dir), Some(output))?;
+        export_collection(Some(dir), Some(output), None, ExportFormat::Json, false)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_deck_export() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        write(dir.join("Geology.md"), "Q: a\nA: b\n")?;
+        write(dir.join("Biology.md"), "Q: c\nA: d\n")?;
+        let mut coll = Collection::new(Some(dir.display().to_string()))?;
+        let now = Timestamp::now();
+        let mut reviews = Vec::new();
+        for card in coll.cards.iter() {
+            coll.db.insert_card(card.hash(), now)?;
+            reviews.push(ReviewRecord {
+                card_hash: card.hash(),
+                reviewed_at: now,
+                grade: Grade::Good,
+                stability: 1.0,
+                difficulty: 3.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: now.date(),
+                revealed_at: None,
+                confidence: None,
+                duration_ms: None,
+            });
+        }
+        coll.db.save_session(now, now, reviews)?;
+        coll.cards.sort_by_key(|card| card.deck_name().clone());
+
+        // Only the matching deck, and its reviews, are exported.
+        let geology = coll.cards[1].hash();
+        let export = get_export(coll, Some("Geo*".to_string()))?;
+        assert_eq!(export.cards.len(), 1);
+        assert_eq!(export.cards[0].hash, geology);
+        assert_eq!(export.sessions.len(), 1);
+        assert_eq!(export.sessions[0].reviews.len(), 1);
+        assert_eq!(export.sessions[0].reviews[0].hash, geology);
+
+        // A pattern that matches nothing is an error.
+        let result = export_collection(
+            Some(dir.display().to_string()),
+            None,
+            Some("Chemistry".to_string()),
+            ExportFormat::Json,
+            false,
+        );
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "error: no decks match the pattern 'Chemistry'."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_export() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        write(
+            dir.join("Geology.md"),
+            "Q: What is a rock?\nA: A solid.\n#rocks\n\nC: [Granite] is an [igneous] rock.\n\nQ: Multiple\nlines?\nA: Yes.\n",
+        )?;
+        write(dir.join("Biology.md"), "Q: c\nA: d\n")?;
+        let coll = Collection::new(Some(dir.display().to_string()))?;
+
+        let text = get_text_export(&coll, Some("Geo*"), false)?;
+        assert_eq!(
+            text,
+            "Q: What is a rock?\nA: A solid.\n#rocks\n\nC: [Granite] is an [igneous] rock.\n\nQ: Multiple\nlines?\nA: Yes."
+        );
+
+        // The text parses back into the same cards.
+        let parser = Parser::new("Geology".to_string(), dir.join("Export.md"));
+        let parsed: HashSet<CardHash> = parser
+            .parse(&text)
+            .map_err(|e| ErrorReport::new(e.to_string()))?
+            .iter()
+            .map(|card| card.hash())
+            .collect();
+        let original: HashSet<CardHash> = coll
+            .cards
+            .iter()
+            .filter(|card| card.deck_name() == "Geology")
+            .map(|card| card.hash())
+            .collect();
+        assert_eq!(original.len(), 4);
+        assert_eq!(parsed, original);
+
+        let text = get_text_export(&coll, Some("Geo*"), true)?;
+        assert_eq!(
+            text,
+            "What is a rock?\nA solid.\n#rocks\n\n[Granite] is an [igneous] rock.\n\nMultiple\nlines?\nYes."
+        );
+
+        // Prefixes are only stripped in the text format.
+        let result = export_collection(
+            Some(dir.display().to_string()),
+            None,
+            None,
+            ExportFormat::Json,
+            true,
+        );
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "error: --no-prefixes only applies to the text format."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_export() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        let geology = "Q: What is a rock?\nA: A solid, \"mostly\".\n#rocks\n\nC: [Granite] is an [igneous] rock.\n";
+        let spanish =
+            "T: el gato\nA: the cat\n\nQ: Multiple\nlines?\nA: Yes.\n\nR: el perro\nA: the dog\n";
+        write(dir.join("Geology.md"), geology)?;
+        create_dir_all(dir.join("languages"))?;
+        write(dir.join("languages/spanish.md"), spanish)?;
+        let coll = Collection::new(Some(dir.display().to_string()))?;
+        let csv = get_csv_export(&coll, None)?;
+        assert_eq!(
+            csv,
+            "deck,type,front,back,tags\n\
+             Geology,basic,What is a rock?,\"A solid, \"\"mostly\"\".\",rocks\n\
+             Geology,cloze,[Granite] is an [igneous] rock.,,\n\
+             languages::spanish,type-in,el gato,the cat,\n\
+             languages::spanish,basic,\"Multiple\nlines?\",Yes.,\n\
+             languages::spanish,reversible,el perro,the dog,"
+        );
+
+        // Importing the export writes the same deck files.
+        let input = dir.join("cards.csv");
+        write(&input, csv)?;
+        let target = create_tmp_directory()?;
+        import_cards(
+            input.display().to_string(),
+            Some(target.display().to_string()),
+            ImportFormat::Csv,
+            Vec::new(),
+        )?;
+        assert_eq!(read_to_string(target.join("Geology.md"))?, geology);
+        assert_eq!(
+            read_to_string(target.join("languages/spanish.md"))?,
+            spanish
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_numbered_cloze_export() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        let source = "C: [c1::Granite] and [c1::basalt] are [c2::igneous].";
+        write(dir.join("Geology.md"), format!("{source}\n"))?;
+        let coll = Collection::new(Some(dir.display().to_string()))?;
+        assert_eq!(coll.cards.len(), 2);
+        // The family is exported once, with its numbers.
+        assert_eq!(get_text_export(&coll, None, false)?, source);
+        assert_eq!(
+            get_csv_export(&coll, None)?,
+            "deck,type,front,back,tags\nGeology,cloze,[c1::Granite] and [c1::basalt] are [c2::igneous].,,"
+        );
+        let cards: Vec<&Card> = coll.cards.iter().collect();
+        let json = serde_json::to_value(get_card_export(&coll, &cards)?)?;
+        let first = json
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|card| &card["content"]["groupedCloze"])
+            .find(|content| content["group"] == 1);
+        assert_eq!(
+            first.cloned(),
+            Some(serde_json::json!({
+                "text": "Granite and basalt are igneous.",
+                "deletions": [
+                    {"group": 1, "start": 0, "end": 6},
+                    {"group": 1, "start": 12, "end": 17},
+                    {"group": 2, "start": 23, "end": 29},
+                ],
+                "group": 1,
+            }))
+        );
         Ok(())
     }
 }
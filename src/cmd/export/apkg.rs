@@ -0,0 +1,718 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Exporting cards as an Anki package, in the legacy format that every Anki
+//! version since 2.1 imports: a zip archive of a SQLite collection, a
+//! `media` file that maps numbers to file names, and the media files, named
+//! by number.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::fs::read;
+use std::fs::remove_file;
+use std::fs::write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use chrono::Utc;
+use pulldown_cmark::Event;
+use pulldown_cmark::Options;
+use pulldown_cmark::Parser;
+use pulldown_cmark::Tag;
+use pulldown_cmark::TagEnd;
+use pulldown_cmark::html::push_html;
+use rusqlite::Connection;
+use rusqlite::params;
+use serde_json::Value;
+use serde_json::json;
+
+use crate::cmd::export::group_families;
+use crate::cmd::export::select_cards;
+use crate::collection::Collection;
+use crate::error::ErrorReport;
+use crate::error::Fallible;
+use crate::markdown::is_audio_file;
+use crate::media::resolve::MediaResolver;
+use crate::media::resolve::MediaResolverBuilder;
+use crate::types::card::CardContent;
+use crate::utils::temp_file_path;
+use crate::zip::write_zip;
+
+/// The IDs of the note types. Anki tells note types apart by ID, so fixed
+/// IDs let the notes of a package exported again share the note types of
+/// the first.
+const BASIC_MODEL_ID: i64 = 1_700_000_000_001;
+const TYPE_IN_MODEL_ID: i64 = 1_700_000_000_002;
+const CLOZE_MODEL_ID: i64 = 1_700_000_000_003;
+const REVERSED_MODEL_ID: i64 = 1_700_000_000_004;
+
+/// Anki's note types: standard notes, and cloze notes.
+const MODEL_STANDARD: i64 = 0;
+const MODEL_CLOZE: i64 = 1;
+
+/// Every Anki collection has a deck called `Default`, with this ID.
+const DEFAULT_DECK: &str = "Default";
+const DEFAULT_DECK_ID: i64 = 1;
+
+/// Anki's field separator in the `notes.flds` column.
+const FIELD_SEPARATOR: &str = "\u{1f}";
+
+/// The styling and LaTeX preamble of Anki's stock note types.
+const CSS: &str = ".card {\n    font-family: arial;\n    font-size: 20px;\n    text-align: center;\n    color: black;\n    background-color: white;\n}\n\n.cloze {\n    font-weight: bold;\n    color: blue;\n}\n";
+const LATEX_PRE: &str = "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\\begin{document}\n";
+const LATEX_POST: &str = "\\end{document}";
+
+/// A note to write into the package.
+struct AnkiNote {
+    /// The hash of the card, or the family hash of a cloze card, so
+    /// importing a package again updates the notes of the first.
+    guid: String,
+    model: i64,
+    fields: Vec<String>,
+    tags: Vec<String>,
+    /// The Anki deck name, with `::` between the components.
+    deck: String,
+    /// The number of cards: one per cloze deletion, or one.
+    cards: usize,
+}
+
+/// The media files referenced by the notes.
+#[derive(Default)]
+struct Media {
+    /// The name of each file in the package, by collection-relative path.
+    names: HashMap<PathBuf, String>,
+    /// The contents of each file, by name.
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl Media {
+    /// Add a file, given its collection-relative path, and return its name
+    /// in the package. Anki keeps media in a single flat directory, so files
+    /// are named by the hash of their contents, which also stores identical
+    /// files once.
+    fn add(&mut self, directory: &Path, path: PathBuf) -> Fallible<String> {
+        if let Some(name) = self.names.get(&path) {
+            return Ok(name.clone());
+        }
+        let data: Vec<u8> = read(directory.join(&path))?;
+        let hash: String = blake3::hash(&data).to_hex().to_string();
+        let name: String = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => format!("{hash}.{ext}"),
+            None => hash,
+        };
+        self.names.insert(path, name.clone());
+        self.files.insert(name.clone(), data);
+        Ok(name)
+    }
+}
+
+/// Export the cards in decks matching the pattern, or every card, as an
+/// Anki package. Basic cards become Basic notes, type-in cards become
+/// notes of the "Basic (type in the answer)" type, and the deletions of a
+/// cloze card become a single Cloze note.
+pub fn export_apkg(coll: &Collection, deck: Option<&str>, output: &Path) -> Fallible<()> {
+    let cards = select_cards(coll, deck)?;
+    let separator: &str = coll.config.deck_separator();
+    let mut media: Media = Media::default();
+    let mut notes: Vec<AnkiNote> = Vec::new();
+    for (card, deletions) in group_families(cards) {
+        let resolver: MediaResolver = MediaResolverBuilder::new()
+            .with_collection_path(coll.directory.clone())?
+            .with_deck_path(card.relative_file_path(&coll.directory)?)?
+            .build()?;
+        let mut html =
+            |markdown: &str| field_to_html(markdown, &resolver, &coll.directory, &mut media);
+        let (model, fields, cards): (i64, Vec<String>, usize) = match card.content() {
+            CardContent::Basic { question, answer } => {
+                (BASIC_MODEL_ID, vec![html(question)?, html(answer)?], 1)
+            }
+            CardContent::TypeIn { prompt, answer } => {
+                (TYPE_IN_MODEL_ID, vec![html(prompt)?, html(answer)?], 1)
+            }
+            CardContent::Reversible {
+                question, answer, ..
+            } => (REVERSED_MODEL_ID, vec![html(question)?, html(answer)?], 2),
+            CardContent::Cloze { text, .. } => {
+                let mut deletions: Vec<(usize, usize)> = deletions;
+                deletions.sort();
+                let numbered: Vec<(usize, usize, usize)> = deletions
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (start, end))| (index + 1, start, end))
+                    .collect();
+                let text: String = with_cloze_markers(text, &numbered);
+                (
+                    CLOZE_MODEL_ID,
+                    vec![html(&text)?, String::new()],
+                    numbered.len(),
+                )
+            }
+            CardContent::GroupedCloze {
+                text, deletions, ..
+            } => {
+                let groups: BTreeSet<usize> =
+                    deletions.iter().map(|(group, _, _)| *group).collect();
+                let text: String = with_cloze_markers(text, deletions);
+                (
+                    CLOZE_MODEL_ID,
+                    vec![html(&text)?, String::new()],
+                    groups.len(),
+                )
+            }
+        };
+        let guid: String = card.family_hash().unwrap_or(card.hash()).to_hex();
+        let deck: String = card
+            .deck_name()
+            .split(separator)
+            .collect::<Vec<&str>>()
+            .join("::");
+        let tags: Vec<String> = card
+            .tags()
+            .iter()
+            .map(|tag| tag.replace('/', "::"))
+            .collect();
+        notes.push(AnkiNote {
+            guid,
+            model,
+            fields,
+            tags,
+            deck,
+            cards,
+        });
+    }
+    let decks: BTreeMap<String, i64> = deck_ids(&notes);
+
+    // SQLite needs a file, so the collection is written to a temporary one.
+    let path: PathBuf = temp_file_path("collection.anki2");
+    let result = write_collection(&path, &notes, &decks);
+    let collection: Fallible<Vec<u8>> = result.and_then(|_| Ok(read(&path)?));
+    let _ = remove_file(&path);
+    let collection: Vec<u8> = collection?;
+
+    // The media files are numbered in the package.
+    let numbers: Vec<String> = (0..media.files.len()).map(|n| n.to_string()).collect();
+    let names: BTreeMap<&str, &str> = numbers
+        .iter()
+        .zip(media.files.keys())
+        .map(|(number, name)| (number.as_str(), name.as_str()))
+        .collect();
+    let names: Vec<u8> = serde_json::to_vec(&names)?;
+    let mut entries: Vec<(&str, &[u8])> =
+        vec![("collection.anki2", &collection), ("media", &names)];
+    for (number, data) in numbers.iter().zip(media.files.values()) {
+        entries.push((number, data));
+    }
+    write(output, write_zip(&entries))?;
+
+    let cards: usize = notes.iter().map(|note| note.cards).sum();
+    let note_decks: BTreeSet<&str> = notes.iter().map(|note| note.deck.as_str()).collect();
+    println!(
+        "Exported {} notes into {} decks, as {} cards, with {} media files.",
+        notes.len(),
+        note_decks.len(),
+        cards,
+        media.files.len()
+    );
+    Ok(())
+}
+
+/// The source text of a cloze card, with each deletion written as Anki's
+/// `{{cN::...}}`. The deletions are `(group, start, end)`, as in
+/// [`CardContent::GroupedCloze`], and the group is the number.
+fn with_cloze_markers(text: &str, deletions: &[(usize, usize, usize)]) -> String {
+    let mut deletions: Vec<(usize, usize, usize)> = deletions.to_vec();
+    deletions.sort_by_key(|(_, start, _)| *start);
+    let mut result: String = String::with_capacity(text.len() + 8 * deletions.len());
+    let mut position: usize = 0;
+    for (group, start, end) in deletions {
+        result.push_str(text.get(position..start).unwrap_or_default());
+        result.push_str(&format!("{{{{c{group}::"));
+        result.push_str(text.get(start..=end).unwrap_or_default());
+        result.push_str("}}");
+        position = end + 1;
+    }
+    result.push_str(text.get(position..).unwrap_or_default());
+    result
+}
+
+/// Render the Markdown of a field as HTML for Anki. Math is written with
+/// MathJax delimiters, images point to their name in the package, and audio
+/// files become `[sound:...]` references, which Anki plays.
+fn field_to_html(
+    markdown: &str,
+    resolver: &MediaResolver,
+    directory: &Path,
+    media: &mut Media,
+) -> Fallible<String> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_MATH);
+    let mut events: Vec<Event<'_>> = Vec::new();
+    // Whether the events are the alt text of an audio file, which is dropped.
+    let mut in_audio: bool = false;
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                let path: PathBuf = resolver.resolve(&dest_url).map_err(|err| {
+                    ErrorReport::new(format!("Failed to resolve media path '{dest_url}': {err}"))
+                })?;
+                let name: String = media.add(directory, path)?;
+                if is_audio_file(&name) {
+                    events.push(Event::Text(format!("[sound:{name}]").into()));
+                    in_audio = true;
+                } else {
+                    events.push(Event::Start(Tag::Image {
+                        link_type,
+                        dest_url: name.into(),
+                        title,
+                        id,
+                    }));
+                }
+            }
+            Event::End(TagEnd::Image) if in_audio => in_audio = false,
+            _ if in_audio => {}
+            Event::InlineMath(math) => events.push(Event::Text(format!("\\({math}\\)").into())),
+            Event::DisplayMath(math) => events.push(Event::Text(format!("\\[{math}\\]").into())),
+            event => events.push(event),
+        }
+    }
+    let mut html: String = String::new();
+    push_html(&mut html, events.into_iter());
+    // A single paragraph is unwrapped, as Anki's editor writes fields.
+    let html: &str = html.trim_end();
+    match html
+        .strip_prefix("<p>")
+        .and_then(|html| html.strip_suffix("</p>"))
+    {
+        Some(inner) if !inner.contains("<p>") => Ok(inner.to_string()),
+        _ => Ok(html.to_string()),
+    }
+}
+
+/// Number the decks of the notes. Anki expects the parents of a deck to
+/// exist, so `A::B` also adds `A`.
+fn deck_ids(notes: &[AnkiNote]) -> BTreeMap<String, i64> {
+    let mut names: BTreeSet<String> = BTreeSet::new();
+    names.insert(DEFAULT_DECK.to_string());
+    for note in notes {
+        let components: Vec<&str> = note.deck.split("::").collect();
+        for length in 1..=components.len() {
+            names.insert(components[..length].join("::"));
+        }
+    }
+    let mut ids: BTreeMap<String, i64> = BTreeMap::new();
+    let mut next: i64 = DEFAULT_DECK_ID + 1;
+    for name in names {
+        if name == DEFAULT_DECK {
+            ids.insert(name, DEFAULT_DECK_ID);
+        } else {
+            ids.insert(name, next);
+            next += 1;
+        }
+    }
+    ids
+}
+
+/// Write the notes as a collection in Anki's legacy schema. The cards are
+/// new, and due in the order of the notes.
+fn write_collection(
+    path: &Path,
+    notes: &[AnkiNote],
+    decks: &BTreeMap<String, i64>,
+) -> Fallible<()> {
+    let mut conn = Connection::open(path)?;
+    let tx = conn.transaction()?;
+    tx.execute_batch(include_str!("anki_schema.sql"))?;
+    let millis: i64 = Utc::now().timestamp_millis();
+    let secs: i64 = millis / 1000;
+    let conf = json!({
+        "activeDecks": [DEFAULT_DECK_ID],
+        "curDeck": DEFAULT_DECK_ID,
+        "curModel": BASIC_MODEL_ID,
+        "newSpread": 0,
+        "collapseTime": 1200,
+        "timeLim": 0,
+        "estTimes": true,
+        "dueCounts": true,
+        "sortType": "noteFld",
+        "sortBackwards": false,
+        "addToCur": true,
+        "nextPos": notes.len() + 1,
+    });
+    let models = json!({
+        BASIC_MODEL_ID.to_string(): model(
+            BASIC_MODEL_ID,
+            "Basic",
+            MODEL_STANDARD,
+            &["Front", "Back"],
+            &[("{{Front}}", "{{FrontSide}}\n\n<hr id=answer>\n\n{{Back}}")],
+            secs,
+        ),
+        REVERSED_MODEL_ID.to_string(): model(
+            REVERSED_MODEL_ID,
+            "Basic (and reversed card)",
+            MODEL_STANDARD,
+            &["Front", "Back"],
+            &[
+                ("{{Front}}", "{{FrontSide}}\n\n<hr id=answer>\n\n{{Back}}"),
+                ("{{Back}}", "{{FrontSide}}\n\n<hr id=answer>\n\n{{Front}}"),
+            ],
+            secs,
+        ),
+        TYPE_IN_MODEL_ID.to_string(): model(
+            TYPE_IN_MODEL_ID,
+            "Basic (type in the answer)",
+            MODEL_STANDARD,
+            &["Front", "Back"],
+            &[(
+                "{{Front}}\n\n{{type:Back}}",
+                "{{Front}}\n\n<hr id=answer>\n\n{{type:Back}}",
+            )],
+            secs,
+        ),
+        CLOZE_MODEL_ID.to_string(): model(
+            CLOZE_MODEL_ID,
+            "Cloze",
+            MODEL_CLOZE,
+            &["Text", "Back Extra"],
+            &[("{{cloze:Text}}", "{{cloze:Text}}<br>\n{{Back Extra}}")],
+            secs,
+        ),
+    });
+    let deck_json: serde_json::Map<String, Value> = decks
+        .iter()
+        .map(|(name, id)| (id.to_string(), deck(*id, name, secs)))
+        .collect();
+    let dconf = json!({
+        "1": {
+            "id": 1,
+            "name": "Default",
+            "mod": 0,
+            "usn": 0,
+            "maxTaken": 60,
+            "autoplay": true,
+            "timer": 0,
+            "replayq": true,
+            "dyn": false,
+            "new": {
+                "bury": false,
+                "delays": [1.0, 10.0],
+                "initialFactor": 2500,
+                "ints": [1, 4, 0],
+                "order": 1,
+                "perDay": 20,
+            },
+            "rev": {
+                "bury": false,
+                "ease4": 1.3,
+                "ivlFct": 1.0,
+                "maxIvl": 36500,
+                "perDay": 200,
+                "hardFactor": 1.2,
+            },
+            "lapse": {
+                "delays": [10.0],
+                "leechAction": 1,
+                "leechFails": 8,
+                "minInt": 1,
+                "mult": 0.0,
+            },
+        },
+    });
+    tx.execute(
+        "insert into col (id, crt, mod, scm, ver, dty, usn, ls, conf, models, decks, dconf, tags)
+         values (1, ?, ?, ?, 11, 0, 0, 0, ?, ?, ?, ?, '{}');",
+        params![
+            secs,
+            millis,
+            millis,
+            conf.to_string(),
+            models.to_string(),
+            Value::Object(deck_json).to_string(),
+            dconf.to_string()
+        ],
+    )?;
+    let mut card_id: i64 = millis;
+    for (index, note) in notes.iter().enumerate() {
+        let note_id: i64 = millis + index as i64;
+        let tags: String = if note.tags.is_empty() {
+            String::new()
+        } else {
+            format!(" {} ", note.tags.join(" "))
+        };
+        // Anki computes the sort field and the checksum, used to find
+        // duplicates, when it imports the note.
+        let sort_field: &str = note.fields.first().map(String::as_str).unwrap_or_default();
+        tx.execute(
+            "insert into notes (id, guid, mid, mod, usn, tags, flds, sfld, csum, flags, data)
+             values (?, ?, ?, ?, 0, ?, ?, ?, 0, 0, '');",
+            params![
+                note_id,
+                note.guid,
+                note.model,
+                secs,
+                tags,
+                note.fields.join(FIELD_SEPARATOR),
+                sort_field
+            ],
+        )?;
+        let deck_id: i64 = decks.get(&note.deck).copied().unwrap_or(DEFAULT_DECK_ID);
+        for ord in 0..note.cards {
+            tx.execute(
+                "insert into cards (id, nid, did, ord, mod, usn, type, queue, due, ivl, factor, reps, lapses, left, odue, odid, flags, data)
+                 values (?, ?, ?, ?, ?, 0, 0, 0, ?, 0, 0, 0, 0, 0, 0, 0, 0, '');",
+                params![card_id, note_id, deck_id, ord as i64, secs, index as i64 + 1],
+            )?;
+            card_id += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// A note type, with a card template for each pair of question and answer
+/// formats.
+fn model(
+    id: i64,
+    name: &str,
+    kind: i64,
+    fields: &[&str],
+    templates: &[(&str, &str)],
+    secs: i64,
+) -> Value {
+    let fields: Vec<Value> = fields
+        .iter()
+        .enumerate()
+        .map(|(ord, name)| {
+            json!({
+                "name": name,
+                "ord": ord,
+                "sticky": false,
+                "rtl": false,
+                "font": "Arial",
+                "size": 20,
+                "media": [],
+            })
+        })
+        .collect();
+    let tmpls: Vec<Value> = templates
+        .iter()
+        .enumerate()
+        .map(|(ord, (qfmt, afmt))| {
+            let name: String = if kind == MODEL_CLOZE {
+                "Cloze".to_string()
+            } else {
+                format!("Card {}", ord + 1)
+            };
+            json!({
+                "name": name,
+                "ord": ord,
+                "qfmt": qfmt,
+                "afmt": afmt,
+                "bqfmt": "",
+                "bafmt": "",
+                "did": null,
+            })
+        })
+        .collect();
+    // Each card is generated if the field it asks about is not empty.
+    let req: Vec<Value> = (0..templates.len())
+        .map(|ord| json!([ord, "any", [ord]]))
+        .collect();
+    json!({
+        "id": id,
+        "name": name,
+        "type": kind,
+        "mod": secs,
+        "usn": 0,
+        "sortf": 0,
+        "did": DEFAULT_DECK_ID,
+        "tmpls": tmpls,
+        "flds": fields,
+        "css": CSS,
+        "latexPre": LATEX_PRE,
+        "latexPost": LATEX_POST,
+        "latexsvg": false,
+        "req": req,
+        "tags": [],
+        "vers": [],
+    })
+}
+
+/// A deck, with Anki's default options.
+fn deck(id: i64, name: &str, secs: i64) -> Value {
+    json!({
+        "id": id,
+        "name": name,
+        "mod": secs,
+        "usn": 0,
+        "desc": "",
+        "dyn": 0,
+        "conf": 1,
+        "collapsed": false,
+        "browserCollapsed": false,
+        "extendNew": 0,
+        "extendRev": 0,
+        "newToday": [0, 0],
+        "revToday": [0, 0],
+        "lrnToday": [0, 0],
+        "timeToday": [0, 0],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::create_dir_all;
+    use std::fs::read_to_string;
+
+    use super::*;
+    use crate::cmd::export::ExportFormat;
+    use crate::cmd::export::export_collection;
+    use crate::cmd::import::ImportFormat;
+    use crate::cmd::import::import_cards;
+    use crate::helper::create_tmp_directory;
+    use crate::zip::ZipArchive;
+
+    /// Export a collection as a package, and import the package into an
+    /// empty directory.
+    fn round_trip(dir: &Path) -> Fallible<(PathBuf, PathBuf)> {
+        let output: PathBuf = create_tmp_directory()?.join("deck.apkg");
+        export_collection(
+            Some(dir.display().to_string()),
+            Some(output.display().to_string()),
+            None,
+            ExportFormat::Apkg,
+            false,
+        )?;
+        let target: PathBuf = create_tmp_directory()?;
+        import_cards(
+            output.display().to_string(),
+            Some(target.display().to_string()),
+            ImportFormat::Apkg,
+            Vec::new(),
+        )?;
+        Ok((output, target))
+    }
+
+    #[test]
+    fn test_round_trip() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        let geology = "Q: What is a *rock*?\nA: A **solid**.\n#rocks #earth/science\n\nC: [Granite] is an [igneous] rock.\n";
+        let spanish = "T: el gato\nA: the cat\n\nQ: What is $x^2$ at $x = 2$?\nA: $$4$$\n";
+        write(dir.join("Geology.md"), geology)?;
+        create_dir_all(dir.join("languages"))?;
+        write(dir.join("languages/spanish.md"), spanish)?;
+        let (_, target) = round_trip(&dir)?;
+        assert_eq!(read_to_string(target.join("Geology.md"))?, geology);
+        assert_eq!(
+            read_to_string(target.join("languages/spanish.md"))?,
+            spanish
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_media() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        create_dir_all(dir.join("animals"))?;
+        write(dir.join("animals/cat.png"), b"image")?;
+        write(dir.join("meow.mp3"), b"audio")?;
+        write(
+            dir.join("animals/Cats.md"),
+            "Q: ![a cat](cat.png)\nA: ![](@/meow.mp3)\n\nQ: Again: ![](@/animals/cat.png)\nA: A cat.\n",
+        )?;
+        let (output, target) = round_trip(&dir)?;
+        let image = format!("{}.png", blake3::hash(b"image").to_hex());
+        let audio = format!("{}.mp3", blake3::hash(b"audio").to_hex());
+
+        // Each file is packaged once, under its hashed name.
+        let zip = ZipArchive::new(read(&output)?)?;
+        let names: HashMap<String, String> =
+            serde_json::from_slice(&zip.read("media")?.unwrap_or_default())?;
+        assert_eq!(names.len(), 2);
+        for (number, name) in names {
+            let data = zip.read(&number)?.unwrap_or_default();
+            assert_eq!(
+                blake3::hash(&data),
+                blake3::hash(&read(target.join(&name))?)
+            );
+            assert!(name == image || name == audio);
+        }
+
+        assert_eq!(
+            read_to_string(target.join("animals/Cats.md"))?,
+            format!(
+                "Q: ![](@/{image})\nA: ![](@/{audio})\n\nQ: Again: ![](@/{image})\nA: A cat.\n"
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_field_to_html() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        write(dir.join("deck.md"), "")?;
+        write(dir.join("song.ogg"), b"song")?;
+        let resolver = MediaResolverBuilder::new()
+            .with_collection_path(dir.clone())?
+            .with_deck_path(PathBuf::from("deck.md"))?
+            .build()?;
+        let mut media = Media::default();
+        let song = format!("{}.ogg", blake3::hash(b"song").to_hex());
+        let cases = [
+            ("Plain & simple", "Plain &amp; simple".to_string()),
+            ("$a < b$", "\\(a &lt; b\\)".to_string()),
+            ("$$\\sum x$$", "\\[\\sum x\\]".to_string()),
+            (
+                "Listen: ![a song](song.ogg)",
+                format!("Listen: [sound:{song}]"),
+            ),
+            ("One\n\nTwo", "<p>One</p>\n<p>Two</p>".to_string()),
+        ];
+        for (markdown, html) in cases {
+            assert_eq!(field_to_html(markdown, &resolver, &dir, &mut media)?, html);
+        }
+        assert!(field_to_html("![](missing.png)", &resolver, &dir, &mut media).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_cloze_markers() {
+        assert_eq!(
+            with_cloze_markers("Granite is an igneous rock.", &[(1, 14, 20), (1, 0, 6)]),
+            "{{c1::Granite}} is an {{c1::igneous}} rock."
+        );
+    }
+
+    #[test]
+    fn test_output_required() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        write(dir.join("deck.md"), "Q: a\nA: b\n")?;
+        let result = export_collection(
+            Some(dir.display().to_string()),
+            None,
+            None,
+            ExportFormat::Apkg,
+            false,
+        );
+        assert!(result.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,411 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env::current_dir;
+use std::fs::create_dir_all;
+use std::fs::read_to_string;
+use std::fs::write;
+use std::ops::Range;
+use std::path::Path;
+use std::path::PathBuf;
+
+use pulldown_cmark::Event;
+use pulldown_cmark::Parser;
+use pulldown_cmark::Tag;
+use reqwest::Client;
+
+use crate::collection::Collection;
+use crate::collection::StoreMode;
+use crate::config::CardRegex;
+use crate::db::Database;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::ignore::IgnoreRules;
+use crate::media::remote::download;
+use crate::media::remote::is_remote_url;
+use crate::media::remote::media_client;
+use crate::media::remote::media_extension;
+use crate::parse_cache::CacheMode;
+use crate::parser::deck_files;
+use crate::parser::parse_text;
+use crate::types::card::Card;
+use crate::types::card_hash::CardHash;
+use crate::types::timestamp::Timestamp;
+
+/// The collection-relative directory where remote media is stored.
+const MEDIA_DIR: &str = "media";
+
+/// Download every remote image referenced in the collection into the media
+/// directory, and rewrite the links to point to the local copies. Rewriting
+/// a link changes the hash of its card, so the review history of the card
+/// is moved to the new hash.
+pub async fn fetch_media(directory: Option<String>) -> Fallible<()> {
+    let directory: PathBuf = match directory {
+        Some(dir) => PathBuf::from(dir),
+        None => current_dir()?,
+    };
+    // Without a store there is no history to move, so none is created.
+    let store: StoreMode = if directory.join("hashcards.db").exists() {
+        StoreMode::ReadWrite
+    } else {
+        StoreMode::NoPersistence
+    };
+    let Collection {
+        directory,
+        mut db,
+        config,
+        ..
+    } = Collection::load(
+        Some(directory.display().to_string()),
+        store,
+        CacheMode::Enabled,
+    )?;
+    let card_regex: Option<CardRegex> = config.card_regex()?;
+    let ignore: IgnoreRules = IgnoreRules::load(&directory, &config.ignored_extensions)?;
+    let media_dir: PathBuf = directory.join(MEDIA_DIR);
+    let client: Client = media_client()?;
+
+    // Map of remote URLs to the local paths they were downloaded to.
+    let mut local_paths: HashMap<String, String> = HashMap::new();
+    let mut failures: usize = 0;
+    for path in deck_files(&directory, &ignore, card_regex.as_ref())? {
+        let text = read_to_string(&path)?;
+        for url in extract_remote_image_urls(&text) {
+            if local_paths.contains_key(&url) {
+                continue;
+            }
+            match download(&client, &url).await {
+                Ok((bytes, content_type)) => {
+                    let file_name = save_media(&media_dir, &url, &bytes, content_type.as_deref())?;
+                    println!("Fetched {url}");
+                    local_paths.insert(url, format!("@/{MEDIA_DIR}/{file_name}"));
+                }
+                Err(e) => {
+                    eprintln!("Failed to fetch {url}: {e}");
+                    failures += 1;
+                }
+            }
+        }
+        let rewritten = rewrite_image_urls(&text, &local_paths);
+        if rewritten != text {
+            let separator: &str = config.deck_separator();
+            let old_cards = parse_text(&directory, &path, &text, card_regex.as_ref(), separator)?;
+            let new_cards = parse_text(
+                &directory,
+                &path,
+                &rewritten,
+                card_regex.as_ref(),
+                separator,
+            )?;
+            write(&path, rewritten)?;
+            transfer_histories(&mut db, &old_cards, &new_cards)?;
+            println!("Updated {}", path.display());
+        }
+    }
+
+    if failures > 0 {
+        return fail(format!("failed to fetch {failures} media files."));
+    }
+    Ok(())
+}
+
+/// Move the review history of each card of a file to the card it became
+/// when the file's links were rewritten. Rewriting links doesn't add or
+/// remove cards, so the cards of the file before and after, in the order
+/// they were parsed, correspond one to one.
+fn transfer_histories(db: &mut Database, old_cards: &[Card], new_cards: &[Card]) -> Fallible<()> {
+    if old_cards.len() != new_cards.len() {
+        return fail("Rewriting the links of a file changed its cards.");
+    }
+    let known: HashSet<CardHash> = db.card_hashes()?;
+    let now: Timestamp = Timestamp::now();
+    for (old, new) in old_cards.iter().zip(new_cards) {
+        let (old, new) = (old.hash(), new.hash());
+        if old == new || !known.contains(&old) {
+            continue;
+        }
+        if !known.contains(&new) {
+            db.insert_card(new, now)?;
+        }
+        db.transfer_history(old, new)?;
+    }
+    Ok(())
+}
+
+/// Extract the URLs of all remote images in the Markdown text.
+fn extract_remote_image_urls(markdown: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    for event in Parser::new(markdown) {
+        if let Event::Start(Tag::Image { dest_url, .. }) = event {
+            if is_remote_url(&dest_url) && !urls.contains(&dest_url.to_string()) {
+                urls.push(dest_url.to_string());
+            }
+        }
+    }
+    urls
+}
+
+/// Replace the URLs of remote images that have a local copy. The URL is
+/// replaced as written in the source, which may be in angle brackets or
+/// contain escapes, so it is found by its position after the alt text.
+/// Images whose URL is in a reference definition are left as they are.
+fn rewrite_image_urls(markdown: &str, local_paths: &HashMap<String, String>) -> String {
+    let mut replacements: Vec<(Range<usize>, &str)> = Vec::new();
+    // The image being read, its local path, and the end of its alt text.
+    let mut image: Option<(&str, usize)> = None;
+    let mut depth: usize = 0;
+    for (event, range) in Parser::new(markdown).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Image { dest_url, .. }) if depth == 0 => {
+                if let Some(local_path) = local_paths.get(dest_url.as_ref()) {
+                    // The alt text starts after the `![`.
+                    image = Some((local_path, range.start + 2));
+                }
+                depth = 1;
+            }
+            Event::End(_) if depth == 1 => {
+                depth = 0;
+                if let Some((local_path, alt_end)) = image.take() {
+                    if let Some(url) = destination_range(markdown, alt_end, range.end) {
+                        replacements.push((url, local_path));
+                    }
+                }
+            }
+            _ if depth > 0 => {
+                match event {
+                    Event::Start(_) => depth += 1,
+                    Event::End(_) => depth -= 1,
+                    _ => {}
+                }
+                if let Some((_, alt_end)) = &mut image {
+                    *alt_end = (*alt_end).max(range.end);
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut result = markdown.to_string();
+    // Replace back to front, so earlier offsets stay valid.
+    for (range, local_path) in replacements.into_iter().rev() {
+        result.replace_range(range, local_path);
+    }
+    result
+}
+
+/// The range of the destination of an inline image, as written, given the
+/// end of its alt text and the end of the image. `None` if the image is not
+/// inline, e.g. `![alt][reference]`.
+fn destination_range(markdown: &str, alt_end: usize, end: usize) -> Option<Range<usize>> {
+    let source: &str = markdown.get(alt_end..end)?;
+    let rest: &str = source.strip_prefix("](")?;
+    let start: usize = alt_end + 2 + (rest.len() - rest.trim_start().len());
+    let rest: &str = rest.trim_start();
+    if rest.starts_with('<') {
+        let mut escaped: bool = false;
+        for (index, c) in rest.char_indices().skip(1) {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '>' => return Some(start..start + index + 1),
+                _ => {}
+            }
+        }
+        return None;
+    }
+    let mut escaped: bool = false;
+    let mut parentheses: usize = 0;
+    for (index, c) in rest.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '(' => parentheses += 1,
+            ')' if parentheses > 0 => parentheses -= 1,
+            c if c == ')' || c.is_whitespace() => return Some(start..start + index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Save a downloaded file in the media directory, named by the hash of its
+/// contents, and return the file name. Files that already exist are not
+/// overwritten.
+fn save_media(
+    media_dir: &Path,
+    url: &str,
+    bytes: &[u8],
+    content_type: Option<&str>,
+) -> Fallible<String> {
+    let hash = blake3::hash(bytes).to_hex().to_string();
+    let file_name = match media_extension(url, content_type) {
+        Some(ext) => format!("{hash}.{ext}"),
+        None => hash,
+    };
+    let path = media_dir.join(&file_name);
+    if !path.exists() {
+        create_dir_all(media_dir)?;
+        write(&path, bytes)?;
+    }
+    Ok(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_dir;
+
+    use axum::Router;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use portpicker::pick_unused_port;
+    use tempfile::tempdir;
+    use tokio::net::TcpListener;
+    use tokio::spawn;
+
+    use super::*;
+    use crate::types::performance::Performance;
+    use crate::types::performance::ReviewedPerformance;
+    use crate::utils::wait_for_server;
+
+    #[test]
+    fn test_extract_remote_image_urls() {
+        let markdown = "![a](https://example.com/a.png) ![b](b.png) ![c](http://example.com/c.jpg) ![a](https://example.com/a.png)";
+        assert_eq!(
+            extract_remote_image_urls(markdown),
+            vec!["https://example.com/a.png", "http://example.com/c.jpg"]
+        );
+    }
+
+    #[test]
+    fn test_rewrite_image_urls() {
+        let markdown =
+            "Q: ![a](https://example.com/a.png \"A\")\nA: ![b](https://example.com/b.png)\n";
+        let mut local_paths = HashMap::new();
+        local_paths.insert(
+            "https://example.com/a.png".to_string(),
+            "@/media/abc.png".to_string(),
+        );
+        assert_eq!(
+            rewrite_image_urls(markdown, &local_paths),
+            "Q: ![a](@/media/abc.png \"A\")\nA: ![b](https://example.com/b.png)\n"
+        );
+    }
+
+    /// URLs are replaced as written: in angle brackets, with escapes, or
+    /// after an alt text that contains the URL. Reference images are left
+    /// as they are.
+    #[test]
+    fn test_rewrite_written_urls() {
+        let mut local_paths = HashMap::new();
+        local_paths.insert(
+            "https://example.com/a_(1).png".to_string(),
+            "@/media/abc.png".to_string(),
+        );
+        let cases = [
+            (
+                "![](<https://example.com/a_(1).png>)",
+                "![](@/media/abc.png)",
+            ),
+            (
+                "![](https://example.com/a\\_\\(1\\).png)",
+                "![](@/media/abc.png)",
+            ),
+            ("![](https://example.com/a_(1).png)", "![](@/media/abc.png)"),
+            (
+                "![*https://example.com/a_(1).png*]( https://example.com/a_(1).png 'T')",
+                "![*https://example.com/a_(1).png*]( @/media/abc.png 'T')",
+            ),
+            (
+                "![a][r]\n\n[r]: https://example.com/a_(1).png\n",
+                "![a][r]\n\n[r]: https://example.com/a_(1).png\n",
+            ),
+        ];
+        for (markdown, expected) in cases {
+            assert_eq!(rewrite_image_urls(markdown, &local_paths), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_media() -> Fallible<()> {
+        let host = "127.0.0.1";
+        let port = pick_unused_port().unwrap();
+        let app = Router::new()
+            .route("/a.png", get(|| async { b"image".to_vec() }))
+            .route("/copy.png", get(|| async { b"image".to_vec() }))
+            .route("/missing.png", get(|| async { StatusCode::NOT_FOUND }));
+        let listener = TcpListener::bind(format!("{host}:{port}")).await?;
+        spawn(async move { axum::serve(listener, app).await });
+        wait_for_server(host, port).await?;
+
+        let dir = tempdir()?;
+        let deck = dir.path().join("deck.md");
+        let text = format!(
+            "Q: ![](http://{host}:{port}/a.png)\nA: ![](http://{host}:{port}/copy.png)\n\nQ: ![](http://{host}:{port}/missing.png)\nA: x\n"
+        );
+        write(&deck, &text)?;
+        // A card that has been reviewed.
+        let old_cards = parse_text(dir.path(), &deck, &text, None, "::")?;
+        let db_path = dir.path().join("hashcards.db");
+        let db = Database::new(&db_path.display().to_string())?;
+        let now = Timestamp::now();
+        let performance = Performance::Reviewed(ReviewedPerformance {
+            last_reviewed_at: now,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            review_count: 1,
+        });
+        for card in &old_cards {
+            db.insert_card(card.hash(), now)?;
+        }
+        db.update_card_performance(old_cards[0].hash(), performance)?;
+        drop(db);
+
+        let result = fetch_media(Some(dir.path().display().to_string())).await;
+        assert!(result.is_err());
+
+        // Identical files are deduplicated.
+        let hash = blake3::hash(b"image").to_hex().to_string();
+        assert!(
+            dir.path()
+                .join("media")
+                .join(format!("{hash}.png"))
+                .exists()
+        );
+        assert_eq!(read_dir(dir.path().join("media"))?.count(), 1);
+
+        // Failed downloads are left as-is.
+        let text = read_to_string(&deck)?;
+        assert_eq!(
+            text,
+            format!(
+                "Q: ![](@/media/{hash}.png)\nA: ![](@/media/{hash}.png)\n\nQ: ![](http://{host}:{port}/missing.png)\nA: x\n"
+            )
+        );
+
+        // The rewritten card keeps its history.
+        let new_cards = parse_text(dir.path(), &deck, &text, None, "::")?;
+        let db = Database::new(&db_path.display().to_string())?;
+        assert_ne!(new_cards[0].hash(), old_cards[0].hash());
+        assert_eq!(db.get_card_performance(new_cards[0].hash())?, performance);
+        assert_eq!(
+            db.get_card_performance(old_cards[0].hash())?,
+            Performance::New
+        );
+        Ok(())
+    }
+}
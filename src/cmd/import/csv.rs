@@ -0,0 +1,246 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Importing cards from a CSV file, with a card per row, for editing cards
+//! in bulk in a spreadsheet.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::cmd::import::deck_file;
+use crate::cmd::import::deck_path;
+use crate::cmd::import::write_decks;
+use crate::csv::CARD_COLUMNS;
+use crate::csv::REVERSIBLE_TYPE_NAME;
+use crate::csv::card_type_name;
+use crate::csv::parse_csv;
+use crate::error::ErrorReport;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::parser::Parser;
+use crate::parser::is_tag;
+use crate::types::card::CardType;
+
+/// Import the cards of a CSV file into deck files, and return the source
+/// text of the cards of each deck file. The first row must name the
+/// columns, as in [`CARD_COLUMNS`]. A row that isn't a valid card fails the
+/// whole import, and nothing is written.
+pub fn import_csv(
+    input: &Path,
+    directory: &Path,
+    mapping: &HashMap<String, PathBuf>,
+) -> Fallible<BTreeMap<PathBuf, Vec<String>>> {
+    let rows: Vec<Vec<String>> = parse_csv(&read_to_string(input)?)?;
+    let mut rows = rows.into_iter().enumerate();
+    let header: Vec<String> = match rows.next() {
+        Some((_, header)) => header
+            .iter()
+            .map(|column| column.trim().to_lowercase())
+            .collect(),
+        None => Vec::new(),
+    };
+    if header != CARD_COLUMNS {
+        return fail(format!(
+            "The first row of the CSV file must name the columns: {}.",
+            CARD_COLUMNS.join(",")
+        ));
+    }
+    let mut decks: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for (index, row) in rows {
+        // Spreadsheets often write blank rows at the end.
+        if row.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let deck: &str = row.first().map(|deck| deck.trim()).unwrap_or_default();
+        let path: PathBuf = deck_path(Some(deck).filter(|deck| !deck.is_empty()), mapping);
+        let file: PathBuf = deck_file(directory, &path);
+        let text: String = row_to_text(&row, &file)
+            .map_err(|err| ErrorReport::new(format!("Row {}: {err}", index + 1)))?;
+        decks.entry(path).or_default().push(text);
+    }
+    write_decks(directory, &decks)?;
+    Ok(decks)
+}
+
+/// Convert a row into the source text of a card, or explain why it isn't a
+/// valid card.
+fn row_to_text(row: &[String], file: &Path) -> Result<String, String> {
+    let [_, kind, front, back, tags] = row else {
+        return Err(format!(
+            "expected {} columns, found {}.",
+            CARD_COLUMNS.len(),
+            row.len()
+        ));
+    };
+    let front: &str = front.trim();
+    let back: &str = back.trim();
+    let kind: String = kind.trim().to_lowercase();
+    let reversible: bool = kind == REVERSIBLE_TYPE_NAME;
+    // A reversible card is a basic card, written with `R:`.
+    let card_type: CardType = if reversible {
+        CardType::Basic
+    } else {
+        [CardType::Basic, CardType::Cloze, CardType::TypeIn]
+            .into_iter()
+            .find(|card_type| card_type_name(*card_type) == kind)
+            .ok_or_else(|| {
+                format!(
+                    "unknown card type '{kind}': expected basic, reversible, cloze, or type-in."
+                )
+            })?
+    };
+    let text: String = match card_type {
+        CardType::Basic | CardType::TypeIn => {
+            if front.is_empty() || back.is_empty() {
+                return Err("the front and the back must not be empty.".to_string());
+            }
+            let prefix: &str = if reversible {
+                "R"
+            } else if card_type == CardType::Basic {
+                "Q"
+            } else {
+                "T"
+            };
+            format!("{prefix}: {front}\nA: {back}")
+        }
+        CardType::Cloze => {
+            if !back.is_empty() {
+                return Err("cloze cards have no back: the text goes in the front.".to_string());
+            }
+            format!("C: {front}")
+        }
+    };
+    let mut tag_line: Vec<String> = Vec::new();
+    for tag in tags.split_whitespace() {
+        let tag: String = format!("#{}", tag.trim_start_matches('#'));
+        if !is_tag(&tag) {
+            return Err(format!("invalid tag '{tag}'."));
+        }
+        tag_line.push(tag);
+    }
+    let text: String = if tag_line.is_empty() {
+        text
+    } else {
+        format!("{text}\n{}", tag_line.join(" "))
+    };
+    // The text must parse back into the card, and not, say, into two cards
+    // because a line of the back starts with `Q:`.
+    let parser = Parser::new(String::new(), file.to_path_buf());
+    let cards = parser.parse(&text).map_err(|err| err.message)?;
+    // A reversible card parses into a card for each direction.
+    let count: usize = if reversible { 2 } else { 1 };
+    let single: bool =
+        cards.len() == count && cards.iter().all(|card| card.to_source_text() == text);
+    if cards.is_empty() || (card_type != CardType::Cloze && !single) {
+        return Err("the text doesn't parse as a single card.".to_string());
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use super::*;
+    use crate::helper::create_tmp_directory;
+
+    fn row(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|field| field.to_string()).collect()
+    }
+
+    #[test]
+    fn test_import_csv() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        let input = dir.join("cards.csv");
+        write(
+            &input,
+            "Deck,Type,Front,Back,Tags\r\n\
+             Geology,basic,\"What is a rock?\",\"A solid,\nmade of minerals.\",rocks #earth/science\r\n\
+             Geology,cloze,[Granite] is an [igneous] rock.,,\r\n\
+             languages::spanish,Type-In,el gato,the cat,\r\n\
+             languages::spanish,reversible,el perro,the dog,\r\n\
+             ,,,,\r\n",
+        )?;
+        let decks = import_csv(&input, &dir, &HashMap::new())?;
+        assert_eq!(decks.len(), 2);
+        assert_eq!(
+            read_to_string(dir.join("Geology.md"))?,
+            "Q: What is a rock?\nA: A solid,\nmade of minerals.\n#rocks #earth/science\n\nC: [Granite] is an [igneous] rock.\n"
+        );
+        assert_eq!(
+            read_to_string(dir.join("languages/spanish.md"))?,
+            "T: el gato\nA: the cat\n\nR: el perro\nA: the dog\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_rows() -> Fallible<()> {
+        let file = PathBuf::from("deck.md");
+        let cases = [
+            (vec!["d", "basic", "q"], "expected 5 columns, found 3."),
+            (
+                vec!["d", "note", "q", "a", ""],
+                "unknown card type 'note': expected basic, reversible, cloze, or type-in.",
+            ),
+            (
+                vec!["d", "basic", "q", "", ""],
+                "the front and the back must not be empty.",
+            ),
+            (
+                vec!["d", "cloze", "[a]", "b", ""],
+                "cloze cards have no back: the text goes in the front.",
+            ),
+            (vec!["d", "basic", "q", "a", "#"], "invalid tag '#'."),
+            (
+                vec!["d", "basic", "q", "a\nQ: b\nA: c", ""],
+                "the text doesn't parse as a single card.",
+            ),
+        ];
+        for (fields, message) in cases {
+            assert_eq!(row_to_text(&row(&fields), &file), Err(message.to_string()));
+        }
+        assert!(row_to_text(&row(&["d", "cloze", "no deletions", "", ""]), &file).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_errors() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        let input = dir.join("cards.csv");
+
+        write(&input, "front,back\nq,a\n")?;
+        let err = import_csv(&input, &dir, &HashMap::new()).err().unwrap();
+        assert_eq!(
+            err.message(),
+            "The first row of the CSV file must name the columns: deck,type,front,back,tags."
+        );
+
+        // A bad row fails the import before any deck file is written.
+        write(
+            &input,
+            "deck,type,front,back,tags\nA,basic,q,a,\nB,basic,q,,\n",
+        )?;
+        let err = import_csv(&input, &dir, &HashMap::new()).err().unwrap();
+        assert_eq!(
+            err.message(),
+            "Row 3: the front and the back must not be empty."
+        );
+        assert!(!dir.join("A.md").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// Marks the start of a cloze deletion while a field is converted, so that
+/// its brackets are not escaped.
+const DELETION_START: char = '\u{E000}';
+
+/// Marks the end of a cloze deletion.
+const DELETION_END: char = '\u{E001}';
+
+/// An Anki cloze deletion, `{{c1::text}}` or `{{c1::text::hint}}`.
+static DELETION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)\{\{c(\d+)::(.*?)(?:::(?:.*?))?\}\}").unwrap());
+
+/// The Markdown of a field, and the media files it refers to.
+#[derive(Debug, Default, PartialEq)]
+pub struct Converted {
+    pub markdown: String,
+    pub media: Vec<String>,
+    /// For cloze text, the number of cards: one per deletion, or one per
+    /// number when deletions share a number.
+    pub deletions: usize,
+}
+
+/// Convert the HTML of an Anki field to Markdown. Line breaks, bold,
+/// italics, lists, images, sounds, and MathJax are kept; other markup is
+/// dropped, and the text is escaped so it reads the same in Markdown.
+pub fn field_to_markdown(html: &str) -> Converted {
+    convert(html, false)
+}
+
+/// Convert the text field of an Anki cloze note. Each deletion becomes a
+/// hashcards deletion in brackets, and hints are dropped. Other brackets are
+/// escaped. When deletions share a number, and so are hidden together, the
+/// deletions keep their numbers, as in `[c1::Paris]`.
+pub fn cloze_to_markdown(html: &str) -> Converted {
+    let numbers: Vec<&str> = DELETION
+        .captures_iter(html)
+        .filter_map(|captures| captures.get(1))
+        .map(|number| number.as_str())
+        .collect();
+    let distinct: BTreeSet<&str> = numbers.iter().copied().collect();
+    let grouped: bool = distinct.len() < numbers.len();
+    let marked = DELETION.replace_all(html, |captures: &regex::Captures| {
+        if grouped {
+            format!(
+                "{DELETION_START}c{}::{}{DELETION_END}",
+                &captures[1], &captures[2]
+            )
+        } else {
+            format!("{DELETION_START}{}{DELETION_END}", &captures[2])
+        }
+    });
+    let deletions: usize = if grouped {
+        distinct.len()
+    } else {
+        numbers.len()
+    };
+    let mut converted: Converted = convert(&marked, true);
+    converted.markdown = converted
+        .markdown
+        .replace(DELETION_START, "[")
+        .replace(DELETION_END, "]");
+    converted.deletions = deletions;
+    converted
+}
+
+fn convert(html: &str, cloze: bool) -> Converted {
+    let mut converted = Converted::default();
+    let mut output: String = String::new();
+    let mut rest: &str = html;
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with('<') {
+            if let Some(end) = rest.find('>') {
+                push_tag(&rest[1..end], &mut output, &mut converted.media);
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+        // Text, up to the next tag. A `<` that doesn't start a tag is text.
+        let first: usize = rest.chars().next().map_or(1, char::len_utf8);
+        let end: usize = rest[first..]
+            .find('<')
+            .map_or(rest.len(), |end| end + first);
+        push_text(
+            &decode_entities(&rest[..end]),
+            cloze,
+            &mut output,
+            &mut converted.media,
+        );
+        rest = &rest[end..];
+    }
+    converted.markdown = tidy(&output);
+    converted
+}
+
+fn push_tag(tag: &str, output: &mut String, media: &mut Vec<String>) {
+    let closing: bool = tag.starts_with('/');
+    let name: String = tag
+        .trim_start_matches('/')
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    match name.as_str() {
+        "br" => output.push('\n'),
+        "div" | "p" | "ul" | "ol" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "tr" => {
+            start_line(output);
+        }
+        "li" if !closing => {
+            start_line(output);
+            output.push_str("- ");
+        }
+        "b" | "strong" => output.push_str("**"),
+        "i" | "em" => output.push('*'),
+        "img" if !closing => {
+            if let Some(source) = attribute(tag, "src") {
+                push_media(&source, output, media);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Start a new line, unless the output is at the start of one.
+fn start_line(output: &mut String) {
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+}
+
+/// The value of an attribute of a tag, quoted or not.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower: String = tag.to_lowercase();
+    let mut search: usize = 0;
+    while let Some(found) = lower[search..].find(name) {
+        let start: usize = search + found;
+        search = start + name.len();
+        let preceded: bool = lower[..start].ends_with(char::is_whitespace);
+        let rest: &str = tag[search..].trim_start();
+        let Some(value) = rest.strip_prefix('=') else {
+            continue;
+        };
+        if !preceded {
+            continue;
+        }
+        let value: &str = value.trim_start();
+        let value: &str = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let value: &str = &value[1..];
+                &value[..value.find(quote).unwrap_or(value.len())]
+            }
+            _ => value.split_whitespace().next().unwrap_or_default(),
+        };
+        return Some(decode_entities(value));
+    }
+    None
+}
+
+/// A link to a media file, copied to the root of the collection.
+fn push_media(name: &str, output: &mut String, media: &mut Vec<String>) {
+    if name.contains(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == '<') {
+        output.push_str(&format!("![](<@/{name}>)"));
+    } else {
+        output.push_str(&format!("![](@/{name})"));
+    }
+    if !media.iter().any(|m| m == name) {
+        media.push(name.to_string());
+    }
+}
+
+/// Push decoded text: sounds become media links, MathJax becomes `$` math,
+/// and the rest is escaped.
+fn push_text(text: &str, cloze: bool, output: &mut String, media: &mut Vec<String>) {
+    let mut rest: &str = text;
+    loop {
+        let next = ["[sound:", "\\(", "\\["]
+            .iter()
+            .filter_map(|start| rest.find(start).map(|index| (index, *start)))
+            .min();
+        let Some((index, start)) = next else {
+            escape(rest, output);
+            return;
+        };
+        let close: &str = match start {
+            "[sound:" => "]",
+            "\\(" => "\\)",
+            _ => "\\]",
+        };
+        let body: &str = &rest[index + start.len()..];
+        let Some(end) = body.find(close) else {
+            escape(rest, output);
+            return;
+        };
+        escape(&rest[..index], output);
+        let inner: &str = &body[..end];
+        match start {
+            "[sound:" => push_media(inner, output, media),
+            _ => {
+                let delimiter: &str = if start == "\\(" { "$" } else { "$$" };
+                // Brackets start deletions in cloze text, even in math.
+                let inner: String = if cloze {
+                    inner.replace('[', "\\[").replace(']', "\\]")
+                } else {
+                    inner.to_string()
+                };
+                output.push_str(&format!("{delimiter}{inner}{delimiter}"));
+            }
+        }
+        rest = &body[end + close.len()..];
+    }
+}
+
+/// Escape the characters that are Markdown syntax, or hashcards syntax.
+fn escape(text: &str, output: &mut String) {
+    for c in text.chars() {
+        match c {
+            '\\' | '*' | '_' | '`' | '$' | '[' | ']' | '#' => {
+                output.push('\\');
+                output.push(c);
+            }
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '\u{a0}' => output.push(' '),
+            c => output.push(c),
+        }
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut output: String = String::new();
+    let mut rest: &str = text;
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded: Option<(char, usize)> = rest.find(';').and_then(|end| {
+            let entity: &str = &rest[1..end];
+            let c: Option<char> = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => {
+                    let code: Option<u32> = match entity.strip_prefix('#') {
+                        Some(hex) if hex.starts_with(['x', 'X']) => {
+                            u32::from_str_radix(&hex[1..], 16).ok()
+                        }
+                        Some(decimal) => decimal.parse().ok(),
+                        None => None,
+                    };
+                    code.and_then(char::from_u32)
+                }
+            };
+            c.map(|c| (c, end + 1))
+        });
+        match decoded {
+            Some((c, length)) => {
+                output.push(c);
+                rest = &rest[length..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Trim trailing whitespace from each line, and collapse runs of blank lines.
+fn tidy(text: &str) -> String {
+    let mut output: String = String::new();
+    let mut blank: bool = false;
+    for line in text.trim().lines() {
+        let line: &str = line.trim_end();
+        if line.is_empty() {
+            blank = true;
+            continue;
+        }
+        if !output.is_empty() {
+            output.push_str(if blank { "\n\n" } else { "\n" });
+        }
+        output.push_str(line);
+        blank = false;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markdown(html: &str) -> String {
+        field_to_markdown(html).markdown
+    }
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(
+            markdown("<b>bold</b> and <i>italic</i>"),
+            "**bold** and *italic*"
+        );
+        assert_eq!(markdown("one<br>two<div>three</div>"), "one\ntwo\nthree");
+        assert_eq!(markdown("<ul><li>a</li><li>b</li></ul>"), "- a\n- b");
+        assert_eq!(markdown("<span style=\"color: red\">red</span>"), "red");
+        assert_eq!(markdown("a<div><br></div><div><br></div>b"), "a\n\nb");
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(
+            markdown("5 &lt; 6 &amp;&nbsp;2*3 [x]"),
+            "5 &lt; 6 &amp; 2\\*3 \\[x\\]"
+        );
+        assert_eq!(markdown("#tag and $5"), "\\#tag and \\$5");
+        assert_eq!(markdown("&#233;&#x4e2d; &unknown;"), "é中 &amp;unknown;");
+    }
+
+    #[test]
+    fn test_media() {
+        let converted =
+            field_to_markdown("<img src=\"cat.png\"> [sound:meow.mp3]<img src='a b.jpg'>");
+        assert_eq!(
+            converted.markdown,
+            "![](@/cat.png) ![](@/meow.mp3)![](<@/a b.jpg>)"
+        );
+        assert_eq!(converted.media, vec!["cat.png", "meow.mp3", "a b.jpg"]);
+    }
+
+    #[test]
+    fn test_math() {
+        assert_eq!(
+            markdown("\\(x &lt; y_1\\) or \\[\\frac{a}{b}\\]"),
+            "$x < y_1$ or $$\\frac{a}{b}$$"
+        );
+        // Brackets in math are escaped in cloze text.
+        let converted = cloze_to_markdown("{{c1::\\(x \\in [0, 1]\\)}}");
+        assert_eq!(converted.markdown, "[$x \\in \\[0, 1\\]$]");
+    }
+
+    #[test]
+    fn test_cloze() {
+        let converted = cloze_to_markdown(
+            "{{c1::Paris}} is the capital of {{c2::<b>France</b>::country}} [sic]",
+        );
+        assert_eq!(
+            converted.markdown,
+            "[Paris] is the capital of [**France**] \\[sic\\]"
+        );
+        assert_eq!(converted.deletions, 2);
+        assert_eq!(cloze_to_markdown("No deletions.").deletions, 0);
+        // Deletions that share a number keep it.
+        let converted =
+            cloze_to_markdown("{{c1::Paris}} is on the {{c1::Seine}}, in {{c2::France}}.");
+        assert_eq!(
+            converted.markdown,
+            "[c1::Paris] is on the [c1::Seine], in [c2::France]."
+        );
+        assert_eq!(converted.deletions, 2);
+    }
+}
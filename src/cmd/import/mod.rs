@@ -0,0 +1,693 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod csv;
+mod html;
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::env::current_dir;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs::create_dir_all;
+use std::fs::read;
+use std::fs::remove_file;
+use std::fs::write;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use rusqlite::Connection;
+use rusqlite::OpenFlags;
+use serde::Deserialize;
+
+use crate::cmd::import::csv::import_csv;
+use crate::cmd::import::html::Converted;
+use crate::cmd::import::html::cloze_to_markdown;
+use crate::cmd::import::html::field_to_markdown;
+use crate::error::ErrorReport;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::parser::Parser;
+use crate::parser::is_tag;
+use crate::types::card::CardContent;
+use crate::utils::temp_file_path;
+use crate::zip::ZipArchive;
+
+/// Anki's field separator in the `notes.flds` column.
+const FIELD_SEPARATOR: char = '\u{1f}';
+
+/// Anki's note types: standard notes, and cloze notes.
+const MODEL_STANDARD: i64 = 0;
+const MODEL_CLOZE: i64 = 1;
+
+/// The collection files of a package, newest first. Both use the legacy
+/// schema, which keeps note types and decks as JSON in the `col` table.
+const COLLECTION_FILES: [&str; 2] = ["collection.anki21", "collection.anki2"];
+
+/// The collection file of packages in the current format, compressed with
+/// Zstandard.
+const COMPRESSED_COLLECTION_FILE: &str = "collection.anki21b";
+
+/// The message for packages that can't be read.
+const LEGACY_EXPORT_HINT: &str =
+    "Export the deck from Anki again, with \"Support older Anki versions\" checked.";
+
+#[derive(Deserialize)]
+struct Model {
+    #[serde(rename = "type")]
+    kind: i64,
+    #[serde(default)]
+    tmpls: Vec<Template>,
+}
+
+#[derive(Deserialize)]
+struct Template {
+    #[serde(default)]
+    qfmt: String,
+}
+
+#[derive(Deserialize)]
+struct AnkiDeck {
+    name: String,
+}
+
+/// A note read from the Anki collection.
+struct Note {
+    model: i64,
+    fields: Vec<String>,
+    tags: Vec<String>,
+    /// The Anki deck of the note's first card.
+    deck: Option<String>,
+}
+
+/// What happened to the notes of the package.
+#[derive(Debug, Default, PartialEq)]
+struct ImportSummary {
+    imported: usize,
+    cards: usize,
+    decks: usize,
+    media: usize,
+    unsupported: usize,
+    empty: usize,
+    no_deletions: usize,
+    invalid: usize,
+    missing_media: usize,
+}
+
+/// The format of an import.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum ImportFormat {
+    /// An Anki package, exported with "Support older Anki versions" checked.
+    Apkg,
+    /// A CSV file with the columns `deck`, `type`, `front`, `back`, and
+    /// `tags`, as written by `export --format csv`.
+    Csv,
+}
+
+impl Display for ImportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportFormat::Apkg => write!(f, "apkg"),
+            ImportFormat::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+/// Import the cards of an Anki package or a CSV file into deck files in the
+/// collection directory. `decks` maps deck names to deck files, as
+/// `DECK=PATH`.
+pub fn import_cards(
+    input: String,
+    directory: Option<String>,
+    format: ImportFormat,
+    decks: Vec<String>,
+) -> Fallible<()> {
+    let directory: PathBuf = match directory {
+        Some(directory) => PathBuf::from(directory),
+        None => current_dir()?,
+    };
+    let mapping: HashMap<String, PathBuf> = parse_deck_mapping(&decks)?;
+    match format {
+        ImportFormat::Apkg => {
+            let summary = import(Path::new(&input), &directory, &mapping)?;
+            print_summary(&summary);
+        }
+        ImportFormat::Csv => {
+            let decks = import_csv(Path::new(&input), &directory, &mapping)?;
+            let cards: usize = decks.values().map(Vec::len).sum();
+            println!("Imported {} cards into {} decks.", cards, decks.len());
+        }
+    }
+    Ok(())
+}
+
+fn print_summary(summary: &ImportSummary) {
+    println!(
+        "Imported {} notes into {} decks, as {} cards.",
+        summary.imported, summary.decks, summary.cards
+    );
+    println!("Copied {} media files.", summary.media);
+    println!("Skipped:");
+    println!(
+        "  {} notes of a type other than Basic or Cloze.",
+        summary.unsupported
+    );
+    println!("  {} notes with an empty side.", summary.empty);
+    println!("  {} cloze notes without deletions.", summary.no_deletions);
+    println!(
+        "  {} notes that don't convert to valid cards.",
+        summary.invalid
+    );
+    if summary.missing_media > 0 {
+        println!(
+            "{} media files referenced by notes were not in the package.",
+            summary.missing_media
+        );
+    }
+}
+
+/// Parse `DECK=PATH` deck mappings. The path is relative to the collection
+/// directory, with or without the `.md` extension.
+fn parse_deck_mapping(decks: &[String]) -> Fallible<HashMap<String, PathBuf>> {
+    let mut mapping: HashMap<String, PathBuf> = HashMap::new();
+    for deck in decks {
+        let Some((anki, path)) = deck.rsplit_once('=') else {
+            return fail(format!(
+                "Invalid deck mapping '{deck}': expected DECK=PATH."
+            ));
+        };
+        let path: PathBuf = PathBuf::from(path.trim().trim_end_matches(".md"));
+        let relative: bool = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if path.as_os_str().is_empty() || !relative {
+            return fail(format!(
+                "Invalid deck mapping '{deck}': the path must be relative to the collection directory."
+            ));
+        }
+        mapping.insert(anki.trim().to_string(), path);
+    }
+    Ok(mapping)
+}
+
+fn import(
+    package: &Path,
+    directory: &Path,
+    mapping: &HashMap<String, PathBuf>,
+) -> Fallible<ImportSummary> {
+    let zip = ZipArchive::new(read(package)?)?;
+    let Some(collection) = COLLECTION_FILES.iter().find(|name| zip.contains(name)) else {
+        if zip.contains(COMPRESSED_COLLECTION_FILE) {
+            return fail(format!(
+                "This package uses a format hashcards can't read. {LEGACY_EXPORT_HINT}"
+            ));
+        }
+        return fail("Not an Anki package: no collection file.");
+    };
+    let data: Vec<u8> = zip.read(collection)?.unwrap_or_default();
+    let (models, notes) = read_collection(&data)?;
+
+    let mut summary = ImportSummary::default();
+    // The source text of the cards of each deck file.
+    let mut decks: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    let mut media: Vec<String> = Vec::new();
+    for note in notes {
+        let path: PathBuf = deck_path(note.deck.as_deref(), mapping);
+        let file: PathBuf = deck_file(directory, &path);
+        match convert_note(&note, models.get(&note.model), &file) {
+            Outcome::Imported {
+                text,
+                cards,
+                media: note_media,
+            } => {
+                summary.imported += 1;
+                summary.cards += cards;
+                decks.entry(path).or_default().push(text);
+                for name in note_media {
+                    if !media.contains(&name) {
+                        media.push(name);
+                    }
+                }
+            }
+            Outcome::Unsupported => summary.unsupported += 1,
+            Outcome::Empty => summary.empty += 1,
+            Outcome::NoDeletions => summary.no_deletions += 1,
+            Outcome::Invalid => summary.invalid += 1,
+        }
+    }
+
+    write_decks(directory, &decks)?;
+    summary.decks = decks.len();
+
+    // Media files are named by number in the package, and the `media` file
+    // maps the numbers to their names.
+    let names: HashMap<String, String> = match zip.read("media")? {
+        Some(data) => serde_json::from_slice(&data).map_err(|_| {
+            ErrorReport::new(format!(
+                "Failed to read the media of the package. {LEGACY_EXPORT_HINT}"
+            ))
+        })?,
+        None => HashMap::new(),
+    };
+    let numbers: HashMap<&str, &str> = names
+        .iter()
+        .map(|(number, name)| (name.as_str(), number.as_str()))
+        .collect();
+    for name in media {
+        let data: Option<Vec<u8>> = match numbers.get(name.as_str()) {
+            Some(number) if is_plain_file_name(&name) => zip.read(number)?,
+            _ => None,
+        };
+        let Some(data) = data else {
+            summary.missing_media += 1;
+            continue;
+        };
+        // A file already in the collection is kept.
+        let target: PathBuf = directory.join(&name);
+        if !target.exists() {
+            write(&target, data)?;
+            summary.media += 1;
+        }
+    }
+    Ok(summary)
+}
+
+/// Write the source text of the cards of each deck file, given by its path
+/// relative to the collection directory, without the extension. Every deck
+/// file is checked before any is written, so a failed import leaves the
+/// collection as it was.
+pub fn write_decks(directory: &Path, decks: &BTreeMap<PathBuf, Vec<String>>) -> Fallible<()> {
+    for path in decks.keys() {
+        let file: PathBuf = deck_file(directory, path);
+        if file.exists() {
+            return fail(format!(
+                "The deck file {} already exists. Use --deck to import the deck into another file.",
+                file.display()
+            ));
+        }
+    }
+    for (path, cards) in decks {
+        let file: PathBuf = deck_file(directory, path);
+        if let Some(parent) = file.parent() {
+            create_dir_all(parent)?;
+        }
+        write(&file, format!("{}\n", cards.join("\n\n")))?;
+    }
+    Ok(())
+}
+
+/// The result of converting a note.
+enum Outcome {
+    Imported {
+        /// The source text of the note's cards.
+        text: String,
+        cards: usize,
+        media: Vec<String>,
+    },
+    Unsupported,
+    Empty,
+    NoDeletions,
+    /// The converted text doesn't parse into the expected cards, e.g.
+    /// because a line of a field looks like the start of a card.
+    Invalid,
+}
+
+fn convert_note(note: &Note, model: Option<&Model>, file: &Path) -> Outcome {
+    let Some(model) = model else {
+        return Outcome::Unsupported;
+    };
+    let fields: Vec<Converted> = note
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match (model.kind, index) {
+            (MODEL_CLOZE, 0) => cloze_to_markdown(field),
+            _ => field_to_markdown(field),
+        })
+        .collect();
+    let media: Vec<String> = fields
+        .iter()
+        .flat_map(|field| field.media.iter().cloned())
+        .collect();
+    // The fields after those that make up the card are added at the end.
+    let extra = |skip: usize| -> String {
+        fields
+            .iter()
+            .skip(skip)
+            .map(|field| field.markdown.as_str())
+            .filter(|markdown| !markdown.is_empty())
+            .collect::<Vec<&str>>()
+            .join("\n\n")
+    };
+    let (text, cards): (String, usize) = match model.kind {
+        MODEL_STANDARD if fields.len() >= 2 => {
+            let question: &str = &fields[0].markdown;
+            let mut answer: String = fields[1].markdown.clone();
+            let rest: String = extra(2);
+            if !rest.is_empty() {
+                answer = format!("{answer}\n\n{rest}");
+            }
+            if question.is_empty() || answer.is_empty() {
+                return Outcome::Empty;
+            }
+            let type_in: bool = model
+                .tmpls
+                .iter()
+                .any(|template| template.qfmt.contains("{{type:"));
+            let content: CardContent = if type_in {
+                CardContent::new_type_in(question, answer)
+            } else {
+                CardContent::new_basic(question, answer)
+            };
+            (content.to_source_text(), 1)
+        }
+        MODEL_CLOZE if !fields.is_empty() => {
+            if fields[0].markdown.is_empty() {
+                return Outcome::Empty;
+            }
+            if fields[0].deletions == 0 {
+                return Outcome::NoDeletions;
+            }
+            let mut text: String = fields[0].markdown.clone();
+            let rest: String = extra(1);
+            if !rest.is_empty() {
+                text = format!("{text}\n\n{rest}");
+            }
+            (format!("C: {text}"), fields[0].deletions)
+        }
+        _ => return Outcome::Unsupported,
+    };
+    let tags: Vec<String> = note
+        .tags
+        .iter()
+        .map(|tag| format!("#{}", tag.replace("::", "/")))
+        .filter(|tag| is_tag(tag))
+        .collect();
+    let text: String = if tags.is_empty() {
+        text
+    } else {
+        format!("{text}\n{}", tags.join(" "))
+    };
+    if !parses_as(&text, cards, file) {
+        return Outcome::Invalid;
+    }
+    Outcome::Imported { text, cards, media }
+}
+
+/// Whether the source text of a note parses into the given number of cards,
+/// and, for a single card, back into the same text.
+fn parses_as(text: &str, count: usize, file: &Path) -> bool {
+    let parser = Parser::new(String::new(), file.to_path_buf());
+    match parser.parse(text) {
+        Ok(cards) if cards.len() == count => count != 1 || cards[0].to_source_text() == text,
+        _ => false,
+    }
+}
+
+/// The deck file of a deck, relative to the collection directory, without
+/// the extension: the mapped path, or the deck's path, with each
+/// `::`-separated part as a directory.
+pub fn deck_path(deck: Option<&str>, mapping: &HashMap<String, PathBuf>) -> PathBuf {
+    let deck: &str = deck.unwrap_or("Default");
+    if let Some(path) = mapping.get(deck) {
+        return path.clone();
+    }
+    deck.split("::")
+        .map(|part| {
+            let part: String = part
+                .trim()
+                .chars()
+                .map(|c| match c {
+                    '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+                    c => c,
+                })
+                .collect();
+            let part: &str = part.trim_start_matches('.');
+            if part.is_empty() {
+                "Default".to_string()
+            } else {
+                part.to_string()
+            }
+        })
+        .collect()
+}
+
+pub fn deck_file(directory: &Path, path: &Path) -> PathBuf {
+    directory.join(format!("{}.md", path.display()))
+}
+
+/// Whether a media file name from the package is a plain file name, which
+/// can't point outside the collection directory.
+fn is_plain_file_name(name: &str) -> bool {
+    !name.starts_with('.')
+        && Path::new(name)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        && Path::new(name).components().count() == 1
+}
+
+/// Read the note types and notes of an Anki collection. SQLite needs a
+/// file, so the collection is written to a temporary one.
+fn read_collection(data: &[u8]) -> Fallible<(HashMap<i64, Model>, Vec<Note>)> {
+    let path: PathBuf = temp_file_path("collection.anki2");
+    write(&path, data)?;
+    let result = read_collection_file(&path);
+    let _ = remove_file(&path);
+    result
+}
+
+fn read_collection_file(path: &Path) -> Fallible<(HashMap<i64, Model>, Vec<Note>)> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let (models, decks): (String, String) =
+        conn.query_row("select models, decks from col;", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+    let models: HashMap<String, Model> = serde_json::from_str(&models).unwrap_or_default();
+    if models.is_empty() {
+        return fail(format!(
+            "This package has no note types hashcards can read. {LEGACY_EXPORT_HINT}"
+        ));
+    }
+    let models: HashMap<i64, Model> = models
+        .into_iter()
+        .filter_map(|(id, model)| id.parse().ok().map(|id| (id, model)))
+        .collect();
+    let decks: HashMap<String, AnkiDeck> = serde_json::from_str(&decks)?;
+
+    // The deck of each note's first card.
+    let mut note_decks: HashMap<i64, String> = HashMap::new();
+    let mut stmt = conn.prepare("select nid, did from cards order by nid, ord;")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
+    for row in rows {
+        let (note, deck) = row?;
+        if let Some(deck) = decks.get(&deck.to_string()) {
+            note_decks.entry(note).or_insert_with(|| deck.name.clone());
+        }
+    }
+
+    let mut notes: Vec<Note> = Vec::new();
+    let mut stmt = conn.prepare("select id, mid, flds, tags from notes order by id;")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+        ))
+    })?;
+    for row in rows {
+        let (id, model, fields, tags) = row?;
+        notes.push(Note {
+            model,
+            fields: fields.split(FIELD_SEPARATOR).map(str::to_string).collect(),
+            tags: tags.split_whitespace().map(str::to_string).collect(),
+            deck: note_decks.remove(&id),
+        });
+    }
+    Ok((models, notes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use rusqlite::params;
+
+    use super::*;
+    use crate::helper::create_tmp_directory;
+    use crate::zip::write_zip;
+
+    const BASIC: i64 = 1;
+    const CLOZE: i64 = 2;
+    const TYPE_IN: i64 = 3;
+
+    /// Write an Anki package with the given notes, as (model, deck, fields,
+    /// tags), and media files.
+    fn make_package(
+        directory: &Path,
+        notes: &[(i64, i64, &str, &str)],
+        media: &[(&str, &[u8])],
+    ) -> Fallible<PathBuf> {
+        let path = directory.join("collection.anki2");
+        let conn = Connection::open(&path)?;
+        conn.execute_batch(
+            "create table col (models text, decks text);
+             create table notes (id integer, mid integer, flds text, tags text);
+             create table cards (nid integer, did integer, ord integer);",
+        )?;
+        let models = serde_json::json!({
+            BASIC.to_string(): {"type": 0, "tmpls": [{"qfmt": "{{Front}}"}]},
+            CLOZE.to_string(): {"type": 1, "tmpls": [{"qfmt": "{{cloze:Text}}"}]},
+            TYPE_IN.to_string(): {"type": 0, "tmpls": [{"qfmt": "{{Front}} {{type:Back}}"}]},
+        });
+        let decks = serde_json::json!({
+            "1": {"name": "Default"},
+            "2": {"name": "Math::Arithmetic"},
+            "3": {"name": "Geography"},
+        });
+        conn.execute(
+            "insert into col (models, decks) values (?, ?);",
+            params![models.to_string(), decks.to_string()],
+        )?;
+        for (id, (model, deck, fields, tags)) in notes.iter().enumerate() {
+            conn.execute(
+                "insert into notes (id, mid, flds, tags) values (?, ?, ?, ?);",
+                params![id as i64, model, fields.replace('|', "\u{1f}"), tags],
+            )?;
+            conn.execute(
+                "insert into cards (nid, did, ord) values (?, ?, 0);",
+                params![id as i64, deck],
+            )?;
+        }
+        drop(conn);
+        let collection = read(&path)?;
+        let names: HashMap<String, &str> = media
+            .iter()
+            .enumerate()
+            .map(|(number, (name, _))| (number.to_string(), *name))
+            .collect();
+        let names = serde_json::to_vec(&names)?;
+        let numbers: Vec<String> = (0..media.len()).map(|n| n.to_string()).collect();
+        let mut entries: Vec<(&str, &[u8])> =
+            vec![("collection.anki2", &collection), ("media", &names)];
+        for (number, (_, data)) in numbers.iter().zip(media) {
+            entries.push((number, data));
+        }
+        let package = directory.join("deck.apkg");
+        write(&package, write_zip(&entries))?;
+        Ok(package)
+    }
+
+    #[test]
+    fn test_import() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        let notes = [
+            (BASIC, 2, "What is <b>2+2</b>?|4", "math::easy leech"),
+            (
+                CLOZE,
+                3,
+                "{{c1::Paris}} is in {{c2::France::country}}.<img src=\"map.png\">|",
+                "",
+            ),
+            (TYPE_IN, 1, "le chat|the cat", ""),
+            (CLOZE, 3, "No deletions.|", ""),
+            (BASIC, 1, "Empty back?|", ""),
+            (BASIC, 1, "Tricky|A<br>Q: not a question", ""),
+            (99, 1, "Unknown|type", ""),
+        ];
+        let package = make_package(&dir, &notes, &[("map.png", b"png"), ("unused.png", b"")])?;
+        let collection = dir.join("Cards");
+        let summary = import(&package, &collection, &HashMap::new())?;
+        assert_eq!(
+            summary,
+            ImportSummary {
+                imported: 3,
+                cards: 4,
+                decks: 3,
+                media: 1,
+                unsupported: 1,
+                empty: 1,
+                no_deletions: 1,
+                invalid: 1,
+                missing_media: 0,
+            }
+        );
+        assert_eq!(
+            read_to_string(collection.join("Math/Arithmetic.md"))?,
+            "Q: What is **2+2**?\nA: 4\n#math/easy #leech\n"
+        );
+        assert_eq!(
+            read_to_string(collection.join("Geography.md"))?,
+            "C: [Paris] is in [France].![](@/map.png)\n"
+        );
+        assert_eq!(
+            read_to_string(collection.join("Default.md"))?,
+            "T: le chat\nA: the cat\n"
+        );
+        assert_eq!(read(collection.join("map.png"))?, b"png");
+        assert!(!collection.join("unused.png").exists());
+
+        // Importing again would overwrite the deck files.
+        let err = import(&package, &collection, &HashMap::new())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("already exists"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deck_mapping() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        let notes = [
+            (BASIC, 2, "1+1|2", ""),
+            (BASIC, 3, "Capital of Peru|Lima", ""),
+        ];
+        let package = make_package(&dir, &notes, &[])?;
+        let mapping = parse_deck_mapping(&["Math::Arithmetic=math/sums.md".to_string()])?;
+        import(&package, &dir, &mapping)?;
+        assert_eq!(read_to_string(dir.join("math/sums.md"))?, "Q: 1+1\nA: 2\n");
+        assert!(dir.join("Geography.md").exists());
+
+        assert!(parse_deck_mapping(&["Math".to_string()]).is_err());
+        assert!(parse_deck_mapping(&["Math=../math".to_string()]).is_err());
+        assert!(parse_deck_mapping(&["Math=/math".to_string()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressed_package() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        let package = dir.join("deck.apkg");
+        write(&package, write_zip(&[("collection.anki21b", b"")]))?;
+        let err = import(&package, &dir, &HashMap::new()).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("error: This package uses a format hashcards can't read. {LEGACY_EXPORT_HINT}")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_deck_path() {
+        let mapping = HashMap::new();
+        assert_eq!(deck_path(Some("A::B"), &mapping), PathBuf::from("A/B"));
+        assert_eq!(
+            deck_path(Some("a/b::..c"), &mapping),
+            PathBuf::from("a-b/c")
+        );
+        assert_eq!(deck_path(None, &mapping), PathBuf::from("Default"));
+    }
+}
@@ -0,0 +1,402 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use chrono::Duration;
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use rusqlite::OpenFlags;
+
+use crate::collection::Collection;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::fsrs::Difficulty;
+use crate::types::card::CardContent;
+use crate::types::card_hash::CardHash;
+use crate::types::date::Date;
+use crate::types::performance::Performance;
+use crate::types::performance::ReviewedPerformance;
+use crate::types::timestamp::Timestamp;
+
+/// Anki's field separator in the `notes.flds` column.
+const FIELD_SEPARATOR: char = '\u{1f}';
+
+/// Anki's `cards.type` for cards in the review queue.
+const TYPE_REVIEW: i64 = 2;
+
+/// Anki's `cards.queue` for suspended cards.
+const QUEUE_SUSPENDED: i64 = -1;
+
+/// The length of an Anki day, in seconds.
+const DAY_SECONDS: i64 = 86_400;
+
+/// Anki's ease factors, in permille: the minimum, and the default.
+const MIN_EASE: f64 = 1300.0;
+const DEFAULT_EASE: f64 = 2500.0;
+
+/// The scheduling state of an Anki card.
+#[derive(Debug, PartialEq)]
+struct AnkiCard {
+    question: String,
+    answer: String,
+    card_type: i64,
+    queue: i64,
+    /// For review cards, the due date as a day number: days since the
+    /// collection was created.
+    due: i64,
+    /// The current interval in days.
+    interval: i64,
+    /// The ease factor in permille.
+    factor: i64,
+    reps: i64,
+}
+
+/// What happened to the cards in the Anki collection.
+#[derive(Debug, Default, PartialEq)]
+struct ImportSummary {
+    imported: usize,
+    suspended: usize,
+    not_in_review: usize,
+    already_reviewed: usize,
+    unmatched: usize,
+}
+
+/// Import the scheduling state of an Anki collection into the hashcards
+/// database.
+pub fn import_anki_schedule(directory: Option<String>, anki: String) -> Fallible<()> {
+    let coll = Collection::new(directory)?;
+    let summary = import_schedule(&coll, &anki)?;
+    println!("Imported the schedule of {} cards.", summary.imported);
+    println!("Suspended {} cards suspended in Anki.", summary.suspended);
+    println!("Skipped:");
+    println!(
+        "  {} cards that are new or in learning in Anki.",
+        summary.not_in_review
+    );
+    println!(
+        "  {} cards already reviewed in hashcards.",
+        summary.already_reviewed
+    );
+    println!(
+        "  {} cards with no matching hashcards card.",
+        summary.unmatched
+    );
+    Ok(())
+}
+
+fn import_schedule(coll: &Collection, anki: &str) -> Fallible<ImportSummary> {
+    let conn = Connection::open_with_flags(anki, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let anki_today: i64 = anki_today(&conn)?;
+    let today: Date = Date::today();
+    let anki_cards: Vec<AnkiCard> = read_cards(&conn)?;
+
+    // Basic and type-in cards, and the forward cards of reversible cards, by
+    // their question and answer.
+    let basic: HashMap<(&str, &str), CardHash> = coll
+        .cards
+        .iter()
+        .filter_map(|card| match card.content() {
+            CardContent::Basic { question, answer }
+            | CardContent::TypeIn {
+                prompt: question,
+                answer,
+            }
+            | CardContent::Reversible {
+                question,
+                answer,
+                reversed: false,
+            } => Some(((question.as_str(), answer.as_str()), card.hash())),
+            CardContent::Cloze { .. }
+            | CardContent::GroupedCloze { .. }
+            | CardContent::Reversible { reversed: true, .. } => None,
+        })
+        .collect();
+    let db_hashes: HashSet<CardHash> = coll.db.card_hashes()?;
+
+    let mut summary = ImportSummary::default();
+    let now = Timestamp::now();
+    for anki_card in anki_cards {
+        let key = (anki_card.question.as_str(), anki_card.answer.as_str());
+        let Some(hash) = basic.get(&key).copied() else {
+            summary.unmatched += 1;
+            continue;
+        };
+        let suspended: bool = anki_card.queue == QUEUE_SUSPENDED;
+        let review: bool = anki_card.card_type == TYPE_REVIEW;
+        if !review && !suspended {
+            summary.not_in_review += 1;
+            continue;
+        }
+        if db_hashes.contains(&hash) {
+            if !coll.db.get_card_performance(hash)?.is_new() {
+                summary.already_reviewed += 1;
+                continue;
+            }
+        } else {
+            coll.db.insert_card(hash, now)?;
+        }
+        if suspended {
+            coll.db.suspend_card(hash)?;
+            summary.suspended += 1;
+        }
+        if review {
+            let performance = to_performance(&anki_card, today, anki_today);
+            coll.db
+                .update_card_performance(hash, Performance::Reviewed(performance))?;
+            summary.imported += 1;
+        }
+    }
+    Ok(summary)
+}
+
+/// Today's day number in the Anki collection: the number of days since it
+/// was created. The creation time is at the collection's day rollover, so
+/// this counts the days as Anki does, and due dates are taken relative to
+/// it rather than converted between timezones.
+fn anki_today(conn: &Connection) -> Fallible<i64> {
+    let crt: i64 = conn.query_row("select crt from col;", [], |row| row.get(0))?;
+    let now: i64 = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(now) => now.as_secs() as i64,
+        Err(_) => return fail("The system clock is before 1970."),
+    };
+    Ok((now - crt).div_euclid(DAY_SECONDS))
+}
+
+/// Read the forward cards (the first card of each note) from the Anki
+/// collection, with the first two fields of their note as question and
+/// answer.
+fn read_cards(conn: &Connection) -> Fallible<Vec<AnkiCard>> {
+    let sql = "select n.flds, c.type, c.queue, c.due, c.ivl, c.factor, c.reps from cards c join notes n on c.nid = n.id where c.ord = 0;";
+    let mut stmt = conn.prepare(sql)?;
+    let mut rows = stmt.query([])?;
+    let mut cards = Vec::new();
+    while let Some(row) = rows.next()? {
+        let fields: String = row.get(0)?;
+        let mut fields = fields.split(FIELD_SEPARATOR);
+        let question = strip_html(fields.next().unwrap_or_default());
+        let answer = strip_html(fields.next().unwrap_or_default());
+        cards.push(AnkiCard {
+            question,
+            answer,
+            card_type: row.get(1)?,
+            queue: row.get(2)?,
+            due: row.get(3)?,
+            interval: row.get(4)?,
+            factor: row.get(5)?,
+            reps: row.get(6)?,
+        });
+    }
+    Ok(cards)
+}
+
+/// Convert an Anki review card's SM-2 state to FSRS performance.
+///
+/// - Stability is the Anki interval: at the 90% target recall, the FSRS
+///   interval equals the stability.
+/// - Difficulty is mapped linearly from the ease factor: the minimum ease of
+///   130% maps to the maximum difficulty of 10, and each 20 points of ease
+///   above that lowers the difficulty by one, down to 1. The default ease of
+///   250% maps to 4.
+/// - The due date is as many days from `today` as Anki's due day number is
+///   from `anki_today`.
+/// - The last review is assumed to be one interval before the due date.
+fn to_performance(card: &AnkiCard, today: Date, anki_today: i64) -> ReviewedPerformance {
+    let interval_days: i64 = card.interval.max(1);
+    let due_date: NaiveDate = today.into_inner() + Duration::days(card.due - anki_today);
+    let last_reviewed_at: NaiveDate = due_date - Duration::days(interval_days);
+    let factor: f64 = if card.factor > 0 {
+        card.factor as f64
+    } else {
+        DEFAULT_EASE
+    };
+    let difficulty: Difficulty = (10.0 - (factor - MIN_EASE) / 200.0).clamp(1.0, 10.0);
+    ReviewedPerformance {
+        last_reviewed_at: Timestamp::new(last_reviewed_at.and_hms_opt(0, 0, 0).unwrap_or_default()),
+        stability: interval_days as f64,
+        difficulty,
+        interval_raw: interval_days as f64,
+        interval_days,
+        due_date: Date::new(due_date),
+        review_count: usize::try_from(card.reps).unwrap_or(0),
+    }
+}
+
+/// Convert the HTML of an Anki field to plain text: line breaks become
+/// newlines, other tags are removed, and common entities are decoded.
+fn strip_html(html: &str) -> String {
+    let html = html
+        .replace("<br>", "\n")
+        .replace("<br/>", "\n")
+        .replace("<br />", "\n");
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use super::*;
+    use crate::helper::create_tmp_directory;
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(strip_html("<b>Paris</b>&nbsp;"), "Paris");
+        assert_eq!(strip_html("a<br>b"), "a\nb");
+        assert_eq!(strip_html("1 &lt; 2 &amp;&amp; 3"), "1 < 2 && 3");
+    }
+
+    #[test]
+    fn test_to_performance() {
+        let today = Date::new(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+        let card = AnkiCard {
+            question: "Q".to_string(),
+            answer: "A".to_string(),
+            card_type: TYPE_REVIEW,
+            queue: 2,
+            due: 40,
+            interval: 10,
+            factor: 2500,
+            reps: 5,
+        };
+        // Anki's day 40 is 30 days after its day 10.
+        let performance = to_performance(&card, today, 10);
+        assert_eq!(
+            performance.due_date,
+            Date::new(NaiveDate::from_ymd_opt(2025, 1, 31).unwrap())
+        );
+        assert_eq!(
+            performance.last_reviewed_at.date(),
+            Date::new(NaiveDate::from_ymd_opt(2025, 1, 21).unwrap())
+        );
+        assert_eq!(performance.stability, 10.0);
+        assert_eq!(performance.difficulty, 4.0);
+        assert_eq!(performance.interval_days, 10);
+        assert_eq!(performance.review_count, 5);
+    }
+
+    /// Create an Anki collection with the minimal schema, and one card per
+    /// note: `(fields, type, queue, due, ivl, factor, reps)`.
+    fn make_anki_collection(
+        path: &str,
+        cards: &[(&str, i64, i64, i64, i64, i64, i64)],
+    ) -> Fallible<()> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "create table col (crt integer not null);
+             insert into col (crt) values (1735732800);
+             create table notes (id integer primary key, flds text not null);
+             create table cards (id integer primary key, nid integer not null, ord integer not null, type integer not null, queue integer not null, due integer not null, ivl integer not null, factor integer not null, reps integer not null);",
+        )?;
+        for (id, (fields, card_type, queue, due, ivl, factor, reps)) in cards.iter().enumerate() {
+            conn.execute(
+                "insert into notes (id, flds) values (?, ?);",
+                (id as i64, fields),
+            )?;
+            conn.execute(
+                "insert into cards (nid, ord, type, queue, due, ivl, factor, reps) values (?, 0, ?, ?, ?, ?, ?, ?);",
+                (id as i64, card_type, queue, due, ivl, factor, reps),
+            )?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_schedule() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        write(
+            dir.join("Deck.md"),
+            "Q: Capital of France?\nA: Paris\n\nQ: Capital of Spain?\nA: Madrid\n\nQ: Capital of Italy?\nA: Rome\n\nQ: Capital of Peru?\nA: Lima\n",
+        )?;
+        let anki = dir.join("collection.anki2").display().to_string();
+        make_anki_collection(
+            &anki,
+            &[
+                (
+                    "Capital of <b>France</b>?\u{1f}Paris",
+                    2,
+                    2,
+                    40,
+                    10,
+                    2500,
+                    5,
+                ),
+                ("Capital of Spain?\u{1f}Madrid", 2, -1, 40, 10, 2500, 5),
+                ("Capital of Italy?\u{1f}Rome", 0, 0, 1, 0, 0, 0),
+                ("Capital of Peru?\u{1f}Lima", 0, -1, 2, 0, 0, 0),
+                ("Capital of Chile?\u{1f}Santiago", 2, 2, 40, 10, 2500, 5),
+            ],
+        )?;
+        let coll = Collection::new(Some(dir.display().to_string()))?;
+        let summary = import_schedule(&coll, &anki)?;
+        assert_eq!(
+            summary,
+            ImportSummary {
+                imported: 2,
+                suspended: 2,
+                not_in_review: 1,
+                already_reviewed: 0,
+                unmatched: 1,
+            }
+        );
+        let france = coll
+            .cards
+            .iter()
+            .find(|card| card.content().to_source_text().contains("France"))
+            .unwrap();
+        match coll.db.get_card_performance(france.hash())? {
+            Performance::Reviewed(performance) => {
+                assert_eq!(performance.interval_days, 10);
+                assert_eq!(performance.review_count, 5);
+            }
+            Performance::New => panic!("Expected a reviewed card."),
+        }
+        // Cards suspended in Anki are suspended, with their schedule if they
+        // are in review.
+        let suspended: Vec<CardHash> = coll.db.suspended_cards()?;
+        assert_eq!(suspended.len(), 2);
+        let spain = coll
+            .cards
+            .iter()
+            .find(|card| card.content().to_source_text().contains("Spain"))
+            .unwrap();
+        assert!(suspended.contains(&spain.hash()));
+        assert!(!coll.db.get_card_performance(spain.hash())?.is_new());
+
+        // Importing again does not overwrite hashcards progress.
+        let summary = import_schedule(&coll, &anki)?;
+        assert_eq!(summary.imported, 0);
+        assert_eq!(summary.already_reviewed, 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Serialize;
+
+use crate::collection::Collection;
+use crate::error::Fallible;
+use crate::types::aliases::DeckName;
+use crate::types::card::Card;
+use crate::types::card::CardType;
+use crate::types::card_hash::CardHash;
+
+/// A card, as listed by `hashcards list --json`.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct CardListing {
+    hash: CardHash,
+    family_hash: Option<CardHash>,
+    deck_name: DeckName,
+    card_type: CardTypeListing,
+    /// The deck file, relative to the collection directory.
+    file_path: String,
+    /// The first line of the card, zero-based.
+    line_start: usize,
+    /// The last line of the card, zero-based.
+    line_end: usize,
+    source_text: String,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+enum CardTypeListing {
+    Basic,
+    Cloze,
+    TypeIn,
+}
+
+/// Print every card in the collection, ordered by file and line.
+pub fn list_cards(directory: Option<String>, json: bool) -> Fallible<()> {
+    let coll = Collection::new(directory)?;
+    let listings: Vec<CardListing> = get_listings(&coll)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&listings)?);
+    } else {
+        for listing in listings {
+            println!(
+                "{}  {}:{}  {}",
+                listing.hash,
+                listing.file_path,
+                listing.line_start + 1,
+                listing.source_text.lines().next().unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn get_listings(coll: &Collection) -> Fallible<Vec<CardListing>> {
+    let mut listings: Vec<CardListing> = Vec::new();
+    for card in coll.cards.iter() {
+        listings.push(get_listing(coll, card)?);
+    }
+    // Sort for stable output. Cloze siblings share a location, so break ties
+    // by hash.
+    listings.sort_by(|a, b| {
+        (&a.file_path, a.line_start, a.hash).cmp(&(&b.file_path, b.line_start, b.hash))
+    });
+    Ok(listings)
+}
+
+fn get_listing(coll: &Collection, card: &Card) -> Fallible<CardListing> {
+    let (line_start, line_end) = card.range();
+    Ok(CardListing {
+        hash: card.hash(),
+        family_hash: card.family_hash(),
+        deck_name: card.deck_name().clone(),
+        card_type: match card.card_type() {
+            CardType::Basic => CardTypeListing::Basic,
+            CardType::Cloze => CardTypeListing::Cloze,
+            CardType::TypeIn => CardTypeListing::TypeIn,
+        },
+        file_path: card
+            .relative_file_path(&coll.directory)?
+            .display()
+            .to_string(),
+        line_start,
+        line_end,
+        source_text: card.to_source_text(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::create_tmp_copy_of_test_directory;
+
+    #[test]
+    fn test_list_cards() -> Fallible<()> {
+        let dir: String = create_tmp_copy_of_test_directory()?;
+        let coll = Collection::new(Some(dir.clone()))?;
+        let listings = get_listings(&coll)?;
+        assert_eq!(listings.len(), 2);
+        assert_eq!(listings[0].card_type, CardTypeListing::Basic);
+        assert_eq!(listings[0].source_text, "Q: FOO\nA: BAR");
+        assert_eq!(listings[0].family_hash, None);
+        assert_eq!(listings[1].card_type, CardTypeListing::Cloze);
+        assert_eq!(listings[1].file_path, "Deck.md");
+        assert_eq!(listings[1].source_text, "C: baz [quux]");
+        assert!(listings[1].family_hash.is_some());
+
+        let json = serde_json::to_value(&listings[1])?;
+        // serde_json sorts the keys of a `Value`.
+        let keys: Vec<&String> = json.as_object().unwrap().keys().collect();
+        assert_eq!(
+            keys,
+            vec![
+                "cardType",
+                "deckName",
+                "familyHash",
+                "filePath",
+                "hash",
+                "lineEnd",
+                "lineStart",
+                "sourceText"
+            ]
+        );
+        assert_eq!(json["cardType"], "cloze");
+
+        list_cards(Some(dir.clone()), false)?;
+        list_cards(Some(dir), true)?;
+        Ok(())
+    }
+}
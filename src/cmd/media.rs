@@ -0,0 +1,217 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+use crate::collection::Collection;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::media::resolve::MediaResolver;
+use crate::media::resolve::MediaResolverBuilder;
+use crate::media::resolve::ResolveError;
+use crate::media::validate::card_media_paths;
+use crate::parser::frontmatter_lines;
+use crate::types::aliases::DeckName;
+
+/// A media file referenced in a card.
+#[derive(Debug, PartialEq)]
+struct MediaReference {
+    /// The path, as written in the card.
+    path: String,
+    /// The deck file the card is in, relative to the collection.
+    card_file: PathBuf,
+    /// The first line of the card in its file, counting from one, and
+    /// counting the frontmatter.
+    card_line: usize,
+    status: MediaStatus,
+}
+
+#[derive(Debug, PartialEq)]
+enum MediaStatus {
+    /// The file exists, at this absolute path.
+    Found(PathBuf),
+    /// The path is a remote URL, which is not available offline.
+    Remote,
+    /// The file does not exist, or the path is not allowed.
+    Missing(ResolveError),
+}
+
+/// List every media file referenced in the collection, by deck, with the
+/// absolute path it resolves to, to check that a collection can be drilled
+/// offline. Returns an error if any file is missing or remote.
+pub fn list_media(directory: Option<String>) -> Fallible<()> {
+    let coll = Collection::new_without_media_validation(directory)?;
+    let decks: Vec<(DeckName, Vec<MediaReference>)> = media_references(&coll)?;
+    let mut total: usize = 0;
+    let mut missing: usize = 0;
+    let mut remote: usize = 0;
+    for (deck_name, references) in &decks {
+        println!("{deck_name}");
+        for reference in references {
+            let location = format!("{}:{}", reference.card_file.display(), reference.card_line);
+            match &reference.status {
+                MediaStatus::Found(path) => {
+                    println!("  ok       {} -> {}", reference.path, path.display());
+                }
+                MediaStatus::Remote => {
+                    println!("  remote   {} ({location})", reference.path);
+                    remote += 1;
+                }
+                // The resolver reports files that do not exist as invalid.
+                MediaStatus::Missing(ResolveError::InvalidPath) => {
+                    println!("  missing  {} ({location})", reference.path);
+                    missing += 1;
+                }
+                MediaStatus::Missing(e) => {
+                    println!("  invalid  {} ({location}): {e}", reference.path);
+                    missing += 1;
+                }
+            }
+            total += 1;
+        }
+    }
+    println!("{total} media files referenced: {missing} missing, {remote} remote.");
+    if missing + remote > 0 {
+        return fail("some media files are not available locally.");
+    }
+    Ok(())
+}
+
+/// Resolve the media references of every card, grouped by deck. Decks with
+/// no media are left out.
+fn media_references(coll: &Collection) -> Fallible<Vec<(DeckName, Vec<MediaReference>)>> {
+    let mut decks: BTreeMap<DeckName, Vec<MediaReference>> = BTreeMap::new();
+    // Card lines are counted from the end of the frontmatter of their file.
+    let mut offsets: HashMap<PathBuf, usize> = HashMap::new();
+    for card in &coll.cards {
+        let card_file: PathBuf = card.relative_file_path(&coll.directory)?;
+        let resolver: MediaResolver = MediaResolverBuilder::new()
+            .with_collection_path(coll.directory.clone())?
+            .with_deck_path(card_file.clone())?
+            .build()?;
+        let paths: Vec<String> = card_media_paths(card);
+        if paths.is_empty() {
+            continue;
+        }
+        let offset: usize = match offsets.get(card.file_path()) {
+            Some(offset) => *offset,
+            None => {
+                let offset: usize = frontmatter_lines(&read_to_string(card.file_path())?)?;
+                offsets.insert(card.file_path().clone(), offset);
+                offset
+            }
+        };
+        for path in paths {
+            let status: MediaStatus = match resolver.resolve(&path) {
+                Ok(relative) => MediaStatus::Found(coll.directory.join(relative)),
+                Err(ResolveError::ExternalUrl) => MediaStatus::Remote,
+                Err(e) => MediaStatus::Missing(e),
+            };
+            let reference = MediaReference {
+                path,
+                card_file: card_file.clone(),
+                card_line: offset + card.range().0 + 1,
+                status,
+            };
+            let references = decks.entry(card.deck_name().clone()).or_default();
+            // Cloze cards made from the same text share their media.
+            if !references.contains(&reference) {
+                references.push(reference);
+            }
+        }
+    }
+    let mut decks: Vec<(DeckName, Vec<MediaReference>)> = decks.into_iter().collect();
+    coll.config
+        .sort_decks(&mut decks, |(deck_name, _)| deck_name);
+    Ok(decks)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::create_dir_all;
+    use std::fs::read_to_string;
+    use std::fs::write;
+
+    use super::*;
+    use crate::helper::create_tmp_copy_of_test_directory;
+    use crate::helper::create_tmp_directory;
+
+    #[test]
+    fn test_media_references() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        create_dir_all(dir.join("Spanish"))?;
+        write(dir.join("Spanish/perro.jpg"), "")?;
+        write(
+            dir.join("Spanish/Animals.md"),
+            "---\nname = \"Spanish::Animals\"\n---\nQ: ![](perro.jpg)\nA: dog\n\nQ: ![](gato.jpg)\nA: cat\n",
+        )?;
+        write(
+            dir.join("Physics.md"),
+            "C: [![](https://example.com/c.png)] is the speed of [light].\n",
+        )?;
+        write(dir.join("Chemistry.md"), "Q: H2O\nA: water\n")?;
+        let coll = Collection::new_without_media_validation(Some(dir.display().to_string()))?;
+        let decks = media_references(&coll)?;
+        let dir = dir.canonicalize()?;
+        assert_eq!(
+            decks,
+            vec![
+                (
+                    "Physics".to_string(),
+                    vec![MediaReference {
+                        path: "https://example.com/c.png".to_string(),
+                        card_file: PathBuf::from("Physics.md"),
+                        card_line: 1,
+                        status: MediaStatus::Remote,
+                    }]
+                ),
+                (
+                    "Spanish::Animals".to_string(),
+                    vec![
+                        MediaReference {
+                            path: "perro.jpg".to_string(),
+                            card_file: PathBuf::from("Spanish/Animals.md"),
+                            card_line: 4,
+                            status: MediaStatus::Found(dir.join("Spanish/perro.jpg")),
+                        },
+                        MediaReference {
+                            path: "gato.jpg".to_string(),
+                            card_file: PathBuf::from("Spanish/Animals.md"),
+                            card_line: 7,
+                            status: MediaStatus::Missing(ResolveError::InvalidPath),
+                        },
+                    ]
+                ),
+            ]
+        );
+        let result = list_media(Some(dir.display().to_string()));
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "error: some media files are not available locally."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_media_all_found() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+        let deck = PathBuf::from(&directory).join("Deck.md");
+        let text = read_to_string(&deck)?;
+        write(&deck, format!("{text}\nQ: ![](foo.jpg)\nA: foo\n"))?;
+        list_media(Some(directory))
+    }
+}
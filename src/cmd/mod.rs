@@ -12,8 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod browse;
 pub mod check;
 pub mod drill;
+pub mod due;
 pub mod export;
+pub mod fetch_media;
+pub mod import;
+pub mod import_schedule;
+pub mod list;
+pub mod media;
+pub mod open_source;
 pub mod orphans;
+pub mod report;
+pub mod reset;
 pub mod stats;
+pub mod unsuspend;
@@ -0,0 +1,121 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::env::var;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::collection::Collection;
+use crate::error::ErrorReport;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::parser::frontmatter_lines;
+use crate::types::card::Card;
+use crate::types::card_hash::CardHash;
+
+/// Open the source of the card with the given hash in `$EDITOR`, at the
+/// card's first line.
+pub fn open_source(directory: Option<String>, hash: String) -> Fallible<()> {
+    let coll = Collection::new(directory)?;
+    let hash = CardHash::from_hex(&hash)
+        .map_err(|_| ErrorReport::new(format!("Invalid card hash: '{hash}'.")))?;
+    let (path, line) = card_location(&coll.cards, hash)?;
+    let editor = var("EDITOR")
+        .map_err(|_| ErrorReport::new("Set $EDITOR to the editor to open cards in."))?;
+    let status = editor_command(&editor, &path, line)?.status()?;
+    if !status.success() {
+        return fail(format!("The editor exited with {status}."));
+    }
+    Ok(())
+}
+
+/// The file of the card with the given hash, and its first line, counting
+/// from one, as editors do.
+fn card_location(cards: &[Card], hash: CardHash) -> Fallible<(PathBuf, usize)> {
+    let Some(card) = cards.iter().find(|card| card.hash() == hash) else {
+        return fail(format!("No card with hash {hash} in the collection."));
+    };
+    // Card lines are counted from the end of the frontmatter.
+    let text = read_to_string(card.file_path())?;
+    let line = frontmatter_lines(&text)? + card.range().0 + 1;
+    Ok((card.file_path().clone(), line))
+}
+
+/// The command to open a file at a line: `$EDITOR +LINE FILE`, which most
+/// editors support. `$EDITOR` can include arguments, like `code --wait`.
+fn editor_command(editor: &str, path: &Path, line: usize) -> Fallible<Command> {
+    let mut words = editor.split_whitespace();
+    let Some(program) = words.next() else {
+        return fail("$EDITOR is empty.");
+    };
+    let mut command = Command::new(program);
+    command.args(words).arg(format!("+{line}")).arg(path);
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+    use std::fs::write;
+
+    use super::*;
+    use crate::helper::create_tmp_copy_of_test_directory;
+    use crate::types::card::CardType;
+
+    #[test]
+    fn test_card_location() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+        write(
+            PathBuf::from(&directory).join("Named.md"),
+            "---\nname = \"Named\"\n---\n\nQ: Zebra?\nA: Stripes.\n",
+        )?;
+        let coll = Collection::new(Some(directory))?;
+        let cloze = coll
+            .cards
+            .iter()
+            .find(|card| card.card_type() == CardType::Cloze)
+            .unwrap();
+        let (path, line) = card_location(&coll.cards, cloze.hash())?;
+        assert!(path.ends_with("Deck.md"));
+        assert_eq!(line, 4);
+        let named = coll
+            .cards
+            .iter()
+            .find(|card| card.deck_name() == "Named")
+            .unwrap();
+        let (path, line) = card_location(&coll.cards, named.hash())?;
+        assert!(path.ends_with("Named.md"));
+        assert_eq!(line, 5);
+
+        let missing = CardHash::hash_bytes(b"missing");
+        let err = card_location(&coll.cards, missing).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("error: No card with hash {missing} in the collection.")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_editor_command() -> Fallible<()> {
+        let command = editor_command("code --wait", Path::new("Deck.md"), 12)?;
+        assert_eq!(command.get_program(), "code");
+        let args: Vec<&OsStr> = command.get_args().collect();
+        assert_eq!(args, ["--wait", "+12", "Deck.md"]);
+        assert!(editor_command("  ", Path::new("Deck.md"), 1).is_err());
+        Ok(())
+    }
+}
@@ -17,6 +17,7 @@ use std::collections::HashSet;
 use crate::collection::Collection;
 use crate::error::Fallible;
 use crate::types::card_hash::CardHash;
+use crate::types::timestamp::Timestamp;
 
 pub fn list_orphans(directory: Option<String>) -> Fallible<()> {
     let coll = Collection::new(directory)?;
@@ -28,11 +29,14 @@ pub fn list_orphans(directory: Option<String>) -> Fallible<()> {
     Ok(())
 }
 
-pub fn delete_orphans(directory: Option<String>) -> Fallible<()> {
+/// Delete the orphan cards from the database. If `archive` is true, their
+/// reviews are moved to the `archived_reviews` table instead of being deleted.
+pub fn delete_orphans(directory: Option<String>, archive: bool) -> Fallible<()> {
     let coll = Collection::new(directory)?;
     let orphans: Vec<CardHash> = get_orphans(&coll)?;
+    let now: Timestamp = Timestamp::now();
     for hash in &orphans {
-        coll.db.delete_card(*hash)?;
+        coll.db.delete_card(*hash, archive, now)?;
         println!("{}", hash);
     }
     Ok(())
@@ -59,7 +63,6 @@ fn get_orphans(coll: &Collection) -> Fallible<Vec<CardHash>> {
 mod tests {
     use super::*;
     use crate::helper::create_tmp_copy_of_test_directory;
-    use crate::types::timestamp::Timestamp;
 
     #[test]
     fn test_get_orphans() -> Fallible<()> {
@@ -81,8 +84,29 @@ mod tests {
         let now = Timestamp::now();
         coll.db.insert_card(hash, now)?;
         list_orphans(Some(dir.clone()))?;
-        delete_orphans(Some(dir.clone()))?;
+        delete_orphans(Some(dir.clone()), false)?;
         assert!(coll.db.card_hashes()?.is_empty());
         Ok(())
     }
+
+    #[test]
+    fn test_delete_orphans_keeps_collection_cards() -> Fallible<()> {
+        let dir: String = create_tmp_copy_of_test_directory()?;
+        let coll = Collection::new(Some(dir.clone()))?;
+        let now = Timestamp::now();
+        for card in coll.cards.iter() {
+            coll.db.insert_card(card.hash(), now)?;
+        }
+        let orphans = [CardHash::hash_bytes(b"a"), CardHash::hash_bytes(b"b")];
+        for hash in orphans {
+            coll.db.insert_card(hash, now)?;
+        }
+        assert_eq!(get_orphans(&coll)?.len(), 2);
+        delete_orphans(Some(dir.clone()), true)?;
+        let remaining = coll.db.card_hashes()?;
+        assert_eq!(remaining.len(), coll.cards.len());
+        assert!(orphans.iter().all(|hash| !remaining.contains(hash)));
+        assert!(get_orphans(&coll)?.is_empty());
+        Ok(())
+    }
 }
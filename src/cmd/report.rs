@@ -0,0 +1,157 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use crate::collection::Collection;
+use crate::error::Fallible;
+use crate::types::card::Card;
+use crate::types::card_hash::CardHash;
+
+/// Maximum length of the card text shown in reports.
+const MAX_TEXT_LENGTH: usize = 60;
+
+/// A card's answer time statistics.
+#[derive(Debug, PartialEq)]
+struct SlowCard {
+    card_hash: CardHash,
+    /// Median time from revealing the answer to grading, in milliseconds.
+    median_ms: i64,
+    reviews: usize,
+}
+
+/// Print the cards with the highest median answer time.
+pub fn print_slow_cards(directory: Option<String>, limit: usize) -> Fallible<()> {
+    let coll = Collection::new(directory)?;
+    let cards: HashMap<CardHash, &Card> =
+        coll.cards.iter().map(|card| (card.hash(), card)).collect();
+    let times = coll.db.get_answer_times()?;
+    // Cards that are no longer in the collection are skipped.
+    let times = times
+        .into_iter()
+        .filter(|(card_hash, _)| cards.contains_key(card_hash))
+        .collect();
+    let slow_cards = slow_cards(times, limit);
+    if slow_cards.is_empty() {
+        println!("No answer times recorded.");
+        return Ok(());
+    }
+    for slow_card in slow_cards {
+        let card = cards[&slow_card.card_hash];
+        let noun = if slow_card.reviews == 1 {
+            "review"
+        } else {
+            "reviews"
+        };
+        println!(
+            "{:>7.1}s  {:>4} {noun:<7}  {}: {}",
+            slow_card.median_ms as f64 / 1000.0,
+            slow_card.reviews,
+            card.deck_name(),
+            card_summary(card)
+        );
+    }
+    Ok(())
+}
+
+/// Rank cards by their median answer time, slowest first.
+fn slow_cards(times: HashMap<CardHash, Vec<i64>>, limit: usize) -> Vec<SlowCard> {
+    let mut slow_cards: Vec<SlowCard> = times
+        .into_iter()
+        .filter(|(_, times)| !times.is_empty())
+        .map(|(card_hash, times)| SlowCard {
+            card_hash,
+            median_ms: median(times.clone()),
+            reviews: times.len(),
+        })
+        .collect();
+    slow_cards.sort_by(|a, b| {
+        b.median_ms
+            .cmp(&a.median_ms)
+            .then_with(|| a.card_hash.to_string().cmp(&b.card_hash.to_string()))
+    });
+    slow_cards.truncate(limit);
+    slow_cards
+}
+
+/// The median of a non-empty list. For an even number of elements, the mean
+/// of the two middle elements.
+fn median(mut values: Vec<i64>) -> i64 {
+    values.sort();
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2
+    } else {
+        values[mid]
+    }
+}
+
+/// The first line of the card's source text, truncated.
+fn card_summary(card: &Card) -> String {
+    let text = card.content().to_source_text();
+    let line = text.lines().next().unwrap_or_default();
+    if line.chars().count() > MAX_TEXT_LENGTH {
+        let truncated: String = line.chars().take(MAX_TEXT_LENGTH).collect();
+        format!("{truncated}…")
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::create_tmp_copy_of_test_directory;
+
+    #[test]
+    fn test_median() {
+        assert_eq!(median(vec![3]), 3);
+        assert_eq!(median(vec![5, 1, 3]), 3);
+        assert_eq!(median(vec![4, 1, 3, 2]), 2);
+    }
+
+    #[test]
+    fn test_slow_cards() {
+        let a = CardHash::hash_bytes(b"a");
+        let b = CardHash::hash_bytes(b"b");
+        let c = CardHash::hash_bytes(b"c");
+        let mut times = HashMap::new();
+        times.insert(a, vec![1000, 9000, 2000]);
+        times.insert(b, vec![5000]);
+        times.insert(c, vec![100, 200]);
+        let ranked = slow_cards(times, 2);
+        assert_eq!(
+            ranked,
+            vec![
+                SlowCard {
+                    card_hash: b,
+                    median_ms: 5000,
+                    reviews: 1
+                },
+                SlowCard {
+                    card_hash: a,
+                    median_ms: 2000,
+                    reviews: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_print_slow_cards() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+        print_slow_cards(Some(directory), 10)?;
+        Ok(())
+    }
+}
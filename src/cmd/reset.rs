@@ -0,0 +1,204 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::io::stdin;
+use std::io::stdout;
+
+use crate::collection::Collection;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::types::card_hash::CardHash;
+use crate::types::performance::Performance;
+use crate::types::timestamp::Timestamp;
+use crate::utils::glob_match;
+
+/// How to run a reset.
+pub struct ResetOptions {
+    /// Only print how many cards would be reset.
+    pub dry_run: bool,
+    /// Reset without asking for confirmation.
+    pub yes: bool,
+    /// Keep the reviews of the reset cards in the `archived_reviews` table,
+    /// instead of deleting them.
+    pub archive: bool,
+}
+
+/// Reset the scheduling state of the cards in the decks matching a pattern,
+/// so they are drilled as new cards.
+pub fn reset(directory: Option<String>, deck: String, options: ResetOptions) -> Fallible<()> {
+    let mut coll = Collection::new(directory)?;
+    let hashes: Vec<CardHash> = get_reset_targets(&coll, &deck)?;
+    if hashes.is_empty() {
+        println!("No reviewed cards in decks matching '{deck}'.");
+        return Ok(());
+    }
+    let count = hashes.len();
+    if options.dry_run {
+        println!("{count} cards would be reset.");
+        return Ok(());
+    }
+    if !options.yes && !confirm(&format!("Reset {count} cards in decks matching '{deck}'?"))? {
+        println!("Aborted.");
+        return Ok(());
+    }
+    coll.db
+        .reset_cards(&hashes, options.archive, Timestamp::now())?;
+    if options.archive {
+        println!("Reset {count} cards. Their reviews were archived.");
+    } else {
+        println!("Reset {count} cards.");
+    }
+    Ok(())
+}
+
+/// Find the cards to reset: the cards in matching decks that have been
+/// reviewed. New cards have nothing to reset.
+fn get_reset_targets(coll: &Collection, pattern: &str) -> Fallible<Vec<CardHash>> {
+    let matching: HashSet<CardHash> = coll
+        .cards
+        .iter()
+        .filter(|card| glob_match(pattern, card.deck_name()))
+        .map(|card| card.hash())
+        .collect();
+    if matching.is_empty() {
+        return fail(format!("no decks match the pattern '{pattern}'."));
+    }
+    let mut targets: Vec<CardHash> = Vec::new();
+    for hash in matching {
+        if let Some(Performance::Reviewed(_)) = coll.db.get_card_performance_opt(hash)? {
+            targets.push(hash);
+        }
+    }
+    // Sort the targets for consistent output.
+    targets.sort();
+    Ok(targets)
+}
+
+/// Ask a yes/no question on the terminal. Anything but "y" or "yes" is a no.
+fn confirm(question: &str) -> Fallible<bool> {
+    print!("{question} [y/N] ");
+    stdout().flush()?;
+    let mut answer = String::new();
+    stdin().read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use super::*;
+    use crate::error::ErrorReport;
+    use crate::helper::create_tmp_directory;
+    use crate::types::performance::ReviewedPerformance;
+
+    /// Mark every card in the collection as reviewed.
+    fn review_all(coll: &Collection) -> Fallible<()> {
+        let now = Timestamp::now();
+        for card in &coll.cards {
+            coll.db.insert_card(card.hash(), now)?;
+            let performance = ReviewedPerformance {
+                last_reviewed_at: now,
+                stability: 1.0,
+                difficulty: 5.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: now.date(),
+                review_count: 1,
+            };
+            coll.db
+                .update_card_performance(card.hash(), Performance::Reviewed(performance))?;
+        }
+        Ok(())
+    }
+
+    fn make_collection() -> Fallible<String> {
+        let dir = create_tmp_directory()?;
+        write(dir.join("Spanish.md"), "Q: uno\nA: one\n\nQ: dos\nA: two\n")?;
+        write(dir.join("Physics.md"), "Q: c\nA: speed of light\n")?;
+        Ok(dir.display().to_string())
+    }
+
+    fn is_new(coll: &Collection, deck: &str) -> Fallible<Vec<bool>> {
+        let mut new = Vec::new();
+        for card in coll.cards.iter().filter(|card| card.deck_name() == deck) {
+            let performance = coll.db.get_card_performance(card.hash())?;
+            new.push(performance == Performance::New);
+        }
+        Ok(new)
+    }
+
+    #[test]
+    fn test_reset_matching_decks() -> Fallible<()> {
+        let dir = make_collection()?;
+        let coll = Collection::new(Some(dir.clone()))?;
+        review_all(&coll)?;
+        let suspended = coll
+            .cards
+            .iter()
+            .find(|card| card.deck_name() == "Spanish")
+            .map(|card| card.hash())
+            .ok_or_else(|| ErrorReport::new("no Spanish card"))?;
+        coll.db.suspend_card(suspended)?;
+        drop(coll);
+        let options = ResetOptions {
+            dry_run: false,
+            yes: true,
+            archive: false,
+        };
+        reset(Some(dir.clone()), "Span*".to_string(), options)?;
+        let coll = Collection::new(Some(dir))?;
+        assert_eq!(is_new(&coll, "Spanish")?, vec![true, true]);
+        assert_eq!(is_new(&coll, "Physics")?, vec![false]);
+        assert!(get_reset_targets(&coll, "Spanish")?.is_empty());
+        // Resetting a suspended card keeps it suspended.
+        assert_eq!(coll.db.suspended_cards()?, vec![suspended]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_dry_run() -> Fallible<()> {
+        let dir = make_collection()?;
+        let coll = Collection::new(Some(dir.clone()))?;
+        review_all(&coll)?;
+        assert_eq!(get_reset_targets(&coll, "*")?.len(), 3);
+        let options = ResetOptions {
+            dry_run: true,
+            yes: true,
+            archive: false,
+        };
+        reset(Some(dir), "*".to_string(), options)?;
+        assert_eq!(get_reset_targets(&coll, "*")?.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_no_matching_deck() -> Fallible<()> {
+        let dir = make_collection()?;
+        let options = ResetOptions {
+            dry_run: true,
+            yes: true,
+            archive: false,
+        };
+        let result = reset(Some(dir), "Chemistry".to_string(), options);
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "error: no decks match the pattern 'Chemistry'."
+        );
+        Ok(())
+    }
+}
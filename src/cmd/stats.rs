@@ -12,18 +12,46 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod history;
+
+use std::collections::BTreeMap;
+use std::collections::HashSet;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::io::stdout;
 
+use chrono::Duration;
 use clap::ValueEnum;
 use serde::Serialize;
 
+use crate::cmd::drill::leech::count_lapses;
+use crate::cmd::due::card_due_dates;
+use crate::cmd::stats::history::HistoryFilter;
+use crate::cmd::stats::history::write_history;
 use crate::collection::Collection;
 use crate::error::Fallible;
+use crate::error::fail;
+use crate::fsrs::Grade;
+use crate::fsrs::Recall;
+use crate::fsrs::Stability;
+use crate::types::aliases::DeckName;
+use crate::types::card::CardType;
+use crate::types::card_hash::CardHash;
 use crate::types::date::Date;
+use crate::types::performance::DEFAULT_RETENTION;
+use crate::types::performance::IntervalBounds;
+use crate::types::performance::Performance;
+
+/// The retention levels to estimate the review load at, for comparison.
+const RETENTION_LEVELS: [Recall; 4] = [0.8, 0.85, 0.9, 0.95];
 
 #[derive(ValueEnum, Clone)]
 pub enum StatsFormat {
+    /// A human-readable summary.
+    Text,
     /// HTML output.
     Html,
     /// JSON output.
@@ -33,16 +61,62 @@ pub enum StatsFormat {
 impl Display for StatsFormat {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
+            StatsFormat::Text => write!(f, "text"),
             StatsFormat::Html => write!(f, "html"),
             StatsFormat::Json => write!(f, "json"),
         }
     }
 }
 
-pub fn print_stats(directory: Option<String>, format: StatsFormat) -> Fallible<()> {
-    let stats = get_stats(directory)?;
+/// Print the statistics of the collection, with the retention and pace of
+/// the last `days` days, the cards with at least `leech_threshold` lapses,
+/// and the review load within the interval `bounds`, or, with `export`, write the review history to that path, or to
+/// stdout if it is `-`.
+#[allow(clippy::too_many_arguments)]
+pub fn print_stats(
+    directory: Option<String>,
+    format: StatsFormat,
+    days: usize,
+    leech_threshold: usize,
+    bounds: IntervalBounds,
+    export: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    deck: Option<String>,
+) -> Fallible<()> {
+    let Some(output) = export else {
+        if from.is_some() || to.is_some() || deck.is_some() {
+            return fail("--from, --to, and --deck only apply with --export.");
+        }
+        return print_statistics(directory, format, days, leech_threshold, bounds);
+    };
+    let filter = HistoryFilter {
+        from: from.map(Date::try_from).transpose()?,
+        to: to.map(Date::try_from).transpose()?,
+        deck,
+    };
+    let coll = Collection::new(directory)?;
+    let writer: Box<dyn Write> = if output == "-" {
+        Box::new(stdout().lock())
+    } else {
+        Box::new(File::create(&output)?)
+    };
+    let count = write_history(&coll, &filter, BufWriter::new(writer))?;
+    eprintln!("Exported {count} reviews.");
+    Ok(())
+}
+
+fn print_statistics(
+    directory: Option<String>,
+    format: StatsFormat,
+    days: usize,
+    leech_threshold: usize,
+    bounds: IntervalBounds,
+) -> Fallible<()> {
+    let stats = get_stats(directory, days, leech_threshold, bounds)?;
     // Print.
     match format {
+        StatsFormat::Text => print!("{}", format_text(&stats)),
         StatsFormat::Html => {
             eprintln!("HTML output is not implemented yet.");
         }
@@ -61,28 +135,325 @@ pub struct Stats {
     cards_in_db_count: usize,
     tex_macro_count: usize,
     cards_reviewed_today_count: usize,
+    /// The collection's desired retention.
+    desired_retention: Recall,
+    /// Whether some decks have their own desired retention.
+    deck_retention: bool,
+    /// The estimated number of reviews a day, once every reviewed card is on
+    /// its schedule, at the desired retention of each deck.
+    estimated_reviews_per_day: f64,
+    /// The same estimate at other retention levels, for all cards.
+    review_load: Vec<ReviewLoad>,
+    /// Cards due today, including new cards, as in `hashcards due`.
+    cards_due_today_count: usize,
+    card_types: CardTypeCounts,
+    recent: RecentReviews,
+    /// The counts of each top-level deck, which include the decks under it.
+    decks: Vec<DeckStats>,
+    /// The cards with at least as many lapses as the leech threshold, with
+    /// the most lapses first.
+    leeches: Vec<LeechStats>,
 }
 
-fn get_stats(directory: Option<String>) -> Fallible<Stats> {
+#[derive(Serialize, PartialEq, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct CardTypeCounts {
+    basic: usize,
+    cloze: usize,
+    type_in: usize,
+}
+
+impl CardTypeCounts {
+    fn add(&mut self, card_type: CardType) {
+        match card_type {
+            CardType::Basic => self.basic += 1,
+            CardType::Cloze => self.cloze += 1,
+            CardType::TypeIn => self.type_in += 1,
+        }
+    }
+}
+
+/// The reviews of the last days.
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+struct RecentReviews {
+    /// The number of days, ending today.
+    days: usize,
+    review_count: usize,
+    /// The share of the reviews of cards reviewed before that were not
+    /// forgotten, from 0 to 1. A card's first review doesn't count, since
+    /// there was nothing to remember. `null` without such reviews.
+    retention: Option<f64>,
+    /// The average time per reviewed card in the sessions of the period, in
+    /// seconds, as on the completion page. `null` without reviews.
+    seconds_per_card: Option<f64>,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DeckStats {
+    deck_name: DeckName,
+    card_count: usize,
+    cards_due_today_count: usize,
+    card_types: CardTypeCounts,
+}
+
+/// A card forgotten often, counting lapses as the drill does.
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LeechStats {
+    card_hash: CardHash,
+    deck_name: DeckName,
+    lapses: usize,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ReviewLoad {
+    desired_retention: Recall,
+    estimated_reviews_per_day: f64,
+}
+
+fn get_stats(
+    directory: Option<String>,
+    days: usize,
+    leech_threshold: usize,
+    bounds: IntervalBounds,
+) -> Fallible<Stats> {
+    if days == 0 {
+        return fail("--days must be at least 1.");
+    }
+    if leech_threshold == 0 {
+        return fail("--leech-threshold must be at least 1.");
+    }
     let coll = Collection::new(directory)?;
     let cards_in_db_count = coll.db.card_hashes()?.len();
     let today = Date::today();
+    let retention = &coll.config.retention;
+    // The stability of every reviewed card, and the desired retention of its
+    // deck.
+    let mut cards: Vec<(Stability, Recall)> = Vec::new();
+    for card in coll.cards.iter() {
+        if let Some(Performance::Reviewed(performance)) =
+            coll.db.get_card_performance_opt(card.hash())?
+        {
+            let desired_retention = retention.desired_retention(card.deck_name());
+            cards.push((performance.stability, desired_retention));
+        }
+    }
+    let review_load: Vec<ReviewLoad> = RETENTION_LEVELS
+        .iter()
+        .map(|level| {
+            let cards: Vec<(Stability, Recall)> = cards.iter().map(|(s, _)| (*s, *level)).collect();
+            ReviewLoad {
+                desired_retention: *level,
+                estimated_reviews_per_day: estimate_daily_reviews(&cards, bounds),
+            }
+        })
+        .collect();
+    // Suspended cards are counted, but are never due.
+    let due: HashSet<CardHash> = card_due_dates(&coll.cards, &coll.db)?
+        .into_iter()
+        .filter(|(_, due_date)| due_date.is_none_or(|due_date| due_date <= today))
+        .map(|(card, _)| card.hash())
+        .collect();
+    let separator: &str = coll.config.deck_separator();
+    let mut card_types = CardTypeCounts::default();
+    let mut decks: BTreeMap<DeckName, DeckStats> = BTreeMap::new();
+    for card in coll.cards.iter() {
+        let top: &str = card.deck_name().split(separator).next().unwrap_or_default();
+        let deck = decks.entry(top.to_string()).or_insert_with(|| DeckStats {
+            deck_name: top.to_string(),
+            card_count: 0,
+            cards_due_today_count: 0,
+            card_types: CardTypeCounts::default(),
+        });
+        deck.card_count += 1;
+        deck.card_types.add(card.card_type());
+        card_types.add(card.card_type());
+        if due.contains(&card.hash()) {
+            deck.cards_due_today_count += 1;
+        }
+    }
+    let mut decks: Vec<DeckStats> = decks.into_values().collect();
+    coll.config.sort_decks(&mut decks, |deck| &deck.deck_name);
     let stats = Stats {
         cards_in_deck_count: coll.cards.len(),
         cards_in_db_count,
         tex_macro_count: coll.macros.len(),
         cards_reviewed_today_count: coll.db.count_reviews_in_date(today)?,
+        desired_retention: retention.target.unwrap_or(DEFAULT_RETENTION),
+        deck_retention: !retention.decks.is_empty(),
+        estimated_reviews_per_day: estimate_daily_reviews(&cards, bounds),
+        review_load,
+        cards_due_today_count: due.len(),
+        card_types,
+        recent: recent_reviews(&coll, today, days)?,
+        decks,
+        leeches: find_leeches(&coll, leech_threshold)?,
     };
     Ok(stats)
 }
 
+/// The retention and pace of the reviews of the last `days` days, ending
+/// today.
+fn recent_reviews(coll: &Collection, today: Date, days: usize) -> Fallible<RecentReviews> {
+    let first: Date = Date::new(today.into_inner() - Duration::days(days as i64 - 1));
+    // Every review is read, to know which cards were reviewed before.
+    let mut seen: HashSet<CardHash> = HashSet::new();
+    let mut review_count: usize = 0;
+    let mut repeated: usize = 0;
+    let mut remembered: usize = 0;
+    coll.db.for_each_review(None, Some(today), |review| {
+        let first_review: bool = seen.insert(review.data.card_hash);
+        if review.data.reviewed_at.date() >= first {
+            review_count += 1;
+            if !first_review {
+                repeated += 1;
+                if review.data.grade != Grade::Forgot {
+                    remembered += 1;
+                }
+            }
+        }
+        Ok(())
+    })?;
+    let mut seconds: i64 = 0;
+    let mut session_reviews: usize = 0;
+    for session in coll.db.get_all_sessions()? {
+        if session.started_at.date() >= first {
+            seconds +=
+                (session.ended_at.into_inner() - session.started_at.into_inner()).num_seconds();
+            session_reviews += coll.db.get_reviews_for_session(session.session_id)?.len();
+        }
+    }
+    Ok(RecentReviews {
+        days,
+        review_count,
+        retention: (repeated > 0).then(|| remembered as f64 / repeated as f64),
+        seconds_per_card: (session_reviews > 0).then(|| seconds as f64 / session_reviews as f64),
+    })
+}
+
+/// The cards of the collection with at least `threshold` lapses, with the
+/// most lapses first.
+fn find_leeches(coll: &Collection, threshold: usize) -> Fallible<Vec<LeechStats>> {
+    let mut leeches: Vec<LeechStats> = Vec::new();
+    for card in coll.cards.iter() {
+        let lapses: usize = count_lapses(&coll.db.get_grades(card.hash())?);
+        if lapses >= threshold {
+            leeches.push(LeechStats {
+                card_hash: card.hash(),
+                deck_name: card.deck_name().clone(),
+                lapses,
+            });
+        }
+    }
+    leeches.sort_by(|a, b| {
+        b.lapses
+            .cmp(&a.lapses)
+            .then_with(|| a.deck_name.cmp(&b.deck_name))
+    });
+    Ok(leeches)
+}
+
+/// The statistics as a human-readable summary.
+fn format_text(stats: &Stats) -> String {
+    let mut text: String = String::new();
+    let types: &CardTypeCounts = &stats.card_types;
+    text.push_str(&format!(
+        "Cards:      {} ({} basic, {} cloze, {} type-in)\n",
+        stats.cards_in_deck_count, types.basic, types.cloze, types.type_in
+    ));
+    text.push_str(&format!("Due today:  {}\n", stats.cards_due_today_count));
+    text.push_str(&format!(
+        "Reviewed:   {} today, {} in the last {} days\n",
+        stats.cards_reviewed_today_count, stats.recent.review_count, stats.recent.days
+    ));
+    if let Some(retention) = stats.recent.retention {
+        text.push_str(&format!("Retention:  {:.1}%\n", retention * 100.0));
+    }
+    if let Some(seconds) = stats.recent.seconds_per_card {
+        text.push_str(&format!("Pace:       {seconds:.1} seconds per card\n"));
+    }
+    text.push_str(&format!("Load:       {}\n", load_summary(stats)));
+    if !stats.decks.is_empty() {
+        let width: usize = stats
+            .decks
+            .iter()
+            .map(|deck| deck.deck_name.chars().count())
+            .chain(["Deck".len()])
+            .max()
+            .unwrap_or_default();
+        text.push_str(&format!(
+            "\n{:width$}  {:>5}  {:>5}  {:>5}  {:>5}  {:>7}\n",
+            "Deck", "Cards", "Due", "Basic", "Cloze", "Type-in"
+        ));
+        for deck in &stats.decks {
+            let types: &CardTypeCounts = &deck.card_types;
+            text.push_str(&format!(
+                "{:width$}  {:>5}  {:>5}  {:>5}  {:>5}  {:>7}\n",
+                deck.deck_name,
+                deck.card_count,
+                deck.cards_due_today_count,
+                types.basic,
+                types.cloze,
+                types.type_in
+            ));
+        }
+    }
+    if !stats.leeches.is_empty() {
+        text.push_str(&format!("\nLeeches:    {}\n", stats.leeches.len()));
+        for leech in &stats.leeches {
+            text.push_str(&format!(
+                "{}  {:>3} lapses  {}\n",
+                leech.card_hash, leech.lapses, leech.deck_name
+            ));
+        }
+    }
+    text
+}
+
+/// The average number of reviews a day in the long run: each card is
+/// reviewed once per interval, so it adds the inverse of its interval. New
+/// cards are not counted, nor are lapses, which shorten intervals for a
+/// while.
+fn estimate_daily_reviews(cards: &[(Stability, Recall)], bounds: IntervalBounds) -> f64 {
+    cards
+        .iter()
+        .map(|(stability, retention)| 1.0 / bounds.interval_days(*stability, *retention))
+        .sum()
+}
+
+/// E.g. "≈12 reviews/day at 90% retention."
+fn load_summary(stats: &Stats) -> String {
+    let percent = (stats.desired_retention * 100.0).round();
+    let decks = if stats.deck_retention {
+        ", with the deck targets"
+    } else {
+        ""
+    };
+    format!(
+        "≈{} reviews/day at {percent}% retention{decks}.",
+        stats.estimated_reviews_per_day.round()
+    )
+}
+
 #[cfg(test)]
 mod tests {
+    use std::fs::create_dir_all;
+    use std::fs::write;
+
     use super::*;
+    use crate::cmd::drill::leech::DEFAULT_LEECH_THRESHOLD;
+    use crate::db::ReviewRecord;
     use crate::helper::create_tmp_copy_of_test_directory;
+    use crate::helper::create_tmp_directory;
+    use crate::types::performance::ReviewedPerformance;
+    use crate::types::timestamp::Timestamp;
 
     #[test]
     fn test_display_stats_format() {
+        assert_eq!(StatsFormat::Text.to_string(), "text");
         assert_eq!(StatsFormat::Html.to_string(), "html");
         assert_eq!(StatsFormat::Json.to_string(), "json");
     }
@@ -90,24 +461,307 @@ mod tests {
     #[test]
     fn test_print_stats_json() -> Fallible<()> {
         let directory = create_tmp_copy_of_test_directory()?;
-        print_stats(Some(directory), StatsFormat::Json)?;
+        print_stats(
+            Some(directory),
+            StatsFormat::Json,
+            30,
+            DEFAULT_LEECH_THRESHOLD,
+            IntervalBounds::default(),
+            None,
+            None,
+            None,
+            None,
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_history() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+        let output = create_tmp_directory()?.join("reviews.json");
+        print_stats(
+            Some(directory.clone()),
+            StatsFormat::Json,
+            30,
+            DEFAULT_LEECH_THRESHOLD,
+            IntervalBounds::default(),
+            Some(output.display().to_string()),
+            Some("2025-01-01".to_string()),
+            None,
+            None,
+        )?;
+        let reviews: Vec<serde_json::Value> = serde_json::from_slice(&std::fs::read(&output)?)?;
+        assert!(reviews.is_empty());
+
+        // The filters need `--export`.
+        let result = print_stats(
+            Some(directory.clone()),
+            StatsFormat::Json,
+            30,
+            DEFAULT_LEECH_THRESHOLD,
+            IntervalBounds::default(),
+            None,
+            None,
+            None,
+            Some("Geology".to_string()),
+        );
+        assert_eq!(
+            result.err().unwrap().message(),
+            "--from, --to, and --deck only apply with --export."
+        );
+
+        let result = print_stats(
+            Some(directory),
+            StatsFormat::Json,
+            30,
+            DEFAULT_LEECH_THRESHOLD,
+            IntervalBounds::default(),
+            Some(output.display().to_string()),
+            Some("March 1st".to_string()),
+            None,
+            None,
+        );
+        assert_eq!(result.err().unwrap().message(), "invalid date: March 1st");
         Ok(())
     }
 
     #[test]
     fn test_get_stats() -> Fallible<()> {
         let directory = create_tmp_copy_of_test_directory()?;
-        let stats = get_stats(Some(directory)).unwrap();
+        let stats = get_stats(
+            Some(directory),
+            30,
+            DEFAULT_LEECH_THRESHOLD,
+            IntervalBounds::default(),
+        )
+        .unwrap();
         let Stats {
             cards_in_deck_count,
             cards_in_db_count,
             tex_macro_count,
             cards_reviewed_today_count,
+            desired_retention,
+            deck_retention,
+            estimated_reviews_per_day,
+            review_load,
+            cards_due_today_count,
+            card_types,
+            recent,
+            decks,
+            leeches,
         } = stats;
         assert_eq!(cards_in_deck_count, 2);
         assert_eq!(cards_in_db_count, 0);
         assert_eq!(tex_macro_count, 1);
         assert_eq!(cards_reviewed_today_count, 0);
+        assert_eq!(desired_retention, DEFAULT_RETENTION);
+        assert!(!deck_retention);
+        assert_eq!(estimated_reviews_per_day, 0.0);
+        assert_eq!(review_load.len(), RETENTION_LEVELS.len());
+        assert_eq!(cards_due_today_count, 2);
+        assert_eq!(card_types.basic + card_types.cloze + card_types.type_in, 2);
+        assert_eq!(recent.review_count, 0);
+        assert_eq!(recent.retention, None);
+        assert_eq!(recent.seconds_per_card, None);
+        assert_eq!(decks.iter().map(|deck| deck.card_count).sum::<usize>(), 2);
+        assert!(leeches.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deck_and_review_stats() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        create_dir_all(dir.join("languages"))?;
+        write(
+            dir.join("languages/spanish.md"),
+            "Q: a\nA: b\n\nC: [c] and [d]\n",
+        )?;
+        write(dir.join("languages/french.md"), "T: e\nA: f\n")?;
+        write(dir.join("Geology.md"), "Q: g\nA: h\n")?;
+        let mut coll = Collection::new(Some(dir.display().to_string()))?;
+        coll.cards.sort_by_key(|card| card.hash());
+        let geology = coll
+            .cards
+            .iter()
+            .find(|card| card.deck_name() == "Geology")
+            .unwrap()
+            .clone();
+        let today = Date::today();
+        let now = Timestamp::now();
+        let end = Timestamp::new(now.into_inner() + Duration::seconds(30));
+        coll.db.insert_card(geology.hash(), now)?;
+        let review = |grade: Grade, at: Timestamp| ReviewRecord {
+            card_hash: geology.hash(),
+            reviewed_at: at,
+            grade,
+            stability: 1.0,
+            difficulty: 5.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: Date::new(today.into_inner() + Duration::days(1)),
+            revealed_at: None,
+            confidence: None,
+            duration_ms: None,
+        };
+        // The first review doesn't count towards retention: of the other
+        // two, one was forgotten.
+        coll.db.save_session(
+            now,
+            end,
+            vec![
+                review(Grade::Good, now),
+                review(Grade::Forgot, now),
+                review(Grade::Good, now),
+            ],
+        )?;
+        coll.db.update_card_performance(
+            geology.hash(),
+            Performance::Reviewed(ReviewedPerformance {
+                last_reviewed_at: now,
+                stability: 1.0,
+                difficulty: 5.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: Date::new(today.into_inner() + Duration::days(1)),
+                review_count: 3,
+            }),
+        )?;
+
+        // With a threshold of one lapse, Geology is a leech.
+        let stats = get_stats(
+            Some(dir.display().to_string()),
+            7,
+            1,
+            IntervalBounds::default(),
+        )?;
+        assert_eq!(stats.cards_due_today_count, 4);
+        assert_eq!(
+            stats.card_types,
+            CardTypeCounts {
+                basic: 2,
+                cloze: 2,
+                type_in: 1,
+            }
+        );
+        assert_eq!(
+            stats.recent,
+            RecentReviews {
+                days: 7,
+                review_count: 3,
+                retention: Some(0.5),
+                seconds_per_card: Some(10.0),
+            }
+        );
+        assert_eq!(
+            stats.decks,
+            vec![
+                DeckStats {
+                    deck_name: "Geology".to_string(),
+                    card_count: 1,
+                    cards_due_today_count: 0,
+                    card_types: CardTypeCounts {
+                        basic: 1,
+                        cloze: 0,
+                        type_in: 0,
+                    },
+                },
+                DeckStats {
+                    deck_name: "languages".to_string(),
+                    card_count: 4,
+                    cards_due_today_count: 4,
+                    card_types: CardTypeCounts {
+                        basic: 1,
+                        cloze: 2,
+                        type_in: 1,
+                    },
+                },
+            ]
+        );
+
+        let text = format_text(&stats);
+        assert!(text.starts_with(
+            "Cards:      5 (2 basic, 2 cloze, 1 type-in)\nDue today:  4\nReviewed:   3 today, 3 in the last 7 days\nRetention:  50.0%\nPace:       10.0 seconds per card\n"
+        ));
+        assert!(text.contains(
+            "Deck       Cards    Due  Basic  Cloze  Type-in\nGeology        1      0      1      0        0\nlanguages      4      4      1      2        1\n"
+        ));
+        assert_eq!(
+            stats.leeches,
+            vec![LeechStats {
+                card_hash: geology.hash(),
+                deck_name: "Geology".to_string(),
+                lapses: 1,
+            }]
+        );
+        assert!(text.ends_with(&format!(
+            "\nLeeches:    1\n{}    1 lapses  Geology\n",
+            geology.hash()
+        )));
+        assert!(
+            get_stats(
+                Some(dir.display().to_string()),
+                7,
+                2,
+                IntervalBounds::default()
+            )?
+            .leeches
+            .is_empty()
+        );
+
+        assert!(
+            get_stats(
+                Some(dir.display().to_string()),
+                0,
+                1,
+                IntervalBounds::default()
+            )
+            .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate_daily_reviews() -> Fallible<()> {
+        let bounds = IntervalBounds::default();
+        // At 90% retention, the interval is the stability: 1, 10, and 100
+        // days.
+        let cards = [(1.0, 0.9), (10.0, 0.9), (100.0, 0.9)];
+        let load = estimate_daily_reviews(&cards, bounds);
+        assert!((load - 1.11).abs() < 1e-9);
+        // Intervals are at least a day, so a card is reviewed at most once a
+        // day.
+        assert_eq!(estimate_daily_reviews(&[(0.1, 0.9)], bounds), 1.0);
+        // Higher retention costs more reviews.
+        let lower = estimate_daily_reviews(&[(10.0, 0.8), (100.0, 0.8)], bounds);
+        let higher = estimate_daily_reviews(&[(10.0, 0.95), (100.0, 0.95)], bounds);
+        assert!(lower < 0.11);
+        assert!(higher > 0.11);
+        assert_eq!(estimate_daily_reviews(&[], bounds), 0.0);
+        // The interval bounds apply: with a maximum of 10 days, the card
+        // with 100 days of stability is reviewed every 10 days.
+        let capped = IntervalBounds::new(None, Some(10))?;
+        let load = estimate_daily_reviews(&cards, capped);
+        assert!((load - 1.2).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_summary() -> Fallible<()> {
+        let directory = create_tmp_copy_of_test_directory()?;
+        let mut stats = get_stats(
+            Some(directory),
+            30,
+            DEFAULT_LEECH_THRESHOLD,
+            IntervalBounds::default(),
+        )?;
+        stats.estimated_reviews_per_day = 12.4;
+        assert_eq!(load_summary(&stats), "≈12 reviews/day at 90% retention.");
+        stats.desired_retention = 0.85;
+        stats.deck_retention = true;
+        assert_eq!(
+            load_summary(&stats),
+            "≈12 reviews/day at 85% retention, with the deck targets."
+        );
         Ok(())
     }
 }
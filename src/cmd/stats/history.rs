@@ -0,0 +1,248 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Exporting the review history as JSON, to analyze it elsewhere.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::collection::Collection;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::fsrs::Grade;
+use crate::types::aliases::DeckName;
+use crate::types::card_hash::CardHash;
+use crate::types::date::Date;
+use crate::types::timestamp::Timestamp;
+use crate::utils::glob_match;
+
+/// A review in the exported history, which is a JSON array of these, in the
+/// order the reviews were made. The format is stable: fields may be added,
+/// but are not renamed or removed.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryReview {
+    /// The hash of the card, in hex.
+    card_hash: CardHash,
+    /// The deck of the card, or `null` if the card is no longer in the
+    /// collection.
+    deck_name: Option<DeckName>,
+    /// When the card was reviewed, in local time, as
+    /// `YYYY-MM-DDTHH:MM:SS.sss`.
+    reviewed_at: Timestamp,
+    /// The grade: `Forgot`, `Hard`, `Good`, or `Easy`.
+    grade: Grade,
+    /// The interval until the next review, in days.
+    interval_days: i64,
+    /// The date of the next review, as `YYYY-MM-DD`.
+    due_date: Date,
+    /// The stability of the card after the review, in days.
+    stability: f64,
+    /// The difficulty of the card after the review, from 1 to 10.
+    difficulty: f64,
+    /// How long the card took to answer, in milliseconds, as timed by
+    /// `drill`, or `null` for reviews made before cards were timed.
+    duration_ms: Option<i64>,
+}
+
+/// Which reviews to export.
+pub struct HistoryFilter {
+    /// The first date of the reviews, inclusive.
+    pub from: Option<Date>,
+    /// The last date of the reviews, inclusive.
+    pub to: Option<Date>,
+    /// A pattern that the deck of the reviewed card must match. Reviews of
+    /// cards no longer in the collection have no deck, and are left out.
+    pub deck: Option<String>,
+}
+
+/// Write the reviews that pass the filter as a JSON array, a review at a
+/// time, and return how many were written.
+pub fn write_history(
+    coll: &Collection,
+    filter: &HistoryFilter,
+    mut writer: impl Write,
+) -> Fallible<usize> {
+    if let (Some(from), Some(to)) = (filter.from, filter.to) {
+        if from > to {
+            return fail(format!("--from ({from}) is after --to ({to})."));
+        }
+    }
+    let decks: HashMap<CardHash, &DeckName> = coll
+        .cards
+        .iter()
+        .map(|card| (card.hash(), card.deck_name()))
+        .collect();
+    let mut count: usize = 0;
+    writer.write_all(b"[")?;
+    coll.db.for_each_review(filter.from, filter.to, |review| {
+        let deck_name: Option<&DeckName> = decks.get(&review.data.card_hash).copied();
+        if let Some(pattern) = &filter.deck {
+            if !deck_name.is_some_and(|deck_name| glob_match(pattern, deck_name)) {
+                return Ok(());
+            }
+        }
+        let entry = HistoryReview {
+            card_hash: review.data.card_hash,
+            deck_name: deck_name.cloned(),
+            reviewed_at: review.data.reviewed_at,
+            grade: review.data.grade,
+            interval_days: review.data.interval_days,
+            due_date: review.data.due_date,
+            stability: review.data.stability,
+            difficulty: review.data.difficulty,
+            duration_ms: review.data.duration_ms,
+        };
+        writer.write_all(if count == 0 { b"\n  " } else { b",\n  " })?;
+        serde_json::to_writer(&mut writer, &entry)?;
+        count += 1;
+        Ok(())
+    })?;
+    writer.write_all(if count == 0 { b"]\n" } else { b"\n]\n" })?;
+    writer.flush()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use chrono::NaiveDate;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::db::ReviewRecord;
+    use crate::helper::create_tmp_directory;
+
+    fn date(day: u32) -> Date {
+        Date::new(NaiveDate::from_ymd_opt(2025, 3, day).unwrap())
+    }
+
+    fn review(coll: &Collection, index: usize, day: u32) -> ReviewRecord {
+        let reviewed_at = Timestamp::new(date(day).into_inner().and_hms_opt(9, 0, 0).unwrap());
+        ReviewRecord {
+            card_hash: coll.cards[index].hash(),
+            reviewed_at,
+            grade: Grade::Good,
+            stability: 2.5,
+            difficulty: 5.0,
+            interval_raw: 2.5,
+            interval_days: 3,
+            due_date: date(day + 3),
+            revealed_at: None,
+            confidence: None,
+            duration_ms: None,
+        }
+    }
+
+    fn export(coll: &Collection, filter: HistoryFilter) -> Fallible<Vec<Value>> {
+        let mut output: Vec<u8> = Vec::new();
+        let count = write_history(coll, &filter, &mut output)?;
+        let reviews: Vec<Value> = serde_json::from_slice(&output)?;
+        assert_eq!(reviews.len(), count);
+        Ok(reviews)
+    }
+
+    #[test]
+    fn test_write_history() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        write(dir.join("Geology.md"), "Q: a\nA: b\n")?;
+        write(dir.join("Biology.md"), "Q: c\nA: d\n")?;
+        let mut coll = Collection::new(Some(dir.display().to_string()))?;
+        coll.cards.sort_by_key(|card| card.deck_name().clone());
+        let now = Timestamp::now();
+        for card in coll.cards.iter() {
+            coll.db.insert_card(card.hash(), now)?;
+        }
+        let reviews = vec![
+            review(&coll, 0, 1),
+            review(&coll, 1, 2),
+            review(&coll, 0, 5),
+        ];
+        coll.db.save_session(now, now, reviews)?;
+
+        let all = export(
+            &coll,
+            HistoryFilter {
+                from: None,
+                to: None,
+                deck: None,
+            },
+        )?;
+        assert_eq!(all.len(), 3);
+        assert_eq!(
+            all[0],
+            serde_json::json!({
+                "cardHash": coll.cards[0].hash().to_hex(),
+                "deckName": "Biology",
+                "reviewedAt": "2025-03-01T09:00:00.000",
+                "grade": "Good",
+                "intervalDays": 3,
+                "dueDate": "2025-03-04",
+                "stability": 2.5,
+                "difficulty": 5.0,
+                "durationMs": null,
+            })
+        );
+        assert_eq!(all[1]["deckName"], "Geology");
+
+        let range = export(
+            &coll,
+            HistoryFilter {
+                from: Some(date(2)),
+                to: Some(date(5)),
+                deck: None,
+            },
+        )?;
+        assert_eq!(range.len(), 2);
+        assert_eq!(range[0]["reviewedAt"], "2025-03-02T09:00:00.000");
+
+        let deck = export(
+            &coll,
+            HistoryFilter {
+                from: None,
+                to: Some(date(4)),
+                deck: Some("Bio*".to_string()),
+            },
+        )?;
+        assert_eq!(deck.len(), 1);
+        assert_eq!(deck[0]["deckName"], "Biology");
+
+        let none = export(
+            &coll,
+            HistoryFilter {
+                from: Some(date(6)),
+                to: None,
+                deck: None,
+            },
+        )?;
+        assert!(none.is_empty());
+
+        let result = export(
+            &coll,
+            HistoryFilter {
+                from: Some(date(5)),
+                to: Some(date(1)),
+                deck: None,
+            },
+        );
+        assert_eq!(
+            result.err().unwrap().message(),
+            "--from (2025-03-05) is after --to (2025-03-01)."
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::collection::Collection;
+use crate::error::ErrorReport;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::types::card::Card;
+use crate::types::card_hash::CardHash;
+
+/// Unsuspend the cards with the given hashes, or every suspended card. With
+/// neither, print the suspended cards.
+pub fn unsuspend(directory: Option<String>, cards: Vec<String>, all: bool) -> Fallible<()> {
+    let coll = Collection::new(directory)?;
+    let suspended: Vec<CardHash> = coll.db.suspended_cards()?;
+    if cards.is_empty() && !all {
+        if suspended.is_empty() {
+            println!("No suspended cards.");
+        }
+        for hash in suspended {
+            println!("{}", describe(&coll.cards, hash));
+        }
+        return Ok(());
+    }
+    let targets: Vec<CardHash> = if all {
+        suspended
+    } else {
+        let mut targets: Vec<CardHash> = Vec::new();
+        for card in &cards {
+            let hash = CardHash::from_hex(card)
+                .map_err(|_| ErrorReport::new(format!("Invalid card hash: '{card}'.")))?;
+            if !suspended.contains(&hash) {
+                return fail(format!("The card {hash} is not suspended."));
+            }
+            targets.push(hash);
+        }
+        targets
+    };
+    for hash in &targets {
+        coll.db.unsuspend_card(*hash)?;
+    }
+    println!("Unsuspended {} cards.", targets.len());
+    Ok(())
+}
+
+/// A card's hash, followed by its deck, if it is still in the collection.
+fn describe(cards: &[Card], hash: CardHash) -> String {
+    match cards.iter().find(|card| card.hash() == hash) {
+        Some(card) => format!("{hash} {}", card.deck_name()),
+        None => format!("{hash} (not in the collection)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use super::*;
+    use crate::helper::create_tmp_directory;
+    use crate::types::timestamp::Timestamp;
+
+    fn make_collection() -> Fallible<(String, Vec<CardHash>)> {
+        let dir = create_tmp_directory()?;
+        write(dir.join("Spanish.md"), "Q: uno\nA: one\n\nQ: dos\nA: two\n")?;
+        let dir = dir.display().to_string();
+        let coll = Collection::new(Some(dir.clone()))?;
+        let hashes: Vec<CardHash> = coll.cards.iter().map(|card| card.hash()).collect();
+        for hash in &hashes {
+            coll.db.insert_card(*hash, Timestamp::now())?;
+            coll.db.suspend_card(*hash)?;
+        }
+        Ok((dir, hashes))
+    }
+
+    #[test]
+    fn test_unsuspend_card() -> Fallible<()> {
+        let (dir, hashes) = make_collection()?;
+        unsuspend(Some(dir.clone()), vec![hashes[0].to_hex()], false)?;
+        let coll = Collection::new(Some(dir))?;
+        assert_eq!(coll.db.suspended_cards()?, vec![hashes[1]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsuspend_all() -> Fallible<()> {
+        let (dir, _) = make_collection()?;
+        unsuspend(Some(dir.clone()), Vec::new(), true)?;
+        let coll = Collection::new(Some(dir))?;
+        assert!(coll.db.suspended_cards()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsuspend_not_suspended() -> Fallible<()> {
+        let (dir, hashes) = make_collection()?;
+        unsuspend(Some(dir.clone()), vec![hashes[0].to_hex()], false)?;
+        let result = unsuspend(Some(dir), vec![hashes[0].to_hex()], false);
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            format!("error: The card {} is not suspended.", hashes[0])
+        );
+        Ok(())
+    }
+}
@@ -12,28 +12,92 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::env::current_dir;
 use std::fs::read_to_string;
+use std::path::Path;
 use std::path::PathBuf;
 use std::time::Instant;
 
+use crate::config::CardRegex;
+use crate::config::CollectionConfig;
 use crate::db::Database;
 use crate::error::ErrorReport;
 use crate::error::Fallible;
 use crate::error::fail;
+use crate::ignore::IgnoreRules;
 use crate::media::validate::validate_media_files;
+use crate::parse_cache::CacheMode;
+use crate::parse_cache::ParseCache;
 use crate::parser::parse_deck;
+use crate::parser::parse_deck_cached;
 use crate::types::card::Card;
+use crate::types::card_hash::CardHash;
+use crate::types::timestamp::Timestamp;
+use std::collections::HashSet;
 
 pub struct Collection {
     pub directory: PathBuf,
     pub db: Database,
     pub cards: Vec<Card>,
     pub macros: Vec<(String, String)>,
+    pub config: CollectionConfig,
+}
+
+/// The file with the collection's KaTeX macros.
+pub const MACROS_FILE: &str = "macros.tex";
+
+/// Added to the error when `drill` can't open the review store.
+const NO_STORE_HINT: &str = "To study without saving reviews, run `hashcards drill --no-store`.";
+
+/// How to open the review store of a collection.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StoreMode {
+    /// Read and write the store.
+    ReadWrite,
+    /// Work on an in-memory copy of the store, so nothing is written to it.
+    /// If the store cannot be read, e.g. because it is corrupt, start from an
+    /// empty one.
+    NoPersistence,
 }
 
 impl Collection {
     pub fn new(directory: Option<String>) -> Fallible<Self> {
+        let coll = Self::load(directory, StoreMode::ReadWrite, CacheMode::Enabled)?;
+        validate_media_files(&coll.cards, &coll.directory)?;
+        Ok(coll)
+    }
+
+    /// Load a collection without checking that the media files referenced in
+    /// its cards exist, to report on them.
+    pub fn new_without_media_validation(directory: Option<String>) -> Fallible<Self> {
+        Self::load(directory, StoreMode::ReadWrite, CacheMode::Enabled)
+    }
+
+    /// Load a collection for `drill`, with the given store and parse cache
+    /// modes. With `StoreMode::NoPersistence`, the scheduling state is read
+    /// from the store if possible, and changes to it are lost. If the store
+    /// can't be opened, the error suggests `drill --no-store`.
+    pub fn open(directory: Option<String>, store: StoreMode, cache: CacheMode) -> Fallible<Self> {
+        let coll = Self::read(directory, store, cache, Some(NO_STORE_HINT))?;
+        validate_media_files(&coll.cards, &coll.directory)?;
+        Ok(coll)
+    }
+
+    /// Load a collection with the given store and parse cache modes, without
+    /// checking the media files.
+    pub fn load(directory: Option<String>, store: StoreMode, cache: CacheMode) -> Fallible<Self> {
+        Self::read(directory, store, cache, None)
+    }
+
+    /// Load a collection. `store_hint` is added to the error if the store
+    /// can't be opened.
+    fn read(
+        directory: Option<String>,
+        store: StoreMode,
+        cache: CacheMode,
+        store_hint: Option<&str>,
+    ) -> Fallible<Self> {
         let directory: PathBuf = match directory {
             Some(dir) => PathBuf::from(dir),
             None => current_dir()?,
@@ -45,14 +109,14 @@ impl Collection {
         };
 
         let db_path: PathBuf = directory.join("hashcards.db");
-        let db_path: &str = db_path
-            .to_str()
-            .ok_or_else(|| ErrorReport::new("invalid path"))?;
-        let db: Database = Database::new(db_path)?;
+        let mut db: Database = open_store(&db_path, store).map_err(|e| match store_hint {
+            Some(hint) => ErrorReport::new(format!("{}\n{hint}", e.message())),
+            None => e,
+        })?;
 
         let macros: Vec<(String, String)> = {
             let mut macros = Vec::new();
-            let macros_path = directory.join("macros.tex");
+            let macros_path = directory.join(MACROS_FILE);
             if macros_path.exists() {
                 let content = read_to_string(macros_path)?;
                 for line in content.lines() {
@@ -71,24 +135,325 @@ impl Collection {
             macros
         };
 
+        let config: CollectionConfig = CollectionConfig::load(&directory)?;
+        let card_regex: Option<CardRegex> = config.card_regex()?;
+        let ignore: IgnoreRules = IgnoreRules::load(&directory, &config.ignored_extensions)?;
+
         let cards: Vec<Card> = {
             log::debug!("Loading deck...");
             let start = Instant::now();
-            let cards = parse_deck(&directory)?;
+            let cards = match cache {
+                CacheMode::Enabled => {
+                    let mut parse_cache = ParseCache::load(
+                        &directory,
+                        config.card_regex.as_deref(),
+                        config.deck_separator(),
+                    );
+                    let cards = parse_deck_cached(
+                        &directory,
+                        card_regex.as_ref(),
+                        &ignore,
+                        config.deck_separator(),
+                        &mut parse_cache,
+                    )?;
+                    parse_cache.save();
+                    cards
+                }
+                CacheMode::Disabled => parse_deck(
+                    &directory,
+                    card_regex.as_ref(),
+                    &ignore,
+                    config.deck_separator(),
+                )?,
+            };
             let end = Instant::now();
             let duration = end.duration_since(start).as_millis();
             log::debug!("Deck loaded in {duration}ms.");
             cards
         };
-
-        // Validate media files
-        validate_media_files(&cards, &directory)?;
+        migrate_legacy_hashes(&mut db, &cards)?;
 
         Ok(Self {
             directory,
             db,
             cards,
             macros,
+            config,
         })
     }
 }
+
+fn open_store(db_path: &Path, store: StoreMode) -> Fallible<Database> {
+    let path: &str = db_path
+        .to_str()
+        .ok_or_else(|| ErrorReport::new("invalid path"))?;
+    match store {
+        StoreMode::ReadWrite => Database::new(path),
+        StoreMode::NoPersistence => {
+            if !db_path.exists() {
+                return Database::new(":memory:");
+            }
+            match Database::new_in_memory_copy(path) {
+                Ok(db) => Ok(db),
+                Err(e) => {
+                    log::warn!("{} Every card is treated as new.", e.message());
+                    Database::new(":memory:")
+                }
+            }
+        }
+    }
+}
+
+/// Move the progress of cards that were reviewed before their text was
+/// normalized for hashing, and so are in the database under the hash of their
+/// raw text, to their current hash.
+fn migrate_legacy_hashes(db: &mut Database, cards: &[Card]) -> Fallible<()> {
+    let current: HashSet<CardHash> = cards.iter().map(Card::hash).collect();
+    let mut stored: HashSet<CardHash> = db.card_hashes()?;
+    let now: Timestamp = Timestamp::now();
+    for card in cards {
+        let legacy: CardHash = card.content().legacy_hash();
+        if legacy == card.hash()
+            || current.contains(&legacy)
+            || !stored.contains(&legacy)
+            || stored.contains(&card.hash())
+        {
+            continue;
+        }
+        log::debug!("Moving the progress of card {legacy} to {}.", card.hash());
+        db.insert_card(card.hash(), now)?;
+        db.transfer_history(legacy, card.hash())?;
+        stored.insert(card.hash());
+    }
+    Ok(())
+}
+
+/// The difference between the cards of two loads of a collection. Cards are
+/// identified by their hash, so an edited card is both removed and added.
+pub struct CollectionDiff {
+    /// Cards in the new load only.
+    pub added: Vec<Card>,
+    /// Cards in the old load only.
+    pub removed: Vec<Card>,
+    /// Cards in both loads, whose deck, location, tags, or extra notes
+    /// changed. These are the new versions.
+    pub changed: Vec<Card>,
+}
+
+impl CollectionDiff {
+    pub fn new(old: &[Card], new: &[Card]) -> Self {
+        let old_cards: HashMap<CardHash, &Card> = old.iter().map(|c| (c.hash(), c)).collect();
+        let new_cards: HashMap<CardHash, &Card> = new.iter().map(|c| (c.hash(), c)).collect();
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for card in new {
+            match old_cards.get(&card.hash()) {
+                None => added.push(card.clone()),
+                Some(old_card) => {
+                    if old_card.deck_name() != card.deck_name()
+                        || old_card.file_path() != card.file_path()
+                        || old_card.range() != card.range()
+                        || old_card.tags() != card.tags()
+                        || old_card.deck_tags() != card.deck_tags()
+                        || old_card.extra() != card.extra()
+                    {
+                        changed.push(card.clone());
+                    }
+                }
+            }
+        }
+        let removed = old
+            .iter()
+            .filter(|c| !new_cards.contains_key(&c.hash()))
+            .cloned()
+            .collect();
+        Self {
+            added,
+            removed,
+            changed,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::create_dir_all;
+    use std::fs::write;
+
+    use super::*;
+    use crate::config::CONFIG_FILE;
+    use crate::helper::create_tmp_directory;
+    use crate::ignore::IGNORE_FILE;
+    use crate::parse_cache::CACHE_FILE_NAME;
+    use crate::types::card::CardContent;
+    use crate::types::performance::Performance;
+    use crate::types::performance::ReviewedPerformance;
+
+    fn make_card(question: &str, range: (usize, usize)) -> Card {
+        Card::new(
+            "Deck".to_string(),
+            PathBuf::from("Deck.md"),
+            range,
+            CardContent::new_basic(question, "A"),
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn test_collection_diff() {
+        let old = vec![make_card("Q1", (0, 1)), make_card("Q2", (3, 4))];
+        let new = vec![
+            make_card("Q1", (0, 1)),
+            make_card("Q3", (3, 4)),
+            make_card("Q2", (6, 7)),
+        ];
+        let diff = CollectionDiff::new(&old, &new);
+        let questions = |cards: &[Card]| -> Vec<String> {
+            cards.iter().map(|c| c.content().to_source_text()).collect()
+        };
+        assert_eq!(questions(&diff.added), vec!["Q: Q3\nA: A"]);
+        assert!(diff.removed.is_empty());
+        assert_eq!(questions(&diff.changed), vec!["Q: Q2\nA: A"]);
+        assert_eq!(diff.changed[0].range(), (6, 7));
+
+        // Extra notes are not part of the hash, but they are a change.
+        let noted = vec![
+            make_card("Q1", (0, 1)).with_extra(Some("Note".to_string())),
+            make_card("Q2", (3, 4)),
+        ];
+        let diff = CollectionDiff::new(&old, &noted);
+        assert!(diff.added.is_empty());
+        assert_eq!(questions(&diff.changed), vec!["Q: Q1\nA: A"]);
+
+        let diff = CollectionDiff::new(&new, &old);
+        assert_eq!(questions(&diff.removed), vec!["Q: Q3\nA: A"]);
+        assert!(CollectionDiff::new(&old, &old).is_empty());
+    }
+
+    /// Ignored files are not parsed, so they can't cause parse errors.
+    #[test]
+    fn test_ignored_files() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        // Not a valid deck.
+        let readme = "Q: only a question\n";
+        write(dir.join("Deck.md"), "Q: Q1\nA: A1\n")?;
+        write(dir.join("README.md"), readme)?;
+        write(dir.join("Card.template.md"), readme)?;
+        create_dir_all(dir.join("templates"))?;
+        write(dir.join("templates/Basic.md"), readme)?;
+        write(dir.join("templates/Kept.md"), "Q: Q2\nA: A2\n")?;
+        create_dir_all(dir.join(".obsidian"))?;
+        write(dir.join(".obsidian/Notes.md"), readme)?;
+        write(dir.join(".Hidden.md"), "Q: Q3\nA: A3\n")?;
+        write(
+            dir.join(IGNORE_FILE),
+            "README.md\ntemplates/*\n!templates/Kept.md\n!.Hidden.md\n",
+        )?;
+        write(
+            dir.join(CONFIG_FILE),
+            "ignored_extensions = [\"template.md\"]\n",
+        )?;
+        let coll = Collection::new(Some(dir.display().to_string()))?;
+        let mut questions: Vec<String> = coll
+            .cards
+            .iter()
+            .map(|card| card.content().to_source_text())
+            .collect();
+        questions.sort();
+        assert_eq!(
+            questions,
+            vec!["Q: Q1\nA: A1", "Q: Q2\nA: A2", "Q: Q3\nA: A3"]
+        );
+        Ok(())
+    }
+
+    /// Only `drill`, which has `--no-store`, suggests it when the store can't
+    /// be opened.
+    #[test]
+    fn test_store_hint() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        write(dir.join("Deck.md"), "Q: Q1\nA: A1\n")?;
+        write(
+            dir.join("hashcards.db"),
+            "this is not a database, but it is long enough to look like one",
+        )?;
+        let directory = Some(dir.display().to_string());
+        let error = Collection::new(directory.clone())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("could not open the review store"));
+        assert!(!error.contains("--no-store"));
+        let error = Collection::open(directory.clone(), StoreMode::ReadWrite, CacheMode::Enabled)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("hashcards drill --no-store"));
+        // Without persistence, the corrupt store is replaced by an empty one.
+        let coll = Collection::open(directory, StoreMode::NoPersistence, CacheMode::Enabled)?;
+        assert!(coll.db.card_hashes()?.is_empty());
+        Ok(())
+    }
+
+    /// Loading a collection caches its cards, unless the cache is disabled.
+    #[test]
+    fn test_parse_cache() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        write(dir.join("Deck.md"), "Q: Q1\nA: A1\n")?;
+        let directory = Some(dir.display().to_string());
+        Collection::open(directory.clone(), StoreMode::ReadWrite, CacheMode::Disabled)?;
+        assert!(!dir.join(CACHE_FILE_NAME).exists());
+        let coll = Collection::new(directory.clone())?;
+        assert!(dir.join(CACHE_FILE_NAME).exists());
+        let again = Collection::open(directory, StoreMode::ReadWrite, CacheMode::Disabled)?;
+        assert_eq!(coll.cards.len(), 1);
+        assert_eq!(coll.cards[0].hash(), again.cards[0].hash());
+        assert_eq!(coll.cards[0].range(), again.cards[0].range());
+        Ok(())
+    }
+
+    /// A card reviewed under the hash of its raw text, before card text was
+    /// normalized for hashing, keeps its progress.
+    #[test]
+    fn test_migrate_legacy_hashes() -> Fallible<()> {
+        let dir = create_tmp_directory()?;
+        write(
+            dir.join("Deck.md"),
+            "Q: What is  the capital of France?\nA: Paris\n",
+        )?;
+        let content = CardContent::new_basic("What is  the capital of France?", "Paris");
+        let legacy = content.legacy_hash();
+        assert_ne!(legacy, content.hash());
+        let now = Timestamp::now();
+        let performance = Performance::Reviewed(ReviewedPerformance {
+            last_reviewed_at: now,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            review_count: 1,
+        });
+        {
+            let db = Database::new(&dir.join("hashcards.db").display().to_string())?;
+            db.insert_card(legacy, now)?;
+            db.update_card_performance(legacy, performance)?;
+        }
+
+        let dir = dir.to_string_lossy().to_string();
+        let coll = Collection::load(Some(dir.clone()), StoreMode::ReadWrite, CacheMode::Disabled)?;
+        assert_eq!(coll.cards[0].hash(), content.hash());
+        assert_eq!(coll.db.get_card_performance(content.hash())?, performance);
+        assert_eq!(coll.db.get_card_performance(legacy)?, Performance::New);
+        drop(coll);
+
+        // Loading again leaves the progress where it is.
+        let coll = Collection::load(Some(dir), StoreMode::ReadWrite, CacheMode::Disabled)?;
+        assert_eq!(coll.db.get_card_performance(content.hash())?, performance);
+        Ok(())
+    }
+}
@@ -0,0 +1,695 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::error::ErrorReport;
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::fsrs::Grade;
+use crate::fsrs::Recall;
+use crate::parser::ANSWER_GROUP;
+use crate::parser::DEFAULT_DECK_SEPARATOR;
+use crate::parser::QUESTION_GROUP;
+use crate::types::aliases::DeckName;
+use crate::types::aliases::Tag;
+use crate::types::card::Card;
+use crate::types::performance::DEFAULT_RETENTION;
+
+/// The name of the configuration file in the collection root.
+pub const CONFIG_FILE: &str = "hashcards.toml";
+
+/// Collection-wide settings, read from `hashcards.toml`.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct CollectionConfig {
+    /// Decks pinned to the top, in this order. Unlisted decks come after.
+    #[serde(default)]
+    pub deck_order: Vec<DeckName>,
+    /// A regular expression to extract basic cards from deck files, with
+    /// named groups `q` and `a` for the question and the answer.
+    pub card_regex: Option<String>,
+    /// Extensions of other files to extract cards from with `card_regex`,
+    /// besides deck files, e.g. `txt`.
+    #[serde(default)]
+    pub card_regex_extensions: Vec<String>,
+    /// Colors for decks and tags, shown in the drill interface.
+    #[serde(default)]
+    pub colors: ColorConfig,
+    /// The language and direction of the text.
+    #[serde(default)]
+    pub language: LanguageConfig,
+    /// Extensions of files that are not decks, e.g. `template.md`.
+    #[serde(default)]
+    pub ignored_extensions: Vec<String>,
+    /// The desired retention of the collection, and of some decks.
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    /// The separator between the directories and file of a nested deck's
+    /// name. Defaults to `::`.
+    pub deck_separator: Option<String>,
+}
+
+/// The `card_regex` setting, compiled, with the extensions of the files it
+/// is matched in besides deck files.
+#[derive(Debug, Clone)]
+pub struct CardRegex {
+    pub regex: Regex,
+    pub extensions: Vec<String>,
+}
+
+/// Colors assigned to decks, tags, and grades, in the `[colors.decks]`,
+/// `[colors.tags]`, and `[colors.grades]` tables. Values are CSS colors: a hex
+/// color like `#e07a5f`, or a named color like `teal`.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct ColorConfig {
+    #[serde(default)]
+    pub decks: BTreeMap<DeckName, String>,
+    /// Tags are written without the `#`.
+    #[serde(default)]
+    pub tags: BTreeMap<Tag, String>,
+    /// The colors flashed after grading.
+    #[serde(default)]
+    pub grades: GradeColors,
+}
+
+/// The color of each grade, flashed after a card is graded.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GradeColors {
+    pub forgot: String,
+    pub hard: String,
+    pub good: String,
+    pub easy: String,
+}
+
+impl Default for GradeColors {
+    fn default() -> Self {
+        Self {
+            forgot: "#d9534f".to_string(),
+            hard: "#f0ad4e".to_string(),
+            good: "#8bc34a".to_string(),
+            easy: "#2e9e4f".to_string(),
+        }
+    }
+}
+
+impl GradeColors {
+    pub fn color(&self, grade: Grade) -> &str {
+        match grade {
+            Grade::Forgot => &self.forgot,
+            Grade::Hard => &self.hard,
+            Grade::Good => &self.good,
+            Grade::Easy => &self.easy,
+        }
+    }
+}
+
+impl ColorConfig {
+    /// Check that every color is a hex or named color. Colors are put in
+    /// `style` attributes, so anything else is rejected rather than escaped.
+    fn validate(&self) -> Fallible<()> {
+        let decks = self
+            .decks
+            .iter()
+            .map(|(name, color)| ("deck", name.as_str(), color));
+        let tags = self
+            .tags
+            .iter()
+            .map(|(name, color)| ("tag", name.as_str(), color));
+        let grades = [
+            ("forgot", &self.grades.forgot),
+            ("hard", &self.grades.hard),
+            ("good", &self.grades.good),
+            ("easy", &self.grades.easy),
+        ];
+        let grades = grades
+            .into_iter()
+            .map(|(name, color)| ("grade", name, color));
+        for (kind, name, color) in decks.chain(tags).chain(grades) {
+            if !is_valid_color(color) {
+                return Err(ErrorReport::new(format!(
+                    "Invalid color '{color}' for {kind} '{name}' in {CONFIG_FILE}: expected a hex color like `#e07a5f`, or a color name like `teal`."
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The color of a card: the color of its first tag that has one, or else
+    /// the color of its deck.
+    pub fn card_color(&self, card: &Card) -> Option<&str> {
+        card.tags()
+            .iter()
+            .find_map(|tag| self.tag_color(tag))
+            .or_else(|| self.deck_color(card.deck_name()))
+    }
+
+    pub fn deck_color(&self, deck_name: &DeckName) -> Option<&str> {
+        self.decks.get(deck_name).map(String::as_str)
+    }
+
+    pub fn tag_color(&self, tag: &Tag) -> Option<&str> {
+        self.tags.get(tag).map(String::as_str)
+    }
+}
+
+/// Whether the string is a hex color (`#rgb`, `#rgba`, `#rrggbb`, or
+/// `#rrggbbaa`) or a color name, i.e. only ASCII letters.
+fn is_valid_color(color: &str) -> bool {
+    match color.strip_prefix('#') {
+        Some(hex) => {
+            matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic()),
+    }
+}
+
+/// The lowest and highest desired retention allowed.
+const MIN_RETENTION: Recall = 0.5;
+const MAX_RETENTION: Recall = 0.99;
+
+/// The desired retention, in the `[retention]` table: the probability of
+/// recalling a card when it comes due. Higher retention means shorter
+/// intervals, and more reviews. Decks in the `[retention.decks]` table
+/// override the collection's target.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub target: Option<Recall>,
+    pub decks: BTreeMap<DeckName, Recall>,
+}
+
+impl RetentionConfig {
+    fn validate(&self) -> Fallible<()> {
+        let target = self
+            .target
+            .iter()
+            .map(|r| ("the collection".to_string(), *r));
+        let decks = self
+            .decks
+            .iter()
+            .map(|(name, r)| (format!("deck '{name}'"), *r));
+        for (what, retention) in target.chain(decks) {
+            if !(MIN_RETENTION..=MAX_RETENTION).contains(&retention) {
+                return Err(ErrorReport::new(format!(
+                    "Invalid retention {retention} for {what} in {CONFIG_FILE}: expected a number between {MIN_RETENTION} and {MAX_RETENTION}."
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The desired retention of cards in the given deck.
+    pub fn desired_retention(&self, deck_name: &DeckName) -> Recall {
+        self.decks
+            .get(deck_name)
+            .copied()
+            .or(self.target)
+            .unwrap_or(DEFAULT_RETENTION)
+    }
+}
+
+/// The tags that set the direction of a card's text, whatever the
+/// configured direction.
+pub const RTL_TAG: &str = "rtl";
+pub const LTR_TAG: &str = "ltr";
+
+/// The language of the collection, in the `[language]` table: the `lang` and
+/// `dir` attributes of every page.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LanguageConfig {
+    /// A language tag, like `en` or `ar`.
+    pub lang: String,
+    pub dir: TextDirection,
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        Self {
+            lang: "en".to_string(),
+            dir: TextDirection::Ltr,
+        }
+    }
+}
+
+impl LanguageConfig {
+    /// Check that the language is a language tag: letters, digits, and
+    /// hyphens.
+    fn validate(&self) -> Fallible<()> {
+        let lang = &self.lang;
+        if lang.is_empty() || !lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(ErrorReport::new(format!(
+                "Invalid language '{lang}' in {CONFIG_FILE}: expected a language tag like `en` or `ar`."
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The direction of text, as in the HTML `dir` attribute.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextDirection {
+    #[default]
+    Ltr,
+    Rtl,
+    /// Guess the direction from the text.
+    Auto,
+}
+
+impl TextDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TextDirection::Ltr => "ltr",
+            TextDirection::Rtl => "rtl",
+            TextDirection::Auto => "auto",
+        }
+    }
+
+    /// The direction a card sets with a `#rtl` or `#ltr` tag, if any.
+    pub fn of_card(card: &Card) -> Option<Self> {
+        if card.has_tag(RTL_TAG) {
+            Some(TextDirection::Rtl)
+        } else if card.has_tag(LTR_TAG) {
+            Some(TextDirection::Ltr)
+        } else {
+            None
+        }
+    }
+}
+
+impl CollectionConfig {
+    /// Load the configuration file from the collection directory. If there is
+    /// no configuration file, the defaults are used.
+    pub fn load(directory: &Path) -> Fallible<Self> {
+        let path = directory.join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = read_to_string(path)?;
+        let config: Self = toml::from_str(&text)
+            .map_err(|e| ErrorReport::new(format!("Failed to parse {CONFIG_FILE}: {e}")))?;
+        config.colors.validate()?;
+        config.language.validate()?;
+        config.retention.validate()?;
+        if config.deck_separator.as_deref() == Some("") {
+            return fail(format!(
+                "The deck_separator in {CONFIG_FILE} can't be empty."
+            ));
+        }
+        Ok(config)
+    }
+
+    /// The separator between the components of nested deck names.
+    pub fn deck_separator(&self) -> &str {
+        self.deck_separator
+            .as_deref()
+            .unwrap_or(DEFAULT_DECK_SEPARATOR)
+    }
+
+    /// Compile the card regex, if any, checking that it has the question and
+    /// answer groups.
+    pub fn card_regex(&self) -> Fallible<Option<CardRegex>> {
+        let Some(pattern) = &self.card_regex else {
+            return Ok(None);
+        };
+        let regex = Regex::new(pattern)
+            .map_err(|e| ErrorReport::new(format!("Invalid card_regex in {CONFIG_FILE}: {e}")))?;
+        for group in [QUESTION_GROUP, ANSWER_GROUP] {
+            if !regex.capture_names().any(|name| name == Some(group)) {
+                return Err(ErrorReport::new(format!(
+                    "The card_regex in {CONFIG_FILE} must have a named group `{group}`, e.g. `(?P<{group}>...)`."
+                )));
+            }
+        }
+        Ok(Some(CardRegex {
+            regex,
+            extensions: self.card_regex_extensions.clone(),
+        }))
+    }
+
+    /// The position of the deck in the configured order. Unlisted decks all
+    /// share the last position.
+    fn deck_rank(&self, deck_name: &DeckName) -> usize {
+        self.deck_order
+            .iter()
+            .position(|name| name == deck_name)
+            .unwrap_or(self.deck_order.len())
+    }
+
+    /// Sort a list of decks: pinned decks first, in the configured order,
+    /// followed by unlisted decks in alphabetical order.
+    pub fn sort_decks<T>(&self, decks: &mut [T], name: impl Fn(&T) -> &DeckName) {
+        decks.sort_by(|a, b| {
+            let (a, b) = (name(a), name(b));
+            self.deck_rank(a)
+                .cmp(&self.deck_rank(b))
+                .then_with(|| a.cmp(b))
+        });
+    }
+
+    /// Move cards from pinned decks to the front, in the configured order.
+    /// The relative order of cards within each deck, and of cards from
+    /// unlisted decks, is preserved.
+    pub fn pin_cards(&self, mut cards: Vec<Card>) -> Vec<Card> {
+        cards.sort_by_key(|card| self.deck_rank(card.deck_name()));
+        cards
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+    use std::path::PathBuf;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::types::card::CardContent;
+
+    fn make_card(deck_name: &str, question: &str) -> Card {
+        Card::new(
+            deck_name.to_string(),
+            PathBuf::from("test.md"),
+            (0, 1),
+            CardContent::new_basic(question, "A"),
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn test_load_missing_config() -> Fallible<()> {
+        let dir = tempdir()?;
+        let config = CollectionConfig::load(dir.path())?;
+        assert!(config.deck_order.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config() -> Fallible<()> {
+        let dir = tempdir()?;
+        write(
+            dir.path().join(CONFIG_FILE),
+            "deck_order = [\"Physics\", \"Chemistry\"]\n",
+        )?;
+        let config = CollectionConfig::load(dir.path())?;
+        assert_eq!(config.deck_order, vec!["Physics", "Chemistry"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_deck_separator() -> Fallible<()> {
+        let dir = tempdir()?;
+        assert_eq!(CollectionConfig::load(dir.path())?.deck_separator(), "::");
+        write(dir.path().join(CONFIG_FILE), "deck_separator = \"/\"\n")?;
+        assert_eq!(CollectionConfig::load(dir.path())?.deck_separator(), "/");
+        write(dir.path().join(CONFIG_FILE), "deck_separator = \"\"\n")?;
+        assert!(CollectionConfig::load(dir.path()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_invalid_config() -> Fallible<()> {
+        let dir = tempdir()?;
+        write(dir.path().join(CONFIG_FILE), "deck_order = 1\n")?;
+        let result = CollectionConfig::load(dir.path());
+        assert!(result.is_err());
+        assert!(
+            result
+                .err()
+                .unwrap()
+                .to_string()
+                .contains("Failed to parse hashcards.toml")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_card_regex() -> Fallible<()> {
+        let config = CollectionConfig {
+            card_regex: Some("(?P<q>.+) :: (?P<a>.+)".to_string()),
+            ..Default::default()
+        };
+        assert!(config.card_regex()?.is_some());
+        assert!(CollectionConfig::default().card_regex()?.is_none());
+
+        // The answer group is missing.
+        let config = CollectionConfig {
+            card_regex: Some("(?P<q>.+) ::".to_string()),
+            ..Default::default()
+        };
+        let err = config.card_regex().err().unwrap().to_string();
+        assert!(err.contains("must have a named group `a`"));
+
+        // The pattern is invalid.
+        let config = CollectionConfig {
+            card_regex: Some("(?P<q>.+".to_string()),
+            ..Default::default()
+        };
+        let err = config.card_regex().err().unwrap().to_string();
+        assert!(err.contains("Invalid card_regex in hashcards.toml"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_colors() -> Fallible<()> {
+        let dir = tempdir()?;
+        write(
+            dir.path().join(CONFIG_FILE),
+            "[colors.decks]\nPhysics = \"#e07a5f\"\n\n[colors.tags]\nhard = \"crimson\"\n",
+        )?;
+        let config = CollectionConfig::load(dir.path())?;
+        assert_eq!(
+            config.colors.deck_color(&"Physics".to_string()),
+            Some("#e07a5f")
+        );
+        assert_eq!(
+            config.colors.tag_color(&"hard".to_string()),
+            Some("crimson")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_grade_colors() -> Fallible<()> {
+        let dir = tempdir()?;
+        write(
+            dir.path().join(CONFIG_FILE),
+            "[colors.grades]\nforgot = \"purple\"\n",
+        )?;
+        let config = CollectionConfig::load(dir.path())?;
+        assert_eq!(config.colors.grades.color(Grade::Forgot), "purple");
+        // Grades that are not configured keep their default color.
+        assert_eq!(
+            config.colors.grades.color(Grade::Easy),
+            GradeColors::default().easy
+        );
+
+        write(
+            dir.path().join(CONFIG_FILE),
+            "[colors.grades]\ngood = \"url(x)\"\n",
+        )?;
+        let err = CollectionConfig::load(dir.path())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("Invalid color 'url(x)' for grade 'good'"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_invalid_color() -> Fallible<()> {
+        let dir = tempdir()?;
+        write(
+            dir.path().join(CONFIG_FILE),
+            "[colors.tags]\nhard = \"red; background: url(x)\"\n",
+        )?;
+        let err = CollectionConfig::load(dir.path())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("Invalid color 'red; background: url(x)' for tag 'hard'"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_retention() -> Fallible<()> {
+        let dir = tempdir()?;
+        write(
+            dir.path().join(CONFIG_FILE),
+            "[retention]\ntarget = 0.85\n\n[retention.decks]\nMedicine = 0.95\n",
+        )?;
+        let config = CollectionConfig::load(dir.path())?;
+        let retention = &config.retention;
+        assert_eq!(retention.desired_retention(&"Medicine".to_string()), 0.95);
+        assert_eq!(retention.desired_retention(&"Physics".to_string()), 0.85);
+        assert_eq!(
+            RetentionConfig::default().desired_retention(&"Physics".to_string()),
+            DEFAULT_RETENTION
+        );
+
+        write(
+            dir.path().join(CONFIG_FILE),
+            "[retention.decks]\nMedicine = 1.0\n",
+        )?;
+        let err = CollectionConfig::load(dir.path())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("Invalid retention 1 for deck 'Medicine'"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_language() -> Fallible<()> {
+        let dir = tempdir()?;
+        let config = CollectionConfig::load(dir.path())?;
+        assert_eq!(config.language.lang, "en");
+        assert_eq!(config.language.dir, TextDirection::Ltr);
+
+        write(
+            dir.path().join(CONFIG_FILE),
+            "[language]\nlang = \"he\"\ndir = \"rtl\"\n",
+        )?;
+        let config = CollectionConfig::load(dir.path())?;
+        assert_eq!(config.language.lang, "he");
+        assert_eq!(config.language.dir, TextDirection::Rtl);
+
+        write(
+            dir.path().join(CONFIG_FILE),
+            "[language]\nlang = \"he\\\" onload=\\\"x\"\n",
+        )?;
+        let err = CollectionConfig::load(dir.path())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("Invalid language"));
+
+        write(dir.path().join(CONFIG_FILE), "[language]\ndir = \"up\"\n")?;
+        assert!(CollectionConfig::load(dir.path()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_card_direction() {
+        let card = |tags: &[&str]| {
+            Card::new(
+                "Hebrew".to_string(),
+                PathBuf::from("test.md"),
+                (0, 1),
+                CardContent::new_basic("Q", "A"),
+                tags.iter().map(|tag| tag.to_string()).collect(),
+            )
+        };
+        assert_eq!(TextDirection::of_card(&card(&[])), None);
+        assert_eq!(
+            TextDirection::of_card(&card(&["hard", "rtl"])),
+            Some(TextDirection::Rtl)
+        );
+        assert_eq!(
+            TextDirection::of_card(&card(&["ltr"])),
+            Some(TextDirection::Ltr)
+        );
+    }
+
+    #[test]
+    fn test_is_valid_color() {
+        for color in [
+            "#fff",
+            "#ffff",
+            "#e07a5f",
+            "#E07A5F80",
+            "teal",
+            "RebeccaPurple",
+        ] {
+            assert!(is_valid_color(color), "{color}");
+        }
+        for color in [
+            "",
+            "#",
+            "#ff",
+            "#12345",
+            "#ggg",
+            "e07a5f1",
+            "red;",
+            "light blue",
+            "url(x)",
+        ] {
+            assert!(!is_valid_color(color), "{color}");
+        }
+    }
+
+    #[test]
+    fn test_card_color() {
+        let colors = ColorConfig {
+            decks: BTreeMap::from([("Physics".to_string(), "teal".to_string())]),
+            tags: BTreeMap::from([("hard".to_string(), "crimson".to_string())]),
+            grades: GradeColors::default(),
+        };
+        let card = make_card("Physics", "P1");
+        assert_eq!(colors.card_color(&card), Some("teal"));
+        let card = Card::new(
+            "Physics".to_string(),
+            PathBuf::from("test.md"),
+            (0, 1),
+            CardContent::new_basic("P2", "A"),
+            vec!["easy".to_string(), "hard".to_string()],
+        );
+        assert_eq!(colors.card_color(&card), Some("crimson"));
+        assert_eq!(colors.card_color(&make_card("Art", "A1")), None);
+    }
+
+    #[test]
+    fn test_sort_decks() {
+        let config = CollectionConfig {
+            deck_order: vec!["Physics".to_string(), "Chemistry".to_string()],
+            ..Default::default()
+        };
+        let mut decks = vec!["Biology", "Chemistry", "Art", "Physics"]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<DeckName>>();
+        config.sort_decks(&mut decks, |name| name);
+        assert_eq!(decks, vec!["Physics", "Chemistry", "Art", "Biology"]);
+    }
+
+    #[test]
+    fn test_pin_cards() {
+        let config = CollectionConfig {
+            deck_order: vec!["Physics".to_string()],
+            ..Default::default()
+        };
+        let cards = vec![
+            make_card("Biology", "B1"),
+            make_card("Physics", "P1"),
+            make_card("Art", "A1"),
+            make_card("Physics", "P2"),
+        ];
+        let cards = config.pin_cards(cards);
+        let cards: Vec<String> = cards
+            .iter()
+            .map(|card| card.content().to_source_text())
+            .collect();
+        assert_eq!(
+            cards,
+            vec!["Q: P1\nA: A", "Q: P2\nA: A", "Q: B1\nA: A", "Q: A1\nA: A"]
+        );
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading and writing CSV, as RFC 4180 describes it: fields are separated
+//! by commas and rows by line breaks, and a field in double quotes can
+//! contain commas, line breaks, and quotes, which are doubled.
+
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::types::card::Card;
+use crate::types::card::CardContent;
+use crate::types::card::CardType;
+
+/// The columns of a CSV file of cards, with a card per row.
+pub const CARD_COLUMNS: [&str; 5] = ["deck", "type", "front", "back", "tags"];
+
+/// The name of a card type in the `type` column.
+pub fn card_type_name(card_type: CardType) -> &'static str {
+    match card_type {
+        CardType::Basic => "basic",
+        CardType::Cloze => "cloze",
+        CardType::TypeIn => "type-in",
+    }
+}
+
+/// The name of a reversible card's type in the `type` column.
+pub const REVERSIBLE_TYPE_NAME: &str = "reversible";
+
+/// The `type` column of a card. Reversible cards are basic cards with a name
+/// of their own, so that they are imported as reversible.
+pub fn card_type_column(card: &Card) -> &'static str {
+    match card.content() {
+        CardContent::Reversible { .. } => REVERSIBLE_TYPE_NAME,
+        _ => card_type_name(card.card_type()),
+    }
+}
+
+/// Parse CSV text into rows of fields. Line breaks may be LF or CRLF, and
+/// are read as LF, in quoted fields too. A byte order mark, which
+/// spreadsheets often write, is skipped.
+pub fn parse_csv(text: &str) -> Fallible<Vec<Vec<String>>> {
+    let text: &str = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut field: String = String::new();
+    // Whether the current field is quoted, and its closing quote not yet
+    // read.
+    let mut quoted: bool = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            '\r' if chars.peek() == Some(&'\n') => {}
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return fail(format!(
+            "Invalid CSV: a quoted field in row {} is not closed.",
+            rows.len() + 1
+        ));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Write a row of CSV, without the line break. Fields with commas, quotes,
+/// or line breaks are quoted.
+pub fn csv_row(fields: &[&str]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() -> Fallible<()> {
+        let text = "\u{feff}a,b,c\r\n\"one, two\",\"say \"\"hi\"\"\",\"line\r\nbreak\"\n,,\n";
+        assert_eq!(
+            parse_csv(text)?,
+            vec![
+                vec!["a", "b", "c"],
+                vec!["one, two", "say \"hi\"", "line\nbreak"],
+                vec!["", "", ""],
+            ]
+        );
+        // The last line break is optional.
+        assert_eq!(parse_csv("a,b")?, vec![vec!["a", "b"]]);
+        assert_eq!(parse_csv("")?, Vec::<Vec<String>>::new());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unclosed_quote() {
+        let err = parse_csv("a,b\n\"c,d\n").err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "error: Invalid CSV: a quoted field in row 2 is not closed."
+        );
+    }
+
+    #[test]
+    fn test_round_trip() -> Fallible<()> {
+        let fields = ["plain", "with, comma", "with \"quotes\"", "two\nlines", ""];
+        let text = csv_row(&fields);
+        assert_eq!(
+            text,
+            "plain,\"with, comma\",\"with \"\"quotes\"\"\",\"two\nlines\","
+        );
+        assert_eq!(parse_csv(&text)?, vec![fields.to_vec()]);
+        Ok(())
+    }
+}
@@ -12,22 +12,33 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::collections::HashSet;
 
 use rusqlite::Connection;
+use rusqlite::Error;
+use rusqlite::ErrorCode;
+use rusqlite::OpenFlags;
+use rusqlite::Row;
 use rusqlite::Transaction;
+use rusqlite::TransactionBehavior;
+use rusqlite::backup::Backup;
+use rusqlite::backup::StepResult;
 use rusqlite::config::DbConfig;
 use rusqlite::params;
 
+use crate::error::ErrorReport;
 use crate::error::Fallible;
 use crate::error::fail;
 use crate::fsrs::Difficulty;
 use crate::fsrs::Grade;
 use crate::fsrs::Stability;
 use crate::types::card_hash::CardHash;
+use crate::types::confidence::Confidence;
 use crate::types::date::Date;
 use crate::types::performance::Performance;
 use crate::types::performance::ReviewedPerformance;
+use crate::types::retention::Retention;
 use crate::types::timestamp::Timestamp;
 
 pub struct Database {
@@ -43,6 +54,15 @@ pub struct ReviewRecord {
     pub interval_raw: f64,
     pub interval_days: i64,
     pub due_date: Date,
+    /// When the answer was revealed. Reviews recorded before reveal times
+    /// were tracked have none.
+    pub revealed_at: Option<Timestamp>,
+    /// The self-rated confidence in the answer, from 1 to 5, if tracked.
+    pub confidence: Option<Confidence>,
+    /// How long the card took, in milliseconds: from when it was shown, or
+    /// its answer revealed, to when it was graded. Reviews recorded before
+    /// cards were timed have none.
+    pub duration_ms: Option<i64>,
 }
 
 pub struct SessionRow {
@@ -58,14 +78,46 @@ pub struct ReviewRow {
 
 impl Database {
     pub fn new(database_path: &str) -> Fallible<Self> {
-        let mut conn = Connection::open(database_path)?;
+        let conn = Connection::open(database_path)?;
+        Self::init(conn, database_path)
+    }
+
+    /// Open an in-memory copy of a database file. The file is only read:
+    /// changes are made to the copy, and lost when it is dropped.
+    ///
+    /// If the file is locked for writing, returns an error.
+    pub fn new_in_memory_copy(database_path: &str) -> Fallible<Self> {
+        let explain = |e| explain_open_error(database_path, e);
+        let source = Connection::open_with_flags(database_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut conn = Connection::open_in_memory()?;
+        {
+            let backup = Backup::new(&source, &mut conn).map_err(explain)?;
+            // Copy every page in one step.
+            match backup.step(-1).map_err(explain)? {
+                StepResult::Done => {}
+                _ => return Err(store_error(database_path, LOCKED)),
+            }
+        }
+        Self::init(conn, database_path)
+    }
+
+    /// Set up a connection to the database at the given path, creating the
+    /// schema or bringing it up to date.
+    fn init(mut conn: Connection, database_path: &str) -> Fallible<Self> {
+        let explain = |e| explain_open_error(database_path, e);
         conn.set_db_config(DbConfig::SQLITE_DBCONFIG_ENABLE_FKEY, true)?;
         {
-            let tx = conn.transaction()?;
-            if !probe_schema_exists(&tx)? {
+            // Take the write lock up front, so a database that is locked by
+            // another program is reported before any work is done.
+            let tx = conn
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(explain)?;
+            if !probe_schema_exists(&tx).map_err(explain)? {
                 tx.execute_batch(include_str!("schema.sql"))?;
-                tx.commit()?;
+            } else {
+                migrate(&tx)?;
             }
+            tx.commit()?;
         }
         Ok(Self { conn })
     }
@@ -97,10 +149,10 @@ impl Database {
         Ok(card_hashes)
     }
 
-    /// Find the hashes of the cards due today.
+    /// Find the hashes of the cards due today. Suspended cards are never due.
     pub fn due_today(&self, today: Date) -> Fallible<HashSet<CardHash>> {
         let mut due = HashSet::new();
-        let sql = "select card_hash, due_date from cards;";
+        let sql = "select card_hash, due_date from cards where suspended = 0;";
         let mut stmt = self.conn.prepare(sql)?;
         let mut rows = stmt.query(params![])?;
         while let Some(row) = rows.next()? {
@@ -121,6 +173,21 @@ impl Database {
         Ok(due)
     }
 
+    /// Find the due date of every card that isn't suspended. New cards have
+    /// no due date.
+    pub fn due_dates(&self) -> Fallible<HashMap<CardHash, Option<Date>>> {
+        let mut due_dates = HashMap::new();
+        let sql = "select card_hash, due_date from cards where suspended = 0;";
+        let mut stmt = self.conn.prepare(sql)?;
+        let mut rows = stmt.query(params![])?;
+        while let Some(row) = rows.next()? {
+            let hash: CardHash = row.get(0)?;
+            let due_date: Option<Date> = row.get(1)?;
+            due_dates.insert(hash, due_date);
+        }
+        Ok(due_dates)
+    }
+
     /// Get a card's performance information.
     pub fn get_card_performance_opt(&self, card_hash: CardHash) -> Fallible<Option<Performance>> {
         let sql = "select last_reviewed_at, stability, difficulty, interval_raw, interval_days, due_date, review_count from cards where card_hash = ?;";
@@ -187,56 +254,48 @@ impl Database {
         card_hash: CardHash,
         performance: Performance,
     ) -> Fallible<()> {
-        if !self.card_exists(card_hash)? {
-            return fail("Card not found");
-        }
-        let (
-            last_reviewed_at,
-            stability,
-            difficulty,
-            interval_raw,
-            interval_days,
-            due_date,
-            review_count,
-        ) = match performance {
-            Performance::New => (None, None, None, None, None, None, 0),
-            Performance::Reviewed(rp) => (
-                Some(rp.last_reviewed_at),
-                Some(rp.stability),
-                Some(rp.difficulty),
-                Some(rp.interval_raw),
-                Some(rp.interval_days as i32),
-                Some(rp.due_date),
-                rp.review_count as i32,
-            ),
-        };
-        let sql = "update cards set last_reviewed_at = ?, stability = ?, difficulty = ?, interval_raw = ?, interval_days = ?, due_date = ?, review_count = ? where card_hash = ?;";
-        let params = params![
-            last_reviewed_at,
-            stability,
-            difficulty,
-            interval_raw,
-            interval_days,
-            due_date,
-            review_count,
-            card_hash
-        ];
-        self.conn.execute(sql, params)?;
-        Ok(())
+        update_performance(&self.conn, card_hash, performance)
     }
 
     /// Save a session.
+    #[cfg(test)]
     pub fn save_session(
         &mut self,
         started_at: Timestamp,
         ended_at: Timestamp,
         reviews: Vec<ReviewRecord>,
     ) -> Fallible<()> {
+        self.save_reviews(None, started_at, ended_at, reviews, &[])?;
+        Ok(())
+    }
+
+    /// Save reviews, and the performance of cards, in one transaction. The
+    /// reviews are added to the given session, or to a new one if there is
+    /// none, and the end of the session is set to `ended_at`. Cards not in the
+    /// database yet, like those added to the collection during the session,
+    /// are added. Returns the ID of the session.
+    pub fn save_reviews(
+        &mut self,
+        session_id: Option<i64>,
+        started_at: Timestamp,
+        ended_at: Timestamp,
+        reviews: Vec<ReviewRecord>,
+        performance: &[(CardHash, Performance)],
+    ) -> Fallible<i64> {
         let tx = self.conn.transaction()?;
-        let sql = "insert into sessions (started_at, ended_at) values (?, ?) returning session_id;";
-        let session_id: i64 = tx.query_row(sql, params![started_at, ended_at], |row| row.get(0))?;
+        let session_id: i64 = match session_id {
+            Some(session_id) => {
+                let sql = "update sessions set ended_at = ? where session_id = ?;";
+                tx.execute(sql, params![ended_at, session_id])?;
+                session_id
+            }
+            None => {
+                let sql = "insert into sessions (started_at, ended_at) values (?, ?) returning session_id;";
+                tx.query_row(sql, params![started_at, ended_at], |row| row.get(0))?
+            }
+        };
         for review in reviews {
-            let sql = "insert into reviews (session_id, card_hash, reviewed_at, grade, stability, difficulty, interval_raw, interval_days, due_date) values (?, ?, ?, ?, ?, ?, ?, ?, ?);";
+            let sql = "insert into reviews (session_id, card_hash, reviewed_at, grade, stability, difficulty, interval_raw, interval_days, due_date, revealed_at, confidence, duration_ms) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);";
             tx.execute(
                 sql,
                 params![
@@ -248,21 +307,34 @@ impl Database {
                     review.difficulty,
                     review.interval_raw,
                     review.interval_days as i32,
-                    review.due_date
+                    review.due_date,
+                    review.revealed_at,
+                    review.confidence,
+                    review.duration_ms
                 ],
             )?;
         }
+        for (card_hash, performance) in performance {
+            let sql =
+                "insert or ignore into cards (card_hash, added_at, review_count) values (?, ?, 0);";
+            tx.execute(sql, params![card_hash, ended_at])?;
+            update_performance(&tx, *card_hash, *performance)?;
+        }
         tx.commit()?;
-        Ok(())
+        Ok(session_id)
     }
 
-    /// Delete a card and its reviews.
+    /// Delete a card and its reviews. If `archive` is true, the reviews are
+    /// moved to the `archived_reviews` table instead of being deleted.
     ///
     /// If no card with the given hash exists, returns an error.
-    pub fn delete_card(&self, card_hash: CardHash) -> Fallible<()> {
+    pub fn delete_card(&self, card_hash: CardHash, archive: bool, now: Timestamp) -> Fallible<()> {
         if !self.card_exists(card_hash)? {
             return fail("Card not found");
         }
+        if archive {
+            archive_reviews(&self.conn, card_hash, now)?;
+        }
         let sql = "delete from reviews where card_hash = ?;";
         self.conn.execute(sql, params![card_hash])?;
         let sql = "delete from cards where card_hash = ?;";
@@ -270,6 +342,50 @@ impl Database {
         Ok(())
     }
 
+    /// Reset the given cards, so they are new again: clear their scheduling
+    /// state and remove their reviews. Suspended cards stay suspended. If `archive` is true,
+    /// the reviews are moved to the `archived_reviews` table instead of being
+    /// deleted. Cards not in the database are skipped.
+    pub fn reset_cards(
+        &mut self,
+        card_hashes: &[CardHash],
+        archive: bool,
+        now: Timestamp,
+    ) -> Fallible<()> {
+        let tx = self.conn.transaction()?;
+        for card_hash in card_hashes {
+            if archive {
+                archive_reviews(&tx, *card_hash, now)?;
+            }
+            let sql = "delete from reviews where card_hash = ?;";
+            tx.execute(sql, params![card_hash])?;
+            let sql = "update cards set last_reviewed_at = null, stability = null, difficulty = null, interval_raw = null, interval_days = null, due_date = null, review_count = 0 where card_hash = ?;";
+            tx.execute(sql, params![card_hash])?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Move a card's reviews, scheduling state, and suspension to another
+    /// card, e.g. when a card is edited but keeps its learning progress. The
+    /// old card is left with no reviews, as if it were new, and unsuspended.
+    ///
+    /// If either card doesn't exist, returns an error.
+    pub fn transfer_history(&mut self, from: CardHash, to: CardHash) -> Fallible<()> {
+        if !self.card_exists(from)? || !self.card_exists(to)? {
+            return fail("Card not found");
+        }
+        let tx = self.conn.transaction()?;
+        let sql = "update cards set (added_at, last_reviewed_at, stability, difficulty, interval_raw, interval_days, due_date, review_count, suspended) = (select added_at, last_reviewed_at, stability, difficulty, interval_raw, interval_days, due_date, review_count, suspended from cards where card_hash = ?) where card_hash = ?;";
+        tx.execute(sql, params![from, to])?;
+        let sql = "update reviews set card_hash = ? where card_hash = ?;";
+        tx.execute(sql, params![to, from])?;
+        let sql = "update cards set last_reviewed_at = null, stability = null, difficulty = null, interval_raw = null, interval_days = null, due_date = null, review_count = 0, suspended = 0 where card_hash = ?;";
+        tx.execute(sql, params![from])?;
+        tx.commit()?;
+        Ok(())
+    }
+
     /// Does a card with the given hash exist?
     fn card_exists(&self, card_hash: CardHash) -> Fallible<bool> {
         let sql = "select count(*) from cards where card_hash = ?;";
@@ -284,6 +400,70 @@ impl Database {
         Ok(count as usize)
     }
 
+    /// The grades of a card's reviews, oldest first.
+    pub fn get_grades(&self, card_hash: CardHash) -> Fallible<Vec<Grade>> {
+        let sql = "select grade from reviews where card_hash = ? order by reviewed_at, review_id;";
+        let mut stmt = self.conn.prepare(sql)?;
+        let grades: Vec<Grade> = stmt
+            .query_map(params![card_hash], |row| row.get(0))?
+            .collect::<Result<Vec<Grade>, _>>()?;
+        Ok(grades)
+    }
+
+    /// Suspend a card, so it is no longer due.
+    pub fn suspend_card(&self, card_hash: CardHash) -> Fallible<()> {
+        let sql = "update cards set suspended = 1 where card_hash = ?;";
+        self.conn.execute(sql, params![card_hash])?;
+        Ok(())
+    }
+
+    /// Unsuspend a card, so it is scheduled again.
+    pub fn unsuspend_card(&self, card_hash: CardHash) -> Fallible<()> {
+        let sql = "update cards set suspended = 0 where card_hash = ?;";
+        self.conn.execute(sql, params![card_hash])?;
+        Ok(())
+    }
+
+    /// The hashes of the suspended cards, sorted.
+    pub fn suspended_cards(&self) -> Fallible<Vec<CardHash>> {
+        let sql = "select card_hash from cards where suspended = 1;";
+        let mut stmt = self.conn.prepare(sql)?;
+        let mut hashes: Vec<CardHash> = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<CardHash>, _>>()?;
+        hashes.sort();
+        Ok(hashes)
+    }
+
+    /// Measure a card's retention from its review history. Returns `None` if
+    /// the card has never been reviewed.
+    pub fn get_retention(&self, card_hash: CardHash) -> Fallible<Option<Retention>> {
+        let sql = "select count(*), coalesce(sum(grade <> 'forgot'), 0) from reviews where card_hash = ?;";
+        let (reviews, successes): (i64, i64) =
+            self.conn.query_row(sql, params![card_hash], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+        Ok(Retention::new(reviews as usize, successes as usize))
+    }
+
+    /// Get the answer times of every card, i.e. the time from revealing the
+    /// answer to grading the card, in milliseconds. Reviews without a reveal
+    /// time are skipped.
+    pub fn get_answer_times(&self) -> Fallible<HashMap<CardHash, Vec<i64>>> {
+        let sql = "select card_hash, revealed_at, reviewed_at from reviews where revealed_at is not null;";
+        let mut stmt = self.conn.prepare(sql)?;
+        let mut rows = stmt.query(params![])?;
+        let mut times: HashMap<CardHash, Vec<i64>> = HashMap::new();
+        while let Some(row) = rows.next()? {
+            let card_hash: CardHash = row.get(0)?;
+            let revealed_at: Timestamp = row.get(1)?;
+            let reviewed_at: Timestamp = row.get(2)?;
+            let time = (reviewed_at.into_inner() - revealed_at.into_inner()).num_milliseconds();
+            times.entry(card_hash).or_default().push(time);
+        }
+        Ok(times)
+    }
+
     /// Get the list of all sessions in the database.
     pub fn get_all_sessions(&self) -> Fallible<Vec<SessionRow>> {
         let sql = "select session_id, started_at, ended_at from sessions order by started_at;";
@@ -304,39 +484,236 @@ impl Database {
 
     /// Get the list of all reviews for a given session.
     pub fn get_reviews_for_session(&self, session_id: i64) -> Fallible<Vec<ReviewRow>> {
-        let sql = "select review_id, card_hash, reviewed_at, grade, stability, difficulty, interval_raw, interval_days, due_date from reviews where session_id = ? order by reviewed_at;";
+        let sql = "select review_id, card_hash, reviewed_at, grade, stability, difficulty, interval_raw, interval_days, due_date, revealed_at, confidence, duration_ms from reviews where session_id = ? order by reviewed_at;";
         let mut stmt = self.conn.prepare(sql)?;
-        let review_iter = stmt.query_map(params![session_id], |row| {
-            Ok(ReviewRow {
-                review_id: row.get(0)?,
-                data: ReviewRecord {
-                    card_hash: row.get(1)?,
-                    reviewed_at: row.get(2)?,
-                    grade: row.get(3)?,
-                    stability: row.get(4)?,
-                    difficulty: row.get(5)?,
-                    interval_raw: row.get(6)?,
-                    interval_days: row.get(7)?,
-                    due_date: row.get(8)?,
-                },
-            })
-        })?;
+        let review_iter = stmt.query_map(params![session_id], review_row)?;
         let mut reviews = Vec::new();
         for review in review_iter {
             reviews.push(review?);
         }
         Ok(reviews)
     }
+
+    /// Call `f` with every review, in the order they were made, or only the
+    /// reviews made between two dates, inclusive. Reviews are read one at a
+    /// time, so the history doesn't have to fit in memory.
+    pub fn for_each_review(
+        &self,
+        from: Option<Date>,
+        to: Option<Date>,
+        mut f: impl FnMut(ReviewRow) -> Fallible<()>,
+    ) -> Fallible<()> {
+        // Timestamps start with the date, as `YYYY-MM-DD`.
+        let sql = "select review_id, card_hash, reviewed_at, grade, stability, difficulty, interval_raw, interval_days, due_date, revealed_at, confidence, duration_ms from reviews where (?1 is null or substr(reviewed_at, 1, 10) >= ?1) and (?2 is null or substr(reviewed_at, 1, 10) <= ?2) order by reviewed_at, review_id;";
+        let mut stmt = self.conn.prepare(sql)?;
+        let mut rows = stmt.query(params![from, to])?;
+        while let Some(row) = rows.next()? {
+            f(review_row(row)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read a review from a row with the columns of the `reviews` table, but
+/// `session_id`.
+fn review_row(row: &Row<'_>) -> rusqlite::Result<ReviewRow> {
+    Ok(ReviewRow {
+        review_id: row.get(0)?,
+        data: ReviewRecord {
+            card_hash: row.get(1)?,
+            reviewed_at: row.get(2)?,
+            grade: row.get(3)?,
+            stability: row.get(4)?,
+            difficulty: row.get(5)?,
+            interval_raw: row.get(6)?,
+            interval_days: row.get(7)?,
+            due_date: row.get(8)?,
+            revealed_at: row.get(9)?,
+            confidence: row.get(10)?,
+            duration_ms: row.get(11)?,
+        },
+    })
+}
+
+/// Explain the errors that are likely when opening a database that is synced
+/// across devices: another program holds a lock on it, or it is corrupt.
+/// Other errors are reported as they are.
+fn explain_open_error(database_path: &str, error: Error) -> ErrorReport {
+    match error.sqlite_error_code() {
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => {
+            store_error(database_path, LOCKED)
+        }
+        Some(ErrorCode::NotADatabase | ErrorCode::DatabaseCorrupt) => {
+            store_error(database_path, CORRUPT)
+        }
+        _ => error.into(),
+    }
+}
+
+const LOCKED: &str = "it is locked by another program, e.g. a sync client";
+
+const CORRUPT: &str = "the file is corrupt, or is not a database";
+
+fn store_error(database_path: &str, reason: &str) -> ErrorReport {
+    ErrorReport::new(format!(
+        "could not open the review store {database_path}: {reason}."
+    ))
+}
+
+fn probe_schema_exists(tx: &Transaction) -> rusqlite::Result<bool> {
+    probe_table_exists(tx, "cards")
+}
+
+/// Update a card's performance information, failing if no card with the
+/// given hash exists.
+fn update_performance(
+    conn: &Connection,
+    card_hash: CardHash,
+    performance: Performance,
+) -> Fallible<()> {
+    let sql = "select count(*) from cards where card_hash = ?;";
+    let count: i64 = conn.query_row(sql, [card_hash], |row| row.get(0))?;
+    if count == 0 {
+        return fail("Card not found");
+    }
+    let (
+        last_reviewed_at,
+        stability,
+        difficulty,
+        interval_raw,
+        interval_days,
+        due_date,
+        review_count,
+    ) = match performance {
+        Performance::New => (None, None, None, None, None, None, 0),
+        Performance::Reviewed(rp) => (
+            Some(rp.last_reviewed_at),
+            Some(rp.stability),
+            Some(rp.difficulty),
+            Some(rp.interval_raw),
+            Some(rp.interval_days as i32),
+            Some(rp.due_date),
+            rp.review_count as i32,
+        ),
+    };
+    let sql = "update cards set last_reviewed_at = ?, stability = ?, difficulty = ?, interval_raw = ?, interval_days = ?, due_date = ?, review_count = ? where card_hash = ?;";
+    let params = params![
+        last_reviewed_at,
+        stability,
+        difficulty,
+        interval_raw,
+        interval_days,
+        due_date,
+        review_count,
+        card_hash
+    ];
+    conn.execute(sql, params)?;
+    Ok(())
+}
+
+/// The columns of the `archived_reviews` table, other than its own ID.
+const ARCHIVED_REVIEW_COLUMNS: &str = "review_id, session_id, card_hash, reviewed_at, grade, stability, difficulty, interval_raw, interval_days, due_date, revealed_at, confidence, archived_at, duration_ms";
+
+/// Copy a card's reviews to the `archived_reviews` table. The ID of each
+/// review is kept as `review_id`, though the archived review has its own.
+fn archive_reviews(conn: &Connection, card_hash: CardHash, now: Timestamp) -> Fallible<()> {
+    let sql = format!(
+        "insert into archived_reviews ({ARCHIVED_REVIEW_COLUMNS}) select review_id, session_id, card_hash, reviewed_at, grade, stability, difficulty, interval_raw, interval_days, due_date, revealed_at, confidence, ?, duration_ms from reviews where card_hash = ?;"
+    );
+    conn.execute(&sql, params![now, card_hash])?;
+    Ok(())
 }
 
-fn probe_schema_exists(tx: &Transaction) -> Fallible<bool> {
+fn probe_table_exists(tx: &Transaction, table: &str) -> rusqlite::Result<bool> {
     let sql = "select count(*) from sqlite_master where type='table' AND name=?;";
-    let count: i64 = tx.query_row(sql, ["cards"], |row| row.get(0))?;
+    let count: i64 = tx.query_row(sql, [table], |row| row.get(0))?;
+    Ok(count > 0)
+}
+
+/// Bring a database created by an older version up to date with the schema.
+fn migrate(tx: &Transaction) -> Fallible<()> {
+    if !probe_column_exists(tx, "reviews", "revealed_at")? {
+        tx.execute("alter table reviews add column revealed_at text;", [])?;
+    }
+    if !probe_column_exists(tx, "reviews", "confidence")? {
+        tx.execute("alter table reviews add column confidence integer;", [])?;
+    }
+    if !probe_column_exists(tx, "cards", "suspended")? {
+        tx.execute(
+            "alter table cards add column suspended integer not null default 0;",
+            [],
+        )?;
+    }
+    // The durations come last, after the columns of the older tables.
+    if !probe_column_exists(tx, "reviews", "duration_ms")? {
+        tx.execute("alter table reviews add column duration_ms integer;", [])?;
+    }
+    if !probe_table_exists(tx, "archived_reviews")? {
+        tx.execute_batch(CREATE_ARCHIVED_REVIEWS)?;
+        return Ok(());
+    }
+    if !probe_column_exists(tx, "archived_reviews", "duration_ms")? {
+        tx.execute(
+            "alter table archived_reviews add column duration_ms integer;",
+            [],
+        )?;
+    }
+    if probe_archive_references_cards(tx)?
+        || !probe_column_exists(tx, "archived_reviews", "archive_id")?
+    {
+        // Archived reviews used to be deleted along with their card, and to
+        // keep the IDs of the reviews as their own, which clash once those
+        // are reused. The table is rebuilt, since SQLite can't drop a foreign
+        // key or change a primary key.
+        tx.execute_batch("alter table archived_reviews rename to archived_reviews_old;")?;
+        tx.execute_batch(CREATE_ARCHIVED_REVIEWS)?;
+        tx.execute_batch(&format!(
+            "insert into archived_reviews ({ARCHIVED_REVIEW_COLUMNS}) select {ARCHIVED_REVIEW_COLUMNS} from archived_reviews_old;
+            drop table archived_reviews_old;"
+        ))?;
+    }
+    Ok(())
+}
+
+const CREATE_ARCHIVED_REVIEWS: &str = "create table archived_reviews (
+    archive_id integer primary key autoincrement,
+    review_id integer not null,
+    session_id integer not null
+        references sessions (session_id)
+        on update cascade
+        on delete cascade,
+    card_hash text not null,
+    reviewed_at text not null,
+    grade text not null,
+    stability real not null,
+    difficulty real not null,
+    interval_raw real not null,
+    interval_days integer not null,
+    due_date text not null,
+    revealed_at text,
+    confidence integer,
+    archived_at text not null,
+    duration_ms integer
+) strict;";
+
+/// Whether the `archived_reviews` table references the `cards` table, as it
+/// did in older versions.
+fn probe_archive_references_cards(tx: &Transaction) -> Fallible<bool> {
+    let sql = "select count(*) from pragma_foreign_key_list('archived_reviews') where \"table\" = 'cards';";
+    let count: i64 = tx.query_row(sql, [], |row| row.get(0))?;
+    Ok(count > 0)
+}
+
+fn probe_column_exists(tx: &Transaction, table: &str, column: &str) -> Fallible<bool> {
+    let sql = "select count(*) from pragma_table_info(?) where name = ?;";
+    let count: i64 = tx.query_row(sql, [table, column], |row| row.get(0))?;
     Ok(count > 0)
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::TimeDelta;
+
     use super::*;
     use crate::fsrs::Grade;
     use crate::types::performance::ReviewedPerformance;
@@ -349,6 +726,72 @@ mod tests {
         Ok(())
     }
 
+    /// Opening a database created before reveal times were tracked adds the
+    /// missing column.
+    #[test]
+    fn test_migrate_revealed_at() -> Fallible<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hashcards.db");
+        let path = path.to_str().unwrap();
+        {
+            let conn = Connection::open(path)?;
+            let old_schema = include_str!("schema.sql").replace(",\n    revealed_at text", "");
+            conn.execute_batch(&old_schema)?;
+        }
+        let mut db = Database::new(path)?;
+        let tx = db.conn.transaction()?;
+        assert!(probe_column_exists(&tx, "reviews", "revealed_at")?);
+        Ok(())
+    }
+
+    /// Opening a database created before cards were timed adds the missing
+    /// columns, at the end of the tables as in a new database.
+    #[test]
+    fn test_migrate_duration() -> Fallible<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hashcards.db");
+        let path = path.to_str().unwrap();
+        {
+            let conn = Connection::open(path)?;
+            let old_schema = include_str!("schema.sql").replace(",\n    duration_ms integer", "");
+            conn.execute_batch(&old_schema)?;
+        }
+        let mut db = Database::new(path)?;
+        let tx = db.conn.transaction()?;
+        assert!(probe_column_exists(&tx, "reviews", "duration_ms")?);
+        assert!(probe_column_exists(&tx, "archived_reviews", "duration_ms")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_answer_times() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let card_hash = CardHash::hash_bytes(b"a");
+        let now = Timestamp::now();
+        db.insert_card(card_hash, now)?;
+        let revealed_at = Timestamp::new(now.into_inner() - TimeDelta::milliseconds(1500));
+        let reviews = [Some(revealed_at), None]
+            .into_iter()
+            .map(|revealed_at| ReviewRecord {
+                card_hash,
+                reviewed_at: now,
+                grade: Grade::Good,
+                stability: 2.0,
+                difficulty: 2.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: now.date(),
+                revealed_at,
+                confidence: None,
+                duration_ms: None,
+            })
+            .collect();
+        db.save_session(now, now, reviews)?;
+        let times = db.get_answer_times()?;
+        assert_eq!(times.get(&card_hash), Some(&vec![1500]));
+        Ok(())
+    }
+
     /// Insert a card, and see that its hash is returned by `card_hashes`, and
     /// that `get_card_performance` returns an initial empty performance, and
     /// `due_today` returns it since it's new.
@@ -450,6 +893,9 @@ mod tests {
             interval_raw: 1.0,
             interval_days: 1,
             due_date: now.date(),
+            revealed_at: Some(now),
+            confidence: Some(Confidence::new(4)?),
+            duration_ms: None,
         };
         db.save_session(now, now, vec![review])?;
 
@@ -460,6 +906,8 @@ mod tests {
         assert_eq!(session.ended_at, now);
         let reviews = db.get_reviews_for_session(session.session_id)?;
         assert_eq!(reviews.len(), 1);
+        assert_eq!(reviews[0].data.revealed_at, Some(now));
+        assert_eq!(reviews[0].data.confidence, Some(Confidence::new(4)?));
         let fetched_review = &reviews[0];
         assert_eq!(fetched_review.data.card_hash, card_hash);
         assert_eq!(fetched_review.data.reviewed_at, now);
@@ -472,12 +920,293 @@ mod tests {
         Ok(())
     }
 
+    /// Retention is measured from the review history.
+    #[test]
+    fn test_get_retention() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let card_hash = CardHash::hash_bytes(b"a");
+        let now = Timestamp::now();
+        db.insert_card(card_hash, now)?;
+        assert_eq!(db.get_retention(card_hash)?, None);
+        let reviews = [Grade::Forgot, Grade::Hard, Grade::Good, Grade::Forgot]
+            .into_iter()
+            .map(|grade| ReviewRecord {
+                card_hash,
+                reviewed_at: now,
+                grade,
+                stability: 2.0,
+                difficulty: 2.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: now.date(),
+                revealed_at: None,
+                confidence: None,
+                duration_ms: None,
+            })
+            .collect();
+        db.save_session(now, now, reviews)?;
+        let retention = db.get_retention(card_hash)?.unwrap();
+        assert_eq!(retention, Retention::new(4, 2).unwrap());
+        assert_eq!(
+            db.get_grades(card_hash)?,
+            vec![Grade::Forgot, Grade::Hard, Grade::Good, Grade::Forgot]
+        );
+        Ok(())
+    }
+
+    /// Suspended cards are not due.
+    #[test]
+    fn test_suspend_card() -> Fallible<()> {
+        let db = Database::new(":memory:")?;
+        let a = CardHash::hash_bytes(b"a");
+        let b = CardHash::hash_bytes(b"b");
+        let now = Timestamp::now();
+        db.insert_card(a, now)?;
+        db.insert_card(b, now)?;
+        db.suspend_card(a)?;
+        assert_eq!(db.due_today(now.date())?, HashSet::from([b]));
+        assert_eq!(db.suspended_cards()?, vec![a]);
+        db.unsuspend_card(a)?;
+        assert_eq!(db.due_today(now.date())?, HashSet::from([a, b]));
+        assert!(db.suspended_cards()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_suspended() -> Fallible<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hashcards.db");
+        let path = path.to_str().unwrap();
+        let card_hash = CardHash::hash_bytes(b"a");
+        {
+            let conn = Connection::open(path)?;
+            let old_schema = include_str!("schema.sql")
+                .replace(",\n    suspended integer not null default 0", "");
+            conn.execute_batch(&old_schema)?;
+            conn.execute(
+                "insert into cards (card_hash, added_at, review_count) values (?, ?, 0);",
+                params![card_hash, Timestamp::now()],
+            )?;
+        }
+        let db = Database::new(path)?;
+        assert_eq!(db.due_today(Timestamp::now().date())?.len(), 1);
+        Ok(())
+    }
+
+    /// Resetting a card makes it new again, deleting or archiving its
+    /// reviews, and leaves other cards alone.
+    #[test]
+    fn test_reset_cards() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let a = CardHash::hash_bytes(b"a");
+        let b = CardHash::hash_bytes(b"b");
+        let c = CardHash::hash_bytes(b"c");
+        let now = Timestamp::now();
+        let mut reviews = Vec::new();
+        for card_hash in [a, b, c] {
+            db.insert_card(card_hash, now)?;
+            let performance = ReviewedPerformance {
+                last_reviewed_at: now,
+                stability: 2.0,
+                difficulty: 2.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: now.date(),
+                review_count: 1,
+            };
+            db.update_card_performance(card_hash, Performance::Reviewed(performance))?;
+            reviews.push(ReviewRecord {
+                card_hash,
+                reviewed_at: now,
+                grade: Grade::Forgot,
+                stability: 2.0,
+                difficulty: 2.0,
+                interval_raw: 1.0,
+                interval_days: 1,
+                due_date: now.date(),
+                revealed_at: None,
+                confidence: None,
+                duration_ms: None,
+            });
+        }
+        db.save_session(now, now, reviews)?;
+        db.suspend_card(a)?;
+
+        db.reset_cards(&[a], false, now)?;
+        db.reset_cards(&[b], true, now)?;
+
+        for card_hash in [a, b] {
+            assert_eq!(db.get_card_performance(card_hash)?, Performance::New);
+            assert!(db.get_grades(card_hash)?.is_empty());
+        }
+        // A suspended card stays suspended.
+        assert_eq!(db.suspended_cards()?, vec![a]);
+        assert!(!db.due_today(now.date())?.contains(&a));
+        assert!(db.due_today(now.date())?.contains(&b));
+        assert_eq!(count_archived_reviews(&db, a)?, 0);
+        assert_eq!(count_archived_reviews(&db, b)?, 1);
+        assert!(matches!(
+            db.get_card_performance(c)?,
+            Performance::Reviewed(_)
+        ));
+        assert_eq!(db.get_grades(c)?, vec![Grade::Forgot]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_archived_reviews() -> Fallible<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hashcards.db");
+        let path = path.to_str().unwrap();
+        let card_hash = CardHash::hash_bytes(b"a");
+        {
+            let conn = Connection::open(path)?;
+            let schema = include_str!("schema.sql");
+            let old_schema = &schema[..schema.find("create table archived_reviews").unwrap()];
+            conn.execute_batch(old_schema)?;
+            conn.execute(
+                "insert into cards (card_hash, added_at, review_count) values (?, ?, 0);",
+                params![card_hash, Timestamp::now()],
+            )?;
+        }
+        let mut db = Database::new(path)?;
+        db.reset_cards(&[card_hash], true, Timestamp::now())?;
+        assert_eq!(count_archived_reviews(&db, card_hash)?, 0);
+        Ok(())
+    }
+
+    /// Archives that reference the cards, or that use the IDs of the reviews
+    /// as their own, are rebuilt, keeping their reviews.
+    #[test]
+    fn test_migrate_archive_card_reference() -> Fallible<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hashcards.db");
+        let path = path.to_str().unwrap();
+        let card_hash = CardHash::hash_bytes(b"a");
+        let now = Timestamp::now();
+        {
+            let mut db = Database::new(path)?;
+            db.insert_card(card_hash, now)?;
+            db.save_session(now, now, Vec::new())?;
+            db.conn.execute_batch(
+                "drop table archived_reviews;
+                create table archived_reviews (
+                    review_id integer primary key,
+                    session_id integer not null
+                        references sessions (session_id)
+                        on update cascade
+                        on delete cascade,
+                    card_hash text not null
+                        references cards (card_hash)
+                        on update cascade
+                        on delete cascade,
+                    reviewed_at text not null,
+                    grade text not null,
+                    stability real not null,
+                    difficulty real not null,
+                    interval_raw real not null,
+                    interval_days integer not null,
+                    due_date text not null,
+                    revealed_at text,
+                    confidence integer,
+                    archived_at text not null
+                ) strict;",
+            )?;
+            db.conn.execute(
+                "insert into archived_reviews (session_id, card_hash, reviewed_at, grade, stability, difficulty, interval_raw, interval_days, due_date, archived_at) values (1, ?, ?, 'good', 1.0, 1.0, 1.0, 1, ?, ?);",
+                params![card_hash, now, now.date(), now],
+            )?;
+        }
+        let mut db = Database::new(path)?;
+        db.delete_card(card_hash, false, now)?;
+        assert_eq!(count_archived_reviews(&db, card_hash)?, 1);
+        let tx = db.conn.transaction()?;
+        assert!(probe_column_exists(&tx, "archived_reviews", "archive_id")?);
+        Ok(())
+    }
+
+    /// Reviews can be archived again once the IDs of the archived reviews are
+    /// reused, by resetting or by deleting a card.
+    #[test]
+    fn test_archive_twice() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let card_hash = CardHash::hash_bytes(b"a");
+        let now = Timestamp::now();
+        let review = || ReviewRecord {
+            card_hash,
+            reviewed_at: now,
+            grade: Grade::Good,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            revealed_at: None,
+            confidence: None,
+            duration_ms: None,
+        };
+        db.insert_card(card_hash, now)?;
+        db.save_session(now, now, vec![review()])?;
+        db.reset_cards(&[card_hash], true, now)?;
+        db.save_session(now, now, vec![review()])?;
+        db.reset_cards(&[card_hash], true, now)?;
+        db.save_session(now, now, vec![review()])?;
+        db.delete_card(card_hash, true, now)?;
+        assert_eq!(count_archived_reviews(&db, card_hash)?, 3);
+        Ok(())
+    }
+
+    fn count_archived_reviews(db: &Database, card_hash: CardHash) -> Fallible<usize> {
+        let sql = "select count(*) from archived_reviews where card_hash = ?;";
+        let count: i64 = db
+            .conn
+            .query_row(sql, params![card_hash], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// Opening a corrupt database explains the error.
+    #[test]
+    fn test_open_corrupt_database() -> Fallible<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hashcards.db");
+        std::fs::write(
+            &path,
+            "this is not a database, but it is long enough to look like one",
+        )?;
+        let path = path.to_str().unwrap();
+        let expected = format!(
+            "error: could not open the review store {path}: the file is corrupt, or is not a database."
+        );
+        let result = Database::new(path);
+        assert_eq!(result.err().unwrap().to_string(), expected);
+        let result = Database::new_in_memory_copy(path);
+        assert_eq!(result.err().unwrap().to_string(), expected);
+        Ok(())
+    }
+
+    /// Changes to an in-memory copy are not written to the file.
+    #[test]
+    fn test_in_memory_copy() -> Fallible<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("hashcards.db");
+        let path = path.to_str().unwrap();
+        let a = CardHash::hash_bytes(b"a");
+        let b = CardHash::hash_bytes(b"b");
+        let now = Timestamp::now();
+        Database::new(path)?.insert_card(a, now)?;
+        let copy = Database::new_in_memory_copy(path)?;
+        assert_eq!(copy.card_hashes()?, HashSet::from([a]));
+        copy.insert_card(b, now)?;
+        assert_eq!(Database::new(path)?.card_hashes()?, HashSet::from([a]));
+        Ok(())
+    }
+
     /// Trying to delete a non-existent card returns an error.
     #[test]
     fn test_delete_nonexistent_card() -> Fallible<()> {
         let db = Database::new(":memory:")?;
         let card_hash = CardHash::hash_bytes(b"a");
-        let result = db.delete_card(card_hash);
+        let result = db.delete_card(card_hash, false, Timestamp::now());
         assert!(result.is_err());
         let err = result.err().unwrap();
         assert_eq!(err.to_string(), "error: Card not found");
@@ -491,7 +1220,7 @@ mod tests {
         let card_hash = CardHash::hash_bytes(b"a");
         let now = Timestamp::now();
         db.insert_card(card_hash, now)?;
-        db.delete_card(card_hash)?;
+        db.delete_card(card_hash, false, now)?;
         let result = db.get_card_performance(card_hash);
         assert!(result.is_err());
         let err = result.err().unwrap();
@@ -501,4 +1230,78 @@ mod tests {
         );
         Ok(())
     }
+
+    /// Deleting a card with `archive` keeps its reviews in the archive.
+    #[test]
+    fn test_delete_card_archive() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let card_hash = CardHash::hash_bytes(b"a");
+        let now = Timestamp::now();
+        db.insert_card(card_hash, now)?;
+        let review = ReviewRecord {
+            card_hash,
+            reviewed_at: now,
+            grade: Grade::Good,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            revealed_at: None,
+            confidence: None,
+            duration_ms: None,
+        };
+        db.save_session(now, now, vec![review])?;
+        db.delete_card(card_hash, true, now)?;
+        assert!(db.card_hashes()?.is_empty());
+        assert_eq!(count_archived_reviews(&db, card_hash)?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transfer_history() -> Fallible<()> {
+        let mut db = Database::new(":memory:")?;
+        let old = CardHash::hash_bytes(b"a");
+        let new = CardHash::hash_bytes(b"b");
+        let now = Timestamp::now();
+        db.insert_card(old, now)?;
+        db.insert_card(new, now)?;
+        let review = ReviewRecord {
+            card_hash: old,
+            reviewed_at: now,
+            grade: Grade::Good,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            revealed_at: None,
+            confidence: None,
+            duration_ms: None,
+        };
+        db.save_session(now, now, vec![review])?;
+        let performance = Performance::Reviewed(ReviewedPerformance {
+            last_reviewed_at: now,
+            stability: 2.0,
+            difficulty: 2.0,
+            interval_raw: 1.0,
+            interval_days: 1,
+            due_date: now.date(),
+            review_count: 1,
+        });
+        db.update_card_performance(old, performance)?;
+        db.suspend_card(old)?;
+
+        db.transfer_history(old, new)?;
+        assert_eq!(db.get_card_performance(new)?, performance);
+        assert_eq!(db.suspended_cards()?, vec![new]);
+        assert_eq!(db.get_card_performance(old)?, Performance::New);
+        assert_eq!(db.get_retention(new)?, Retention::new(1, 1));
+        assert_eq!(db.get_retention(old)?, None);
+        assert!(
+            db.transfer_history(old, CardHash::hash_bytes(b"c"))
+                .is_err()
+        );
+        Ok(())
+    }
 }
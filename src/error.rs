@@ -31,6 +31,11 @@ impl ErrorReport {
             message: msg.into(),
         }
     }
+
+    /// The message, without the `error: ` prefix.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
 impl From<std::io::Error> for ErrorReport {
@@ -0,0 +1,433 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// The syntax of a language, as far as highlighting is concerned.
+struct Language {
+    /// The names a code block can be tagged with.
+    names: &'static [&'static str],
+    keywords: &'static [&'static str],
+    /// Whether keywords match regardless of case, as in SQL.
+    ignore_case: bool,
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    /// The characters that delimit strings.
+    quotes: &'static [char],
+}
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        names: &["rust", "rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        ignore_case: false,
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        // Single quotes are also lifetimes, so only double-quoted strings are
+        // highlighted.
+        quotes: &['"'],
+    },
+    Language {
+        names: &["python", "py"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+            "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return",
+            "True", "try", "while", "with", "yield",
+        ],
+        ignore_case: false,
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Language {
+        names: &["javascript", "js", "typescript", "ts"],
+        keywords: &[
+            "async",
+            "await",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "else",
+            "export",
+            "extends",
+            "false",
+            "finally",
+            "for",
+            "function",
+            "if",
+            "import",
+            "in",
+            "instanceof",
+            "interface",
+            "let",
+            "new",
+            "null",
+            "of",
+            "return",
+            "static",
+            "super",
+            "switch",
+            "this",
+            "throw",
+            "true",
+            "try",
+            "type",
+            "typeof",
+            "undefined",
+            "var",
+            "void",
+            "while",
+            "yield",
+        ],
+        ignore_case: false,
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+    },
+    Language {
+        names: &["c", "h"],
+        keywords: &[
+            "auto", "break", "case", "char", "const", "continue", "default", "do", "double",
+            "else", "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long",
+            "register", "return", "short", "signed", "sizeof", "static", "struct", "switch",
+            "typedef", "union", "unsigned", "void", "volatile", "while", "bool", "true", "false",
+            "NULL",
+        ],
+        ignore_case: false,
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Language {
+        names: &["cpp", "c++", "java"],
+        keywords: &[
+            "auto",
+            "bool",
+            "break",
+            "case",
+            "catch",
+            "char",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "double",
+            "else",
+            "enum",
+            "extends",
+            "false",
+            "final",
+            "float",
+            "for",
+            "if",
+            "implements",
+            "import",
+            "int",
+            "interface",
+            "long",
+            "namespace",
+            "new",
+            "null",
+            "nullptr",
+            "package",
+            "private",
+            "protected",
+            "public",
+            "return",
+            "short",
+            "static",
+            "struct",
+            "switch",
+            "template",
+            "this",
+            "throw",
+            "throws",
+            "true",
+            "try",
+            "typename",
+            "using",
+            "virtual",
+            "void",
+            "while",
+        ],
+        ignore_case: false,
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Language {
+        names: &["go"],
+        keywords: &[
+            "break",
+            "case",
+            "chan",
+            "const",
+            "continue",
+            "default",
+            "defer",
+            "else",
+            "false",
+            "fallthrough",
+            "for",
+            "func",
+            "go",
+            "goto",
+            "if",
+            "import",
+            "interface",
+            "map",
+            "nil",
+            "package",
+            "range",
+            "return",
+            "select",
+            "struct",
+            "switch",
+            "true",
+            "type",
+            "var",
+        ],
+        ignore_case: false,
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+    },
+    Language {
+        names: &["haskell", "hs"],
+        keywords: &[
+            "case", "class", "data", "deriving", "do", "else", "if", "import", "in", "instance",
+            "let", "module", "newtype", "of", "then", "type", "where",
+        ],
+        ignore_case: false,
+        line_comments: &["--"],
+        block_comment: Some(("{-", "-}")),
+        quotes: &['"'],
+    },
+    Language {
+        names: &["bash", "sh", "shell", "zsh"],
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+            "in", "local", "return", "then", "until", "while",
+        ],
+        ignore_case: false,
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Language {
+        names: &["sql"],
+        keywords: &[
+            "and", "as", "asc", "by", "create", "delete", "desc", "distinct", "drop", "from",
+            "group", "having", "in", "index", "insert", "into", "is", "join", "left", "limit",
+            "not", "null", "on", "or", "order", "primary", "key", "select", "set", "table",
+            "union", "update", "values", "where",
+        ],
+        ignore_case: true,
+        line_comments: &["--"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['\''],
+    },
+];
+
+/// The kind of a highlighted token, which is also its CSS class.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum TokenKind {
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+impl TokenKind {
+    fn class(self) -> &'static str {
+        match self {
+            TokenKind::Keyword => "tok-keyword",
+            TokenKind::String => "tok-string",
+            TokenKind::Comment => "tok-comment",
+            TokenKind::Number => "tok-number",
+        }
+    }
+}
+
+/// Highlight the code of a code block tagged with a language, as HTML to go
+/// inside its `<code>` element: each token is a `<span>` whose class is its
+/// kind, and the rest is escaped text. Returns `None` if the language is not
+/// known, in which case the block is shown as plain text.
+pub fn highlight(language: &str, code: &str) -> Option<String> {
+    let language: &Language = find_language(language)?;
+    let mut html: String = String::new();
+    let mut rest: &str = code;
+    while let Some(c) = rest.chars().next() {
+        let (kind, len): (Option<TokenKind>, usize) = next_token(language, rest, c);
+        let (token, tail) = rest.split_at(len);
+        match kind {
+            Some(kind) => {
+                html.push_str(&format!("<span class=\"{}\">", kind.class()));
+                push_escaped(&mut html, token);
+                html.push_str("</span>");
+            }
+            None => push_escaped(&mut html, token),
+        }
+        rest = tail;
+    }
+    Some(html)
+}
+
+fn find_language(name: &str) -> Option<&'static Language> {
+    let name: String = name.to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|language| language.names.contains(&name.as_str()))
+}
+
+/// The kind and byte length of the token at the start of `text`, whose first
+/// character is `c`.
+fn next_token(language: &Language, text: &str, c: char) -> (Option<TokenKind>, usize) {
+    if language
+        .line_comments
+        .iter()
+        .any(|prefix| text.starts_with(prefix))
+    {
+        let len: usize = text.find('\n').unwrap_or(text.len());
+        return (Some(TokenKind::Comment), len);
+    }
+    if let Some((open, close)) = language.block_comment {
+        if let Some(body) = text.strip_prefix(open) {
+            let len: usize = match body.find(close) {
+                Some(end) => open.len() + end + close.len(),
+                None => text.len(),
+            };
+            return (Some(TokenKind::Comment), len);
+        }
+    }
+    if language.quotes.contains(&c) {
+        return (Some(TokenKind::String), string_length(text, c));
+    }
+    if c.is_ascii_digit() {
+        let len: usize = word_length(text, |c| c.is_ascii_alphanumeric() || c == '.' || c == '_');
+        return (Some(TokenKind::Number), len);
+    }
+    if c.is_alphabetic() || c == '_' {
+        let len: usize = word_length(text, |c| c.is_alphanumeric() || c == '_');
+        let word: &str = &text[..len];
+        let keyword: bool = language.keywords.iter().any(|keyword| {
+            if language.ignore_case {
+                keyword.eq_ignore_ascii_case(word)
+            } else {
+                *keyword == word
+            }
+        });
+        return (keyword.then_some(TokenKind::Keyword), len);
+    }
+    (None, c.len_utf8())
+}
+
+/// The length of the string starting at the start of `text`, up to and
+/// including the closing quote. Backslashes escape the next character. A
+/// string that isn't closed ends at the end of the line, except for
+/// backquoted strings, which can span lines.
+fn string_length(text: &str, quote: char) -> usize {
+    let mut chars = text.char_indices().skip(1);
+    while let Some((index, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == quote {
+            return index + c.len_utf8();
+        } else if c == '\n' && quote != '`' {
+            return index;
+        }
+    }
+    text.len()
+}
+
+/// The length of the longest prefix of `text` whose characters all match.
+fn word_length(text: &str, matches: impl Fn(char) -> bool) -> usize {
+    text.find(|c: char| !matches(c)).unwrap_or(text.len())
+}
+
+/// Push `text` onto `html`, escaping the HTML special characters.
+pub fn push_escaped(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            c => html.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_rust() {
+        let html = highlight("rust", "fn main() {\n    let x = \"<a>\"; // 42\n}").unwrap();
+        assert_eq!(
+            html,
+            "<span class=\"tok-keyword\">fn</span> main() {\n    <span class=\"tok-keyword\">let</span> x = <span class=\"tok-string\">&quot;&lt;a&gt;&quot;</span>; <span class=\"tok-comment\">// 42</span>\n}"
+        );
+    }
+
+    #[test]
+    fn test_highlight_numbers_and_escapes() {
+        let html = highlight("Python", "x = 'it\\'s' + 3.5e2").unwrap();
+        assert_eq!(
+            html,
+            "x = <span class=\"tok-string\">'it\\'s'</span> + <span class=\"tok-number\">3.5e2</span>"
+        );
+        // Digits inside identifiers are not numbers.
+        assert_eq!(highlight("py", "x2").unwrap(), "x2");
+    }
+
+    #[test]
+    fn test_highlight_block_comment() {
+        let html = highlight("c", "/* a\nb */ int").unwrap();
+        assert_eq!(
+            html,
+            "<span class=\"tok-comment\">/* a\nb */</span> <span class=\"tok-keyword\">int</span>"
+        );
+        // An unclosed comment runs to the end.
+        let html = highlight("c", "x /* a").unwrap();
+        assert_eq!(html, "x <span class=\"tok-comment\">/* a</span>");
+    }
+
+    #[test]
+    fn test_highlight_ignore_case() {
+        let html = highlight("sql", "SELECT id from t").unwrap();
+        assert_eq!(
+            html,
+            "<span class=\"tok-keyword\">SELECT</span> id <span class=\"tok-keyword\">from</span> t"
+        );
+    }
+
+    #[test]
+    fn test_unknown_language() {
+        assert_eq!(highlight("klingon", "x = 1"), None);
+    }
+}
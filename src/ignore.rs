@@ -0,0 +1,316 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::read_to_string;
+use std::path::Path;
+
+use crate::error::Fallible;
+
+/// The name of the ignore file in the collection root.
+pub const IGNORE_FILE: &str = ".hashcardsignore";
+
+/// Which files and directories to skip when loading a collection.
+///
+/// By default, dotfiles (e.g. `.git` or `.obsidian`) and files with an ignored
+/// extension are skipped. The patterns in `.hashcardsignore`, in gitignore
+/// syntax, are applied after that, in order: a pattern ignores the paths it
+/// matches, and a negated pattern (`!pattern`) includes them again.
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<IgnorePattern>,
+    /// Extensions of files to skip, without a leading dot, e.g.
+    /// `template.md`.
+    extensions: Vec<String>,
+}
+
+/// A line of the ignore file.
+#[derive(Debug, PartialEq)]
+struct IgnorePattern {
+    /// The pattern includes the paths it matches, rather than ignoring them.
+    negated: bool,
+    /// The pattern only matches directories.
+    dir_only: bool,
+    /// The path segments of the pattern. Patterns that are not anchored to
+    /// the collection root start with `**`.
+    segments: Vec<String>,
+}
+
+impl IgnoreRules {
+    /// Load the ignore file of a collection, if there is one.
+    pub fn load(directory: &Path, extensions: &[String]) -> Fallible<Self> {
+        let path = directory.join(IGNORE_FILE);
+        let text = if path.exists() {
+            read_to_string(path)?
+        } else {
+            String::new()
+        };
+        Ok(Self::parse(&text, extensions))
+    }
+
+    fn parse(text: &str, extensions: &[String]) -> Self {
+        Self {
+            patterns: text.lines().filter_map(IgnorePattern::parse).collect(),
+            extensions: extensions
+                .iter()
+                .map(|extension| extension.trim_start_matches('.').to_string())
+                .collect(),
+        }
+    }
+
+    /// Whether to skip a file or directory, given its path relative to the
+    /// collection root.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let segments: Vec<&str> = path
+            .components()
+            .filter_map(|component| component.as_os_str().to_str())
+            .collect();
+        let Some(name) = segments.last() else {
+            return false;
+        };
+        let mut ignored = name.starts_with('.')
+            || (!is_dir
+                && self
+                    .extensions
+                    .iter()
+                    .any(|extension| name.ends_with(&format!(".{extension}"))));
+        for pattern in &self.patterns {
+            if pattern.matches(&segments, is_dir) {
+                ignored = !pattern.negated;
+            }
+        }
+        ignored
+    }
+}
+
+impl IgnorePattern {
+    /// Parse a line of the ignore file. Blank lines and comments are skipped.
+    fn parse(line: &str) -> Option<Self> {
+        // Trailing spaces are ignored, unless escaped with a backslash, which
+        // the glob then reads as a literal space.
+        let trimmed: &str = line.trim_end();
+        let line: String = if trimmed.ends_with('\\') && line.len() > trimmed.len() {
+            format!("{trimmed} ")
+        } else {
+            trimmed.to_string()
+        };
+        let line: &str = &line;
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        // A pattern with a slash at the start or in the middle is anchored to
+        // the root. Other patterns match at any depth.
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+        let mut segments: Vec<String> = Vec::new();
+        if !anchored {
+            segments.push("**".to_string());
+        }
+        segments.extend(line.split('/').map(str::to_string));
+        Some(Self {
+            negated,
+            dir_only,
+            segments,
+        })
+    }
+
+    fn matches(&self, path: &[&str], is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        match_segments(&self.segments, path)
+    }
+}
+
+/// Match path segments against pattern segments, where `**` matches any
+/// number of segments.
+fn match_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((segment, path)) => match_segment(first, segment) && match_segments(rest, path),
+            None => false,
+        },
+    }
+}
+
+/// Match a file name against a glob: `*` matches any sequence of characters,
+/// `?` any single character, `[abc]` or `[a-z]` one character in the set (or
+/// not in it, with `[!abc]`), and `\` escapes the next character.
+fn match_segment(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_chars(&pattern, &text)
+}
+
+fn match_chars(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| match_chars(rest, &text[skip..])),
+        Some((first, rest)) => {
+            let Some((c, text)) = text.split_first() else {
+                return false;
+            };
+            match first {
+                '?' => match_chars(rest, text),
+                '[' => match match_class(rest, *c) {
+                    Some((true, rest)) => match_chars(rest, text),
+                    Some((false, _)) => false,
+                    // An unclosed bracket is a literal.
+                    None => *c == '[' && match_chars(rest, text),
+                },
+                '\\' => match rest.split_first() {
+                    Some((escaped, rest)) => escaped == c && match_chars(rest, text),
+                    None => *c == '\\',
+                },
+                _ => first == c && match_chars(rest, text),
+            }
+        }
+    }
+}
+
+/// Match a character against a bracket expression, given the pattern after
+/// the `[`. Returns whether it matched, and the pattern after the `]`, or
+/// `None` if the bracket is not closed.
+fn match_class(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let (negated, mut pattern) = match pattern.split_first() {
+        Some(('!' | '^', rest)) => (true, rest),
+        _ => (false, pattern),
+    };
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        match pattern {
+            [] => return None,
+            [']', rest @ ..] if !first => return Some((matched != negated, rest)),
+            [low, '-', high, rest @ ..] if *high != ']' => {
+                matched |= (*low..=*high).contains(&c);
+                pattern = rest;
+            }
+            [member, rest @ ..] => {
+                matched |= *member == c;
+                pattern = rest;
+            }
+        }
+        first = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn ignored(rules: &IgnoreRules, path: &str) -> bool {
+        rules.is_ignored(&PathBuf::from(path), false)
+    }
+
+    fn ignored_dir(rules: &IgnoreRules, path: &str) -> bool {
+        rules.is_ignored(&PathBuf::from(path), true)
+    }
+
+    #[test]
+    fn test_defaults() {
+        let rules = IgnoreRules::parse("", &[".template.md".to_string()]);
+        assert!(!ignored(&rules, "Deck.md"));
+        assert!(!ignored(&rules, "Spanish/Verbs.md"));
+        assert!(ignored(&rules, ".Deck.md"));
+        assert!(ignored_dir(&rules, ".obsidian"));
+        assert!(ignored_dir(&rules, "Spanish/.git"));
+        assert!(ignored(&rules, "Card.template.md"));
+        assert!(!ignored_dir(&rules, "Card.template.md"));
+    }
+
+    #[test]
+    fn test_patterns() {
+        let text = "\
+# Not decks.
+README.md
+templates/
+/Drafts/*.md
+notes/**/scratch.md
+[Tt]odo?.md
+";
+        let rules = IgnoreRules::parse(text, &[]);
+        assert!(ignored(&rules, "README.md"));
+        assert!(ignored(&rules, "Spanish/README.md"));
+        assert!(!ignored(&rules, "README2.md"));
+        assert!(ignored_dir(&rules, "templates"));
+        assert!(ignored_dir(&rules, "Spanish/templates"));
+        assert!(!ignored(&rules, "templates"));
+        assert!(ignored(&rules, "Drafts/Verbs.md"));
+        assert!(!ignored(&rules, "Spanish/Drafts/Verbs.md"));
+        assert!(!ignored(&rules, "Drafts/Old/Verbs.md"));
+        assert!(ignored(&rules, "notes/scratch.md"));
+        assert!(ignored(&rules, "notes/a/b/scratch.md"));
+        assert!(!ignored(&rules, "other/scratch.md"));
+        assert!(ignored(&rules, "todo1.md"));
+        assert!(ignored(&rules, "Todo2.md"));
+        assert!(!ignored(&rules, "todo.md"));
+        assert!(!ignored(&rules, "# Not decks."));
+    }
+
+    #[test]
+    fn test_negation() {
+        let text = "\
+*.md
+!Spanish.md
+Spanish.md
+!Keep.md
+!.Hidden.md
+";
+        let rules = IgnoreRules::parse(text, &[]);
+        // The last matching pattern wins.
+        assert!(ignored(&rules, "Spanish.md"));
+        assert!(!ignored(&rules, "Keep.md"));
+        assert!(ignored(&rules, "Other.md"));
+        // Negation can include a dotfile again.
+        assert!(!ignored(&rules, ".Hidden.md"));
+    }
+
+    #[test]
+    fn test_escapes() {
+        let rules = IgnoreRules::parse("\\#Hash.md\n\\!Bang.md\nStar\\*.md\nSpace\\ \n", &[]);
+        assert!(ignored(&rules, "Space "));
+        assert!(!ignored(&rules, "Space"));
+        assert!(ignored(&rules, "#Hash.md"));
+        assert!(ignored(&rules, "!Bang.md"));
+        assert!(ignored(&rules, "Star*.md"));
+        assert!(!ignored(&rules, "Starry.md"));
+    }
+
+    #[test]
+    fn test_match_segment() {
+        assert!(match_segment("*.md", "Deck.md"));
+        assert!(!match_segment("*.md", "Deck.txt"));
+        assert!(match_segment("[a-c]?", "bx"));
+        assert!(!match_segment("[!a-c]?", "bx"));
+        assert!(match_segment("[]]", "]"));
+        assert!(match_segment("[x", "[x"));
+    }
+}
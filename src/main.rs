@@ -12,20 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod casefold;
 mod cli;
 mod cmd;
 mod collection;
+mod config;
+mod csv;
 mod db;
 mod error;
 mod fsrs;
 #[cfg(test)]
 mod helper;
+mod highlight;
+mod ignore;
 mod markdown;
 mod media;
+mod parse_cache;
 mod parser;
 mod rng;
 mod types;
 mod utils;
+mod zip;
 
 use std::process::ExitCode;
 
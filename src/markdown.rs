@@ -12,20 +12,141 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::mem::take;
+use std::ops::Range;
+use std::str::FromStr;
+
+use maud::Render;
+
+use pulldown_cmark::CodeBlockKind;
 use pulldown_cmark::CowStr;
 use pulldown_cmark::Event;
 use pulldown_cmark::Options;
 use pulldown_cmark::Parser;
 use pulldown_cmark::Tag;
+use pulldown_cmark::TagEnd;
 use pulldown_cmark::html::push_html;
 
 use crate::error::ErrorReport;
 use crate::error::Fallible;
+use crate::highlight::highlight;
+use crate::highlight::push_escaped;
 use crate::media::resolve::MediaResolver;
 
+/// The prefix of the first line of a collapsible section.
+const DETAILS_PREFIX: &str = ">!";
+
+/// Marks the start of a cloze deletion in the text before parsing. Like the
+/// `[` it replaces, this is a punctuation character, so emphasis around the
+/// deletion parses the same way it would around the brackets.
+const CLOZE_START: char = '\u{2E26}';
+
+/// Marks the end of a cloze deletion.
+const CLOZE_END: char = '\u{2E27}';
+
+const CLOZE_REVEAL_OPEN: &str = "<span class='cloze-reveal'>";
+
+const CLOZE_REVEAL_CLOSE: &str = "</span>";
+
+const CLOZE_BLOCK_REVEAL_OPEN: &str = "<div class='cloze-reveal'>";
+
+const CLOZE_BLOCK_REVEAL_CLOSE: &str = "</div>";
+
+/// Which side of a cloze card to render.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClozeSide {
+    /// The deletion is hidden.
+    Front,
+    /// The deletion is revealed.
+    Back,
+}
+
+/// The number of dots in the blank of a hidden deletion, by default.
+pub const DEFAULT_CLOZE_DOTS: usize = 13;
+
+/// How to draw the blank in place of a hidden cloze deletion. The blank is a
+/// `cloze` span of dots, which can be styled.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClozeBlank {
+    /// The same number of dots for every deletion, so the blank does not
+    /// give away the length of the answer.
+    Fixed(usize),
+    /// One dot per character of the deletion.
+    Proportional,
+    /// The first character of the deletion, followed by one dot per
+    /// remaining character.
+    Hint,
+}
+
+impl Default for ClozeBlank {
+    fn default() -> Self {
+        ClozeBlank::Fixed(DEFAULT_CLOZE_DOTS)
+    }
+}
+
+impl ClozeBlank {
+    /// The contents of the blank hiding a deletion with the given text.
+    /// Deletions without text, like images, and hidden block elements, get
+    /// the fixed blank.
+    fn contents(self, text: &str) -> String {
+        let text: &str = text.trim();
+        let length: usize = text.chars().count();
+        match (self, text.chars().next()) {
+            (ClozeBlank::Fixed(dots), _) => ".".repeat(dots),
+            (ClozeBlank::Proportional, Some(_)) => ".".repeat(length),
+            (ClozeBlank::Hint, Some(first)) => {
+                let first: String = first.to_string();
+                format!("{}{}", first.render().into_string(), ".".repeat(length - 1))
+            }
+            (_, None) => ".".repeat(DEFAULT_CLOZE_DOTS),
+        }
+    }
+
+    /// The blank hiding an inline deletion with the given text.
+    fn inline(self, text: &str) -> String {
+        format!("<span class='cloze'>{}</span>", self.contents(text))
+    }
+
+    /// The blank hiding a whole block element, like a list or a code block.
+    fn block(self) -> String {
+        format!("<div class='cloze cloze-block'>{}</div>", self.contents(""))
+    }
+}
+
+impl Display for ClozeBlank {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClozeBlank::Fixed(DEFAULT_CLOZE_DOTS) => write!(f, "fixed"),
+            ClozeBlank::Fixed(dots) => write!(f, "{dots}"),
+            ClozeBlank::Proportional => write!(f, "proportional"),
+            ClozeBlank::Hint => write!(f, "hint"),
+        }
+    }
+}
+
+impl FromStr for ClozeBlank {
+    type Err = ErrorReport;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fixed" => Ok(ClozeBlank::default()),
+            "proportional" => Ok(ClozeBlank::Proportional),
+            "hint" => Ok(ClozeBlank::Hint),
+            _ => match s.parse::<usize>() {
+                Ok(dots) if dots > 0 => Ok(ClozeBlank::Fixed(dots)),
+                _ => Err(ErrorReport::new(format!(
+                    "Invalid cloze blank '{s}': expected 'fixed', 'proportional', 'hint', or a number of dots."
+                ))),
+            },
+        }
+    }
+}
+
 const AUDIO_EXTENSIONS: [&str; 3] = ["mp3", "wav", "ogg"];
 
-fn is_audio_file(url: &str) -> bool {
+pub fn is_audio_file(url: &str) -> bool {
     if let Some(ext) = url.split('.').next_back() {
         AUDIO_EXTENSIONS.contains(&ext)
     } else {
@@ -37,17 +158,169 @@ fn is_audio_file(url: &str) -> bool {
 pub struct MarkdownRenderConfig {
     /// A media resolver.
     pub resolver: MediaResolver,
-    /// The port where the server is exposed.
+    /// The port where the server is exposed. Media is served on this port,
+    /// unless the resolver has a base URL.
     pub port: u16,
+    /// Whether to dim the text around a cloze deletion on the front of the
+    /// card.
+    pub dim_cloze_context: bool,
+    /// Whether to highlight the syntax of code blocks tagged with a
+    /// language.
+    pub highlight_code: bool,
+    /// How to draw the blank in place of a hidden cloze deletion.
+    pub cloze_blank: ClozeBlank,
 }
 
 pub fn markdown_to_html(config: &MarkdownRenderConfig, markdown: &str) -> Fallible<String> {
+    render(config, markdown, None, &[])
+}
+
+/// Render a cloze card, where `start` and `end` are the byte positions of the
+/// first and last characters of the deletion.
+///
+/// The deletion is marked in the text, and the text is rendered as a whole,
+/// so formatting inside, around, or across the deletion renders as it would
+/// without it. The deletion is then hidden or revealed in the parsed events,
+/// rather than in the source text.
+///
+/// A deletion that spans whole lines, like `[- a list item]` or
+/// `[![alt](image.png)]`, and covers whole block elements, is a block
+/// deletion: the elements are hidden or revealed as a whole. Paragraphs,
+/// headings, and list items keep their tags, so a hidden list item is still
+/// a bullet; other elements, like lists and code blocks, are replaced
+/// entirely.
+pub fn cloze_to_html(
+    config: &MarkdownRenderConfig,
+    text: &str,
+    start: usize,
+    end: usize,
+    side: ClozeSide,
+) -> Fallible<String> {
+    cloze_group_to_html(config, text, &[(start, end)], side)
+}
+
+/// Render several cloze deletions of the same text at once, hiding or
+/// revealing all of them. The deletions are `(start, end)` byte positions, as
+/// in [`cloze_to_html`], and must not overlap.
+pub fn cloze_group_to_html(
+    config: &MarkdownRenderConfig,
+    text: &str,
+    deletions: &[(usize, usize)],
+    side: ClozeSide,
+) -> Fallible<String> {
+    let mut deletions: Vec<(usize, usize)> = deletions.to_vec();
+    deletions.sort();
+    let mut position: usize = 0;
+    for (start, end) in deletions.iter() {
+        if *start < position || text.get(*start..*end + 1).is_none() {
+            return Err(ErrorReport::new(format!(
+                "Invalid cloze deletion range ({start}-{end})."
+            )));
+        }
+        position = end + 1;
+    }
+    let blocks: Vec<Range<usize>> = block_deletions(config, text, &deletions)?;
+    // Mark the inline deletions. Block deletions are not marked, since a
+    // marker at the start of a line would change how the line parses.
+    let mut marked: String = String::new();
+    let mut marked_blocks: Vec<Range<usize>> = Vec::new();
+    let mut position: usize = 0;
+    for (start, end) in deletions {
+        // The ranges were checked above.
+        marked.push_str(text.get(position..start).unwrap_or_default());
+        let deleted: &str = text.get(start..end + 1).unwrap_or_default();
+        if blocks.contains(&(start..end + 1)) {
+            marked_blocks.push(marked.len()..marked.len() + deleted.len());
+            marked.push_str(deleted);
+        } else {
+            marked.push(CLOZE_START);
+            marked.push_str(deleted);
+            marked.push(CLOZE_END);
+        }
+        position = end + 1;
+    }
+    marked.push_str(text.get(position..).unwrap_or_default());
+    render(config, &marked, Some(side), &marked_blocks)
+}
+
+/// The deletions that are block deletions: those that span whole lines, and
+/// cover at least one block element. Collapsible sections are rewritten
+/// before parsing, so there are no block deletions in cards that have them.
+fn block_deletions(
+    config: &MarkdownRenderConfig,
+    text: &str,
+    deletions: &[(usize, usize)],
+) -> Fallible<Vec<Range<usize>>> {
+    let candidates: Vec<Range<usize>> = deletions
+        .iter()
+        .map(|(start, end)| *start..*end + 1)
+        .filter(|range| {
+            let starts_line = range.start == 0 || text[..range.start].ends_with('\n');
+            let ends_line = range.end == text.len() || text[range.end..].starts_with('\n');
+            starts_line && ends_line
+        })
+        .collect();
+    if candidates.is_empty() || expand_details(config, text)? != text {
+        return Ok(Vec::new());
+    }
+    let elements: Vec<Range<usize>> = Parser::new_ext(text, markdown_options())
+        .into_offset_iter()
+        .filter(|(event, _)| matches!(event, Event::Start(tag) if is_block(tag)))
+        .map(|(_, range)| range)
+        .collect();
+    Ok(candidates
+        .into_iter()
+        .filter(|deletion| {
+            elements
+                .iter()
+                .any(|element| covers(text, deletion, element))
+        })
+        .collect())
+}
+
+/// Whether the source of an element lies within a deletion, ignoring
+/// trailing whitespace.
+fn covers(text: &str, deletion: &Range<usize>, element: &Range<usize>) -> bool {
+    let source: &str = text.get(element.clone()).unwrap_or_default();
+    let end: usize = element.start + source.trim_end().len();
+    element.start >= deletion.start && end <= deletion.end && element.start < end
+}
+
+fn is_block(tag: &Tag<'_>) -> bool {
+    matches!(
+        tag,
+        Tag::Paragraph
+            | Tag::Heading { .. }
+            | Tag::BlockQuote(_)
+            | Tag::CodeBlock(_)
+            | Tag::HtmlBlock
+            | Tag::List(_)
+            | Tag::Item
+            | Tag::Table(_)
+    )
+}
+
+fn markdown_options() -> Options {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_TABLES);
     options.insert(Options::ENABLE_MATH);
-    let parser = Parser::new_ext(markdown, options);
-    let events: Vec<Event<'_>> = parser
-        .map(|event| match event {
+    options
+}
+
+/// Render Markdown to HTML. With a cloze side, the deletions are hidden or
+/// revealed: the inline deletions are delimited by markers in the text, and
+/// the block deletions are given as byte ranges of the text.
+fn render(
+    config: &MarkdownRenderConfig,
+    markdown: &str,
+    cloze: Option<ClozeSide>,
+    blocks: &[Range<usize>],
+) -> Fallible<String> {
+    let markdown: String = expand_details(config, markdown)?;
+    let parser = Parser::new_ext(&markdown, markdown_options());
+    let events: Vec<(Event<'_>, Range<usize>)> = parser
+        .into_offset_iter()
+        .map(|(event, range)| match event {
             Event::Start(Tag::Image {
                 link_type,
                 title,
@@ -55,8 +328,9 @@ pub fn markdown_to_html(config: &MarkdownRenderConfig, markdown: &str) -> Fallib
                 id,
             }) => {
                 let url = modify_url(&dest_url, config)?;
-                // Does the URL point to an audio file?
-                let ev = if is_audio_file(&url) {
+                // Does the URL point to an audio file? This is checked on the
+                // URL as written, since remote media is served under its hash.
+                let ev = if is_audio_file(&dest_url) {
                     // If so, render it as an HTML5 audio element.
                     Event::Html(CowStr::Boxed(
                         format!(
@@ -74,16 +348,398 @@ pub fn markdown_to_html(config: &MarkdownRenderConfig, markdown: &str) -> Fallib
                         id,
                     })
                 };
-                Ok(ev)
+                Ok((ev, range))
             }
-            _ => Ok(event),
+            _ => Ok((event, range)),
         })
         .collect::<Fallible<Vec<_>>>()?;
+    let events: Vec<Event<'_>> = match cloze {
+        Some(side) => {
+            let events = apply_block_cloze(events, &markdown, blocks, side, config.cloze_blank);
+            apply_cloze(events, side, config.cloze_blank)
+        }
+        None => events.into_iter().map(|(event, _)| event).collect(),
+    };
+    let events: Vec<Event<'_>> = if config.highlight_code {
+        highlight_code_blocks(events)
+    } else {
+        events
+    };
     let mut html_output: String = String::new();
     push_html(&mut html_output, events.into_iter());
     Ok(html_output)
 }
 
+/// Replace fenced code blocks tagged with a known language with their
+/// highlighted HTML. Blocks in an unknown language are marked with
+/// `data-highlight="browser"`, so highlight.js highlights them in the
+/// browser. Blocks with a cloze deletion inside, whose text is split by the
+/// deletion's markup, are left as they are.
+fn highlight_code_blocks(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut output: Vec<Event<'_>> = Vec::new();
+    let mut events = events.into_iter();
+    while let Some(event) = events.next() {
+        let Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) = &event else {
+            output.push(event);
+            continue;
+        };
+        // The language is the first word of the info string.
+        let language: String = info
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let mut block: Vec<Event<'_>> = vec![event];
+        for event in events.by_ref() {
+            let end: bool = matches!(event, Event::End(TagEnd::CodeBlock));
+            block.push(event);
+            if end {
+                break;
+            }
+        }
+        let mut code: String = String::new();
+        let mut plain: bool = true;
+        for event in block.iter().skip(1) {
+            match event {
+                Event::Text(text) => code.push_str(text),
+                Event::End(TagEnd::CodeBlock) => {}
+                _ => plain = false,
+            }
+        }
+        if !plain {
+            output.extend(block);
+            continue;
+        }
+        let html: String = match highlight(&language, &code) {
+            Some(html) => format!("<pre><code class=\"language-{language}\">{html}</code></pre>\n"),
+            None if language.is_empty() => {
+                output.extend(block);
+                continue;
+            }
+            None => {
+                let mut html: String = String::from("<pre><code class=\"language-");
+                push_escaped(&mut html, &language);
+                html.push_str("\" data-highlight=\"browser\">");
+                push_escaped(&mut html, &code);
+                html.push_str("</code></pre>\n");
+                html
+            }
+        };
+        output.push(Event::Html(html.into()));
+    }
+    output
+}
+
+/// Hide or reveal the block elements covered by the block deletions.
+fn apply_block_cloze<'a>(
+    events: Vec<(Event<'a>, Range<usize>)>,
+    markdown: &str,
+    blocks: &[Range<usize>],
+    side: ClozeSide,
+    blank: ClozeBlank,
+) -> Vec<Event<'a>> {
+    let mut output: Vec<Event<'a>> = Vec::new();
+    let mut events = events.into_iter();
+    while let Some((event, range)) = events.next() {
+        let covered: bool = matches!(&event, Event::Start(tag) if is_block(tag))
+            && blocks.iter().any(|block| covers(markdown, block, &range));
+        if !covered {
+            output.push(event);
+            continue;
+        }
+        // Take the whole element, up to its end tag.
+        let mut element: Vec<Event<'a>> = vec![event];
+        let mut depth: usize = 1;
+        while depth > 0 {
+            let Some((event, _)) = events.next() else {
+                break;
+            };
+            match event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth -= 1,
+                _ => {}
+            }
+            element.push(event);
+        }
+        output.extend(block_element(element, side, blank));
+    }
+    output
+}
+
+/// Hide or reveal a block element. Paragraphs, headings, and list items keep
+/// their tags, and only their content is hidden.
+fn block_element(
+    mut element: Vec<Event<'_>>,
+    side: ClozeSide,
+    blank: ClozeBlank,
+) -> Vec<Event<'_>> {
+    let (open, close): (&'static str, &'static str) = match element.first() {
+        Some(Event::Start(Tag::Paragraph | Tag::Heading { .. })) => {
+            (CLOZE_REVEAL_OPEN, CLOZE_REVEAL_CLOSE)
+        }
+        Some(Event::Start(Tag::Item)) => (CLOZE_BLOCK_REVEAL_OPEN, CLOZE_BLOCK_REVEAL_CLOSE),
+        _ => {
+            return match side {
+                ClozeSide::Front => vec![Event::Html(blank.block().into())],
+                ClozeSide::Back => {
+                    let mut output = vec![Event::Html(CLOZE_BLOCK_REVEAL_OPEN.into())];
+                    output.extend(element);
+                    output.push(Event::Html(CLOZE_BLOCK_REVEAL_CLOSE.into()));
+                    output
+                }
+            };
+        }
+    };
+    let end: Option<Event<'_>> = element.pop();
+    let mut inner = element.drain(1..).collect::<Vec<_>>();
+    let mut output: Vec<Event<'_>> = element;
+    match side {
+        ClozeSide::Front => {
+            let text: String = visible_text(inner.iter());
+            output.push(Event::InlineHtml(blank.inline(&text).into()))
+        }
+        ClozeSide::Back => {
+            output.push(Event::InlineHtml(open.into()));
+            output.append(&mut inner);
+            output.push(Event::InlineHtml(close.into()));
+        }
+    }
+    output.extend(end);
+    output
+}
+
+/// An event, or a cloze deletion marker.
+enum Item<'a> {
+    Event(Event<'a>),
+    Start,
+    End,
+}
+
+/// Hide or reveal the cloze deletions delimited by the marker characters.
+///
+/// Formatting tags that open and close within the deletion are hidden or
+/// revealed with it. Tags that cross the deletion's boundary, like the `**` in
+/// `**[bold** text]`, are kept, so the HTML stays balanced: the revealed
+/// deletion is split into several spans around them.
+fn apply_cloze(events: Vec<Event<'_>>, side: ClozeSide, blank: ClozeBlank) -> Vec<Event<'_>> {
+    let items: Vec<Item<'_>> = events.into_iter().flat_map(split_markers).collect();
+
+    // Whether each item is inside the deletion.
+    let mut inside: Vec<bool> = Vec::with_capacity(items.len());
+    let mut in_deletion = false;
+    for item in items.iter() {
+        match item {
+            Item::Start => in_deletion = true,
+            Item::End => in_deletion = false,
+            Item::Event(_) => {}
+        }
+        inside.push(in_deletion);
+    }
+
+    // The text of the deletion starting at each start marker.
+    let mut texts: Vec<String> = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        if let Item::Start = item {
+            let deleted = items[index + 1..].iter().map_while(|item| match item {
+                Item::Event(event) => Some(event),
+                _ => None,
+            });
+            texts.push(visible_text(deleted));
+        }
+    }
+    let mut texts = texts.into_iter();
+
+    // Find the tags that cross the deletion's boundary, i.e. those where only
+    // one of the start and end tags is inside the deletion.
+    let mut crossing: Vec<bool> = vec![false; items.len()];
+    let mut open: Vec<usize> = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        match item {
+            Item::Event(Event::Start(_)) => open.push(index),
+            Item::Event(Event::End(_)) => {
+                if let Some(start) = open.pop() {
+                    if inside[start] != inside[index] {
+                        crossing[start] = true;
+                        crossing[index] = true;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut output: Vec<Event<'_>> = Vec::new();
+    let mut span_open = false;
+    for (index, item) in items.into_iter().enumerate() {
+        match (item, side) {
+            (Item::Start, ClozeSide::Front) => {
+                let text: String = texts.next().unwrap_or_default();
+                output.push(Event::InlineHtml(blank.inline(&text).into()))
+            }
+            (Item::Start, ClozeSide::Back) => {}
+            (Item::End, _) => {
+                if span_open {
+                    output.push(Event::InlineHtml(CLOZE_REVEAL_CLOSE.into()));
+                    span_open = false;
+                }
+            }
+            (Item::Event(event), _) if !inside[index] => output.push(event),
+            (Item::Event(event), _) if crossing[index] => {
+                if span_open {
+                    output.push(Event::InlineHtml(CLOZE_REVEAL_CLOSE.into()));
+                    span_open = false;
+                }
+                output.push(event);
+            }
+            (Item::Event(_), ClozeSide::Front) => {}
+            (Item::Event(event), ClozeSide::Back) => {
+                if !span_open {
+                    output.push(Event::InlineHtml(CLOZE_REVEAL_OPEN.into()));
+                    span_open = true;
+                }
+                output.push(event);
+            }
+        }
+    }
+    output
+}
+
+/// The text shown by a sequence of events, ignoring markup. The alt text of
+/// images is not shown.
+fn visible_text<'a, 'b: 'a>(events: impl Iterator<Item = &'a Event<'b>>) -> String {
+    let mut text: String = String::new();
+    let mut images: usize = 0;
+    for event in events {
+        match event {
+            Event::Start(Tag::Image { .. }) => images += 1,
+            Event::End(TagEnd::Image) => images = images.saturating_sub(1),
+            Event::Text(shown)
+            | Event::Code(shown)
+            | Event::InlineMath(shown)
+            | Event::DisplayMath(shown)
+                if images == 0 =>
+            {
+                text.push_str(shown)
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Split the text of an event at the cloze deletion markers.
+fn split_markers(event: Event<'_>) -> Vec<Item<'_>> {
+    let rebuild: fn(CowStr<'static>) -> Event<'static> = match &event {
+        Event::Text(_) => Event::Text,
+        Event::Code(_) => Event::Code,
+        Event::InlineMath(_) => Event::InlineMath,
+        Event::DisplayMath(_) => Event::DisplayMath,
+        Event::Html(_) => Event::Html,
+        Event::InlineHtml(_) => Event::InlineHtml,
+        _ => return vec![Item::Event(event)],
+    };
+    let text: &str = match &event {
+        Event::Text(text)
+        | Event::Code(text)
+        | Event::InlineMath(text)
+        | Event::DisplayMath(text)
+        | Event::Html(text)
+        | Event::InlineHtml(text) => text,
+        _ => return vec![Item::Event(event)],
+    };
+    if !text.contains([CLOZE_START, CLOZE_END]) {
+        return vec![Item::Event(event)];
+    }
+    let mut items: Vec<Item<'_>> = Vec::new();
+    let mut piece = String::new();
+    for c in text.chars() {
+        if c == CLOZE_START || c == CLOZE_END {
+            if !piece.is_empty() {
+                items.push(Item::Event(rebuild(CowStr::from(take(&mut piece)))));
+            }
+            items.push(if c == CLOZE_START {
+                Item::Start
+            } else {
+                Item::End
+            });
+        } else {
+            piece.push(c);
+        }
+    }
+    if !piece.is_empty() {
+        items.push(Item::Event(rebuild(CowStr::from(piece))));
+    }
+    items
+}
+
+/// Expand collapsible sections into `<details>` elements. A section starts
+/// with a `>! Summary` line, and its body is the `>`-prefixed lines that
+/// follow:
+///
+/// ```markdown
+/// >! Proof
+/// > By induction on `n`.
+/// ```
+///
+/// The body is left as Markdown between the HTML tags, so it is rendered
+/// normally, and may contain nested sections.
+fn expand_details(config: &MarkdownRenderConfig, markdown: &str) -> Fallible<String> {
+    if !markdown
+        .lines()
+        .any(|line| line.starts_with(DETAILS_PREFIX))
+    {
+        return Ok(markdown.to_string());
+    }
+    let mut output = String::new();
+    let mut lines = markdown.lines().peekable();
+    while let Some(line) = lines.next() {
+        match line.strip_prefix(DETAILS_PREFIX) {
+            Some(summary) => {
+                let mut body: Vec<&str> = Vec::new();
+                while let Some(next) = lines.peek() {
+                    if next.starts_with(DETAILS_PREFIX) {
+                        break;
+                    }
+                    match next.strip_prefix('>') {
+                        Some(next) => {
+                            body.push(next.strip_prefix(' ').unwrap_or(next));
+                            lines.next();
+                        }
+                        None => break,
+                    }
+                }
+                let summary = markdown_to_html_inline(config, summary.trim())?;
+                let body = expand_details(config, &body.join("\n"))?;
+                output.push_str(&format!(
+                    "\n<details>\n<summary>{summary}</summary>\n\n{body}\n\n</details>\n\n"
+                ));
+            }
+            None => {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// The text of the Markdown without its markup, as it reads once rendered:
+/// `**Paris**` is `Paris`. Blocks and line breaks are separated by spaces,
+/// and math is kept as written.
+pub fn markdown_to_plain_text(markdown: &str) -> String {
+    let mut text: String = String::new();
+    for event in Parser::new_ext(markdown, markdown_options()) {
+        match event {
+            Event::Text(t) | Event::Code(t) | Event::InlineMath(t) | Event::DisplayMath(t) => {
+                text.push_str(&t)
+            }
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
 pub fn markdown_to_html_inline(config: &MarkdownRenderConfig, markdown: &str) -> Fallible<String> {
     let text = markdown_to_html(config, markdown)?;
     if text.starts_with("<p>") && text.ends_with("</p>\n") {
@@ -94,8 +750,14 @@ pub fn markdown_to_html_inline(config: &MarkdownRenderConfig, markdown: &str) ->
     }
 }
 
+/// Resolve a media path to the URL it is served at: the app's `/file/`
+/// endpoint, or the media base URL if one is configured. Remote media is
+/// served by the app at `/remote/`, if enabled.
 fn modify_url(url: &str, config: &MarkdownRenderConfig) -> Fallible<String> {
     let port = config.port;
+    if let Some(key) = config.resolver.resolve_remote(url) {
+        return Ok(format!("http://localhost:{port}/remote/{key}"));
+    }
     let path: String = config
         .resolver
         .resolve(url)
@@ -104,7 +766,10 @@ fn modify_url(url: &str, config: &MarkdownRenderConfig) -> Fallible<String> {
         })?
         .display()
         .to_string();
-    Ok(format!("http://localhost:{port}/file/{path}"))
+    match config.resolver.base_url() {
+        Some(base_url) => Ok(format!("{base_url}/{path}")),
+        None => Ok(format!("http://localhost:{port}/file/{path}")),
+    }
 }
 
 #[cfg(test)]
@@ -115,6 +780,10 @@ mod tests {
     use crate::helper::create_tmp_directory;
     use crate::media::resolve::MediaResolverBuilder;
 
+    const CLOZE_HIDDEN: &str = "<span class='cloze'>.............</span>";
+
+    const CLOZE_BLOCK_HIDDEN: &str = "<div class='cloze cloze-block'>.............</div>";
+
     fn make_test_config() -> Fallible<MarkdownRenderConfig> {
         let coll_path: PathBuf = create_tmp_directory()?;
         let abs_deck_path: PathBuf = coll_path.join("deck.md");
@@ -127,6 +796,9 @@ mod tests {
                 .with_deck_path(PathBuf::from("deck.md"))?
                 .build()?,
             port: 1234,
+            dim_cloze_context: false,
+            highlight_code: true,
+            cloze_blank: ClozeBlank::default(),
         };
         Ok(config)
     }
@@ -143,6 +815,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_media_base_url() -> Fallible<()> {
+        let coll_path: PathBuf = create_tmp_directory()?;
+        std::fs::create_dir(coll_path.join("notes"))?;
+        std::fs::write(coll_path.join("notes/image.png"), "")?;
+        let config = MarkdownRenderConfig {
+            resolver: MediaResolverBuilder::new()
+                .with_collection_path(coll_path)?
+                .with_deck_path(PathBuf::from("notes/deck.md"))?
+                .with_base_url(Some("https://cdn.example.com/media/".to_string()))?
+                .build()?,
+            port: 1234,
+            dim_cloze_context: false,
+            highlight_code: true,
+            cloze_blank: ClozeBlank::default(),
+        };
+        let html = markdown_to_html(&config, "![alt](image.png)")?;
+        assert_eq!(
+            html,
+            "<p><img src=\"https://cdn.example.com/media/notes/image.png\" alt=\"alt\" /></p>\n"
+        );
+        assert!(
+            MediaResolverBuilder::new()
+                .with_base_url(Some(" ".to_string()))
+                .is_err()
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_markdown_to_html_inline() -> Fallible<()> {
         let markdown = "This is **bold** text.";
@@ -160,4 +861,283 @@ mod tests {
         assert_eq!(html, "<h1>Foo</h1>\n");
         Ok(())
     }
+
+    /// Math is emitted as spans with its LaTeX source, which KaTeX renders in
+    /// the browser.
+    #[test]
+    fn test_math() -> Fallible<()> {
+        let config = make_test_config()?;
+        let html = markdown_to_html(&config, "Inline $x < y^2$ math.")?;
+        assert_eq!(
+            html,
+            "<p>Inline <span class=\"math math-inline\">x &lt; y^2</span> math.</p>\n"
+        );
+        let html = markdown_to_html(&config, "$$\nn = \\frac{N}{N_A}\n$$")?;
+        assert_eq!(
+            html,
+            "<p><span class=\"math math-display\">\nn = \\frac{N}{N_A}\n</span></p>\n"
+        );
+        let html = markdown_to_html_inline(&config, "Euler: $e^{i\\pi} = -1$")?;
+        assert_eq!(
+            html,
+            "Euler: <span class=\"math math-inline\">e^{i\\pi} = -1</span>"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_highlight_code_block() -> Fallible<()> {
+        let mut config = make_test_config()?;
+        let markdown = "```rust\nlet x = 1;\n```";
+        let html = markdown_to_html(&config, markdown)?;
+        assert_eq!(
+            html,
+            "<pre><code class=\"language-rust\"><span class=\"tok-keyword\">let</span> x = <span class=\"tok-number\">1</span>;\n</code></pre>\n"
+        );
+        // Unknown languages are left to highlight.js, untagged blocks are
+        // plain text.
+        let html = markdown_to_html(&config, "```klingon\nif x < 1 {}\n```")?;
+        assert_eq!(
+            html,
+            "<pre><code class=\"language-klingon\" data-highlight=\"browser\">if x &lt; 1 {}\n</code></pre>\n"
+        );
+        let html = markdown_to_html(&config, "```\nlet x = 1;\n```")?;
+        assert_eq!(html, "<pre><code>let x = 1;\n</code></pre>\n");
+        // Highlighting can be turned off.
+        config.highlight_code = false;
+        let html = markdown_to_html(&config, markdown)?;
+        assert_eq!(
+            html,
+            "<pre><code class=\"language-rust\">let x = 1;\n</code></pre>\n"
+        );
+        Ok(())
+    }
+
+    /// A code block with a deletion inside is not highlighted, so the
+    /// deletion's markup stays intact.
+    #[test]
+    fn test_cloze_in_highlighted_code() -> Fallible<()> {
+        let config = make_test_config()?;
+        let text = "```rust\nlet x = 1;\n```";
+        let start = text.find('1').unwrap();
+        let back = cloze_to_html(&config, text, start, start, ClozeSide::Back)?;
+        assert_eq!(
+            back,
+            "<pre><code class=\"language-rust\">let x = <span class='cloze-reveal'>1</span>;\n</code></pre>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_markdown_to_plain_text() {
+        assert_eq!(markdown_to_plain_text("**Paris**"), "Paris");
+        assert_eq!(markdown_to_plain_text("*le* `chat`"), "le chat");
+        assert_eq!(markdown_to_plain_text("[Bern](https://bern.ch)"), "Bern");
+        assert_eq!(markdown_to_plain_text("a\nb\n\n- c"), "a b c");
+        assert_eq!(markdown_to_plain_text("$x^2$"), "x^2");
+        assert_eq!(markdown_to_plain_text("1 \\* 2"), "1 * 2");
+    }
+
+    /// Escaped dollar signs are literal.
+    #[test]
+    fn test_escaped_dollar() -> Fallible<()> {
+        let config = make_test_config()?;
+        let html = markdown_to_html(&config, "Costs \\$5, or \\$6.")?;
+        assert_eq!(html, "<p>Costs $5, or $6.</p>\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_details() -> Fallible<()> {
+        let markdown = "Answer.\n>! More **detail**\n> First.\n>\n> Second.\nAfter.";
+        let config = make_test_config()?;
+        let html = markdown_to_html(&config, markdown)?;
+        assert_eq!(
+            html,
+            "<p>Answer.</p>\n<details>\n<summary>More <strong>detail</strong></summary>\n<p>First.</p>\n<p>Second.</p>\n</details>\n<p>After.</p>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_details() -> Fallible<()> {
+        let markdown = ">! Outer\n> Outer body.\n> >! Inner\n> > Inner body.";
+        let config = make_test_config()?;
+        let html = markdown_to_html(&config, markdown)?;
+        assert_eq!(
+            html,
+            "<details>\n<summary>Outer</summary>\n<p>Outer body.</p>\n<details>\n<summary>Inner</summary>\n<p>Inner body.</p>\n</details>\n</details>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_blockquote_is_not_details() -> Fallible<()> {
+        let markdown = "> Quote.";
+        let config = make_test_config()?;
+        let html = markdown_to_html(&config, markdown)?;
+        assert_eq!(html, "<blockquote>\n<p>Quote.</p>\n</blockquote>\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_group() -> Fallible<()> {
+        let config = make_test_config()?;
+        let text = "Paris is in France.";
+        let deletions = [(12, 17), (0, 4)];
+        let front = cloze_group_to_html(&config, text, &deletions, ClozeSide::Front)?;
+        assert_eq!(
+            front,
+            format!("<p>{CLOZE_HIDDEN} is in {CLOZE_HIDDEN}.</p>\n")
+        );
+        let back = cloze_group_to_html(&config, text, &deletions, ClozeSide::Back)?;
+        assert_eq!(
+            back,
+            "<p><span class='cloze-reveal'>Paris</span> is in <span class='cloze-reveal'>France</span>.</p>\n"
+        );
+        // Overlapping deletions are an error.
+        assert!(cloze_group_to_html(&config, text, &[(0, 4), (3, 6)], ClozeSide::Front).is_err());
+        Ok(())
+    }
+
+    /// A deletion can hide a whole formula.
+    #[test]
+    fn test_cloze_math() -> Fallible<()> {
+        let config = make_test_config()?;
+        let text = "Euler: $e^{i\\pi} = -1$.";
+        let deletions = [(7, text.len() - 2)];
+        let front = cloze_group_to_html(&config, text, &deletions, ClozeSide::Front)?;
+        assert_eq!(front, format!("<p>Euler: {CLOZE_HIDDEN}.</p>\n"));
+        let back = cloze_group_to_html(&config, text, &deletions, ClozeSide::Back)?;
+        assert_eq!(
+            back,
+            "<p>Euler: <span class='cloze-reveal'><span class=\"math math-inline\">e^{i\\pi} = -1</span></span>.</p>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_list_item() -> Fallible<()> {
+        let config = make_test_config()?;
+        let text = "Steps:\n\n- Mix\n- Bake\n";
+        let start = text.find("- Bake").unwrap();
+        let deletions = [(start, start + "- Bake".len() - 1)];
+        let front = cloze_group_to_html(&config, text, &deletions, ClozeSide::Front)?;
+        assert_eq!(
+            front,
+            format!("<p>Steps:</p>\n<ul>\n<li>Mix</li>\n<li>{CLOZE_HIDDEN}</li>\n</ul>\n")
+        );
+        let back = cloze_group_to_html(&config, text, &deletions, ClozeSide::Back)?;
+        assert_eq!(
+            back,
+            "<p>Steps:</p>\n<ul>\n<li>Mix</li>\n<li><div class='cloze-reveal'>Bake</div></li>\n</ul>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_image() -> Fallible<()> {
+        let config = make_test_config()?;
+        let text = "The logo:\n\n![logo](@/image.png)";
+        let start = text.find('!').unwrap();
+        let deletions = [(start, text.len() - 1)];
+        let front = cloze_group_to_html(&config, text, &deletions, ClozeSide::Front)?;
+        assert_eq!(front, format!("<p>The logo:</p>\n<p>{CLOZE_HIDDEN}</p>\n"));
+        let back = cloze_group_to_html(&config, text, &deletions, ClozeSide::Back)?;
+        assert_eq!(
+            back,
+            "<p>The logo:</p>\n<p><span class='cloze-reveal'><img src=\"http://localhost:1234/file/image.png\" alt=\"logo\" /></span></p>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_whole_list() -> Fallible<()> {
+        let config = make_test_config()?;
+        let text = "- Mix\n- Bake";
+        let deletions = [(0, text.len() - 1)];
+        let front = cloze_group_to_html(&config, text, &deletions, ClozeSide::Front)?;
+        assert_eq!(front, CLOZE_BLOCK_HIDDEN);
+        let back = cloze_group_to_html(&config, text, &deletions, ClozeSide::Back)?;
+        assert_eq!(
+            back,
+            "<div class='cloze-reveal'>\n<ul>\n<li>Mix</li>\n<li>Bake</li>\n</ul>\n</div>"
+        );
+        // An inline deletion on the same card is still inline.
+        let text = "Paris\n\n- Mix\n- Bake";
+        let deletions = [(0, 4), (7, text.len() - 1)];
+        let front = cloze_group_to_html(&config, text, &deletions, ClozeSide::Front)?;
+        assert_eq!(
+            front,
+            format!("<p>{CLOZE_HIDDEN}</p>\n{CLOZE_BLOCK_HIDDEN}")
+        );
+        Ok(())
+    }
+
+    /// Render the front of a cloze card with the given blank, where the
+    /// deletion is between the first `[` and the last `]`.
+    fn render_blank(blank: ClozeBlank, source: &str) -> Fallible<String> {
+        let config = MarkdownRenderConfig {
+            cloze_blank: blank,
+            ..make_test_config()?
+        };
+        let start: usize = source.find('[').unwrap_or_default();
+        let end: usize = source.rfind(']').unwrap_or_default() - 2;
+        let close: usize = end + 2;
+        let text: String = format!(
+            "{}{}{}",
+            &source[..start],
+            &source[start + 1..close],
+            &source[close + 1..]
+        );
+        cloze_to_html(&config, &text, start, end, ClozeSide::Front)
+    }
+
+    #[test]
+    fn test_cloze_blank() -> Fallible<()> {
+        let front = render_blank(ClozeBlank::Fixed(3), "A [long answer].")?;
+        assert_eq!(front, "<p>A <span class='cloze'>...</span>.</p>\n");
+        // Characters are counted, not bytes, and markup is ignored.
+        let front = render_blank(ClozeBlank::Proportional, "Un [café] au lait.")?;
+        assert_eq!(
+            front,
+            "<p>Un <span class='cloze'>....</span> au lait.</p>\n"
+        );
+        let front = render_blank(ClozeBlank::Proportional, "The [**bold** `x`].")?;
+        assert_eq!(front, "<p>The <span class='cloze'>......</span>.</p>\n");
+        let front = render_blank(ClozeBlank::Hint, "The capital is [Paris].")?;
+        assert_eq!(
+            front,
+            "<p>The capital is <span class='cloze'>P....</span>.</p>\n"
+        );
+        // The hint is escaped.
+        let front = render_blank(ClozeBlank::Hint, "Less than: [`<`].")?;
+        assert_eq!(
+            front,
+            "<p>Less than: <span class='cloze'>&lt;</span>.</p>\n"
+        );
+        // A deletion on a line of its own is a block deletion, and is sized
+        // the same way.
+        let front = render_blank(ClozeBlank::Proportional, "[Paris]")?;
+        assert_eq!(front, "<p><span class='cloze'>.....</span></p>\n");
+        // A deletion without text gets the default blank.
+        let front = render_blank(ClozeBlank::Hint, "Logo: [![logo](@/image.png)].")?;
+        assert_eq!(front, format!("<p>Logo: {CLOZE_HIDDEN}.</p>\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_cloze_blank() {
+        for blank in [
+            ClozeBlank::default(),
+            ClozeBlank::Fixed(5),
+            ClozeBlank::Proportional,
+            ClozeBlank::Hint,
+        ] {
+            assert_eq!(blank.to_string().parse::<ClozeBlank>().ok(), Some(blank));
+        }
+        assert_eq!(ClozeBlank::default().to_string(), "fixed");
+        assert!("0".parse::<ClozeBlank>().is_err());
+        assert!("wide".parse::<ClozeBlank>().is_err());
+    }
 }
@@ -13,5 +13,6 @@
 // limitations under the License.
 
 pub mod load;
+pub mod remote;
 pub mod resolve;
 pub mod validate;
@@ -0,0 +1,325 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Media referenced by remote URLs. It is downloaded the first time it is
+//! shown, and served from a cache in the collection, so the browser never
+//! fetches third-party hosts directly.
+
+use std::collections::HashMap;
+use std::fs::read_dir;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::Client;
+use reqwest::header::CONTENT_TYPE;
+
+use crate::error::ErrorReport;
+use crate::error::Fallible;
+use crate::error::fail;
+
+/// The collection-relative directory where remote media is cached.
+pub const REMOTE_CACHE_DIR: &str = ".remote-media";
+
+/// How long a download can take, from connecting to reading the last byte.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The largest remote media file that is downloaded, in bytes.
+const MAX_MEDIA_SIZE: usize = 32 * 1024 * 1024;
+
+/// Shown in place of remote media that could not be downloaded.
+pub const BROKEN_IMAGE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64" viewBox="0 0 64 64"><rect x="4" y="4" width="56" height="56" rx="6" fill="none" stroke="#999" stroke-width="4"/><path d="M20 20L44 44M44 20L20 44" stroke="#999" stroke-width="4"/></svg>"##;
+
+pub fn is_remote_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// The HTTP client remote media is downloaded with. Downloads time out, so a
+/// stalled host doesn't hold up a card or a fetch forever.
+pub fn media_client() -> Fallible<Client> {
+    Client::builder()
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()
+        .map_err(http_error)
+}
+
+fn http_error(e: reqwest::Error) -> ErrorReport {
+    ErrorReport::new(e.to_string())
+}
+
+/// The key remote media is cached under: the hash of its URL.
+pub fn remote_key(url: &str) -> String {
+    blake3::hash(url.as_bytes()).to_hex().to_string()
+}
+
+/// The cache of remote media. Cards register the URLs they reference as they
+/// are rendered, and only those are downloaded.
+pub struct RemoteCache {
+    /// Absolute path to the cache directory.
+    directory: PathBuf,
+    /// How long a downloaded file is used before it is downloaded again. If
+    /// `None`, it is used forever.
+    expiry: Option<Duration>,
+    /// The URLs referenced by cards, by key.
+    urls: Mutex<HashMap<String, String>>,
+    client: Client,
+}
+
+impl RemoteCache {
+    pub fn new(directory: PathBuf, expiry: Option<Duration>) -> Fallible<Self> {
+        Ok(Self {
+            directory,
+            expiry,
+            urls: Mutex::new(HashMap::new()),
+            client: media_client()?,
+        })
+    }
+
+    /// Register a URL referenced by a card, and return its key.
+    pub fn register(&self, url: &str) -> String {
+        let key: String = remote_key(url);
+        let mut urls = self.urls.lock().unwrap();
+        urls.entry(key.clone()).or_insert_with(|| url.to_string());
+        key
+    }
+
+    /// The path to the cached file for a key. The file is downloaded if it is
+    /// not cached, or if it has expired. If downloading an expired file fails,
+    /// the expired copy is used.
+    pub async fn fetch(&self, key: &str) -> Fallible<PathBuf> {
+        if key.len() != 64 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+            return fail(format!("Invalid remote media key '{key}'."));
+        }
+        let cached: Option<PathBuf> = self.cached(key)?;
+        if let Some(path) = &cached {
+            if !self.is_expired(path) {
+                return Ok(path.clone());
+            }
+        }
+        let url: Option<String> = self.urls.lock().unwrap().get(key).cloned();
+        let Some(url) = url else {
+            return match cached {
+                Some(path) => Ok(path),
+                None => fail(format!("No card references remote media '{key}'.")),
+            };
+        };
+        match download(&self.client, &url).await {
+            Ok((bytes, content_type)) => {
+                if let Some(path) = &cached {
+                    tokio::fs::remove_file(path).await?;
+                }
+                let path: PathBuf = match media_extension(&url, content_type.as_deref()) {
+                    Some(ext) => self.directory.join(format!("{key}.{ext}")),
+                    None => self.directory.join(key),
+                };
+                tokio::fs::create_dir_all(&self.directory).await?;
+                tokio::fs::write(&path, bytes).await?;
+                log::debug!("Downloaded {url}");
+                Ok(path)
+            }
+            Err(e) => match cached {
+                Some(path) => {
+                    log::warn!("Failed to download {url}, using the cached copy: {e}");
+                    Ok(path)
+                }
+                None => fail(format!("Failed to download {url}: {e}")),
+            },
+        }
+    }
+
+    /// The cached file for a key, named by the key and the file's extension.
+    fn cached(&self, key: &str) -> Fallible<Option<PathBuf>> {
+        if !self.directory.exists() {
+            return Ok(None);
+        }
+        for entry in read_dir(&self.directory)? {
+            let path: PathBuf = entry?.path();
+            if path.file_stem().is_some_and(|stem| stem == key) {
+                return Ok(Some(path));
+            }
+        }
+        Ok(None)
+    }
+
+    fn is_expired(&self, path: &Path) -> bool {
+        let Some(expiry) = self.expiry else {
+            return false;
+        };
+        let age: Option<Duration> = path
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok());
+        age.is_none_or(|age| age >= expiry)
+    }
+}
+
+/// Download a file, returning its contents and content type. Files larger
+/// than [`MAX_MEDIA_SIZE`] are refused.
+pub async fn download(client: &Client, url: &str) -> Fallible<(Vec<u8>, Option<String>)> {
+    download_at_most(client, url, MAX_MEDIA_SIZE).await
+}
+
+async fn download_at_most(
+    client: &Client,
+    url: &str,
+    limit: usize,
+) -> Fallible<(Vec<u8>, Option<String>)> {
+    let too_large = || format!("the file is larger than {limit} bytes.");
+    let mut response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(http_error)?;
+    if response
+        .content_length()
+        .is_some_and(|length| length > limit as u64)
+    {
+        return fail(too_large());
+    }
+    let content_type: Option<String> = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    // The length may be missing or wrong, so the body is read in chunks.
+    let mut bytes: Vec<u8> = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(http_error)? {
+        if bytes.len() + chunk.len() > limit {
+            return fail(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok((bytes, content_type))
+}
+
+/// Guess the file extension of a remote file, first from the URL, and then
+/// from the content type.
+pub fn media_extension(url: &str, content_type: Option<&str>) -> Option<String> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    if let Some((_, ext)) = file_name.rsplit_once('.') {
+        if !ext.is_empty() && ext.len() <= 5 && ext.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Some(ext.to_ascii_lowercase());
+        }
+    }
+    let ext = match content_type?.split(';').next()?.trim() {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        "audio/mpeg" => "mp3",
+        "audio/wav" => "wav",
+        "audio/ogg" => "ogg",
+        _ => return None,
+    };
+    Some(ext.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use axum::Router;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use portpicker::pick_unused_port;
+    use tempfile::tempdir;
+    use tokio::net::TcpListener;
+    use tokio::spawn;
+
+    use super::*;
+    use crate::utils::wait_for_server;
+
+    #[test]
+    fn test_media_extension() {
+        assert_eq!(
+            media_extension("https://example.com/a.PNG?size=large", None),
+            Some("png".to_string())
+        );
+        assert_eq!(
+            media_extension(
+                "https://example.com/image",
+                Some("image/jpeg; charset=binary")
+            ),
+            Some("jpg".to_string())
+        );
+        assert_eq!(media_extension("https://example.com/image", None), None);
+    }
+
+    #[tokio::test]
+    async fn test_remote_cache() -> Fallible<()> {
+        let host = "127.0.0.1";
+        let port = pick_unused_port().unwrap();
+        let downloads = Arc::new(AtomicUsize::new(0));
+        let counter = downloads.clone();
+        let app = Router::new()
+            .route(
+                "/image",
+                get(move || async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    ([(CONTENT_TYPE, "image/png")], b"image".to_vec())
+                }),
+            )
+            .route("/missing.png", get(|| async { StatusCode::NOT_FOUND }));
+        let listener = TcpListener::bind(format!("{host}:{port}")).await?;
+        spawn(async move { axum::serve(listener, app).await });
+        wait_for_server(host, port).await?;
+
+        // The file is downloaded once, and named by the content type.
+        let dir = tempdir()?;
+        let cache = RemoteCache::new(dir.path().join(REMOTE_CACHE_DIR), None)?;
+        let key = cache.register(&format!("http://{host}:{port}/image"));
+        let path = cache.fetch(&key).await?;
+        assert_eq!(
+            path,
+            dir.path().join(REMOTE_CACHE_DIR).join(format!("{key}.png"))
+        );
+        assert_eq!(std::fs::read(&path)?, b"image");
+        cache.fetch(&key).await?;
+        assert_eq!(downloads.load(Ordering::SeqCst), 1);
+
+        // A cached file is used without registering its URL again.
+        let restarted = RemoteCache::new(dir.path().join(REMOTE_CACHE_DIR), None)?;
+        assert_eq!(restarted.fetch(&key).await?, path);
+
+        // Expired files are downloaded again.
+        let expiring = RemoteCache::new(dir.path().join(REMOTE_CACHE_DIR), Some(Duration::ZERO))?;
+        expiring.register(&format!("http://{host}:{port}/image"));
+        assert_eq!(expiring.fetch(&key).await?, path);
+        assert_eq!(downloads.load(Ordering::SeqCst), 2);
+
+        let missing = cache.register(&format!("http://{host}:{port}/missing.png"));
+        assert!(cache.fetch(&missing).await.is_err());
+        assert!(
+            cache
+                .fetch(&remote_key("https://example.com/a.png"))
+                .await
+                .is_err()
+        );
+        assert!(cache.fetch("../hashcards.db").await.is_err());
+
+        // Files over the size limit are refused.
+        let client = media_client()?;
+        let url = format!("http://{host}:{port}/image");
+        assert!(download_at_most(&client, &url, 5).await.is_ok());
+        assert!(download_at_most(&client, &url, 4).await.is_err());
+        Ok(())
+    }
+}
@@ -15,9 +15,12 @@
 use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use crate::error::ErrorReport;
 use crate::error::Fallible;
+use crate::media::remote::RemoteCache;
+use crate::media::remote::is_remote_url;
 
 /// The media resolver takes media paths as entered in the Markdown text of the
 /// flashcards, and resolves them to collection-relative paths.
@@ -27,12 +30,20 @@ pub struct MediaResolver {
     /// Collection-relative path to the deck. The resolver must only be used
     /// with flashcards parsed from this deck.
     deck_path: PathBuf,
+    /// The URL that collection-relative media paths are appended to, if media
+    /// is not served by the app itself.
+    base_url: Option<String>,
+    /// The cache remote media is served from. If `None`, remote URLs are
+    /// rejected.
+    remote_cache: Option<Arc<RemoteCache>>,
 }
 
 /// Builder to construct a [`MediaResolver`].
 pub struct MediaResolverBuilder {
     collection_path: Option<PathBuf>,
     deck_path: Option<PathBuf>,
+    base_url: Option<String>,
+    remote_cache: Option<Arc<RemoteCache>>,
 }
 
 /// Errors that can occur when resolving a file path.
@@ -67,6 +78,23 @@ impl std::fmt::Display for ResolveError {
 }
 
 impl MediaResolver {
+    /// The base URL of the media server, without a trailing slash, if media
+    /// is served separately from the app.
+    pub fn base_url(&self) -> Option<&str> {
+        self.base_url.as_deref()
+    }
+
+    /// Resolve a remote URL to the key it is cached under, registering it
+    /// for download. Returns `None` for local paths, or if there is no remote
+    /// cache.
+    pub fn resolve_remote(&self, url: &str) -> Option<String> {
+        let url: &str = url.trim();
+        match &self.remote_cache {
+            Some(cache) if is_remote_url(url) => Some(cache.register(url)),
+            _ => None,
+        }
+    }
+
     /// Resolve a path string to a collection-relative file path.
     ///
     /// If the path string starts with `@/`, it will be resolved relative to
@@ -147,6 +175,8 @@ impl MediaResolverBuilder {
         Self {
             collection_path: None,
             deck_path: None,
+            base_url: None,
+            remote_cache: None,
         }
     }
 
@@ -164,7 +194,7 @@ impl MediaResolverBuilder {
         }
         Ok(Self {
             collection_path: Some(collection_path),
-            deck_path: self.deck_path,
+            ..self
         })
     }
 
@@ -174,11 +204,36 @@ impl MediaResolverBuilder {
             return Err(ErrorReport::new("Deck path is not relative."));
         }
         Ok(Self {
-            collection_path: self.collection_path,
             deck_path: Some(deck_path),
+            ..self
         })
     }
 
+    /// Set a value for `base_url`: a URL (e.g. `https://cdn.example.com/media`)
+    /// or a path prefix (e.g. `/media`) to serve media from. If unset, media
+    /// is served by the app at `/file/`.
+    pub fn with_base_url(self, base_url: Option<String>) -> Fallible<Self> {
+        let base_url: Option<String> = match base_url {
+            Some(base_url) => {
+                let base_url: &str = base_url.trim().trim_end_matches('/');
+                if base_url.is_empty() {
+                    return Err(ErrorReport::new("Media base URL is empty."));
+                }
+                Some(base_url.to_string())
+            }
+            None => None,
+        };
+        Ok(Self { base_url, ..self })
+    }
+
+    /// Set a value for `remote_cache`, to serve remote media from.
+    pub fn with_remote_cache(self, remote_cache: Option<Arc<RemoteCache>>) -> Self {
+        Self {
+            remote_cache,
+            ..self
+        }
+    }
+
     /// Consume the builder and return a [`MediaResolver`].
     pub fn build(self) -> Fallible<MediaResolver> {
         let collection_path = self
@@ -190,6 +245,8 @@ impl MediaResolverBuilder {
         Ok(MediaResolver {
             collection_path,
             deck_path,
+            base_url: self.base_url,
+            remote_cache: self.remote_cache,
         })
     }
 }
@@ -199,6 +256,7 @@ mod tests {
     use super::*;
     use crate::error::Fallible;
     use crate::helper::create_tmp_directory;
+    use crate::media::remote::remote_key;
 
     /// Empty strings are rejected.
     #[test]
@@ -241,6 +299,24 @@ mod tests {
         Ok(())
     }
 
+    /// Remote URLs are registered with the remote cache, if there is one.
+    #[test]
+    fn test_resolve_remote() -> Fallible<()> {
+        let coll_path: PathBuf = create_tmp_directory()?;
+        let builder = || -> Fallible<MediaResolverBuilder> {
+            MediaResolverBuilder::new()
+                .with_collection_path(coll_path.clone())?
+                .with_deck_path(PathBuf::from("deck.md"))
+        };
+        let url = "https://example.com/a.png";
+        assert_eq!(builder()?.build()?.resolve_remote(url), None);
+        let cache = Arc::new(RemoteCache::new(coll_path.join("cache"), None)?);
+        let r: MediaResolver = builder()?.with_remote_cache(Some(cache)).build()?;
+        assert_eq!(r.resolve_remote(&format!(" {url} ")), Some(remote_key(url)));
+        assert_eq!(r.resolve_remote("a.png"), None);
+        Ok(())
+    }
+
     /// Test collection-relative paths.
     #[test]
     fn test_collection_relative() -> Fallible<()> {
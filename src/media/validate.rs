@@ -12,7 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fs::read_to_string;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -22,8 +24,10 @@ use pulldown_cmark::Tag;
 
 use crate::error::ErrorReport;
 use crate::error::Fallible;
+use crate::media::remote::is_remote_url;
 use crate::media::resolve::MediaResolver;
 use crate::media::resolve::MediaResolverBuilder;
+use crate::parser::frontmatter_lines;
 use crate::types::card::Card;
 use crate::types::card::CardContent;
 
@@ -49,6 +53,30 @@ fn extract_media_paths(markdown: &str) -> Vec<String> {
     paths
 }
 
+/// Extract the paths of all media files referenced in a card.
+pub fn card_media_paths(card: &Card) -> Vec<String> {
+    // TODO: perhaps this should be lifted to a method of the `CardContent`
+    // enum.
+    let mut markdown_texts = match card.content() {
+        CardContent::Basic { question, answer }
+        | CardContent::TypeIn {
+            prompt: question,
+            answer,
+        }
+        | CardContent::Reversible {
+            question, answer, ..
+        } => vec![question.as_str(), answer.as_str()],
+        CardContent::Cloze { text, .. } | CardContent::GroupedCloze { text, .. } => {
+            vec![text.as_str()]
+        }
+    };
+    markdown_texts.extend(card.extra());
+    markdown_texts
+        .into_iter()
+        .flat_map(extract_media_paths)
+        .collect()
+}
+
 /// Validate that all media files referenced in cards exist.
 pub fn validate_media_files(cards: &[Card], base_dir: &Path) -> Fallible<()> {
     let base_dir = base_dir.to_path_buf();
@@ -60,27 +88,20 @@ pub fn validate_media_files(cards: &[Card], base_dir: &Path) -> Fallible<()> {
             .with_deck_path(card.relative_file_path(&base_dir)?)?
             .build()?;
 
-        // Extract markdown content from the card.
-        //
-        // TODO: perhaps this should be lifted to a method of the `CardContent`
-        // enum.
-        let markdown_texts = match card.content() {
-            CardContent::Basic { question, answer } => vec![question.as_str(), answer.as_str()],
-            CardContent::Cloze { text, .. } => vec![text.as_str()],
-        };
-
-        for markdown in markdown_texts {
-            for path in extract_media_paths(markdown) {
-                // Try to resolve the path using MediaResolver.
-                match resolver.resolve(&path) {
-                    Ok(_) => {}
-                    Err(_) => {
-                        missing.insert(MissingMedia {
-                            file_path: path,
-                            card_file: card.file_path().clone(),
-                            card_lines: card.range(),
-                        });
-                    }
+        // Remote media is downloaded when it is shown.
+        for path in card_media_paths(card)
+            .into_iter()
+            .filter(|path| !is_remote_url(path.trim()))
+        {
+            // Try to resolve the path using MediaResolver.
+            match resolver.resolve(&path) {
+                Ok(_) => {}
+                Err(_) => {
+                    missing.insert(MissingMedia {
+                        file_path: path,
+                        card_file: card.file_path().clone(),
+                        card_lines: card.range(),
+                    });
                 }
             }
         }
@@ -91,14 +112,23 @@ pub fn validate_media_files(cards: &[Card], base_dir: &Path) -> Fallible<()> {
         let mut missing: Vec<MissingMedia> = missing.into_iter().collect();
         missing.sort();
 
-        // Build error message.
+        // Build error message. Card lines are counted from the end of the
+        // frontmatter, and shown counting from the start of the file, from
+        // one.
+        let mut offsets: HashMap<PathBuf, usize> = HashMap::new();
         let mut msg = String::from("Missing media files referenced in cards:\n");
         for m in missing {
+            let offset: usize = *offsets.entry(m.card_file.clone()).or_insert_with(|| {
+                read_to_string(&m.card_file)
+                    .ok()
+                    .and_then(|text| frontmatter_lines(&text).ok())
+                    .unwrap_or(0)
+            });
             msg.push_str(&format!(
                 "  - {} (referenced in {}:{})\n",
                 m.file_path,
                 m.card_file.display(),
-                m.card_lines.0
+                offset + m.card_lines.0 + 1
             ));
         }
 
@@ -113,8 +143,13 @@ mod tests {
     use std::env::temp_dir;
     use std::fs::create_dir_all;
 
+    use tempfile::tempdir;
+
     use super::*;
+    use crate::ignore::IgnoreRules;
+    use crate::parser::DEFAULT_DECK_SEPARATOR;
     use crate::parser::Parser as CardParser;
+    use crate::parser::parse_deck;
 
     #[test]
     fn test_extract_media_paths() {
@@ -177,6 +212,28 @@ mod tests {
         Ok(())
     }
 
+    /// Missing media is reported at the line of its card in the file,
+    /// counting the frontmatter.
+    #[test]
+    fn test_validate_media_files_line_numbers() -> Fallible<()> {
+        let dir = tempdir()?;
+        let card_file = dir.path().join("deck.md");
+        let text = "---\nname = \"Deck\"\n---\n\nQ: Q1\nA: A1\n\nQ: ![](missing.jpg)\nA: A2\n";
+        std::fs::write(&card_file, text)?;
+        let cards = parse_deck(
+            &dir.path().to_path_buf(),
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )?;
+        let err = validate_media_files(&cards, dir.path())
+            .err()
+            .map(|e| e.to_string());
+        let location = format!("{}:8)", card_file.display());
+        assert!(err.is_some_and(|e| e.contains(&location)));
+        Ok(())
+    }
+
     #[test]
     fn test_validate_media_files_with_existing_files() -> Fallible<()> {
         // Create a temporary directory for the test.
@@ -0,0 +1,444 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A cache of the cards parsed from each deck file, so that files that have
+//! not changed since the collection was last loaded are not parsed again.
+//!
+//! The cache is a JSON file in the collection directory. It is only ever an
+//! optimization: if it is missing, stale, or corrupt, it is ignored, and
+//! rebuilt from a fresh parse.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::read_to_string;
+use std::fs::rename;
+use std::fs::write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::types::aliases::DeckName;
+use crate::types::aliases::Tag;
+use crate::types::card::Card;
+use crate::types::card::CardContent;
+
+/// The name of the cache file, in the collection directory.
+pub const CACHE_FILE_NAME: &str = ".hashcards-cache.json";
+
+/// Whether to use the parse cache when loading a collection.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CacheMode {
+    /// Reuse the cards of unchanged files, and update the cache.
+    Enabled,
+    /// Parse every file, and leave the cache alone.
+    Disabled,
+}
+
+/// What the cards of a file were parsed with, besides the file itself. If any
+/// of it changes, every entry is stale.
+#[derive(Serialize, Deserialize, PartialEq)]
+struct Settings {
+    /// The version of hashcards that wrote the cache, since the parser may
+    /// change between versions.
+    version: String,
+    card_regex: Option<String>,
+    separator: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    settings: Settings,
+    /// Entries by the absolute path of their file.
+    files: HashMap<PathBuf, FileEntry>,
+}
+
+/// The size and modification time of a file, which tell cheaply whether it
+/// may have changed.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+pub struct FileStamp {
+    size: u64,
+    /// Seconds and nanoseconds since the Unix epoch.
+    modified: (u64, u32),
+}
+
+impl FileStamp {
+    /// The stamp of a file, or `None` if the file system can't tell when it
+    /// was modified, in which case the file is not cached.
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = path.metadata().ok()?;
+        let modified: SystemTime = metadata.modified().ok()?;
+        let modified = modified.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            size: metadata.len(),
+            modified: (modified.as_secs(), modified.subsec_nanos()),
+        })
+    }
+}
+
+/// The cards parsed from a file, and what the file was like when they were.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    stamp: FileStamp,
+    /// The hex BLAKE3 hash of the file's text.
+    content_hash: String,
+    cards: Vec<CachedCard>,
+}
+
+impl FileEntry {
+    pub fn new(stamp: FileStamp, text: &str, cards: &[Card]) -> Self {
+        Self {
+            stamp,
+            content_hash: content_hash(text),
+            cards: cards.iter().map(CachedCard::from).collect(),
+        }
+    }
+}
+
+/// A card without its path, which is the key of its entry, or its hash, which
+/// is computed from its content when it is loaded.
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedCard {
+    deck_name: DeckName,
+    range: (usize, usize),
+    content: CardContent,
+    tags: Vec<Tag>,
+    deck_tags: Vec<Tag>,
+    extra: Option<String>,
+    from_regex: bool,
+}
+
+impl From<&Card> for CachedCard {
+    fn from(card: &Card) -> Self {
+        Self {
+            deck_name: card.deck_name().clone(),
+            range: card.range(),
+            content: card.content().clone(),
+            tags: card.tags().to_vec(),
+            deck_tags: card.deck_tags().to_vec(),
+            extra: card.extra().map(str::to_string),
+            from_regex: card.is_from_regex(),
+        }
+    }
+}
+
+impl CachedCard {
+    fn to_card(&self, path: &Path) -> Card {
+        let card = Card::new(
+            self.deck_name.clone(),
+            path.to_path_buf(),
+            self.range,
+            self.content.clone(),
+            self.tags.clone(),
+        )
+        .with_deck_tags(&self.deck_tags)
+        .with_extra(self.extra.clone());
+        if self.from_regex {
+            card.with_regex_origin()
+        } else {
+            card
+        }
+    }
+}
+
+pub struct ParseCache {
+    path: PathBuf,
+    settings: Settings,
+    files: HashMap<PathBuf, FileEntry>,
+    /// Whether the entries differ from the cache file.
+    changed: bool,
+}
+
+impl ParseCache {
+    /// Load the cache of the collection in the directory. A cache that can't
+    /// be read, or that was written with other settings, is treated as empty.
+    pub fn load(directory: &Path, card_regex: Option<&str>, separator: &str) -> Self {
+        let path: PathBuf = directory.join(CACHE_FILE_NAME);
+        let settings = Settings {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            card_regex: card_regex.map(str::to_string),
+            separator: separator.to_string(),
+        };
+        let cached: Option<CacheFile> = read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok());
+        match cached {
+            Some(cached) if cached.settings == settings => Self {
+                path,
+                settings,
+                files: cached.files,
+                changed: false,
+            },
+            Some(_) => {
+                log::debug!("Ignoring a stale parse cache.");
+                Self::empty(path, settings)
+            }
+            None => {
+                if path.exists() {
+                    log::debug!("Ignoring a corrupt parse cache.");
+                }
+                Self::empty(path, settings)
+            }
+        }
+    }
+
+    fn empty(path: PathBuf, settings: Settings) -> Self {
+        Self {
+            path,
+            settings,
+            files: HashMap::new(),
+            changed: true,
+        }
+    }
+
+    /// The cached cards of a file, if its size and modification time are
+    /// unchanged, and so is its text.
+    pub fn get(&self, path: &Path, stamp: FileStamp, text: &str) -> Option<Vec<Card>> {
+        let entry: &FileEntry = self.files.get(path)?;
+        if entry.stamp != stamp || entry.content_hash != content_hash(text) {
+            return None;
+        }
+        Some(entry.cards.iter().map(|card| card.to_card(path)).collect())
+    }
+
+    /// Cache the cards of a file.
+    pub fn insert(&mut self, path: PathBuf, entry: FileEntry) {
+        self.files.insert(path, entry);
+        self.changed = true;
+    }
+
+    /// Drop the entries of files other than the given ones, e.g. because they
+    /// were deleted.
+    pub fn retain(&mut self, paths: &[PathBuf]) {
+        let paths: HashSet<&PathBuf> = paths.iter().collect();
+        let before: usize = self.files.len();
+        self.files.retain(|path, _| paths.contains(path));
+        if self.files.len() != before {
+            self.changed = true;
+        }
+    }
+
+    /// Write the cache, if it changed. Failing to write it only means the
+    /// next load is slower, so errors are logged and otherwise ignored.
+    pub fn save(self) {
+        if !self.changed {
+            return;
+        }
+        let cached = CacheFile {
+            settings: self.settings,
+            files: self.files,
+        };
+        let text: String = match serde_json::to_string(&cached) {
+            Ok(text) => text,
+            Err(e) => {
+                log::debug!("Failed to serialize the parse cache: {e}");
+                return;
+            }
+        };
+        // Write to a temporary file first, so that a load never reads a
+        // partly written cache.
+        let tmp: PathBuf = self.path.with_extension("json.tmp");
+        if let Err(e) = write(&tmp, text).and_then(|_| rename(&tmp, &self.path)) {
+            log::debug!("Failed to write the parse cache: {e}");
+        }
+    }
+}
+
+fn content_hash(text: &str) -> String {
+    blake3::hash(text.as_bytes()).to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::create_dir_all;
+
+    use super::*;
+    use crate::error::Fallible;
+    use crate::helper::create_tmp_directory;
+    use crate::ignore::IgnoreRules;
+    use crate::parser::parse_deck;
+    use crate::parser::parse_deck_cached;
+    use crate::types::card_hash::CardHash;
+
+    type Summary = (
+        CardHash,
+        DeckName,
+        PathBuf,
+        (usize, usize),
+        Vec<Tag>,
+        Vec<Tag>,
+        Option<String>,
+    );
+
+    fn summarize(cards: &[Card]) -> Vec<Summary> {
+        cards
+            .iter()
+            .map(|card| {
+                (
+                    card.hash(),
+                    card.deck_name().clone(),
+                    card.file_path().clone(),
+                    card.range(),
+                    card.tags().to_vec(),
+                    card.deck_tags().to_vec(),
+                    card.extra().map(str::to_string),
+                )
+            })
+            .collect()
+    }
+
+    fn make_directory() -> Fallible<PathBuf> {
+        let directory = create_tmp_directory()?;
+        create_dir_all(directory.join("Geography"))?;
+        write(
+            directory.join("Basics.md"),
+            "Q: What is 2 + 2?\nA: 4\nE: Count on your fingers.\n#math\n\nT: Capital of Italy?\nA: Rome\n",
+        )?;
+        write(
+            directory.join("Geography/Europe.md"),
+            "---\nname = \"Europe\"\ntags = [\"geo\"]\n---\n\nC: [Paris] is the capital of [France].\n\nC: [c1::Madrid] is in [c1::Spain], [c2::Lisbon] is not.\n",
+        )?;
+        Ok(directory)
+    }
+
+    fn load(directory: &Path) -> ParseCache {
+        ParseCache::load(directory, None, "::")
+    }
+
+    fn parse_cached(directory: &Path) -> Fallible<Vec<Card>> {
+        let mut cache = load(directory);
+        let cards = parse_deck_cached(
+            &directory.to_path_buf(),
+            None,
+            &IgnoreRules::default(),
+            "::",
+            &mut cache,
+        )?;
+        cache.save();
+        Ok(cards)
+    }
+
+    fn parse_fresh(directory: &Path) -> Fallible<Vec<Card>> {
+        parse_deck(
+            &directory.to_path_buf(),
+            None,
+            &IgnoreRules::default(),
+            "::",
+        )
+    }
+
+    /// Whether every deck file's cards would be taken from the cache.
+    fn all_cached(directory: &Path) -> Fallible<bool> {
+        let cache = load(directory);
+        for path in ["Basics.md", "Geography/Europe.md"] {
+            let path = directory.join(path);
+            let Some(stamp) = FileStamp::of(&path) else {
+                return Ok(false);
+            };
+            if cache.get(&path, stamp, &read_to_string(&path)?).is_none() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Cards loaded from the cache are the same as freshly parsed ones.
+    #[test]
+    fn test_cached_cards_match_fresh_parse() -> Fallible<()> {
+        let directory = make_directory()?;
+        let fresh = summarize(&parse_fresh(&directory)?);
+        assert_eq!(fresh.len(), 6);
+        assert!(!all_cached(&directory)?);
+        assert_eq!(summarize(&parse_cached(&directory)?), fresh);
+        assert!(directory.join(CACHE_FILE_NAME).exists());
+        assert!(all_cached(&directory)?);
+        assert_eq!(summarize(&parse_cached(&directory)?), fresh);
+        Ok(())
+    }
+
+    /// A file whose text changed is parsed again.
+    #[test]
+    fn test_changed_file() -> Fallible<()> {
+        let directory = make_directory()?;
+        parse_cached(&directory)?;
+        write(
+            directory.join("Basics.md"),
+            "Q: What is 2 + 3?\nA: 5\nE: Count on your fingers.\n#math\n\nT: Capital of Italy?\nA: Rome\n",
+        )?;
+        assert!(!all_cached(&directory)?);
+        let cards = parse_cached(&directory)?;
+        assert_eq!(summarize(&cards), summarize(&parse_fresh(&directory)?));
+        assert!(
+            cards
+                .iter()
+                .any(|card| card.content().to_source_text().contains("2 + 3"))
+        );
+        assert!(all_cached(&directory)?);
+        Ok(())
+    }
+
+    /// An entry whose stamp matches but whose text doesn't is not used.
+    #[test]
+    fn test_content_hash_mismatch() -> Fallible<()> {
+        let directory = make_directory()?;
+        parse_cached(&directory)?;
+        let path = directory.join("Basics.md");
+        let cache = load(&directory);
+        let stamp = FileStamp::of(&path).unwrap();
+        assert!(cache.get(&path, stamp, &read_to_string(&path)?).is_some());
+        assert!(cache.get(&path, stamp, "Q: Other\nA: Text\n").is_none());
+        Ok(())
+    }
+
+    /// A deleted file's cards are not loaded, and its entry is dropped.
+    #[test]
+    fn test_deleted_file() -> Fallible<()> {
+        let directory = make_directory()?;
+        parse_cached(&directory)?;
+        std::fs::remove_file(directory.join("Basics.md"))?;
+        let cards = parse_cached(&directory)?;
+        assert_eq!(cards.len(), 4);
+        assert_eq!(load(&directory).files.len(), 1);
+        Ok(())
+    }
+
+    /// A corrupt cache is ignored, and replaced.
+    #[test]
+    fn test_corrupt_cache() -> Fallible<()> {
+        let directory = make_directory()?;
+        let fresh = summarize(&parse_fresh(&directory)?);
+        write(directory.join(CACHE_FILE_NAME), "{\"settings\": [1, 2")?;
+        assert_eq!(summarize(&parse_cached(&directory)?), fresh);
+        assert!(all_cached(&directory)?);
+        Ok(())
+    }
+
+    /// A cache written with other settings is ignored.
+    #[test]
+    fn test_stale_settings() -> Fallible<()> {
+        let directory = make_directory()?;
+        parse_cached(&directory)?;
+        assert!(!load(&directory).files.is_empty());
+        assert!(ParseCache::load(&directory, None, "/").files.is_empty());
+        assert!(
+            ParseCache::load(&directory, Some("(?P<q>.+)::(?P<a>.+)"), "::")
+                .files
+                .is_empty()
+        );
+        Ok(())
+    }
+}
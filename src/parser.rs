@@ -14,24 +14,49 @@
 
 use std::collections::HashSet;
 use std::error::Error;
+use std::ffi::OsStr;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fs::read_to_string;
+use std::num::NonZeroUsize;
+use std::path::Component;
+use std::path::Path;
 use std::path::PathBuf;
+use std::thread::ScopedJoinHandle;
+use std::thread::available_parallelism;
+use std::thread::scope;
 
+use chrono::NaiveDate;
+use regex::Regex;
 use serde::Deserialize;
+use toml::Value;
 use walkdir::WalkDir;
 
+use crate::config::CardRegex;
 use crate::error::ErrorReport;
 use crate::error::Fallible;
+use crate::error::fail;
+use crate::ignore::IgnoreRules;
+use crate::parse_cache::FileEntry;
+use crate::parse_cache::FileStamp;
+use crate::parse_cache::ParseCache;
 use crate::types::aliases::DeckName;
+use crate::types::aliases::Tag;
 use crate::types::card::Card;
 use crate::types::card::CardContent;
+use crate::types::card_hash::CardHash;
 
 /// Metadata that can be specified at the top of a deck file.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct DeckMetadata {
     name: Option<String>,
+    /// When the deck was written, as a TOML date, e.g. `2025-03-01`, or a
+    /// string in that format. It is only read by [`deck_created`], so an
+    /// invalid date doesn't stop the deck from loading.
+    created: Option<Value>,
+    /// Tags added to every card in the deck, without the `#`.
+    #[serde(default)]
+    tags: Vec<Tag>,
 }
 
 /// Extract TOML frontmatter from markdown text.
@@ -45,7 +70,7 @@ fn extract_frontmatter(text: &str) -> Fallible<(DeckMetadata, &str)> {
     // Check if the file starts with frontmatter delimiter
     match lines.peek() {
         Some((_, line)) if line.trim() == "---" => {}
-        _ => return Ok((DeckMetadata { name: None }, text)),
+        _ => return Ok((DeckMetadata::default(), text)),
     };
     lines.next(); // consume the opening delimiter
 
@@ -94,39 +119,318 @@ fn extract_frontmatter(text: &str) -> Fallible<(DeckMetadata, &str)> {
     Ok((metadata, content))
 }
 
-/// Parses all Markdown files in the given directory.
-pub fn parse_deck(directory: &PathBuf) -> Fallible<Vec<Card>> {
-    let mut all_cards = Vec::new();
-    for entry in WalkDir::new(directory) {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file() && path.extension().is_some_and(|ext| ext == "md") {
-            let text = read_to_string(path)?;
+/// The number of lines of frontmatter at the start of a deck file. Card line
+/// numbers are counted from the end of the frontmatter.
+pub fn frontmatter_lines(text: &str) -> Fallible<usize> {
+    let (_, content) = extract_frontmatter(text)?;
+    Ok(text[..text.len() - content.len()].lines().count())
+}
+
+/// The `created` date in a deck file's frontmatter, if any.
+pub fn deck_created(text: &str) -> Fallible<Option<NaiveDate>> {
+    let (metadata, _) = extract_frontmatter(text)?;
+    let Some(created) = metadata.created else {
+        return Ok(None);
+    };
+    let date: Option<NaiveDate> = match &created {
+        Value::Datetime(datetime) => datetime
+            .date
+            .and_then(|d| NaiveDate::from_ymd_opt(d.year as i32, d.month as u32, d.day as u32)),
+        Value::String(string) => NaiveDate::parse_from_str(string, "%Y-%m-%d").ok(),
+        _ => None,
+    };
+    match date {
+        Some(date) => Ok(Some(date)),
+        None => fail(format!("invalid created date in frontmatter: {created}.")),
+    }
+}
 
-            // Extract frontmatter and get custom deck name if specified
-            let (metadata, content) = extract_frontmatter(&text)?;
+/// The named groups of a card regex that match the question and the answer.
+pub const QUESTION_GROUP: &str = "q";
+pub const ANSWER_GROUP: &str = "a";
+
+/// The separator between the components of deck names derived from nested
+/// directories, unless the collection sets another.
+pub const DEFAULT_DECK_SEPARATOR: &str = "::";
+
+/// The name of the deck in the file at `path`: its path under the collection
+/// root, without the extension, with the components joined by the separator,
+/// e.g. `languages::spanish::verbs`. A deck at the root has a single-component
+/// name. A name set in the frontmatter is used as is.
+fn deck_name(directory: &Path, path: &Path, name: Option<String>, separator: &str) -> DeckName {
+    if let Some(name) = name {
+        return name;
+    }
+    let relative: &Path = path.strip_prefix(directory).unwrap_or(path);
+    let mut components: Vec<String> = relative
+        .parent()
+        .into_iter()
+        .flat_map(|parent| parent.components())
+        .filter_map(|component| match component {
+            Component::Normal(name) => name.to_str().map(str::to_string),
+            _ => None,
+        })
+        .collect();
+    components.push(
+        path.file_stem()
+            .and_then(|os_str| os_str.to_str())
+            .unwrap_or("None")
+            .to_string(),
+    );
+    components.join(separator)
+}
 
-            let deck_name: DeckName = metadata.name.unwrap_or_else(|| {
-                path.file_stem()
-                    .and_then(|os_str| os_str.to_str())
-                    .unwrap_or("None")
-                    .to_string()
-            });
+/// Parses all Markdown files in the given directory. If a card regex is given,
+/// the basic cards it matches are extracted from each file as well. Deck names
+/// of nested files are joined with the separator. Files are parsed in
+/// parallel, but the result doesn't depend on the order they are parsed in.
+pub fn parse_deck(
+    directory: &PathBuf,
+    card_regex: Option<&CardRegex>,
+    ignore: &IgnoreRules,
+    separator: &str,
+) -> Fallible<Vec<Card>> {
+    let paths: Vec<PathBuf> = deck_files(directory, ignore, card_regex)?;
+    let files: Vec<Vec<Card>> = parse_files(&paths, |path| {
+        let text: String = read_file(path)?;
+        parse_text(directory, path, &text, card_regex, separator)
+    })?;
+    Ok(finish_deck(files.into_iter().flatten().collect()))
+}
 
-            let parser = Parser::new(deck_name, path.to_path_buf());
-            let cards = parser.parse(content)?;
-            all_cards.extend(cards);
+/// Like `parse_deck`, but the cards of files that haven't changed since they
+/// were cached are taken from the cache rather than parsed again. The cache
+/// is updated with the files that were parsed.
+pub fn parse_deck_cached(
+    directory: &PathBuf,
+    card_regex: Option<&CardRegex>,
+    ignore: &IgnoreRules,
+    separator: &str,
+    cache: &mut ParseCache,
+) -> Fallible<Vec<Card>> {
+    let paths: Vec<PathBuf> = deck_files(directory, ignore, card_regex)?;
+    let cached: &ParseCache = cache;
+    let files: Vec<(Vec<Card>, Option<FileEntry>)> = parse_files(&paths, |path| {
+        // The stamp is taken before the file is read, so that if the file
+        // changes in between, the entry is stale rather than wrong.
+        let stamp: Option<FileStamp> = FileStamp::of(path);
+        let text: String = read_file(path)?;
+        if let Some(stamp) = stamp {
+            if let Some(cards) = cached.get(path, stamp, &text) {
+                return Ok((cards, None));
+            }
+        }
+        let cards: Vec<Card> = parse_text(directory, path, &text, card_regex, separator)?;
+        let entry: Option<FileEntry> = stamp.map(|stamp| FileEntry::new(stamp, &text, &cards));
+        Ok((cards, entry))
+    })?;
+    cache.retain(&paths);
+    let mut all_cards: Vec<Card> = Vec::new();
+    for (path, (cards, entry)) in paths.into_iter().zip(files) {
+        if let Some(entry) = entry {
+            cache.insert(path, entry);
         }
+        all_cards.extend(cards);
     }
+    Ok(finish_deck(all_cards))
+}
+
+/// Drop duplicate cards, and sort the rest.
+fn finish_deck(mut all_cards: Vec<Card>) -> Vec<Card> {
+    // Of duplicate cards, the first by path, then by line range, is kept.
+    all_cards.sort_by(|a, b| {
+        a.file_path()
+            .cmp(b.file_path())
+            .then_with(|| a.range().cmp(&b.range()))
+    });
+    let mut seen: HashSet<CardHash> = HashSet::new();
+    all_cards.retain(|card| seen.insert(card.hash()));
 
     // Cards are sorted by their hash to make subsequent code more
     // deterministic.
     all_cards.sort_by_key(|c| c.hash());
 
-    // Remove duplicates.
-    all_cards.dedup_by_key(|c| c.hash());
+    all_cards
+}
+
+/// The Markdown files in the directory that are not ignored, and those with
+/// the extensions of the card regex, if any, sorted by path.
+pub fn deck_files(
+    directory: &PathBuf,
+    ignore: &IgnoreRules,
+    card_regex: Option<&CardRegex>,
+) -> Fallible<Vec<PathBuf>> {
+    let extensions: &[String] = card_regex.map_or(&[], |card_regex| &card_regex.extensions);
+    let entries = WalkDir::new(directory)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            // Skipping an ignored directory skips everything in it.
+            match entry.path().strip_prefix(directory) {
+                Ok(path) => !ignore.is_ignored(path, entry.file_type().is_dir()),
+                Err(_) => true,
+            }
+        });
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let scanned = |ext: &OsStr| ext == "md" || extensions.iter().any(|e| ext == e.as_str());
+        if path.is_file() && path.extension().is_some_and(scanned) {
+            paths.push(path.to_path_buf());
+        }
+    }
+    Ok(paths)
+}
+
+/// Parse the files on as many threads as there are cores, each with a share
+/// of the files, in order. The results are returned in the order of the
+/// files. If several files fail to parse, the error of the first one is
+/// returned.
+fn parse_files<T, F>(paths: &[PathBuf], parse: F) -> Fallible<Vec<T>>
+where
+    T: Send,
+    F: Fn(&Path) -> Fallible<T> + Sync,
+{
+    let threads: usize = available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size: usize = paths.len().div_ceil(threads).max(1);
+    let parse = &parse;
+    let results: Vec<Fallible<Vec<T>>> = scope(|scope| {
+        let handles: Vec<ScopedJoinHandle<'_, Fallible<Vec<T>>>> = paths
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut results: Vec<T> = Vec::new();
+                    for path in chunk {
+                        results.push(parse(path)?);
+                    }
+                    Ok(results)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| match handle.join() {
+                Ok(result) => result,
+                Err(_) => fail("A thread parsing the deck files panicked."),
+            })
+            .collect()
+    });
+    let mut all_results: Vec<T> = Vec::new();
+    for result in results {
+        all_results.extend(result?);
+    }
+    Ok(all_results)
+}
+
+fn in_file(e: ErrorReport, path: &Path) -> ErrorReport {
+    ErrorReport::new(format!("{} Location: {}", e.message(), path.display()))
+}
+
+/// Read a deck file. Errors give the path of the file.
+fn read_file(path: &Path) -> Fallible<String> {
+    read_to_string(path).map_err(|e| in_file(e.into(), path))
+}
+
+/// Parse the text of a deck file. Errors give the path of the file.
+pub fn parse_text(
+    directory: &Path,
+    path: &Path,
+    text: &str,
+    card_regex: Option<&CardRegex>,
+    separator: &str,
+) -> Fallible<Vec<Card>> {
+    // Files other than Markdown files are notes: their cards are only those
+    // matched by the card regex.
+    if !is_deck_file(path) {
+        let Some(card_regex) = card_regex else {
+            return Ok(Vec::new());
+        };
+        let deck_name: DeckName = deck_name(directory, path, None, separator);
+        return Ok(extract_regex_cards(
+            &card_regex.regex,
+            &deck_name,
+            path,
+            text,
+        ));
+    }
+
+    // Extract frontmatter and get custom deck name if specified
+    let (metadata, content) = extract_frontmatter(text).map_err(|e| in_file(e, path))?;
+
+    for tag in &metadata.tags {
+        if !is_tag(&format!("#{tag}")) {
+            return fail(format!(
+                "Invalid tag '{tag}' in the frontmatter of {}.",
+                path.display()
+            ));
+        }
+    }
+    let deck_name: DeckName = deck_name(directory, path, metadata.name, separator);
+
+    let mut cards: Vec<Card> = Vec::new();
+    if let Some(card_regex) = card_regex {
+        cards.extend(extract_regex_cards(
+            &card_regex.regex,
+            &deck_name,
+            path,
+            content,
+        ));
+    }
 
-    Ok(all_cards)
+    let parser = Parser::new(deck_name, path.to_path_buf());
+    cards.extend(parser.parse(content)?);
+    Ok(cards
+        .into_iter()
+        .map(|card| card.with_deck_tags(&metadata.tags))
+        .collect())
+}
+
+/// Extract basic cards from the text with a regular expression, whose named
+/// groups `q` and `a` capture the question and the answer. A card's lines are
+/// those spanned by its match. Matches where either group is empty are
+/// skipped.
+fn extract_regex_cards(
+    card_regex: &Regex,
+    deck_name: &DeckName,
+    file_path: &Path,
+    text: &str,
+) -> Vec<Card> {
+    let line_of = |position: usize| text[..position].matches('\n').count();
+    let mut cards = Vec::new();
+    for captures in card_regex.captures_iter(text) {
+        let question = captures.name(QUESTION_GROUP).map(|m| m.as_str().trim());
+        let answer = captures.name(ANSWER_GROUP).map(|m| m.as_str().trim());
+        let (Some(question), Some(answer)) = (question, answer) else {
+            continue;
+        };
+        if question.is_empty() || answer.is_empty() {
+            continue;
+        }
+        let Some(whole) = captures.get(0) else {
+            continue;
+        };
+        // A trailing newline belongs to the last line of the match.
+        let end = whole.as_str().trim_end_matches('\n').len() + whole.start();
+        let range = (line_of(whole.start()), line_of(end.max(whole.start())));
+        cards.push(
+            Card::new(
+                deck_name.clone(),
+                file_path.to_path_buf(),
+                range,
+                CardContent::new_basic(question, answer),
+                Vec::new(),
+            )
+            .with_regex_origin(),
+        );
+    }
+    cards
+}
+
+/// Whether the file is a deck, in the card format, rather than notes that
+/// cards are only extracted from with the card regex.
+fn is_deck_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "md")
 }
 
 pub struct Parser {
@@ -165,30 +469,63 @@ impl Display for ParserError {
 
 impl Error for ParserError {}
 
+/// The kind of card a question starts.
+#[derive(Clone, Copy, PartialEq)]
+enum QuestionKind {
+    /// A basic card (Q:).
+    Basic,
+    /// A type-in card (T:).
+    TypeIn,
+    /// A reversible card (R:), studied in both directions.
+    Reversible,
+}
+
 enum State {
     /// Initial state.
     Initial,
-    /// Reading a question (Q:)
-    ReadingQuestion { question: String, start_line: usize },
-    /// Reading an answer (A:)
+    /// Reading a question (Q:), the prompt of a type-in card (T:), or the
+    /// question of a reversible card (R:).
+    ReadingQuestion {
+        question: String,
+        kind: QuestionKind,
+        tags: Vec<Tag>,
+        start_line: usize,
+    },
+    /// Reading an answer (A:), and then its extra notes (E:), if any.
     ReadingAnswer {
         question: String,
         answer: String,
+        kind: QuestionKind,
+        extra: Option<String>,
+        tags: Vec<Tag>,
+        start_line: usize,
+    },
+    /// Reading a cloze card (C:), and then its extra notes (E:), if any.
+    ReadingCloze {
+        text: String,
+        extra: Option<String>,
+        tags: Vec<Tag>,
         start_line: usize,
     },
-    /// Reading a cloze card (C:)
-    ReadingCloze { text: String, start_line: usize },
 }
 
 enum Line {
     /// A line like `Q: <text>`.
     StartQuestion(String),
+    /// A line like `T: <text>`.
+    StartTypeIn(String),
+    /// A line like `R: <text>`.
+    StartReversible(String),
     /// A line like `A: <text>`.
     StartAnswer(String),
     /// A line like `C: <text>`.
     StartCloze(String),
+    /// A line like `E: <text>`.
+    StartExtra(String),
     /// A line that's just `---` (flashcard separator).
     Separator,
+    /// A line that only contains tags, like `#foo #bar`.
+    Tags(Vec<Tag>),
     /// Any other line.
     Text(String),
 }
@@ -197,22 +534,74 @@ impl Line {
     fn read(line: &str) -> Self {
         if is_question(line) {
             Line::StartQuestion(trim(line))
+        } else if is_type_in(line) {
+            Line::StartTypeIn(trim(line))
+        } else if is_reversible(line) {
+            Line::StartReversible(trim(line))
         } else if is_answer(line) {
             Line::StartAnswer(trim(line))
         } else if is_cloze(line) {
             Line::StartCloze(trim(line))
+        } else if is_extra(line) {
+            Line::StartExtra(trim(line))
         } else if is_separator(line) {
             Line::Separator
+        } else if is_tags(line) {
+            Line::Tags(read_tags(line))
         } else {
             Line::Text(line.to_string())
         }
     }
 }
 
+/// The position of the first and last characters of a cloze deletion, given
+/// the positions of its brackets in the text without them, leaving out any
+/// whitespace at the edges: `[Paris ]` deletes `Paris`, not the space. `None`
+/// if the deletion is blank.
+fn trim_deletion(text: &str, start: usize, end: usize) -> Option<(usize, usize)> {
+    let deletion = text.get(start..end)?;
+    let trimmed = deletion.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let start = start + deletion.len() - deletion.trim_start().len();
+    Some((start, start + trimmed.len() - 1))
+}
+
+/// The number at the start of a numbered cloze deletion, like the `c1::` in
+/// `[c1::Paris]`, and the length of the prefix in bytes.
+fn deletion_number(text: &str) -> Option<(usize, usize)> {
+    let rest: &str = text.strip_prefix('c')?;
+    let digits: usize = rest.bytes().take_while(u8::is_ascii_digit).count();
+    if digits == 0 || !rest[digits..].starts_with("::") {
+        return None;
+    }
+    let number: usize = rest[..digits].parse().ok()?;
+    Some((number, digits + 3))
+}
+
+/// Whether the line ends the card before it: it starts a new card, or is a
+/// separator.
+pub fn is_card_boundary(line: &str) -> bool {
+    is_question(line)
+        || is_type_in(line)
+        || is_reversible(line)
+        || is_cloze(line)
+        || is_separator(line)
+}
+
 fn is_question(line: &str) -> bool {
     line.starts_with("Q:")
 }
 
+fn is_type_in(line: &str) -> bool {
+    line.starts_with("T:")
+}
+
+fn is_reversible(line: &str) -> bool {
+    line.starts_with("R:")
+}
+
 fn is_answer(line: &str) -> bool {
     line.starts_with("A:")
 }
@@ -221,10 +610,107 @@ fn is_cloze(line: &str) -> bool {
     line.starts_with("C:")
 }
 
+fn is_extra(line: &str) -> bool {
+    line.starts_with("E:")
+}
+
 fn is_separator(line: &str) -> bool {
     line.trim() == "---"
 }
 
+/// The opening of a fenced code block: its character, `` ` `` or `~`, and
+/// how many of them there are.
+#[derive(Clone, Copy)]
+struct Fence {
+    marker: char,
+    length: usize,
+}
+
+/// The fence a line opens or closes, if any: a run of at least three
+/// backticks or tildes, indented by at most three spaces. Returns the fence
+/// and the text after it.
+fn read_fence(line: &str) -> Option<(Fence, &str)> {
+    let rest: &str = line.trim_start_matches(' ');
+    if line.len() - rest.len() > 3 {
+        return None;
+    }
+    let marker: char = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let length: usize = rest.chars().take_while(|c| *c == marker).count();
+    if length < 3 {
+        return None;
+    }
+    Some((Fence { marker, length }, &rest[length..]))
+}
+
+/// The fence the lines after `line` are in, given the fence `line` is in.
+/// The text of a line that starts a card, an answer, or extra notes starts
+/// after the marker.
+fn next_fence(fence: Option<Fence>, line: &str) -> Option<Fence> {
+    let text: &str = if is_card_boundary(line) || is_answer(line) || is_extra(line) {
+        line.get(2..).unwrap_or_default().trim_start()
+    } else {
+        line
+    };
+    match (fence, read_fence(text)) {
+        // The info string of a backtick fence can't contain backticks.
+        (None, Some((open, info))) if !(open.marker == '`' && info.contains('`')) => Some(open),
+        (Some(open), Some((close, rest)))
+            if close.marker == open.marker
+                && close.length >= open.length
+                && rest.trim().is_empty() =>
+        {
+            None
+        }
+        (fence, _) => fence,
+    }
+}
+
+/// A tag line is a non-empty line where every word is a tag.
+fn is_tags(line: &str) -> bool {
+    let mut words = line.split_whitespace().peekable();
+    words.peek().is_some() && words.all(is_tag)
+}
+
+/// A tag is a `#` followed by a letter, followed by letters, digits, or any of
+/// `-`, `_`, `/`.
+pub fn is_tag(word: &str) -> bool {
+    match word.strip_prefix('#') {
+        Some(name) => {
+            name.starts_with(char::is_alphabetic)
+                && name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '/')
+        }
+        None => false,
+    }
+}
+
+fn read_tags(line: &str) -> Vec<Tag> {
+    line.split_whitespace()
+        .map(|word| word[1..].to_string())
+        .collect()
+}
+
+fn add_tags(mut tags: Vec<Tag>, new_tags: Vec<Tag>) -> Vec<Tag> {
+    for tag in new_tags {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// The contents of the cards read from a question, a type-in prompt, or the
+/// question of a reversible card, and its answer. A reversible card gives a
+/// card for each direction.
+fn question_contents(question: String, answer: String, kind: QuestionKind) -> Vec<CardContent> {
+    match kind {
+        QuestionKind::Basic => vec![CardContent::new_basic(question, answer)],
+        QuestionKind::TypeIn => vec![CardContent::new_type_in(question, answer)],
+        QuestionKind::Reversible => CardContent::new_reversible(question, answer).to_vec(),
+    }
+}
+
 fn trim(line: &str) -> String {
     line[2..].trim().to_string()
 }
@@ -243,9 +729,20 @@ impl Parser {
         let mut state = State::Initial;
         let lines: Vec<&str> = text.lines().collect();
         let last_line = if lines.is_empty() { 0 } else { lines.len() - 1 };
+        let mut fence: Option<Fence> = None;
         for (line_num, line) in lines.iter().enumerate() {
-            let line = Line::read(line);
-            state = self.parse_line(state, line, line_num, &mut cards)?;
+            // A new card, or a separator, closes any fence left open.
+            if is_card_boundary(line) {
+                fence = None;
+            }
+            // In a code block, lines like `#include` are code, not tags.
+            let read = if fence.is_some() && is_tags(line) {
+                Line::Text(line.to_string())
+            } else {
+                Line::read(line)
+            };
+            fence = next_fence(fence, line);
+            state = self.parse_line(state, read, line_num, &mut cards)?;
         }
         self.finalize(state, last_line, &mut cards)?;
 
@@ -266,10 +763,20 @@ impl Parser {
         line_num: usize,
         cards: &mut Vec<Card>,
     ) -> Result<State, ParserError> {
+        // The kind of card the line starts, if it starts a question.
+        let next_kind: QuestionKind = match line {
+            Line::StartTypeIn(_) => QuestionKind::TypeIn,
+            Line::StartReversible(_) => QuestionKind::Reversible,
+            _ => QuestionKind::Basic,
+        };
         match state {
             State::Initial => match line {
-                Line::StartQuestion(text) => Ok(State::ReadingQuestion {
+                Line::StartQuestion(text)
+                | Line::StartTypeIn(text)
+                | Line::StartReversible(text) => Ok(State::ReadingQuestion {
                     question: text,
+                    kind: next_kind,
+                    tags: Vec::new(),
                     start_line: line_num,
                 }),
                 Line::StartAnswer(_) => Err(ParserError::new(
@@ -279,23 +786,38 @@ impl Parser {
                 )),
                 Line::StartCloze(text) => Ok(State::ReadingCloze {
                     text,
+                    extra: None,
+                    tags: Vec::new(),
                     start_line: line_num,
                 }),
+                Line::StartExtra(_) => Err(ParserError::new(
+                    "Found extra tag without a card.",
+                    self.file_path.clone(),
+                    line_num,
+                )),
                 Line::Separator => Ok(State::Initial),
+                Line::Tags(_) => Ok(State::Initial),
                 Line::Text(_) => Ok(State::Initial),
             },
             State::ReadingQuestion {
                 question,
+                kind,
+                tags,
                 start_line,
             } => match line {
-                Line::StartQuestion(_) => Err(ParserError::new(
-                    "New question without answer.",
-                    self.file_path.clone(),
-                    line_num,
-                )),
+                Line::StartQuestion(_) | Line::StartTypeIn(_) | Line::StartReversible(_) => {
+                    Err(ParserError::new(
+                        "New question without answer.",
+                        self.file_path.clone(),
+                        line_num,
+                    ))
+                }
                 Line::StartAnswer(text) => Ok(State::ReadingAnswer {
                     question,
                     answer: text,
+                    kind,
+                    extra: None,
+                    tags,
                     start_line,
                 }),
                 Line::StartCloze(_) => Err(ParserError::new(
@@ -303,34 +825,55 @@ impl Parser {
                     self.file_path.clone(),
                     line_num,
                 )),
+                Line::StartExtra(_) => Err(ParserError::new(
+                    "Found extra tag while reading a question.",
+                    self.file_path.clone(),
+                    line_num,
+                )),
                 Line::Separator => Err(ParserError::new(
                     "Found flashcard separator while reading a question.",
                     self.file_path.clone(),
                     line_num,
                 )),
+                Line::Tags(new_tags) => Ok(State::ReadingQuestion {
+                    question,
+                    kind,
+                    tags: add_tags(tags, new_tags),
+                    start_line,
+                }),
                 Line::Text(text) => Ok(State::ReadingQuestion {
                     question: format!("{question}\n{text}"),
+                    kind,
+                    tags,
                     start_line,
                 }),
             },
             State::ReadingAnswer {
                 question,
                 answer,
+                kind,
+                extra,
+                tags,
                 start_line,
             } => {
                 match line {
-                    Line::StartQuestion(text) => {
+                    Line::StartQuestion(text)
+                    | Line::StartTypeIn(text)
+                    | Line::StartReversible(text) => {
                         // Finalize the previous card.
-                        let card = Card::new(
-                            self.deck_name.clone(),
-                            self.file_path.clone(),
+                        cards.extend(self.question_cards(
+                            question,
+                            answer,
+                            kind,
+                            tags,
+                            extra,
                             (start_line, line_num),
-                            CardContent::new_basic(question, answer),
-                        );
-                        cards.push(card);
+                        ));
                         // Start a new question.
                         Ok(State::ReadingQuestion {
                             question: text,
+                            kind: next_kind,
+                            tags: Vec::new(),
                             start_line: line_num,
                         })
                     }
@@ -341,46 +884,98 @@ impl Parser {
                     )),
                     Line::StartCloze(text) => {
                         // Finalize the previous card.
-                        let card = Card::new(
-                            self.deck_name.clone(),
-                            self.file_path.clone(),
+                        cards.extend(self.question_cards(
+                            question,
+                            answer,
+                            kind,
+                            tags,
+                            extra,
                             (start_line, line_num),
-                            CardContent::new_basic(question, answer),
-                        );
-                        cards.push(card);
+                        ));
                         // Start reading a new cloze card.
                         Ok(State::ReadingCloze {
                             text,
+                            extra: None,
+                            tags: Vec::new(),
                             start_line: line_num,
                         })
                     }
                     Line::Separator => {
                         // Finalize the current card.
-                        let card = Card::new(
-                            self.deck_name.clone(),
-                            self.file_path.clone(),
+                        cards.extend(self.question_cards(
+                            question,
+                            answer,
+                            kind,
+                            tags,
+                            extra,
                             (start_line, line_num),
-                            CardContent::new_basic(question, answer),
-                        );
-                        cards.push(card);
+                        ));
                         // Return to initial state.
                         Ok(State::Initial)
                     }
-                    Line::Text(text) => Ok(State::ReadingAnswer {
+                    Line::StartExtra(text) => match extra {
+                        Some(_) => Err(ParserError::new(
+                            "Found extra tag while reading an extra.",
+                            self.file_path.clone(),
+                            line_num,
+                        )),
+                        None => Ok(State::ReadingAnswer {
+                            question,
+                            answer,
+                            kind,
+                            extra: Some(text),
+                            tags,
+                            start_line,
+                        }),
+                    },
+                    Line::Tags(new_tags) => Ok(State::ReadingAnswer {
                         question,
-                        answer: format!("{answer}\n{text}"),
+                        answer,
+                        kind,
+                        extra,
+                        tags: add_tags(tags, new_tags),
                         start_line,
                     }),
+                    // Text after an extra tag is part of the extra.
+                    Line::Text(text) => match extra {
+                        Some(extra) => Ok(State::ReadingAnswer {
+                            question,
+                            answer,
+                            kind,
+                            extra: Some(format!("{extra}\n{text}")),
+                            tags,
+                            start_line,
+                        }),
+                        None => Ok(State::ReadingAnswer {
+                            question,
+                            answer: format!("{answer}\n{text}"),
+                            kind,
+                            extra,
+                            tags,
+                            start_line,
+                        }),
+                    },
                 }
             }
-            State::ReadingCloze { text, start_line } => {
+            State::ReadingCloze {
+                text,
+                extra,
+                tags,
+                start_line,
+            } => {
                 match line {
-                    Line::StartQuestion(new_text) => {
+                    Line::StartQuestion(new_text)
+                    | Line::StartTypeIn(new_text)
+                    | Line::StartReversible(new_text) => {
                         // Finalize the previous cloze card.
-                        cards.extend(self.parse_cloze_cards(text, start_line, line_num)?);
+                        cards.extend(
+                            self.parse_cloze_cards(text, tags, extra, start_line, line_num)?,
+                        );
                         // Start a new question card
                         Ok(State::ReadingQuestion {
                             question: new_text,
+                            kind: next_kind,
+                            tags: Vec::new(),
                             start_line: line_num,
                         })
                     }
@@ -391,23 +986,59 @@ impl Parser {
                     )),
                     Line::StartCloze(new_text) => {
                         // Finalize the previous card.
-                        cards.extend(self.parse_cloze_cards(text, start_line, line_num)?);
+                        cards.extend(
+                            self.parse_cloze_cards(text, tags, extra, start_line, line_num)?,
+                        );
                         // Start reading a new cloze card.
                         Ok(State::ReadingCloze {
                             text: new_text,
+                            extra: None,
+                            tags: Vec::new(),
                             start_line: line_num,
                         })
                     }
                     Line::Separator => {
                         // Finalize the current cloze card.
-                        cards.extend(self.parse_cloze_cards(text, start_line, line_num)?);
+                        cards.extend(
+                            self.parse_cloze_cards(text, tags, extra, start_line, line_num)?,
+                        );
                         // Return to initial state.
                         Ok(State::Initial)
                     }
-                    Line::Text(new_text) => Ok(State::ReadingCloze {
-                        text: format!("{text}\n{new_text}"),
+                    Line::StartExtra(new_text) => match extra {
+                        Some(_) => Err(ParserError::new(
+                            "Found extra tag while reading an extra.",
+                            self.file_path.clone(),
+                            line_num,
+                        )),
+                        None => Ok(State::ReadingCloze {
+                            text,
+                            extra: Some(new_text),
+                            tags,
+                            start_line,
+                        }),
+                    },
+                    Line::Tags(new_tags) => Ok(State::ReadingCloze {
+                        text,
+                        extra,
+                        tags: add_tags(tags, new_tags),
                         start_line,
                     }),
+                    // Text after an extra tag is part of the extra.
+                    Line::Text(new_text) => match extra {
+                        Some(extra) => Ok(State::ReadingCloze {
+                            text,
+                            extra: Some(format!("{extra}\n{new_text}")),
+                            tags,
+                            start_line,
+                        }),
+                        None => Ok(State::ReadingCloze {
+                            text: format!("{text}\n{new_text}"),
+                            extra,
+                            tags,
+                            start_line,
+                        }),
+                    },
                 }
             }
         }
@@ -429,29 +1060,65 @@ impl Parser {
             State::ReadingAnswer {
                 question,
                 answer,
+                kind,
+                extra,
+                tags,
                 start_line,
             } => {
                 // Finalize the last card.
-                let card = Card::new(
-                    self.deck_name.clone(),
-                    self.file_path.clone(),
+                cards.extend(self.question_cards(
+                    question,
+                    answer,
+                    kind,
+                    tags,
+                    extra,
                     (start_line, last_line),
-                    CardContent::new_basic(question, answer),
-                );
-                cards.push(card);
+                ));
                 Ok(())
             }
-            State::ReadingCloze { text, start_line } => {
+            State::ReadingCloze {
+                text,
+                extra,
+                tags,
+                start_line,
+            } => {
                 // Finalize the last cloze card.
-                cards.extend(self.parse_cloze_cards(text, start_line, last_line)?);
+                cards.extend(self.parse_cloze_cards(text, tags, extra, start_line, last_line)?);
                 Ok(())
             }
         }
     }
 
+    /// The cards of a question and its answer.
+    fn question_cards(
+        &self,
+        question: String,
+        answer: String,
+        kind: QuestionKind,
+        tags: Vec<Tag>,
+        extra: Option<String>,
+        range: (usize, usize),
+    ) -> Vec<Card> {
+        question_contents(question, answer, kind)
+            .into_iter()
+            .map(|content| {
+                Card::new(
+                    self.deck_name.clone(),
+                    self.file_path.clone(),
+                    range,
+                    content,
+                    tags.clone(),
+                )
+                .with_extra(extra.clone())
+            })
+            .collect()
+    }
+
     fn parse_cloze_cards(
         &self,
         text: String,
+        tags: Vec<Tag>,
+        extra: Option<String>,
         start_line: usize,
         end_line: usize,
     ) -> Result<Vec<Card>, ParserError> {
@@ -466,12 +1133,26 @@ impl Parser {
             // markdown and not part of the cloze and therefore added to clean_text.
             let mut image_mode = false; // ![
             let mut escape_mode = false; // \[ and \]
+            // The number of bytes of a deletion's number left to skip.
+            let mut skip: usize = 0;
             // We use `bytes` rather than `chars` because the cloze start/end
             // positions are byte positions, not character positions. This
             // keeps things tractable: bytes are well-understood, "characters"
             // are a vague abstract concept.
             for (bytepos, c) in text.bytes().enumerate() {
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
                 if c == b'[' {
+                    if !image_mode && !escape_mode {
+                        // The start of a deletion, whose number is not part
+                        // of the text.
+                        if let Some((_, length)) = text.get(bytepos + 1..).and_then(deletion_number)
+                        {
+                            skip = length;
+                        }
+                    }
                     if image_mode {
                         clean_text.push(c);
                     }
@@ -536,11 +1217,19 @@ impl Parser {
             }
         };
 
+        // The deletions, as their number, if any, and their byte range.
+        let mut deletions: Vec<(Option<usize>, usize, usize)> = Vec::new();
         let mut start = None;
+        let mut number = None;
         let mut index = 0;
         let mut image_mode = false;
         let mut escape_mode = false;
+        let mut skip: usize = 0;
         for (bytepos, c) in text.bytes().enumerate() {
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
             if c == b'[' {
                 if image_mode {
                     // We are in image mode, so this closing bracket is part of a markdown image.
@@ -551,6 +1240,11 @@ impl Parser {
                     escape_mode = false;
                 } else {
                     start = Some(index);
+                    number = None;
+                    if let Some((n, length)) = text.get(bytepos + 1..).and_then(deletion_number) {
+                        number = Some(n);
+                        skip = length;
+                    }
                 }
             } else if c == b']' {
                 if image_mode {
@@ -562,15 +1256,14 @@ impl Parser {
                     escape_mode = false;
                     index += 1;
                 } else if let Some(s) = start {
-                    let end = index;
-                    let content = CardContent::new_cloze(clean_text.clone(), s, end - 1);
-                    let card = Card::new(
-                        self.deck_name.clone(),
-                        self.file_path.clone(),
-                        (start_line, end_line),
-                        content,
-                    );
-                    cards.push(card);
+                    let Some((s, end)) = trim_deletion(&clean_text, s, index) else {
+                        return Err(ParserError::new(
+                            "Cloze deletion is empty.",
+                            self.file_path.clone(),
+                            start_line,
+                        ));
+                    };
+                    deletions.push((number, s, end));
                     start = None;
                 }
             } else if c == b'!' {
@@ -607,6 +1300,53 @@ impl Parser {
             }
         }
 
+        // Without numbers, each deletion is a card. With numbers, each group
+        // of deletions with the same number is a card, in the order the
+        // groups first appear.
+        let numbered: Vec<(usize, usize, usize)> = deletions
+            .iter()
+            .filter_map(|(number, s, end)| number.map(|number| (number, *s, *end)))
+            .collect();
+        let contents: Vec<Fallible<CardContent>> = if numbered.is_empty() {
+            deletions
+                .iter()
+                .map(|(_, s, end)| CardContent::new_cloze(clean_text.clone(), *s, *end))
+                .collect()
+        } else if numbered.len() < deletions.len() {
+            return Err(ParserError::new(
+                "Cloze card mixes numbered and unnumbered deletions.",
+                self.file_path.clone(),
+                start_line,
+            ));
+        } else {
+            let mut groups: Vec<usize> = Vec::new();
+            for (number, _, _) in numbered.iter() {
+                if !groups.contains(number) {
+                    groups.push(*number);
+                }
+            }
+            groups
+                .into_iter()
+                .map(|group| {
+                    CardContent::new_grouped_cloze(clean_text.clone(), numbered.clone(), group)
+                })
+                .collect()
+        };
+        for content in contents {
+            let content = content
+                .map_err(|e| ParserError::new(e.message(), self.file_path.clone(), start_line))?;
+            cards.push(
+                Card::new(
+                    self.deck_name.clone(),
+                    self.file_path.clone(),
+                    (start_line, end_line),
+                    content,
+                    tags.clone(),
+                )
+                .with_extra(extra.clone()),
+            );
+        }
+
         if cards.is_empty() {
             Err(ParserError::new(
                 "Cloze card must contain at least one cloze deletion.",
@@ -625,6 +1365,9 @@ mod tests {
     use std::fs::create_dir_all;
 
     use super::*;
+    use crate::helper::create_tmp_directory;
+    use crate::types::card::CardType;
+    use crate::types::card_hash::CardHash;
 
     #[test]
     fn test_empty_string() -> Result<(), ParserError> {
@@ -678,6 +1421,65 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_type_in() -> Result<(), ParserError> {
+        let input = "T: chat\nA: cat\nQ: foo\nA: bar\nT: chien\nA: dog\n#animals";
+        let parser = make_test_parser();
+        let cards = parser.parse(input)?;
+
+        assert_eq!(cards.len(), 3);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::TypeIn { prompt, answer } if prompt == "chat" && answer == "cat"
+        ));
+        assert_eq!(cards[1].card_type(), CardType::Basic);
+        assert_eq!(cards[2].card_type(), CardType::TypeIn);
+        assert_eq!(cards[2].tags(), ["animals"]);
+        assert_eq!(cards[2].to_source_text(), "T: chien\nA: dog\n#animals");
+        // A prompt needs an answer.
+        assert!(parser.parse("T: chat").is_err());
+        assert!(parser.parse("T: chat\nT: chien\nA: dog").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reversible() -> Result<(), ParserError> {
+        let input = "R: le chat\nA: the cat\nE: Not the dog.\n#french\nQ: foo\nA: bar\n";
+        let parser = make_test_parser();
+        let cards = parser.parse(input)?;
+
+        assert_eq!(cards.len(), 3);
+        let reversed: Vec<bool> = cards[..2]
+            .iter()
+            .map(|card| match card.content() {
+                CardContent::Reversible { reversed, .. } => *reversed,
+                _ => panic!("not a reversible card"),
+            })
+            .collect();
+        assert_eq!(reversed, vec![false, true]);
+        assert_eq!(cards[0].family_hash(), cards[1].family_hash());
+        assert_ne!(cards[0].hash(), cards[1].hash());
+        for card in &cards[..2] {
+            assert_eq!(card.card_type(), CardType::Basic);
+            assert_eq!(card.range(), (0, 4));
+            assert_eq!(card.tags(), ["french"]);
+            assert_eq!(card.extra(), Some("Not the dog."));
+            // The source round-trips, so the card stays reversible when
+            // edited.
+            let source = card.to_source_text();
+            assert_eq!(source, "R: le chat\nA: the cat\nE: Not the dog.\n#french");
+            let again = parser.parse(&source)?;
+            assert_eq!(
+                again.iter().map(Card::hash).collect::<Vec<_>>(),
+                cards[..2].iter().map(Card::hash).collect::<Vec<_>>()
+            );
+        }
+        assert!(is_card_boundary("R: le chien"));
+        assert!(parser.parse("R: le chat").is_err());
+        assert!(parser.parse("R: le chat\nR: le chien\nA: the dog").is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_two_questions() -> Result<(), ParserError> {
         let input = "Q: foo\nA: bar\n\nQ: baz\nA: quux\n\n";
@@ -862,6 +1664,215 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_extra() -> Result<(), ParserError> {
+        let parser = make_test_parser();
+        let input = "Q: Capital of France?\nA: Paris\nE: On the Seine.\nSee *Wikipedia*.\n#geo\n\nC: [Rome] is in [Italy].\nE: Mnemonic.";
+        let cards = parser.parse(input)?;
+        assert_eq!(cards.len(), 3);
+        assert_eq!(cards[0].extra(), Some("On the Seine.\nSee *Wikipedia*."));
+        assert_eq!(cards[0].tags(), ["geo"]);
+        assert!(matches!(
+            cards[0].content(),
+            CardContent::Basic { answer, .. } if answer == "Paris"
+        ));
+        // The siblings of a cloze card share the notes.
+        assert_eq!(cards[1].extra(), Some("Mnemonic."));
+        assert_eq!(cards[2].extra(), Some("Mnemonic."));
+        // The notes are not part of the card's identity.
+        let plain = parser.parse("Q: Capital of France?\nA: Paris")?;
+        assert_eq!(plain[0].hash(), cards[0].hash());
+        assert_eq!(plain[0].extra(), None);
+        // The source includes the notes, and parses back into the same card.
+        let source = cards[0].to_source_text();
+        assert_eq!(
+            source,
+            "Q: Capital of France?\nA: Paris\nE: On the Seine.\nSee *Wikipedia*.\n#geo"
+        );
+        let parsed = parser.parse(&source)?;
+        assert_eq!(parsed[0].hash(), cards[0].hash());
+        assert_eq!(parsed[0].extra(), cards[0].extra());
+        // Blank notes are dropped.
+        let cards = parser.parse("Q: a\nA: b\nE:  ")?;
+        assert_eq!(cards[0].extra(), None);
+        assert_eq!(cards[0].to_source_text(), "Q: a\nA: b");
+        Ok(())
+    }
+
+    #[test]
+    fn test_extra_errors() {
+        let parser = make_test_parser();
+        let error = |input: &str| parser.parse(input).err().map(|e| e.to_string());
+        assert_eq!(
+            error("E: Notes"),
+            Some("Found extra tag without a card. Location: test.md:1".to_string())
+        );
+        assert_eq!(
+            error("Q: a\nE: Notes\nA: b"),
+            Some("Found extra tag while reading a question. Location: test.md:2".to_string())
+        );
+        assert_eq!(
+            error("C: [a]\nE: Notes\nE: More"),
+            Some("Found extra tag while reading an extra. Location: test.md:3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_numbered_cloze() -> Result<(), ParserError> {
+        let parser = make_test_parser();
+        let input = "C: [c1::Paris] is on the [c1::Seine], in [c2::France].";
+        let cards = parser.parse(input)?;
+        assert_eq!(cards.len(), 2);
+        let text = "Paris is on the Seine, in France.";
+        assert_eq!(
+            cards[0].content().cloze_deletions(),
+            Some((text, vec![(0, 4), (16, 20)]))
+        );
+        assert_eq!(
+            cards[1].content().cloze_deletions(),
+            Some((text, vec![(26, 31)]))
+        );
+        // The groups are siblings, but distinct cards.
+        assert_eq!(cards[0].family_hash(), cards[1].family_hash());
+        assert_ne!(cards[0].hash(), cards[1].hash());
+        // Every card has the source of the whole text, which parses back into
+        // the same cards.
+        for card in &cards {
+            let source = card.content().to_source_text();
+            assert_eq!(source, input);
+            let hashes: Vec<CardHash> = parser.parse(&source)?.iter().map(Card::hash).collect();
+            assert_eq!(hashes, vec![cards[0].hash(), cards[1].hash()]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_numbered_cloze_order() -> Result<(), ParserError> {
+        let parser = make_test_parser();
+        // Cards are in the order their groups first appear, and the numbers
+        // need not be consecutive.
+        let cards = parser.parse("C: [c3::a] [c1::b] [c3::c] [c7::d]")?;
+        let groups: Vec<usize> = cards
+            .iter()
+            .map(|card| match card.content() {
+                CardContent::GroupedCloze { group, .. } => *group,
+                _ => 0,
+            })
+            .collect();
+        assert_eq!(groups, vec![3, 1, 7]);
+        assert_eq!(
+            cards[0].content().cloze_deletions(),
+            Some(("a b c d", vec![(0, 0), (4, 4)]))
+        );
+        Ok(())
+    }
+
+    /// A group of one deletion is the same card as the deletion without a
+    /// number, so numbering the deletions of a card keeps its progress.
+    #[test]
+    fn test_numbered_cloze_hash() -> Result<(), ParserError> {
+        let parser = make_test_parser();
+        let plain = parser.parse("C: [Paris] is in [France].")?;
+        let numbered = parser.parse("C: [c1::Paris] is in [c2::France].")?;
+        let hashes = |cards: &[Card]| -> Vec<CardHash> { cards.iter().map(Card::hash).collect() };
+        assert_eq!(hashes(&plain), hashes(&numbered));
+        // Grouping them makes a new card.
+        let grouped = parser.parse("C: [c1::Paris] is in [c1::France].")?;
+        assert_eq!(grouped.len(), 1);
+        assert!(!hashes(&plain).contains(&grouped[0].hash()));
+        assert_eq!(grouped[0].family_hash(), plain[0].family_hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_numbered_cloze_syntax() -> Result<(), ParserError> {
+        let parser = make_test_parser();
+        // Mixing numbered and unnumbered deletions is an error.
+        let error = parser.parse("C: [c1::Paris] is in [France].").err();
+        assert_eq!(
+            error.map(|e| e.to_string()),
+            Some(
+                "Cloze card mixes numbered and unnumbered deletions. Location: test.md:1"
+                    .to_string()
+            )
+        );
+        // An empty numbered deletion is an error.
+        assert!(parser.parse("C: [c1::] is in [c1::France].").is_err());
+        // Only a prefix of `c`, digits, and `::` is a number.
+        let cards = parser.parse("C: [c::a] [C1::b] [c1:c] [std::vec]")?;
+        assert_cloze(
+            &cards,
+            "c::a C1::b c1:c std::vec",
+            &[(0, 3), (5, 9), (11, 14), (16, 23)],
+        );
+        // Escaped brackets and image brackets are not deletions.
+        let cards = parser.parse("C: \\[c1::a\\] [c1::![b](b.png)] [c1::c]")?;
+        assert_eq!(cards.len(), 1);
+        assert_eq!(
+            cards[0].content().cloze_deletions(),
+            Some(("[c1::a] ![b](b.png) c", vec![(8, 18), (20, 20)]))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_deletion_whitespace() -> Result<(), ParserError> {
+        let parser = make_test_parser();
+        // Whitespace at the edges of a deletion is left outside of it.
+        let cards = parser.parse("C: The capital is [ Paris ].")?;
+        assert_cloze(&cards, "The capital is  Paris .", &[(16, 20)]);
+        assert_eq!(
+            cards[0].content().to_source_text(),
+            "C: The capital is  [Paris] ."
+        );
+        // Whitespace inside a deletion is kept.
+        let cards = parser.parse("C: [New York] is big.")?;
+        assert_cloze(&cards, "New York is big.", &[(0, 7)]);
+        // Deletions at the start and end of the text.
+        let cards = parser.parse("C: [Paris ]is the [ capital]")?;
+        assert_cloze(&cards, "Paris is the  capital", &[(0, 4), (14, 20)]);
+        assert_eq!(
+            cards[1].content().to_source_text(),
+            "C: Paris is the  [capital]"
+        );
+        // The normalized source parses to the same card.
+        let source = cards[0].content().to_source_text();
+        assert_eq!(parser.parse(&source)?[0].hash(), cards[0].hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_multibyte() -> Result<(), ParserError> {
+        let parser = make_test_parser();
+        // Positions are in bytes: "é" takes two, and each kanji three.
+        let cards = parser.parse("C: Un [café] au lait.")?;
+        assert_cloze(&cards, "Un café au lait.", &[(3, 7)]);
+        let cards = parser.parse("C: [東京]は日本の[首都]です。")?;
+        assert_cloze(&cards, "東京は日本の首都です。", &[(0, 5), (18, 23)]);
+        // The source round-trips through every card.
+        for card in &cards {
+            let source = card.content().to_source_text();
+            assert_eq!(parser.parse(&source)?[0].family_hash(), card.family_hash());
+        }
+        assert_eq!(
+            cards[1].content().to_source_text(),
+            "C: 東京は日本の[首都]です。"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_blank_cloze_deletion() {
+        let parser = make_test_parser();
+        for input in ["C: Empty [].", "C: Blank [  ].", "C: []Start"] {
+            let err = parser.parse(input).err().unwrap();
+            assert_eq!(
+                err.to_string(),
+                "Cloze deletion is empty. Location: test.md:1"
+            );
+        }
+    }
+
     #[test]
     fn test_cloze_with_initial_blank_line() -> Result<(), ParserError> {
         let input = "C:\nBuild something people want in Lisp.\n\n— [Paul Graham], [_Hackers and Painters_]\n\n";
@@ -879,7 +1890,12 @@ mod tests {
     #[test]
     fn test_parse_deck() -> Fallible<()> {
         let directory = PathBuf::from("./test");
-        let deck = parse_deck(&directory);
+        let deck = parse_deck(
+            &directory,
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        );
 
         assert!(deck.is_ok());
         let cards = deck?;
@@ -887,6 +1903,77 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_regex_cards() -> Fallible<()> {
+        let regex = Regex::new(r"(?m)^(?P<q>[^\n]+?) :: (?P<a>(?:[^\n]+\n?)+)").unwrap();
+        let text = "# Notes\n\nParis :: Capital of France.\nOn the Seine.\n\nRome ::  \n\nBern :: Capital of Switzerland.";
+        let cards = extract_regex_cards(&regex, &"Notes".to_string(), Path::new("notes.md"), text);
+        assert_eq!(cards.len(), 2);
+        assert!(matches!(
+            cards[0].content(),
+            CardContent::Basic { question, answer }
+                if question == "Paris" && answer == "Capital of France.\nOn the Seine."
+        ));
+        assert_eq!(cards[0].range(), (2, 3));
+        assert_eq!(cards[1].range(), (7, 7));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_deck_with_regex() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("notes.md"),
+            "Paris :: France\n\nQ: What is 2 + 2?\nA: 4\n",
+        )?;
+        std::fs::write(
+            directory.join("notes.txt"),
+            "---\nBern :: Switzerland\n\nQ: What is 3 + 3?\nA: 6\n",
+        )?;
+        let mut card_regex = CardRegex {
+            regex: Regex::new(r"(?m)^(?P<q>.+) :: (?P<a>.+)$").unwrap(),
+            extensions: Vec::new(),
+        };
+        let deck = parse_deck(
+            &directory,
+            Some(&card_regex),
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )?;
+        assert_eq!(deck.len(), 2);
+        let regex_cards: Vec<&Card> = deck.iter().filter(|card| card.is_from_regex()).collect();
+        assert_eq!(regex_cards.len(), 1);
+        assert!(regex_cards[0].content().to_source_text().contains("Paris"));
+
+        // Other files are only matched with the regex, if their extension is
+        // listed.
+        card_regex.extensions = vec!["txt".to_string()];
+        let deck = parse_deck(
+            &directory,
+            Some(&card_regex),
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )?;
+        assert_eq!(deck.len(), 3);
+        let bern = deck
+            .iter()
+            .find(|card| card.content().to_source_text().contains("Bern"))
+            .unwrap();
+        assert!(bern.is_from_regex());
+        assert_eq!(bern.deck_name(), "notes");
+        assert_eq!(
+            parse_deck(
+                &directory,
+                None,
+                &IgnoreRules::default(),
+                DEFAULT_DECK_SEPARATOR
+            )?
+            .len(),
+            1
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_identical_basic_cards() -> Result<(), ParserError> {
         let input = "Q: foo\nA: bar\n\nQ: foo\nA: bar\n\n";
@@ -916,12 +2003,105 @@ mod tests {
         let file2 = directory.join("file2.md");
         std::fs::write(&file1, "Q: foo\nA: bar").expect("Failed to write test file");
         std::fs::write(&file2, "Q: foo\nA: bar").expect("Failed to write test file");
-        let deck = parse_deck(&directory)?;
+        let deck = parse_deck(
+            &directory,
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )?;
 
         assert_eq!(deck.len(), 1);
         Ok(())
     }
 
+    #[test]
+    fn test_basic_card_with_tags() -> Result<(), ParserError> {
+        let input = "Q: foo\n#geology\nA: bar\n#hard #geology";
+        let parser = make_test_parser();
+        let cards = parser.parse(input)?;
+
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].tags(), &["geology", "hard"]);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic {
+                question,
+                answer,
+            } if question == "foo" && answer == "bar"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_card_with_tags() -> Result<(), ParserError> {
+        let input = "C: Foo [bar] [baz].\n#hard";
+        let parser = make_test_parser();
+        let cards = parser.parse(input)?;
+
+        assert_cloze(&cards, "Foo bar baz.", &[(4, 6), (8, 10)]);
+        for card in cards {
+            assert_eq!(card.tags(), &["hard"]);
+        }
+        Ok(())
+    }
+
+    /// Tags are not part of the card's content, so adding them does not reset
+    /// the card's progress.
+    #[test]
+    fn test_tags_do_not_change_hash() -> Result<(), ParserError> {
+        let parser = make_test_parser();
+        let untagged = parser.parse("Q: foo\nA: bar")?;
+        let tagged = parser.parse("Q: foo\nA: bar\n#hard")?;
+        assert_eq!(untagged[0].hash(), tagged[0].hash());
+        Ok(())
+    }
+
+    /// Lines that merely contain a `#` are not tag lines.
+    #[test]
+    fn test_non_tag_lines() -> Result<(), ParserError> {
+        let input = "Q: foo\nA: bar\n# Heading\n#hard and more\n#1";
+        let parser = make_test_parser();
+        let cards = parser.parse(input)?;
+
+        assert_eq!(cards.len(), 1);
+        assert!(cards[0].tags().is_empty());
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic {
+                answer,
+                ..
+            } if answer == "bar\n# Heading\n#hard and more\n#1"
+        ));
+        Ok(())
+    }
+
+    /// Lines in a fenced code block are code, even if they look like tags.
+    #[test]
+    fn test_tags_in_code_block() -> Result<(), ParserError> {
+        let input = "Q: foo\nA: ```c\n#include\n#pragma once\n```\n#hard\n\nQ: bar\nA:\n~~~~\n#define\n~~~\n#x\n~~~~\n#easy";
+        let parser = make_test_parser();
+        let cards = parser.parse(input)?;
+
+        assert_eq!(cards.len(), 2);
+        assert_eq!(cards[0].tags(), &["hard"]);
+        assert!(matches!(
+            &cards[0].content(),
+            CardContent::Basic {
+                answer,
+                ..
+            } if answer == "```c\n#include\n#pragma once\n```"
+        ));
+        assert_eq!(cards[1].tags(), &["easy"]);
+        assert!(matches!(
+            &cards[1].content(),
+            CardContent::Basic {
+                answer,
+                ..
+            } if answer == "~~~~\n#define\n~~~\n#x\n~~~~"
+        ));
+        Ok(())
+    }
+
     fn make_test_parser() -> Parser {
         Parser::new("test_deck".to_string(), PathBuf::from("test.md"))
     }
@@ -1088,6 +2268,38 @@ Q: What is Rust?"#;
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_deck_created() -> Fallible<()> {
+        let input = "---\ncreated = 2025-03-01\n---\n\nQ: Q\nA: A";
+        assert_eq!(deck_created(input)?, NaiveDate::from_ymd_opt(2025, 3, 1));
+        let input = "---\nname = \"Deck\"\n---\n\nQ: Q\nA: A";
+        assert_eq!(deck_created(input)?, None);
+        assert_eq!(deck_created("Q: Q\nA: A")?, None);
+        // Dates can be strings.
+        assert_eq!(
+            deck_created("---\ncreated = \"2025-03-01\"\n---\n")?,
+            NaiveDate::from_ymd_opt(2025, 3, 1)
+        );
+        // A time alone is not a date.
+        assert!(deck_created("---\ncreated = 10:00:00\n---\n").is_err());
+        assert!(deck_created("---\ncreated = \"March\"\n---\n").is_err());
+        assert!(deck_created("---\ncreated = 2025\n---\n").is_err());
+        // An invalid date doesn't stop the deck from loading.
+        let input = "---\ncreated = \"March\"\n---\n\nQ: Q\nA: A";
+        assert_eq!(
+            parse_text(
+                Path::new(""),
+                Path::new("Deck.md"),
+                input,
+                None,
+                DEFAULT_DECK_SEPARATOR
+            )?
+            .len(),
+            1
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_parse_with_frontmatter() -> Result<(), ParserError> {
         let input = r#"---
@@ -1106,6 +2318,62 @@ A: A systems programming language."#;
         Ok(())
     }
 
+    /// Many files, parsed in parallel, give the same cards every time.
+    #[test]
+    fn test_parse_many_files() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        for dir in 0..10 {
+            create_dir_all(directory.join(format!("deck{dir}")))?;
+            for file in 0..50 {
+                let text: String = (0..4)
+                    .map(|card| format!("Q: {dir} {file} {card}\nA: answer\n\n"))
+                    .collect();
+                std::fs::write(directory.join(format!("deck{dir}/{file}.md")), text)?;
+            }
+        }
+        let parse = || parse_deck(&directory, None, &IgnoreRules::default(), "::");
+        let cards = parse()?;
+        assert_eq!(cards.len(), 10 * 50 * 4);
+        let hashes: Vec<CardHash> = cards.iter().map(Card::hash).collect();
+        for _ in 0..3 {
+            let again: Vec<CardHash> = parse()?.iter().map(Card::hash).collect();
+            assert_eq!(again, hashes);
+        }
+        Ok(())
+    }
+
+    /// A parse error in one of many files names that file.
+    #[test]
+    fn test_parse_many_files_error() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        for file in 0..100 {
+            std::fs::write(directory.join(format!("{file}.md")), "Q: q\nA: a\n")?;
+        }
+        std::fs::write(directory.join("57.md"), "A: no question\n")?;
+        let err = parse_deck(
+            &directory,
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )
+        .err()
+        .unwrap();
+        assert!(err.message().contains("57.md"));
+
+        std::fs::write(directory.join("57.md"), "---\nname = \"Deck\"\n")?;
+        let err = parse_deck(
+            &directory,
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )
+        .err()
+        .unwrap();
+        assert!(err.message().contains("no closing '---'"));
+        assert!(err.message().contains("57.md"));
+        Ok(())
+    }
+
     #[test]
     fn test_parse_deck_with_frontmatter() -> Fallible<()> {
         let directory = temp_dir();
@@ -1137,7 +2405,12 @@ A: Genetic material."#,
         )
         .expect("Failed to write test file");
 
-        let deck = parse_deck(&directory)?;
+        let deck = parse_deck(
+            &directory,
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )?;
 
         // Both cards should have the custom deck name "Cell Biology"
         assert_eq!(deck.len(), 2);
@@ -1151,6 +2424,71 @@ A: Genetic material."#,
         Ok(())
     }
 
+    #[test]
+    fn test_nested_deck_names() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        create_dir_all(directory.join("languages/spanish"))?;
+        std::fs::write(directory.join("Root.md"), "Q: a\nA: b")?;
+        std::fs::write(directory.join("languages/spanish/verbs.md"), "Q: c\nA: d")?;
+        std::fs::write(
+            directory.join("languages/spanish/nouns.md"),
+            "---\nname = \"Sustantivos\"\n---\nQ: e\nA: f",
+        )?;
+        let deck_names = |separator: &str| -> Fallible<HashSet<String>> {
+            Ok(
+                parse_deck(&directory, None, &IgnoreRules::default(), separator)?
+                    .iter()
+                    .map(|card| card.deck_name().clone())
+                    .collect(),
+            )
+        };
+        assert_eq!(
+            deck_names(DEFAULT_DECK_SEPARATOR)?,
+            HashSet::from([
+                "Root".to_string(),
+                "languages::spanish::verbs".to_string(),
+                "Sustantivos".to_string(),
+            ])
+        );
+        assert!(deck_names(" / ")?.contains("languages / spanish / verbs"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deck_tags() -> Fallible<()> {
+        let directory = create_tmp_directory()?;
+        std::fs::write(
+            directory.join("Geology.md"),
+            "---\ntags = [\"geology\", \"earth/crust\"]\n---\nQ: a\nA: b\n#hard #geology",
+        )?;
+        let deck = parse_deck(
+            &directory,
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )?;
+        assert_eq!(deck[0].tags(), ["hard", "geology"]);
+        assert_eq!(deck[0].deck_tags(), ["earth/crust"]);
+        assert!(deck[0].has_tag("earth/crust"));
+        // Deck tags are not written back with the card.
+        assert_eq!(deck[0].to_source_text(), "Q: a\nA: b\n#hard #geology");
+
+        std::fs::write(
+            directory.join("Geology.md"),
+            "---\ntags = [\"#geology\"]\n---\n",
+        )?;
+        let err = parse_deck(
+            &directory,
+            None,
+            &IgnoreRules::default(),
+            DEFAULT_DECK_SEPARATOR,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("Invalid tag '#geology'"));
+        Ok(())
+    }
+
     #[test]
     fn test_separator_between_basic_cards() -> Result<(), ParserError> {
         let input = "Q: foo\nA: bar\n---\nQ: baz\nA: quux";
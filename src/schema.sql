@@ -9,7 +9,8 @@ create table cards (
     interval_raw real,
     interval_days integer,
     due_date text,
-    review_count integer not null
+    review_count integer not null,
+    suspended integer not null default 0
 ) strict;
 
 create table sessions (
@@ -34,5 +35,31 @@ create table reviews (
     difficulty real not null,
     interval_raw real not null,
     interval_days integer not null,
-    due_date text not null
+    due_date text not null,
+    revealed_at text,
+    confidence integer,
+    duration_ms integer
+) strict;
+
+-- Archived reviews outlive their cards, so that deleted cards can keep them.
+-- The IDs of deleted reviews are reused, so archived reviews have their own.
+create table archived_reviews (
+    archive_id integer primary key autoincrement,
+    review_id integer not null,
+    session_id integer not null
+        references sessions (session_id)
+        on update cascade
+        on delete cascade,
+    card_hash text not null,
+    reviewed_at text not null,
+    grade text not null,
+    stability real not null,
+    difficulty real not null,
+    interval_raw real not null,
+    interval_days integer not null,
+    due_date text not null,
+    revealed_at text,
+    confidence integer,
+    archived_at text not null,
+    duration_ms integer
 ) strict;
@@ -13,3 +13,5 @@
 // limitations under the License.
 
 pub type DeckName = String;
+
+pub type Tag = String;
@@ -12,23 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::env::current_dir;
+use std::ffi::OsStr;
+use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
 
 use maud::Markup;
 use maud::PreEscaped;
 use maud::html;
+use serde::Deserialize;
+use serde::Serialize;
 
 use crate::error::Fallible;
+use crate::error::fail;
+use crate::markdown::ClozeSide;
 use crate::markdown::MarkdownRenderConfig;
+use crate::markdown::cloze_group_to_html;
+use crate::markdown::cloze_to_html;
 use crate::markdown::markdown_to_html;
-use crate::markdown::markdown_to_html_inline;
 use crate::types::aliases::DeckName;
+use crate::types::aliases::Tag;
 use crate::types::card_hash::CardHash;
 use crate::types::card_hash::Hasher;
-
-const CLOZE_TAG_BYTES: &[u8] = b"CLOZE_DELETION";
-const CLOZE_TAG: &str = "CLOZE_DELETION";
+use crate::types::normalize::normalize_cloze;
+use crate::types::normalize::normalize_text;
 
 #[derive(Clone)]
 pub struct Card {
@@ -40,11 +48,24 @@ pub struct Card {
     range: (usize, usize),
     /// The card's content.
     content: CardContent,
+    /// The card's tags. These are not part of the content, so tagging a card
+    /// does not change its hash.
+    tags: Vec<Tag>,
+    /// The tags of the card's deck, from its frontmatter, other than the
+    /// card's own. They only select cards: they are not written back with the
+    /// card when it is edited or exported.
+    deck_tags: Vec<Tag>,
+    /// Notes shown under the answer once it is revealed, like a mnemonic or
+    /// a source. Like the tags, they are not part of the content.
+    extra: Option<String>,
+    /// Whether the card was matched by the collection's `card_regex`, rather
+    /// than written in the card format. Such cards can't be written back.
+    from_regex: bool,
     /// The cached hash of the card's content.
     hash: CardHash,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum CardContent {
     Basic {
         question: String,
@@ -53,17 +74,45 @@ pub enum CardContent {
     Cloze {
         /// The text of the card without brackets.
         text: String,
-        /// The position of the first character of the deletion.
+        /// The byte position of the first byte of the deletion.
         start: usize,
-        /// The position of the last character of the deletion.
+        /// The byte position of the last byte of the deletion, so the deleted
+        /// text is `text[start..=end]`.
         end: usize,
     },
+    /// A cloze card whose deletions are numbered, like `[c1::Paris] is in
+    /// [c1::France]`. The deletions with the same number form a group, which
+    /// is hidden by a single card.
+    GroupedCloze {
+        /// The text of the card without brackets.
+        text: String,
+        /// Every deletion of the text, as the group it belongs to and the
+        /// byte positions of its first and last bytes, in order.
+        deletions: Vec<(usize, usize, usize)>,
+        /// The group hidden by this card.
+        group: usize,
+    },
+    /// A card whose answer is typed in, and checked against the expected
+    /// answer.
+    TypeIn {
+        prompt: String,
+        answer: String,
+    },
+    /// One direction of a basic card written with `R:`, which is studied both
+    /// ways: the forward card shows the question first, like a basic card,
+    /// and the reversed card shows the answer first. The two are siblings.
+    Reversible {
+        question: String,
+        answer: String,
+        reversed: bool,
+    },
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CardType {
     Basic,
     Cloze,
+    TypeIn,
 }
 
 impl Card {
@@ -72,12 +121,17 @@ impl Card {
         file_path: PathBuf,
         range: (usize, usize),
         content: CardContent,
+        tags: Vec<Tag>,
     ) -> Self {
         let hash = content.hash();
         Self {
             deck_name,
             file_path,
             content,
+            tags,
+            deck_tags: Vec::new(),
+            extra: None,
+            from_regex: false,
             range,
             hash,
         }
@@ -91,6 +145,53 @@ impl Card {
         &self.content
     }
 
+    /// The card's own tags, without those of its deck.
+    pub fn tags(&self) -> &[Tag] {
+        &self.tags
+    }
+
+    pub fn deck_tags(&self) -> &[Tag] {
+        &self.deck_tags
+    }
+
+    /// Whether the card has the tag, or its deck does.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().chain(&self.deck_tags).any(|t| t == tag)
+    }
+
+    /// Set the tags of the card's deck. Those the card has itself are left
+    /// out.
+    pub fn with_deck_tags(mut self, tags: &[Tag]) -> Self {
+        self.deck_tags = tags
+            .iter()
+            .filter(|tag| !self.tags.contains(tag))
+            .cloned()
+            .collect();
+        self
+    }
+
+    /// Set the card's extra notes. Blank notes are dropped.
+    pub fn with_extra(mut self, extra: Option<String>) -> Self {
+        self.extra = extra
+            .map(|extra| extra.trim().to_string())
+            .filter(|extra| !extra.is_empty());
+        self
+    }
+
+    pub fn extra(&self) -> Option<&str> {
+        self.extra.as_deref()
+    }
+
+    /// Mark the card as matched by the collection's `card_regex`.
+    pub fn with_regex_origin(mut self) -> Self {
+        self.from_regex = true;
+        self
+    }
+
+    pub fn is_from_regex(&self) -> bool {
+        self.from_regex
+    }
+
     pub fn hash(&self) -> CardHash {
         self.hash
     }
@@ -109,9 +210,12 @@ impl Card {
     ///
     /// e.g., if the collection root is `/foo/bar/` and the file path is
     /// `/foo/bar/baz/deck.md`, this returns `baz/deck.md`.
+    ///
+    /// The file need not exist: a card that has been edited but not yet saved
+    /// may point to a file that has not been created.
     pub fn relative_file_path(&self, collection_root: &Path) -> Fallible<PathBuf> {
         let canon_root: PathBuf = collection_root.canonicalize()?;
-        let canon_file: PathBuf = self.file_path.canonicalize()?;
+        let canon_file: PathBuf = resolve_path(&self.file_path)?;
         let result: PathBuf = canon_file.strip_prefix(&canon_root)?.to_path_buf();
         Ok(result)
     }
@@ -122,8 +226,24 @@ impl Card {
 
     pub fn card_type(&self) -> CardType {
         match &self.content {
-            CardContent::Basic { .. } => CardType::Basic,
-            CardContent::Cloze { .. } => CardType::Cloze,
+            CardContent::Basic { .. } | CardContent::Reversible { .. } => CardType::Basic,
+            CardContent::Cloze { .. } | CardContent::GroupedCloze { .. } => CardType::Cloze,
+            CardContent::TypeIn { .. } => CardType::TypeIn,
+        }
+    }
+
+    /// Reconstruct the original markdown source text for this card,
+    /// including its extra notes and tags.
+    pub fn to_source_text(&self) -> String {
+        let text = match &self.extra {
+            Some(extra) => format!("{}\nE: {extra}", self.content.to_source_text()),
+            None => self.content.to_source_text(),
+        };
+        if self.tags.is_empty() {
+            text
+        } else {
+            let tags: Vec<String> = self.tags.iter().map(|tag| format!("#{tag}")).collect();
+            format!("{text}\n{}", tags.join(" "))
         }
     }
 
@@ -134,6 +254,15 @@ impl Card {
     pub fn html_back(&self, config: &MarkdownRenderConfig) -> Fallible<Markup> {
         self.content.html_back(config)
     }
+
+    pub fn html_extra(&self, config: &MarkdownRenderConfig) -> Fallible<Option<Markup>> {
+        match &self.extra {
+            Some(extra) => Ok(Some(html! {
+                (PreEscaped(markdown_to_html(config, extra)?))
+            })),
+            None => Ok(None),
+        }
+    }
 }
 
 impl CardContent {
@@ -144,27 +273,172 @@ impl CardContent {
         }
     }
 
-    pub fn new_cloze(prompt: impl Into<String>, start: usize, end: usize) -> Self {
-        Self::Cloze {
-            text: prompt.into(),
-            start,
-            end,
+    pub fn new_type_in(prompt: impl Into<String>, answer: impl Into<String>) -> Self {
+        Self::TypeIn {
+            prompt: prompt.into().trim().to_string(),
+            answer: answer.into().trim().to_string(),
+        }
+    }
+
+    /// The forward and reversed cards of a reversible card.
+    pub fn new_reversible(question: impl Into<String>, answer: impl Into<String>) -> [Self; 2] {
+        let question: String = question.into().trim().to_string();
+        let answer: String = answer.into().trim().to_string();
+        [false, true].map(|reversed| Self::Reversible {
+            question: question.clone(),
+            answer: answer.clone(),
+            reversed,
+        })
+    }
+
+    /// A cloze card deleting the byte range `start..=end` of the text. The
+    /// range must be non-empty and fall on character boundaries, so that the
+    /// deletion can be spliced without splitting a multibyte character.
+    pub fn new_cloze(prompt: impl Into<String>, start: usize, end: usize) -> Fallible<Self> {
+        let text: String = prompt.into();
+        if text.get(start..=end).is_none() {
+            return fail(format!(
+                "Invalid cloze deletion range ({start}-{end}): not on a character boundary."
+            ));
+        }
+        Ok(Self::Cloze { text, start, end })
+    }
+
+    /// A cloze card with numbered deletions, hiding those of the given group.
+    /// The deletions are `(group, start, end)`, with byte ranges as in
+    /// [`CardContent::new_cloze`], and must not overlap.
+    pub fn new_grouped_cloze(
+        prompt: impl Into<String>,
+        deletions: Vec<(usize, usize, usize)>,
+        group: usize,
+    ) -> Fallible<Self> {
+        let text: String = prompt.into();
+        let mut deletions: Vec<(usize, usize, usize)> = deletions;
+        deletions.sort_by_key(|(_, start, _)| *start);
+        let mut position: usize = 0;
+        for (_, start, end) in deletions.iter() {
+            if *start < position {
+                return fail(format!(
+                    "Invalid cloze deletion range ({start}-{end}): overlaps another deletion."
+                ));
+            }
+            if text.get(*start..=*end).is_none() {
+                return fail(format!(
+                    "Invalid cloze deletion range ({start}-{end}): not on a character boundary."
+                ));
+            }
+            position = end + 1;
+        }
+        if !deletions.iter().any(|(g, _, _)| *g == group) {
+            return fail(format!("Cloze card has no deletions in group {group}."));
         }
+        Ok(Self::GroupedCloze {
+            text,
+            deletions,
+            group,
+        })
     }
 
+    /// For cloze cards, the text, and the `(start, end)` byte ranges of the
+    /// deletions the card hides, in order.
+    pub fn cloze_deletions(&self) -> Option<(&str, Vec<(usize, usize)>)> {
+        match self {
+            CardContent::Basic { .. }
+            | CardContent::TypeIn { .. }
+            | CardContent::Reversible { .. } => None,
+            CardContent::Cloze { text, start, end } => Some((text, vec![(*start, *end)])),
+            CardContent::GroupedCloze {
+                text,
+                deletions,
+                group,
+            } => Some((
+                text,
+                deletions
+                    .iter()
+                    .filter(|(g, _, _)| g == group)
+                    .map(|(_, start, end)| (*start, *end))
+                    .collect(),
+            )),
+        }
+    }
+
+    /// The hash of the card's content. The text is normalized first, so that
+    /// changes to whitespace, line endings, or the Unicode normalization form
+    /// of the text don't change the hash.
     pub fn hash(&self) -> CardHash {
+        self.compute_hash(true)
+    }
+
+    /// The hash the card had before its text was normalized for hashing.
+    /// Used to move the progress of cards reviewed under their old hash.
+    pub fn legacy_hash(&self) -> CardHash {
+        self.compute_hash(false)
+    }
+
+    fn compute_hash(&self, normalized: bool) -> CardHash {
+        let normalize_text = |text: &str| -> String {
+            if normalized {
+                normalize_text(text)
+            } else {
+                text.to_string()
+            }
+        };
+        let normalize_cloze = |text: &str, deletions: &[(usize, usize)]| {
+            if normalized {
+                normalize_cloze(text, deletions)
+            } else {
+                (text.to_string(), deletions.to_vec())
+            }
+        };
         let mut hasher = Hasher::new();
         match &self {
-            CardContent::Basic { question, answer } => {
+            // The forward card of a reversible card hashes like the basic
+            // card, so writing `R:` for `Q:` keeps the card's progress.
+            CardContent::Basic { question, answer }
+            | CardContent::Reversible {
+                question,
+                answer,
+                reversed: false,
+            } => {
                 hasher.update(b"Basic");
-                hasher.update(question.as_bytes());
-                hasher.update(answer.as_bytes());
+                hasher.update(normalize_text(question).as_bytes());
+                hasher.update(normalize_text(answer).as_bytes());
             }
             CardContent::Cloze { text, start, end } => {
+                let (text, deletions) = normalize_cloze(text, &[(*start, *end)]);
+                hasher.update(b"Cloze");
+                hasher.update(text.as_bytes());
+                for (start, end) in deletions {
+                    hasher.update(&start.to_le_bytes());
+                    hasher.update(&end.to_le_bytes());
+                }
+            }
+            // The hash covers the deletions the card hides, not their
+            // number, so a group of one deletion has the hash of the plain
+            // cloze card with that deletion.
+            CardContent::GroupedCloze { text, .. } => {
+                let hidden: Vec<(usize, usize)> = self.cloze_deletions().unwrap_or_default().1;
+                let (text, deletions) = normalize_cloze(text, &hidden);
                 hasher.update(b"Cloze");
                 hasher.update(text.as_bytes());
-                hasher.update(&start.to_le_bytes());
-                hasher.update(&end.to_le_bytes());
+                for (start, end) in deletions {
+                    hasher.update(&start.to_le_bytes());
+                    hasher.update(&end.to_le_bytes());
+                }
+            }
+            CardContent::TypeIn { prompt, answer } => {
+                hasher.update(b"TypeIn");
+                hasher.update(normalize_text(prompt).as_bytes());
+                hasher.update(normalize_text(answer).as_bytes());
+            }
+            CardContent::Reversible {
+                question,
+                answer,
+                reversed: true,
+            } => {
+                hasher.update(b"Reversed");
+                hasher.update(normalize_text(question).as_bytes());
+                hasher.update(normalize_text(answer).as_bytes());
             }
         }
         hasher.finalize()
@@ -173,35 +447,54 @@ impl CardContent {
     /// Reconstruct the original markdown source text for this card.
     ///
     /// For basic cards: `Q: {question}\nA: {answer}`
-    /// For cloze cards: `C: {text_with_brackets}`
+    /// For cloze cards: `C: {text_with_brackets}`, with every group of a
+    /// card with numbered deletions
+    /// For type-in cards: `T: {prompt}\nA: {answer}`
+    /// For reversible cards, in either direction: `R: {question}\nA: {answer}`
     pub fn to_source_text(&self) -> String {
         match self {
             CardContent::Basic { question, answer } => {
                 format!("Q: {}\nA: {}", question, answer)
             }
             CardContent::Cloze { text, start, end } => {
-                let mut bytes = text.as_bytes().to_vec();
-                // Insert ] after the last character of the deletion (higher position first)
-                bytes.insert(*end + 1, b']');
-                // Insert [ before the first character of the deletion
-                bytes.insert(*start, b'[');
-                let with_brackets =
-                    String::from_utf8(bytes).unwrap_or_else(|_| text.clone());
-                format!("C: {}", with_brackets)
+                format!("C: {}", with_brackets(text, &[(*start, *end)]))
+            }
+            CardContent::GroupedCloze {
+                text, deletions, ..
+            } => {
+                format!("C: {}", with_numbered_brackets(text, deletions))
+            }
+            CardContent::TypeIn { prompt, answer } => {
+                format!("T: {}\nA: {}", prompt, answer)
+            }
+            CardContent::Reversible {
+                question, answer, ..
+            } => {
+                format!("R: {}\nA: {}", question, answer)
             }
         }
     }
 
-    /// All cloze cards derived from the same text have the same family hash.
+    /// All cloze cards derived from the same text have the same family hash,
+    /// and so do both directions of a reversible card.
     ///
-    /// For basic cards, this is `None`.
+    /// For basic and type-in cards, this is `None`.
     pub fn family_hash(&self) -> Option<CardHash> {
         match &self {
-            CardContent::Basic { .. } => None,
-            CardContent::Cloze { text, .. } => {
+            CardContent::Basic { .. } | CardContent::TypeIn { .. } => None,
+            CardContent::Reversible {
+                question, answer, ..
+            } => {
+                let mut hasher = Hasher::new();
+                hasher.update(b"Reversible");
+                hasher.update(normalize_text(question).as_bytes());
+                hasher.update(normalize_text(answer).as_bytes());
+                Some(hasher.finalize())
+            }
+            CardContent::Cloze { text, .. } | CardContent::GroupedCloze { text, .. } => {
                 let mut hasher = Hasher::new();
                 hasher.update(b"Cloze");
-                hasher.update(text.as_bytes());
+                hasher.update(normalize_text(text).as_bytes());
                 Some(hasher.finalize())
             }
         }
@@ -209,20 +502,36 @@ impl CardContent {
 
     pub fn html_front(&self, config: &MarkdownRenderConfig) -> Fallible<Markup> {
         let html = match self {
-            CardContent::Basic { question, .. } => {
+            CardContent::Basic { question, .. }
+            | CardContent::TypeIn {
+                prompt: question, ..
+            }
+            | CardContent::Reversible {
+                question,
+                reversed: false,
+                ..
+            }
+            | CardContent::Reversible {
+                answer: question,
+                reversed: true,
+                ..
+            } => {
                 html! {
                     (PreEscaped(markdown_to_html(config, question)?))
                 }
             }
-            CardContent::Cloze { text, start, end } => {
-                let mut text_bytes: Vec<u8> = text.as_bytes().to_owned();
-                text_bytes.splice(*start..*end + 1, CLOZE_TAG_BYTES.iter().copied());
-                let text: String = String::from_utf8(text_bytes)?;
-                let text: String = markdown_to_html(config, &text)?;
-                let text: String =
-                    text.replace(CLOZE_TAG, "<span class='cloze'>.............</span>");
-                html! {
-                    (PreEscaped(text))
+            CardContent::Cloze { .. } | CardContent::GroupedCloze { .. } => {
+                let text: String = self.cloze_to_html(config, ClozeSide::Front)?;
+                if config.dim_cloze_context {
+                    html! {
+                        div.cloze-context {
+                            (PreEscaped(text))
+                        }
+                    }
+                } else {
+                    html! {
+                        (PreEscaped(text))
+                    }
                 }
             }
         };
@@ -231,23 +540,24 @@ impl CardContent {
 
     pub fn html_back(&self, config: &MarkdownRenderConfig) -> Fallible<Markup> {
         let html = match self {
-            CardContent::Basic { answer, .. } => {
+            CardContent::Basic { answer, .. }
+            | CardContent::TypeIn { answer, .. }
+            | CardContent::Reversible {
+                answer,
+                reversed: false,
+                ..
+            }
+            | CardContent::Reversible {
+                question: answer,
+                reversed: true,
+                ..
+            } => {
                 html! {
                     (PreEscaped(markdown_to_html(config, answer)?))
                 }
             }
-            CardContent::Cloze { text, start, end } => {
-                let mut text_bytes: Vec<u8> = text.as_bytes().to_owned();
-                let deleted_text: Vec<u8> = text_bytes[*start..*end + 1].to_owned();
-                let deleted_text: String = String::from_utf8(deleted_text)?;
-                let deleted_text: String = markdown_to_html_inline(config, &deleted_text)?;
-                text_bytes.splice(*start..*end + 1, CLOZE_TAG_BYTES.iter().copied());
-                let text: String = String::from_utf8(text_bytes)?;
-                let text = markdown_to_html(config, &text)?;
-                let text = text.replace(
-                    CLOZE_TAG,
-                    &format!("<span class='cloze-reveal'>{}</span>", deleted_text),
-                );
+            CardContent::Cloze { .. } | CardContent::GroupedCloze { .. } => {
+                let text: String = self.cloze_to_html(config, ClozeSide::Back)?;
                 html! {
                     (PreEscaped(text))
                 }
@@ -255,11 +565,108 @@ impl CardContent {
         };
         Ok(html)
     }
+
+    /// Render a side of a cloze card, hiding or revealing its deletions.
+    fn cloze_to_html(&self, config: &MarkdownRenderConfig, side: ClozeSide) -> Fallible<String> {
+        match self {
+            CardContent::Cloze { text, start, end } => {
+                cloze_to_html(config, text, *start, *end, side)
+            }
+            _ => match self.cloze_deletions() {
+                Some((text, deletions)) => cloze_group_to_html(config, text, &deletions, side),
+                None => fail("Not a cloze card."),
+            },
+        }
+    }
+}
+
+/// The text of a cloze card with brackets around the given deletions, as
+/// written in the source. The deletions are `(start, end)` byte ranges, as in
+/// [`CardContent::Cloze`], and must not overlap.
+pub fn with_brackets(text: &str, deletions: &[(usize, usize)]) -> String {
+    let mut deletions: Vec<(usize, usize)> = deletions.to_vec();
+    deletions.sort();
+    let mut result: String = String::with_capacity(text.len() + 2 * deletions.len());
+    let mut position: usize = 0;
+    for (start, end) in deletions {
+        // The ranges are checked in `new_cloze`, so they fall on character
+        // boundaries.
+        result.push_str(text.get(position..start).unwrap_or_default());
+        result.push('[');
+        result.push_str(text.get(start..=end).unwrap_or_default());
+        result.push(']');
+        position = end + 1;
+    }
+    result.push_str(text.get(position..).unwrap_or_default());
+    result
+}
+
+/// The text of a cloze card with numbered deletions, with each deletion
+/// written as in the source, e.g. `[c1::Paris]`. The deletions are
+/// `(group, start, end)`, as in [`CardContent::GroupedCloze`], and must not
+/// overlap.
+pub fn with_numbered_brackets(text: &str, deletions: &[(usize, usize, usize)]) -> String {
+    let mut deletions: Vec<(usize, usize, usize)> = deletions.to_vec();
+    deletions.sort_by_key(|(_, start, _)| *start);
+    let mut result: String = String::with_capacity(text.len() + 7 * deletions.len());
+    let mut position: usize = 0;
+    for (group, start, end) in deletions {
+        result.push_str(text.get(position..start).unwrap_or_default());
+        result.push_str(&format!("[c{group}::"));
+        result.push_str(text.get(start..=end).unwrap_or_default());
+        result.push(']');
+        position = end + 1;
+    }
+    result.push_str(text.get(position..).unwrap_or_default());
+    result
+}
+
+/// Make a path absolute, resolve its `.` and `..` components lexically, and
+/// resolve symbolic links in the longest prefix of it that exists. For a path
+/// that exists, this is the same as canonicalizing it.
+fn resolve_path(path: &Path) -> Fallible<PathBuf> {
+    let absolute: PathBuf = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        current_dir()?.join(path)
+    };
+    let mut normal: PathBuf = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normal.pop();
+            }
+            component => normal.push(component),
+        }
+    }
+    // Canonicalize the longest prefix that exists, and append the rest.
+    let mut prefix: &Path = &normal;
+    let mut rest: Vec<&OsStr> = Vec::new();
+    loop {
+        if let Ok(mut result) = prefix.canonicalize() {
+            result.extend(rest.iter().rev());
+            return Ok(result);
+        }
+        match (prefix.parent(), prefix.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                prefix = parent;
+            }
+            _ => return Ok(normal),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs::create_dir;
+    use std::fs::write;
+
     use super::*;
+    use crate::helper::create_tmp_directory;
+    use crate::markdown::ClozeBlank;
+    use crate::media::resolve::MediaResolverBuilder;
 
     #[test]
     fn test_basic_card_hash() {
@@ -271,18 +678,20 @@ mod tests {
     }
 
     #[test]
-    fn test_cloze_card_hash() {
-        let a = CardContent::new_cloze("The capital of France is Paris", 0, 1);
-        let b = CardContent::new_cloze("The capital of France is Paris", 0, 2);
+    fn test_cloze_card_hash() -> Fallible<()> {
+        let a = CardContent::new_cloze("The capital of France is Paris", 0, 1)?;
+        let b = CardContent::new_cloze("The capital of France is Paris", 0, 2)?;
         // Different positions produce different card hashes
         assert_ne!(a.hash(), b.hash());
+        Ok(())
     }
 
     #[test]
-    fn test_family_hash() {
-        let a = CardContent::new_cloze("The capital of France is Paris", 0, 1);
-        let b = CardContent::new_cloze("The capital of France is Paris", 0, 2);
+    fn test_family_hash() -> Fallible<()> {
+        let a = CardContent::new_cloze("The capital of France is Paris", 0, 1)?;
+        let b = CardContent::new_cloze("The capital of France is Paris", 0, 2)?;
         assert_eq!(a.family_hash(), b.family_hash());
+        Ok(())
     }
 
     #[test]
@@ -292,9 +701,398 @@ mod tests {
     }
 
     #[test]
-    fn test_cloze_to_source_text() {
+    fn test_card_to_source_text_with_tags() {
+        let content = CardContent::new_basic("What is 2+2?", "4");
+        let card = Card::new(
+            "deck".to_string(),
+            PathBuf::from("deck.md"),
+            (0, 1),
+            content,
+            vec!["math".to_string(), "hard".to_string()],
+        );
+        assert_eq!(card.to_source_text(), "Q: What is 2+2?\nA: 4\n#math #hard");
+        assert!(card.has_tag("hard"));
+        assert!(!card.has_tag("easy"));
+    }
+
+    fn card_at(file_path: PathBuf) -> Card {
+        let content = CardContent::new_basic("Q", "A");
+        Card::new("deck".to_string(), file_path, (0, 1), content, vec![])
+    }
+
+    #[test]
+    fn test_relative_file_path() -> Fallible<()> {
+        let root = create_tmp_directory()?;
+        create_dir(root.join("sub"))?;
+        write(root.join("sub/deck.md"), "")?;
+        let card = card_at(root.join("sub/deck.md"));
+        assert_eq!(
+            card.relative_file_path(&root)?,
+            PathBuf::from("sub/deck.md")
+        );
+        let card = card_at(root.join("sub/./../sub/deck.md"));
+        assert_eq!(
+            card.relative_file_path(&root)?,
+            PathBuf::from("sub/deck.md")
+        );
+        // A file outside the root is rejected.
+        let card = card_at(root.join("../deck.md"));
+        assert!(card.relative_file_path(&root).is_err());
+        Ok(())
+    }
+
+    /// A card in a file that has not been created yet.
+    #[test]
+    fn test_relative_file_path_not_existing() -> Fallible<()> {
+        let root = create_tmp_directory()?;
+        let card = card_at(root.join("new/../new/deck.md"));
+        assert_eq!(
+            card.relative_file_path(&root)?,
+            PathBuf::from("new/deck.md")
+        );
+        Ok(())
+    }
+
+    /// A collection root reached through a symbolic link.
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_relative_file_path_symlinked_root() -> Fallible<()> {
+        use std::os::unix::fs::symlink;
+
+        let dir = create_tmp_directory()?;
+        create_dir(dir.join("real"))?;
+        write(dir.join("real/deck.md"), "")?;
+        let root = dir.join("link");
+        symlink(dir.join("real"), &root)?;
+        let card = card_at(root.join("deck.md"));
+        assert_eq!(card.relative_file_path(&root)?, PathBuf::from("deck.md"));
+        let card = card_at(root.join("new.md"));
+        assert_eq!(card.relative_file_path(&root)?, PathBuf::from("new.md"));
+        // The real path of the root works as well.
+        let card = card_at(dir.join("real/new.md"));
+        assert_eq!(card.relative_file_path(&root)?, PathBuf::from("new.md"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_grouped_cloze() -> Fallible<()> {
+        let config = make_render_config()?;
+        // "Paris is on the Seine, in France." with group 1 at "Paris" and
+        // "Seine", and group 2 at "France".
+        let text = "Paris is on the Seine, in France.";
+        let deletions = vec![(1, 0, 4), (2, 26, 31), (1, 16, 20)];
+        let card = CardContent::new_grouped_cloze(text, deletions.clone(), 1)?;
+        // Only the card's group is hidden.
+        let front = card.html_front(&config)?.into_string();
+        assert_eq!(
+            front,
+            format!("<p>{HIDDEN} is on the {HIDDEN}, in France.</p>\n")
+        );
+        let back = card.html_back(&config)?.into_string();
+        assert_eq!(
+            back,
+            "<p><span class='cloze-reveal'>Paris</span> is on the <span class='cloze-reveal'>Seine</span>, in France.</p>\n"
+        );
+        let other = CardContent::new_grouped_cloze(text, deletions, 2)?;
+        let front = other.html_front(&config)?.into_string();
+        assert_eq!(
+            front,
+            format!("<p>Paris is on the Seine, in {HIDDEN}.</p>\n")
+        );
+        assert_eq!(
+            card.to_source_text(),
+            "C: [c1::Paris] is on the [c1::Seine], in [c2::France]."
+        );
+        assert_eq!(card.to_source_text(), other.to_source_text());
+        assert_eq!(card.family_hash(), other.family_hash());
+        assert_ne!(card.hash(), other.hash());
+        // A group of one deletion hashes like the plain cloze card.
+        assert_eq!(other.hash(), CardContent::new_cloze(text, 26, 31)?.hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_grouped_cloze_invalid() {
+        let text = "café au lait";
+        // Overlapping deletions.
+        assert!(CardContent::new_grouped_cloze(text, vec![(1, 0, 4), (2, 3, 6)], 1).is_err());
+        // A range inside a character.
+        assert!(CardContent::new_grouped_cloze(text, vec![(1, 0, 3)], 1).is_err());
+        // A group without deletions.
+        assert!(CardContent::new_grouped_cloze(text, vec![(1, 0, 4)], 2).is_err());
+    }
+
+    #[test]
+    fn test_type_in_card() {
+        let card = CardContent::new_type_in("chat", " cat ");
+        assert_eq!(card.to_source_text(), "T: chat\nA: cat");
+        // A type-in card is not the basic card with the same text.
+        assert_ne!(card.hash(), CardContent::new_basic("chat", "cat").hash());
+        assert_eq!(card.family_hash(), None);
+    }
+
+    /// Cosmetic differences in the text don't change a card's hash, but the
+    /// card keeps its text as written.
+    #[test]
+    fn test_hash_normalization() -> Fallible<()> {
+        let a = CardContent::new_basic("What is  the\r\ncapital   of France?", "Paris");
+        let b = CardContent::new_basic("What is the\ncapital of France?", "Paris");
+        assert_eq!(a.hash(), b.hash());
+        assert_eq!(
+            a.to_source_text(),
+            "Q: What is  the\r\ncapital   of France?\nA: Paris"
+        );
+        assert_eq!(
+            b.to_source_text(),
+            "Q: What is the\ncapital of France?\nA: Paris"
+        );
+        assert_ne!(
+            a.hash(),
+            CardContent::new_basic("What is the capital?", "Paris").hash()
+        );
+
+        let a = CardContent::new_type_in("caf\u{0065}\u{0301}", "coffee");
+        let b = CardContent::new_type_in("caf\u{00e9}", "coffee");
+        assert_eq!(a.hash(), b.hash());
+        assert_eq!(a.to_source_text(), "T: caf\u{0065}\u{0301}\nA: coffee");
+
+        // The deletion is "Paris" in both, at different byte positions.
+        let a = CardContent::new_cloze("The capital  of France \t is Paris", 28, 32)?;
+        let b = CardContent::new_cloze("The capital of France is Paris", 25, 29)?;
+        assert_eq!(a.hash(), b.hash());
+        assert_eq!(a.family_hash(), b.family_hash());
+        assert_eq!(
+            a.to_source_text(),
+            "C: The capital  of France \t is [Paris]"
+        );
+        assert_eq!(b.to_source_text(), "C: The capital of France is [Paris]");
+        // The deletion still matters.
+        let c = CardContent::new_cloze("The capital  of France \t is Paris", 4, 10)?;
+        assert_ne!(a.hash(), c.hash());
+        // Cards that were already normalized keep their legacy hash.
+        assert_eq!(b.hash(), b.legacy_hash());
+        assert_ne!(a.hash(), a.legacy_hash());
+
+        // Indentation is part of the text.
+        let a = CardContent::new_basic("List:\n- a\n  - b", "c");
+        let b = CardContent::new_basic("List:\n- a\n    - b", "c");
+        assert_ne!(a.hash(), b.hash());
+
+        let a = CardContent::new_grouped_cloze(
+            "Paris is on the  Seine,\r\nin France.",
+            vec![(1, 0, 4), (2, 17, 21), (1, 28, 33)],
+            1,
+        )?;
+        let b = CardContent::new_grouped_cloze(
+            "Paris is on the Seine,\nin France.",
+            vec![(1, 0, 4), (2, 16, 20), (1, 26, 31)],
+            1,
+        )?;
+        assert_eq!(a.hash(), b.hash());
+        assert_eq!(
+            a.to_source_text(),
+            "C: [c1::Paris] is on the  [c2::Seine],\r\nin [c1::France]."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_reversible_card() -> Fallible<()> {
+        let config = make_render_config()?;
+        let [forward, reversed] = CardContent::new_reversible(" le chat ", "the cat");
+        // The directions are distinct cards of the same family.
+        assert_ne!(forward.hash(), reversed.hash());
+        assert!(forward.family_hash().is_some());
+        assert_eq!(forward.family_hash(), reversed.family_hash());
+        // The forward card is the basic card.
+        let basic = CardContent::new_basic("le chat", "the cat");
+        assert_eq!(forward.hash(), basic.hash());
+        assert_eq!(basic.family_hash(), None);
+        // The reversed card shows the answer first.
+        assert_eq!(
+            forward.html_front(&config)?.into_string(),
+            "<p>le chat</p>\n"
+        );
+        assert_eq!(
+            forward.html_back(&config)?.into_string(),
+            "<p>the cat</p>\n"
+        );
+        assert_eq!(
+            reversed.html_front(&config)?.into_string(),
+            "<p>the cat</p>\n"
+        );
+        assert_eq!(
+            reversed.html_back(&config)?.into_string(),
+            "<p>le chat</p>\n"
+        );
+        // Both are written as the reversible card.
+        assert_eq!(forward.to_source_text(), "R: le chat\nA: the cat");
+        assert_eq!(reversed.to_source_text(), forward.to_source_text());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_to_source_text() -> Fallible<()> {
         // "Foo bar baz." with deletion at "bar" (positions 4-6)
-        let card = CardContent::new_cloze("Foo bar baz.", 4, 6);
+        let card = CardContent::new_cloze("Foo bar baz.", 4, 6)?;
         assert_eq!(card.to_source_text(), "C: Foo [bar] baz.");
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_multibyte() -> Fallible<()> {
+        // "café" is bytes 0-4: the "é" takes two.
+        let card = CardContent::new_cloze("café au lait", 0, 4)?;
+        assert_eq!(card.to_source_text(), "C: [café] au lait");
+        // A range that ends inside the "é" is rejected.
+        assert!(CardContent::new_cloze("café au lait", 0, 3).is_err());
+        assert!(CardContent::new_cloze("café au lait", 4, 5).is_err());
+
+        let (front, back) = render_cloze("Un [café] au lait.")?;
+        assert_eq!(front, format!("<p>Un {HIDDEN} au lait.</p>\n"));
+        assert_eq!(
+            back,
+            "<p>Un <span class='cloze-reveal'>café</span> au lait.</p>\n"
+        );
+        let (front, back) = render_cloze("[東京]は日本の首都です。")?;
+        assert_eq!(front, format!("<p>{HIDDEN}は日本の首都です。</p>\n"));
+        assert_eq!(
+            back,
+            "<p><span class='cloze-reveal'>東京</span>は日本の首都です。</p>\n"
+        );
+        Ok(())
+    }
+
+    fn make_render_config() -> Fallible<MarkdownRenderConfig> {
+        Ok(MarkdownRenderConfig {
+            resolver: MediaResolverBuilder::new()
+                .with_collection_path(create_tmp_directory()?)?
+                .with_deck_path(PathBuf::from("deck.md"))?
+                .build()?,
+            port: 1234,
+            dim_cloze_context: false,
+            highlight_code: true,
+            cloze_blank: ClozeBlank::default(),
+        })
+    }
+
+    /// Render both sides of a cloze card, given its source with the deletion
+    /// in brackets.
+    fn render_cloze(source: &str) -> Fallible<(String, String)> {
+        let start = source.find('[').unwrap();
+        let end = source.find(']').unwrap() - 2;
+        let text = source.replace(['[', ']'], "");
+        let card = CardContent::new_cloze(text, start, end)?;
+        let config = make_render_config()?;
+        let front = card.html_front(&config)?.into_string();
+        let back = card.html_back(&config)?.into_string();
+        Ok((front, back))
+    }
+
+    const HIDDEN: &str = "<span class='cloze'>.............</span>";
+
+    #[test]
+    fn test_cloze_around_formatting() -> Fallible<()> {
+        let (front, back) = render_cloze("The **[bold answer]** is *[important]*.")?;
+        assert_eq!(
+            front,
+            format!("<p>The <strong>{HIDDEN}</strong> is <em>important</em>.</p>\n")
+        );
+        assert_eq!(
+            back,
+            "<p>The <strong><span class='cloze-reveal'>bold answer</span></strong> is <em>important</em>.</p>\n"
+        );
+        let (front, back) = render_cloze("Use `git` to [commit].")?;
+        assert_eq!(front, format!("<p>Use <code>git</code> to {HIDDEN}.</p>\n"));
+        assert_eq!(
+            back,
+            "<p>Use <code>git</code> to <span class='cloze-reveal'>commit</span>.</p>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_containing_formatting() -> Fallible<()> {
+        let (front, back) = render_cloze("The [**bold** and _italic_ `code`] x.")?;
+        assert_eq!(front, format!("<p>The {HIDDEN} x.</p>\n"));
+        assert_eq!(
+            back,
+            "<p>The <span class='cloze-reveal'><strong>bold</strong> and <em>italic</em> <code>code</code></span> x.</p>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_across_formatting() -> Fallible<()> {
+        // The deletion starts inside the bold text and ends outside it.
+        let (front, back) = render_cloze("The **bold [answer** is] here.")?;
+        assert_eq!(
+            front,
+            format!("<p>The <strong>bold {HIDDEN}</strong> here.</p>\n")
+        );
+        assert_eq!(
+            back,
+            "<p>The <strong>bold <span class='cloze-reveal'>answer</span></strong><span class='cloze-reveal'> is</span> here.</p>\n"
+        );
+        // The deletion starts outside the italic text and ends inside it.
+        let (front, back) = render_cloze("The [answer *is] here*.")?;
+        assert_eq!(front, format!("<p>The {HIDDEN}<em> here</em>.</p>\n"));
+        assert_eq!(
+            back,
+            "<p>The <span class='cloze-reveal'>answer </span><em><span class='cloze-reveal'>is</span> here</em>.</p>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_inside_code_span() -> Fallible<()> {
+        let (front, back) = render_cloze("Run `git [commit]`.")?;
+        assert_eq!(front, format!("<p>Run <code>git </code>{HIDDEN}.</p>\n"));
+        assert_eq!(
+            back,
+            "<p>Run <code>git </code><span class='cloze-reveal'><code>commit</code></span>.</p>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dim_cloze_context() -> Fallible<()> {
+        let config = MarkdownRenderConfig {
+            dim_cloze_context: true,
+            ..make_render_config()?
+        };
+        let card = CardContent::new_cloze("Foo bar baz.", 4, 6)?;
+        let front = card.html_front(&config)?.into_string();
+        assert_eq!(
+            front,
+            format!("<div class=\"cloze-context\"><p>Foo {HIDDEN} baz.</p>\n</div>")
+        );
+        // The revealed side is unaffected.
+        let back = card.html_back(&config)?.into_string();
+        assert_eq!(
+            back,
+            "<p>Foo <span class='cloze-reveal'>bar</span> baz.</p>\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloze_inside_details() -> Fallible<()> {
+        let config = make_render_config()?;
+        // "Paris" is at bytes 28-32.
+        let text = "Capital.\n>! Details\n> It is Paris.";
+        let card = CardContent::new_cloze(text, 28, 32)?;
+        assert_eq!(
+            card.to_source_text(),
+            "C: Capital.\n>! Details\n> It is [Paris]."
+        );
+        let front = card.html_front(&config)?.into_string();
+        assert_eq!(
+            front,
+            "<p>Capital.</p>\n<details>\n<summary>Details</summary>\n<p>It is <span class='cloze'>.............</span>.</p>\n</details>\n"
+        );
+        let back = card.html_back(&config)?.into_string();
+        assert!(back.contains("<p>It is <span class='cloze-reveal'>Paris</span>.</p>"));
+        Ok(())
     }
 }
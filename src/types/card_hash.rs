@@ -42,6 +42,13 @@ impl CardHash {
         }
     }
 
+    /// The first eight bytes of the hash, as an integer.
+    pub fn to_u64(self) -> u64 {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&self.inner.as_bytes()[..8]);
+        u64::from_le_bytes(bytes)
+    }
+
     pub fn to_hex(self) -> String {
         self.inner.to_hex().to_string()
     }
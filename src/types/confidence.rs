@@ -0,0 +1,120 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+use rusqlite::ToSql;
+use rusqlite::types::FromSql;
+use rusqlite::types::FromSqlError;
+use rusqlite::types::FromSqlResult;
+use rusqlite::types::ToSqlOutput;
+use rusqlite::types::ValueRef;
+use serde::Serialize;
+
+use crate::error::ErrorReport;
+use crate::error::Fallible;
+
+/// The highest confidence rating.
+pub const MAX_CONFIDENCE: u8 = 5;
+
+/// A self-rated confidence in an answer, from 1 to [`MAX_CONFIDENCE`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Confidence(u8);
+
+impl Confidence {
+    pub fn new(value: u8) -> Fallible<Self> {
+        if (1..=MAX_CONFIDENCE).contains(&value) {
+            Ok(Self(value))
+        } else {
+            Err(ErrorReport::new(format!(
+                "Invalid confidence '{value}': expected a number from 1 to {MAX_CONFIDENCE}."
+            )))
+        }
+    }
+
+    /// Every rating, from lowest to highest.
+    pub fn all() -> impl Iterator<Item = Confidence> {
+        (1..=MAX_CONFIDENCE).map(Self)
+    }
+}
+
+impl Display for Confidence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Confidence {
+    type Err = ErrorReport;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<u8>() {
+            Ok(value) => Confidence::new(value),
+            Err(_) => Err(ErrorReport::new(format!(
+                "Invalid confidence '{s}': expected a number from 1 to {MAX_CONFIDENCE}."
+            ))),
+        }
+    }
+}
+
+impl ToSql for Confidence {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.0))
+    }
+}
+
+impl FromSql for Confidence {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let value: u8 = FromSql::column_result(value)?;
+        Confidence::new(value).map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+impl Serialize for Confidence {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u8(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert!(Confidence::new(0).is_err());
+        assert!(Confidence::new(1).is_ok());
+        assert!(Confidence::new(MAX_CONFIDENCE).is_ok());
+        assert!(Confidence::new(MAX_CONFIDENCE + 1).is_err());
+    }
+
+    #[test]
+    fn test_from_str() -> Fallible<()> {
+        assert_eq!("3".parse::<Confidence>()?, Confidence::new(3)?);
+        assert!("6".parse::<Confidence>().is_err());
+        assert!("high".parse::<Confidence>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize() -> Fallible<()> {
+        assert_eq!(serde_json::to_string(&Confidence::new(4)?)?, "4");
+        Ok(())
+    }
+}
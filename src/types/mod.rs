@@ -15,6 +15,9 @@
 pub mod aliases;
 pub mod card;
 pub mod card_hash;
+pub mod confidence;
 pub mod date;
+pub mod normalize;
 pub mod performance;
+pub mod retention;
 pub mod timestamp;
@@ -0,0 +1,315 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Normalization of card text before it is hashed, so that cosmetic changes
+//! to a card, like trailing spaces or Windows line endings, don't change its
+//! hash and reset its progress. Cards keep their original text: this is only
+//! used to compute hashes.
+
+use icu_normalizer::ComposingNormalizerBorrowed;
+
+/// Normalize text:
+///
+/// - Line endings are `\n`.
+/// - Runs of spaces and tabs within a line, after its indentation, are
+///   collapsed into a single space, and spaces and tabs at the end of a line
+///   are removed, except for a hard line break, which becomes two spaces.
+///   Blank lines are emptied.
+/// - Lines of fenced or indented code blocks are left as they are.
+/// - The text is in Unicode Normalization Form C.
+///
+/// Text that is already normalized is returned as is, so the hash of a card
+/// whose text is already normalized doesn't change.
+pub fn normalize_text(text: &str) -> String {
+    normalize_cloze(text, &[]).0
+}
+
+/// Normalize the text of a cloze card, and move its deletions, given as the
+/// byte positions of their first and last bytes, to where they are in the
+/// normalized text.
+pub fn normalize_cloze(text: &str, deletions: &[(usize, usize)]) -> (String, Vec<(usize, usize)>) {
+    let (spaced, offsets) = normalize_whitespace(text);
+    // Deletions as half-open byte ranges of the whitespace-normalized text.
+    let ranges: Vec<(usize, usize)> = deletions
+        .iter()
+        .map(|&(start, end)| {
+            let start = offsets[start.min(text.len())];
+            let end = offsets[(end + 1).min(text.len())];
+            (start, end.max(start))
+        })
+        .collect();
+    // Each piece of text between the ends of deletions is put in NFC on its
+    // own, so the new ends of the deletions are known.
+    let mut boundaries: Vec<usize> = ranges.iter().flat_map(|&(s, e)| [s, e]).collect();
+    boundaries.push(0);
+    boundaries.push(spaced.len());
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    let nfc = ComposingNormalizerBorrowed::new_nfc();
+    let mut normalized: String = String::with_capacity(spaced.len());
+    // The position of each boundary in the normalized text.
+    let mut positions: Vec<(usize, usize)> = vec![(0, 0)];
+    for pair in boundaries.windows(2) {
+        normalized.push_str(&nfc.normalize(&spaced[pair[0]..pair[1]]));
+        positions.push((pair[1], normalized.len()));
+    }
+    let position = |offset: usize| -> usize {
+        positions
+            .iter()
+            .find(|(from, _)| *from == offset)
+            .map_or(offset, |(_, to)| *to)
+    };
+    let deletions: Vec<(usize, usize)> = ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let start = position(start);
+            (start, position(end).max(start + 1) - 1)
+        })
+        .collect();
+    (normalized, deletions)
+}
+
+/// Normalize the line endings and whitespace of the text. Returns the new
+/// text, and for each byte position of the text, and the position one past
+/// its end, the matching position in the new text.
+fn normalize_whitespace(text: &str) -> (String, Vec<usize>) {
+    let mut output: String = String::with_capacity(text.len());
+    let mut offsets: Vec<usize> = vec![0; text.len() + 1];
+    // The marker and length of the fence of the code block we are in.
+    let mut fence: Option<(char, usize)> = None;
+    let mut start: usize = 0;
+    loop {
+        let end: usize = text[start..]
+            .find(['\r', '\n'])
+            .map_or(text.len(), |i| start + i);
+        let line: &str = &text[start..end];
+        let verbatim: bool = match fence {
+            Some((marker, length)) => {
+                if closes_fence(line, marker, length) {
+                    fence = None;
+                }
+                true
+            }
+            None => match opening_fence(line) {
+                Some(open) => {
+                    fence = Some(open);
+                    true
+                }
+                None => is_indented_code(line),
+            },
+        };
+        if verbatim {
+            copy_verbatim(line, start, &mut output, &mut offsets);
+        } else {
+            normalize_line(line, start, end < text.len(), &mut output, &mut offsets);
+        }
+        if end == text.len() {
+            break;
+        }
+        // A `\r\n` is a single line ending.
+        let ending: usize = if text[end..].starts_with("\r\n") {
+            2
+        } else {
+            1
+        };
+        offsets[end..end + ending].fill(output.len());
+        output.push('\n');
+        start = end + ending;
+    }
+    offsets[text.len()] = output.len();
+    (output, offsets)
+}
+
+/// Copy a line that starts at `base` in the text as is.
+fn copy_verbatim(line: &str, base: usize, output: &mut String, offsets: &mut [usize]) {
+    for (k, offset) in offsets[base..base + line.len()].iter_mut().enumerate() {
+        *offset = output.len() + k;
+    }
+    output.push_str(line);
+}
+
+/// Normalize a line of prose that starts at `base` in the text. Indentation
+/// is kept, since it's meaningful in Markdown, and runs of spaces and tabs
+/// after it are collapsed into a single space. Whitespace at the end of the
+/// line is removed, except for a hard line break, which is two spaces.
+fn normalize_line(
+    line: &str,
+    base: usize,
+    has_ending: bool,
+    output: &mut String,
+    offsets: &mut [usize],
+) {
+    let body: &str = line.trim_start_matches([' ', '\t']);
+    if body.trim_end_matches([' ', '\t']).is_empty() {
+        // A blank line.
+        offsets[base..base + line.len()].fill(output.len());
+        return;
+    }
+    let indent: usize = line.len() - body.len();
+    copy_verbatim(&line[..indent], base, output, offsets);
+    let base: usize = base + indent;
+    let mut chars = body.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == ' ' || c == '\t' {
+            // Find the end of the run of spaces and tabs.
+            let mut end: usize = i + 1;
+            while let Some(&(j, ' ' | '\t')) = chars.peek() {
+                end = j + 1;
+                chars.next();
+            }
+            offsets[base + i..base + end].fill(output.len());
+            if chars.peek().is_some() {
+                output.push(' ');
+            } else if has_ending && end - i >= 2 && !body[i..end].contains('\t') {
+                output.push_str("  ");
+            }
+        } else {
+            offsets[base + i..base + i + c.len_utf8()].fill(output.len());
+            output.push(c);
+        }
+    }
+}
+
+/// Whether the line is a line of an indented code block.
+fn is_indented_code(line: &str) -> bool {
+    line.starts_with('\t') || line.starts_with("    ")
+}
+
+/// If the line opens a fenced code block, its marker and length.
+fn opening_fence(line: &str) -> Option<(char, usize)> {
+    let (marker, length, rest) = fence_marker(line)?;
+    // The info string of a backtick fence can't contain backticks.
+    if marker == '`' && rest.contains('`') {
+        return None;
+    }
+    Some((marker, length))
+}
+
+/// Whether the line closes a fenced code block with the given marker and
+/// length.
+fn closes_fence(line: &str, marker: char, length: usize) -> bool {
+    match fence_marker(line) {
+        Some((m, l, rest)) => m == marker && l >= length && rest.trim().is_empty(),
+        None => false,
+    }
+}
+
+/// The marker and length of the run of at least three backticks or tildes
+/// that starts the line, after at most three spaces, and the rest of the
+/// line.
+fn fence_marker(line: &str) -> Option<(char, usize, &str)> {
+    let rest: &str = line.trim_start_matches(' ');
+    if line.len() - rest.len() > 3 {
+        return None;
+    }
+    let marker: char = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let after: &str = rest.trim_start_matches(marker);
+    let length: usize = rest.len() - after.len();
+    if length < 3 {
+        return None;
+    }
+    Some((marker, length, after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_text() {
+        assert_eq!(
+            normalize_text("What is  the\tcapital?"),
+            "What is the capital?"
+        );
+        assert_eq!(
+            normalize_text("line one \r\nline two\t\n"),
+            "line one\nline two\n"
+        );
+        assert_eq!(normalize_text("a\rb"), "a\nb");
+        assert_eq!(normalize_text("a\n \t\nb"), "a\n\nb");
+        // "é" as "e" and a combining acute accent.
+        assert_eq!(normalize_text("caf\u{0065}\u{0301}"), "caf\u{00e9}");
+        // Non-breaking spaces are not collapsed.
+        assert_eq!(
+            normalize_text("\u{00a0}non-breaking\u{00a0}\u{00a0}space"),
+            "\u{00a0}non-breaking\u{00a0}\u{00a0}space"
+        );
+    }
+
+    /// Indentation is kept, so cards that differ only in their indentation
+    /// have different text.
+    #[test]
+    fn test_normalize_indentation() {
+        assert_eq!(
+            normalize_text("-  item\n  -  nested  item"),
+            "- item\n  - nested item"
+        );
+        assert_ne!(normalize_text("a\n  - b"), normalize_text("a\n - b"));
+    }
+
+    #[test]
+    fn test_normalize_hard_break() {
+        assert_eq!(normalize_text("one   \ntwo"), "one  \ntwo");
+        assert_eq!(normalize_text("one  \ntwo"), "one  \ntwo");
+        assert_eq!(normalize_text("one \ntwo"), "one\ntwo");
+        assert_eq!(normalize_text("one\t\t\ntwo"), "one\ntwo");
+        // There is no line to break at the end of the text.
+        assert_eq!(normalize_text("one  "), "one");
+    }
+
+    /// Code blocks are left alone, except for their line endings.
+    #[test]
+    fn test_normalize_code() {
+        let fenced = "```\nlet  x = 1;  \n```\na  b";
+        assert_eq!(normalize_text(fenced), "```\nlet  x = 1;  \n```\na b");
+        let tildes = "~~~~ rust\n~~~\nx  =  1\n~~~~\na  b";
+        assert_eq!(normalize_text(tildes), "~~~~ rust\n~~~\nx  =  1\n~~~~\na b");
+        assert_eq!(
+            normalize_text("Code:\r\n\r\n    x  =  1\r\n\tdone  "),
+            "Code:\n\n    x  =  1\n\tdone  "
+        );
+    }
+
+    /// Normalized text is left alone.
+    #[test]
+    fn test_normalized_text() {
+        let text = "Q: café\n\n- one\n- two";
+        assert_eq!(normalize_text(text), text);
+        assert_eq!(
+            normalize_cloze(text, &[(3, 7), (10, 10)]),
+            (text.to_string(), vec![(3, 7), (10, 10)])
+        );
+    }
+
+    #[test]
+    fn test_normalize_cloze() {
+        let text = "Paris  is the capital of  France .";
+        assert_eq!(&text[0..=4], "Paris");
+        assert_eq!(&text[26..=31], "France");
+        let (normalized, deletions) = normalize_cloze(text, &[(0, 4), (26, 31)]);
+        assert_eq!(normalized, "Paris is the capital of France .");
+        assert_eq!(deletions, vec![(0, 4), (24, 29)]);
+        assert_eq!(&normalized[24..=29], "France");
+    }
+
+    #[test]
+    fn test_normalize_cloze_unicode() {
+        // The deletion is "e\u{0301}", which is three bytes.
+        let text = "caf\u{0065}\u{0301}  au lait";
+        let (normalized, deletions) = normalize_cloze(text, &[(3, 5)]);
+        assert_eq!(normalized, "caf\u{00e9} au lait");
+        assert_eq!(deletions, vec![(3, 4)]);
+        assert_eq!(&normalized[3..=4], "\u{00e9}");
+    }
+}
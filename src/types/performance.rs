@@ -12,9 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
 use chrono::Duration;
 use chrono::NaiveDate;
 
+use crate::error::ErrorReport;
+use crate::error::Fallible;
+use crate::error::fail;
 use crate::fsrs::Difficulty;
 use crate::fsrs::Grade;
 use crate::fsrs::Interval;
@@ -26,18 +33,158 @@ use crate::fsrs::interval;
 use crate::fsrs::new_difficulty;
 use crate::fsrs::new_stability;
 use crate::fsrs::retrievability;
+use crate::rng::TinyRng;
 use crate::types::date::Date;
 use crate::types::timestamp::Timestamp;
 
-/// The desired recall probability.
-const TARGET_RECALL: f64 = 0.9;
+/// The default desired retention: the probability of recalling a card when
+/// it comes due.
+pub const DEFAULT_RETENTION: Recall = 0.9;
 
-/// The minimum review interval in days.
+/// The default minimum review interval in days.
 const MIN_INTERVAL: f64 = 1.0;
 
-/// The maximum review interval in days.
+/// The default maximum review interval in days.
 const MAX_INTERVAL: f64 = 256.0;
 
+/// The default interval fuzz, in percent.
+const FUZZ_PERCENT: f64 = 5.0;
+
+/// The largest interval fuzz allowed, in percent.
+const MAX_FUZZ_PERCENT: f64 = 25.0;
+
+/// The length of a review interval given on the command line: a number of
+/// days, weeks, or years, like `3d`, `2w`, or `1y`. A bare number is a number
+/// of days. Reviews are scheduled by date, so intervals are whole days, and
+/// there is no unit shorter than a day.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IntervalLength(u64);
+
+impl IntervalLength {
+    pub fn days(self) -> u64 {
+        self.0
+    }
+}
+
+impl Display for IntervalLength {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}d", self.0)
+    }
+}
+
+impl FromStr for IntervalLength {
+    type Err = ErrorReport;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            ErrorReport::new(format!(
+                "Invalid interval '{s}': expected a number of days, weeks, or years, like '3d', '2w', or '1y'. Intervals are whole days."
+            ))
+        };
+        let split: usize = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let number: u64 = number.parse().map_err(|_| invalid())?;
+        let days_per_unit: u64 = match unit {
+            "" | "d" => 1,
+            "w" => 7,
+            "y" => 365,
+            _ => return Err(invalid()),
+        };
+        number
+            .checked_mul(days_per_unit)
+            .map(IntervalLength)
+            .ok_or_else(invalid)
+    }
+}
+
+/// The range review intervals are clamped to, in days.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IntervalBounds {
+    min: f64,
+    max: f64,
+}
+
+impl IntervalBounds {
+    /// Bounds with the given minimum and maximum, in days, or the defaults.
+    pub fn new(min: Option<u64>, max: Option<u64>) -> Fallible<Self> {
+        let min: f64 = min.map_or(MIN_INTERVAL, |min| min as f64);
+        let max: f64 = max.map_or(MAX_INTERVAL, |max| max as f64);
+        if min < 1.0 {
+            return fail("the minimum interval must be at least one day.");
+        }
+        if min > max {
+            return fail(format!(
+                "the minimum interval ({min} days) is greater than the maximum interval ({max} days)."
+            ));
+        }
+        Ok(Self { min, max })
+    }
+}
+
+impl IntervalBounds {
+    /// The number of days until the next review of a card with the given
+    /// stability, at the desired retention, without fuzz.
+    pub fn interval_days(&self, stability: Stability, desired_retention: Recall) -> f64 {
+        interval(desired_retention, stability)
+            .round()
+            .clamp(self.min, self.max)
+    }
+}
+
+impl Default for IntervalBounds {
+    fn default() -> Self {
+        Self {
+            min: MIN_INTERVAL,
+            max: MAX_INTERVAL,
+        }
+    }
+}
+
+/// Random jitter applied to review intervals, so that cards learned together
+/// do not keep coming due on the same day.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IntervalFuzz {
+    /// The largest change to the interval, as a fraction of the interval.
+    ratio: f64,
+    seed: u64,
+}
+
+impl IntervalFuzz {
+    /// Fuzz of the given magnitude, in percent, or the default.
+    pub fn new(percent: Option<f64>, seed: u64) -> Fallible<Self> {
+        let percent: f64 = percent.unwrap_or(FUZZ_PERCENT);
+        if !(0.0..=MAX_FUZZ_PERCENT).contains(&percent) {
+            return fail(format!(
+                "the interval fuzz must be between 0 and {MAX_FUZZ_PERCENT} percent."
+            ));
+        }
+        Ok(Self {
+            ratio: percent / 100.0,
+            seed,
+        })
+    }
+
+    /// No fuzz at all: intervals are used as computed.
+    #[cfg(test)]
+    pub fn none() -> Self {
+        Self {
+            ratio: 0.0,
+            seed: 0,
+        }
+    }
+
+    /// The factor to multiply an interval by. The same seed and key always
+    /// give the same factor, which is within `1 ± ratio`.
+    pub fn factor(&self, key: u64) -> f64 {
+        if self.ratio == 0.0 {
+            return 1.0;
+        }
+        let mut rng = TinyRng::from_seed(self.seed ^ key);
+        let unit: f64 = rng.next_u32() as f64 / u32::MAX as f64;
+        1.0 + self.ratio * (2.0 * unit - 1.0)
+    }
+}
+
 /// Represents performance information for a card.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Performance {
@@ -62,7 +209,7 @@ pub struct ReviewedPerformance {
     /// The card's difficulty (an FSRS parameter).
     pub difficulty: Difficulty,
     /// The FSRS-calculated interval in hours until the next review. This is
-    /// the raw interval, before any rounding and clamping.
+    /// the raw interval, before any fuzz, rounding, and clamping.
     pub interval_raw: Interval,
     /// The FSRS interval as an integer number of days.
     pub interval_days: i64,
@@ -76,6 +223,9 @@ pub fn update_performance(
     perf: Performance,
     grade: Grade,
     reviewed_at: Timestamp,
+    bounds: IntervalBounds,
+    desired_retention: Recall,
+    fuzz: f64,
 ) -> ReviewedPerformance {
     let today: NaiveDate = reviewed_at.date().into_inner();
     let (stability, difficulty, review_count): (Stability, Difficulty, usize) = match perf {
@@ -95,9 +245,10 @@ pub fn update_performance(
             (stability, difficulty, review_count)
         }
     };
-    let interval_raw: Interval = interval(TARGET_RECALL, stability);
-    let interval_rounded: Interval = interval_raw.round();
-    let interval_clamped: Interval = interval_rounded.clamp(MIN_INTERVAL, MAX_INTERVAL);
+    let interval_raw: Interval = interval(desired_retention, stability);
+    let interval_fuzzed: Interval = interval_raw * fuzz;
+    let interval_rounded: Interval = interval_fuzzed.round();
+    let interval_clamped: Interval = interval_rounded.clamp(bounds.min, bounds.max);
     let interval_days: i64 = interval_clamped as i64;
     let interval_duration: Duration = Duration::days(interval_days);
     let due_date: Date = Date::new(today + interval_duration);
@@ -124,14 +275,28 @@ mod tests {
     fn test_new() {
         assert!(Performance::New.is_new());
         let reviewed_at = Timestamp::now();
-        let reviewed_perf = update_performance(Performance::New, Grade::Good, reviewed_at);
+        let reviewed_perf = update_performance(
+            Performance::New,
+            Grade::Good,
+            reviewed_at,
+            IntervalBounds::default(),
+            DEFAULT_RETENTION,
+            1.0,
+        );
         assert!(!Performance::Reviewed(reviewed_perf).is_new());
     }
 
     #[test]
     fn test_update_new_card() {
         let reviewed_at = Timestamp::now();
-        let result = update_performance(Performance::New, Grade::Good, reviewed_at);
+        let result = update_performance(
+            Performance::New,
+            Grade::Good,
+            reviewed_at,
+            IntervalBounds::default(),
+            DEFAULT_RETENTION,
+            1.0,
+        );
         let ReviewedPerformance {
             last_reviewed_at,
             stability,
@@ -169,6 +334,9 @@ mod tests {
             Performance::Reviewed(initial_perf),
             Grade::Easy,
             reviewed_at,
+            IntervalBounds::default(),
+            DEFAULT_RETENTION,
+            1.0,
         );
         let ReviewedPerformance {
             last_reviewed_at,
@@ -186,4 +354,129 @@ mod tests {
         assert_eq!(interval_days, 26);
         assert_eq!(review_count, 2);
     }
+
+    #[test]
+    fn test_min_interval() -> Fallible<()> {
+        // A forgotten new card would be due the next day.
+        let bounds = IntervalBounds::new(Some(3), None)?;
+        let result = update_performance(
+            Performance::New,
+            Grade::Forgot,
+            Timestamp::now(),
+            bounds,
+            DEFAULT_RETENTION,
+            1.0,
+        );
+        assert!(result.interval_raw < 1.0);
+        assert_eq!(result.interval_days, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_interval() -> Fallible<()> {
+        // An easy new card would be due in about two weeks.
+        let reviewed_at = Timestamp::now();
+        let bounds = IntervalBounds::new(None, Some(7))?;
+        let result = update_performance(
+            Performance::New,
+            Grade::Easy,
+            reviewed_at,
+            bounds,
+            DEFAULT_RETENTION,
+            1.0,
+        );
+        assert!(result.interval_raw > 7.0);
+        assert_eq!(result.interval_days, 7);
+        let expected = Date::new(reviewed_at.date().into_inner() + Duration::days(7));
+        assert_eq!(result.due_date, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_desired_retention() {
+        let bounds = IntervalBounds::default();
+        // At the default retention, the interval is the stability.
+        assert_eq!(bounds.interval_days(10.0, DEFAULT_RETENTION), 10.0);
+        // Higher retention means shorter intervals.
+        assert!(bounds.interval_days(10.0, 0.95) < 10.0);
+        assert!(bounds.interval_days(10.0, 0.8) > 10.0);
+        let reviewed_at = Timestamp::now();
+        let low = update_performance(Performance::New, Grade::Easy, reviewed_at, bounds, 0.8, 1.0);
+        let high = update_performance(
+            Performance::New,
+            Grade::Easy,
+            reviewed_at,
+            bounds,
+            0.95,
+            1.0,
+        );
+        assert!(low.interval_days > high.interval_days);
+    }
+
+    #[test]
+    fn test_invalid_interval_bounds() {
+        assert!(IntervalBounds::new(Some(0), None).is_err());
+        assert!(IntervalBounds::new(Some(10), Some(5)).is_err());
+        assert!(IntervalBounds::new(Some(5), Some(5)).is_ok());
+    }
+
+    #[test]
+    fn test_interval_length() -> Fallible<()> {
+        assert_eq!("3".parse::<IntervalLength>()?.days(), 3);
+        assert_eq!("3d".parse::<IntervalLength>()?.days(), 3);
+        assert_eq!("2w".parse::<IntervalLength>()?.days(), 14);
+        assert_eq!("1y".parse::<IntervalLength>()?.days(), 365);
+        assert!("12h".parse::<IntervalLength>().is_err());
+        assert!("d".parse::<IntervalLength>().is_err());
+        assert!("-1d".parse::<IntervalLength>().is_err());
+        assert!("1.5d".parse::<IntervalLength>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_interval_fuzz() -> Fallible<()> {
+        let fuzz = IntervalFuzz::new(Some(10.0), 42)?;
+        let mut factors = Vec::new();
+        for key in 0..1000 {
+            let factor = fuzz.factor(key);
+            assert!((0.9..=1.1).contains(&factor));
+            // The same seed and key give the same factor.
+            assert_eq!(factor, IntervalFuzz::new(Some(10.0), 42)?.factor(key));
+            factors.push(factor);
+        }
+        // The factors are spread over the whole range.
+        assert!(factors.iter().any(|f| *f < 0.95));
+        assert!(factors.iter().any(|f| *f > 1.05));
+        // A different seed gives different factors.
+        let other = IntervalFuzz::new(Some(10.0), 43)?;
+        assert!((0..1000).any(|key| other.factor(key) != factors[key as usize]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fuzzed_interval() -> Fallible<()> {
+        // An easy new card would be due in about two weeks.
+        let result = update_performance(
+            Performance::New,
+            Grade::Easy,
+            Timestamp::now(),
+            IntervalBounds::default(),
+            DEFAULT_RETENTION,
+            1.1,
+        );
+        let fuzzed = (result.interval_raw * 1.1).round() as i64;
+        assert_eq!(result.interval_days, fuzzed);
+        assert!(result.interval_days > result.interval_raw.round() as i64);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_interval_fuzz() -> Fallible<()> {
+        assert!(IntervalFuzz::new(Some(-1.0), 0).is_err());
+        assert!(IntervalFuzz::new(Some(30.0), 0).is_err());
+        assert!(IntervalFuzz::new(Some(0.0), 0).is_ok());
+        assert_eq!(IntervalFuzz::new(Some(0.0), 7)?.factor(1), 1.0);
+        assert_eq!(IntervalFuzz::none().factor(1), 1.0);
+        Ok(())
+    }
 }
@@ -0,0 +1,73 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+/// A card's measured retention, computed from its review history.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Retention {
+    /// The number of times the card was reviewed.
+    reviews: usize,
+    /// The number of reviews where the card was not forgotten.
+    successes: usize,
+}
+
+impl Retention {
+    /// Construct a [`Retention`]. Returns `None` if the card has no reviews,
+    /// since retention is meaningless without history.
+    pub fn new(reviews: usize, successes: usize) -> Option<Self> {
+        if reviews == 0 {
+            None
+        } else {
+            Some(Self { reviews, successes })
+        }
+    }
+
+    /// The fraction of reviews where the card was remembered, in `[0, 1]`.
+    pub fn rate(self) -> f64 {
+        self.successes as f64 / self.reviews as f64
+    }
+}
+
+impl Display for Retention {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let percent = (self.rate() * 100.0).round();
+        let noun = if self.reviews == 1 {
+            "review"
+        } else {
+            "reviews"
+        };
+        write!(f, "{percent}% ({} {noun})", self.reviews)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_reviews() {
+        assert_eq!(Retention::new(0, 0), None);
+    }
+
+    #[test]
+    fn test_rate() {
+        let retention = Retention::new(4, 3).unwrap();
+        assert_eq!(retention.rate(), 0.75);
+        assert_eq!(retention.to_string(), "75% (4 reviews)");
+        let retention = Retention::new(1, 0).unwrap();
+        assert_eq!(retention.to_string(), "0% (1 review)");
+    }
+}
@@ -34,7 +34,6 @@ use crate::types::date::Date;
 pub struct Timestamp(NaiveDateTime);
 
 impl Timestamp {
-    #[cfg(test)]
     pub fn new(ndt: NaiveDateTime) -> Self {
         Self(ndt.trunc_subsecs(3))
     }
@@ -12,23 +12,149 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::env::temp_dir;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::time::Duration;
 
+use tokio::net::TcpListener;
 use tokio::net::TcpStream;
+use tokio::spawn;
 use tokio::time::sleep;
 
 use crate::error::Fallible;
+use crate::error::fail;
+use tokio::time::timeout;
 
 // max-age is one week in seconds.
 pub const CACHE_CONTROL_IMMUTABLE: &str = "public, max-age=604800, immutable";
 
+/// How long to wait for a server to start accepting connections.
+pub const SERVER_START_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Wait until a server accepts connections on the given host and port, or
+/// fail after `SERVER_START_TIMEOUT`.
 pub async fn wait_for_server(host: &str, port: u16) -> Fallible<()> {
-    loop {
-        if let Ok(stream) = TcpStream::connect(format!("{host}:{port}")).await {
-            drop(stream);
-            break;
+    let connect = async {
+        loop {
+            if let Ok(stream) = TcpStream::connect(format!("{host}:{port}")).await {
+                drop(stream);
+                break;
+            }
+            sleep(Duration::from_millis(1)).await;
+        }
+    };
+    match timeout(SERVER_START_TIMEOUT, connect).await {
+        Ok(()) => Ok(()),
+        Err(_) => fail(format!(
+            "the server on {host}:{port} did not start in time."
+        )),
+    }
+}
+
+/// Bind a server to the given host and port. If the port is 0, or it is
+/// already in use, the operating system picks a free port: the caller should
+/// use the listener's local address for the port actually bound to.
+pub async fn bind_listener(host: &str, port: u16) -> Fallible<TcpListener> {
+    log::debug!("Starting server on {host}:{port}");
+    match TcpListener::bind(format!("{host}:{port}")).await {
+        Ok(listener) => return Ok(listener),
+        Err(e) if port != 0 && e.kind() == ErrorKind::AddrInUse => {
+            println!("Port {port} is in use, so a free port was chosen instead.");
+        }
+        Err(e) => return fail(format!("could not start the server on {host}:{port}: {e}")),
+    }
+    match TcpListener::bind(format!("{host}:0")).await {
+        Ok(listener) => Ok(listener),
+        Err(e) => fail(format!("could not start the server on {host}: {e}")),
+    }
+}
+
+/// A path for a temporary file, unique to this process and call, so that
+/// concurrent commands and tests don't overwrite each other's files.
+pub fn temp_file_path(name: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let count: usize = COUNTER.fetch_add(1, Ordering::Relaxed);
+    temp_dir().join(format!("hashcards-{}-{count}-{name}", std::process::id()))
+}
+
+/// Start a separate task to open the browser once the server is up.
+pub fn open_browser_when_ready(host: String, port: u16) {
+    spawn(async move {
+        match wait_for_server(&host, port).await {
+            Ok(_) => {
+                let _ = open::that(format!("http://{host}:{port}/"));
+            }
+            Err(e) => {
+                eprintln!("Failed to connect to server: {e}");
+                exit(-1)
+            }
         }
-        sleep(Duration::from_millis(1)).await;
+    });
+}
+
+/// Match `text` against a glob `pattern`, where `*` matches any sequence of
+/// characters and `?` matches any single character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // The position of the last `*` in the pattern, and the position in the
+    // text it was matched against, for backtracking.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the `*` absorb one more character.
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bind_listener_busy_port() -> Fallible<()> {
+        let busy = TcpListener::bind("127.0.0.1:0").await?;
+        let port: u16 = busy.local_addr()?.port();
+        let listener = bind_listener("127.0.0.1", port).await?;
+        let bound: u16 = listener.local_addr()?.port();
+        assert_ne!(bound, port);
+        assert_ne!(bound, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bind_listener_invalid_host() {
+        assert!(bind_listener("not a host", 0).await.is_err());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("Geology", "Geology"));
+        assert!(!glob_match("Geology", "Geology 2"));
+        assert!(glob_match("Geo*", "Geology"));
+        assert!(glob_match("*logy", "Geology"));
+        assert!(glob_match("G*l*y", "Geology"));
+        assert!(glob_match("Ge?logy", "Geology"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("?", ""));
+        assert!(!glob_match("Bio*", "Geology"));
+        assert!(glob_match("Ch?.*", "Ch1.Notes"));
     }
-    Ok(())
 }
@@ -0,0 +1,365 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A decoder for DEFLATE data (RFC 1951), the compression used by zip files.
+//! It follows the structure of zlib's `puff.c`: simple rather than fast,
+//! which is plenty for reading an Anki package once.
+
+use crate::error::Fallible;
+use crate::error::fail;
+
+/// The maximum number of bits in a Huffman code.
+const MAX_BITS: usize = 15;
+
+/// The base lengths of the length codes 257 to 285, and their extra bits.
+const LENGTH_BASE: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// The base distances of the distance codes 0 to 29, and their extra bits.
+const DISTANCE_BASE: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u32; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// The order in which the lengths of the code length code are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const TOO_LONG: &str = "Invalid compressed data: longer than expected.";
+
+/// Decompress raw DEFLATE data, without a zlib or gzip header. Fails if the
+/// output would be longer than `limit` bytes, so a small, malicious input
+/// can't use up the memory.
+pub fn inflate(data: &[u8], limit: usize) -> Fallible<Vec<u8>> {
+    let mut input = BitReader::new(data);
+    let mut output: Vec<u8> = Vec::new();
+    loop {
+        let last: bool = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => stored(&mut input, &mut output, limit)?,
+            1 => {
+                let (lengths, distances) = fixed_codes()?;
+                codes(&mut input, &mut output, limit, &lengths, &distances)?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut input)?;
+                codes(&mut input, &mut output, limit, &lengths, &distances)?;
+            }
+            _ => return fail("Invalid compressed data: unknown block type."),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+/// Reads bits from the input, least significant bit first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, need: u32) -> Fallible<u32> {
+        while self.count < need {
+            let Some(byte) = self.data.get(self.position) else {
+                return fail("Invalid compressed data: unexpected end of input.");
+            };
+            self.buffer |= u32::from(*byte) << self.count;
+            self.position += 1;
+            self.count += 8;
+        }
+        let value: u32 = self.buffer & ((1 << need) - 1);
+        self.buffer >>= need;
+        self.count -= need;
+        Ok(value)
+    }
+
+    /// Discard the bits up to the next byte boundary.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// A canonical Huffman code: the number of codes of each length, and the
+/// symbols, ordered by code.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Build a code from the code length of each symbol. Incomplete codes
+    /// are allowed, since a single distance code is.
+    fn new(lengths: &[u8]) -> Fallible<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for length in lengths {
+            counts[usize::from(*length)] += 1;
+        }
+        // Check that the code isn't over-subscribed.
+        let mut left: i32 = 1;
+        for count in &counts[1..] {
+            left <<= 1;
+            left -= i32::from(*count);
+            if left < 0 {
+                return fail("Invalid compressed data: over-subscribed code.");
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols: Vec<u16> = vec![0; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                let offset: &mut u16 = &mut offsets[usize::from(*length)];
+                symbols[usize::from(*offset)] = symbol as u16;
+                *offset += 1;
+            }
+        }
+        Ok(Self { counts, symbols })
+    }
+
+    /// Decode the next symbol, reading the code bit by bit.
+    fn decode(&self, input: &mut BitReader) -> Fallible<usize> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..=MAX_BITS {
+            code |= input.bits(1)? as i32;
+            let count: i32 = i32::from(self.counts[length]);
+            if code - count < first {
+                return Ok(usize::from(self.symbols[(index + code - first) as usize]));
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        fail("Invalid compressed data: invalid code.")
+    }
+}
+
+/// Copy a stored block, which is not compressed.
+fn stored(input: &mut BitReader, output: &mut Vec<u8>, limit: usize) -> Fallible<()> {
+    input.align();
+    let header: &[u8] = input
+        .data
+        .get(input.position..input.position + 4)
+        .unwrap_or_default();
+    if header.len() < 4 {
+        return fail("Invalid compressed data: unexpected end of input.");
+    }
+    let length: u16 = u16::from_le_bytes([header[0], header[1]]);
+    let complement: u16 = u16::from_le_bytes([header[2], header[3]]);
+    if length != !complement {
+        return fail("Invalid compressed data: invalid stored block length.");
+    }
+    let start: usize = input.position + 4;
+    let Some(block) = input.data.get(start..start + usize::from(length)) else {
+        return fail("Invalid compressed data: unexpected end of input.");
+    };
+    if output.len() + block.len() > limit {
+        return fail(TOO_LONG);
+    }
+    output.extend_from_slice(block);
+    input.position = start + usize::from(length);
+    Ok(())
+}
+
+/// Decode a block compressed with the given codes, up to its end code.
+fn codes(
+    input: &mut BitReader,
+    output: &mut Vec<u8>,
+    limit: usize,
+    lengths: &Huffman,
+    distances: &Huffman,
+) -> Fallible<()> {
+    loop {
+        let symbol: usize = lengths.decode(input)?;
+        if symbol < 256 {
+            if output.len() >= limit {
+                return fail(TOO_LONG);
+            }
+            output.push(symbol as u8);
+        } else if symbol == 256 {
+            return Ok(());
+        } else {
+            let symbol: usize = symbol - 257;
+            if symbol >= LENGTH_BASE.len() {
+                return fail("Invalid compressed data: invalid length code.");
+            }
+            let length: usize = LENGTH_BASE[symbol] + input.bits(LENGTH_EXTRA[symbol])? as usize;
+            let symbol: usize = distances.decode(input)?;
+            if symbol >= DISTANCE_BASE.len() {
+                return fail("Invalid compressed data: invalid distance code.");
+            }
+            let distance: usize =
+                DISTANCE_BASE[symbol] + input.bits(DISTANCE_EXTRA[symbol])? as usize;
+            if distance > output.len() {
+                return fail("Invalid compressed data: distance too far back.");
+            }
+            if output.len() + length > limit {
+                return fail(TOO_LONG);
+            }
+            // The copy can overlap the bytes it produces.
+            let start: usize = output.len() - distance;
+            for index in start..start + length {
+                output.push(output[index]);
+            }
+        }
+    }
+}
+
+/// The fixed codes of block type 1.
+fn fixed_codes() -> Fallible<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+/// Read the codes of a block of type 2, which are themselves compressed.
+fn dynamic_codes(input: &mut BitReader) -> Fallible<(Huffman, Huffman)> {
+    let length_count: usize = input.bits(5)? as usize + 257;
+    let distance_count: usize = input.bits(5)? as usize + 1;
+    let code_length_count: usize = input.bits(4)? as usize + 4;
+    if length_count > 286 || distance_count > 30 {
+        return fail("Invalid compressed data: too many codes.");
+    }
+    let mut code_lengths = [0u8; 19];
+    for index in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[*index] = input.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+    let mut lengths: Vec<u8> = Vec::new();
+    while lengths.len() < length_count + distance_count {
+        let symbol: usize = code_length_code.decode(input)?;
+        let (length, repeat): (u8, u32) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Some(previous) => (*previous, 3 + input.bits(2)?),
+                None => return fail("Invalid compressed data: repeat with no first length."),
+            },
+            17 => (0, 3 + input.bits(3)?),
+            _ => (0, 11 + input.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(length);
+        }
+    }
+    if lengths.len() > length_count + distance_count {
+        return fail("Invalid compressed data: too many lengths.");
+    }
+    if lengths[256] == 0 {
+        return fail("Invalid compressed data: no end-of-block code.");
+    }
+    Ok((
+        Huffman::new(&lengths[..length_count])?,
+        Huffman::new(&lengths[length_count..])?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_stored_block() -> Fallible<()> {
+        assert_eq!(
+            inflate(&from_hex("010600f9ff73746f726564"), usize::MAX)?,
+            b"stored"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fixed_codes() -> Fallible<()> {
+        assert_eq!(
+            inflate(&from_hex("4b4c4a4e042300"), usize::MAX)?,
+            b"abcabcabc"
+        );
+        let text = "hashcards hashcards hashcards: a plain text-based spaced repetition system. "
+            .repeat(3);
+        let data = from_hex(
+            "cb482cce484e2c4a2956c8c0645929242a14e42466e62994a45694e8262516a7a6281417242603a9a2d482d492cc92ccfc3c85e2cae292d45c3d6c260c0ab300",
+        );
+        assert_eq!(inflate(&data, usize::MAX)?, text.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dynamic_codes() -> Fallible<()> {
+        // Letters drawn with a skewed distribution, by a linear congruential
+        // generator, and compressed by zlib.
+        let alphabet = b"aaaaaaaabbbbccd";
+        let mut state: u64 = 0;
+        let mut expected: Vec<u8> = Vec::new();
+        for _ in 0..400 {
+            state = (state * 1103515245 + 12345) % (1 << 31);
+            expected.push(alphabet[(state % 15) as usize]);
+        }
+        let data = from_hex(
+            "45508b15c430089a5560ff194e205e6b625fd5f2716606d0f8352ffb10fde05eb7e5b1cc121c97c9fed2b2a30568a39d03cc352280e463319016282c9d939e083c802415dd93df2394782959cc2af9e871e351a7927309d7d2ba8e076d449b55e0d41a63bb3a0b06b26f448737523abe2e8ec8e0f0d15bd7bf35b784ec61c8e525ce63c8303f",
+        );
+        assert_eq!(inflate(&data, usize::MAX)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_limit() -> Fallible<()> {
+        let data = from_hex("4b4c4a4e042300");
+        assert_eq!(inflate(&data, 9)?, b"abcabcabc");
+        assert!(inflate(&data, 8).is_err());
+        assert!(inflate(&from_hex("010600f9ff73746f726564"), 5).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert!(inflate(&from_hex("4b4c4a"), usize::MAX).is_err());
+        assert!(inflate(&[0x07], usize::MAX).is_err());
+    }
+}
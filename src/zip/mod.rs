@@ -0,0 +1,273 @@
+// Copyright 2025 Fernando Borretti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading and writing zip archives, as far as Anki packages need: entries
+//! are read stored or compressed with DEFLATE, and written stored.
+
+mod inflate;
+
+use std::collections::HashMap;
+
+use crate::error::Fallible;
+use crate::error::fail;
+use crate::zip::inflate::inflate;
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x02014b50;
+const LOCAL_FILE_HEADER: u32 = 0x04034b50;
+
+/// The compression methods.
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// The largest entry that is decompressed, 1 GiB.
+const MAX_ENTRY_SIZE: usize = 1 << 30;
+
+/// An entry of the archive, not yet decompressed.
+struct Entry {
+    method: u16,
+    compressed_size: usize,
+    size: usize,
+    header_offset: usize,
+}
+
+/// A zip archive, read into memory.
+pub struct ZipArchive {
+    data: Vec<u8>,
+    entries: HashMap<String, Entry>,
+}
+
+impl ZipArchive {
+    pub fn new(data: Vec<u8>) -> Fallible<Self> {
+        let end: usize = find_end_of_central_directory(&data)?;
+        let count: usize = usize::from(read_u16(&data, end + 10)?);
+        let mut offset: usize = read_u32(&data, end + 16)? as usize;
+        let mut entries: HashMap<String, Entry> = HashMap::new();
+        for _ in 0..count {
+            if read_u32(&data, offset)? != CENTRAL_DIRECTORY_HEADER {
+                return fail("Invalid zip file: bad central directory.");
+            }
+            let method: u16 = read_u16(&data, offset + 10)?;
+            let compressed_size: u32 = read_u32(&data, offset + 20)?;
+            let size: u32 = read_u32(&data, offset + 24)?;
+            let name_length: usize = usize::from(read_u16(&data, offset + 28)?);
+            let extra_length: usize = usize::from(read_u16(&data, offset + 30)?);
+            let comment_length: usize = usize::from(read_u16(&data, offset + 32)?);
+            let header_offset: u32 = read_u32(&data, offset + 42)?;
+            if compressed_size == u32::MAX || size == u32::MAX || header_offset == u32::MAX {
+                return fail("Zip files larger than 4 GiB are not supported.");
+            }
+            let name: &[u8] = slice(&data, offset + 46, name_length)?;
+            let name: String = String::from_utf8_lossy(name).into_owned();
+            entries.insert(
+                name,
+                Entry {
+                    method,
+                    compressed_size: compressed_size as usize,
+                    size: size as usize,
+                    header_offset: header_offset as usize,
+                },
+            );
+            offset += 46 + name_length + extra_length + comment_length;
+        }
+        Ok(Self { data, entries })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// Read and decompress an entry. `None` if there is no such entry.
+    /// Entries larger than [`MAX_ENTRY_SIZE`], or that decompress to more
+    /// than their recorded size, are an error.
+    pub fn read(&self, name: &str) -> Fallible<Option<Vec<u8>>> {
+        let Some(entry) = self.entries.get(name) else {
+            return Ok(None);
+        };
+        if entry.size > MAX_ENTRY_SIZE {
+            return fail(format!(
+                "The entry '{name}' is too large to read: {} bytes.",
+                entry.size
+            ));
+        }
+        let offset: usize = entry.header_offset;
+        if read_u32(&self.data, offset)? != LOCAL_FILE_HEADER {
+            return fail(format!("Invalid zip file: bad header for '{name}'."));
+        }
+        let name_length: usize = usize::from(read_u16(&self.data, offset + 26)?);
+        let extra_length: usize = usize::from(read_u16(&self.data, offset + 28)?);
+        let start: usize = offset + 30 + name_length + extra_length;
+        let compressed: &[u8] = slice(&self.data, start, entry.compressed_size)?;
+        let contents: Vec<u8> = match entry.method {
+            STORED => compressed.to_vec(),
+            DEFLATED => inflate(compressed, entry.size)?,
+            method => {
+                return fail(format!(
+                    "Unsupported compression method {method} for '{name}'."
+                ));
+            }
+        };
+        if contents.len() != entry.size {
+            return fail(format!("Invalid zip file: wrong size for '{name}'."));
+        }
+        Ok(Some(contents))
+    }
+}
+
+/// The end of central directory record is at the end of the file, followed
+/// by a comment of up to 64 KiB.
+fn find_end_of_central_directory(data: &[u8]) -> Fallible<usize> {
+    let last: usize = match data.len().checked_sub(22) {
+        Some(last) => last,
+        None => return fail("Invalid zip file: too short."),
+    };
+    let first: usize = last.saturating_sub(u16::MAX as usize);
+    for offset in (first..=last).rev() {
+        if read_u32(data, offset)? == END_OF_CENTRAL_DIRECTORY {
+            return Ok(offset);
+        }
+    }
+    fail("Invalid zip file: no central directory.")
+}
+
+fn slice(data: &[u8], offset: usize, length: usize) -> Fallible<&[u8]> {
+    match data.get(offset..offset + length) {
+        Some(slice) => Ok(slice),
+        None => fail("Invalid zip file: unexpected end of file."),
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Fallible<u16> {
+    let bytes: &[u8] = slice(data, offset, 2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Fallible<u32> {
+    let bytes: &[u8] = slice(data, offset, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// The modification date of written entries, as an MS-DOS date: 1980-01-01,
+/// the earliest it can represent.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Write a zip archive whose entries are stored, uncompressed.
+pub fn write_zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut data: Vec<u8> = Vec::new();
+    let mut central: Vec<u8> = Vec::new();
+    for (name, contents) in entries {
+        let offset = data.len() as u32;
+        let size = contents.len() as u32;
+        let name_length = name.len() as u16;
+        let crc = crc32(contents);
+        data.extend(LOCAL_FILE_HEADER.to_le_bytes());
+        data.extend([20, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend(DOS_DATE.to_le_bytes());
+        data.extend(crc.to_le_bytes());
+        data.extend(size.to_le_bytes());
+        data.extend(size.to_le_bytes());
+        data.extend(name_length.to_le_bytes());
+        data.extend([0, 0]);
+        data.extend(name.as_bytes());
+        data.extend(*contents);
+        central.extend(CENTRAL_DIRECTORY_HEADER.to_le_bytes());
+        central.extend([20, 0, 20, 0, 0, 0, 0, 0, 0, 0]);
+        central.extend(DOS_DATE.to_le_bytes());
+        central.extend(crc.to_le_bytes());
+        central.extend(size.to_le_bytes());
+        central.extend(size.to_le_bytes());
+        central.extend(name_length.to_le_bytes());
+        central.extend([0; 12]);
+        central.extend(offset.to_le_bytes());
+        central.extend(name.as_bytes());
+    }
+    let central_offset = data.len() as u32;
+    let central_size = central.len() as u32;
+    let count = entries.len() as u16;
+    data.extend(central);
+    data.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+    data.extend([0, 0, 0, 0]);
+    data.extend(count.to_le_bytes());
+    data.extend(count.to_le_bytes());
+    data.extend(central_size.to_le_bytes());
+    data.extend(central_offset.to_le_bytes());
+    data.extend([0, 0]);
+    data
+}
+
+/// The CRC-32 checksum of an entry, computed bit by bit.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = !0;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb88320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_stored() -> Fallible<()> {
+        let zip = ZipArchive::new(write_zip(&[("a.txt", b"alpha"), ("b", b"")]))?;
+        assert!(zip.contains("a.txt"));
+        assert_eq!(zip.read("a.txt")?, Some(b"alpha".to_vec()));
+        assert_eq!(zip.read("b")?, Some(Vec::new()));
+        assert_eq!(zip.read("c")?, None);
+        Ok(())
+    }
+
+    /// An archive made by Python's `zipfile`, with a deflated entry.
+    #[test]
+    fn test_read_deflated() -> Fallible<()> {
+        let hex = "504b03041400000008006a554f5d96e1b0670f00000028000000080000006465636b2e7478740bb45208e472b45270e40ac4cb0200504b010214031400000008006a554f5d96e1b0670f000000280000000800000000000000000000008001000000006465636b2e747874504b0506000000000100010036000000350000000000";
+        let data: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        let zip = ZipArchive::new(data)?;
+        assert_eq!(zip.read("deck.txt")?, Some(b"Q: Q\nA: A\n".repeat(4)));
+        Ok(())
+    }
+
+    /// An entry that decompresses to more than its recorded size is an
+    /// error.
+    #[test]
+    fn test_read_size_mismatch() -> Fallible<()> {
+        let mut data: Vec<u8> = write_zip(&[("a.txt", b"alpha")]);
+        // The uncompressed size in the central directory.
+        let central: usize = 30 + "a.txt".len() + "alpha".len();
+        data[central + 24] = 4;
+        let zip = ZipArchive::new(data)?;
+        assert!(zip.read("a.txt").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(ZipArchive::new(b"not a zip file at all, really".to_vec()).is_err());
+        assert!(ZipArchive::new(Vec::new()).is_err());
+    }
+}